@@ -0,0 +1,401 @@
+//! Packing many small images into a single texture, spilling into further array layers.
+
+use std::num::NonZeroU32;
+
+use crate::rect_packer::{PackedRect, PackingAlgorithm, RectPacker};
+
+/// Descriptor for [`TextureAtlas`].
+#[derive(Clone, Debug)]
+pub struct TextureAtlasDescriptor<'a> {
+    /// Debug label of the atlas texture.
+    pub label: wgpu::Label<'a>,
+    /// Width of the atlas texture in texels.
+    pub width: u32,
+    /// Height of the atlas texture in texels.
+    pub height: u32,
+    /// Number of array layers, the pages filled one after the other. All of them are allocated
+    /// up front.
+    pub layers: u32,
+    /// Packing strategy of every layer.
+    pub algorithm: PackingAlgorithm,
+    /// Zero the texels of removed regions before the next write, so reused regions don't show
+    /// stale texels, e.g. in padding sampled by filtering.
+    pub clear_removed: bool,
+    /// Format of the atlas texture. Must not be compressed.
+    pub format: wgpu::TextureFormat,
+    /// Usages of the atlas texture. [`wgpu::TextureUsages::COPY_SRC`] and
+    /// [`wgpu::TextureUsages::COPY_DST`] are always added.
+    pub usage: wgpu::TextureUsages,
+}
+
+/// A rectangular region inside a [`TextureAtlas`] in texels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AtlasAllocation {
+    /// Array layer of the region.
+    pub layer: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AtlasAllocation {
+    /// A sub region relative to this allocation.
+    pub fn sub_region(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        assert!(
+            x + width <= self.width && y + height <= self.height,
+            "sub region must lie within the allocation"
+        );
+        Self {
+            layer: self.layer,
+            x: self.x + x,
+            y: self.y + y,
+            width,
+            height,
+        }
+    }
+}
+
+/// A texture into which images are packed at runtime.
+///
+/// Every layer is packed by a [`RectPacker`], by default with shelves: regions are placed left to
+/// right on horizontal shelves whose height is determined by the first region placed on them.
+/// Once a layer is full, regions spill into the next one.
+#[derive(Debug)]
+pub struct TextureAtlas {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    array_view: wgpu::TextureView,
+
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+
+    pages: Vec<RectPacker>,
+    clear_removed: bool,
+    /// Removed regions not cleared yet.
+    pending_clears: Vec<AtlasAllocation>,
+}
+
+impl TextureAtlas {
+    /// Creates a new empty atlas.
+    pub fn new(device: &wgpu::Device, descriptor: &TextureAtlasDescriptor) -> Self {
+        assert!(
+            !descriptor.format.describe().is_compressed(),
+            "atlas format must not be compressed"
+        );
+
+        let texture = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: descriptor.label,
+                size: wgpu::Extent3d {
+                    width: descriptor.width,
+                    height: descriptor.height,
+                    depth_or_array_layers: descriptor.layers,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: descriptor.format,
+                usage: descriptor.usage
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::COPY_DST,
+            },
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let array_view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            array_view,
+
+            width: descriptor.width,
+            height: descriptor.height,
+            format: descriptor.format,
+
+            pages: (0..descriptor.layers)
+                .map(|_| RectPacker::new(descriptor.width, descriptor.height, descriptor.algorithm))
+                .collect(),
+            clear_removed: descriptor.clear_removed,
+            pending_clears: Vec::new(),
+        }
+    }
+
+    /// Reserves a `width` x `height` region in the first layer with space left.
+    ///
+    /// Returns `None` if there is no space left.
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<AtlasAllocation> {
+        self.pages.iter_mut().enumerate().find_map(|(layer, page)| {
+            page.allocate(width, height)
+                .map(|rect| allocation(layer as u32, rect))
+        })
+    }
+
+    /// Returns a region of [`Self::allocate`] for reuse by later allocations.
+    ///
+    /// With [`TextureAtlasDescriptor::clear_removed`], its texels are zeroed by the next
+    /// [`Self::write`] or [`Self::flush`].
+    pub fn remove(&mut self, allocation: &AtlasAllocation) {
+        self.pages[allocation.layer as usize].deallocate(PackedRect {
+            x: allocation.x,
+            y: allocation.y,
+            width: allocation.width,
+            height: allocation.height,
+        });
+        if self.clear_removed {
+            self.pending_clears.push(*allocation);
+        }
+    }
+
+    /// Marks the whole atlas as vacant. Old contents are not cleared.
+    pub fn clear(&mut self) {
+        for page in &mut self.pages {
+            page.clear();
+        }
+        self.pending_clears.clear();
+    }
+
+    /// Zeroes the texels of removed regions, if [`TextureAtlasDescriptor::clear_removed`].
+    pub fn flush(&mut self, queue: &wgpu::Queue) {
+        let texel_size = self.format.describe().block_size as usize;
+        for allocation in std::mem::take(&mut self.pending_clears) {
+            let zeros =
+                vec![0; allocation.width as usize * allocation.height as usize * texel_size];
+            self.write_region(queue, &allocation, &zeros);
+        }
+    }
+
+    /// Packs the live `allocations` anew, from the largest to the smallest, and moves their
+    /// texels, updating them in place. Regions not in `allocations` are dropped.
+    ///
+    /// Recovers space lost to fragmentation after many insertions, e.g. of differently sized
+    /// images. The texture stays the same, so bind groups remain valid. With
+    /// [`TextureAtlasDescriptor::clear_removed`], all free space is zeroed. Returns `false` and
+    /// changes nothing if the allocations don't fit anymore.
+    pub fn repack(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        allocations: &mut [AtlasAllocation],
+    ) -> bool {
+        let mut order: Vec<usize> = (0..allocations.len()).collect();
+        order.sort_by_key(|&i| {
+            let allocation = &allocations[i];
+            std::cmp::Reverse((allocation.height, allocation.width))
+        });
+
+        let mut pages = self.pages.clone();
+        for page in &mut pages {
+            page.clear();
+        }
+        let mut repacked = allocations.to_vec();
+        for &i in &order {
+            let (width, height) = (allocations[i].width, allocations[i].height);
+            let new = pages.iter_mut().enumerate().find_map(|(layer, page)| {
+                page.allocate(width, height)
+                    .map(|rect| allocation(layer as u32, rect))
+            });
+            match new {
+                Some(new) => repacked[i] = new,
+                None => return false,
+            }
+        }
+
+        // Regions may overlap their old positions, so the texels are moved through a copy.
+        let size = wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: self.layers(),
+        };
+        let scratch = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("atlas repack scratch texture"),
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.format,
+                usage: wgpu::TextureUsages::COPY_SRC | wgpu::TextureUsages::COPY_DST,
+            },
+        );
+        encoder.copy_texture_to_texture(
+            self.texture.as_image_copy(),
+            scratch.as_image_copy(),
+            size,
+        );
+        if self.clear_removed {
+            // New buffers are zeroed, clearing everything outside the copied regions.
+            let bytes_per_row = (self.width * self.format.describe().block_size as u32)
+                .next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+            let zeros = crate::resource_log::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("atlas repack zero buffer"),
+                    size: bytes_per_row as u64
+                        * self.height as u64
+                        * size.depth_or_array_layers as u64,
+                    usage: wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                },
+            );
+            encoder.copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &zeros,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: NonZeroU32::new(bytes_per_row),
+                        rows_per_image: NonZeroU32::new(self.height),
+                    },
+                },
+                self.texture.as_image_copy(),
+                size,
+            );
+            self.pending_clears.clear();
+        }
+        let image_copy = |texture, allocation: &AtlasAllocation| wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: allocation.x,
+                y: allocation.y,
+                z: allocation.layer,
+            },
+            aspect: wgpu::TextureAspect::All,
+        };
+        for (old, new) in allocations.iter().zip(&repacked) {
+            if (old == new && !self.clear_removed) || old.width == 0 || old.height == 0 {
+                continue;
+            }
+            encoder.copy_texture_to_texture(
+                image_copy(&scratch, old),
+                image_copy(&self.texture, new),
+                wgpu::Extent3d {
+                    width: old.width,
+                    height: old.height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        self.pages = pages;
+        allocations.copy_from_slice(&repacked);
+        true
+    }
+
+    /// Writes tightly packed texel `data` into `allocation` using [`wgpu::Queue`], after
+    /// clearing removed regions.
+    pub fn write(&mut self, queue: &wgpu::Queue, allocation: &AtlasAllocation, data: &[u8]) {
+        self.flush(queue);
+        self.write_region(queue, allocation, data);
+    }
+
+    fn write_region(&self, queue: &wgpu::Queue, allocation: &AtlasAllocation, data: &[u8]) {
+        let texel_size = self.format.describe().block_size as u32;
+        assert_eq!(
+            data.len() as u32,
+            allocation.width * allocation.height * texel_size,
+            "data must exactly cover the allocation"
+        );
+        if allocation.width == 0 || allocation.height == 0 {
+            return;
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: allocation.x,
+                    y: allocation.y,
+                    z: allocation.layer,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            data,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(allocation.width * texel_size),
+                rows_per_image: None,
+            },
+            wgpu::Extent3d {
+                width: allocation.width,
+                height: allocation.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Normalized texture coordinates `[min_u, min_v, max_u, max_v]` of `allocation`.
+    pub fn uv_rect(&self, allocation: &AtlasAllocation) -> [f32; 4] {
+        let (width, height) = (self.width as f32, self.height as f32);
+        [
+            allocation.x as f32 / width,
+            allocation.y as f32 / height,
+            (allocation.x + allocation.width) as f32 / width,
+            (allocation.y + allocation.height) as f32 / height,
+        ]
+    }
+
+    /// Array layer and normalized texture coordinates of `allocation`, for sampling
+    /// [`Self::array_view`].
+    pub fn layer_uv_rect(&self, allocation: &AtlasAllocation) -> (u32, [f32; 4]) {
+        (allocation.layer, self.uv_rect(allocation))
+    }
+
+    /// Get a reference to the atlas texture.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// Get a reference to the default view of the atlas texture. It's a 2D view for a single
+    /// layer and a 2D array view otherwise.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Get a reference to a 2D array view of all layers, bound as `texture_2d_array` with
+    /// [`Self::array_binding_type`].
+    pub fn array_view(&self) -> &wgpu::TextureView {
+        &self.array_view
+    }
+
+    /// Binding type of [`Self::array_view`].
+    pub fn array_binding_type(&self) -> wgpu::BindingType {
+        wgpu::BindingType::Texture {
+            sample_type: self.format.describe().sample_type,
+            view_dimension: wgpu::TextureViewDimension::D2Array,
+            multisampled: false,
+        }
+    }
+
+    /// Size of the atlas texture in texels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Number of array layers of the atlas texture.
+    pub fn layers(&self) -> u32 {
+        self.pages.len() as u32
+    }
+
+    /// Format of the atlas texture.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+}
+
+fn allocation(layer: u32, rect: PackedRect) -> AtlasAllocation {
+    AtlasAllocation {
+        layer,
+        x: rect.x,
+        y: rect.y,
+        width: rect.width,
+        height: rect.height,
+    }
+}
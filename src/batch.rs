@@ -0,0 +1,198 @@
+//! Rendering batches of images offline.
+
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::{mpsc, Mutex},
+};
+
+use crate::{
+    readback::{read_texture, TextureInfo},
+    screenshot::{save_texels, SaveError, SaveOptions},
+};
+
+type SceneFn<'a> = Box<
+    dyn FnOnce(&wgpu::Device, &wgpu::Queue, &mut wgpu::CommandEncoder, &wgpu::TextureView) + 'a,
+>;
+
+/// An image rendered by [`BatchRenderer::render`].
+pub struct BatchJob<'a> {
+    pub width: u32,
+    pub height: u32,
+    /// File the image is saved to, see [`save_texels`] for the supported formats.
+    pub path: PathBuf,
+    render: SceneFn<'a>,
+}
+
+impl<'a> BatchJob<'a> {
+    /// Creates a job rendering with `render`, which records the scene into the encoder, drawing
+    /// into the view of a `width` x `height` target.
+    pub fn new(
+        width: u32,
+        height: u32,
+        path: impl Into<PathBuf>,
+        render: impl FnOnce(&wgpu::Device, &wgpu::Queue, &mut wgpu::CommandEncoder, &wgpu::TextureView)
+            + 'a,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            path: path.into(),
+            render: Box::new(render),
+        }
+    }
+}
+
+impl fmt::Debug for BatchJob<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BatchJob")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Descriptor for [`BatchRenderer`].
+#[derive(Clone, Debug)]
+pub struct BatchRendererDescriptor {
+    /// Format of the render targets.
+    pub format: wgpu::TextureFormat,
+    /// Number of threads encoding and writing images.
+    pub workers: usize,
+    /// Number of read back images which may wait for a worker before rendering blocks.
+    pub capacity: usize,
+    pub options: SaveOptions,
+}
+
+impl Default for BatchRendererDescriptor {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            workers: 4,
+            capacity: 4,
+            options: SaveOptions::default(),
+        }
+    }
+}
+
+/// Renders lists of jobs to offscreen targets and saves them as image files, e.g. for offline
+/// image generation.
+///
+/// Jobs are rendered and read back one after another on the calling thread, while encoding and
+/// writing happens on worker threads. The number of images held in memory is bounded by the
+/// workers and the capacity.
+#[derive(Debug)]
+pub struct BatchRenderer {
+    format: wgpu::TextureFormat,
+    workers: usize,
+    capacity: usize,
+    options: SaveOptions,
+}
+
+impl BatchRenderer {
+    pub fn new(descriptor: &BatchRendererDescriptor) -> Self {
+        assert!(descriptor.workers > 0, "at least one worker is required");
+        Self {
+            format: descriptor.format,
+            workers: descriptor.workers,
+            capacity: descriptor.capacity,
+            options: descriptor.options,
+        }
+    }
+
+    /// Renders and saves all jobs, blocking until all files are written.
+    ///
+    /// Returns the result of every job in order.
+    pub fn render<'a>(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        jobs: impl IntoIterator<Item = BatchJob<'a>>,
+    ) -> Vec<Result<(), SaveError>> {
+        let (sender, receiver) =
+            mpsc::sync_channel::<(usize, PathBuf, TextureInfo, Vec<u8>)>(self.capacity);
+        let receiver = Mutex::new(receiver);
+        let (result_sender, result_receiver) = mpsc::channel();
+        let options = self.options;
+
+        let mut results = Vec::new();
+        std::thread::scope(|scope| {
+            for _ in 0..self.workers {
+                let result_sender = result_sender.clone();
+                let receiver = &receiver;
+                scope.spawn(move || loop {
+                    // The lock is released before saving, so workers save in parallel.
+                    let job = receiver.lock().unwrap().recv();
+                    let Ok((index, path, info, texels)) = job else {
+                        break;
+                    };
+                    let result = save_texels(&texels, &info, &path, &options);
+                    let _ = result_sender.send((index, result));
+                });
+            }
+
+            for (index, job) in jobs.into_iter().enumerate() {
+                results.push(None);
+                let info = TextureInfo {
+                    width: job.width,
+                    height: job.height,
+                    format: self.format,
+                };
+                match self.render_job(device, queue, job.render, &info) {
+                    Ok(texels) => {
+                        // Workers only stop once the sender is dropped.
+                        let _ = sender.send((index, job.path, info, texels));
+                    }
+                    Err(e) => results[index] = Some(Err(e)),
+                }
+            }
+            drop(sender);
+        });
+
+        drop(result_sender);
+        for (index, result) in result_receiver {
+            results[index] = Some(result);
+        }
+        results
+            .into_iter()
+            .map(|result| result.expect("every job got a result"))
+            .collect()
+    }
+
+    fn render_job(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        render: SceneFn,
+        info: &TextureInfo,
+    ) -> Result<Vec<u8>, SaveError> {
+        let texture = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("batch render target"),
+                size: wgpu::Extent3d {
+                    width: info.width,
+                    height: info.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: info.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            },
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("batch render encoder"),
+        });
+        render(device, queue, &mut encoder, &view);
+        queue.submit(Some(encoder.finish()));
+
+        let texels = read_texture(device, queue, &texture, info).map_err(SaveError::Readback);
+        texture.destroy();
+        texels
+    }
+}
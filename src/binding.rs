@@ -0,0 +1,456 @@
+//! Validated buffer bindings and pooled bind groups.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    num::NonZeroU64,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{DynamicBuffer, SizedBuffer};
+
+/// A buffer whose size is tracked on the CPU, so bindings of it can be validated.
+pub trait BindableBuffer {
+    /// Get a reference to the raw buffer.
+    fn raw_buffer(&self) -> &wgpu::Buffer;
+
+    /// Size of the buffer in bytes.
+    fn buffer_size(&self) -> wgpu::BufferAddress;
+
+    /// Usages of the buffer, if known.
+    fn buffer_usage(&self) -> Option<wgpu::BufferUsages> {
+        None
+    }
+}
+
+impl BindableBuffer for SizedBuffer {
+    fn raw_buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    fn buffer_size(&self) -> wgpu::BufferAddress {
+        self.size
+    }
+}
+
+impl BindableBuffer for DynamicBuffer {
+    fn raw_buffer(&self) -> &wgpu::Buffer {
+        self.raw()
+    }
+
+    fn buffer_size(&self) -> wgpu::BufferAddress {
+        self.size()
+    }
+
+    fn buffer_usage(&self) -> Option<wgpu::BufferUsages> {
+        Some(self.usage())
+    }
+}
+
+/// Error returned by [`BufferBinding::new`] and [`BufferBinding::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindingError {
+    /// The bound range is empty.
+    Empty { offset: wgpu::BufferAddress },
+    /// The bound range exceeds the buffer.
+    OutOfBounds {
+        offset: wgpu::BufferAddress,
+        size: wgpu::BufferAddress,
+        buffer_size: wgpu::BufferAddress,
+    },
+    /// The offset isn't a multiple of the device's alignment for the binding type.
+    UnalignedOffset {
+        offset: wgpu::BufferAddress,
+        alignment: u32,
+    },
+    /// Storage bindings must have a size that is a multiple of 4.
+    UnalignedSize { size: wgpu::BufferAddress },
+    /// The bound range exceeds the device's maximum binding size for the binding type.
+    TooLarge {
+        size: wgpu::BufferAddress,
+        max_size: u32,
+    },
+    /// The buffer lacks the usage required by the binding type.
+    MissingUsage {
+        required: wgpu::BufferUsages,
+        usage: wgpu::BufferUsages,
+    },
+}
+
+impl fmt::Display for BindingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty { offset } => write!(f, "binding at offset {} is empty", offset),
+            Self::OutOfBounds {
+                offset,
+                size,
+                buffer_size,
+            } => write!(
+                f,
+                "binding {}..{} exceeds buffer of size {}",
+                offset,
+                offset + size,
+                buffer_size
+            ),
+            Self::UnalignedOffset { offset, alignment } => write!(
+                f,
+                "binding offset {} isn't a multiple of the required alignment {}",
+                offset, alignment
+            ),
+            Self::UnalignedSize { size } => {
+                write!(f, "storage binding size {} isn't a multiple of 4", size)
+            }
+            Self::TooLarge { size, max_size } => write!(
+                f,
+                "binding size {} exceeds the device limit of {}",
+                size, max_size
+            ),
+            Self::MissingUsage { required, usage } => write!(
+                f,
+                "buffer with usage {:?} lacks required usage {:?}",
+                usage, required
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BindingError {}
+
+/// A range of a [`BindableBuffer`] checked against its size before it's bound.
+///
+/// Catches out of bounds and misaligned bindings with a descriptive error when they're created,
+/// instead of a validation error when the bind group is created or used.
+#[derive(Clone, Copy, Debug)]
+pub struct BufferBinding<'a> {
+    buffer: &'a wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    size: NonZeroU64,
+    usage: Option<wgpu::BufferUsages>,
+}
+
+impl<'a> BufferBinding<'a> {
+    /// Binds `size` bytes at `offset` of `buffer`, or the rest of the buffer if `size` is
+    /// `None`.
+    pub fn new(
+        buffer: &'a impl BindableBuffer,
+        offset: wgpu::BufferAddress,
+        size: Option<wgpu::BufferAddress>,
+    ) -> Result<Self, BindingError> {
+        let buffer_size = buffer.buffer_size();
+        let size = match size {
+            Some(size) => size,
+            None => buffer_size.saturating_sub(offset),
+        };
+        if offset.checked_add(size).is_none_or(|end| end > buffer_size) {
+            return Err(BindingError::OutOfBounds {
+                offset,
+                size,
+                buffer_size,
+            });
+        }
+        let size = NonZeroU64::new(size).ok_or(BindingError::Empty { offset })?;
+
+        Ok(Self {
+            buffer: buffer.raw_buffer(),
+            offset,
+            size,
+            usage: buffer.buffer_usage(),
+        })
+    }
+
+    /// Checks the binding against `limits` and the usage of the buffer, if known, for binding
+    /// as `ty`.
+    pub fn validate(
+        &self,
+        limits: &wgpu::Limits,
+        ty: wgpu::BufferBindingType,
+    ) -> Result<(), BindingError> {
+        let (alignment, max_size, required) = match ty {
+            wgpu::BufferBindingType::Uniform => (
+                limits.min_uniform_buffer_offset_alignment,
+                limits.max_uniform_buffer_binding_size,
+                wgpu::BufferUsages::UNIFORM,
+            ),
+            wgpu::BufferBindingType::Storage { .. } => (
+                limits.min_storage_buffer_offset_alignment,
+                limits.max_storage_buffer_binding_size,
+                wgpu::BufferUsages::STORAGE,
+            ),
+        };
+
+        if !self.offset.is_multiple_of(alignment as wgpu::BufferAddress) {
+            return Err(BindingError::UnalignedOffset {
+                offset: self.offset,
+                alignment,
+            });
+        }
+        let size = self.size.get();
+        if matches!(ty, wgpu::BufferBindingType::Storage { .. }) && !size.is_multiple_of(4) {
+            return Err(BindingError::UnalignedSize { size });
+        }
+        if size > max_size as wgpu::BufferAddress {
+            return Err(BindingError::TooLarge { size, max_size });
+        }
+        if let Some(usage) = self.usage {
+            if !usage.contains(required) {
+                return Err(BindingError::MissingUsage { required, usage });
+            }
+        }
+        Ok(())
+    }
+
+    /// [`Self::new`] followed by [`Self::validate`] against the limits of `device`.
+    pub fn new_validated(
+        device: &wgpu::Device,
+        buffer: &'a impl BindableBuffer,
+        offset: wgpu::BufferAddress,
+        size: Option<wgpu::BufferAddress>,
+        ty: wgpu::BufferBindingType,
+    ) -> Result<Self, BindingError> {
+        let binding = Self::new(buffer, offset, size)?;
+        binding.validate(&device.limits(), ty)?;
+        Ok(binding)
+    }
+
+    /// Offset of the binding in bytes.
+    pub fn offset(&self) -> wgpu::BufferAddress {
+        self.offset
+    }
+
+    /// Size of the binding in bytes.
+    pub fn size(&self) -> NonZeroU64 {
+        self.size
+    }
+
+    /// Binding resource of the range.
+    pub fn resource(&self) -> wgpu::BindingResource<'a> {
+        wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+            buffer: self.buffer,
+            offset: self.offset,
+            size: Some(self.size),
+        })
+    }
+
+    /// Bind group entry binding the range at `binding`.
+    pub fn entry(&self, binding: u32) -> wgpu::BindGroupEntry<'a> {
+        wgpu::BindGroupEntry {
+            binding,
+            resource: self.resource(),
+        }
+    }
+}
+
+/// Identity of a resource issued by this crate, unique for the lifetime of the process.
+///
+/// Resources replaced in place get a new id, e.g. a [`DynamicBuffer`] reallocated when growing,
+/// so ids of the bound resources can key bind groups in a [`BindGroupPool`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ResourceId(u64);
+
+impl ResourceId {
+    /// Issues a new id.
+    pub fn unique() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Kind and buffer range of a bound resource, which a [`BindGroupPool`] matches in addition to
+/// the user key.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum EntryRange {
+    Buffer {
+        offset: wgpu::BufferAddress,
+        size: Option<NonZeroU64>,
+    },
+    Sampler,
+    TextureView,
+    Array(Vec<EntryRange>),
+}
+
+impl EntryRange {
+    /// `None` for resources the pool doesn't know, whose bind groups aren't cached.
+    fn new(resource: &wgpu::BindingResource<'_>) -> Option<Self> {
+        let range = match resource {
+            wgpu::BindingResource::Buffer(binding) => Self::buffer(binding),
+            wgpu::BindingResource::BufferArray(bindings) => {
+                Self::Array(bindings.iter().map(Self::buffer).collect())
+            }
+            wgpu::BindingResource::Sampler(_) => Self::Sampler,
+            wgpu::BindingResource::SamplerArray(samplers) => {
+                Self::Array(vec![Self::Sampler; samplers.len()])
+            }
+            wgpu::BindingResource::TextureView(_) => Self::TextureView,
+            wgpu::BindingResource::TextureViewArray(views) => {
+                Self::Array(vec![Self::TextureView; views.len()])
+            }
+            _ => return None,
+        };
+        Some(range)
+    }
+
+    fn buffer(binding: &wgpu::BufferBinding<'_>) -> Self {
+        Self::Buffer {
+            offset: binding.offset,
+            size: binding.size,
+        }
+    }
+}
+
+/// Key of a bind group acquired from a [`BindGroupPool`], valid until the next
+/// [`BindGroupPool::clear`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BindGroupKey<K>(PoolKey<K>);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum PoolKey<K> {
+    Cached {
+        key: K,
+        entries: Vec<(u32, EntryRange)>,
+    },
+    /// Index into the bind groups created uncached in `frame`.
+    Uncached { frame: u64, index: usize },
+}
+
+#[derive(Debug)]
+struct PooledBindGroup {
+    bind_group: wgpu::BindGroup,
+    last_used: u64,
+}
+
+/// Descriptor for [`BindGroupPool`].
+#[derive(Clone, Debug)]
+pub struct BindGroupPoolDescriptor {
+    /// Number of [`BindGroupPool::clear`] calls a bind group may stay unused before it's
+    /// dropped.
+    pub max_unused_frames: u64,
+}
+
+impl Default for BindGroupPoolDescriptor {
+    fn default() -> Self {
+        Self {
+            max_unused_frames: 1,
+        }
+    }
+}
+
+/// A cache of bind groups, so bind groups rebuilt every frame from the same resources are only
+/// created once.
+///
+/// wgpu handles have no stable identity, so bind groups are matched by a user key `K`, along
+/// with the bindings and buffer ranges of the descriptor. The key must identify the layout and
+/// every bound resource, e.g. by their [`ResourceId`]s, and change whenever one of them is
+/// replaced. Bind groups of resources the pool doesn't know are created uncached and dropped
+/// by the next [`Self::clear`].
+#[derive(Debug)]
+pub struct BindGroupPool<K> {
+    groups: HashMap<PoolKey<K>, PooledBindGroup>,
+    uncached: Vec<wgpu::BindGroup>,
+    frame: u64,
+    max_unused_frames: u64,
+    created: usize,
+}
+
+impl<K: Clone + Eq + Hash> BindGroupPool<K> {
+    /// Creates a new empty pool.
+    pub fn new(descriptor: &BindGroupPoolDescriptor) -> Self {
+        Self {
+            groups: HashMap::new(),
+            uncached: Vec::new(),
+            frame: 0,
+            max_unused_frames: descriptor.max_unused_frames,
+            created: 0,
+        }
+    }
+
+    /// Gets the bind group with `key` and the bindings of `descriptor`.
+    ///
+    /// If no matching bind group is cached, a new one is created. The label is only used for new
+    /// bind groups.
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        key: K,
+        descriptor: &wgpu::BindGroupDescriptor<'_>,
+    ) -> &wgpu::BindGroup {
+        let key = self.acquire(device, key, descriptor);
+        self.get(&key).expect("bind group was just acquired")
+    }
+
+    /// [`Self::get_or_create`] but returns the key of the bind group, so multiple bind groups
+    /// can be acquired before getting them with [`Self::get`], e.g. while recording a pass.
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        key: K,
+        descriptor: &wgpu::BindGroupDescriptor<'_>,
+    ) -> BindGroupKey<K> {
+        let entries = descriptor
+            .entries
+            .iter()
+            .map(|entry| Some((entry.binding, EntryRange::new(&entry.resource)?)))
+            .collect::<Option<Vec<_>>>();
+        let entries = match entries {
+            Some(entries) => entries,
+            None => {
+                self.created += 1;
+                #[cfg(feature = "metrics")]
+                crate::metrics::record_bind_group(false);
+                self.uncached.push(device.create_bind_group(descriptor));
+                return BindGroupKey(PoolKey::Uncached {
+                    frame: self.frame,
+                    index: self.uncached.len() - 1,
+                });
+            }
+        };
+
+        let key = PoolKey::Cached { key, entries };
+        let frame = self.frame;
+        #[cfg(feature = "metrics")]
+        let created = self.created;
+        let group = self.groups.entry(key.clone()).or_insert_with(|| {
+            self.created += 1;
+            PooledBindGroup {
+                bind_group: device.create_bind_group(descriptor),
+                last_used: frame,
+            }
+        });
+        group.last_used = frame;
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_bind_group(self.created == created);
+        BindGroupKey(key)
+    }
+
+    /// Get a bind group by key. Keys stay valid until the bind group is dropped by
+    /// [`Self::clear`].
+    pub fn get(&self, key: &BindGroupKey<K>) -> Option<&wgpu::BindGroup> {
+        match &key.0 {
+            PoolKey::Cached { .. } => self.groups.get(&key.0).map(|group| &group.bind_group),
+            PoolKey::Uncached { frame, index } => match *frame == self.frame {
+                true => self.uncached.get(*index),
+                false => None,
+            },
+        }
+    }
+
+    /// Drops uncached bind groups and bind groups unused for too long, and starts a new frame.
+    pub fn clear(&mut self) {
+        self.frame += 1;
+        self.created = 0;
+        self.uncached.clear();
+        let (frame, max_unused_frames) = (self.frame, self.max_unused_frames);
+        self.groups
+            .retain(|_, group| frame - group.last_used <= max_unused_frames);
+    }
+
+    /// Pool size
+    pub fn size(&self) -> usize {
+        self.groups.len()
+    }
+
+    /// Number of bind groups created since the last [`Self::clear`].
+    pub fn created(&self) -> usize {
+        self.created
+    }
+}
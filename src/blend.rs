@@ -0,0 +1,298 @@
+//! Blend state presets, a builder for custom equations, and conversions between straight and
+//! premultiplied alpha.
+//!
+//! Premultiplied colors store `rgb * a` instead of `rgb`. They filter and blend without dark
+//! fringes around transparent texels and can express additive blending in the same equation.
+
+use crate::{BufferInitDescriptor, DeviceExt};
+
+/// Blends straight (non-premultiplied) colors over the target.
+pub const STRAIGHT_ALPHA: wgpu::BlendState = wgpu::BlendState::ALPHA_BLENDING;
+
+/// Blends premultiplied colors over the target.
+pub const PREMULTIPLIED_ALPHA: wgpu::BlendState = wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING;
+
+/// Adds colors to the target, e.g. for light accumulation and particles. Alpha is added too.
+pub const ADDITIVE: wgpu::BlendState = wgpu::BlendState {
+    color: component(
+        wgpu::BlendFactor::One,
+        wgpu::BlendFactor::One,
+        wgpu::BlendOperation::Add,
+    ),
+    alpha: component(
+        wgpu::BlendFactor::One,
+        wgpu::BlendFactor::One,
+        wgpu::BlendOperation::Add,
+    ),
+};
+
+/// Multiplies the target by premultiplied colors, darkening it. Alpha is blended over.
+pub const MULTIPLY: wgpu::BlendState = wgpu::BlendState {
+    color: component(
+        wgpu::BlendFactor::Dst,
+        wgpu::BlendFactor::OneMinusSrcAlpha,
+        wgpu::BlendOperation::Add,
+    ),
+    alpha: wgpu::BlendComponent::OVER,
+};
+
+/// Inverse of multiplying the inverted target and premultiplied colors, brightening it. Alpha
+/// is blended over.
+pub const SCREEN: wgpu::BlendState = wgpu::BlendState {
+    color: component(
+        wgpu::BlendFactor::One,
+        wgpu::BlendFactor::OneMinusSrc,
+        wgpu::BlendOperation::Add,
+    ),
+    alpha: wgpu::BlendComponent::OVER,
+};
+
+/// Subtracts colors from the target. Alpha is kept.
+pub const SUBTRACT: wgpu::BlendState = wgpu::BlendState {
+    color: component(
+        wgpu::BlendFactor::One,
+        wgpu::BlendFactor::One,
+        wgpu::BlendOperation::ReverseSubtract,
+    ),
+    alpha: component(
+        wgpu::BlendFactor::Zero,
+        wgpu::BlendFactor::One,
+        wgpu::BlendOperation::Add,
+    ),
+};
+
+const fn component(
+    src_factor: wgpu::BlendFactor,
+    dst_factor: wgpu::BlendFactor,
+    operation: wgpu::BlendOperation,
+) -> wgpu::BlendComponent {
+    wgpu::BlendComponent {
+        src_factor,
+        dst_factor,
+        operation,
+    }
+}
+
+/// Builds custom blend equations `src * src_factor <operation> dst * dst_factor`, starting
+/// from replacing the target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlendBuilder {
+    state: wgpu::BlendState,
+}
+
+impl Default for BlendBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BlendBuilder {
+    pub const fn new() -> Self {
+        Self {
+            state: wgpu::BlendState::REPLACE,
+        }
+    }
+
+    /// Starts from an existing state, e.g. one of the presets.
+    pub const fn from_state(state: wgpu::BlendState) -> Self {
+        Self { state }
+    }
+
+    /// Sets the equation of the color channels.
+    pub const fn color(
+        mut self,
+        src_factor: wgpu::BlendFactor,
+        dst_factor: wgpu::BlendFactor,
+        operation: wgpu::BlendOperation,
+    ) -> Self {
+        self.state.color = component(src_factor, dst_factor, operation);
+        self
+    }
+
+    /// Sets the equation of the alpha channel.
+    pub const fn alpha(
+        mut self,
+        src_factor: wgpu::BlendFactor,
+        dst_factor: wgpu::BlendFactor,
+        operation: wgpu::BlendOperation,
+    ) -> Self {
+        self.state.alpha = component(src_factor, dst_factor, operation);
+        self
+    }
+
+    /// Sets the same equation for color and alpha channels.
+    pub const fn both(
+        self,
+        src_factor: wgpu::BlendFactor,
+        dst_factor: wgpu::BlendFactor,
+        operation: wgpu::BlendOperation,
+    ) -> Self {
+        self.color(src_factor, dst_factor, operation)
+            .alpha(src_factor, dst_factor, operation)
+    }
+
+    pub const fn build(self) -> wgpu::BlendState {
+        self.state
+    }
+}
+
+/// Multiplies the color channels of a straight color by its alpha.
+pub fn premultiply(color: [f32; 4]) -> [f32; 4] {
+    let [r, g, b, a] = color;
+    [r * a, g * a, b * a, a]
+}
+
+/// Divides the color channels of a premultiplied color by its alpha. Fully transparent colors
+/// become transparent black.
+pub fn unpremultiply(color: [f32; 4]) -> [f32; 4] {
+    let [r, g, b, a] = color;
+    match a > 0.0 {
+        true => [r / a, g / a, b / a, a],
+        false => [0.0; 4],
+    }
+}
+
+/// [`premultiply`] for 8-bit RGBA or BGRA texels in place. Channels are multiplied as stored,
+/// so sRGB encoded texels are premultiplied in sRGB space.
+pub fn premultiply_rgba8(texels: &mut [u8]) {
+    for texel in texels.chunks_exact_mut(4) {
+        let a = texel[3] as u32;
+        for c in &mut texel[..3] {
+            *c = ((*c as u32 * a + 127) / 255) as u8;
+        }
+    }
+}
+
+/// [`unpremultiply`] for 8-bit RGBA or BGRA texels in place. Precision lost by premultiplying
+/// low alpha values can't be recovered.
+pub fn unpremultiply_rgba8(texels: &mut [u8]) {
+    for texel in texels.chunks_exact_mut(4) {
+        let a = texel[3] as u32;
+        for c in &mut texel[..3] {
+            *c = match a {
+                0 => 0,
+                _ => ((*c as u32 * 255 + a / 2) / a).min(255) as u8,
+            };
+        }
+    }
+}
+
+/// Direction of an [`AlphaConverter`] pass.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AlphaConversion {
+    Premultiply,
+    Unpremultiply,
+}
+
+/// Converts whole textures between straight and premultiplied alpha on the GPU.
+///
+/// Input and output must have the same size. Colors are converted as sampled, so sRGB textures
+/// are converted in linear space.
+#[derive(Debug)]
+pub struct AlphaConverter {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl AlphaConverter {
+    /// Creates a converter writing to textures of `output_format`.
+    pub fn new(device: &wgpu::Device, output_format: wgpu::TextureFormat) -> Self {
+        let shader = crate::post::fullscreen_shader_module(
+            device,
+            Some("alpha conversion shader"),
+            include_str!("shaders/alpha.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("alpha conversion bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("alpha conversion pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::post::fullscreen_pipeline(
+            device,
+            Some("alpha conversion pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(output_format.into())],
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    /// Converts `input` into `output`.
+    pub fn convert(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        conversion: AlphaConversion,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let unpremultiply = (conversion == AlphaConversion::Unpremultiply) as u32;
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("alpha conversion params"),
+            contents: &[unpremultiply, 0, 0, 0]
+                .iter()
+                .flat_map(|w| w.to_le_bytes())
+                .collect::<Vec<_>>(),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("alpha conversion bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("alpha conversion pass")
+                .read("input", input)
+                .write("output", output)
+        });
+        crate::post::draw_fullscreen(
+            encoder,
+            Some("alpha conversion pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+}
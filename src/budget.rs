@@ -0,0 +1,285 @@
+//! Tracking GPU memory of allocating types against a shared budget.
+//!
+//! wgpu neither reports available memory nor fails allocations gracefully, exceeding it usually
+//! ends in a lost device. A [`GpuBudget`] shared by [`TexturePool`](crate::texture::TexturePool),
+//! [`TextureCache`](crate::texture::TextureCache) and
+//! [`DynamicBuffer`](crate::DynamicBuffer) accounts their allocations and calls a hook when
+//! nearing the budget, e.g. to evict caches or reduce quality.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, ThreadId},
+};
+
+/// Passed to the hook of [`GpuBudget::on_pressure`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BudgetPressure {
+    /// Bytes tracked before the allocation.
+    pub used: u64,
+    /// Bytes of the allocation.
+    pub requested: u64,
+    pub budget: u64,
+}
+
+impl BudgetPressure {
+    /// Whether the allocation exceeds the budget, not just the threshold.
+    pub fn exceeded(&self) -> bool {
+        self.used + self.requested > self.budget
+    }
+}
+
+type PressureFn = Box<dyn FnMut(&BudgetPressure) + Send>;
+
+#[derive(Default)]
+struct HookState {
+    /// Taken out while the hook runs.
+    hook: Option<PressureFn>,
+    /// Thread running the hook.
+    running: Option<ThreadId>,
+}
+
+struct Inner {
+    budget: AtomicU64,
+    /// Fraction of the budget in percent above which the hook is called.
+    threshold: AtomicU64,
+    used: AtomicU64,
+    hook: Mutex<HookState>,
+    hook_returned: Condvar,
+}
+
+/// Puts the hook back and wakes threads waiting to call it, also if it panicked.
+struct RunningHook<'a> {
+    inner: &'a Inner,
+    hook: Option<PressureFn>,
+}
+
+impl Drop for RunningHook<'_> {
+    fn drop(&mut self) {
+        let mut state = self.inner.hook.lock().unwrap();
+        // Keep a hook set by the running one.
+        if state.hook.is_none() {
+            state.hook = self.hook.take();
+        }
+        state.running = None;
+        self.inner.hook_returned.notify_all();
+    }
+}
+
+/// Memory budget shared by allocating types. Cloning it returns another handle to the same
+/// budget.
+///
+/// Allocations are only tracked, never refused. The pressure hook is called before every
+/// allocation which would raise the tracked memory above the threshold, so it can free memory
+/// first. Allocations made by the hook itself don't call it again.
+#[derive(Clone)]
+pub struct GpuBudget {
+    inner: Arc<Inner>,
+}
+
+impl fmt::Debug for GpuBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GpuBudget")
+            .field("budget", &self.budget())
+            .field("threshold", &self.threshold())
+            .field("used", &self.used())
+            .finish_non_exhaustive()
+    }
+}
+
+impl GpuBudget {
+    /// Creates a budget of `budget` bytes with a threshold of 90%.
+    pub fn new(budget: u64) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                budget: AtomicU64::new(budget),
+                threshold: AtomicU64::new(90),
+                used: AtomicU64::new(0),
+                hook: Mutex::new(HookState::default()),
+                hook_returned: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Sets the hook called when nearing the budget.
+    ///
+    /// The hook runs on the allocating thread without holding any lock, so it may free memory,
+    /// allocate against the budget without being called again and replace itself through this
+    /// function. Other threads nearing the budget meanwhile wait for it to return and call it
+    /// afterwards, so it must not wait on them.
+    pub fn on_pressure(&self, hook: impl FnMut(&BudgetPressure) + Send + 'static) {
+        self.inner.hook.lock().unwrap().hook = Some(Box::new(hook));
+    }
+
+    /// Sets the percentage of the budget above which the pressure hook is called.
+    pub fn set_threshold(&self, percent: u8) {
+        self.inner
+            .threshold
+            .store(percent.min(100) as u64, Ordering::Relaxed);
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.inner.threshold.load(Ordering::Relaxed) as u8
+    }
+
+    pub fn set_budget(&self, budget: u64) {
+        self.inner.budget.store(budget, Ordering::Relaxed);
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.inner.budget.load(Ordering::Relaxed)
+    }
+
+    /// Bytes of all live allocations.
+    pub fn used(&self) -> u64 {
+        self.inner.used.load(Ordering::Relaxed)
+    }
+
+    /// Accounts an allocation of `bytes` until the returned handle is dropped, calling the
+    /// pressure hook first if it would exceed the threshold.
+    pub fn track(&self, bytes: u64) -> BudgetAllocation {
+        let budget = self.budget();
+        // Multiplied first so budgets below 100 bytes aren't truncated to 0. The threshold is at
+        // most 100%, so the limit fits back into a u64.
+        let limit = (budget as u128 * self.threshold() as u128 / 100) as u64;
+        if self.used().saturating_add(bytes) > limit {
+            self.call_hook(bytes, budget, limit);
+        }
+        self.inner.used.fetch_add(bytes, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_budget(self.used(), budget);
+
+        BudgetAllocation {
+            budget: self.clone(),
+            bytes,
+        }
+    }
+
+    fn call_hook(&self, bytes: u64, budget: u64, limit: u64) {
+        let current = thread::current().id();
+        let mut state = self.inner.hook.lock().unwrap();
+        // Allocations made by the hook itself.
+        if state.running == Some(current) {
+            return;
+        }
+        if state.running.is_some() {
+            while state.running.is_some() {
+                state = self.inner.hook_returned.wait(state).unwrap();
+            }
+            // The other call may have freed enough memory.
+            if self.used().saturating_add(bytes) <= limit {
+                return;
+            }
+        }
+        let Some(hook) = state.hook.take() else {
+            return;
+        };
+        state.running = Some(current);
+        drop(state);
+
+        let mut running = RunningHook {
+            inner: &self.inner,
+            hook: Some(hook),
+        };
+        if let Some(hook) = running.hook.as_mut() {
+            hook(&BudgetPressure {
+                used: self.used(),
+                requested: bytes,
+                budget,
+            });
+        }
+    }
+}
+
+/// Memory accounted against a [`GpuBudget`], released when dropped.
+#[derive(Debug)]
+pub struct BudgetAllocation {
+    budget: GpuBudget,
+    bytes: u64,
+}
+
+impl BudgetAllocation {
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// The budget the allocation is accounted against.
+    pub fn budget(&self) -> &GpuBudget {
+        &self.budget
+    }
+}
+
+impl Drop for BudgetAllocation {
+    fn drop(&mut self) {
+        self.budget
+            .inner
+            .used
+            .fetch_sub(self.bytes, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_budget(self.budget.used(), self.budget.budget());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hook_can_allocate_and_replace_itself() {
+        let budget = GpuBudget::new(100);
+        let calls = Arc::new(AtomicU64::new(0));
+        budget.on_pressure({
+            let budget = budget.clone();
+            let calls = Arc::clone(&calls);
+            move |_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                // Allocations of the hook don't call it again.
+                drop(budget.track(200));
+                let calls = Arc::clone(&calls);
+                budget.on_pressure(move |_| {
+                    calls.fetch_add(10, Ordering::Relaxed);
+                });
+            }
+        });
+
+        let _small = budget.track(50);
+        assert_eq!(calls.load(Ordering::Relaxed), 0);
+        let _large = budget.track(50);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        let _larger = budget.track(50);
+        assert_eq!(
+            calls.load(Ordering::Relaxed),
+            11,
+            "the replaced hook is called"
+        );
+        assert_eq!(budget.used(), 150);
+    }
+
+    #[test]
+    fn concurrent_pressure_waits_for_the_running_hook() {
+        let budget = GpuBudget::new(100);
+        let calls = Arc::new(AtomicU64::new(0));
+        budget.on_pressure({
+            let calls = Arc::clone(&calls);
+            move |_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                thread::sleep(std::time::Duration::from_millis(20));
+            }
+        });
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let budget = budget.clone();
+                thread::spawn(move || drop(budget.track(200)))
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(calls.load(Ordering::Relaxed), 4);
+        assert_eq!(budget.used(), 0);
+    }
+}
@@ -0,0 +1,339 @@
+//! Camera uniforms shared between passes.
+
+use std::num::NonZeroU64;
+
+use crate::shader::ShaderComposer;
+
+/// Column-major 4x4 matrix, e.g. `glam::Mat4::to_cols_array_2d`.
+pub type Matrix4 = [[f32; 4]; 4];
+
+/// Order of the elements of a matrix handed to [`Mat4Uniform`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MatrixLayout {
+    /// `m[column][row]`, as used by WGSL, glam, nalgebra and cgmath.
+    ColumnMajor,
+    /// `m[row][column]`, as used by DirectXMath and most textbooks.
+    RowMajor,
+}
+
+/// A 4x4 matrix in the column-major layout of WGSL's `mat4x4<f32>`.
+///
+/// Constructing it from a [`Matrix4`] or `[f32; 16]` requires stating the layout of the source,
+/// which is converted on write. In debug builds, affine matrices which look transposed, i.e. with
+/// the translation in the last row, panic.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mat4Uniform {
+    columns: Matrix4,
+}
+
+impl Mat4Uniform {
+    /// Size of a `mat4x4<f32>` in a uniform or storage buffer.
+    pub const SIZE: wgpu::BufferAddress = 64;
+
+    pub const IDENTITY: Self = Self { columns: IDENTITY };
+
+    pub fn new(matrix: Matrix4, layout: MatrixLayout) -> Self {
+        let columns = match layout {
+            MatrixLayout::ColumnMajor => matrix,
+            MatrixLayout::RowMajor => transpose(&matrix),
+        };
+        debug_assert!(
+            !looks_transposed(&columns),
+            "matrix has its translation in the last row, it's probably {:?} instead of {:?}",
+            match layout {
+                MatrixLayout::ColumnMajor => MatrixLayout::RowMajor,
+                MatrixLayout::RowMajor => MatrixLayout::ColumnMajor,
+            },
+            layout,
+        );
+        Self { columns }
+    }
+
+    /// Creates a matrix from `[[f32; 4]; 4]` indexed `m[column][row]`.
+    pub fn from_column_major(columns: Matrix4) -> Self {
+        Self::new(columns, MatrixLayout::ColumnMajor)
+    }
+
+    /// Creates a matrix from `[[f32; 4]; 4]` indexed `m[row][column]`.
+    pub fn from_row_major(rows: Matrix4) -> Self {
+        Self::new(rows, MatrixLayout::RowMajor)
+    }
+
+    /// Creates a matrix from 16 consecutive elements in `layout`.
+    pub fn from_array(values: [f32; 16], layout: MatrixLayout) -> Self {
+        let mut matrix = [[0.0; 4]; 4];
+        for (i, value) in values.into_iter().enumerate() {
+            matrix[i / 4][i % 4] = value;
+        }
+        Self::new(matrix, layout)
+    }
+
+    /// Columns of the matrix.
+    pub fn columns(&self) -> Matrix4 {
+        self.columns
+    }
+
+    /// Bytes of the matrix as WGSL expects them.
+    pub fn to_bytes(&self) -> [u8; Self::SIZE as usize] {
+        let mut bytes = [0; Self::SIZE as usize];
+        for (chunk, value) in bytes.chunks_exact_mut(4).zip(self.columns.iter().flatten()) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Writes the matrix at `offset` of `buffer` using [`wgpu::Queue`].
+    pub fn write(&self, queue: &wgpu::Queue, buffer: &wgpu::Buffer, offset: wgpu::BufferAddress) {
+        queue.write_buffer(buffer, offset, &self.to_bytes());
+    }
+}
+
+impl Default for Mat4Uniform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+fn transpose(m: &Matrix4) -> Matrix4 {
+    let mut transposed = [[0.0; 4]; 4];
+    for (c, column) in m.iter().enumerate() {
+        for (r, value) in column.iter().enumerate() {
+            transposed[r][c] = *value;
+        }
+    }
+    transposed
+}
+
+/// Whether `columns` is an affine transform with the translation in the last row instead of the
+/// last column, the usual result of uploading a row-major matrix unconverted.
+fn looks_transposed(columns: &Matrix4) -> bool {
+    columns[3] == [0.0, 0.0, 0.0, 1.0] && columns[..3].iter().any(|column| column[3] != 0.0)
+}
+
+pub(crate) const CAMERA_SIZE: wgpu::BufferAddress = 3 * Mat4Uniform::SIZE + 16;
+
+/// Name of the snippet registered by [`CameraBuffer::register`].
+pub const CAMERA_SNIPPET: &str = "wgpu_util::camera";
+
+const CAMERA_WGSL: &str = r#"
+#include "wgpu_util::motion"
+
+struct WgpuUtilCamera {
+    view_projection: mat4x4<f32>,
+    previous_view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    jitter: vec2<f32>,
+    previous_jitter: vec2<f32>,
+};
+
+@group({group}) @binding({binding})
+var<uniform> wgpu_util_camera: WgpuUtilCamera;
+
+// Jittered clip position of a world position, for rasterization.
+fn camera_clip_position(world_position: vec3<f32>) -> vec4<f32> {
+    var clip = wgpu_util_camera.view_projection * vec4<f32>(world_position, 1.0);
+    clip = vec4<f32>(clip.xy + wgpu_util_camera.jitter * clip.w, clip.zw);
+    return clip;
+}
+
+// Unjittered clip position in the current frame, for motion vectors.
+fn camera_current_clip(world_position: vec3<f32>) -> vec4<f32> {
+    return wgpu_util_camera.view_projection * vec4<f32>(world_position, 1.0);
+}
+
+// Unjittered clip position in the previous frame, for motion vectors. Pass the previous world
+// position of skinned or animated vertices, the current one for static vertices.
+fn camera_previous_clip(previous_world_position: vec3<f32>) -> vec4<f32> {
+    return wgpu_util_camera.previous_view_projection * vec4<f32>(previous_world_position, 1.0);
+}
+"#;
+
+/// WGSL source of the camera snippet bound at `group` and `binding`, for passes binding a
+/// [`CameraBuffer`] in their own layout.
+pub(crate) fn camera_snippet(group: u32, binding: u32) -> String {
+    CAMERA_WGSL
+        .replace("{group}", &group.to_string())
+        .replace("{binding}", &binding.to_string())
+}
+
+/// Uniform buffer holding the view projection matrices of the current and previous frame.
+///
+/// Register with a [`ShaderComposer`] and `#include "wgpu_util::camera"` to get
+/// `camera_clip_position` for rasterizing and `camera_current_clip`/`camera_previous_clip`, which
+/// feed `motion_vector` from the `wgpu_util::motion` snippet.
+///
+/// Matrices are kept without jitter, which is applied separately so it doesn't show up in motion
+/// vectors.
+#[derive(Debug)]
+pub struct CameraBuffer {
+    buffer: wgpu::Buffer,
+    group: u32,
+    binding: u32,
+
+    view_projection: Matrix4,
+    previous_view_projection: Matrix4,
+    jitter: [f32; 2],
+    previous_jitter: [f32; 2],
+    updated: bool,
+}
+
+impl CameraBuffer {
+    /// Creates a buffer bound at `group` and `binding` in shaders. Call [`Self::update`] before
+    /// using it.
+    pub fn new(device: &wgpu::Device, group: u32, binding: u32) -> Self {
+        let buffer = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("camera buffer"),
+                size: CAMERA_SIZE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            buffer,
+            group,
+            binding,
+
+            view_projection: IDENTITY,
+            previous_view_projection: IDENTITY,
+            jitter: [0.0; 2],
+            previous_jitter: [0.0; 2],
+            updated: false,
+        }
+    }
+
+    /// Sets the camera of a new frame, keeping the current one as previous, and uploads it using
+    /// [`wgpu::Queue`].
+    ///
+    /// `jitter` is a clip space offset, e.g. from
+    /// [`crate::post::JitterSequence::clip_offset`]. On the first update, the previous camera is
+    /// the same as the current one.
+    pub fn update(&mut self, queue: &wgpu::Queue, view_projection: Mat4Uniform, jitter: [f32; 2]) {
+        let view_projection = view_projection.columns();
+        if self.updated {
+            self.previous_view_projection = self.view_projection;
+            self.previous_jitter = self.jitter;
+        } else {
+            self.previous_view_projection = view_projection;
+            self.previous_jitter = jitter;
+            self.updated = true;
+        }
+        self.view_projection = view_projection;
+        self.jitter = jitter;
+
+        // A singular matrix leaves the inverse zeroed, which only affects depth reprojection.
+        let inverse = invert(&view_projection).unwrap_or([[0.0; 4]; 4]);
+        let mut contents = Vec::with_capacity(CAMERA_SIZE as usize);
+        for matrix in [self.view_projection, self.previous_view_projection, inverse] {
+            // Already column-major, skip the transpose check which doesn't apply to inverses.
+            contents.extend_from_slice(&Mat4Uniform { columns: matrix }.to_bytes());
+        }
+        for value in self.jitter.iter().chain(&self.previous_jitter) {
+            contents.extend_from_slice(&value.to_le_bytes());
+        }
+        queue.write_buffer(&self.buffer, 0, &contents);
+    }
+
+    /// WGSL source of the snippet for this buffer's group and binding.
+    pub fn snippet(&self) -> String {
+        camera_snippet(self.group, self.binding)
+    }
+
+    /// Registers the snippet as [`CAMERA_SNIPPET`].
+    pub fn register(&self, composer: &mut ShaderComposer) {
+        composer.add_snippet(CAMERA_SNIPPET, self.snippet());
+    }
+
+    /// Layout entry for binding the buffer in all shader stages.
+    pub fn bind_group_layout_entry(&self) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility: wgpu::ShaderStages::all(),
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(CAMERA_SIZE),
+            },
+            count: None,
+        }
+    }
+
+    /// Bind group entry for the buffer.
+    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    /// View projection matrix of the current frame, without jitter.
+    pub fn view_projection(&self) -> Matrix4 {
+        self.view_projection
+    }
+
+    /// View projection matrix of the previous frame, without jitter.
+    pub fn previous_view_projection(&self) -> Matrix4 {
+        self.previous_view_projection
+    }
+
+    /// Clip space jitter of the current frame.
+    pub fn jitter(&self) -> [f32; 2] {
+        self.jitter
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+const IDENTITY: Matrix4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// Inverts `m` by Gauss-Jordan elimination with partial pivoting.
+pub(crate) fn invert(m: &Matrix4) -> Option<Matrix4> {
+    // Work on rows of the augmented matrix [m | I], transposed from the column-major input.
+    let mut a = [[0.0f64; 8]; 4];
+    for (r, row) in a.iter_mut().enumerate() {
+        for c in 0..4 {
+            row[c] = m[c][r] as f64;
+        }
+        row[4 + r] = 1.0;
+    }
+
+    for col in 0..4 {
+        let pivot = (col..4).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+
+        let scale = a[col][col];
+        for value in &mut a[col] {
+            *value /= scale;
+        }
+        let pivot_row = a[col];
+        for (r, row) in a.iter_mut().enumerate() {
+            if r != col {
+                let factor = row[col];
+                for (value, pivot_value) in row.iter_mut().zip(pivot_row) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    let mut inverse = [[0.0; 4]; 4];
+    for (c, column) in inverse.iter_mut().enumerate() {
+        for (r, value) in column.iter_mut().enumerate() {
+            *value = a[r][4 + c] as f32;
+        }
+    }
+    Some(inverse)
+}
@@ -0,0 +1,628 @@
+//! Clustered forward lighting: assigning lights to a grid of view space clusters.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{Mat4Uniform, Matrix4},
+    shader::ShaderComposer,
+};
+
+const WORKGROUP_SIZE: u32 = 64;
+const PARAMS_SIZE: wgpu::BufferAddress = 112;
+
+/// Size of a cluster in [`ClusterLighting::clusters`]: the offset of its first light index and
+/// the number of lights as `u32`.
+pub const CLUSTER_SIZE: wgpu::BufferAddress = 8;
+
+/// Size of a light in [`ClusterLighting::lights`]: the world position as `vec3<f32>` and the
+/// radius as `f32`.
+pub const LIGHT_SIZE: wgpu::BufferAddress = 16;
+
+/// Size of the bounds of a cluster: the minimum and maximum as `vec4<f32>` with unused `w`.
+pub const CLUSTER_BOUNDS_SIZE: wgpu::BufferAddress = 32;
+
+/// Name of the snippet registered by [`ClusterLighting::register`].
+pub const CLUSTER_SNIPPET: &str = "wgpu_util::clusters";
+
+const CLUSTER_WGSL: &str = r#"
+struct WgpuUtilClusterParams {
+    view: mat4x4<f32>,
+    dimensions: vec4<u32>,
+    viewport: vec2<f32>,
+    near: f32,
+    far: f32,
+    max_lights_per_cluster: u32,
+};
+
+struct WgpuUtilCluster {
+    offset: u32,
+    count: u32,
+};
+
+struct WgpuUtilLight {
+    position: vec3<f32>,
+    radius: f32,
+};
+
+@group({group}) @binding(0)
+var<uniform> wgpu_util_cluster_params: WgpuUtilClusterParams;
+@group({group}) @binding(1)
+var<storage, read> wgpu_util_clusters: array<WgpuUtilCluster>;
+@group({group}) @binding(2)
+var<storage, read> wgpu_util_cluster_light_indices: array<u32>;
+@group({group}) @binding(3)
+var<storage, read> wgpu_util_cluster_lights: array<WgpuUtilLight>;
+
+// Cluster of the fragment at `frag_coord`, `view_depth` units in front of the camera.
+fn cluster_index(frag_coord: vec2<f32>, view_depth: f32) -> u32 {
+    let params = wgpu_util_cluster_params;
+    let tiles = params.dimensions.xy;
+    let tile = min(vec2<u32>(frag_coord / params.viewport * vec2<f32>(tiles)), tiles - 1u);
+    let slices = f32(params.dimensions.z);
+    let depth = max(view_depth, params.near);
+    let exponent = log(depth / params.near) / log(params.far / params.near);
+    let slice = u32(clamp(exponent * slices, 0.0, slices - 1.0));
+    return (slice * tiles.y + tile.y) * tiles.x + tile.x;
+}
+
+// Number of lights affecting `cluster`.
+fn cluster_light_count(cluster: u32) -> u32 {
+    return wgpu_util_clusters[cluster].count;
+}
+
+// Index of the `i`th light of `cluster`, into `wgpu_util_cluster_lights` and per light data of the
+// application.
+fn cluster_light_index(cluster: u32, i: u32) -> u32 {
+    return wgpu_util_cluster_light_indices[wgpu_util_clusters[cluster].offset + i];
+}
+
+// The `i`th light of `cluster`.
+fn cluster_light(cluster: u32, i: u32) -> WgpuUtilLight {
+    return wgpu_util_cluster_lights[cluster_light_index(cluster, i)];
+}
+"#;
+
+/// Descriptor for [`ClusterGrid`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClusterGridDescriptor {
+    /// Tiles along the width of the screen.
+    pub tiles_x: u32,
+    /// Tiles along the height of the screen.
+    pub tiles_y: u32,
+    /// Depth slices between `near` and `far`, growing exponentially with depth.
+    pub slices: u32,
+    /// View depth of the start of the first slice.
+    pub near: f32,
+    /// View depth of the end of the last slice. Fragments further away use the last slice.
+    pub far: f32,
+}
+
+impl Default for ClusterGridDescriptor {
+    fn default() -> Self {
+        Self {
+            tiles_x: 16,
+            tiles_y: 9,
+            slices: 24,
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+/// Axis aligned bounding box of a cluster in view space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClusterBounds {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl ClusterBounds {
+    /// Whether a sphere at the view space position `center` with `radius` touches the box.
+    pub fn intersects_sphere(&self, center: [f32; 3], radius: f32) -> bool {
+        let distance_squared: f32 = (0..3)
+            .map(|i| {
+                let delta = center[i].clamp(self.min[i], self.max[i]) - center[i];
+                delta * delta
+            })
+            .sum();
+        distance_squared <= radius * radius
+    }
+}
+
+/// A point light, as seen by the light assignment. Colors and other parameters are kept by the
+/// application, indexed like the lights passed to [`ClusterLighting::assign`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClusterLight {
+    /// World position.
+    pub position: [f32; 3],
+    /// Distance beyond which the light has no effect.
+    pub radius: f32,
+}
+
+impl ClusterLight {
+    /// The light in the layout of `WgpuUtilLight`.
+    pub fn to_bytes(&self) -> [u8; LIGHT_SIZE as usize] {
+        let mut bytes = [0; LIGHT_SIZE as usize];
+        for (chunk, value) in bytes
+            .chunks_exact_mut(4)
+            .zip(self.position.iter().chain([&self.radius]))
+        {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes
+    }
+}
+
+/// Tiles of the screen subdivided into depth slices, the clusters of clustered lighting.
+///
+/// Clusters are indexed with x varying fastest, then y from the top of the screen, then slices
+/// from near to far. View space is right-handed with the camera looking along `-z`, view depth is
+/// the distance along `-z`.
+#[derive(Clone, Debug)]
+pub struct ClusterGrid {
+    descriptor: ClusterGridDescriptor,
+}
+
+impl ClusterGrid {
+    pub fn new(descriptor: &ClusterGridDescriptor) -> Self {
+        assert!(
+            descriptor.tiles_x > 0 && descriptor.tiles_y > 0 && descriptor.slices > 0,
+            "cluster grid must not be empty"
+        );
+        assert!(
+            descriptor.near > 0.0 && descriptor.far > descriptor.near,
+            "cluster depth range must be positive"
+        );
+        Self {
+            descriptor: *descriptor,
+        }
+    }
+
+    pub fn descriptor(&self) -> &ClusterGridDescriptor {
+        &self.descriptor
+    }
+
+    /// Tiles along x and y and the number of slices.
+    pub fn dimensions(&self) -> [u32; 3] {
+        let d = &self.descriptor;
+        [d.tiles_x, d.tiles_y, d.slices]
+    }
+
+    /// Number of clusters.
+    pub fn count(&self) -> u32 {
+        let [x, y, z] = self.dimensions();
+        x * y * z
+    }
+
+    /// Index of the cluster of tile `x`, `y` in `slice`.
+    pub fn index(&self, x: u32, y: u32, slice: u32) -> u32 {
+        let [tiles_x, tiles_y, _] = self.dimensions();
+        (slice * tiles_y + y) * tiles_x + x
+    }
+
+    /// View depth at which `slice` starts, `slices` gives the far depth.
+    pub fn slice_depth(&self, slice: u32) -> f32 {
+        let d = &self.descriptor;
+        d.near * (d.far / d.near).powf(slice as f32 / d.slices as f32)
+    }
+
+    /// Slice containing `view_depth`, clamped to the grid like `cluster_index` in WGSL.
+    pub fn slice(&self, view_depth: f32) -> u32 {
+        let d = &self.descriptor;
+        let depth = view_depth.max(d.near);
+        let slice = (depth / d.near).ln() / (d.far / d.near).ln() * d.slices as f32;
+        slice.clamp(0.0, (d.slices - 1) as f32) as u32
+    }
+
+    /// View space bounds of all clusters in index order for the perspective `projection`.
+    ///
+    /// Tile corners are unprojected into rays from the camera, which are cut at the depths of
+    /// the slices, so any depth convention of the projection works, including reversed and
+    /// infinite depth.
+    ///
+    /// Panics if `projection` isn't invertible.
+    pub fn bounds(&self, projection: &Mat4Uniform) -> Vec<ClusterBounds> {
+        let inverse =
+            crate::camera::invert(&projection.columns()).expect("projection must be invertible");
+        let [tiles_x, tiles_y, slices] = self.dimensions();
+
+        // Rays through the tile corners, scaled to a view depth of 1.
+        let rays: Vec<[f32; 3]> = (0..=tiles_y)
+            .flat_map(|y| (0..=tiles_x).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let ndc_x = x as f32 / tiles_x as f32 * 2.0 - 1.0;
+                let ndc_y = 1.0 - y as f32 / tiles_y as f32 * 2.0;
+                let [px, py, pz, pw] = transform(&inverse, [ndc_x, ndc_y, 0.5, 1.0]);
+                let depth = -pz / pw;
+                [px / pw / depth, py / pw / depth, -1.0]
+            })
+            .collect();
+        let ray = |x: u32, y: u32| rays[(y * (tiles_x + 1) + x) as usize];
+
+        let mut bounds = Vec::with_capacity(self.count() as usize);
+        for slice in 0..slices {
+            let depths = [self.slice_depth(slice), self.slice_depth(slice + 1)];
+            for y in 0..tiles_y {
+                for x in 0..tiles_x {
+                    let mut min = [f32::INFINITY; 3];
+                    let mut max = [f32::NEG_INFINITY; 3];
+                    for corner in [ray(x, y), ray(x + 1, y), ray(x, y + 1), ray(x + 1, y + 1)] {
+                        for depth in depths {
+                            for i in 0..3 {
+                                min[i] = min[i].min(corner[i] * depth);
+                                max[i] = max[i].max(corner[i] * depth);
+                            }
+                        }
+                    }
+                    bounds.push(ClusterBounds { min, max });
+                }
+            }
+        }
+        bounds
+    }
+}
+
+fn buffer_entry(binding: u32, buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry {
+        binding,
+        resource: buffer.as_entire_binding(),
+    }
+}
+
+fn transform(m: &Matrix4, v: [f32; 4]) -> [f32; 4] {
+    let mut result = [0.0; 4];
+    for (column, value) in m.iter().zip(v) {
+        for (r, component) in result.iter_mut().enumerate() {
+            *component += column[r] * value;
+        }
+    }
+    result
+}
+
+/// Descriptor for [`ClusterLighting`].
+#[derive(Clone, Copy, Debug)]
+pub struct ClusterLightingDescriptor {
+    pub grid: ClusterGridDescriptor,
+    /// Lights passed to [`ClusterLighting::assign`] beyond this are ignored.
+    pub max_lights: u32,
+    /// Lights of a cluster beyond this are dropped, in the order they were passed.
+    pub max_lights_per_cluster: u32,
+    /// Group of the bind group in the lighting shaders, see [`ClusterLighting::snippet`].
+    pub group: u32,
+    /// Stages of the lighting pass reading the clusters.
+    pub visibility: wgpu::ShaderStages,
+}
+
+impl Default for ClusterLightingDescriptor {
+    fn default() -> Self {
+        Self {
+            grid: ClusterGridDescriptor::default(),
+            max_lights: 256,
+            max_lights_per_cluster: 64,
+            group: 0,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+        }
+    }
+}
+
+/// Light lists of a [`ClusterGrid`], assigned on the GPU, for forward+ lighting.
+///
+/// Set the projection with [`Self::set_projection`] whenever it or the viewport changes, which
+/// uploads the cluster bounds built on the CPU. [`Self::assign`] then records a compute pass
+/// collecting the lights touching every cluster.
+///
+/// Lighting shaders bind [`Self::bind_group`] at the group of the descriptor and
+/// `#include "wgpu_util::clusters"` after [`Self::register`]. The bindings are:
+///
+/// 0. Uniform parameters, including the view matrix and the viewport size.
+/// 1. [`Self::clusters`]: one `WgpuUtilCluster` of [`CLUSTER_SIZE`] bytes per cluster, holding
+///    the offset of its first light index and the number of lights.
+/// 2. [`Self::light_indices`]: `u32` indices into the lights, `max_lights_per_cluster` slots per
+///    cluster.
+/// 3. [`Self::lights`]: one `WgpuUtilLight` of [`LIGHT_SIZE`] bytes per light.
+///
+/// A fragment shader finds its cluster with `cluster_index(frag_coord.xy, view_depth)` and
+/// iterates over `cluster_light_count(cluster)` lights with `cluster_light_index` or
+/// `cluster_light`.
+#[derive(Debug)]
+pub struct ClusterLighting {
+    grid: ClusterGrid,
+    max_lights: u32,
+    max_lights_per_cluster: u32,
+    group: u32,
+
+    pipeline: wgpu::ComputePipeline,
+    assign_bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+
+    params: wgpu::Buffer,
+    bounds: wgpu::Buffer,
+    lights: wgpu::Buffer,
+    clusters: wgpu::Buffer,
+    light_indices: wgpu::Buffer,
+
+    projection: Option<Matrix4>,
+    viewport: [f32; 2],
+}
+
+impl ClusterLighting {
+    pub fn new(device: &wgpu::Device, descriptor: &ClusterLightingDescriptor) -> Self {
+        let grid = ClusterGrid::new(&descriptor.grid);
+        let max_lights = descriptor.max_lights.max(1);
+        let max_lights_per_cluster = descriptor.max_lights_per_cluster.max(1);
+
+        let source = include_str!("shaders/cluster_assign.wgsl");
+
+        let shader = crate::create_shader_module(device, Some("cluster assign shader"), source);
+
+        let buffer = |label, size, usage| {
+            crate::resource_log::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: usage | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            )
+        };
+        let clusters = grid.count() as wgpu::BufferAddress;
+        let params = buffer(
+            "cluster params buffer",
+            PARAMS_SIZE,
+            wgpu::BufferUsages::UNIFORM,
+        );
+        let bounds = buffer(
+            "cluster bounds buffer",
+            clusters * CLUSTER_BOUNDS_SIZE,
+            wgpu::BufferUsages::STORAGE,
+        );
+        let lights = buffer(
+            "cluster lights buffer",
+            max_lights as wgpu::BufferAddress * LIGHT_SIZE,
+            wgpu::BufferUsages::STORAGE,
+        );
+        let cluster_buffer = buffer(
+            "clusters buffer",
+            clusters * CLUSTER_SIZE,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        );
+        let light_indices = buffer(
+            "cluster light indices buffer",
+            clusters * max_lights_per_cluster as wgpu::BufferAddress * 4,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        );
+
+        let uniform_entry = |binding, visibility| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+            },
+            count: None,
+        };
+        let storage_entry = |binding, visibility, read_only, size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(size),
+            },
+            count: None,
+        };
+
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let assign_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cluster assign bind group layout"),
+            entries: &[
+                uniform_entry(0, compute),
+                storage_entry(1, compute, true, CLUSTER_BOUNDS_SIZE),
+                storage_entry(2, compute, true, LIGHT_SIZE),
+                storage_entry(3, compute, false, CLUSTER_SIZE),
+                storage_entry(4, compute, false, 4),
+            ],
+        });
+        let assign_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("cluster assign bind group"),
+            layout: &assign_layout,
+            entries: &[
+                buffer_entry(0, &params),
+                buffer_entry(1, &bounds),
+                buffer_entry(2, &lights),
+                buffer_entry(3, &cluster_buffer),
+                buffer_entry(4, &light_indices),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("cluster assign pipeline layout"),
+            bind_group_layouts: &[&assign_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::resource_log::create_compute_pipeline(
+            device,
+            &wgpu::ComputePipelineDescriptor {
+                label: Some("cluster assign pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "assign",
+            },
+        );
+
+        let visibility = descriptor.visibility;
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("cluster lighting bind group layout"),
+            entries: &[
+                uniform_entry(0, visibility),
+                storage_entry(1, visibility, true, CLUSTER_SIZE),
+                storage_entry(2, visibility, true, 4),
+                storage_entry(3, visibility, true, LIGHT_SIZE),
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("cluster lighting bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                buffer_entry(0, &params),
+                buffer_entry(1, &cluster_buffer),
+                buffer_entry(2, &light_indices),
+                buffer_entry(3, &lights),
+            ],
+        });
+
+        Self {
+            grid,
+            max_lights,
+            max_lights_per_cluster,
+            group: descriptor.group,
+
+            pipeline,
+            assign_bind_group,
+            bind_group_layout,
+            bind_group,
+
+            params,
+            bounds,
+            lights,
+            clusters: cluster_buffer,
+            light_indices,
+
+            projection: None,
+            viewport: [1.0; 2],
+        }
+    }
+
+    pub fn grid(&self) -> &ClusterGrid {
+        &self.grid
+    }
+
+    /// Sets the perspective projection and the viewport size in pixels, rebuilding and uploading
+    /// the cluster bounds if the projection changed.
+    ///
+    /// Panics if `projection` isn't invertible.
+    pub fn set_projection(
+        &mut self,
+        queue: &wgpu::Queue,
+        projection: Mat4Uniform,
+        width: u32,
+        height: u32,
+    ) {
+        self.viewport = [width.max(1) as f32, height.max(1) as f32];
+        if self.projection == Some(projection.columns()) {
+            return;
+        }
+        self.projection = Some(projection.columns());
+
+        let bounds = self.grid.bounds(&projection);
+        let mut contents = Vec::with_capacity(bounds.len() * CLUSTER_BOUNDS_SIZE as usize);
+        for ClusterBounds { min, max } in bounds {
+            for value in min.into_iter().chain([0.0]).chain(max).chain([0.0]) {
+                contents.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        queue.write_buffer(&self.bounds, 0, &contents);
+    }
+
+    /// Uploads `view` and `lights` and records a pass assigning the lights to the clusters.
+    ///
+    /// Lights beyond the maximum of the descriptor are ignored. Since the upload goes through
+    /// [`wgpu::Queue::write_buffer`], only the last call before a submission takes effect.
+    ///
+    /// Panics if [`Self::set_projection`] wasn't called yet.
+    pub fn assign(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: Mat4Uniform,
+        lights: &[ClusterLight],
+    ) {
+        assert!(
+            self.projection.is_some(),
+            "set_projection must be called before assign"
+        );
+        let lights = &lights[..lights.len().min(self.max_lights as usize)];
+
+        let d = self.grid.descriptor();
+        let mut params = Vec::with_capacity(PARAMS_SIZE as usize);
+        params.extend_from_slice(&view.to_bytes());
+        for value in [d.tiles_x, d.tiles_y, d.slices, lights.len() as u32] {
+            params.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [self.viewport[0], self.viewport[1], d.near, d.far] {
+            params.extend_from_slice(&value.to_le_bytes());
+        }
+        params.extend_from_slice(&self.max_lights_per_cluster.to_le_bytes());
+        params.resize(PARAMS_SIZE as usize, 0);
+        queue.write_buffer(&self.params, 0, &params);
+
+        if !lights.is_empty() {
+            let contents: Vec<u8> = lights.iter().flat_map(|light| light.to_bytes()).collect();
+            queue.write_buffer(&self.lights, 0, &contents);
+        }
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::dispatch("cluster assign pass")
+                .read("bounds", &self.bounds)
+                .read("lights", &self.lights)
+                .write("clusters", &self.clusters)
+                .write("light indices", &self.light_indices)
+        });
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("cluster assign pass"),
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.assign_bind_group, &[]);
+        crate::dispatch(
+            &mut pass,
+            "cluster assign pass",
+            [self.grid.count().div_ceil(WORKGROUP_SIZE), 1, 1],
+        );
+    }
+
+    /// WGSL source of the snippet for the group of the descriptor.
+    pub fn snippet(&self) -> String {
+        CLUSTER_WGSL.replace("{group}", &self.group.to_string())
+    }
+
+    /// Registers the snippet as [`CLUSTER_SNIPPET`].
+    pub fn register(&self, composer: &mut ShaderComposer) {
+        composer.add_snippet(CLUSTER_SNIPPET, self.snippet());
+    }
+
+    /// Layout of [`Self::bind_group`], for lighting pipelines.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Bind group reading the clusters in the lighting pass.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Offsets and counts of the light indices of all clusters, [`CLUSTER_SIZE`] bytes each.
+    pub fn clusters(&self) -> &wgpu::Buffer {
+        &self.clusters
+    }
+
+    /// Light indices, `max_lights_per_cluster` `u32` slots per cluster.
+    pub fn light_indices(&self) -> &wgpu::Buffer {
+        &self.light_indices
+    }
+
+    /// Lights of the last [`Self::assign`], [`LIGHT_SIZE`] bytes each.
+    pub fn lights(&self) -> &wgpu::Buffer {
+        &self.lights
+    }
+
+    pub fn max_lights(&self) -> u32 {
+        self.max_lights
+    }
+
+    pub fn max_lights_per_cluster(&self) -> u32 {
+        self.max_lights_per_cluster
+    }
+}
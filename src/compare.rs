@@ -0,0 +1,267 @@
+//! Comparing images on the GPU.
+
+use std::{fmt, num::NonZeroU64};
+
+use crate::{
+    readback::TextureInfo,
+    reduce::{ReduceOp, Reducer},
+    shader::ShaderComposer,
+    BufferInitDescriptor, DeviceExt,
+};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Options for [`ImageComparer::compare`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct CompareOptions {
+    /// Also compute the structural similarity, which is more expensive than PSNR.
+    pub ssim: bool,
+}
+
+/// Similarity of two images.
+///
+/// Values are computed on the texels the shader sees, so sRGB formats are compared in linear
+/// space, with a dynamic range of `1.0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Metrics {
+    /// Mean squared error over the RGB channels.
+    pub mse: f64,
+    /// Peak signal-to-noise ratio in decibels. Infinite for equal images.
+    pub psnr: f64,
+    /// Mean structural similarity of the luminance, `1.0` for equal images. Only computed if
+    /// [`CompareOptions::ssim`] is set.
+    pub ssim: Option<f64>,
+}
+
+impl fmt::Display for Metrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "PSNR {:.2} dB, MSE {:.3e}", self.psnr, self.mse)?;
+        if let Some(ssim) = self.ssim {
+            write!(f, ", SSIM {:.5}", ssim)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute pipelines measuring PSNR and SSIM between textures, reduced with [`Reducer`].
+#[derive(Debug)]
+pub struct ImageComparer {
+    squared_error_pipeline: wgpu::ComputePipeline,
+    ssim_pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    reducer: Reducer,
+}
+
+impl ImageComparer {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = ShaderComposer::new()
+            .create_shader_module(
+                device,
+                Some("image compare shader"),
+                include_str!("shaders/compare.wgsl"),
+            )
+            .expect("builtin snippets must compose");
+
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("image compare bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                texture_entry(1),
+                texture_entry(2),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(4),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("image compare pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label, entry_point| {
+            crate::resource_log::create_compute_pipeline(
+                device,
+                &wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    module: &shader,
+                    entry_point,
+                },
+            )
+        };
+
+        Self {
+            squared_error_pipeline: create_pipeline(
+                "image compare squared error pipeline",
+                "squared_error",
+            ),
+            ssim_pipeline: create_pipeline("image compare ssim pipeline", "ssim"),
+            bind_group_layout,
+            reducer: Reducer::new(device),
+        }
+    }
+
+    /// Compares the first mip levels of `a` and `b` and blocks until the metrics are read back.
+    ///
+    /// Both textures must match `info`, have a float or unorm format and
+    /// [`wgpu::TextureUsages::TEXTURE_BINDING`].
+    pub fn compare(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        a: &wgpu::Texture,
+        b: &wgpu::Texture,
+        info: &TextureInfo,
+        options: &CompareOptions,
+    ) -> Result<Metrics, wgpu::BufferAsyncError> {
+        let len = info.width * info.height;
+        if len == 0 {
+            return Ok(Metrics {
+                mse: 0.0,
+                psnr: f64::INFINITY,
+                ssim: options.ssim.then_some(1.0),
+            });
+        }
+
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("image compare params buffer"),
+            contents: &[info.width, info.height, 0, 0]
+                .iter()
+                .flat_map(|w| w.to_le_bytes())
+                .collect::<Vec<_>>(),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let view_descriptor = wgpu::TextureViewDescriptor {
+            mip_level_count: std::num::NonZeroU32::new(1),
+            ..Default::default()
+        };
+        let view_a = a.create_view(&view_descriptor);
+        let view_b = b.create_view(&view_descriptor);
+
+        let mut pipelines = vec![&self.squared_error_pipeline];
+        if options.ssim {
+            pipelines.push(&self.ssim_pipeline);
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("image compare encoder"),
+        });
+        let results = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("image compare results buffer"),
+                size: 8,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        for (i, pipeline) in pipelines.iter().enumerate() {
+            let texel_values = crate::resource_log::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("image compare texel buffer"),
+                    size: len as wgpu::BufferAddress * 4,
+                    usage: wgpu::BufferUsages::STORAGE,
+                    mapped_at_creation: false,
+                },
+            );
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("image compare bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&view_a),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&view_b),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: texel_values.as_entire_binding(),
+                    },
+                ],
+            });
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("image compare pass"),
+                });
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                crate::dispatch(
+                    &mut pass,
+                    "image compare pass",
+                    [
+                        info.width.div_ceil(WORKGROUP_SIZE),
+                        info.height.div_ceil(WORKGROUP_SIZE),
+                        1,
+                    ],
+                );
+            }
+
+            let sum = self
+                .reducer
+                .reduce(device, &mut encoder, &texel_values, len, ReduceOp::Sum);
+            encoder.copy_buffer_to_buffer(&sum, 0, &results, i as wgpu::BufferAddress * 4, 4);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        let bytes = crate::readback::read_buffer(device, queue, &results, 0..8)?;
+        let sums: Vec<f64> = bytes
+            .chunks_exact(4)
+            .map(|v| f32::from_le_bytes(v.try_into().unwrap()) as f64)
+            .collect();
+
+        let mse = sums[0] / (len as f64 * 3.0);
+        Ok(Metrics {
+            mse,
+            psnr: -10.0 * mse.log10(),
+            ssim: options.ssim.then(|| sums[1] / len as f64),
+        })
+    }
+}
+
+/// Compares `a` and `b` with a new [`ImageComparer`]. See [`ImageComparer::compare`].
+pub fn compare_textures(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    a: &wgpu::Texture,
+    b: &wgpu::Texture,
+    info: &TextureInfo,
+    options: &CompareOptions,
+) -> Result<Metrics, wgpu::BufferAsyncError> {
+    ImageComparer::new(device).compare(device, queue, a, b, info, options)
+}
@@ -0,0 +1,343 @@
+//! Compressing textures into BC formats on the GPU.
+
+use std::num::{NonZeroU32, NonZeroU64};
+
+use crate::{readback::TextureInfo, BufferInitDescriptor, DeviceExt};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Block compressed format written by [`BlockCompressor`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BlockFormat {
+    /// RGB in 4 bits per texel, alpha is ignored.
+    Bc1,
+    /// The red channel in 4 bits per texel, e.g. for masks and heightmaps.
+    Bc4,
+    /// RGBA in 8 bits per texel, using only mode 6 of BC7, which is fast to encode but doesn't
+    /// reach the quality of offline encoders.
+    Bc7,
+}
+
+impl BlockFormat {
+    /// Size of a 4x4 block in bytes.
+    pub fn block_size(self) -> u32 {
+        match self {
+            Self::Bc1 | Self::Bc4 => 8,
+            Self::Bc7 => 16,
+        }
+    }
+
+    /// Texture format of the blocks. `srgb` is ignored for [`Self::Bc4`].
+    pub fn texture_format(self, srgb: bool) -> wgpu::TextureFormat {
+        use wgpu::TextureFormat as F;
+        match (self, srgb) {
+            (Self::Bc1, false) => F::Bc1RgbaUnorm,
+            (Self::Bc1, true) => F::Bc1RgbaUnormSrgb,
+            (Self::Bc4, _) => F::Bc4RUnorm,
+            (Self::Bc7, false) => F::Bc7RgbaUnorm,
+            (Self::Bc7, true) => F::Bc7RgbaUnormSrgb,
+        }
+    }
+
+    fn entry_point(self) -> &'static str {
+        match self {
+            Self::Bc1 => "bc1",
+            Self::Bc4 => "bc4",
+            Self::Bc7 => "bc7",
+        }
+    }
+}
+
+fn padded_bytes_per_row(unpadded: u32) -> u32 {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded.div_ceil(align) * align
+}
+
+/// Blocks written by [`BlockCompressor::compress`].
+#[derive(Debug)]
+pub struct CompressedBlocks {
+    /// Blocks row by row, with rows padded to [`Self::padded_bytes_per_row`].
+    pub buffer: wgpu::Buffer,
+    pub format: wgpu::TextureFormat,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CompressedBlocks {
+    fn block_size(&self) -> u32 {
+        self.format.describe().block_size as u32
+    }
+
+    pub fn blocks_per_row(&self) -> u32 {
+        self.width.div_ceil(4)
+    }
+
+    pub fn block_rows(&self) -> u32 {
+        self.height.div_ceil(4)
+    }
+
+    /// Size of a tightly packed row of blocks in bytes.
+    pub fn unpadded_bytes_per_row(&self) -> u32 {
+        self.blocks_per_row() * self.block_size()
+    }
+
+    /// Size of a row of blocks in the buffer, aligned for copies to textures.
+    pub fn padded_bytes_per_row(&self) -> u32 {
+        padded_bytes_per_row(self.unpadded_bytes_per_row())
+    }
+
+    /// Records a copy of the blocks into the first mip level of `texture`, which must have
+    /// [`Self::format`], the same size and [`wgpu::TextureUsages::COPY_DST`].
+    pub fn copy_to_texture(&self, encoder: &mut wgpu::CommandEncoder, texture: &wgpu::Texture) {
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &self.buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(self.padded_bytes_per_row()),
+                    rows_per_image: None,
+                },
+            },
+            texture.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.blocks_per_row() * 4,
+                height: self.block_rows() * 4,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Creates a texture of the blocks, e.g. to re-upload render-to-texture results compressed.
+    /// [`wgpu::TextureUsages::COPY_DST`] is added to `usage`.
+    ///
+    /// The device must have [`wgpu::Features::TEXTURE_COMPRESSION_BC`] enabled.
+    pub fn create_texture(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        label: wgpu::Label,
+        usage: wgpu::TextureUsages,
+    ) -> wgpu::Texture {
+        let texture = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label,
+                size: wgpu::Extent3d {
+                    width: self.width,
+                    height: self.height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.format,
+                usage: usage | wgpu::TextureUsages::COPY_DST,
+            },
+        );
+        self.copy_to_texture(encoder, &texture);
+        texture
+    }
+
+    /// Reads back the tightly packed blocks, e.g. for storing them in a DDS or KTX2 file. Blocks
+    /// until the readback finished.
+    ///
+    /// The commands writing the blocks must have been submitted.
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+        let size = self.padded_bytes_per_row() as wgpu::BufferAddress * self.block_rows() as u64;
+        let padded = crate::readback::read_buffer(device, queue, &self.buffer, 0..size)?;
+        let row = self.unpadded_bytes_per_row() as usize;
+        Ok(padded
+            .chunks_exact(self.padded_bytes_per_row() as usize)
+            .flat_map(|padded_row| &padded_row[..row])
+            .copied()
+            .collect())
+    }
+}
+
+/// Compute pipelines compressing textures into BC1, BC4 and BC7 blocks, e.g. to store baked
+/// render-to-texture results in a fraction of the memory.
+///
+/// Works without [`wgpu::Features::TEXTURE_COMPRESSION_BC`], which is only needed for creating
+/// textures of the blocks.
+#[derive(Debug)]
+pub struct BlockCompressor {
+    bc1_pipeline: wgpu::ComputePipeline,
+    bc4_pipeline: wgpu::ComputePipeline,
+    bc7_pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl BlockCompressor {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let source = include_str!("shaders/block_compress.wgsl");
+
+        let shader = crate::create_shader_module(device, Some("block compress shader"), source);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("block compress bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(8),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("block compress pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let create_pipeline = |label, format: BlockFormat| {
+            crate::resource_log::create_compute_pipeline(
+                device,
+                &wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    module: &shader,
+                    entry_point: format.entry_point(),
+                },
+            )
+        };
+
+        Self {
+            bc1_pipeline: create_pipeline("bc1 compress pipeline", BlockFormat::Bc1),
+            bc4_pipeline: create_pipeline("bc4 compress pipeline", BlockFormat::Bc4),
+            bc7_pipeline: create_pipeline("bc7 compress pipeline", BlockFormat::Bc7),
+            bind_group_layout,
+        }
+    }
+
+    /// Records a pass compressing the first mip level of `input` into `format` blocks.
+    ///
+    /// `input` must match `info`, have a float or unorm format and
+    /// [`wgpu::TextureUsages::TEXTURE_BINDING`]. With `srgb`, linear texels are encoded as sRGB
+    /// for a [`BlockFormat::texture_format`] with sRGB. Texels of sRGB inputs are read linear, so
+    /// they need `srgb` too. Partial blocks at the edges repeat the last texels.
+    pub fn compress(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::Texture,
+        info: &TextureInfo,
+        format: BlockFormat,
+        srgb: bool,
+    ) -> CompressedBlocks {
+        let padded_bytes_per_row =
+            padded_bytes_per_row(info.width.div_ceil(4) * format.block_size());
+        let size = padded_bytes_per_row as wgpu::BufferAddress * info.height.div_ceil(4) as u64;
+        let blocks = CompressedBlocks {
+            buffer: crate::resource_log::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("compressed blocks buffer"),
+                    size: size.max(wgpu::COPY_BUFFER_ALIGNMENT),
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                },
+            ),
+            format: format.texture_format(srgb),
+            width: info.width,
+            height: info.height,
+        };
+        if info.width == 0 || info.height == 0 {
+            return blocks;
+        }
+
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("block compress params buffer"),
+            contents: &[
+                info.width,
+                info.height,
+                padded_bytes_per_row / 4,
+                srgb as u32,
+            ]
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .collect::<Vec<_>>(),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let view = input.create_view(&wgpu::TextureViewDescriptor {
+            mip_level_count: NonZeroU32::new(1),
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("block compress bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: blocks.buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::dispatch("block compress pass")
+                .read("input", input)
+                .write("output", &blocks.buffer)
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("block compress pass"),
+            });
+            pass.set_pipeline(match format {
+                BlockFormat::Bc1 => &self.bc1_pipeline,
+                BlockFormat::Bc4 => &self.bc4_pipeline,
+                BlockFormat::Bc7 => &self.bc7_pipeline,
+            });
+            pass.set_bind_group(0, &bind_group, &[]);
+            crate::dispatch(
+                &mut pass,
+                "block compress pass",
+                [
+                    blocks.blocks_per_row().div_ceil(WORKGROUP_SIZE),
+                    blocks.block_rows().div_ceil(WORKGROUP_SIZE),
+                    1,
+                ],
+            );
+        }
+
+        blocks
+    }
+}
@@ -0,0 +1,114 @@
+//! Bundled wgpu instance, adapter, device and queue.
+
+use std::fmt;
+
+/// Descriptor for [`GpuContext`].
+#[derive(Clone, Debug)]
+pub struct GpuContextDescriptor<'a> {
+    /// Debug label of the device.
+    pub label: wgpu::Label<'a>,
+    /// Backends the instance may use.
+    pub backends: wgpu::Backends,
+    /// Power preference for the adapter.
+    pub power_preference: wgpu::PowerPreference,
+    /// Features the device must support.
+    pub features: wgpu::Features,
+    /// Limits the device must support.
+    pub limits: wgpu::Limits,
+}
+
+impl Default for GpuContextDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: None,
+            backends: wgpu::Backends::all(),
+            power_preference: wgpu::PowerPreference::default(),
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+        }
+    }
+}
+
+/// Error returned when creating a [`GpuContext`].
+#[derive(Debug)]
+pub enum ContextError {
+    /// No adapter matching the requirements was found.
+    NoAdapter,
+    /// The adapter couldn't provide a device.
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The adapter supports no format for the surface, e.g. because it can't present to it.
+    NoSurfaceFormat,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAdapter => write!(f, "no suitable adapter found"),
+            Self::RequestDevice(e) => write!(f, "failed to request device: {}", e),
+            Self::NoSurfaceFormat => write!(f, "adapter supports no format for the surface"),
+        }
+    }
+}
+
+impl std::error::Error for ContextError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NoAdapter | Self::NoSurfaceFormat => None,
+            Self::RequestDevice(e) => Some(e),
+        }
+    }
+}
+
+/// Everything needed to talk to a GPU.
+#[derive(Debug)]
+pub struct GpuContext {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl GpuContext {
+    /// Creates a new instance and requests an adapter and device from it.
+    pub async fn new(descriptor: &GpuContextDescriptor<'_>) -> Result<Self, ContextError> {
+        let instance = wgpu::Instance::new(descriptor.backends);
+        Self::with_instance(instance, descriptor, None).await
+    }
+
+    /// Requests an adapter and device from an existing `instance`.
+    ///
+    /// If `compatible_surface` is given, the adapter is guaranteed to be able to present to it.
+    pub async fn with_instance(
+        instance: wgpu::Instance,
+        descriptor: &GpuContextDescriptor<'_>,
+        compatible_surface: Option<&wgpu::Surface>,
+    ) -> Result<Self, ContextError> {
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: descriptor.power_preference,
+                force_fallback_adapter: false,
+                compatible_surface,
+            })
+            .await
+            .ok_or(ContextError::NoAdapter)?;
+
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: descriptor.label,
+                    features: descriptor.features,
+                    limits: descriptor.limits.clone(),
+                },
+                None,
+            )
+            .await
+            .map_err(ContextError::RequestDevice)?;
+
+        Ok(Self {
+            instance,
+            adapter,
+            device,
+            queue,
+        })
+    }
+}
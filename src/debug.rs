@@ -0,0 +1,494 @@
+//! Debugging shaders from the CPU.
+
+use std::{collections::HashMap, fmt, num::NonZeroU64};
+
+use crate::shader::ShaderComposer;
+
+const RECORD_SIZE: wgpu::BufferAddress = 32;
+const HEADER_SIZE: wgpu::BufferAddress = 16;
+
+/// Name of the snippet registered by [`ShaderDebugChannel::register`].
+pub const DEBUG_SNIPPET: &str = "wgpu_util::debug";
+
+const DEBUG_WGSL: &str = r#"
+struct WgpuUtilDebugRecord {
+    tag: u32,
+    kind: u32,
+    len: u32,
+    _padding: u32,
+    values: vec4<u32>,
+};
+
+struct WgpuUtilDebugChannel {
+    count: atomic<u32>,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+    records: array<WgpuUtilDebugRecord>,
+};
+
+@group({group}) @binding({binding})
+var<storage, read_write> wgpu_util_debug: WgpuUtilDebugChannel;
+
+fn debug_write(tag: u32, kind: u32, len: u32, values: vec4<u32>) {
+    let index = atomicAdd(&wgpu_util_debug.count, 1u);
+    if (index < arrayLength(&wgpu_util_debug.records)) {
+        wgpu_util_debug.records[index] = WgpuUtilDebugRecord(tag, kind, len, 0u, values);
+    }
+}
+
+fn debug_f32(tag: u32, v: f32) {
+    debug_write(tag, 0u, 1u, vec4<u32>(bitcast<u32>(v), 0u, 0u, 0u));
+}
+fn debug_vec2f(tag: u32, v: vec2<f32>) {
+    debug_write(tag, 0u, 2u, vec4<u32>(bitcast<vec2<u32>>(v), 0u, 0u));
+}
+fn debug_vec3f(tag: u32, v: vec3<f32>) {
+    debug_write(tag, 0u, 3u, vec4<u32>(bitcast<vec3<u32>>(v), 0u));
+}
+fn debug_vec4f(tag: u32, v: vec4<f32>) {
+    debug_write(tag, 0u, 4u, bitcast<vec4<u32>>(v));
+}
+fn debug_u32(tag: u32, v: u32) {
+    debug_write(tag, 1u, 1u, vec4<u32>(v, 0u, 0u, 0u));
+}
+fn debug_vec2u(tag: u32, v: vec2<u32>) {
+    debug_write(tag, 1u, 2u, vec4<u32>(v, 0u, 0u));
+}
+fn debug_vec3u(tag: u32, v: vec3<u32>) {
+    debug_write(tag, 1u, 3u, vec4<u32>(v, 0u));
+}
+fn debug_vec4u(tag: u32, v: vec4<u32>) {
+    debug_write(tag, 1u, 4u, v);
+}
+fn debug_i32(tag: u32, v: i32) {
+    debug_write(tag, 2u, 1u, vec4<u32>(bitcast<u32>(v), 0u, 0u, 0u));
+}
+fn debug_vec2i(tag: u32, v: vec2<i32>) {
+    debug_write(tag, 2u, 2u, vec4<u32>(bitcast<vec2<u32>>(v), 0u, 0u));
+}
+fn debug_vec3i(tag: u32, v: vec3<i32>) {
+    debug_write(tag, 2u, 3u, vec4<u32>(bitcast<vec3<u32>>(v), 0u));
+}
+fn debug_vec4i(tag: u32, v: vec4<i32>) {
+    debug_write(tag, 2u, 4u, bitcast<vec4<u32>>(v));
+}
+"#;
+
+/// Values of a [`DebugRecord`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum DebugValues {
+    F32(Vec<f32>),
+    U32(Vec<u32>),
+    I32(Vec<i32>),
+}
+
+impl fmt::Display for DebugValues {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T: fmt::Debug>(f: &mut fmt::Formatter<'_>, values: &[T]) -> fmt::Result {
+            match values {
+                [value] => write!(f, "{:?}", value),
+                _ => {
+                    write!(f, "(")?;
+                    for (i, value) in values.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ", ")?;
+                        }
+                        write!(f, "{:?}", value)?;
+                    }
+                    write!(f, ")")
+                }
+            }
+        }
+        match self {
+            Self::F32(values) => list(f, values),
+            Self::U32(values) => list(f, values),
+            Self::I32(values) => list(f, values),
+        }
+    }
+}
+
+/// A single value written by a shader through a [`ShaderDebugChannel`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct DebugRecord {
+    /// Tag passed to the `debug_*` function, identifying the call site.
+    pub tag: u32,
+    pub values: DebugValues,
+}
+
+/// Printf debugging for WGSL.
+///
+/// Register the channel with a [`ShaderComposer`] and `#include "wgpu_util::debug"` to get
+/// `debug_f32(tag, value)`, `debug_vec3u(tag, value)`, etc. for `f32`, `u32` and `i32` scalars and
+/// vectors. Records are appended to a storage buffer and decoded on the CPU, formatted with the
+/// format string registered for their tag.
+#[derive(Debug)]
+pub struct ShaderDebugChannel {
+    buffer: wgpu::Buffer,
+    capacity: u32,
+    group: u32,
+    binding: u32,
+
+    formats: HashMap<u32, String>,
+}
+
+impl ShaderDebugChannel {
+    /// Creates a channel holding up to `capacity` records per readback, bound at `group` and
+    /// `binding` in shaders.
+    pub fn new(device: &wgpu::Device, capacity: u32, group: u32, binding: u32) -> Self {
+        let buffer = create_record_buffer(device, "shader debug channel", capacity);
+
+        Self {
+            buffer,
+            capacity: capacity.max(1),
+            group,
+            binding,
+
+            formats: HashMap::new(),
+        }
+    }
+
+    /// WGSL source of the snippet for this channel's group and binding.
+    pub fn snippet(&self) -> String {
+        DEBUG_WGSL
+            .replace("{group}", &self.group.to_string())
+            .replace("{binding}", &self.binding.to_string())
+    }
+
+    /// Registers the snippet as [`DEBUG_SNIPPET`].
+    pub fn register(&self, composer: &mut ShaderComposer) {
+        composer.add_snippet(DEBUG_SNIPPET, self.snippet());
+    }
+
+    /// Layout entry for binding the channel in compute and fragment shaders.
+    pub fn bind_group_layout_entry(&self) -> wgpu::BindGroupLayoutEntry {
+        record_layout_entry(self.binding)
+    }
+
+    /// Bind group entry for the channel.
+    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    /// Sets the format string for records with `tag`. `{}` is replaced by the values.
+    pub fn set_format(&mut self, tag: u32, format: impl Into<String>) {
+        self.formats.insert(tag, format.into());
+    }
+
+    /// Discards all records using [`wgpu::Queue`].
+    pub fn clear(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, &[0; HEADER_SIZE as usize]);
+    }
+
+    /// Reads back the records written since the last [`Self::clear`].
+    ///
+    /// Blocks until the data is available. Returns the records and the number of records which
+    /// didn't fit into the channel.
+    pub fn read(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(Vec<DebugRecord>, u32), wgpu::BufferAsyncError> {
+        let (records, overflow) = read_records(device, queue, &self.buffer, self.capacity)?;
+        let records = records
+            .into_iter()
+            .map(|(tag, values)| DebugRecord { tag, values })
+            .collect();
+        Ok((records, overflow))
+    }
+
+    /// Formats `record` with the format string registered for its tag.
+    ///
+    /// Records without format are printed as `[tag] values`.
+    pub fn format(&self, record: &DebugRecord) -> String {
+        match self.formats.get(&record.tag) {
+            Some(format) => format.replacen("{}", &record.values.to_string(), 1),
+            None => format!("[{}] {}", record.tag, record.values),
+        }
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+/// Name of the snippet registered by [`GpuAssert::register`].
+pub const ASSERT_SNIPPET: &str = "wgpu_util::assert";
+
+const ASSERT_WGSL: &str = r#"
+struct WgpuUtilAssertRecord {
+    code: u32,
+    kind: u32,
+    len: u32,
+    _padding: u32,
+    values: vec4<u32>,
+};
+
+struct WgpuUtilAssertBuffer {
+    count: atomic<u32>,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+    records: array<WgpuUtilAssertRecord>,
+};
+
+@group({group}) @binding({binding})
+var<storage, read_write> wgpu_util_assert: WgpuUtilAssertBuffer;
+
+fn gpu_assert_write(code: u32, kind: u32, len: u32, values: vec4<u32>) {
+    let index = atomicAdd(&wgpu_util_assert.count, 1u);
+    if (index < arrayLength(&wgpu_util_assert.records)) {
+        wgpu_util_assert.records[index] = WgpuUtilAssertRecord(code, kind, len, 0u, values);
+    }
+}
+
+fn gpu_assert(condition: bool, code: u32) {
+    if (!condition) {
+        gpu_assert_write(code, 1u, 0u, vec4<u32>(0u));
+    }
+}
+fn gpu_assert_f32(condition: bool, code: u32, v: f32) {
+    if (!condition) {
+        gpu_assert_write(code, 0u, 1u, vec4<u32>(bitcast<u32>(v), 0u, 0u, 0u));
+    }
+}
+fn gpu_assert_vec4f(condition: bool, code: u32, v: vec4<f32>) {
+    if (!condition) {
+        gpu_assert_write(code, 0u, 4u, bitcast<vec4<u32>>(v));
+    }
+}
+fn gpu_assert_u32(condition: bool, code: u32, v: u32) {
+    if (!condition) {
+        gpu_assert_write(code, 1u, 1u, vec4<u32>(v, 0u, 0u, 0u));
+    }
+}
+fn gpu_assert_vec4u(condition: bool, code: u32, v: vec4<u32>) {
+    if (!condition) {
+        gpu_assert_write(code, 1u, 4u, v);
+    }
+}
+fn gpu_assert_i32(condition: bool, code: u32, v: i32) {
+    if (!condition) {
+        gpu_assert_write(code, 2u, 1u, vec4<u32>(bitcast<u32>(v), 0u, 0u, 0u));
+    }
+}
+fn gpu_assert_vec4i(condition: bool, code: u32, v: vec4<i32>) {
+    if (!condition) {
+        gpu_assert_write(code, 2u, 4u, bitcast<vec4<u32>>(v));
+    }
+}
+"#;
+
+/// A failed assertion read back by [`GpuAssert`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssertionFailure {
+    /// Code passed to the `gpu_assert*` function, identifying the assertion.
+    pub code: u32,
+    /// Values passed along, empty for `gpu_assert`.
+    pub values: DebugValues,
+}
+
+/// GPU-side assertions for WGSL.
+///
+/// Register with a [`ShaderComposer`] and `#include "wgpu_util::assert"` to get
+/// `gpu_assert(condition, code)` and `gpu_assert_f32(condition, code, value)`, etc. for `f32`,
+/// `u32` and `i32` scalars and `vec4`s. Failures are appended to a storage buffer which
+/// [`Self::verify`] checks after submission, panicking with the message registered for the code.
+#[derive(Debug)]
+pub struct GpuAssert {
+    buffer: wgpu::Buffer,
+    capacity: u32,
+    group: u32,
+    binding: u32,
+
+    messages: HashMap<u32, String>,
+}
+
+impl GpuAssert {
+    /// Creates an assertion buffer holding up to `capacity` failures, bound at `group` and
+    /// `binding` in shaders.
+    pub fn new(device: &wgpu::Device, capacity: u32, group: u32, binding: u32) -> Self {
+        Self {
+            buffer: create_record_buffer(device, "gpu assert", capacity),
+            capacity: capacity.max(1),
+            group,
+            binding,
+
+            messages: HashMap::new(),
+        }
+    }
+
+    /// WGSL source of the snippet for this buffer's group and binding.
+    pub fn snippet(&self) -> String {
+        ASSERT_WGSL
+            .replace("{group}", &self.group.to_string())
+            .replace("{binding}", &self.binding.to_string())
+    }
+
+    /// Registers the snippet as [`ASSERT_SNIPPET`].
+    pub fn register(&self, composer: &mut ShaderComposer) {
+        composer.add_snippet(ASSERT_SNIPPET, self.snippet());
+    }
+
+    /// Layout entry for binding the buffer in compute and fragment shaders.
+    pub fn bind_group_layout_entry(&self) -> wgpu::BindGroupLayoutEntry {
+        record_layout_entry(self.binding)
+    }
+
+    /// Bind group entry for the buffer.
+    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    /// Sets the message for failures with `code`. `{}` is replaced by the values.
+    pub fn set_message(&mut self, code: u32, message: impl Into<String>) {
+        self.messages.insert(code, message.into());
+    }
+
+    /// Discards all failures using [`wgpu::Queue`].
+    pub fn clear(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.buffer, 0, &[0; HEADER_SIZE as usize]);
+    }
+
+    /// Reads back the failures recorded since the last [`Self::clear`].
+    ///
+    /// Blocks until the data is available. Returns the failures and the number of failures which
+    /// didn't fit into the buffer.
+    pub fn check(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<(Vec<AssertionFailure>, u32), wgpu::BufferAsyncError> {
+        let (records, overflow) = read_records(device, queue, &self.buffer, self.capacity)?;
+        let failures = records
+            .into_iter()
+            .map(|(code, values)| AssertionFailure { code, values })
+            .collect();
+        Ok((failures, overflow))
+    }
+
+    /// Checks for failures and clears the buffer. Call after submitting the work using the
+    /// assertions.
+    ///
+    /// Only does something in debug builds.
+    ///
+    /// # Panics
+    ///
+    /// If any assertion failed, with the decoded messages of all failures.
+    pub fn verify(&self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !cfg!(debug_assertions) {
+            return;
+        }
+
+        let (failures, overflow) = self
+            .check(device, queue)
+            .expect("failed to read back gpu assertions");
+        self.clear(queue);
+        if failures.is_empty() {
+            return;
+        }
+
+        let mut message = format!(
+            "{} gpu assertion(s) failed:",
+            failures.len() as u32 + overflow
+        );
+        for failure in &failures {
+            message.push_str("\n  ");
+            message.push_str(&self.format(failure));
+        }
+        if overflow > 0 {
+            message.push_str(&format!("\n  ... and {} more", overflow));
+        }
+        panic!("{}", message);
+    }
+
+    /// Formats `failure` with the message registered for its code.
+    ///
+    /// Failures without message are printed as `assertion {code} failed: values`.
+    pub fn format(&self, failure: &AssertionFailure) -> String {
+        let values = match &failure.values {
+            DebugValues::U32(values) if values.is_empty() => String::new(),
+            values => values.to_string(),
+        };
+        match self.messages.get(&failure.code) {
+            Some(message) => message.replacen("{}", &values, 1),
+            None if values.is_empty() => format!("assertion {} failed", failure.code),
+            None => format!("assertion {} failed: {}", failure.code, values),
+        }
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+fn create_record_buffer(device: &wgpu::Device, label: &str, capacity: u32) -> wgpu::Buffer {
+    crate::resource_log::create_buffer(
+        device,
+        &wgpu::BufferDescriptor {
+            label: Some(label),
+            size: HEADER_SIZE + capacity.max(1) as wgpu::BufferAddress * RECORD_SIZE,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        },
+    )
+}
+
+fn record_layout_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: false },
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(HEADER_SIZE + RECORD_SIZE),
+        },
+        count: None,
+    }
+}
+
+/// Reads the records of a debug or assert buffer as `(tag, values)` and the overflow count.
+fn read_records(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    capacity: u32,
+) -> Result<(Vec<(u32, DebugValues)>, u32), wgpu::BufferAsyncError> {
+    let header = crate::readback::read_buffer(device, queue, buffer, 0..4)?;
+    let count = u32::from_le_bytes(header[..4].try_into().unwrap());
+    let stored = count.min(capacity);
+    let bytes = crate::readback::read_buffer(
+        device,
+        queue,
+        buffer,
+        HEADER_SIZE..HEADER_SIZE + stored as wgpu::BufferAddress * RECORD_SIZE,
+    )?;
+
+    let records = bytes
+        .chunks_exact(RECORD_SIZE as usize)
+        .map(|record| {
+            let words: Vec<u32> = record
+                .chunks_exact(4)
+                .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+                .collect();
+            let values = &words[4..4 + (words[2] as usize).min(4)];
+            let values = match words[1] {
+                0 => DebugValues::F32(values.iter().map(|v| f32::from_bits(*v)).collect()),
+                2 => DebugValues::I32(values.iter().map(|v| *v as i32).collect()),
+                _ => DebugValues::U32(values.to_vec()),
+            };
+            (words[0], values)
+        })
+        .collect();
+    Ok((records, count - stored))
+}
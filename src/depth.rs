@@ -0,0 +1,42 @@
+//! [`wgpu::DepthStencilState`] presets named by intent. Stencil is disabled and depth bias is
+//! zero; override the fields for anything else.
+
+fn depth(
+    format: wgpu::TextureFormat,
+    depth_write_enabled: bool,
+    depth_compare: wgpu::CompareFunction,
+) -> wgpu::DepthStencilState {
+    wgpu::DepthStencilState {
+        format,
+        depth_write_enabled,
+        depth_compare,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+    }
+}
+
+/// Tests and writes depth, keeping nearer fragments. For depth cleared to `1.0`.
+pub fn read_write_less(format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+    depth(format, true, wgpu::CompareFunction::Less)
+}
+
+/// Tests and writes depth with reversed Z, keeping nearer fragments. For depth cleared to `0.0`.
+pub fn read_write_greater(format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+    depth(format, true, wgpu::CompareFunction::Greater)
+}
+
+/// Tests depth without writing it, e.g. for transparent geometry drawn after opaque geometry.
+pub fn read_only_less_equal(format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+    depth(format, false, wgpu::CompareFunction::LessEqual)
+}
+
+/// Only passes fragments matching the depth already written, e.g. by a depth prepass, so each
+/// pixel is shaded once.
+pub fn read_only_equal(format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+    depth(format, false, wgpu::CompareFunction::Equal)
+}
+
+/// Writes depth of every fragment without testing it.
+pub fn write_only(format: wgpu::TextureFormat) -> wgpu::DepthStencilState {
+    depth(format, true, wgpu::CompareFunction::Always)
+}
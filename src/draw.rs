@@ -0,0 +1,263 @@
+//! Sorting and batching draw calls on the CPU.
+
+use std::fmt;
+
+const DEPTH_BITS: u32 = 24;
+const MATERIAL_BITS: u32 = 16;
+const PIPELINE_BITS: u32 = 16;
+const LAYER_BITS: u32 = 8;
+
+const DEPTH_SHIFT: u32 = 0;
+const MATERIAL_SHIFT: u32 = DEPTH_SHIFT + DEPTH_BITS;
+const PIPELINE_SHIFT: u32 = MATERIAL_SHIFT + MATERIAL_BITS;
+const LAYER_SHIFT: u32 = PIPELINE_SHIFT + PIPELINE_BITS;
+
+const DEPTH_MAX: u32 = (1 << DEPTH_BITS) - 1;
+
+/// A 64 bit sort key of a draw call.
+///
+/// From the most to the least significant bits, it packs an 8 bit layer, a 16 bit pipeline id,
+/// a 16 bit material id and a 24 bit depth, so sorting by key orders draws by layer first and
+/// then minimizes pipeline and material changes. Keys are built by chaining the `with_*`
+/// methods on [`DrawKey::default`].
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DrawKey(u64);
+
+impl DrawKey {
+    /// Key from its packed bits.
+    pub const fn from_bits(bits: u64) -> Self {
+        Self(bits)
+    }
+
+    pub const fn bits(self) -> u64 {
+        self.0
+    }
+
+    /// Sets the layer, e.g. opaque, transparent or overlay.
+    pub const fn with_layer(self, layer: u8) -> Self {
+        self.with_field(LAYER_SHIFT, LAYER_BITS, layer as u64)
+    }
+
+    pub const fn with_pipeline(self, pipeline: u16) -> Self {
+        self.with_field(PIPELINE_SHIFT, PIPELINE_BITS, pipeline as u64)
+    }
+
+    pub const fn with_material(self, material: u16) -> Self {
+        self.with_field(MATERIAL_SHIFT, MATERIAL_BITS, material as u64)
+    }
+
+    /// Sets the depth, quantized to 24 bits. `depth` is clamped to `0.0..=1.0`, smaller depths
+    /// sort first, i.e. front to back.
+    pub fn with_depth(self, depth: f32) -> Self {
+        let depth = (depth.clamp(0.0, 1.0) * DEPTH_MAX as f32).round() as u64;
+        self.with_field(DEPTH_SHIFT, DEPTH_BITS, depth)
+    }
+
+    /// [`Self::with_depth`] but sorting back to front, e.g. for blended draws.
+    pub fn with_depth_back_to_front(self, depth: f32) -> Self {
+        self.with_depth(1.0 - depth.clamp(0.0, 1.0))
+    }
+
+    pub const fn layer(self) -> u8 {
+        self.field(LAYER_SHIFT, LAYER_BITS) as u8
+    }
+
+    pub const fn pipeline(self) -> u16 {
+        self.field(PIPELINE_SHIFT, PIPELINE_BITS) as u16
+    }
+
+    pub const fn material(self) -> u16 {
+        self.field(MATERIAL_SHIFT, MATERIAL_BITS) as u16
+    }
+
+    /// The quantized depth as stored in the key.
+    pub const fn depth_bits(self) -> u32 {
+        self.field(DEPTH_SHIFT, DEPTH_BITS) as u32
+    }
+
+    const fn with_field(self, shift: u32, bits: u32, value: u64) -> Self {
+        let mask = ((1 << bits) - 1) << shift;
+        Self((self.0 & !mask) | ((value << shift) & mask))
+    }
+
+    const fn field(self, shift: u32, bits: u32) -> u64 {
+        (self.0 >> shift) & ((1 << bits) - 1)
+    }
+}
+
+impl fmt::Debug for DrawKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrawKey")
+            .field("layer", &self.layer())
+            .field("pipeline", &self.pipeline())
+            .field("material", &self.material())
+            .field("depth", &self.depth_bits())
+            .finish()
+    }
+}
+
+/// Consecutive draws of a sorted [`DrawList`] sharing pipeline and material.
+#[derive(Clone, Copy, Debug)]
+pub struct DrawBatch<'a, T> {
+    pub pipeline: u16,
+    pub material: u16,
+    pub draws: &'a [(DrawKey, T)],
+}
+
+impl<'a, T> DrawBatch<'a, T> {
+    /// The draws without their keys.
+    pub fn items(&self) -> impl Iterator<Item = &'a T> + 'a {
+        self.draws.iter().map(|(_, item)| item)
+    }
+}
+
+/// Draws collected during a frame, sorted by [`DrawKey`] and iterated in batches.
+///
+/// Pipeline and material only need to be set once per [`DrawBatch`].
+#[derive(Clone, Debug)]
+pub struct DrawList<T> {
+    draws: Vec<(DrawKey, T)>,
+    sorted: bool,
+}
+
+impl<T> Default for DrawList<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> DrawList<T> {
+    pub fn new() -> Self {
+        Self {
+            draws: Vec::new(),
+            sorted: true,
+        }
+    }
+
+    pub fn push(&mut self, key: DrawKey, item: T) {
+        self.sorted = false;
+        self.draws.push((key, item));
+    }
+
+    /// Sorts the draws by key. Draws with equal keys keep their order.
+    pub fn sort(&mut self) {
+        if !self.sorted {
+            self.draws.sort_by_key(|(key, _)| *key);
+            self.sorted = true;
+        }
+    }
+
+    /// Sorts the draws and iterates over batches of consecutive draws sharing pipeline and
+    /// material.
+    pub fn batches(&mut self) -> impl Iterator<Item = DrawBatch<'_, T>> {
+        self.sort();
+        self.draws
+            .chunk_by(|(a, _), (b, _)| a.pipeline() == b.pipeline() && a.material() == b.material())
+            .map(|draws| DrawBatch {
+                pipeline: draws[0].0.pipeline(),
+                material: draws[0].0.material(),
+                draws,
+            })
+    }
+
+    /// All draws, sorted if [`Self::sort`] was called since the last push.
+    pub fn draws(&self) -> &[(DrawKey, T)] {
+        &self.draws
+    }
+
+    /// Removes all draws, keeping the allocation for the next frame.
+    pub fn clear(&mut self) {
+        self.draws.clear();
+        self.sorted = true;
+    }
+
+    pub fn len(&self) -> usize {
+        self.draws.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_round_trip() {
+        let key = DrawKey::default()
+            .with_layer(0xab)
+            .with_pipeline(0xcdef)
+            .with_material(0x1234)
+            .with_depth(1.0);
+        assert_eq!(key.layer(), 0xab);
+        assert_eq!(key.pipeline(), 0xcdef);
+        assert_eq!(key.material(), 0x1234);
+        assert_eq!(key.depth_bits(), DEPTH_MAX);
+        assert_eq!(DrawKey::from_bits(key.bits()), key);
+
+        // Setting a field again replaces it without touching the others.
+        let key = key.with_pipeline(7).with_depth(0.0);
+        assert_eq!(
+            (
+                key.layer(),
+                key.pipeline(),
+                key.material(),
+                key.depth_bits()
+            ),
+            (0xab, 7, 0x1234, 0)
+        );
+        assert_eq!(DrawKey::default().with_depth(2.0).depth_bits(), DEPTH_MAX);
+        assert_eq!(DrawKey::default().with_depth(-1.0).depth_bits(), 0);
+    }
+
+    #[test]
+    fn keys_order_by_layer_pipeline_material_depth() {
+        let key = |layer, pipeline, material, depth| {
+            DrawKey::default()
+                .with_layer(layer)
+                .with_pipeline(pipeline)
+                .with_material(material)
+                .with_depth(depth)
+        };
+        assert!(key(0, u16::MAX, u16::MAX, 1.0) < key(1, 0, 0, 0.0));
+        assert!(key(0, 0, u16::MAX, 1.0) < key(0, 1, 0, 0.0));
+        assert!(key(0, 0, 0, 1.0) < key(0, 0, 1, 0.0));
+        assert!(key(0, 0, 0, 0.25) < key(0, 0, 0, 0.5));
+
+        let back_to_front = |depth| DrawKey::default().with_depth_back_to_front(depth);
+        assert!(back_to_front(0.5) < back_to_front(0.25));
+    }
+
+    #[test]
+    fn batches_group_pipeline_and_material_in_key_order() {
+        let mut list = DrawList::new();
+        let key = |pipeline, material| {
+            DrawKey::default()
+                .with_pipeline(pipeline)
+                .with_material(material)
+        };
+        for (i, (pipeline, material)) in [(1, 0), (0, 1), (1, 0), (0, 0), (0, 1)]
+            .into_iter()
+            .enumerate()
+        {
+            list.push(key(pipeline, material), i);
+        }
+
+        let batches: Vec<(u16, u16, Vec<usize>)> = list
+            .batches()
+            .map(|batch| {
+                (
+                    batch.pipeline,
+                    batch.material,
+                    batch.items().copied().collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            batches,
+            [(0, 0, vec![3]), (0, 1, vec![1, 4]), (1, 0, vec![0, 2])]
+        );
+    }
+}
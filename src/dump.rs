@@ -0,0 +1,274 @@
+//! Capturing presented frames without stalling rendering.
+
+#[cfg(feature = "png")]
+use std::path::PathBuf;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, SyncSender, TryRecvError, TrySendError},
+        Arc,
+    },
+    thread::JoinHandle,
+};
+
+use crate::readback::{copy_texture_to_staging, unpad_rows, TextureInfo};
+
+/// A frame read back by [`FrameDumper`].
+#[derive(Clone, Debug)]
+pub struct CapturedFrame {
+    /// Index of the frame among all frames passed to [`FrameDumper::capture`].
+    pub index: u64,
+    pub info: TextureInfo,
+    /// Tightly packed texels, row by row from top to bottom.
+    pub texels: Vec<u8>,
+}
+
+/// Where captured frames go. Sinks run on a worker thread.
+pub enum FrameSink {
+    /// Write numbered PNG files `{prefix}{index:06}.png` into `directory`.
+    #[cfg(feature = "png")]
+    Files {
+        directory: PathBuf,
+        prefix: String,
+        options: crate::screenshot::SaveOptions,
+    },
+    /// Call a user callback.
+    Callback(Box<dyn FnMut(CapturedFrame) + Send>),
+}
+
+impl std::fmt::Debug for FrameSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            #[cfg(feature = "png")]
+            Self::Files {
+                directory, prefix, ..
+            } => f
+                .debug_struct("Files")
+                .field("directory", directory)
+                .field("prefix", prefix)
+                .finish(),
+            Self::Callback(_) => f.write_str("Callback"),
+        }
+    }
+}
+
+/// Descriptor for [`FrameDumper`].
+#[derive(Debug)]
+pub struct FrameDumperDescriptor {
+    /// Capture every `interval`th frame. Must be at least 1.
+    pub interval: u32,
+    /// Number of read back frames which may wait for the sink. Further frames are dropped.
+    pub capacity: usize,
+    pub sink: FrameSink,
+}
+
+#[derive(Debug)]
+struct PendingCapture {
+    index: u64,
+    info: TextureInfo,
+    staging: wgpu::Buffer,
+    mapped: Option<Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// Captures every Nth frame via readback and hands it to a [`FrameSink`] on a worker thread.
+///
+/// Readback is asynchronous: [`Self::capture`] records a copy into the frame's encoder and
+/// [`Self::after_submit`] forwards finished copies, so the render thread never waits for the GPU.
+/// If the sink can't keep up, frames are dropped instead.
+#[derive(Debug)]
+pub struct FrameDumper {
+    interval: u64,
+    frame: u64,
+    dropped: u64,
+    /// Incremented by the worker thread.
+    failed: Arc<AtomicU64>,
+
+    pending: Vec<PendingCapture>,
+    sender: Option<SyncSender<CapturedFrame>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FrameDumper {
+    pub fn new(descriptor: FrameDumperDescriptor) -> Self {
+        assert!(descriptor.interval > 0, "interval must be at least 1");
+
+        let (sender, receiver) = mpsc::sync_channel(descriptor.capacity);
+        let mut sink = descriptor.sink;
+        let failed = Arc::new(AtomicU64::new(0));
+        let worker_failed = failed.clone();
+        let worker = std::thread::Builder::new()
+            .name("wgpu-util frame dumper".into())
+            .spawn(move || {
+                for frame in receiver {
+                    if !run_sink(&mut sink, frame) {
+                        worker_failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            })
+            .expect("failed to spawn frame dumper thread");
+
+        Self {
+            interval: descriptor.interval as u64,
+            frame: 0,
+            dropped: 0,
+            failed,
+
+            pending: Vec::new(),
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    /// Must be called once per frame with the texture about to be presented, before `encoder`
+    /// is submitted. Records a copy if this frame should be captured.
+    ///
+    /// `texture` must have [`wgpu::TextureUsages::COPY_SRC`]. Returns whether the frame is
+    /// captured.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        info: &TextureInfo,
+    ) -> bool {
+        let index = self.frame;
+        self.frame += 1;
+        if !index.is_multiple_of(self.interval) {
+            return false;
+        }
+
+        let staging = copy_texture_to_staging(device, encoder, texture, info);
+        self.pending.push(PendingCapture {
+            index,
+            info: *info,
+            staging,
+            mapped: None,
+        });
+        true
+    }
+
+    /// Must be called after the encoder passed to [`Self::capture`] got submitted.
+    ///
+    /// Starts mapping new captures and forwards finished ones to the sink. Doesn't block.
+    pub fn after_submit(&mut self, device: &wgpu::Device) {
+        for capture in &mut self.pending {
+            if capture.mapped.is_none() {
+                let (sender, receiver) = mpsc::channel();
+                capture
+                    .staging
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        let _ = sender.send(result);
+                    });
+                capture.mapped = Some(receiver);
+            }
+        }
+        device.poll(wgpu::Maintain::Poll);
+        self.forward_mapped(false);
+    }
+
+    /// Waits for all pending captures, hands them to the sink and waits until the sink is done.
+    ///
+    /// Returns the number of frames the sink failed to save, see [`Self::failed_frames`].
+    pub fn finish(mut self, device: &wgpu::Device) -> u64 {
+        self.after_submit(device);
+        device.poll(wgpu::Maintain::Wait);
+        self.forward_mapped(true);
+        self.shutdown();
+        self.failed_frames()
+    }
+
+    /// Number of captures dropped because the sink couldn't keep up or mapping failed.
+    pub fn dropped_frames(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Number of frames handed to the sink so far which it failed to save, e.g. files which
+    /// couldn't be written. Each failure is also logged as a warning.
+    pub fn failed_frames(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    fn forward_mapped(&mut self, block: bool) {
+        let mut i = 0;
+        while i < self.pending.len() {
+            let result = match &self.pending[i].mapped {
+                Some(receiver) => receiver.try_recv(),
+                None => Err(TryRecvError::Empty),
+            };
+            let mapped = match result {
+                Ok(mapped) => mapped,
+                Err(TryRecvError::Empty) => {
+                    i += 1;
+                    continue;
+                }
+                Err(TryRecvError::Disconnected) => Err(wgpu::BufferAsyncError),
+            };
+
+            let capture = self.pending.remove(i);
+            if mapped.is_err() {
+                self.dropped += 1;
+                continue;
+            }
+
+            let texels = unpad_rows(&capture.staging.slice(..).get_mapped_range(), &capture.info);
+            capture.staging.unmap();
+
+            let frame = CapturedFrame {
+                index: capture.index,
+                info: capture.info,
+                texels,
+            };
+            let sender = self.sender.as_ref().expect("sender lives until shutdown");
+            let sent = match block {
+                true => sender.send(frame).is_ok(),
+                false => !matches!(
+                    sender.try_send(frame),
+                    Err(TrySendError::Full(_) | TrySendError::Disconnected(_))
+                ),
+            };
+            if !sent {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    fn shutdown(&mut self) {
+        // Closing the channel ends the worker loop.
+        self.sender = None;
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for FrameDumper {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Returns whether the frame was saved.
+fn run_sink(sink: &mut FrameSink, frame: CapturedFrame) -> bool {
+    match sink {
+        #[cfg(feature = "png")]
+        FrameSink::Files {
+            directory,
+            prefix,
+            options,
+        } => {
+            let path = directory.join(format!("{}{:06}.png", prefix, frame.index));
+            match crate::screenshot::save_texels(&frame.texels, &frame.info, &path, options) {
+                Ok(()) => true,
+                Err(e) => {
+                    log::warn!("failed to save frame to {}: {}", path.display(), e);
+                    false
+                }
+            }
+        }
+        FrameSink::Callback(callback) => {
+            callback(frame);
+            true
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! [egui](https://github.com/emilk/egui) integration.
+//!
+//! All egui textures are packed into a single [`TextureAtlas`] and all meshes of a frame are
+//! streamed into one vertex and one index [`DynamicBuffer`].
+//!
+//! The atlas is sampled with a single linear sampler. egui 0.18 has no per-texture filter
+//! options, so nearest filtering of user images isn't supported.
+
+use std::{collections::HashMap, num::NonZeroU64};
+
+use crate::{
+    atlas::{AtlasAllocation, TextureAtlas, TextureAtlasDescriptor},
+    shader::ShaderComposer,
+    DynamicBuffer,
+};
+
+const VERTEX_SIZE: wgpu::BufferAddress = 20;
+
+/// Texels around every texture repeating its edge, so filtering doesn't blend in neighbours.
+const PADDING: u32 = 1;
+
+/// Descriptor for [`EguiRenderer`].
+#[derive(Clone, Debug)]
+pub struct EguiRendererDescriptor {
+    /// Format of the render target. Should be an sRGB format.
+    pub output_format: wgpu::TextureFormat,
+    /// Sample count of the render target.
+    pub sample_count: u32,
+    /// Width and height of the texture atlas in texels.
+    pub atlas_size: u32,
+}
+
+/// Size of the render target.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenDescriptor {
+    /// Width and height of the render target in physical pixels.
+    pub size_in_pixels: [u32; 2],
+    /// Value of [`egui::Context::pixels_per_point`].
+    pub pixels_per_point: f32,
+}
+
+impl ScreenDescriptor {
+    fn size_in_points(&self) -> [f32; 2] {
+        [
+            self.size_in_pixels[0] as f32 / self.pixels_per_point,
+            self.size_in_pixels[1] as f32 / self.pixels_per_point,
+        ]
+    }
+}
+
+#[derive(Debug)]
+struct Draw {
+    indices: std::ops::Range<u32>,
+    base_vertex: i32,
+    scissor: [u32; 4],
+}
+
+/// Renders egui output into a user provided render pass.
+#[derive(Debug)]
+pub struct EguiRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    screen_buffer: wgpu::Buffer,
+
+    atlas: TextureAtlas,
+    textures: HashMap<egui::TextureId, AtlasAllocation>,
+
+    vertex_buffer: DynamicBuffer,
+    index_buffer: DynamicBuffer,
+    draws: Vec<Draw>,
+}
+
+impl EguiRenderer {
+    pub fn new(device: &wgpu::Device, descriptor: &EguiRendererDescriptor) -> Self {
+        let shader_source = ShaderComposer::new()
+            .compose(include_str!("shaders/egui.wgsl"))
+            .expect("builtin snippets must compose");
+        let shader = crate::create_shader_module(device, Some("egui shader"), &shader_source);
+
+        let screen_buffer = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("egui screen buffer"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        let atlas = TextureAtlas::new(
+            device,
+            &TextureAtlasDescriptor {
+                label: Some("egui texture atlas"),
+                width: descriptor.atlas_size,
+                height: descriptor.atlas_size,
+                layers: 1,
+                algorithm: Default::default(),
+                clear_removed: true,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("egui sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("egui bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("egui bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(atlas.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("egui pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = crate::resource_log::create_render_pipeline(
+            device,
+            &wgpu::RenderPipelineDescriptor {
+                label: Some("egui pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: VERTEX_SIZE,
+                        step_mode: wgpu::VertexStepMode::Vertex,
+                        attributes: &wgpu::vertex_attr_array![
+                            0 => Float32x2,
+                            1 => Float32x2,
+                            2 => Unorm8x4,
+                        ],
+                    }],
+                },
+                // egui doesn't have a consistent winding order.
+                primitive: crate::primitive::triangles_no_cull(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: descriptor.sample_count,
+                    ..Default::default()
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: descriptor.output_format,
+                        blend: Some(crate::blend::PREMULTIPLIED_ALPHA),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+            },
+        );
+
+        let vertex_buffer = DynamicBuffer::new(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("egui vertex buffer"),
+                size: 0,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let index_buffer = DynamicBuffer::new(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("egui index buffer"),
+                size: 0,
+                usage: wgpu::BufferUsages::INDEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group,
+            screen_buffer,
+
+            atlas,
+            textures: HashMap::new(),
+
+            vertex_buffer,
+            index_buffer,
+            draws: Vec::new(),
+        }
+    }
+
+    /// Applies new and changed textures of `delta` and frees the removed ones.
+    ///
+    /// Must be called before [`Self::prepare`] with the delta of the same frame.
+    ///
+    /// # Panics
+    /// If a texture doesn't fit into the atlas anymore.
+    pub fn update_textures(&mut self, queue: &wgpu::Queue, delta: &egui::TexturesDelta) {
+        for (id, image_delta) in &delta.set {
+            let [width, height] = image_delta.image.size();
+            let (width, height) = (width as u32, height as u32);
+            let texels: Vec<u8> = match &image_delta.image {
+                egui::ImageData::Color(image) => {
+                    image.pixels.iter().flat_map(|c| c.to_array()).collect()
+                }
+                egui::ImageData::Font(image) => {
+                    image.srgba_pixels(1.0).flat_map(|c| c.to_array()).collect()
+                }
+            };
+
+            let (allocation, [x, y]) = match image_delta.pos {
+                Some([x, y]) => {
+                    let allocation = self
+                        .textures
+                        .get(id)
+                        .expect("partial update of an unknown egui texture");
+                    let [x, y] = [x as u32, y as u32];
+                    assert!(
+                        x + width <= allocation.width && y + height <= allocation.height,
+                        "egui texture patch must lie within the texture"
+                    );
+                    (*allocation, [x, y])
+                }
+                None => {
+                    let allocation = match self.textures.get(id) {
+                        Some(a) if a.width == width && a.height == height => *a,
+                        previous => {
+                            if let Some(previous) = previous {
+                                self.atlas.remove(&padded(previous));
+                            }
+                            self.atlas
+                                .allocate(width + 2 * PADDING, height + 2 * PADDING)
+                                .expect("egui texture doesn't fit into the atlas")
+                                .sub_region(PADDING, PADDING, width, height)
+                        }
+                    };
+                    self.textures.insert(*id, allocation);
+                    (allocation, [0, 0])
+                }
+            };
+
+            if width == 0 || height == 0 {
+                continue;
+            }
+
+            // Patches touching the border of the texture also update the gutter behind it.
+            let gutter = |touches_border: bool| match touches_border {
+                true => PADDING,
+                false => 0,
+            };
+            let gutter = [
+                gutter(x == 0),
+                gutter(y == 0),
+                gutter(x + width == allocation.width),
+                gutter(y + height == allocation.height),
+            ];
+            let region = padded(&allocation).sub_region(
+                x + PADDING - gutter[0],
+                y + PADDING - gutter[1],
+                width + gutter[0] + gutter[2],
+                height + gutter[1] + gutter[3],
+            );
+            let texels = extend_edges(&texels, width, height, gutter);
+            self.atlas.write(queue, &region, &texels);
+        }
+
+        for id in &delta.free {
+            if let Some(allocation) = self.textures.remove(id) {
+                self.atlas.remove(&padded(&allocation));
+            }
+        }
+    }
+
+    /// Uploads the tessellated `primitives` of a frame.
+    ///
+    /// Paint callbacks are not supported and ignored.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        primitives: &[egui::ClippedPrimitive],
+        screen: &ScreenDescriptor,
+    ) {
+        let [points_width, points_height] = screen.size_in_points();
+        let screen_uniform: Vec<u8> = [points_width, points_height, 0.0, 0.0]
+            .iter()
+            .flat_map(|f| f.to_le_bytes())
+            .collect();
+        queue.write_buffer(&self.screen_buffer, 0, &screen_uniform);
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        self.draws.clear();
+
+        for primitive in primitives {
+            let mesh = match &primitive.primitive {
+                egui::epaint::Primitive::Mesh(mesh) => mesh,
+                egui::epaint::Primitive::Callback(_) => continue,
+            };
+            let allocation = match self.textures.get(&mesh.texture_id) {
+                Some(allocation) => allocation,
+                None => continue,
+            };
+            let scissor = match scissor_rect(primitive.clip_rect, screen) {
+                Some(scissor) => scissor,
+                None => continue,
+            };
+
+            let [min_u, min_v, max_u, max_v] = self.atlas.uv_rect(allocation);
+            let base_vertex = (vertices.len() as wgpu::BufferAddress / VERTEX_SIZE) as i32;
+            for vertex in &mesh.vertices {
+                let u = min_u + vertex.uv.x * (max_u - min_u);
+                let v = min_v + vertex.uv.y * (max_v - min_v);
+                for f in [vertex.pos.x, vertex.pos.y, u, v] {
+                    vertices.extend_from_slice(&f.to_le_bytes());
+                }
+                vertices.extend_from_slice(&vertex.color.to_array());
+            }
+
+            let first_index = (indices.len() / 4) as u32;
+            for index in &mesh.indices {
+                indices.extend_from_slice(&index.to_le_bytes());
+            }
+
+            self.draws.push(Draw {
+                indices: first_index..first_index + mesh.indices.len() as u32,
+                base_vertex,
+                scissor,
+            });
+        }
+
+        self.vertex_buffer.upload(device, queue, &vertices);
+        self.index_buffer.upload(device, queue, &indices);
+    }
+
+    /// Records the draws of the last [`Self::prepare`] into `pass`.
+    pub fn render<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if self.draws.is_empty() {
+            return;
+        }
+
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.vertex_buffer.vertex_slice(..));
+        pass.set_index_buffer(self.index_buffer.index_slice(..), wgpu::IndexFormat::Uint32);
+
+        for draw in &self.draws {
+            let [x, y, width, height] = draw.scissor;
+            pass.set_scissor_rect(x, y, width, height);
+            pass.draw_indexed(draw.indices.clone(), draw.base_vertex, 0..1);
+        }
+    }
+
+    /// Get a reference to the texture atlas.
+    pub fn atlas(&self) -> &TextureAtlas {
+        &self.atlas
+    }
+}
+
+/// Clip rectangle in physical pixels clamped to the screen or `None` if empty.
+fn scissor_rect(clip_rect: egui::Rect, screen: &ScreenDescriptor) -> Option<[u32; 4]> {
+    let [screen_width, screen_height] = screen.size_in_pixels;
+    let ppp = screen.pixels_per_point;
+
+    let min_x = ((clip_rect.min.x * ppp).round() as u32).min(screen_width);
+    let min_y = ((clip_rect.min.y * ppp).round() as u32).min(screen_height);
+    let max_x = ((clip_rect.max.x * ppp).round() as u32).clamp(min_x, screen_width);
+    let max_y = ((clip_rect.max.y * ppp).round() as u32).clamp(min_y, screen_height);
+
+    let (width, height) = (max_x - min_x, max_y - min_y);
+    (width > 0 && height > 0).then_some([min_x, min_y, width, height])
+}
+
+/// Extends the non-empty RGBA8 `texels` by `[left, top, right, bottom]` texels repeating their edges.
+fn extend_edges(texels: &[u8], width: u32, height: u32, extent: [u32; 4]) -> Vec<u8> {
+    let [left, top, right, bottom] = extent;
+    let mut extended =
+        Vec::with_capacity(((width + left + right) * (height + top + bottom) * 4) as usize);
+    for y in 0..height + top + bottom {
+        let y = y.saturating_sub(top).min(height - 1);
+        for x in 0..width + left + right {
+            let x = x.saturating_sub(left).min(width - 1);
+            let i = ((y * width + x) * 4) as usize;
+            extended.extend_from_slice(&texels[i..i + 4]);
+        }
+    }
+    extended
+}
+
+/// The region of a texture including its padding.
+fn padded(allocation: &AtlasAllocation) -> AtlasAllocation {
+    AtlasAllocation {
+        layer: allocation.layer,
+        x: allocation.x - PADDING,
+        y: allocation.y - PADDING,
+        width: allocation.width + 2 * PADDING,
+        height: allocation.height + 2 * PADDING,
+    }
+}
@@ -0,0 +1,298 @@
+//! Simulating flocks of boids with a compute shader.
+
+use std::{num::NonZeroU64, ops::Range};
+
+use crate::{BufferInitDescriptor, DeviceExt};
+
+const WORKGROUP_SIZE: u32 = 64;
+const PARAMS_SIZE: u64 = 48;
+
+/// Size of a boid in bytes: position and velocity as `vec4<f32>`.
+pub const BOID_SIZE: wgpu::BufferAddress = 32;
+
+/// Instance attributes of the boids in [`GpuFlock::buffer`]: the position and the velocity as
+/// `Float32x4`, with `w` 1 and 0 respectively.
+pub fn boid_attributes(
+    position_location: u32,
+    velocity_location: u32,
+) -> [wgpu::VertexAttribute; 2] {
+    [
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x4,
+            offset: 0,
+            shader_location: position_location,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x4,
+            offset: 16,
+            shader_location: velocity_location,
+        },
+    ]
+}
+
+/// Tunable parameters of [`GpuFlock::update`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FlockParams {
+    /// Half extent of the cube around the origin the boids wrap around in.
+    pub bounds: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    /// Boids closer than this steer away from each other.
+    pub separation_radius: f32,
+    /// Boids closer than this match their velocities.
+    pub alignment_radius: f32,
+    /// Boids closer than this steer towards their center.
+    pub cohesion_radius: f32,
+    pub separation_weight: f32,
+    pub alignment_weight: f32,
+    pub cohesion_weight: f32,
+}
+
+impl Default for FlockParams {
+    fn default() -> Self {
+        Self {
+            bounds: 10.0,
+            min_speed: 1.0,
+            max_speed: 4.0,
+            separation_radius: 0.5,
+            alignment_radius: 1.5,
+            cohesion_radius: 1.5,
+            separation_weight: 1.0,
+            alignment_weight: 1.0,
+            cohesion_weight: 0.5,
+        }
+    }
+}
+
+/// Descriptor for [`GpuFlock::new`].
+#[derive(Clone, Debug)]
+pub struct GpuFlockDescriptor<'a> {
+    pub label: wgpu::Label<'a>,
+    pub count: u32,
+    pub params: FlockParams,
+    /// Seed of the initial positions and velocities, distributed uniformly within the bounds and
+    /// up to the maximum speed.
+    pub seed: u32,
+    /// Additional usages of the boid buffers.
+    pub usage: wgpu::BufferUsages,
+}
+
+impl Default for GpuFlockDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: None,
+            count: 1024,
+            params: FlockParams::default(),
+            seed: 0,
+            usage: wgpu::BufferUsages::empty(),
+        }
+    }
+}
+
+/// A flock of boids updated on the GPU.
+///
+/// Boids are double-buffered in two storage buffers and every update reads the current one and
+/// writes the other. Every boid visits all others, so updates are quadratic in the count. The
+/// buffers can be bound as instance buffers, see [`boid_attributes`] and [`Self::draw`].
+#[derive(Debug)]
+pub struct GpuFlock {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    buffers: [wgpu::Buffer; 2],
+    current: usize,
+    count: u32,
+    params: FlockParams,
+}
+
+impl GpuFlock {
+    pub fn new(device: &wgpu::Device, descriptor: &GpuFlockDescriptor<'_>) -> Self {
+        let source = include_str!("shaders/flock.wgsl");
+
+        let shader = crate::create_shader_module(device, Some("flock shader"), source);
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(BOID_SIZE),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("flock bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+                storage_entry(1, true),
+                storage_entry(2, false),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("flock pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::resource_log::create_compute_pipeline(
+            device,
+            &wgpu::ComputePipelineDescriptor {
+                label: Some("flock pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "update",
+            },
+        );
+
+        let params = descriptor.params;
+        let mut state = descriptor.seed;
+        let mut random = |scale: f32| {
+            state = pcg_hash(state);
+            ((state >> 8) as f32 / 16777216.0 * 2.0 - 1.0) * scale
+        };
+        let mut boids = Vec::with_capacity((descriptor.count as u64 * BOID_SIZE) as usize);
+        for _ in 0..descriptor.count {
+            let position = [(); 3].map(|_| random(params.bounds));
+            let velocity = [(); 3].map(|_| random(params.max_speed / 3f32.sqrt()));
+            for value in position
+                .into_iter()
+                .chain([1.0])
+                .chain(velocity)
+                .chain([0.0])
+            {
+                boids.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+
+        // Keep the buffers bindable even for empty flocks.
+        let size = descriptor.count.max(1) as u64 * BOID_SIZE;
+        let usage = descriptor.usage | wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX;
+        let buffers = [0, 1].map(|_| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: descriptor.label,
+                contents: &boids,
+                size: Some(size),
+                usage,
+            })
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            buffers,
+            current: 0,
+            count: descriptor.count,
+            params,
+        }
+    }
+
+    /// Records a pass advancing the boids by `dt` seconds.
+    pub fn update(&mut self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, dt: f32) {
+        if self.count == 0 {
+            return;
+        }
+
+        let p = &self.params;
+        let mut params = Vec::with_capacity(PARAMS_SIZE as usize);
+        params.extend_from_slice(&self.count.to_le_bytes());
+        for value in [
+            dt,
+            p.bounds,
+            p.min_speed,
+            p.max_speed,
+            p.separation_radius,
+            p.alignment_radius,
+            p.cohesion_radius,
+            p.separation_weight,
+            p.alignment_weight,
+            p.cohesion_weight,
+        ] {
+            params.extend_from_slice(&value.to_le_bytes());
+        }
+        params.resize(PARAMS_SIZE as usize, 0);
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("flock params buffer"),
+            contents: &params,
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let next = 1 - self.current;
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("flock bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.buffers[self.current].as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.buffers[next].as_entire_binding(),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::dispatch("flock pass")
+                .read("boids", &self.buffers[self.current])
+                .write("next boids", &self.buffers[next])
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("flock pass"),
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            crate::dispatch(
+                &mut pass,
+                "flock pass",
+                [self.count.div_ceil(WORKGROUP_SIZE), 1, 1],
+            );
+        }
+        self.current = next;
+    }
+
+    /// Binds the current boids as instance buffer at `slot` and draws `vertices` for every boid.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, slot: u32, vertices: Range<u32>) {
+        pass.set_vertex_buffer(slot, self.buffer().slice(..));
+        pass.draw(vertices, 0..self.count);
+    }
+
+    /// The buffer holding the current boids, [`BOID_SIZE`] bytes each.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffers[self.current]
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn params(&self) -> &FlockParams {
+        &self.params
+    }
+
+    pub fn set_params(&mut self, params: FlockParams) {
+        self.params = params;
+    }
+}
+
+/// The PCG hash of `wgpu_util::random`.
+fn pcg_hash(value: u32) -> u32 {
+    let state = value.wrapping_mul(747796405).wrapping_add(2891336453);
+    let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+    (word >> 22) ^ word
+}
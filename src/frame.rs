@@ -0,0 +1,148 @@
+//! Resources which exist once per frame in flight and limiting the frames in flight.
+
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+};
+
+/// A ring of `T`s, one per frame, e.g. history textures or uniform buffers written while older
+/// frames are still in flight.
+#[derive(Debug)]
+pub struct PerFrame<T> {
+    items: Vec<T>,
+    current: usize,
+    frame: u64,
+}
+
+impl<T> PerFrame<T> {
+    /// Creates `count` items with `create`, which gets the index of the item.
+    pub fn new(count: usize, create: impl FnMut(usize) -> T) -> Self {
+        assert!(count > 0, "count must be greater than 0");
+        Self {
+            items: (0..count).map(create).collect(),
+            current: 0,
+            frame: 0,
+        }
+    }
+
+    /// Moves on to the next frame, making the oldest item current.
+    pub fn advance(&mut self) {
+        self.current = (self.current + 1) % self.items.len();
+        self.frame += 1;
+    }
+
+    /// Item of the current frame.
+    pub fn current(&self) -> &T {
+        &self.items[self.current]
+    }
+
+    /// Mutable item of the current frame.
+    pub fn current_mut(&mut self) -> &mut T {
+        &mut self.items[self.current]
+    }
+
+    /// Item of the previous frame. Same as [`Self::current`] if there is a single item.
+    pub fn previous(&self) -> &T {
+        self.ago(1)
+    }
+
+    /// Item of the frame `frames` ago, wrapping around after [`Self::len`] frames.
+    pub fn ago(&self, frames: usize) -> &T {
+        let len = self.items.len();
+        &self.items[(self.current + len - frames % len) % len]
+    }
+
+    /// Number of [`Self::advance`] calls.
+    pub fn frame_index(&self) -> u64 {
+        self.frame
+    }
+
+    /// Number of items.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// Always `false`, there is at least one item.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Iterates over all items, starting with the current one.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let (older, newer) = self.items.split_at(self.current);
+        newer.iter().chain(older)
+    }
+
+    /// Mutably iterates over all items, e.g. to recreate them after a resize.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.items.iter_mut()
+    }
+}
+
+/// Limits the number of frames the CPU may record ahead of the GPU, trading throughput for input
+/// latency.
+///
+/// Call [`Self::begin_frame`] before reading input for a frame and [`Self::end_frame`] after
+/// its last submission. With a limit of 1, input is read once the GPU finished the previous
+/// frame, 2 and 3 let the CPU work ahead for smoother frame rates.
+#[derive(Debug)]
+pub struct GpuThrottle {
+    max_frames: usize,
+    /// Last submissions of the frames which may still be in flight, oldest first.
+    submissions: VecDeque<wgpu::SubmissionIndex>,
+    frames_in_flight: Arc<AtomicU32>,
+}
+
+impl GpuThrottle {
+    /// Creates a throttle allowing `max_frames` frames in flight, clamped to 1 to 3.
+    pub fn new(max_frames: usize) -> Self {
+        Self {
+            max_frames: max_frames.clamp(1, 3),
+            submissions: VecDeque::new(),
+            frames_in_flight: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// Changes the number of frames in flight, clamped to 1 to 3. Applies from the next
+    /// [`Self::begin_frame`].
+    pub fn set_max_frames(&mut self, max_frames: usize) {
+        self.max_frames = max_frames.clamp(1, 3);
+    }
+
+    pub fn max_frames(&self) -> usize {
+        self.max_frames
+    }
+
+    /// Blocks until fewer than [`Self::max_frames`] frames are in flight.
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        device.poll(wgpu::Maintain::Poll);
+        // Frames finish in order, so only the newest submissions can still be in flight.
+        let in_flight = self.frames_in_flight() as usize;
+        while self.submissions.len() > in_flight {
+            self.submissions.pop_front();
+        }
+        while self.submissions.len() >= self.max_frames {
+            let submission = self.submissions.pop_front().unwrap();
+            device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+        }
+    }
+
+    /// Counts the frame ending with `submission` as in flight until the GPU finished all work
+    /// submitted so far.
+    pub fn end_frame(&mut self, queue: &wgpu::Queue, submission: wgpu::SubmissionIndex) {
+        self.submissions.push_back(submission);
+        self.frames_in_flight.fetch_add(1, Ordering::AcqRel);
+        let frames_in_flight = self.frames_in_flight.clone();
+        queue.on_submitted_work_done(move || {
+            frames_in_flight.fetch_sub(1, Ordering::AcqRel);
+        });
+    }
+
+    /// Number of ended frames the GPU hasn't finished yet, as of the last device poll.
+    pub fn frames_in_flight(&self) -> u32 {
+        self.frames_in_flight.load(Ordering::Acquire)
+    }
+}
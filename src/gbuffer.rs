@@ -0,0 +1,261 @@
+//! G-buffers of deferred renderers.
+
+/// Descriptor for [`GBuffer`].
+#[derive(Clone, Debug)]
+pub struct GBufferDescriptor {
+    pub width: u32,
+    pub height: u32,
+    /// Base color, alpha is free for user data.
+    pub albedo_format: wgpu::TextureFormat,
+    /// World or view space normals.
+    pub normal_format: wgpu::TextureFormat,
+    /// Material parameters, e.g. roughness, metalness and occlusion.
+    pub material_format: wgpu::TextureFormat,
+    pub depth_format: wgpu::TextureFormat,
+    /// Stages of the lighting pass reading the G-buffer.
+    pub visibility: wgpu::ShaderStages,
+}
+
+impl Default for GBufferDescriptor {
+    fn default() -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            albedo_format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            normal_format: wgpu::TextureFormat::Rgba16Float,
+            material_format: wgpu::TextureFormat::Rgba8Unorm,
+            depth_format: wgpu::TextureFormat::Depth32Float,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct GBufferTarget {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+}
+
+/// Albedo, normal, material and depth attachments of a deferred renderer.
+///
+/// The geometry pass renders into [`Self::color_attachments`] and
+/// [`Self::depth_stencil_attachment`] with pipelines using [`Self::color_targets`] and
+/// [`Self::depth_stencil`]. The lighting pass reads the attachments through [`Self::bind_group`],
+/// with albedo, normal and material bound in order as non-filterable `texture_2d<f32>` and depth
+/// as `texture_depth_2d`, all read with `textureLoad`.
+#[derive(Debug)]
+pub struct GBuffer {
+    targets: GBufferTargets,
+    bind_group_layout: wgpu::BindGroupLayout,
+    descriptor: GBufferDescriptor,
+}
+
+/// Everything recreated on resize.
+#[derive(Debug)]
+struct GBufferTargets {
+    albedo: GBufferTarget,
+    normal: GBufferTarget,
+    material: GBufferTarget,
+    depth: GBufferTarget,
+    bind_group: wgpu::BindGroup,
+}
+
+impl GBuffer {
+    pub fn new(device: &wgpu::Device, descriptor: &GBufferDescriptor) -> Self {
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: descriptor.visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let color = wgpu::TextureSampleType::Float { filterable: false };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("gbuffer bind group layout"),
+            entries: &[
+                texture_entry(0, color),
+                texture_entry(1, color),
+                texture_entry(2, color),
+                texture_entry(3, wgpu::TextureSampleType::Depth),
+            ],
+        });
+
+        Self {
+            targets: Self::create_targets(device, descriptor, &bind_group_layout),
+            bind_group_layout,
+            descriptor: descriptor.clone(),
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        descriptor: &GBufferDescriptor,
+        layout: &wgpu::BindGroupLayout,
+    ) -> GBufferTargets {
+        let create = |label, format| {
+            let texture = crate::resource_log::create_texture(
+                device,
+                &wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: descriptor.width.max(1),
+                        height: descriptor.height.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                },
+            );
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            GBufferTarget { texture, view }
+        };
+        let albedo = create("gbuffer albedo", descriptor.albedo_format);
+        let normal = create("gbuffer normal", descriptor.normal_format);
+        let material = create("gbuffer material", descriptor.material_format);
+        let depth = create("gbuffer depth", descriptor.depth_format);
+        // Depth-stencil textures can only be bound through views of their depth aspect.
+        let depth_input = depth.texture.create_view(&wgpu::TextureViewDescriptor {
+            aspect: wgpu::TextureAspect::DepthOnly,
+            ..Default::default()
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("gbuffer bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&albedo.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&normal.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(&material.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&depth_input),
+                },
+            ],
+        });
+        GBufferTargets {
+            albedo,
+            normal,
+            material,
+            depth,
+            bind_group,
+        }
+    }
+
+    /// Recreates the attachments and the bind group if the size changed. Zero sizes are clamped
+    /// to 1.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if (width, height) == (self.descriptor.width, self.descriptor.height) {
+            return;
+        }
+        self.descriptor.width = width;
+        self.descriptor.height = height;
+        self.targets = Self::create_targets(device, &self.descriptor, &self.bind_group_layout);
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.descriptor.width, self.descriptor.height)
+    }
+
+    /// Color targets of albedo, normal and material without blending, for geometry pipelines.
+    pub fn color_targets(&self) -> [Option<wgpu::ColorTargetState>; 3] {
+        [
+            Some(self.descriptor.albedo_format.into()),
+            Some(self.descriptor.normal_format.into()),
+            Some(self.descriptor.material_format.into()),
+        ]
+    }
+
+    /// Depth-stencil state with a less comparison, for geometry pipelines.
+    pub fn depth_stencil(&self) -> wgpu::DepthStencilState {
+        wgpu::DepthStencilState {
+            format: self.descriptor.depth_format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }
+    }
+
+    /// Color attachments of the geometry pass, cleared to transparent.
+    pub fn color_attachments(&self) -> [Option<wgpu::RenderPassColorAttachment<'_>>; 3] {
+        [
+            &self.targets.albedo,
+            &self.targets.normal,
+            &self.targets.material,
+        ]
+        .map(|target| {
+            Some(wgpu::RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: true,
+                },
+            })
+        })
+    }
+
+    /// Depth attachment of the geometry pass, cleared to 1.
+    pub fn depth_stencil_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.targets.depth.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: true,
+            }),
+            stencil_ops: None,
+        }
+    }
+
+    /// Layout of [`Self::bind_group`], for lighting pipelines.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Bind group reading the attachments in the lighting pass.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.targets.bind_group
+    }
+
+    pub fn albedo(&self) -> &wgpu::TextureView {
+        &self.targets.albedo.view
+    }
+
+    pub fn normal(&self) -> &wgpu::TextureView {
+        &self.targets.normal.view
+    }
+
+    pub fn material(&self) -> &wgpu::TextureView {
+        &self.targets.material.view
+    }
+
+    pub fn depth(&self) -> &wgpu::TextureView {
+        &self.targets.depth.view
+    }
+
+    /// Textures of albedo, normal, material and depth, e.g. for copies.
+    pub fn textures(&self) -> [&wgpu::Texture; 4] {
+        [
+            &self.targets.albedo.texture,
+            &self.targets.normal.texture,
+            &self.targets.material.texture,
+            &self.targets.depth.texture,
+        ]
+    }
+}
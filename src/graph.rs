@@ -0,0 +1,507 @@
+//! Render graphs running passes over transient and imported textures.
+
+use std::{fmt, fmt::Write, ops::Range};
+
+use crate::texture::TexturePool;
+
+/// A texture of a [`RenderGraph`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureHandle(usize);
+
+/// Everything of a [`wgpu::TextureDescriptor`], with an owned label.
+#[derive(Clone, Debug)]
+struct TransientDescriptor {
+    label: String,
+    size: wgpu::Extent3d,
+    mip_level_count: u32,
+    sample_count: u32,
+    dimension: wgpu::TextureDimension,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+}
+
+impl TransientDescriptor {
+    fn descriptor(&self) -> wgpu::TextureDescriptor<'_> {
+        wgpu::TextureDescriptor {
+            label: Some(&self.label),
+            size: self.size,
+            mip_level_count: self.mip_level_count,
+            sample_count: self.sample_count,
+            dimension: self.dimension,
+            format: self.format,
+            usage: self.usage,
+        }
+    }
+
+    /// Whether a texture created for `self` can be used for `other`.
+    fn is_compatible(&self, other: &Self) -> bool {
+        self.size == other.size
+            && self.mip_level_count == other.mip_level_count
+            && self.sample_count == other.sample_count
+            && self.dimension == other.dimension
+            && self.format == other.format
+            && self.usage == other.usage
+    }
+}
+
+enum GraphTexture<'a> {
+    Imported {
+        label: String,
+        view: &'a wgpu::TextureView,
+    },
+    Transient(TransientDescriptor),
+}
+
+impl GraphTexture<'_> {
+    fn label(&self) -> &str {
+        match self {
+            Self::Imported { label, .. } => label,
+            Self::Transient(descriptor) => &descriptor.label,
+        }
+    }
+}
+
+type PassFn<'a> = Box<dyn FnOnce(&mut wgpu::CommandEncoder, &GraphResources<'_, 'a>) + 'a>;
+
+struct Pass<'a> {
+    label: String,
+    reads: Vec<TextureHandle>,
+    writes: Vec<TextureHandle>,
+    run: PassFn<'a>,
+}
+
+/// Error returned by [`RenderGraph::validate`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// A pass reads a transient texture which no earlier pass wrote.
+    ReadBeforeWrite { pass: String, texture: String },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadBeforeWrite { pass, texture } => write!(
+                f,
+                "pass {:?} reads transient texture {:?} before it's written",
+                pass, texture
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Memory of the transient textures of a [`RenderGraph`], see [`RenderGraph::transient_memory`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TransientMemory {
+    /// Number of transient textures used by passes.
+    pub textures: usize,
+    /// Number of textures they're aliased to.
+    pub physical_textures: usize,
+    /// Estimated bytes without aliasing.
+    pub unaliased_bytes: u64,
+    /// Estimated bytes with aliasing.
+    pub aliased_bytes: u64,
+}
+
+impl TransientMemory {
+    /// Estimated bytes saved by aliasing.
+    pub fn saved_bytes(&self) -> u64 {
+        self.unaliased_bytes - self.aliased_bytes
+    }
+}
+
+/// Transient memory attributed to a pass of a [`RenderGraph`], see [`RenderGraph::pass_memory`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PassMemory {
+    pub label: String,
+    /// Estimated bytes of the transient textures the pass reads or writes.
+    pub used_bytes: u64,
+    /// Estimated bytes of the transient textures alive during the pass, including ones only
+    /// kept for later passes. Aliased textures never overlap, so this is the memory of the
+    /// physical textures in use.
+    pub live_bytes: u64,
+}
+
+impl fmt::Display for PassMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: ~{} bytes used, ~{} bytes live",
+            self.label, self.used_bytes, self.live_bytes
+        )
+    }
+}
+
+impl fmt::Display for TransientMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} transient textures in {} physical textures, ~{} bytes instead of ~{}",
+            self.textures, self.physical_textures, self.aliased_bytes, self.unaliased_bytes
+        )
+    }
+}
+
+/// Passes of a frame, declaring the textures they read and write.
+///
+/// Passes run in the order they're added. Transient textures are acquired from a [`TexturePool`]
+/// when the graph is executed, imported textures, e.g. the surface, are provided by the caller.
+/// Transient textures with equal descriptors whose lifetimes don't overlap share a physical
+/// texture. [`Self::dump_dot`] visualizes passes, textures, their lifetimes and aliasing.
+pub struct RenderGraph<'a> {
+    textures: Vec<GraphTexture<'a>>,
+    passes: Vec<Pass<'a>>,
+}
+
+impl fmt::Debug for RenderGraph<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderGraph")
+            .field("textures", &self.textures.len())
+            .field(
+                "passes",
+                &self.passes.iter().map(|p| &p.label).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Default for RenderGraph<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        Self {
+            textures: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    /// Adds a texture owned by the caller.
+    pub fn import_texture(&mut self, label: &str, view: &'a wgpu::TextureView) -> TextureHandle {
+        self.textures.push(GraphTexture::Imported {
+            label: label.to_owned(),
+            view,
+        });
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    /// Adds a texture which only lives during the execution of the graph. It must be written
+    /// before it's read.
+    pub fn create_texture(&mut self, descriptor: &wgpu::TextureDescriptor) -> TextureHandle {
+        self.textures
+            .push(GraphTexture::Transient(TransientDescriptor {
+                label: descriptor.label.unwrap_or("transient texture").to_owned(),
+                size: descriptor.size,
+                mip_level_count: descriptor.mip_level_count,
+                sample_count: descriptor.sample_count,
+                dimension: descriptor.dimension,
+                format: descriptor.format,
+                usage: descriptor.usage,
+            }));
+        TextureHandle(self.textures.len() - 1)
+    }
+
+    /// Adds a pass reading and writing the given textures. `run` records its commands, views of
+    /// the textures are available through [`GraphResources`].
+    pub fn add_pass(
+        &mut self,
+        label: &str,
+        reads: &[TextureHandle],
+        writes: &[TextureHandle],
+        run: impl FnOnce(&mut wgpu::CommandEncoder, &GraphResources<'_, 'a>) + 'a,
+    ) {
+        self.passes.push(Pass {
+            label: label.to_owned(),
+            reads: reads.to_vec(),
+            writes: writes.to_vec(),
+            run: Box::new(run),
+        });
+    }
+
+    /// Indices of the first and one past the last pass using `texture`, or `None` if no pass
+    /// uses it.
+    pub fn lifetime(&self, texture: TextureHandle) -> Option<Range<usize>> {
+        let uses = |pass: &Pass| pass.reads.contains(&texture) || pass.writes.contains(&texture);
+        let first = self.passes.iter().position(uses)?;
+        let last = self.passes.iter().rposition(uses)?;
+        Some(first..last + 1)
+    }
+
+    /// Index of the physical texture of each transient texture used by a pass, assigned greedily
+    /// by start of the lifetime.
+    fn aliasing(&self) -> Vec<Option<usize>> {
+        let mut transients: Vec<(usize, Range<usize>)> = self
+            .textures
+            .iter()
+            .enumerate()
+            .filter(|(_, texture)| matches!(texture, GraphTexture::Transient(_)))
+            .filter_map(|(i, _)| Some((i, self.lifetime(TextureHandle(i))?)))
+            .collect();
+        transients.sort_by_key(|(_, lifetime)| lifetime.start);
+
+        // Texture the physical texture was created for and the end of its current lifetime.
+        let mut physical: Vec<(usize, usize)> = Vec::new();
+        let mut aliasing = vec![None; self.textures.len()];
+        for (i, lifetime) in transients {
+            let descriptor = self.transient_descriptor(i);
+            let index = physical
+                .iter()
+                .position(|&(owner, end)| {
+                    end <= lifetime.start
+                        && self.transient_descriptor(owner).is_compatible(descriptor)
+                })
+                .unwrap_or_else(|| {
+                    physical.push((i, 0));
+                    physical.len() - 1
+                });
+            physical[index].1 = lifetime.end;
+            aliasing[i] = Some(index);
+        }
+        aliasing
+    }
+
+    fn transient_descriptor(&self, i: usize) -> &TransientDescriptor {
+        match &self.textures[i] {
+            GraphTexture::Transient(descriptor) => descriptor,
+            GraphTexture::Imported { .. } => unreachable!("texture is transient"),
+        }
+    }
+
+    /// Estimated memory of the transient textures with and without aliasing.
+    pub fn transient_memory(&self) -> TransientMemory {
+        let aliasing = self.aliasing();
+        let mut memory = TransientMemory::default();
+        let mut counted = Vec::new();
+        for (i, physical) in aliasing.iter().enumerate() {
+            let Some(physical) = physical else {
+                continue;
+            };
+            let bytes = crate::texture::estimate_size(&self.transient_descriptor(i).descriptor());
+            memory.textures += 1;
+            memory.unaliased_bytes += bytes;
+            if !counted.contains(physical) {
+                counted.push(*physical);
+                memory.physical_textures += 1;
+                memory.aliased_bytes += bytes;
+            }
+        }
+        memory
+    }
+
+    /// Transient memory of every pass in execution order, e.g. to find the passes contributing
+    /// to the high-water mark, see [`Self::peak_memory`].
+    pub fn pass_memory(&self) -> Vec<PassMemory> {
+        let transients: Vec<(usize, Range<usize>, u64)> = (0..self.textures.len())
+            .filter(|&i| matches!(self.textures[i], GraphTexture::Transient(_)))
+            .filter_map(|i| {
+                let lifetime = self.lifetime(TextureHandle(i))?;
+                let bytes =
+                    crate::texture::estimate_size(&self.transient_descriptor(i).descriptor());
+                Some((i, lifetime, bytes))
+            })
+            .collect();
+
+        self.passes
+            .iter()
+            .enumerate()
+            .map(|(index, pass)| {
+                let mut memory = PassMemory {
+                    label: pass.label.clone(),
+                    ..Default::default()
+                };
+                for (i, lifetime, bytes) in &transients {
+                    if !lifetime.contains(&index) {
+                        continue;
+                    }
+                    memory.live_bytes += bytes;
+                    let texture = TextureHandle(*i);
+                    if pass.reads.contains(&texture) || pass.writes.contains(&texture) {
+                        memory.used_bytes += bytes;
+                    }
+                }
+                memory
+            })
+            .collect()
+    }
+
+    /// The pass with the most live transient memory, i.e. the high-water mark of the graph, or
+    /// `None` without passes.
+    pub fn peak_memory(&self) -> Option<PassMemory> {
+        self.pass_memory()
+            .into_iter()
+            .rev()
+            .max_by_key(|memory| memory.live_bytes)
+    }
+
+    /// Checks that transient textures are written before they're read.
+    pub fn validate(&self) -> Result<(), GraphError> {
+        let mut written = vec![false; self.textures.len()];
+        for pass in &self.passes {
+            for read in &pass.reads {
+                if matches!(self.textures[read.0], GraphTexture::Transient(_)) && !written[read.0] {
+                    return Err(GraphError::ReadBeforeWrite {
+                        pass: pass.label.clone(),
+                        texture: self.textures[read.0].label().to_owned(),
+                    });
+                }
+            }
+            for write in &pass.writes {
+                written[write.0] = true;
+            }
+        }
+        Ok(())
+    }
+
+    /// Validates the graph, acquires the physical textures of the transient textures from `pool`
+    /// and records all passes into `encoder`. The textures are released afterwards.
+    ///
+    /// Returns the memory saved by aliasing, which is also logged if the
+    /// [`crate::resource_log::ResourceLogger`] is enabled.
+    pub fn execute(
+        self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut TexturePool,
+    ) -> Result<TransientMemory, GraphError> {
+        self.validate()?;
+
+        let memory = self.transient_memory();
+        if crate::resource_log::ResourceLogger::is_enabled() {
+            log::debug!(target: crate::resource_log::LOG_TARGET, "render graph: {}", memory);
+            for pass in self.pass_memory() {
+                log::trace!(target: crate::resource_log::LOG_TARGET, "render graph pass {}", pass);
+            }
+        }
+
+        let aliasing = self.aliasing();
+        let mut physical: Vec<Option<usize>> = vec![None; memory.physical_textures];
+        let pooled: Vec<Option<usize>> = aliasing
+            .iter()
+            .enumerate()
+            .map(|(i, index)| {
+                let index = (*index)?;
+                let descriptor = self.transient_descriptor(i).descriptor();
+                Some(*physical[index].get_or_insert_with(|| pool.acquire(device, &descriptor)))
+            })
+            .collect();
+
+        let resources = GraphResources {
+            textures: &self.textures,
+            pooled: &pooled,
+            pool,
+        };
+        for pass in self.passes {
+            (pass.run)(encoder, &resources);
+        }
+
+        for index in physical.into_iter().flatten() {
+            pool.release(index);
+        }
+        Ok(memory)
+    }
+
+    /// Graphviz graph of the passes in execution order with their live transient memory, the
+    /// textures they read and write and the lifetimes and physical textures of transient
+    /// textures.
+    pub fn dump_dot(&self) -> String {
+        let aliasing = self.aliasing();
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+
+        let mut dot = String::from("digraph render_graph {\n    rankdir=LR;\n");
+        for (i, (pass, memory)) in self.passes.iter().zip(self.pass_memory()).enumerate() {
+            writeln!(
+                dot,
+                "    pass{} [shape=box, style=bold, label=\"{}: {}\\n~{} bytes live\"];",
+                i,
+                i,
+                escape(&pass.label),
+                memory.live_bytes
+            )
+            .unwrap();
+        }
+        for (i, texture) in self.textures.iter().enumerate() {
+            let lifetime = match self.lifetime(TextureHandle(i)) {
+                Some(lifetime) => format!("passes {}..{}", lifetime.start, lifetime.end),
+                None => "unused".to_owned(),
+            };
+            let (details, style) = match texture {
+                GraphTexture::Imported { .. } => ("imported".to_owned(), "dashed"),
+                GraphTexture::Transient(descriptor) => (
+                    format!(
+                        "{}x{} {:?}{}",
+                        descriptor.size.width,
+                        descriptor.size.height,
+                        descriptor.format,
+                        match aliasing[i] {
+                            Some(physical) => format!(", physical #{}", physical),
+                            None => String::new(),
+                        }
+                    ),
+                    "solid",
+                ),
+            };
+            writeln!(
+                dot,
+                "    texture{} [shape=ellipse, style={}, label=\"{}\\n{}\\n{}\"];",
+                i,
+                style,
+                escape(texture.label()),
+                details,
+                lifetime
+            )
+            .unwrap();
+        }
+
+        for (i, pass) in self.passes.iter().enumerate() {
+            for read in &pass.reads {
+                writeln!(dot, "    texture{} -> pass{};", read.0, i).unwrap();
+            }
+            for write in &pass.writes {
+                writeln!(dot, "    pass{} -> texture{};", i, write.0).unwrap();
+            }
+        }
+        for i in 1..self.passes.len() {
+            writeln!(
+                dot,
+                "    pass{} -> pass{} [style=dotted, color=gray, constraint=false];",
+                i - 1,
+                i
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Views of the textures of a [`RenderGraph`] during its execution.
+pub struct GraphResources<'r, 'a> {
+    textures: &'r [GraphTexture<'a>],
+    pooled: &'r [Option<usize>],
+    pool: &'r TexturePool,
+}
+
+impl GraphResources<'_, '_> {
+    /// View of `texture`.
+    pub fn view(&self, texture: TextureHandle) -> &wgpu::TextureView {
+        match (&self.textures[texture.0], self.pooled[texture.0]) {
+            (GraphTexture::Imported { view, .. }, _) => view,
+            (GraphTexture::Transient(_), Some(index)) => self
+                .pool
+                .view(index)
+                .expect("transient texture is acquired"),
+            (GraphTexture::Transient(descriptor), None) => {
+                panic!("texture {:?} isn't used by any pass", descriptor.label)
+            }
+        }
+    }
+
+    /// Transient `texture`, or `None` for imported textures.
+    pub fn texture(&self, texture: TextureHandle) -> Option<&wgpu::Texture> {
+        self.pooled[texture.0].and_then(|index| self.pool.get(index))
+    }
+}
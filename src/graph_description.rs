@@ -0,0 +1,377 @@
+//! Building [`RenderGraph`]s from declarative descriptions, e.g. JSON or RON files.
+//!
+//! A [`GraphDescription`] lists textures and fullscreen passes. Passes read their inputs as
+//! textures and draw into their outputs with a fragment shader loaded from a file, so the frame
+//! can be changed without recompiling. Textures marked as imported are resolved by name through
+//! a [`ResourceRegistry`], e.g. `"gbuffer.albedo"` or the surface.
+//!
+//! ```json
+//! {
+//!     "textures": [
+//!         { "name": "scene", "format": "rgba16float", "import": true },
+//!         { "name": "bloom", "format": "rgba16float", "size": { "Relative": 0.5 } },
+//!         { "name": "surface", "format": "bgra8unorm-srgb", "import": true }
+//!     ],
+//!     "passes": [
+//!         { "name": "bloom", "shader": "bloom.wgsl", "inputs": ["scene"], "outputs": ["bloom"] },
+//!         {
+//!             "name": "composite",
+//!             "shader": "composite.wgsl",
+//!             "inputs": ["scene", "bloom"],
+//!             "outputs": ["surface"]
+//!         }
+//!     ]
+//! }
+//! ```
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    graph::{RenderGraph, TextureHandle},
+    registry::ResourceRegistry,
+    shader::{ComposeError, ShaderComposer},
+};
+
+/// Size of a transient texture of a [`GraphDescription`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TextureSize {
+    /// Fraction of the output size passed to [`DescribedGraph::build`].
+    Relative(f32),
+    Absolute {
+        width: u32,
+        height: u32,
+    },
+}
+
+impl Default for TextureSize {
+    fn default() -> Self {
+        Self::Relative(1.0)
+    }
+}
+
+impl TextureSize {
+    fn resolve(self, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Self::Relative(scale) => (
+                ((width as f32 * scale).round() as u32).max(1),
+                ((height as f32 * scale).round() as u32).max(1),
+            ),
+            Self::Absolute { width, height } => (width, height),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TextureDescription {
+    pub name: String,
+    pub format: wgpu::TextureFormat,
+    /// Ignored for imported textures.
+    #[serde(default)]
+    pub size: TextureSize,
+    /// Whether the texture is looked up in the [`ResourceRegistry`] as a view with the same name,
+    /// instead of being transient.
+    #[serde(default)]
+    pub import: bool,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PassDescription {
+    pub name: String,
+    /// WGSL file with the fragment entry point `fs_main`, relative to the base directory passed
+    /// to [`DescribedGraph::new`].
+    ///
+    /// The fullscreen vertex shader and builtin snippets are available, inputs are bound at
+    /// group 0 in order, followed by a linear clamping sampler.
+    pub shader: PathBuf,
+    /// Textures sampled by the pass.
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    /// Textures the pass draws into, in the order of the fragment outputs.
+    pub outputs: Vec<String>,
+}
+
+/// Textures and passes of a frame, deserialized with any serde format.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GraphDescription {
+    #[serde(default)]
+    pub textures: Vec<TextureDescription>,
+    pub passes: Vec<PassDescription>,
+}
+
+impl GraphDescription {
+    fn texture(&self, pass: &str, name: &str) -> Result<usize, DescriptionError> {
+        self.textures
+            .iter()
+            .position(|texture| texture.name == name)
+            .ok_or_else(|| DescriptionError::UnknownTexture {
+                pass: pass.to_owned(),
+                texture: name.to_owned(),
+            })
+    }
+}
+
+/// Error returned by [`DescribedGraph::new`] and [`DescribedGraph::build`].
+#[derive(Debug)]
+pub enum DescriptionError {
+    /// A pass references a texture which isn't described.
+    UnknownTexture { pass: String, texture: String },
+    /// An imported texture has no view in the registry.
+    MissingImport(String),
+    /// A shader file couldn't be read.
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    /// The includes of a shader couldn't be resolved.
+    Compose { pass: String, error: ComposeError },
+}
+
+impl fmt::Display for DescriptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownTexture { pass, texture } => {
+                write!(f, "pass \"{}\" uses unknown texture \"{}\"", pass, texture)
+            }
+            Self::MissingImport(name) => {
+                write!(f, "imported texture \"{}\" isn't registered", name)
+            }
+            Self::Io { path, error } => {
+                write!(f, "failed to read shader {}: {}", path.display(), error)
+            }
+            Self::Compose { pass, error } => {
+                write!(
+                    f,
+                    "failed to compose shader of pass \"{}\": {}",
+                    pass, error
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DescriptionError {}
+
+#[derive(Debug)]
+struct DescribedPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// Indices of the textures of the description.
+    inputs: Vec<usize>,
+    outputs: Vec<usize>,
+}
+
+/// Pipelines of a [`GraphDescription`], building a [`RenderGraph`] every frame.
+///
+/// Reload the description and create a new one to apply changes.
+#[derive(Debug)]
+pub struct DescribedGraph {
+    description: GraphDescription,
+    passes: Vec<DescribedPass>,
+    sampler: wgpu::Sampler,
+}
+
+impl DescribedGraph {
+    /// Loads the shaders of the passes from `base` and creates their pipelines, resolving
+    /// includes with `composer`.
+    pub fn new(
+        device: &wgpu::Device,
+        description: GraphDescription,
+        base: &Path,
+        composer: &ShaderComposer,
+    ) -> Result<Self, DescriptionError> {
+        let passes = description
+            .passes
+            .iter()
+            .map(|pass| Self::create_pass(device, &description, pass, base, composer))
+            .collect::<Result<_, _>>()?;
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("described graph sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Ok(Self {
+            description,
+            passes,
+            sampler,
+        })
+    }
+
+    pub fn description(&self) -> &GraphDescription {
+        &self.description
+    }
+
+    fn create_pass(
+        device: &wgpu::Device,
+        description: &GraphDescription,
+        pass: &PassDescription,
+        base: &Path,
+        composer: &ShaderComposer,
+    ) -> Result<DescribedPass, DescriptionError> {
+        let inputs = pass
+            .inputs
+            .iter()
+            .map(|name| description.texture(&pass.name, name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = pass
+            .outputs
+            .iter()
+            .map(|name| description.texture(&pass.name, name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let path = base.join(&pass.shader);
+        let source =
+            std::fs::read_to_string(&path).map_err(|error| DescriptionError::Io { path, error })?;
+        let label = format!("{} shader", pass.name);
+        let shader = composer
+            .create_shader_module(
+                device,
+                Some(&label),
+                &format!("#include \"wgpu_util::fullscreen\"\n{}", source),
+            )
+            .map_err(|error| DescriptionError::Compose {
+                pass: pass.name.clone(),
+                error,
+            })?;
+
+        let mut entries: Vec<_> = (0..inputs.len() as u32)
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            })
+            .collect();
+        entries.push(wgpu::BindGroupLayoutEntry {
+            binding: inputs.len() as u32,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} bind group layout", pass.name)),
+            entries: &entries,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} pipeline layout", pass.name)),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let targets: Vec<_> = outputs
+            .iter()
+            .map(|&output| Some(description.textures[output].format.into()))
+            .collect();
+        let pipeline = crate::post::fullscreen_pipeline(
+            device,
+            Some(&format!("{} pipeline", pass.name)),
+            &layout,
+            &shader,
+            &targets,
+        );
+
+        Ok(DescribedPass {
+            pipeline,
+            bind_group_layout,
+            inputs,
+            outputs,
+        })
+    }
+
+    /// Builds a graph of the passes with an output size of `width` x `height`, resolving
+    /// imported textures through `registry`.
+    pub fn build<'a>(
+        &'a self,
+        device: &'a wgpu::Device,
+        registry: &'a ResourceRegistry,
+        width: u32,
+        height: u32,
+    ) -> Result<RenderGraph<'a>, DescriptionError> {
+        let mut graph = RenderGraph::new();
+        let textures = self
+            .description
+            .textures
+            .iter()
+            .map(|texture| match texture.import {
+                true => registry
+                    .get_by_name::<wgpu::TextureView>(&texture.name)
+                    .map(|view| graph.import_texture(&texture.name, view))
+                    .ok_or_else(|| DescriptionError::MissingImport(texture.name.clone())),
+                false => {
+                    let (width, height) = texture.size.resolve(width, height);
+                    Ok(graph.create_texture(&wgpu::TextureDescriptor {
+                        label: Some(&texture.name),
+                        size: wgpu::Extent3d {
+                            width,
+                            height,
+                            depth_or_array_layers: 1,
+                        },
+                        mip_level_count: 1,
+                        sample_count: 1,
+                        dimension: wgpu::TextureDimension::D2,
+                        format: texture.format,
+                        usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                            | wgpu::TextureUsages::TEXTURE_BINDING,
+                    }))
+                }
+            })
+            .collect::<Result<Vec<TextureHandle>, _>>()?;
+
+        for (description, pass) in self.description.passes.iter().zip(&self.passes) {
+            let inputs: Vec<_> = pass.inputs.iter().map(|&i| textures[i]).collect();
+            let outputs: Vec<_> = pass.outputs.iter().map(|&i| textures[i]).collect();
+            let (reads, writes) = (inputs.clone(), outputs.clone());
+            let label = description.name.as_str();
+            graph.add_pass(label, &reads, &writes, move |encoder, resources| {
+                let mut entries: Vec<_> = inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(binding, &input)| wgpu::BindGroupEntry {
+                        binding: binding as u32,
+                        resource: wgpu::BindingResource::TextureView(resources.view(input)),
+                    })
+                    .collect();
+                entries.push(wgpu::BindGroupEntry {
+                    binding: inputs.len() as u32,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some(label),
+                    layout: &pass.bind_group_layout,
+                    entries: &entries,
+                });
+
+                let attachments: Vec<_> = outputs
+                    .iter()
+                    .map(|&output| {
+                        Some(wgpu::RenderPassColorAttachment {
+                            view: resources.view(output),
+                            resolve_target: None,
+                            ops: wgpu::Operations {
+                                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                                store: true,
+                            },
+                        })
+                    })
+                    .collect();
+                let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some(label),
+                    color_attachments: &attachments,
+                    depth_stencil_attachment: None,
+                });
+                render_pass.set_pipeline(&pass.pipeline);
+                render_pass.set_bind_group(0, &bind_group, &[]);
+                render_pass.draw(0..3, 0..1);
+            });
+        }
+        Ok(graph)
+    }
+}
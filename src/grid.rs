@@ -0,0 +1,177 @@
+//! Drawing an infinite ground grid with axes, as editor chrome.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{CameraBuffer, CAMERA_SNIPPET},
+    pipeline::RenderPipelineBuilder,
+    shader::ShaderComposer,
+    BufferInitDescriptor, DeviceExt,
+};
+
+const PARAMS_SIZE: wgpu::BufferAddress = 80;
+
+/// Appearance of the grid drawn by [`GridRenderer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GridParams {
+    /// Distance between minor lines.
+    pub spacing: f32,
+    /// Number of minor cells between major lines.
+    pub major_every: u32,
+    /// Width of the lines in pixels.
+    pub line_width: f32,
+    /// View depth at which the grid has faded out completely. Orthographic cameras don't fade.
+    pub fade_distance: f32,
+    pub minor_color: [f32; 4],
+    pub major_color: [f32; 4],
+    /// Color of the x axis, the line `z = 0`.
+    pub x_axis_color: [f32; 4],
+    /// Color of the z axis, the line `x = 0`.
+    pub z_axis_color: [f32; 4],
+}
+
+impl Default for GridParams {
+    fn default() -> Self {
+        Self {
+            spacing: 1.0,
+            major_every: 10,
+            line_width: 1.0,
+            fade_distance: 100.0,
+            minor_color: [0.5, 0.5, 0.5, 0.3],
+            major_color: [0.6, 0.6, 0.6, 0.6],
+            x_axis_color: [0.9, 0.2, 0.2, 1.0],
+            z_axis_color: [0.2, 0.4, 0.9, 1.0],
+        }
+    }
+}
+
+impl GridParams {
+    fn to_bytes(self) -> Vec<u8> {
+        [
+            self.spacing,
+            self.major_every as f32,
+            self.line_width,
+            self.fade_distance,
+        ]
+        .into_iter()
+        .chain(self.minor_color)
+        .chain(self.major_color)
+        .chain(self.x_axis_color)
+        .chain(self.z_axis_color)
+        .flat_map(f32::to_le_bytes)
+        .collect()
+    }
+}
+
+/// Descriptor for [`GridRenderer::new`].
+#[derive(Clone, Debug)]
+pub struct GridDescriptor {
+    /// Format of the color target, the grid is alpha blended onto it.
+    pub format: wgpu::TextureFormat,
+    /// Depth state of the pass, the grid writes the depth of the plane so scene geometry occludes
+    /// it.
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub sample_count: u32,
+    pub params: GridParams,
+}
+
+/// Renders the `y = 0` plane as an infinite grid with highlighted x and z axes.
+///
+/// The grid is a fullscreen triangle intersecting view rays with the plane, so it needs no
+/// geometry and works with any projection of the [`CameraBuffer`], including reversed and infinite
+/// depth.
+#[derive(Debug)]
+pub struct GridRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    params: GridParams,
+}
+
+impl GridRenderer {
+    pub fn new(device: &wgpu::Device, camera: &CameraBuffer, descriptor: &GridDescriptor) -> Self {
+        let mut composer = ShaderComposer::new();
+        composer.add_snippet(CAMERA_SNIPPET, crate::camera::camera_snippet(0, 0));
+        let shader = composer
+            .create_shader_module(
+                device,
+                Some("grid shader"),
+                include_str!("shaders/grid.wgsl"),
+            )
+            .expect("builtin snippets must compose");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("grid bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ..camera.bind_group_layout_entry()
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("grid params buffer"),
+            contents: &descriptor.params.to_bytes(),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("grid bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline =
+            RenderPipelineBuilder::new(&shader, &[&bind_group_layout], descriptor.format)
+                .label("grid pipeline")
+                .vertex_entry_point("vs_fullscreen")
+                .blend(crate::blend::STRAIGHT_ALPHA)
+                .depth_stencil(descriptor.depth_stencil.clone())
+                .sample_count(descriptor.sample_count)
+                .build(device);
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            params: descriptor.params,
+        }
+    }
+
+    /// Draws the grid into `pass`, which must match the targets of the descriptor. Draw it after
+    /// opaque geometry so it blends onto the scene.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    pub fn params(&self) -> &GridParams {
+        &self.params
+    }
+
+    /// Changes the appearance and uploads it using [`wgpu::Queue`].
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: GridParams) {
+        self.params = params;
+        queue.write_buffer(&self.params_buffer, 0, &params.to_bytes());
+    }
+}
@@ -0,0 +1,290 @@
+//! Simulating ripples on a heightfield with a compute shader.
+
+use std::num::NonZeroU64;
+
+const WORKGROUP_SIZE: u32 = 8;
+/// Splashes applied per step, more are deferred to later steps.
+const MAX_SPLASHES: usize = 16;
+const PARAMS_SIZE: u64 = 32 + MAX_SPLASHES as u64 * 16;
+/// Largest stable distance a wave travels per substep, in cells.
+const MAX_COURANT: f32 = 0.5;
+
+/// Format of the state textures: height and vertical velocity in red and green. Two channel
+/// formats aren't writable as storage textures everywhere.
+pub const HEIGHTFIELD_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba32Float;
+
+/// Descriptor for [`Heightfield::new`].
+#[derive(Clone, Debug)]
+pub struct HeightfieldDescriptor<'a> {
+    pub label: wgpu::Label<'a>,
+    /// Number of cells along x and y.
+    pub size: [u32; 2],
+    /// Speed of the waves in cells per second.
+    pub wave_speed: f32,
+    /// Fraction of the velocity lost per second.
+    pub damping: f32,
+}
+
+impl Default for HeightfieldDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: None,
+            size: [256, 256],
+            wave_speed: 30.0,
+            damping: 0.5,
+        }
+    }
+}
+
+/// A heightfield integrating the wave equation on the GPU, e.g. for water surfaces.
+///
+/// The state is double-buffered in two [`HEIGHTFIELD_FORMAT`] textures and every step reads the
+/// current one and writes the other. Cells outside the field mirror the edge, so waves reflect.
+///
+/// All resources are created up front: a bind group for each direction of the ping-pong and a
+/// uniform buffer with one slot for the substep applying the splashes and one for the rest,
+/// selected with dynamic offsets.
+#[derive(Debug)]
+pub struct Heightfield {
+    pipeline: wgpu::ComputePipeline,
+    params: wgpu::Buffer,
+    params_stride: wgpu::BufferAddress,
+    /// Bind group reading texture `i` and writing the other one.
+    bind_groups: [wgpu::BindGroup; 2],
+    textures: [wgpu::Texture; 2],
+    views: [wgpu::TextureView; 2],
+    current: usize,
+    size: [u32; 2],
+    wave_speed: f32,
+    damping: f32,
+    splashes: Vec<[f32; 4]>,
+}
+
+impl Heightfield {
+    pub fn new(device: &wgpu::Device, descriptor: &HeightfieldDescriptor<'_>) -> Self {
+        let source = include_str!("shaders/heightfield.wgsl");
+
+        let shader = crate::create_shader_module(device, Some("heightfield shader"), source);
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("heightfield bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::StorageTexture {
+                        access: wgpu::StorageTextureAccess::WriteOnly,
+                        format: HEIGHTFIELD_FORMAT,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("heightfield pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::resource_log::create_compute_pipeline(
+            device,
+            &wgpu::ComputePipelineDescriptor {
+                label: Some("heightfield pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "step",
+            },
+        );
+
+        let [width, height] = descriptor.size;
+        let texture = || {
+            crate::resource_log::create_texture(
+                device,
+                &wgpu::TextureDescriptor {
+                    label: descriptor.label,
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: HEIGHTFIELD_FORMAT,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING
+                        | wgpu::TextureUsages::STORAGE_BINDING
+                        | wgpu::TextureUsages::COPY_SRC
+                        | wgpu::TextureUsages::COPY_DST,
+                },
+            )
+        };
+        let textures = [texture(), texture()];
+        let views =
+            [0, 1].map(|i| textures[i].create_view(&wgpu::TextureViewDescriptor::default()));
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as wgpu::BufferAddress;
+        let params_stride = PARAMS_SIZE.div_ceil(alignment) * alignment;
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("heightfield params buffer"),
+                size: 2 * params_stride,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let bind_groups = [0, 1].map(|current| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("heightfield bind group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: &params,
+                            offset: 0,
+                            size: NonZeroU64::new(PARAMS_SIZE),
+                        }),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&views[current]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&views[1 - current]),
+                    },
+                ],
+            })
+        });
+
+        Self {
+            pipeline,
+            params,
+            params_stride,
+            bind_groups,
+            textures,
+            views,
+            current: 0,
+            size: descriptor.size,
+            wave_speed: descriptor.wave_speed,
+            damping: descriptor.damping,
+            splashes: Vec::new(),
+        }
+    }
+
+    /// Raises the surface around `position` in uv coordinates by `strength` at the center,
+    /// falling off smoothly to 0 at `radius`. Applied with the next [`Self::step`].
+    pub fn splash(&mut self, position: [f32; 2], radius: f32, strength: f32) {
+        self.splashes
+            .push([position[0], position[1], radius, strength]);
+    }
+
+    /// Records passes advancing the simulation by `dt` seconds.
+    ///
+    /// Large steps are split into substeps to keep the simulation stable. The parameters are
+    /// written with `queue`, which takes effect before any command of the next submission, so
+    /// call this at most once per submitted encoder.
+    pub fn step(&mut self, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, dt: f32) {
+        let substeps = (self.wave_speed * dt / MAX_COURANT).ceil().max(1.0) as u32;
+        let dt = dt / substeps as f32;
+        let splash_count = self.splashes.len().min(MAX_SPLASHES);
+        let splashes: Vec<_> = self.splashes.drain(..splash_count).collect();
+
+        queue.write_buffer(&self.params, 0, &self.params_bytes(dt, &splashes));
+        let later_offset = match substeps > 1 && !splashes.is_empty() {
+            true => {
+                queue.write_buffer(
+                    &self.params,
+                    self.params_stride,
+                    &self.params_bytes(dt, &[]),
+                );
+                self.params_stride as u32
+            }
+            false => 0,
+        };
+
+        for substep in 0..substeps {
+            let offset = match substep {
+                0 => 0,
+                _ => later_offset,
+            };
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("heightfield pass"),
+                });
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &self.bind_groups[self.current], &[offset]);
+                crate::dispatch(
+                    &mut pass,
+                    "heightfield pass",
+                    [
+                        self.size[0].div_ceil(WORKGROUP_SIZE),
+                        self.size[1].div_ceil(WORKGROUP_SIZE),
+                        1,
+                    ],
+                );
+            }
+            self.current = 1 - self.current;
+        }
+    }
+
+    fn params_bytes(&self, dt: f32, splashes: &[[f32; 4]]) -> Vec<u8> {
+        let mut params = Vec::with_capacity(PARAMS_SIZE as usize);
+        for word in self.size {
+            params.extend_from_slice(&word.to_le_bytes());
+        }
+        for value in [dt, self.wave_speed, self.damping] {
+            params.extend_from_slice(&value.to_le_bytes());
+        }
+        params.extend_from_slice(&(splashes.len() as u32).to_le_bytes());
+        params.resize(32, 0);
+        for value in splashes.iter().flatten() {
+            params.extend_from_slice(&value.to_le_bytes());
+        }
+        params.resize(PARAMS_SIZE as usize, 0);
+        params
+    }
+
+    /// The texture holding the current height and velocity.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.textures[self.current]
+    }
+
+    /// View of [`Self::texture`], for sampling the surface with `textureLoad` when rendering.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.views[self.current]
+    }
+
+    pub fn size(&self) -> [u32; 2] {
+        self.size
+    }
+
+    pub fn set_wave_speed(&mut self, wave_speed: f32) {
+        self.wave_speed = wave_speed;
+    }
+
+    pub fn set_damping(&mut self, damping: f32) {
+        self.damping = damping;
+    }
+}
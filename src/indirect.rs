@@ -0,0 +1,284 @@
+//! Indirect draw arguments and batching them into multi-draws.
+
+use wgpu::util::{DrawIndexedIndirect, DrawIndirect};
+
+use crate::{
+    draw::{DrawKey, DrawList},
+    DynamicBuffer,
+};
+
+/// Arguments of an indirect draw.
+#[derive(Clone, Copy, Debug)]
+pub enum IndirectDraw {
+    Draw(DrawIndirect),
+    DrawIndexed(DrawIndexedIndirect),
+}
+
+impl IndirectDraw {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Draw(args) => args.as_bytes(),
+            Self::DrawIndexed(args) => args.as_bytes(),
+        }
+    }
+
+    pub fn is_indexed(&self) -> bool {
+        matches!(self, Self::DrawIndexed(_))
+    }
+}
+
+/// A buffer of indirect arguments, collected on the CPU and uploaded at once.
+#[derive(Debug)]
+pub struct IndirectArgsBuffer {
+    contents: Vec<u8>,
+    buffer: DynamicBuffer,
+}
+
+impl IndirectArgsBuffer {
+    /// Creates a new empty buffer. [`wgpu::BufferUsages::INDIRECT`] and
+    /// [`wgpu::BufferUsages::COPY_DST`] are added to `usage`.
+    pub fn new(device: &wgpu::Device, label: wgpu::Label, usage: wgpu::BufferUsages) -> Self {
+        Self {
+            contents: Vec::new(),
+            buffer: DynamicBuffer::new(
+                device,
+                &wgpu::BufferDescriptor {
+                    label,
+                    size: 0,
+                    usage: usage | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            ),
+        }
+    }
+
+    /// Appends arguments. Returns their offset in bytes.
+    pub fn push(&mut self, draw: &IndirectDraw) -> wgpu::BufferAddress {
+        let offset = self.contents.len() as wgpu::BufferAddress;
+        self.contents.extend_from_slice(draw.as_bytes());
+        offset
+    }
+
+    /// Removes all arguments.
+    pub fn clear(&mut self) {
+        self.contents.clear();
+    }
+
+    /// Uploads the arguments pushed since the last [`Self::clear`], resizing the buffer if
+    /// needed.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        if !self.contents.is_empty() {
+            self.buffer.upload(device, queue, &self.contents);
+        }
+    }
+
+    /// Size of the pushed arguments in bytes.
+    pub fn len(&self) -> wgpu::BufferAddress {
+        self.contents.len() as wgpu::BufferAddress
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn raw(&self) -> &wgpu::Buffer {
+        self.buffer.raw()
+    }
+}
+
+/// Draws of a [`MultiDrawBatcher`] sharing pipeline and material, with contiguous arguments.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IndirectBatch {
+    pub pipeline: u16,
+    pub material: u16,
+    pub indexed: bool,
+    /// Offset of the first arguments in bytes.
+    pub offset: wgpu::BufferAddress,
+    pub count: u32,
+}
+
+/// Groups indirect draws with the same pipeline and material into ranges drawn by a single
+/// multi-draw.
+///
+/// Draws are sorted by [`DrawKey`], so arguments of compatible draws end up next to each other.
+/// Without [`wgpu::Features::MULTI_DRAW_INDIRECT`], batches are drawn with a loop of single
+/// indirect draws instead.
+#[derive(Debug)]
+pub struct MultiDrawBatcher {
+    draws: DrawList<IndirectDraw>,
+    args: IndirectArgsBuffer,
+    batches: Vec<IndirectBatch>,
+    multi_draw: bool,
+}
+
+impl MultiDrawBatcher {
+    /// Creates a new empty batcher, using multi-draws if `device` supports them.
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self {
+            draws: DrawList::new(),
+            args: IndirectArgsBuffer::new(
+                device,
+                Some("multi draw indirect args"),
+                wgpu::BufferUsages::empty(),
+            ),
+            batches: Vec::new(),
+            multi_draw: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+        }
+    }
+
+    pub fn push(&mut self, key: DrawKey, draw: IndirectDraw) {
+        self.draws.push(key, draw);
+    }
+
+    /// Sorts the pushed draws, groups them into batches and uploads their arguments.
+    pub fn build(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        self.args.clear();
+        self.batches.clear();
+
+        for batch in self.draws.batches() {
+            // Indexed and non-indexed arguments have different strides.
+            for draws in batch
+                .draws
+                .chunk_by(|(_, a), (_, b)| a.is_indexed() == b.is_indexed())
+            {
+                let offset = self.args.len();
+                for (_, draw) in draws {
+                    self.args.push(draw);
+                }
+                self.batches.push(IndirectBatch {
+                    pipeline: batch.pipeline,
+                    material: batch.material,
+                    indexed: draws[0].1.is_indexed(),
+                    offset,
+                    count: draws.len() as u32,
+                });
+            }
+        }
+
+        self.args.upload(device, queue);
+    }
+
+    /// Draws all batches of the last [`Self::build`], calling `set_state` before each batch to
+    /// set its pipeline, bind groups and buffers.
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        mut set_state: impl FnMut(&mut wgpu::RenderPass<'a>, &IndirectBatch),
+    ) {
+        let buffer = self.args.raw();
+        for batch in &self.batches {
+            set_state(pass, batch);
+            multi_draw(
+                pass,
+                batch.indexed,
+                self.multi_draw,
+                buffer,
+                batch.offset,
+                batch.count,
+            );
+        }
+    }
+
+    /// Batches of the last [`Self::build`].
+    pub fn batches(&self) -> &[IndirectBatch] {
+        &self.batches
+    }
+
+    /// Whether batches are drawn with multi-draws.
+    pub fn uses_multi_draw(&self) -> bool {
+        self.multi_draw
+    }
+
+    /// Removes all draws, keeping the allocations for the next frame.
+    pub fn clear(&mut self) {
+        self.draws.clear();
+        self.args.clear();
+        self.batches.clear();
+    }
+}
+
+/// Draws arguments written on the GPU, e.g. by a culling pass compacting visible draws, whose
+/// number is only known on the GPU.
+///
+/// With [`wgpu::Features::MULTI_DRAW_INDIRECT_COUNT`] the number of draws is read from a count
+/// buffer. Otherwise all `max_count` arguments are drawn, so arguments past the count must be
+/// degenerate, i.e. have an instance count of `0`. Clearing the arguments with
+/// [`wgpu::CommandEncoder::clear_buffer`] before writing them works for both paths.
+#[derive(Clone, Copy, Debug)]
+pub struct IndirectCountDrawer {
+    count: bool,
+    multi_draw: bool,
+}
+
+impl IndirectCountDrawer {
+    /// Uses count buffers and multi-draws if `device` supports them.
+    pub fn new(device: &wgpu::Device) -> Self {
+        let features = device.features();
+        Self {
+            count: features.contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+            multi_draw: features.contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+        }
+    }
+
+    /// Draws up to `max_count` arguments starting at `offset` in `args`. The number of draws is
+    /// the `u32` at `count_offset` in `count`, if count buffers are supported.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw<'a>(
+        &self,
+        pass: &mut wgpu::RenderPass<'a>,
+        indexed: bool,
+        args: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        count: &'a wgpu::Buffer,
+        count_offset: wgpu::BufferAddress,
+        max_count: u32,
+    ) {
+        match (indexed, self.count) {
+            (false, true) => {
+                pass.multi_draw_indirect_count(args, offset, count, count_offset, max_count)
+            }
+            (true, true) => {
+                pass.multi_draw_indexed_indirect_count(args, offset, count, count_offset, max_count)
+            }
+            (indexed, false) => multi_draw(pass, indexed, self.multi_draw, args, offset, max_count),
+        }
+    }
+
+    /// Whether the number of draws is read from the count buffer. If not, arguments past the
+    /// count must be degenerate.
+    pub fn uses_count_buffer(&self) -> bool {
+        self.count
+    }
+}
+
+/// Draws `count` consecutive arguments, with a loop of single draws if multi-draws aren't
+/// supported.
+fn multi_draw<'a>(
+    pass: &mut wgpu::RenderPass<'a>,
+    indexed: bool,
+    supported: bool,
+    buffer: &'a wgpu::Buffer,
+    offset: wgpu::BufferAddress,
+    count: u32,
+) {
+    match (indexed, supported) {
+        (false, true) => pass.multi_draw_indirect(buffer, offset, count),
+        (true, true) => pass.multi_draw_indexed_indirect(buffer, offset, count),
+        (indexed, false) => {
+            let stride = match indexed {
+                true => std::mem::size_of::<DrawIndexedIndirect>(),
+                false => std::mem::size_of::<DrawIndirect>(),
+            } as wgpu::BufferAddress;
+            for i in 0..count as wgpu::BufferAddress {
+                let offset = offset + i * stride;
+                match indexed {
+                    true => pass.draw_indexed_indirect(buffer, offset),
+                    false => pass.draw_indirect(buffer, offset),
+                }
+            }
+        }
+    }
+}
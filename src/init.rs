@@ -0,0 +1,77 @@
+//! [winit](https://github.com/rust-windowing/winit) bootstrapping.
+
+use crate::{
+    context::{ContextError, GpuContext, GpuContextDescriptor},
+    surface::{SurfaceManager, SurfaceManagerDescriptor},
+};
+
+/// Creates a [`GpuContext`] able to present to `window` and a configured [`SurfaceManager`] for
+/// it, using default descriptors.
+///
+/// Blocks until the adapter and device are available.
+///
+/// # Safety
+/// `window` must outlive the returned [`SurfaceManager`].
+pub unsafe fn with_window(
+    window: &winit::window::Window,
+) -> Result<(GpuContext, SurfaceManager), ContextError> {
+    with_window_and_descriptors(
+        window,
+        &GpuContextDescriptor::default(),
+        &SurfaceManagerDescriptor::default(),
+    )
+}
+
+/// [`with_window`] but with custom descriptors.
+///
+/// The size of `surface_descriptor` is ignored in favor of the inner size of `window`.
+///
+/// # Safety
+/// `window` must outlive the returned [`SurfaceManager`].
+pub unsafe fn with_window_and_descriptors(
+    window: &winit::window::Window,
+    context_descriptor: &GpuContextDescriptor,
+    surface_descriptor: &SurfaceManagerDescriptor,
+) -> Result<(GpuContext, SurfaceManager), ContextError> {
+    let instance = wgpu::Instance::new(context_descriptor.backends);
+    // SAFETY: The surface is owned by the returned `SurfaceManager`, which the caller
+    // guarantees to drop before `window`.
+    let surface = unsafe { instance.create_surface(window) };
+
+    let context = pollster::block_on(GpuContext::with_instance(
+        instance,
+        context_descriptor,
+        Some(&surface),
+    ))?;
+
+    let size = window.inner_size();
+    let surface_manager = SurfaceManager::new(
+        &context.device,
+        &context.adapter,
+        surface,
+        &SurfaceManagerDescriptor {
+            width: size.width,
+            height: size.height,
+            ..surface_descriptor.clone()
+        },
+    )?;
+
+    Ok((context, surface_manager))
+}
+
+/// Forwards resize related window events to `surface_manager`.
+///
+/// Returns whether the surface was resized.
+pub fn handle_window_event(
+    surface_manager: &mut SurfaceManager,
+    device: &wgpu::Device,
+    event: &winit::event::WindowEvent,
+) -> bool {
+    let size = match event {
+        winit::event::WindowEvent::Resized(size) => *size,
+        winit::event::WindowEvent::ScaleFactorChanged { new_inner_size, .. } => **new_inner_size,
+        _ => return false,
+    };
+    surface_manager.resize(device, size.width, size.height);
+    true
+}
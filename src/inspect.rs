@@ -0,0 +1,231 @@
+//! Inspecting buffer contents for debugging.
+
+use std::{fmt, ops::Range};
+
+/// Scalar type of a [`Field`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ScalarType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    /// Size of a single scalar in bytes.
+    pub fn size(self) -> wgpu::BufferAddress {
+        match self {
+            Self::U8 | Self::I8 => 1,
+            Self::U16 | Self::I16 => 2,
+            Self::U32 | Self::I32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    pub(crate) fn format(self, bytes: &[u8]) -> String {
+        // GPU data is always little endian.
+        match self {
+            Self::U8 => bytes[0].to_string(),
+            Self::I8 => (bytes[0] as i8).to_string(),
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]).to_string(),
+            Self::I16 => i16::from_le_bytes([bytes[0], bytes[1]]).to_string(),
+            Self::U32 => u32::from_le_bytes(bytes[..4].try_into().unwrap()).to_string(),
+            Self::I32 => i32::from_le_bytes(bytes[..4].try_into().unwrap()).to_string(),
+            Self::F32 => format!("{:?}", f32::from_le_bytes(bytes[..4].try_into().unwrap())),
+            Self::F64 => format!("{:?}", f64::from_le_bytes(bytes[..8].try_into().unwrap())),
+        }
+    }
+
+    pub(crate) fn to_f64(self, bytes: &[u8]) -> f64 {
+        match self {
+            Self::U8 => bytes[0] as f64,
+            Self::I8 => bytes[0] as i8 as f64,
+            Self::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Self::U32 => u32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            Self::I32 => i32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            Self::F32 => f32::from_le_bytes(bytes[..4].try_into().unwrap()) as f64,
+            Self::F64 => f64::from_le_bytes(bytes[..8].try_into().unwrap()),
+        }
+    }
+}
+
+/// A named field inside a row of a [`BufferSchema`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Field {
+    /// Name shown in the header.
+    pub name: String,
+    /// Byte offset of the field relative to the start of the row.
+    pub offset: wgpu::BufferAddress,
+    /// Type of each component.
+    pub ty: ScalarType,
+    /// Number of components. `1` for scalars, `2..=4` for vectors, etc.
+    pub components: u32,
+}
+
+impl Field {
+    /// Size of the field in bytes.
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.ty.size() * self.components as wgpu::BufferAddress
+    }
+}
+
+/// Runtime description of the layout of a buffer consisting of equally sized rows.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BufferSchema {
+    fields: Vec<Field>,
+    stride: wgpu::BufferAddress,
+}
+
+impl BufferSchema {
+    /// Creates an empty schema with rows `stride` bytes apart.
+    pub fn new(stride: wgpu::BufferAddress) -> Self {
+        assert!(stride > 0, "stride must be greater than 0");
+        Self {
+            fields: Vec::new(),
+            stride,
+        }
+    }
+
+    /// Adds a field. Fields must lie within the stride.
+    pub fn with_field(
+        mut self,
+        name: impl Into<String>,
+        offset: wgpu::BufferAddress,
+        ty: ScalarType,
+        components: u32,
+    ) -> Self {
+        let field = Field {
+            name: name.into(),
+            offset,
+            ty,
+            components,
+        };
+        assert!(
+            field.offset + field.size() <= self.stride,
+            "field must lie within the stride"
+        );
+        self.fields.push(field);
+        self
+    }
+
+    /// Fields of a row.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Distance between rows in bytes.
+    pub fn stride(&self) -> wgpu::BufferAddress {
+        self.stride
+    }
+}
+
+/// A formatted row produced by [`BufferInspector`].
+///
+/// Cells are in the same order as the fields of the schema, which makes them directly usable as
+/// table rows in debug UIs.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct InspectorRow {
+    /// Index of the row inside the buffer.
+    pub index: u64,
+    /// One formatted cell per field.
+    pub cells: Vec<String>,
+}
+
+impl fmt::Display for InspectorRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.index)?;
+        for cell in &self.cells {
+            write!(f, " {}", cell)?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads back windows of a buffer and formats them according to a [`BufferSchema`].
+#[derive(Clone, Debug)]
+pub struct BufferInspector {
+    schema: BufferSchema,
+}
+
+impl BufferInspector {
+    pub fn new(schema: BufferSchema) -> Self {
+        Self { schema }
+    }
+
+    /// Get a reference to the schema.
+    pub fn schema(&self) -> &BufferSchema {
+        &self.schema
+    }
+
+    /// Field names, usable as table header.
+    pub fn header(&self) -> Vec<&str> {
+        self.schema.fields.iter().map(|f| f.name.as_str()).collect()
+    }
+
+    /// Reads back `rows` of `buffer` and formats them.
+    ///
+    /// `buffer` must have [`wgpu::BufferUsages::COPY_SRC`]. Blocks until the data is available.
+    pub fn read_rows(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        buffer: &wgpu::Buffer,
+        rows: Range<u64>,
+    ) -> Result<Vec<InspectorRow>, wgpu::BufferAsyncError> {
+        let stride = self.schema.stride;
+        let bytes = crate::readback::read_buffer(
+            device,
+            queue,
+            buffer,
+            rows.start * stride..rows.end * stride,
+        )?;
+        Ok(self.format_rows(&bytes, rows.start))
+    }
+
+    /// Formats already read back `bytes`, whose first row has index `first_row`.
+    ///
+    /// Trailing bytes not making up a whole row are ignored.
+    pub fn format_rows(&self, bytes: &[u8], first_row: u64) -> Vec<InspectorRow> {
+        bytes
+            .chunks_exact(self.schema.stride as usize)
+            .zip(first_row..)
+            .map(|(row, index)| InspectorRow {
+                index,
+                cells: self
+                    .schema
+                    .fields
+                    .iter()
+                    .map(|field| format_field(field, row))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Formats rows as a plain text table including a header line.
+    pub fn format_table(&self, rows: &[InspectorRow]) -> String {
+        let mut table = format!("index {}\n", self.header().join(" "));
+        for row in rows {
+            table.push_str(&row.to_string());
+            table.push('\n');
+        }
+        table
+    }
+}
+
+fn format_field(field: &Field, row: &[u8]) -> String {
+    let size = field.ty.size() as usize;
+    let start = field.offset as usize;
+    let values: Vec<_> = (0..field.components as usize)
+        .map(|i| field.ty.format(&row[start + i * size..]))
+        .collect();
+
+    match values.len() {
+        1 => values.into_iter().next().unwrap(),
+        _ => format!("({})", values.join(", ")),
+    }
+}
@@ -0,0 +1,506 @@
+//! Drawing many instances of a mesh, culled against the camera frustum and sorted into levels
+//! of detail on the GPU.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{CameraBuffer, Matrix4, CAMERA_SNIPPET},
+    mesh::Mesh,
+    shader::ShaderComposer,
+    BufferInitDescriptor, DeviceExt, DynamicBuffer,
+};
+
+const WORKGROUP_SIZE: u32 = 256;
+const PARAMS_SIZE: wgpu::BufferAddress = 64;
+/// [`wgpu::util::DrawIndexedIndirect`] arguments of a level of detail.
+const ARGS_SIZE: wgpu::BufferAddress = 20;
+/// Instances fitting into the buffers before the first upload.
+const INITIAL_CAPACITY: wgpu::BufferAddress = 64;
+
+/// Size of an instance: its model matrix as column-major `mat4x4<f32>`.
+pub const INSTANCE_SIZE: wgpu::BufferAddress = 64;
+
+/// Maximum number of levels of detail of [`InstancedDraws`].
+pub const MAX_LODS: usize = 8;
+
+/// A level of detail of the mesh drawn by [`InstancedDraws`], a range of its index buffer
+/// sharing the vertices of the other levels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodLevel {
+    pub first_index: u32,
+    pub index_count: u32,
+    /// Smallest projected size at which the level is drawn, as the diameter of the bounding
+    /// sphere relative to the viewport height. Smaller instances use the next level. Ignored for
+    /// the last level, which draws all instances smaller than the previous one.
+    pub min_screen_size: f32,
+}
+
+/// Concatenates the indices of levels of detail from [`crate::simplify::generate_lods`], finest
+/// first, into one index buffer for a [`Mesh`] and returns it with the [`LodLevel`]s, each drawn
+/// down to its entry of `min_screen_sizes`.
+#[cfg(feature = "simplify")]
+pub fn lod_chain(
+    lods: &[crate::simplify::Lod],
+    min_screen_sizes: &[f32],
+) -> (Vec<u32>, Vec<LodLevel>) {
+    assert_eq!(
+        lods.len(),
+        min_screen_sizes.len(),
+        "every level of detail needs a screen size"
+    );
+    let mut indices = Vec::new();
+    let mut levels = Vec::with_capacity(lods.len());
+    for (lod, &min_screen_size) in lods.iter().zip(min_screen_sizes) {
+        levels.push(LodLevel {
+            first_index: indices.len() as u32,
+            index_count: lod.indices.len() as u32,
+            min_screen_size,
+        });
+        indices.extend_from_slice(&lod.indices);
+    }
+    (indices, levels)
+}
+
+/// Descriptor for [`InstancedDraws::with_descriptor`].
+#[derive(Clone, Debug)]
+pub struct InstancedDrawsDescriptor<'a> {
+    pub mesh: &'a Mesh,
+    /// Levels of detail, finest first, at most [`MAX_LODS`]. If empty, all indices of `mesh`
+    /// are drawn at every size.
+    pub lods: &'a [LodLevel],
+}
+
+/// Instances of one mesh, culled with their bounding spheres against the frustum of a
+/// [`CameraBuffer`] and drawn with a single indirect draw.
+///
+/// Transforms are pushed on the CPU each frame. [`Self::cull`] selects a level of detail for each
+/// visible instance by its projected size, compacts the visible instances of every level into a
+/// region of a vertex buffer on the GPU and counts them into the indirect arguments of the
+/// level, so nothing is read back for drawing. Visible instances are ordered differently from run
+/// to run.
+#[derive(Debug)]
+pub struct InstancedDraws {
+    pipeline: wgpu::ComputePipeline,
+    camera_bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    instances: DynamicBuffer,
+    visible_instances: wgpu::Buffer,
+    args: wgpu::Buffer,
+    initial_args: wgpu::Buffer,
+
+    transforms: Vec<u8>,
+    /// Number of instances of the last upload.
+    uploaded: u32,
+    /// Bounding sphere of the mesh in model space.
+    sphere: ([f32; 3], f32),
+    row_length: u32,
+    min_screen_sizes: [f32; MAX_LODS],
+    lod_count: u32,
+    /// Instances fitting into each level's region of [`Self::visible_instances`].
+    capacity: u32,
+}
+
+impl InstancedDraws {
+    /// Creates empty instances of `mesh` without levels of detail.
+    pub fn new(device: &wgpu::Device, camera: &CameraBuffer, mesh: &Mesh) -> Self {
+        Self::with_descriptor(
+            device,
+            camera,
+            &InstancedDrawsDescriptor { mesh, lods: &[] },
+        )
+    }
+
+    /// Creates empty instances, which are culled with the bounds of the mesh. Meshes without
+    /// bounds are never culled and always drawn with the first level of detail.
+    pub fn with_descriptor(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        descriptor: &InstancedDrawsDescriptor<'_>,
+    ) -> Self {
+        let mesh = descriptor.mesh;
+        assert!(
+            descriptor.lods.len() <= MAX_LODS,
+            "at most {} levels of detail are supported",
+            MAX_LODS
+        );
+        let whole_mesh = [LodLevel {
+            first_index: 0,
+            index_count: mesh.index_count,
+            min_screen_size: 0.0,
+        }];
+        let lods = match descriptor.lods.is_empty() {
+            true => &whole_mesh[..],
+            false => descriptor.lods,
+        };
+        let mut min_screen_sizes = [0.0; MAX_LODS];
+        for (size, lod) in min_screen_sizes.iter_mut().zip(lods) {
+            *size = lod.min_screen_size;
+        }
+
+        let mut composer = ShaderComposer::new();
+        composer.add_snippet(CAMERA_SNIPPET, crate::camera::camera_snippet(0, 0));
+        let shader = composer
+            .create_shader_module(
+                device,
+                Some("instancing shader"),
+                include_str!("shaders/instancing.wgsl"),
+            )
+            .expect("builtin snippets must compose");
+
+        let sphere = match mesh.bounds {
+            Some(bounds) => {
+                let extent = bounds.extent();
+                let radius = extent.iter().map(|e| e * e).sum::<f32>().sqrt() * 0.5;
+                (bounds.center(), radius)
+            }
+            None => ([0.0; 3], f32::INFINITY),
+        };
+
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("instancing params buffer"),
+                size: PARAMS_SIZE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let instances = DynamicBuffer::new(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("instancing instance buffer"),
+                size: INITIAL_CAPACITY * INSTANCE_SIZE,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let capacity = (instances.size() / INSTANCE_SIZE) as u32;
+        let visible_instances = create_visible_buffer(device, capacity, lods.len() as u32);
+        let args = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("instancing indirect buffer"),
+                size: ARGS_SIZE * lods.len() as wgpu::BufferAddress,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            },
+        );
+        let initial_args = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("instancing initial indirect buffer"),
+            contents: &lods
+                .iter()
+                .flat_map(|lod| [lod.index_count, 0, lod.first_index, 0, 0])
+                .flat_map(|value| value.to_le_bytes())
+                .collect::<Vec<_>>(),
+            size: None,
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let camera_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("instancing camera bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ..camera.bind_group_layout_entry()
+                }],
+            });
+        let camera_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("instancing camera bind group"),
+            layout: &camera_bind_group_layout,
+            entries: &[buffer_entry(0, camera.buffer())],
+        });
+
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let storage_entry = |binding, read_only, size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: compute,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(size),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("instancing bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: compute,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+                storage_entry(1, true, INSTANCE_SIZE),
+                storage_entry(2, false, INSTANCE_SIZE),
+                storage_entry(3, false, ARGS_SIZE),
+            ],
+        });
+        let bind_group = create_bind_group(
+            device,
+            &bind_group_layout,
+            [&params, instances.raw(), &visible_instances, &args],
+        );
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("instancing pipeline layout"),
+            bind_group_layouts: &[&camera_bind_group_layout, &bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::resource_log::create_compute_pipeline(
+            device,
+            &wgpu::ComputePipelineDescriptor {
+                label: Some("instancing cull pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "cull",
+            },
+        );
+
+        Self {
+            pipeline,
+            camera_bind_group,
+            bind_group_layout,
+            bind_group,
+            params,
+            instances,
+            visible_instances,
+            args,
+            initial_args,
+
+            transforms: Vec::new(),
+            uploaded: 0,
+            sphere,
+            row_length: 1,
+            min_screen_sizes,
+            lod_count: lods.len() as u32,
+            capacity,
+        }
+    }
+
+    /// Appends an instance with a column-major model matrix.
+    pub fn push(&mut self, transform: Matrix4) {
+        for value in transform.iter().flatten() {
+            self.transforms.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    /// Removes all instances, keeping the allocations for the next frame.
+    pub fn clear(&mut self) {
+        self.transforms.clear();
+    }
+
+    /// Number of instances pushed since the last [`Self::clear`].
+    pub fn len(&self) -> u32 {
+        (self.transforms.len() as wgpu::BufferAddress / INSTANCE_SIZE) as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Uploads the pushed instances, growing the buffers if needed.
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let size = self.instances.size();
+        if !self.transforms.is_empty() {
+            self.instances.upload(device, queue, &self.transforms);
+        }
+        if self.instances.size() != size {
+            self.capacity = (self.instances.size() / INSTANCE_SIZE) as u32;
+            self.visible_instances = create_visible_buffer(device, self.capacity, self.lod_count);
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                [
+                    &self.params,
+                    self.instances.raw(),
+                    &self.visible_instances,
+                    &self.args,
+                ],
+            );
+        }
+
+        self.uploaded = self.len();
+        let groups = self.uploaded.div_ceil(WORKGROUP_SIZE);
+        self.row_length = groups.clamp(1, device.limits().max_compute_workgroups_per_dimension);
+
+        let (center, radius) = self.sphere;
+        let mut bytes = Vec::with_capacity(PARAMS_SIZE as usize);
+        for value in center.iter().chain([&radius]) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in [
+            self.uploaded,
+            self.row_length,
+            self.lod_count,
+            self.capacity,
+        ] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        for value in self.min_screen_sizes {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        queue.write_buffer(&self.params, 0, &bytes);
+    }
+
+    /// Records a pass culling the uploaded instances with the current camera, replacing the
+    /// contents of [`Self::visible_instances`] and [`Self::indirect_buffer`].
+    pub fn cull(&self, encoder: &mut wgpu::CommandEncoder) {
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::copy("instancing reset")
+                .read("initial args", &self.initial_args)
+                .write("args", &self.args)
+        });
+        encoder.copy_buffer_to_buffer(
+            &self.initial_args,
+            0,
+            &self.args,
+            0,
+            ARGS_SIZE * self.lod_count as wgpu::BufferAddress,
+        );
+        if self.uploaded == 0 {
+            return;
+        }
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::dispatch("instancing cull pass")
+                .read("instances", self.instances.raw())
+                .write("visible instances", &self.visible_instances)
+                .write("args", &self.args)
+        });
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("instancing cull pass"),
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        pass.set_bind_group(1, &self.bind_group, &[]);
+        let groups = self.uploaded.div_ceil(WORKGROUP_SIZE);
+        crate::dispatch(
+            &mut pass,
+            "instancing cull pass",
+            [self.row_length, groups.div_ceil(self.row_length), 1],
+        );
+    }
+
+    /// Draws the visible instances of `mesh`, which must be the mesh the instances were created
+    /// for, with the current pipeline of `pass` and one indirect draw per level of detail. The
+    /// pipeline must use the vertex layout of the mesh at slot 0 and
+    /// [`Self::instance_buffer_layout`] at slot 1.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, mesh: &'a Mesh) {
+        pass.set_vertex_buffer(0, mesh.vertices.slice(..));
+        pass.set_index_buffer(mesh.indices.slice(..), wgpu::IndexFormat::Uint32);
+        let region_size = self.capacity as wgpu::BufferAddress * INSTANCE_SIZE;
+        for lod in 0..self.lod_count as wgpu::BufferAddress {
+            pass.set_vertex_buffer(
+                1,
+                self.visible_instances
+                    .slice(lod * region_size..(lod + 1) * region_size),
+            );
+            pass.draw_indexed_indirect(&self.args, lod * ARGS_SIZE);
+        }
+    }
+
+    /// Layout of [`Self::visible_instances`], with the columns of the model matrix at locations
+    /// 4 to 7.
+    pub fn instance_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Float32x4];
+        wgpu::VertexBufferLayout {
+            array_stride: INSTANCE_SIZE,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+
+    /// Model matrices of the instances which passed the last [`Self::cull`], in one region of
+    /// [`Self::capacity`] instances per level of detail.
+    pub fn visible_instances(&self) -> &wgpu::Buffer {
+        &self.visible_instances
+    }
+
+    /// [`wgpu::util::DrawIndexedIndirect`] arguments drawing the visible instances, one per
+    /// level of detail. The instance count of a level is the `u32` at offset 4 of its arguments.
+    pub fn indirect_buffer(&self) -> &wgpu::Buffer {
+        &self.args
+    }
+
+    /// Number of levels of detail, 1 for instances without levels.
+    pub fn lod_count(&self) -> u32 {
+        self.lod_count
+    }
+
+    /// Number of instances fitting into the region of a level of detail, grown by
+    /// [`Self::upload`].
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Reads back the number of instances which passed the last [`Self::cull`], e.g. for
+    /// statistics. Blocks until the GPU is done.
+    pub fn read_visible_count(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<u32, wgpu::BufferAsyncError> {
+        Ok(self.read_lod_counts(device, queue)?.iter().sum())
+    }
+
+    /// Reads back the number of visible instances of each level of detail of the last
+    /// [`Self::cull`]. Blocks until the GPU is done.
+    pub fn read_lod_counts(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+    ) -> Result<Vec<u32>, wgpu::BufferAsyncError> {
+        let size = ARGS_SIZE * self.lod_count as wgpu::BufferAddress;
+        let bytes = crate::readback::read_buffer(device, queue, &self.args, 0..size)?;
+        Ok(bytes
+            .chunks_exact(ARGS_SIZE as usize)
+            .map(|args| u32::from_le_bytes(args[4..8].try_into().unwrap()))
+            .collect())
+    }
+}
+
+fn create_visible_buffer(device: &wgpu::Device, capacity: u32, lod_count: u32) -> wgpu::Buffer {
+    crate::resource_log::create_buffer(
+        device,
+        &wgpu::BufferDescriptor {
+            label: Some("instancing visible instance buffer"),
+            size: (capacity * lod_count).max(1) as wgpu::BufferAddress * INSTANCE_SIZE,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        },
+    )
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    buffers: [&wgpu::Buffer; 4],
+) -> wgpu::BindGroup {
+    let entries = buffers
+        .iter()
+        .enumerate()
+        .map(|(binding, buffer)| buffer_entry(binding as u32, buffer))
+        .collect::<Vec<_>>();
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("instancing bind group"),
+        layout,
+        entries: &entries,
+    })
+}
+
+fn buffer_entry(binding: u32, buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry {
+        binding,
+        resource: buffer.as_entire_binding(),
+    }
+}
@@ -1,5 +1,87 @@
 //! wgpu-util is a utility crate for working with wgpu-rs.
 
+pub mod atlas;
+#[cfg(feature = "png")]
+pub mod batch;
+pub mod binding;
+pub mod blend;
+pub mod budget;
+pub mod camera;
+pub mod cluster;
+pub mod compare;
+pub mod compress;
+pub mod context;
+pub mod debug;
+pub mod depth;
+pub mod draw;
+pub mod dump;
+#[cfg(feature = "egui")]
+pub mod egui;
+pub mod flock;
+pub mod frame;
+pub mod gbuffer;
+pub mod graph;
+#[cfg(feature = "serde")]
+pub mod graph_description;
+pub mod grid;
+pub mod heightfield;
+pub mod indirect;
+#[cfg(feature = "winit")]
+pub mod init;
+pub mod inspect;
+pub mod instancing;
+pub mod line;
+pub mod lut;
+pub mod marching_cubes;
+pub mod material;
+pub mod mesh;
+pub mod mesh_debug;
+pub mod meshlet;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod overdraw;
+pub mod packing;
+#[cfg(feature = "lyon")]
+pub mod path;
+pub mod pipeline;
+pub mod point_cloud;
+pub mod post;
+pub mod primitive;
+pub mod probe;
+pub mod profiler;
+pub mod pulling;
+pub mod random;
+pub mod readback;
+pub mod rect_packer;
+pub mod reduce;
+pub mod registry;
+pub mod resolution;
+pub mod resource_log;
+#[cfg(feature = "png")]
+pub mod screenshot;
+#[cfg(feature = "serve")]
+pub mod serve;
+pub mod shader;
+#[cfg(feature = "simplify")]
+pub mod simplify;
+pub mod skybox;
+pub mod storage;
+pub mod submit;
+pub mod subpass;
+pub mod surface;
+pub mod terrain;
+pub mod testing;
+pub mod text;
+pub mod texture;
+pub mod tiled;
+#[cfg(feature = "debug")]
+pub mod timeline;
+#[cfg(feature = "trace")]
+pub mod trace;
+pub mod upload;
+pub mod validate;
+pub mod volume;
+
 /// Owned [`wgpu::Label`].
 pub type OwnedLabel = Option<String>;
 
@@ -26,66 +108,134 @@ pub trait DeviceExt {
 
 impl DeviceExt for wgpu::Device {
     fn create_buffer_init(&self, descriptor: &BufferInitDescriptor<'_>) -> wgpu::Buffer {
-        let unpadded_size = {
-            let contents_size = descriptor.contents.len() as wgpu::BufferAddress;
-            match descriptor.size {
-                None => contents_size,
-                Some(specified_size) => {
-                    assert!(
-                        specified_size >= contents_size,
-                        "specified size must at least be size of contents"
-                    );
-                    specified_size
-                }
+        #[cfg(feature = "trace")]
+        trace::record_create_buffer(descriptor);
+
+        create_buffer_init_untraced(self, descriptor)
+    }
+}
+
+fn create_buffer_init_untraced(
+    device: &wgpu::Device,
+    descriptor: &BufferInitDescriptor<'_>,
+) -> wgpu::Buffer {
+    let unpadded_size = {
+        let contents_size = descriptor.contents.len() as wgpu::BufferAddress;
+        match descriptor.size {
+            None => contents_size,
+            Some(specified_size) => {
+                assert!(
+                    specified_size >= contents_size,
+                    "specified size must at least be size of contents"
+                );
+                specified_size
             }
+        }
+    };
+
+    if unpadded_size == 0 {
+        let wgt_descriptor = wgpu::BufferDescriptor {
+            label: descriptor.label,
+            size: 0,
+            usage: descriptor.usage,
+            mapped_at_creation: false,
         };
 
-        if unpadded_size == 0 {
-            let wgt_descriptor = wgpu::BufferDescriptor {
-                label: descriptor.label,
-                size: 0,
-                usage: descriptor.usage,
-                mapped_at_creation: false,
-            };
+        crate::resource_log::create_buffer_untraced(device, &wgt_descriptor)
+    } else {
+        // Valid vulkan usage is
+        // 1. buffer size must be a multiple of COPY_BUFFER_ALIGNMENT.
+        // 2. buffer size must be greater than 0.
+        // Therefore we round the value up to the nearest multiple, and ensure it's at least COPY_BUFFER_ALIGNMENT.
+        let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+        let padded_size =
+            ((unpadded_size + align_mask) & !align_mask).max(wgpu::COPY_BUFFER_ALIGNMENT);
 
-            self.create_buffer(&wgt_descriptor)
-        } else {
-            // Valid vulkan usage is
-            // 1. buffer size must be a multiple of COPY_BUFFER_ALIGNMENT.
-            // 2. buffer size must be greater than 0.
-            // Therefore we round the value up to the nearest multiple, and ensure it's at least COPY_BUFFER_ALIGNMENT.
-            let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
-            let padded_size =
-                ((unpadded_size + align_mask) & !align_mask).max(wgpu::COPY_BUFFER_ALIGNMENT);
-
-            let wgt_descriptor = wgpu::BufferDescriptor {
-                label: descriptor.label,
-                size: padded_size,
-                usage: descriptor.usage,
-                mapped_at_creation: true,
-            };
+        let wgt_descriptor = wgpu::BufferDescriptor {
+            label: descriptor.label,
+            size: padded_size,
+            usage: descriptor.usage,
+            mapped_at_creation: true,
+        };
 
-            let buffer = self.create_buffer(&wgt_descriptor);
+        let buffer = crate::resource_log::create_buffer_untraced(device, &wgt_descriptor);
 
-            buffer.slice(..).get_mapped_range_mut()[..unpadded_size as usize]
-                .copy_from_slice(descriptor.contents);
-            buffer.unmap();
+        buffer.slice(..).get_mapped_range_mut()[..descriptor.contents.len()]
+            .copy_from_slice(descriptor.contents);
+        buffer.unmap();
 
-            buffer
-        }
+        buffer
     }
 }
 
+/// [`wgpu::Device::create_shader_module`] for WGSL `source`, recorded in the trace.
+pub(crate) fn create_shader_module(
+    device: &wgpu::Device,
+    label: wgpu::Label,
+    source: &str,
+) -> wgpu::ShaderModule {
+    #[cfg(feature = "trace")]
+    trace::record_shader_module(label, source);
+
+    device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label,
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    })
+}
+
+/// [`wgpu::ComputePass::dispatch_workgroups`], recorded in the trace under `label`.
+pub(crate) fn dispatch(pass: &mut wgpu::ComputePass, label: &str, workgroups: [u32; 3]) {
+    #[cfg(feature = "trace")]
+    trace::record_dispatch(Some(label), workgroups);
+    #[cfg(not(feature = "trace"))]
+    let _ = label;
+
+    pass.dispatch_workgroups(workgroups[0], workgroups[1], workgroups[2]);
+}
+
 /// Thin [`wgpu::Buffer`] wrapper with size.
 #[derive(Debug)]
 pub struct SizedBuffer {
     pub size: wgpu::BufferAddress,
     pub buffer: wgpu::Buffer,
+    id: binding::ResourceId,
+
+    /// `None` for buffers not created through [`Self::new_init`], whose writes are recorded
+    /// untracked.
+    #[cfg(feature = "trace")]
+    trace_id: Option<trace::ResourceId>,
 }
 
 impl SizedBuffer {
     pub fn new(size: wgpu::BufferAddress, buffer: wgpu::Buffer) -> Self {
-        Self { size, buffer }
+        Self {
+            size,
+            buffer,
+            id: binding::ResourceId::unique(),
+
+            #[cfg(feature = "trace")]
+            trace_id: None,
+        }
+    }
+
+    /// Creates a buffer with contents, sized like [`DeviceExt::create_buffer_init`].
+    pub fn new_init(device: &wgpu::Device, descriptor: &BufferInitDescriptor) -> Self {
+        let buffer = create_buffer_init_untraced(device, descriptor);
+        Self {
+            size: descriptor
+                .size
+                .unwrap_or(descriptor.contents.len() as wgpu::BufferAddress),
+            buffer,
+            id: binding::ResourceId::unique(),
+
+            #[cfg(feature = "trace")]
+            trace_id: Some(trace::record_create_buffer(descriptor)),
+        }
+    }
+
+    /// Identity of the buffer, see [`binding::ResourceId`].
+    pub fn id(&self) -> binding::ResourceId {
+        self.id
     }
 }
 
@@ -107,19 +257,75 @@ pub fn resize_write_buffer(
     let contents_size = descriptor.contents.len() as wgpu::BufferAddress;
     let enough_space = contents_size <= buffer.size;
     if enough_space {
+        #[cfg(feature = "trace")]
+        trace::record_write_buffer(buffer.trace_id, 0, descriptor.contents);
+
         queue.write_buffer(&buffer.buffer, 0, descriptor.contents);
         buffer
     } else {
-        let new = device.create_buffer_init(&BufferInitDescriptor {
-            label: descriptor.label,
-            contents: descriptor.contents,
-            size: None,
-            usage: descriptor.usage,
-        });
-        SizedBuffer::new(contents_size, new)
+        SizedBuffer::new_init(
+            device,
+            &BufferInitDescriptor {
+                label: descriptor.label,
+                contents: descriptor.contents,
+                size: None,
+                usage: descriptor.usage,
+            },
+        )
+    }
+}
+
+/// How a [`DynamicBuffer`] grows when uploaded contents don't fit.
+///
+/// The new size is never smaller than the contents.
+#[derive(Clone, Copy, Debug)]
+pub enum GrowthStrategy {
+    /// Exactly the size of the contents, for buffers which rarely change size.
+    Exact,
+    /// The current size times the factor, amortizing steadily growing contents.
+    Multiply(f32),
+    /// The size of the contents rounded up to a multiple of the chunk size in bytes.
+    FixedChunk(wgpu::BufferAddress),
+    /// Computes the new size from the current size and the size of the contents.
+    Custom(fn(wgpu::BufferAddress, wgpu::BufferAddress) -> wgpu::BufferAddress),
+}
+
+impl GrowthStrategy {
+    /// Size of a buffer of `size` bytes grown to fit `contents_size` bytes.
+    pub fn grow(
+        &self,
+        size: wgpu::BufferAddress,
+        contents_size: wgpu::BufferAddress,
+    ) -> wgpu::BufferAddress {
+        let grown = match *self {
+            Self::Exact => contents_size,
+            Self::Multiply(factor) => (size as f64 * factor as f64).ceil() as wgpu::BufferAddress,
+            Self::FixedChunk(chunk) => match chunk {
+                0 => contents_size,
+                _ => contents_size.div_ceil(chunk) * chunk,
+            },
+            Self::Custom(function) => function(size, contents_size),
+        };
+        grown.max(contents_size)
+    }
+}
+
+impl Default for GrowthStrategy {
+    /// Doubles the size.
+    fn default() -> Self {
+        Self::Multiply(2.0)
     }
 }
 
+/// Descriptor for [`DynamicBuffer::with_descriptor`].
+#[derive(Clone, Debug)]
+pub struct DynamicBufferDescriptor<'a> {
+    /// Initial contents, size and usages of the buffer.
+    pub buffer: BufferInitDescriptor<'a>,
+    /// How the buffer grows when uploaded contents don't fit.
+    pub growth: GrowthStrategy,
+}
+
 /// A [`wgpu::Buffer`] which dynamically grows based on the contents.
 #[derive(Debug)]
 pub struct DynamicBuffer {
@@ -128,39 +334,90 @@ pub struct DynamicBuffer {
     label: crate::OwnedLabel,
     size: wgpu::BufferAddress,
     usage: wgpu::BufferUsages,
+    growth: GrowthStrategy,
+    /// Reissued whenever `raw` is reallocated.
+    id: binding::ResourceId,
+
+    /// Set by [`Self::set_budget`].
+    allocation: Option<budget::BudgetAllocation>,
+
+    /// Where the buffer was created, reported by [`Self::assert_usage`].
+    #[cfg(debug_assertions)]
+    backtrace: std::backtrace::Backtrace,
+
+    #[cfg(feature = "trace")]
+    trace_id: trace::ResourceId,
 }
 
 impl DynamicBuffer {
-    const RESERVE: bool = true;
-
-    /// Create a new empty buffer.
+    /// Create a new empty buffer, growing with [`GrowthStrategy::default`].
     pub fn new(device: &wgpu::Device, descriptor: &wgpu::BufferDescriptor) -> Self {
-        let raw = device.create_buffer(descriptor);
+        // Padded like buffers created with contents, so growing can copy whole words.
+        let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+        let raw = crate::resource_log::create_buffer_untraced(
+            device,
+            &wgpu::BufferDescriptor {
+                size: (descriptor.size + align_mask) & !align_mask,
+                ..descriptor.clone()
+            },
+        );
 
         Self {
             raw,
             label: descriptor.label.map(|l| l.to_owned()),
             size: descriptor.size,
             usage: descriptor.usage,
+            growth: GrowthStrategy::default(),
+            id: binding::ResourceId::unique(),
+            allocation: None,
+
+            #[cfg(debug_assertions)]
+            backtrace: std::backtrace::Backtrace::capture(),
+
+            #[cfg(feature = "trace")]
+            trace_id: trace::record_create_buffer(&BufferInitDescriptor {
+                label: descriptor.label,
+                contents: &[],
+                size: Some(descriptor.size),
+                usage: descriptor.usage,
+            }),
         }
     }
 
-    /// Create a new buffer with contents.
+    /// Create a new buffer with contents, growing with [`GrowthStrategy::default`].
     pub fn new_init(device: &wgpu::Device, descriptor: &crate::BufferInitDescriptor) -> Self {
-        let raw = device.create_buffer_init(descriptor);
+        Self::with_descriptor(
+            device,
+            &DynamicBufferDescriptor {
+                buffer: descriptor.clone(),
+                growth: GrowthStrategy::default(),
+            },
+        )
+    }
 
-        let descriptor = wgpu::BufferDescriptor {
-            label: descriptor.label,
-            size: descriptor.contents.len() as wgpu::BufferAddress,
-            usage: descriptor.usage,
-            mapped_at_creation: false,
-        };
+    /// Create a new buffer with contents and growth strategy.
+    pub fn with_descriptor(device: &wgpu::Device, descriptor: &DynamicBufferDescriptor) -> Self {
+        let raw = create_buffer_init_untraced(device, &descriptor.buffer);
+        #[cfg(feature = "trace")]
+        let trace_id = trace::record_create_buffer(&descriptor.buffer);
 
+        let buffer = &descriptor.buffer;
         Self {
             raw,
-            label: descriptor.label.map(|l| l.to_owned()),
-            size: descriptor.size,
-            usage: descriptor.usage,
+            label: buffer.label.map(|l| l.to_owned()),
+            size: buffer
+                .size
+                .unwrap_or(buffer.contents.len() as wgpu::BufferAddress),
+            usage: buffer.usage,
+            growth: descriptor.growth,
+            id: binding::ResourceId::unique(),
+            allocation: None,
+
+            #[cfg(debug_assertions)]
+            backtrace: std::backtrace::Backtrace::capture(),
+
+            #[cfg(feature = "trace")]
+            trace_id,
         }
     }
 
@@ -182,27 +439,125 @@ impl DynamicBuffer {
         contents: &[u8],
     ) -> Result<(), wgpu::BufferAddress> {
         let contents_size = contents.len() as wgpu::BufferAddress;
-        if contents_size < self.size {
+        if contents_size <= self.size {
+            #[cfg(feature = "trace")]
+            trace::record_write_buffer(Some(self.trace_id), 0, contents);
+
             queue.write_buffer(&self.raw, 0, contents);
-            self.size = contents_size;
             Ok(())
         } else {
             Err(contents_size - self.size)
         }
     }
 
-    /// Allocates a new buffer, replaces the old one and uploades the contents using
-    /// [`wgpu::Device`].
+    /// Allocates a new buffer, sized by the [`GrowthStrategy`], replaces the old one and
+    /// uploades the contents using [`wgpu::Device`].
     pub fn upload_by_init(&mut self, device: &wgpu::Device, contents: &[u8]) {
-        device.create_buffer_init(&crate::BufferInitDescriptor {
+        let contents_size = contents.len() as wgpu::BufferAddress;
+        let size = self.growth.grow(self.size, contents_size);
+        self.track_reallocation(size);
+
+        let descriptor = crate::BufferInitDescriptor {
             label: self.label.as_deref(),
             contents,
             usage: self.usage,
-            size: match Self::RESERVE {
-                true => Some(reserve_function(self.size)),
-                false => None,
-            },
-        });
+            size: Some(size),
+        };
+        self.raw = create_buffer_init_untraced(device, &descriptor);
+        self.size = size;
+        self.id = binding::ResourceId::unique();
+
+        #[cfg(feature = "trace")]
+        {
+            self.trace_id = trace::record_create_buffer(&descriptor);
+        }
+    }
+
+    /// Writes `contents` at byte `offset` using [`wgpu::Queue`], keeping the rest of the
+    /// buffer.
+    ///
+    /// If the write runs past the end, the buffer is reallocated with its [`GrowthStrategy`] and
+    /// the old contents are copied over on the GPU, which needs
+    /// [`wgpu::BufferUsages::COPY_SRC`]. `offset` and the size of `contents` must be multiples of
+    /// [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    ///
+    /// The copy is submitted right away, so it carries over everything submitted and written
+    /// through `queue` before. Commands recorded against the old buffer but submitted later still
+    /// use the old buffer, and their results are lost.
+    ///
+    /// # Panics
+    ///
+    /// If growing a buffer without [`wgpu::BufferUsages::COPY_SRC`], in release builds too.
+    pub fn write_at(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        offset: wgpu::BufferAddress,
+        contents: &[u8],
+    ) {
+        let end = offset + contents.len() as wgpu::BufferAddress;
+        if end > self.size {
+            self.grow(device, queue, end);
+        }
+
+        #[cfg(feature = "trace")]
+        trace::record_write_buffer(Some(self.trace_id), offset, contents);
+
+        queue.write_buffer(&self.raw, offset, contents);
+    }
+
+    /// Reallocates the buffer to fit `required_size` bytes and copies the old contents over.
+    fn grow(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        required_size: wgpu::BufferAddress,
+    ) {
+        assert!(
+            self.usage.contains(wgpu::BufferUsages::COPY_SRC),
+            "buffer {:?} with usage {:?} can't grow while keeping its contents without COPY_SRC",
+            self.label,
+            self.usage
+        );
+        let size = self.growth.grow(self.size, required_size);
+        self.track_reallocation(size);
+
+        let descriptor = crate::BufferInitDescriptor {
+            label: self.label.as_deref(),
+            contents: &[],
+            usage: self.usage,
+            size: Some(size),
+        };
+        let raw = create_buffer_init_untraced(device, &descriptor);
+
+        // Buffers are padded to whole words, so the padding of the old contents fits.
+        let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+        let copy_size = (self.size + align_mask) & !align_mask;
+        if copy_size > 0 {
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("dynamic buffer grow encoder"),
+            });
+            encoder.copy_buffer_to_buffer(&self.raw, 0, &raw, 0, copy_size);
+            queue.submit(Some(encoder.finish()));
+        }
+        self.raw = raw;
+        self.size = size;
+        self.id = binding::ResourceId::unique();
+
+        // The copy isn't traced, replays start the grown buffer empty.
+        #[cfg(feature = "trace")]
+        {
+            self.trace_id = trace::record_create_buffer(&descriptor);
+        }
+    }
+
+    /// Moves the budget allocation over to a reallocation of `size` bytes.
+    fn track_reallocation(&mut self, size: wgpu::BufferAddress) {
+        if let Some(allocation) = self.allocation.take() {
+            let budget = allocation.budget().clone();
+            drop(allocation);
+            self.allocation = Some(budget.track(size));
+        }
     }
 
     /// Get a reference to the raw buffer.
@@ -210,14 +565,370 @@ impl DynamicBuffer {
         &self.raw
     }
 
+    /// Current size of the buffer in bytes.
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.size
+    }
+
+    /// Usages of the buffer.
+    pub fn usage(&self) -> wgpu::BufferUsages {
+        self.usage
+    }
+
+    /// Identity of the raw buffer, which changes when it's reallocated, see
+    /// [`binding::ResourceId`].
+    pub fn id(&self) -> binding::ResourceId {
+        self.id
+    }
+
+    /// How the buffer grows on the next reallocation.
+    pub fn growth(&self) -> GrowthStrategy {
+        self.growth
+    }
+
+    /// Changes how the buffer grows on the next reallocation.
+    pub fn set_growth(&mut self, growth: GrowthStrategy) {
+        self.growth = growth;
+    }
+
+    /// Accounts the buffer against `budget`, including reallocations by [`Self::upload`].
+    pub fn set_budget(&mut self, budget: &budget::GpuBudget) {
+        self.allocation = None;
+        self.allocation = Some(budget.track(self.size));
+    }
+
     /// Convert into raw buffer.
     pub fn into_raw(self) -> wgpu::Buffer {
         self.raw
     }
+
+    /// Panics if the buffer lacks `usage`, naming the buffer and where it was created.
+    ///
+    /// Only checked in debug builds. The creation backtrace is captured if `RUST_BACKTRACE` is
+    /// set.
+    #[track_caller]
+    pub fn assert_usage(&self, usage: wgpu::BufferUsages) {
+        #[cfg(debug_assertions)]
+        assert!(
+            self.usage.contains(usage),
+            "buffer {:?} is used as {:?} but was created with usage {:?} at:\n{}",
+            self.label,
+            usage,
+            self.usage,
+            self.backtrace
+        );
+        #[cfg(not(debug_assertions))]
+        let _ = usage;
+    }
+
+    /// Slice for [`wgpu::RenderPass::set_vertex_buffer`], checked with [`Self::assert_usage`].
+    #[track_caller]
+    pub fn vertex_slice<S: std::ops::RangeBounds<wgpu::BufferAddress>>(
+        &self,
+        bounds: S,
+    ) -> wgpu::BufferSlice<'_> {
+        self.assert_usage(wgpu::BufferUsages::VERTEX);
+        self.raw.slice(bounds)
+    }
+
+    /// Slice for [`wgpu::RenderPass::set_index_buffer`], checked with [`Self::assert_usage`].
+    #[track_caller]
+    pub fn index_slice<S: std::ops::RangeBounds<wgpu::BufferAddress>>(
+        &self,
+        bounds: S,
+    ) -> wgpu::BufferSlice<'_> {
+        self.assert_usage(wgpu::BufferUsages::INDEX);
+        self.raw.slice(bounds)
+    }
+
+    /// Binding of the whole buffer as uniform buffer, checked with [`Self::assert_usage`].
+    #[track_caller]
+    pub fn uniform_binding(&self) -> wgpu::BindingResource<'_> {
+        self.assert_usage(wgpu::BufferUsages::UNIFORM);
+        self.raw.as_entire_binding()
+    }
+
+    /// Binding of the whole buffer as storage buffer, checked with [`Self::assert_usage`].
+    #[track_caller]
+    pub fn storage_binding(&self) -> wgpu::BindingResource<'_> {
+        self.assert_usage(wgpu::BufferUsages::STORAGE);
+        self.raw.as_entire_binding()
+    }
+
+    /// The raw buffer for indirect draws and dispatches, checked with [`Self::assert_usage`].
+    #[track_caller]
+    pub fn indirect_buffer(&self) -> &wgpu::Buffer {
+        self.assert_usage(wgpu::BufferUsages::INDIRECT);
+        &self.raw
+    }
 }
 
-fn reserve_function(last_size: wgpu::BufferAddress) -> wgpu::BufferAddress {
-    last_size.pow(2)
+/// A [`DynamicBuffer`] of `T`s, counted and sliced in elements instead of bytes.
+#[cfg(feature = "bytemuck")]
+#[derive(Debug)]
+pub struct TypedBuffer<T: bytemuck::Pod> {
+    buffer: DynamicBuffer,
+    len: usize,
+    element: std::marker::PhantomData<T>,
+}
+
+#[cfg(feature = "bytemuck")]
+impl<T: bytemuck::Pod> TypedBuffer<T> {
+    const ELEMENT_SIZE: wgpu::BufferAddress = std::mem::size_of::<T>() as wgpu::BufferAddress;
+
+    /// Creates an empty buffer with room for `capacity` elements.
+    /// [`wgpu::BufferUsages::COPY_DST`] is added to `usage`.
+    pub fn new(
+        device: &wgpu::Device,
+        label: wgpu::Label,
+        capacity: usize,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        assert!(Self::ELEMENT_SIZE > 0, "elements must not be zero-sized");
+        let size = capacity as wgpu::BufferAddress * Self::ELEMENT_SIZE;
+        let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+        let buffer = DynamicBuffer::new(
+            device,
+            &wgpu::BufferDescriptor {
+                label,
+                size: (size + align_mask) & !align_mask,
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        Self {
+            buffer,
+            len: 0,
+            element: std::marker::PhantomData,
+        }
+    }
+
+    /// Creates a buffer holding `contents`. [`wgpu::BufferUsages::COPY_DST`] is added to `usage`.
+    pub fn new_init(
+        device: &wgpu::Device,
+        label: wgpu::Label,
+        contents: &[T],
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        assert!(Self::ELEMENT_SIZE > 0, "elements must not be zero-sized");
+        let contents: &[u8] = bytemuck::cast_slice(contents);
+        // Sized like padded uploads, so uploading as many elements again doesn't reallocate.
+        let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+        let buffer = DynamicBuffer::new_init(
+            device,
+            &BufferInitDescriptor {
+                label,
+                contents,
+                size: Some((contents.len() as wgpu::BufferAddress + align_mask) & !align_mask),
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+        Self {
+            buffer,
+            len: contents.len() / Self::ELEMENT_SIZE as usize,
+            element: std::marker::PhantomData,
+        }
+    }
+
+    /// Replaces the elements with `contents`, growing the buffer if needed, see
+    /// [`DynamicBuffer::upload`].
+    pub fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, contents: &[T]) {
+        self.buffer
+            .upload(device, queue, &padded_bytes(bytemuck::cast_slice(contents)));
+        self.len = contents.len();
+    }
+
+    /// Number of elements of the last upload.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of elements fitting into the buffer without growing it.
+    pub fn capacity(&self) -> usize {
+        (self.buffer.size() / Self::ELEMENT_SIZE) as usize
+    }
+
+    /// Slice of the elements in `range`, e.g. for
+    /// [`wgpu::RenderPass::set_vertex_buffer`]. Unbounded ends are at the first element and
+    /// [`Self::len`].
+    ///
+    /// Panics if `range` is empty or exceeds [`Self::len`].
+    #[track_caller]
+    pub fn slice<S: std::ops::RangeBounds<usize>>(&self, range: S) -> wgpu::BufferSlice<'_> {
+        self.buffer
+            .raw()
+            .slice(element_range(range, self.len, Self::ELEMENT_SIZE))
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn raw(&self) -> &wgpu::Buffer {
+        self.buffer.raw()
+    }
+
+    /// The underlying buffer, e.g. for its usage checked bindings.
+    pub fn dynamic(&self) -> &DynamicBuffer {
+        &self.buffer
+    }
+
+    /// Convert into the underlying buffer.
+    pub fn into_dynamic(self) -> DynamicBuffer {
+        self.buffer
+    }
+}
+
+/// A [`SizedBuffer`] of `T`s, counted and sliced in elements instead of bytes.
+///
+/// Uploads replace the buffer like [`resize_write_buffer`] if the elements don't fit, for
+/// buffers recreated wholesale. Use [`TypedBuffer`] to grow with a [`GrowthStrategy`].
+#[cfg(feature = "bytemuck")]
+#[derive(Debug)]
+pub struct TypedSizedBuffer<T: bytemuck::Pod> {
+    buffer: SizedBuffer,
+    len: usize,
+    element: std::marker::PhantomData<T>,
+
+    label: crate::OwnedLabel,
+    usage: wgpu::BufferUsages,
+}
+
+#[cfg(feature = "bytemuck")]
+impl<T: bytemuck::Pod> TypedSizedBuffer<T> {
+    const ELEMENT_SIZE: wgpu::BufferAddress = std::mem::size_of::<T>() as wgpu::BufferAddress;
+
+    /// Creates a buffer holding `contents`. [`wgpu::BufferUsages::COPY_DST`] is added to `usage`.
+    pub fn new_init(
+        device: &wgpu::Device,
+        label: wgpu::Label,
+        contents: &[T],
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        assert!(Self::ELEMENT_SIZE > 0, "elements must not be zero-sized");
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        let buffer = SizedBuffer::new_init(
+            device,
+            &BufferInitDescriptor {
+                label,
+                contents: &padded_bytes(bytemuck::cast_slice(contents)),
+                size: None,
+                usage,
+            },
+        );
+        Self {
+            buffer,
+            len: contents.len(),
+            element: std::marker::PhantomData,
+
+            label: label.map(|l| l.to_owned()),
+            usage,
+        }
+    }
+
+    /// Replaces the elements with `contents`, replacing the buffer if they don't fit, see
+    /// [`resize_write_buffer`].
+    pub fn upload(self, device: &wgpu::Device, queue: &wgpu::Queue, contents: &[T]) -> Self {
+        let buffer = resize_write_buffer(
+            device,
+            queue,
+            self.buffer,
+            &BufferResizeWriteDescriptor {
+                label: self.label.as_deref(),
+                contents: &padded_bytes(bytemuck::cast_slice(contents)),
+                usage: self.usage,
+            },
+        );
+        Self {
+            buffer,
+            len: contents.len(),
+            ..self
+        }
+    }
+
+    /// Number of elements of the last upload.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of elements fitting into the buffer without replacing it.
+    pub fn capacity(&self) -> usize {
+        (self.buffer.size / Self::ELEMENT_SIZE) as usize
+    }
+
+    /// Slice of the elements in `range`, see [`TypedBuffer::slice`].
+    #[track_caller]
+    pub fn slice<S: std::ops::RangeBounds<usize>>(&self, range: S) -> wgpu::BufferSlice<'_> {
+        self.buffer
+            .buffer
+            .slice(element_range(range, self.len, Self::ELEMENT_SIZE))
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn raw(&self) -> &wgpu::Buffer {
+        &self.buffer.buffer
+    }
+
+    /// The underlying buffer.
+    pub fn sized(&self) -> &SizedBuffer {
+        &self.buffer
+    }
+
+    /// Convert into the underlying buffer.
+    pub fn into_sized(self) -> SizedBuffer {
+        self.buffer
+    }
+}
+
+/// `bytes` padded with zeros to a multiple of [`wgpu::COPY_BUFFER_ALIGNMENT`], which queue
+/// writes require, e.g. for odd numbers of `u16` indices.
+#[cfg(feature = "bytemuck")]
+fn padded_bytes(bytes: &[u8]) -> std::borrow::Cow<'_, [u8]> {
+    let padding = bytes.len().wrapping_neg() % wgpu::COPY_BUFFER_ALIGNMENT as usize;
+    match padding {
+        0 => std::borrow::Cow::Borrowed(bytes),
+        _ => {
+            let mut padded = Vec::with_capacity(bytes.len() + padding);
+            padded.extend_from_slice(bytes);
+            padded.resize(bytes.len() + padding, 0);
+            std::borrow::Cow::Owned(padded)
+        }
+    }
+}
+
+/// Byte range of the elements in `range` out of `len` elements of `element_size` bytes.
+#[cfg(feature = "bytemuck")]
+#[track_caller]
+fn element_range(
+    range: impl std::ops::RangeBounds<usize>,
+    len: usize,
+    element_size: wgpu::BufferAddress,
+) -> std::ops::Range<wgpu::BufferAddress> {
+    use std::ops::Bound;
+
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(
+        start < end && end <= len,
+        "slice {}..{} is out of the {} elements of the buffer",
+        start,
+        end,
+        len
+    );
+    start as wgpu::BufferAddress * element_size..end as wgpu::BufferAddress * element_size
 }
 
 /// A [`wgpu::Buffer`] Pool (dynamic supply).
@@ -266,7 +977,7 @@ impl BufferPool {
             self.buffers.push(self.create_buffer(device, contents));
         }
         self.occupied += 1;
-        self.occupied
+        self.occupied - 1
     }
 
     /// Clears pool. Buffers are marked as vacant and reusable.
@@ -301,13 +1012,15 @@ impl BufferPool {
 
 impl BufferPool {
     fn create_buffer(&self, device: &wgpu::Device, contents: &[u8]) -> SizedBuffer {
-        let buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: self.label.as_deref(),
-            contents,
-            usage: self.usage,
-            size: None,
-        });
-        SizedBuffer::new(contents.len() as wgpu::BufferAddress, buffer)
+        SizedBuffer::new_init(
+            device,
+            &BufferInitDescriptor {
+                label: self.label.as_deref(),
+                contents,
+                usage: self.usage,
+                size: None,
+            },
+        )
     }
 }
 
@@ -318,3 +1031,30 @@ pub struct BufferPoolDescriptor<'a> {
     /// Usages for all buffer
     pub usage: wgpu::BufferUsages,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_pool_upload_returns_index() {
+        let Ok(context) = crate::testing::headless_context() else {
+            return;
+        };
+        let (device, queue) = (&context.device, &context.queue);
+        let mut pool = BufferPool::new(&BufferPoolDescriptor {
+            label: Some("pool"),
+            usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+        });
+
+        assert_eq!(pool.upload(device, queue, &[0; 4]), 0);
+        assert_eq!(pool.upload(device, queue, &[1; 8]), 1);
+        assert_eq!(pool.occupied(), 2);
+        assert!(pool.get(1).is_some());
+
+        pool.clear();
+        assert_eq!(pool.upload(device, queue, &[2; 4]), 0);
+        assert_eq!(pool.occupied(), 1);
+        assert_eq!(pool.size(), 2);
+    }
+}
@@ -0,0 +1,370 @@
+//! Drawing thick anti-aliased lines, e.g. for debug overlays and gizmos.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{CameraBuffer, CAMERA_SNIPPET},
+    pipeline::RenderPipelineBuilder,
+    shader::ShaderComposer,
+    BufferInitDescriptor, DeviceExt,
+};
+
+const PARAMS_SIZE: wgpu::BufferAddress = 16;
+
+/// Size of a segment in the instance buffer: start, end and both neighbors as `Float32x3`, the
+/// color as `Float32x4`, the width as `Float32` and the end modes as `Uint32`.
+pub const LINE_SEGMENT_SIZE: wgpu::BufferAddress = 72;
+
+/// Shape of the open ends of a line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineCap {
+    /// Ends exactly at the end point.
+    Butt,
+    /// Extends past the end point by half the width.
+    Square,
+    /// A half circle around the end point.
+    Round,
+}
+
+/// Shape of the corners between segments of a polyline.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LineJoin {
+    /// Segments end with butt caps, leaving a notch on the outside of corners.
+    None,
+    /// Edges are extended until they meet, up to the miter limit of the renderer.
+    Miter,
+    /// Segments end with round caps. Translucent lines are blended twice where they overlap.
+    Round,
+}
+
+/// Appearance of lines added to a [`LineBatch`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineStyle {
+    /// Width in pixels.
+    pub width: f32,
+    /// Straight alpha color.
+    pub color: [f32; 4],
+    pub cap: LineCap,
+    pub join: LineJoin,
+}
+
+impl Default for LineStyle {
+    fn default() -> Self {
+        Self {
+            width: 2.0,
+            color: [1.0; 4],
+            cap: LineCap::Butt,
+            join: LineJoin::Miter,
+        }
+    }
+}
+
+fn cap_mode(cap: LineCap) -> u32 {
+    match cap {
+        LineCap::Butt => 0,
+        LineCap::Square => 1,
+        LineCap::Round => 2,
+    }
+}
+
+fn join_mode(join: LineJoin) -> u32 {
+    match join {
+        LineJoin::None => 0,
+        LineJoin::Round => 2,
+        LineJoin::Miter => 3,
+    }
+}
+
+/// Line segments in world space collected on the CPU, drawn by [`LineRenderer`].
+#[derive(Clone, Debug, Default)]
+pub struct LineBatch {
+    instances: Vec<u8>,
+    len: u32,
+}
+
+impl LineBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a single segment from `start` to `end`, with caps at both ends.
+    pub fn line(&mut self, start: [f32; 3], end: [f32; 3], style: &LineStyle) {
+        let cap = cap_mode(style.cap);
+        self.push(start, end, start, end, style, cap, cap);
+    }
+
+    /// Adds segments connecting consecutive `points`, joined at the inner points. Closed
+    /// polylines also connect the last point to the first and have no caps.
+    pub fn polyline(&mut self, points: &[[f32; 3]], closed: bool, style: &LineStyle) {
+        let n = points.len();
+        if n < 2 {
+            return;
+        }
+        let segments = match closed {
+            true => n,
+            false => n - 1,
+        };
+        let join = join_mode(style.join);
+        let cap = cap_mode(style.cap);
+        for i in 0..segments {
+            let start = points[i];
+            let end = points[(i + 1) % n];
+            let (previous, start_mode) = match (i, closed) {
+                (0, false) => (start, cap),
+                _ => (points[(i + n - 1) % n], join),
+            };
+            let (next, end_mode) = match (i + 1 == segments, closed) {
+                (true, false) => (end, cap),
+                _ => (points[(i + 2) % n], join),
+            };
+            self.push(start, end, previous, next, style, start_mode, end_mode);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn push(
+        &mut self,
+        start: [f32; 3],
+        end: [f32; 3],
+        previous: [f32; 3],
+        next: [f32; 3],
+        style: &LineStyle,
+        start_mode: u32,
+        end_mode: u32,
+    ) {
+        for value in start
+            .into_iter()
+            .chain(end)
+            .chain(previous)
+            .chain(next)
+            .chain(style.color)
+            .chain([style.width])
+        {
+            self.instances.extend_from_slice(&value.to_le_bytes());
+        }
+        self.instances
+            .extend_from_slice(&(start_mode | end_mode << 2).to_le_bytes());
+        self.len += 1;
+    }
+
+    /// Number of segments.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Removes all segments, keeping the allocation for the next frame.
+    pub fn clear(&mut self) {
+        self.instances.clear();
+        self.len = 0;
+    }
+
+    /// The segments in the layout of [`LineRenderer`]'s instance buffer, [`LINE_SEGMENT_SIZE`]
+    /// bytes each.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.instances
+    }
+}
+
+/// Descriptor for [`LineRenderer::new`].
+#[derive(Clone, Debug)]
+pub struct LineRendererDescriptor {
+    /// Format of the color target, lines are alpha blended onto it.
+    pub format: wgpu::TextureFormat,
+    /// Depth state of the pass, e.g. [`crate::depth::read_only_less_equal`] for lines hidden by
+    /// geometry or `None` for overlays.
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub sample_count: u32,
+    /// Longest miter relative to half the line width, sharper corners are cut off.
+    pub miter_limit: f32,
+}
+
+impl Default for LineRendererDescriptor {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            depth_stencil: None,
+            sample_count: 1,
+            miter_limit: 4.0,
+        }
+    }
+}
+
+/// Draws the segments of [`LineBatch`]es as screen space quads seen by the camera of a
+/// [`CameraBuffer`].
+///
+/// Native line primitives are a single pixel wide. Here every segment is an instance of a quad
+/// expanded in the vertex shader to its width in pixels plus a pixel of anti-aliased edge, with
+/// coverage from the distance to the outline of the segment and its caps. Segments crossing the
+/// near plane are clipped against it.
+#[derive(Debug)]
+pub struct LineRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    instances: wgpu::Buffer,
+    capacity: u32,
+    len: u32,
+    miter_limit: f32,
+}
+
+impl LineRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        descriptor: &LineRendererDescriptor,
+    ) -> Self {
+        let mut composer = ShaderComposer::new();
+        composer.add_snippet(CAMERA_SNIPPET, crate::camera::camera_snippet(0, 0));
+        let shader = composer
+            .create_shader_module(
+                device,
+                Some("line shader"),
+                include_str!("shaders/line.wgsl"),
+            )
+            .expect("builtin snippets must compose");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("line bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ..camera.bind_group_layout_entry()
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("line params buffer"),
+            contents: &params_bytes([1.0, 1.0], descriptor.miter_limit),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("line bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline =
+            RenderPipelineBuilder::new(&shader, &[&bind_group_layout], descriptor.format)
+                .label("line pipeline")
+                .vertex_buffers(&[wgpu::VertexBufferLayout {
+                    array_stride: LINE_SEGMENT_SIZE,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Float32x3,
+                        2 => Float32x3,
+                        3 => Float32x3,
+                        4 => Float32x4,
+                        5 => Float32,
+                        6 => Uint32,
+                    ],
+                }])
+                .blend(crate::blend::STRAIGHT_ALPHA)
+                .depth_stencil(descriptor.depth_stencil.clone())
+                .sample_count(descriptor.sample_count)
+                .build(device);
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            instances: Self::create_instances(device, 0),
+            capacity: 0,
+            len: 0,
+            miter_limit: descriptor.miter_limit,
+        }
+    }
+
+    fn create_instances(device: &wgpu::Device, capacity: u32) -> wgpu::Buffer {
+        crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("line instance buffer"),
+                // Keep the buffer valid for empty batches.
+                size: capacity.max(1) as wgpu::BufferAddress * LINE_SEGMENT_SIZE,
+                usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        )
+    }
+
+    /// Uploads the segments of `batch` and the size of the `width` x `height` target in pixels,
+    /// growing the instance buffer if needed.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        batch: &LineBatch,
+        width: u32,
+        height: u32,
+    ) {
+        if batch.len() > self.capacity {
+            self.capacity = batch.len().next_power_of_two();
+            self.instances = Self::create_instances(device, self.capacity);
+        }
+        if !batch.is_empty() {
+            queue.write_buffer(&self.instances, 0, batch.as_bytes());
+        }
+        self.len = batch.len();
+        queue.write_buffer(
+            &self.params_buffer,
+            0,
+            &params_bytes([width as f32, height as f32], self.miter_limit),
+        );
+    }
+
+    /// Draws the segments of the last [`Self::prepare`] into `pass`, which must match the
+    /// targets of the descriptor.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        if self.len == 0 {
+            return;
+        }
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_vertex_buffer(0, self.instances.slice(..));
+        pass.draw(0..6, 0..self.len);
+    }
+
+    /// Number of segments drawn by [`Self::draw`].
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+fn params_bytes(viewport: [f32; 2], miter_limit: f32) -> Vec<u8> {
+    let mut bytes: Vec<u8> = viewport
+        .into_iter()
+        .chain([miter_limit])
+        .flat_map(f32::to_le_bytes)
+        .collect();
+    bytes.resize(PARAMS_SIZE as usize, 0);
+    bytes
+}
@@ -0,0 +1,258 @@
+//! Lookup textures baked on the CPU, e.g. transfer functions and color-over-life gradients.
+//!
+//! Sample them with a linearly filtering, clamping sampler at `u = t`. Texel `i` holds the value
+//! at its center `t = (i + 0.5) / resolution`.
+
+use std::num::NonZeroU32;
+
+use crate::packing::f16_from_f32;
+
+/// A color of a gradient at `position`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ColorStop {
+    pub position: f32,
+    /// Linear RGBA, may exceed `1.0`.
+    pub color: [f32; 4],
+}
+
+/// A value of a curve at `time`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+}
+
+/// Descriptor for [`GradientTexture`] and [`CurveTexture`].
+#[derive(Clone, Debug)]
+pub struct LutDescriptor<'a> {
+    pub label: wgpu::Label<'a>,
+    /// Number of texels.
+    pub resolution: u32,
+    /// Create an Nx1 2D texture instead of a 1D texture, for backends without 1D textures.
+    pub as_2d: bool,
+    /// Usages in addition to [`wgpu::TextureUsages::TEXTURE_BINDING`] and
+    /// [`wgpu::TextureUsages::COPY_DST`].
+    pub usage: wgpu::TextureUsages,
+}
+
+impl Default for LutDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: None,
+            resolution: 256,
+            as_2d: false,
+            usage: wgpu::TextureUsages::empty(),
+        }
+    }
+}
+
+/// [`wgpu::TextureFormat::Rgba16Float`] texture interpolating between color stops.
+#[derive(Debug)]
+pub struct GradientTexture {
+    lut: Lut,
+}
+
+impl GradientTexture {
+    /// Bakes `stops` into a new texture. Stops don't have to be sorted. Colors are interpolated
+    /// linearly and clamped to the outermost stops.
+    pub fn bake(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        stops: &[ColorStop],
+        descriptor: &LutDescriptor<'_>,
+    ) -> Self {
+        assert!(!stops.is_empty(), "gradient must have at least one stop");
+        let mut stops = stops.to_vec();
+        stops.sort_by(|a, b| a.position.total_cmp(&b.position));
+
+        let texels: Vec<u8> = (0..descriptor.resolution)
+            .flat_map(|i| {
+                let t = (i as f32 + 0.5) / descriptor.resolution as f32;
+                let (a, b, s) = interpolation(&stops, t, |stop| stop.position);
+                let mut color = [0.0; 4];
+                for (c, color) in color.iter_mut().enumerate() {
+                    *color = a.color[c] + (b.color[c] - a.color[c]) * s;
+                }
+                color
+            })
+            .flat_map(|c| f16_from_f32(c).to_le_bytes())
+            .collect();
+
+        Self {
+            lut: Lut::new(
+                device,
+                queue,
+                descriptor,
+                wgpu::TextureFormat::Rgba16Float,
+                &texels,
+            ),
+        }
+    }
+
+    /// Get a reference to the texture.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.lut.texture
+    }
+
+    /// Get a reference to the default view.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.lut.view
+    }
+
+    /// Dimension of the view, [`wgpu::TextureViewDimension::D1`] unless baked
+    /// [`LutDescriptor::as_2d`].
+    pub fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        self.lut.view_dimension
+    }
+
+    /// Number of texels.
+    pub fn resolution(&self) -> u32 {
+        self.lut.resolution
+    }
+}
+
+/// [`wgpu::TextureFormat::R16Float`] texture interpolating between keyframes.
+#[derive(Debug)]
+pub struct CurveTexture {
+    lut: Lut,
+}
+
+impl CurveTexture {
+    /// Bakes `keyframes` into a new texture. Keyframes don't have to be sorted. Values are
+    /// interpolated linearly and clamped to the outermost keyframes.
+    pub fn bake(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        keyframes: &[Keyframe],
+        descriptor: &LutDescriptor<'_>,
+    ) -> Self {
+        assert!(
+            !keyframes.is_empty(),
+            "curve must have at least one keyframe"
+        );
+        let mut keyframes = keyframes.to_vec();
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+        let texels: Vec<u8> = (0..descriptor.resolution)
+            .flat_map(|i| {
+                let t = (i as f32 + 0.5) / descriptor.resolution as f32;
+                let (a, b, s) = interpolation(&keyframes, t, |keyframe| keyframe.time);
+                f16_from_f32(a.value + (b.value - a.value) * s).to_le_bytes()
+            })
+            .collect();
+
+        Self {
+            lut: Lut::new(
+                device,
+                queue,
+                descriptor,
+                wgpu::TextureFormat::R16Float,
+                &texels,
+            ),
+        }
+    }
+
+    /// Get a reference to the texture.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.lut.texture
+    }
+
+    /// Get a reference to the default view.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.lut.view
+    }
+
+    /// Dimension of the view, [`wgpu::TextureViewDimension::D1`] unless baked
+    /// [`LutDescriptor::as_2d`].
+    pub fn view_dimension(&self) -> wgpu::TextureViewDimension {
+        self.lut.view_dimension
+    }
+
+    /// Number of texels.
+    pub fn resolution(&self) -> u32 {
+        self.lut.resolution
+    }
+}
+
+#[derive(Debug)]
+struct Lut {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    view_dimension: wgpu::TextureViewDimension,
+    resolution: u32,
+}
+
+impl Lut {
+    fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        descriptor: &LutDescriptor<'_>,
+        format: wgpu::TextureFormat,
+        texels: &[u8],
+    ) -> Self {
+        assert!(
+            descriptor.resolution > 0,
+            "resolution must be greater than 0"
+        );
+
+        let (dimension, view_dimension) = match descriptor.as_2d {
+            true => (wgpu::TextureDimension::D2, wgpu::TextureViewDimension::D2),
+            false => (wgpu::TextureDimension::D1, wgpu::TextureViewDimension::D1),
+        };
+        let size = wgpu::Extent3d {
+            width: descriptor.resolution,
+            height: 1,
+            depth_or_array_layers: 1,
+        };
+        let texture = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: descriptor.label,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension,
+                format,
+                usage: descriptor.usage
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST,
+            },
+        );
+        queue.write_texture(
+            texture.as_image_copy(),
+            texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(texels.len() as u32),
+                rows_per_image: None,
+            },
+            size,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(view_dimension),
+            ..Default::default()
+        });
+
+        Self {
+            texture,
+            view,
+            view_dimension,
+            resolution: descriptor.resolution,
+        }
+    }
+}
+
+/// Returns the keys surrounding `t` in sorted `keys` and the interpolation factor between them.
+fn interpolation<T>(keys: &[T], t: f32, position: impl Fn(&T) -> f32) -> (&T, &T, f32) {
+    let next = keys.partition_point(|key| position(key) <= t);
+    if next == 0 {
+        return (&keys[0], &keys[0], 0.0);
+    }
+    if next == keys.len() {
+        return (&keys[next - 1], &keys[next - 1], 0.0);
+    }
+
+    let (a, b) = (&keys[next - 1], &keys[next]);
+    let span = position(b) - position(a);
+    (a, b, (t - position(a)) / span)
+}
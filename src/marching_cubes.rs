@@ -0,0 +1,435 @@
+//! Extracting iso-surfaces of density fields into triangle meshes on the GPU.
+
+use std::num::NonZeroU64;
+
+use crate::{shader::ShaderComposer, BufferInitDescriptor, DeviceExt};
+
+const WORKGROUP_SIZE: u32 = 256;
+const PARAMS_SIZE: wgpu::BufferAddress = 48;
+/// Indirect draw arguments and the required vertex count.
+const ARGS_SIZE: wgpu::BufferAddress = 20;
+
+/// Size of a vertex written by [`MarchingCubes`]: the position and the normal as `Float32x3`,
+/// each padded to 16 bytes.
+pub const MARCHING_CUBES_VERTEX_SIZE: wgpu::BufferAddress = 32;
+
+/// Corners of each cube face, counter-clockwise seen from outside. Corner `i` is offset by
+/// `i & 1` along x, `i >> 1 & 1` along y and `i >> 2` along z.
+const FACES: [[u32; 4]; 6] = [
+    [0, 4, 6, 2],
+    [1, 3, 7, 5],
+    [0, 1, 5, 4],
+    [2, 6, 7, 3],
+    [0, 2, 3, 1],
+    [4, 5, 7, 6],
+];
+
+/// Index of the edge between corners `a` and `b`, matching `edge_corners` of the shader.
+fn edge_index(a: u32, b: u32) -> u32 {
+    let axis = (a ^ b).trailing_zeros();
+    let start = a.min(b);
+    let index = match axis {
+        0 => (start >> 1 & 1) | (start >> 2) << 1,
+        1 => (start & 1) | (start >> 2) << 1,
+        _ => start & 3,
+    };
+    axis * 4 + index
+}
+
+/// Edges crossed by the triangles of all 256 cube configurations, as 4 bit indices packed into
+/// two words per configuration and terminated by 15.
+///
+/// The iso-contour on each face separates the inside corners, also diagonal ones, so neighboring
+/// cells agree on shared faces and meshes are watertight. Contour loops are fanned into
+/// triangles, counter-clockwise seen from outside.
+fn triangle_table() -> Vec<u32> {
+    let mut table = Vec::with_capacity(512);
+    for configuration in 0..256u32 {
+        let inside = |corner: u32| configuration >> corner & 1 == 1;
+
+        // Links the edge where each inside run of a face is left to where it was entered.
+        let mut next = [None; 12];
+        for face in FACES {
+            let edge = |k: usize| edge_index(face[k], face[(k + 1) % 4]);
+            for k in 0..4 {
+                if !inside(face[k]) || inside(face[(k + 1) % 4]) {
+                    continue;
+                }
+                let mut j = (k + 3) % 4;
+                while inside(face[j]) || !inside(face[(j + 1) % 4]) {
+                    j = (j + 3) % 4;
+                }
+                next[edge(k) as usize] = Some(edge(j));
+            }
+        }
+
+        let mut edges = Vec::new();
+        let mut visited = [false; 12];
+        for start in 0..12 {
+            if visited[start] || next[start].is_none() {
+                continue;
+            }
+            let mut contour = vec![start as u32];
+            visited[start] = true;
+            let mut edge = next[start].unwrap();
+            while edge as usize != start {
+                visited[edge as usize] = true;
+                contour.push(edge);
+                edge = next[edge as usize].expect("contours must be closed");
+            }
+            for i in 1..contour.len() - 1 {
+                edges.extend([contour[0], contour[i + 1], contour[i]]);
+            }
+        }
+        assert!(
+            edges.len() <= 15,
+            "cube configurations have at most 5 triangles"
+        );
+
+        let mut words = [u32::MAX; 2];
+        for (i, edge) in edges.into_iter().enumerate() {
+            let shift = (i % 8) * 4;
+            words[i / 8] = words[i / 8] & !(15 << shift) | edge << shift;
+        }
+        table.extend(words);
+    }
+    table
+}
+
+/// Density field extracted by [`MarchingCubes`].
+#[derive(Clone, Copy, Debug)]
+pub enum DensitySource<'a> {
+    /// 3D view of a float texture, whose first channel is loaded without filtering.
+    Texture(&'a wgpu::TextureView),
+    /// Storage buffer of `f32`s, x varying fastest, then y, then z.
+    Buffer(&'a wgpu::Buffer),
+}
+
+/// Descriptor for [`MarchingCubes::new`].
+#[derive(Clone, Debug)]
+pub struct MarchingCubesDescriptor<'a> {
+    pub density: DensitySource<'a>,
+    /// Number of density samples along each axis, at least 2.
+    pub dimensions: [u32; 3],
+    /// Position of the first sample in world space.
+    pub bounds_min: [f32; 3],
+    /// Position of the last sample in world space.
+    pub bounds_max: [f32; 3],
+    /// Density of the surface. Samples above it are inside.
+    pub iso_value: f32,
+    /// Capacity of the vertex buffer, triangles beyond it are dropped.
+    pub max_vertices: u32,
+}
+
+/// Compute passes extracting the iso-surface of a density field into a vertex buffer and
+/// indirect draw arguments, without reading anything back.
+///
+/// Every cell of 2x2x2 samples counts its vertices, the counts of a workgroup are summed with the
+/// `wgpu_util::prefix_sum` snippet and a single atomic per workgroup reserves its range of the
+/// vertex buffer, so the output is compact but ordered differently from run to run. Vertices
+/// form a non-indexed triangle list, see [`Self::vertex_buffer_layout`].
+#[derive(Debug)]
+pub struct MarchingCubes {
+    generate_pipeline: wgpu::ComputePipeline,
+    finish_pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    params: wgpu::Buffer,
+    vertices: wgpu::Buffer,
+    args: wgpu::Buffer,
+    initial_args: wgpu::Buffer,
+
+    dimensions: [u32; 3],
+    bounds: [[f32; 3]; 2],
+    iso_value: f32,
+    max_vertices: u32,
+    groups: u32,
+    row_length: u32,
+}
+
+impl MarchingCubes {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        descriptor: &MarchingCubesDescriptor<'_>,
+    ) -> Self {
+        assert!(
+            descriptor.dimensions.iter().all(|&d| d >= 2),
+            "density must have at least 2 samples along each axis"
+        );
+        let (density_binding, density_load) = match descriptor.density {
+            DensitySource::Texture(_) => (
+                "var density_texture: texture_3d<f32>;",
+                "textureLoad(density_texture, vec3<i32>(q), 0).r",
+            ),
+            DensitySource::Buffer(_) => (
+                "var<storage, read> density_values: array<f32>;",
+                "density_values[q.x + params.dimensions.x * (q.y + params.dimensions.y * q.z)]",
+            ),
+        };
+        let source = include_str!("shaders/marching_cubes.wgsl")
+            .replace("{density_binding}", density_binding)
+            .replace("{density_load}", density_load);
+        let shader = ShaderComposer::new()
+            .create_shader_module(device, Some("marching cubes shader"), &source)
+            .expect("builtin snippets must compose");
+
+        let max_vertices = descriptor.max_vertices.max(3);
+        let [x, y, z] = descriptor.dimensions;
+        let groups = ((x - 1) * (y - 1) * (z - 1)).div_ceil(WORKGROUP_SIZE);
+        let row_length = groups.min(device.limits().max_compute_workgroups_per_dimension);
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("marching cubes params buffer"),
+                size: PARAMS_SIZE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        let table = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("marching cubes triangle table buffer"),
+            contents: &triangle_table()
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .collect::<Vec<_>>(),
+            size: None,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let vertices = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("marching cubes vertex buffer"),
+                size: max_vertices as wgpu::BufferAddress * MARCHING_CUBES_VERTEX_SIZE,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::VERTEX
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            },
+        );
+        let args = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("marching cubes indirect buffer"),
+                size: ARGS_SIZE,
+                usage: wgpu::BufferUsages::STORAGE
+                    | wgpu::BufferUsages::INDIRECT
+                    | wgpu::BufferUsages::COPY_DST
+                    | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            },
+        );
+        let initial_args = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("marching cubes initial indirect buffer"),
+            contents: &[0u32, 1, 0, 0, 0]
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect::<Vec<_>>(),
+            size: None,
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+
+        let compute = wgpu::ShaderStages::COMPUTE;
+        let storage_entry = |binding, read_only, size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: compute,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(size),
+            },
+            count: None,
+        };
+        let density_entry = match descriptor.density {
+            DensitySource::Texture(_) => wgpu::BindGroupLayoutEntry {
+                binding: 4,
+                visibility: compute,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D3,
+                    multisampled: false,
+                },
+                count: None,
+            },
+            DensitySource::Buffer(_) => storage_entry(4, true, 4),
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("marching cubes bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: compute,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+                storage_entry(1, true, 8),
+                storage_entry(2, false, MARCHING_CUBES_VERTEX_SIZE),
+                storage_entry(3, false, ARGS_SIZE),
+                density_entry,
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("marching cubes bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                buffer_entry(0, &params),
+                buffer_entry(1, &table),
+                buffer_entry(2, &vertices),
+                buffer_entry(3, &args),
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: match descriptor.density {
+                        DensitySource::Texture(view) => wgpu::BindingResource::TextureView(view),
+                        DensitySource::Buffer(buffer) => buffer.as_entire_binding(),
+                    },
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("marching cubes pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, entry_point| {
+            crate::resource_log::create_compute_pipeline(
+                device,
+                &wgpu::ComputePipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    module: &shader,
+                    entry_point,
+                },
+            )
+        };
+
+        let mut marching_cubes = Self {
+            generate_pipeline: pipeline("marching cubes generate pipeline", "generate"),
+            finish_pipeline: pipeline("marching cubes finish pipeline", "finish"),
+            bind_group,
+            params,
+            vertices,
+            args,
+            initial_args,
+
+            dimensions: descriptor.dimensions,
+            bounds: [descriptor.bounds_min, descriptor.bounds_max],
+            iso_value: descriptor.iso_value,
+            max_vertices,
+            groups,
+            row_length,
+        };
+        marching_cubes.set_iso_value(queue, descriptor.iso_value);
+        marching_cubes
+    }
+
+    /// Records passes replacing the contents of [`Self::vertices`] and [`Self::indirect_buffer`]
+    /// with the iso-surface of the current density.
+    pub fn extract(&self, encoder: &mut wgpu::CommandEncoder) {
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::copy("marching cubes reset")
+                .read("initial args", &self.initial_args)
+                .write("args", &self.args)
+        });
+        encoder.copy_buffer_to_buffer(&self.initial_args, 0, &self.args, 0, ARGS_SIZE);
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::dispatch("marching cubes pass")
+                .write("vertices", &self.vertices)
+                .write("args", &self.args)
+        });
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("marching cubes pass"),
+        });
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_pipeline(&self.generate_pipeline);
+        crate::dispatch(
+            &mut pass,
+            "marching cubes generate",
+            [self.row_length, self.groups.div_ceil(self.row_length), 1],
+        );
+        pass.set_pipeline(&self.finish_pipeline);
+        crate::dispatch(&mut pass, "marching cubes finish", [1, 1, 1]);
+    }
+
+    pub fn iso_value(&self) -> f32 {
+        self.iso_value
+    }
+
+    /// Changes the density of the surface and uploads it using [`wgpu::Queue`].
+    pub fn set_iso_value(&mut self, queue: &wgpu::Queue, iso_value: f32) {
+        self.iso_value = iso_value;
+        queue.write_buffer(&self.params, 0, &self.params_bytes());
+    }
+
+    fn params_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PARAMS_SIZE as usize);
+        for value in self.dimensions {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.iso_value.to_le_bytes());
+        for (corner, word) in self.bounds.iter().zip([self.max_vertices, self.row_length]) {
+            for value in corner {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes
+    }
+
+    /// Vertices of the last extraction, see [`Self::vertex_buffer_layout`].
+    pub fn vertices(&self) -> &wgpu::Buffer {
+        &self.vertices
+    }
+
+    /// [`wgpu::util::DrawIndirect`] arguments drawing the vertices of the last extraction,
+    /// followed by the vertex count all triangles required as `u32`, which exceeds
+    /// [`Self::max_vertices`] if triangles were dropped.
+    pub fn indirect_buffer(&self) -> &wgpu::Buffer {
+        &self.args
+    }
+
+    pub fn max_vertices(&self) -> u32 {
+        self.max_vertices
+    }
+
+    /// Layout of [`Self::vertices`], with the position at location 0 and the normal at
+    /// location 1.
+    pub fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x3,
+                offset: 16,
+                shader_location: 1,
+            },
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: MARCHING_CUBES_VERTEX_SIZE,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+
+    /// Draws the last extraction into `pass` with its current pipeline, which must use
+    /// [`Self::vertex_buffer_layout`] at slot 0.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_vertex_buffer(0, self.vertices.slice(..));
+        pass.draw_indirect(&self.args, 0);
+    }
+}
+
+fn buffer_entry(binding: u32, buffer: &wgpu::Buffer) -> wgpu::BindGroupEntry<'_> {
+    wgpu::BindGroupEntry {
+        binding,
+        resource: buffer.as_entire_binding(),
+    }
+}
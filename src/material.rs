@@ -0,0 +1,184 @@
+//! Materials tying pipelines, parameters and cached textures together.
+
+use std::hash::Hash;
+
+use crate::{
+    binding::{BindGroupKey, BindGroupPool, ResourceId},
+    texture::TextureCache,
+    DynamicBuffer,
+};
+
+/// Descriptor for [`Material`].
+#[derive(Clone, Debug)]
+pub struct MaterialDescriptor<'a, P, T> {
+    /// Debug label of the parameter buffer.
+    pub label: wgpu::Label<'a>,
+    /// Key of the pipeline permutation the material is drawn with.
+    pub permutation: P,
+    /// Initial parameters. Later parameters must have at most the same size.
+    pub params: &'a [u8],
+    /// Binding of the uniform buffer holding the parameters.
+    pub params_binding: u32,
+    /// Bindings of textures, by key into a [`TextureCache`].
+    pub textures: &'a [(u32, T)],
+}
+
+/// Key of material bind groups in a [`BindGroupPool`]: the ids of the parameter buffer and the
+/// bound textures.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialBindGroupKey {
+    params: ResourceId,
+    textures: Vec<(u32, ResourceId)>,
+}
+
+/// Parameters, textures and a pipeline permutation key, bound as one bind group.
+///
+/// Pipelines are looked up by the user with [`Self::permutation`], e.g. from a
+/// `HashMap<P, wgpu::RenderPipeline>`. Each frame, [`Self::prepare`] resolves the textures through
+/// a [`TextureCache`] and acquires the bind group from a [`BindGroupPool`], so materials sharing
+/// resources share bind groups. [`Self::bind`] then sets it on a render pass.
+#[derive(Debug)]
+pub struct Material<P, T> {
+    permutation: P,
+    params: DynamicBuffer,
+    params_binding: u32,
+    textures: Vec<(u32, T)>,
+
+    /// Bind group acquired by the last [`Self::prepare`], if all textures were resident.
+    bind_group: Option<BindGroupKey<MaterialBindGroupKey>>,
+}
+
+impl<P: Clone + Eq + Hash, T: Clone + Eq + Hash> Material<P, T> {
+    /// Creates a new material, uploading its parameters.
+    pub fn new(device: &wgpu::Device, descriptor: &MaterialDescriptor<'_, P, T>) -> Self {
+        let params = DynamicBuffer::new_init(
+            device,
+            &crate::BufferInitDescriptor {
+                label: descriptor.label,
+                contents: descriptor.params,
+                size: None,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            },
+        );
+
+        Self {
+            permutation: descriptor.permutation.clone(),
+            params,
+            params_binding: descriptor.params_binding,
+            textures: descriptor.textures.to_vec(),
+
+            bind_group: None,
+        }
+    }
+
+    /// Key of the pipeline permutation.
+    pub fn permutation(&self) -> &P {
+        &self.permutation
+    }
+
+    /// Uploads new parameters using [`wgpu::Queue`].
+    ///
+    /// `params` must not exceed the size of the initial parameters.
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: &[u8]) {
+        self.params
+            .try_upload(queue, params)
+            .expect("params must not exceed the size of the initial params");
+    }
+
+    /// Replaces the texture bound at `binding`.
+    pub fn set_texture(&mut self, binding: u32, key: T) {
+        match self.textures.iter_mut().find(|(b, _)| *b == binding) {
+            Some((_, texture)) => *texture = key,
+            None => self.textures.push((binding, key)),
+        }
+    }
+
+    /// Resolves the textures and acquires the bind group from `bind_groups`. `layout` must
+    /// contain the bindings of the parameters, the textures and `samplers`.
+    ///
+    /// Bind groups are keyed by the parameters and textures only, so all materials prepared
+    /// with one pool must use the same `layout` and `samplers`.
+    ///
+    /// Returns whether the material is ready to be bound. Textures which aren't resident are
+    /// reloaded by the cache, so a material may become ready in a later frame.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        textures: &mut TextureCache<T>,
+        samplers: &[(u32, &wgpu::Sampler)],
+        bind_groups: &mut BindGroupPool<MaterialBindGroupKey>,
+    ) -> bool {
+        // Touch every texture first, so all missing ones get reloaded at once.
+        let resident = self.textures.iter().fold(true, |resident, (_, key)| {
+            textures.get(key).is_some() && resident
+        });
+        if !resident {
+            self.bind_group = None;
+            return false;
+        }
+
+        let mut key = MaterialBindGroupKey {
+            params: self.params.id(),
+            textures: Vec::with_capacity(self.textures.len()),
+        };
+        let mut entries = vec![wgpu::BindGroupEntry {
+            binding: self.params_binding,
+            resource: self.params.uniform_binding(),
+        }];
+        entries.extend(self.textures.iter().map(|(binding, texture)| {
+            let texture = textures.peek(texture).expect("texture was just resolved");
+            key.textures.push((*binding, texture.id()));
+            wgpu::BindGroupEntry {
+                binding: *binding,
+                resource: wgpu::BindingResource::TextureView(texture.view()),
+            }
+        }));
+        entries.extend(
+            samplers
+                .iter()
+                .map(|(binding, sampler)| wgpu::BindGroupEntry {
+                    binding: *binding,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                }),
+        );
+
+        self.bind_group = Some(bind_groups.acquire(
+            device,
+            key,
+            &wgpu::BindGroupDescriptor {
+                label: Some("material bind group"),
+                layout,
+                entries: &entries,
+            },
+        ));
+        true
+    }
+
+    /// Sets the bind group acquired by the last [`Self::prepare`] at `index`.
+    ///
+    /// Returns `false` without binding anything if the material wasn't ready.
+    pub fn bind<'a>(
+        &self,
+        pass: &mut wgpu::RenderPass<'a>,
+        index: u32,
+        bind_groups: &'a BindGroupPool<MaterialBindGroupKey>,
+    ) -> bool {
+        match self
+            .bind_group
+            .as_ref()
+            .and_then(|key| bind_groups.get(key))
+        {
+            Some(bind_group) => {
+                pass.set_bind_group(index, bind_group, &[]);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The uniform buffer holding the parameters.
+    pub fn params(&self) -> &DynamicBuffer {
+        &self.params
+    }
+}
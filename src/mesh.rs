@@ -0,0 +1,790 @@
+//! Preparing mesh data on the CPU before uploading it.
+
+use std::{collections::VecDeque, ops::Range};
+
+use crate::{
+    packing::{pack_snorm16, unpack_snorm16},
+    BufferInitDescriptor, DeviceExt,
+};
+
+/// Axis aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// The box containing nothing, neutral to [`Self::union`].
+    pub const EMPTY: Self = Self {
+        min: [f32::INFINITY; 3],
+        max: [f32::NEG_INFINITY; 3],
+    };
+
+    /// Smallest box containing all `points`. Empty if there are no points.
+    pub fn from_points(points: &[[f32; 3]]) -> Self {
+        points
+            .iter()
+            .fold(Self::EMPTY, |aabb, p| aabb.union_point(*p))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..3).any(|i| self.min[i] > self.max[i])
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: [0, 1, 2].map(|i| self.min[i].min(other.min[i])),
+            max: [0, 1, 2].map(|i| self.max[i].max(other.max[i])),
+        }
+    }
+
+    pub fn union_point(&self, point: [f32; 3]) -> Self {
+        self.union(&Self {
+            min: point,
+            max: point,
+        })
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| (self.min[i] + self.max[i]) * 0.5)
+    }
+
+    pub fn extent(&self) -> [f32; 3] {
+        [0, 1, 2].map(|i| self.max[i] - self.min[i])
+    }
+}
+
+/// Size of a [`quantize_vertices`] vertex in bytes, half of `f32` positions and normals.
+pub const QUANTIZED_VERTEX_SIZE: wgpu::BufferAddress = 12;
+
+/// Vertex attributes of [`quantize_vertices`]: the position as `Snorm16x4` within the bounds and
+/// the octahedral normal as `Snorm16x2`. Decode them with the `wgpu_util::quantization` snippet.
+pub fn quantized_attributes(
+    position_location: u32,
+    normal_location: u32,
+) -> [wgpu::VertexAttribute; 2] {
+    [
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Snorm16x4,
+            offset: 0,
+            shader_location: position_location,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Snorm16x2,
+            offset: 8,
+            shader_location: normal_location,
+        },
+    ]
+}
+
+/// Vertices quantized by [`quantize_vertices`].
+#[derive(Clone, Debug)]
+pub struct QuantizedVertices {
+    /// Bounds the positions are quantized within, needed for decoding.
+    pub bounds: Aabb,
+    /// Vertices of [`QUANTIZED_VERTEX_SIZE`] bytes.
+    pub vertices: Vec<u8>,
+}
+
+/// Quantizes positions to 16 bit within their bounds and encodes normals octahedrally.
+///
+/// Positions lose precision relative to the size of the mesh, so large meshes should be split.
+/// `normals` must have the same length as `positions`.
+pub fn quantize_vertices(positions: &[[f32; 3]], normals: &[[f32; 3]]) -> QuantizedVertices {
+    assert_eq!(
+        positions.len(),
+        normals.len(),
+        "every position must have a normal"
+    );
+
+    let bounds = Aabb::from_points(positions);
+    let mut vertices = Vec::with_capacity(positions.len() * QUANTIZED_VERTEX_SIZE as usize);
+    for (position, normal) in positions.iter().zip(normals) {
+        let [x, y, z] = quantize_position(*position, &bounds);
+        for component in [x, y, z, 0] {
+            vertices.extend_from_slice(&component.to_le_bytes());
+        }
+        for component in encode_octahedral(*normal) {
+            vertices.extend_from_slice(&component.to_le_bytes());
+        }
+    }
+    QuantizedVertices { bounds, vertices }
+}
+
+/// Maps `position` within `bounds` to snorm16.
+pub fn quantize_position(position: [f32; 3], bounds: &Aabb) -> [i16; 3] {
+    let extent = bounds.extent();
+    [0, 1, 2].map(|i| {
+        let t = match extent[i] > 0.0 {
+            true => (position[i] - bounds.min[i]) / extent[i],
+            false => 0.5,
+        };
+        pack_snorm16(t * 2.0 - 1.0)
+    })
+}
+
+pub fn dequantize_position(quantized: [i16; 3], bounds: &Aabb) -> [f32; 3] {
+    let extent = bounds.extent();
+    [0, 1, 2].map(|i| bounds.min[i] + (unpack_snorm16(quantized[i]) * 0.5 + 0.5) * extent[i])
+}
+
+/// Encodes a unit vector by projecting it onto an octahedron unfolded into a square.
+pub fn encode_octahedral(normal: [f32; 3]) -> [i16; 2] {
+    let [x, y, z] = normal;
+    let l1 = x.abs() + y.abs() + z.abs();
+    if l1 == 0.0 {
+        return [0, 0];
+    }
+    let (x, y, z) = (x / l1, y / l1, z / l1);
+    // Fold the lower hemisphere over the diagonals.
+    let (x, y) = match z < 0.0 {
+        true => (
+            (1.0 - y.abs()) * sign_not_zero(x),
+            (1.0 - x.abs()) * sign_not_zero(y),
+        ),
+        false => (x, y),
+    };
+    [pack_snorm16(x), pack_snorm16(y)]
+}
+
+pub fn decode_octahedral(encoded: [i16; 2]) -> [f32; 3] {
+    let (x, y) = (unpack_snorm16(encoded[0]), unpack_snorm16(encoded[1]));
+    let z = 1.0 - x.abs() - y.abs();
+    let t = (-z).max(0.0);
+    let (x, y) = (x - t * sign_not_zero(x), y - t * sign_not_zero(y));
+    let length = (x * x + y * y + z * z).sqrt();
+    [x / length, y / length, z / length]
+}
+
+fn sign_not_zero(value: f32) -> f32 {
+    match value >= 0.0 {
+        true => 1.0,
+        false => -1.0,
+    }
+}
+
+/// Vertex and index buffers of an indexed triangle list.
+#[derive(Debug)]
+pub struct Mesh {
+    pub vertices: wgpu::Buffer,
+    pub indices: wgpu::Buffer,
+    pub vertex_count: u32,
+    pub index_count: u32,
+    pub bounds: Option<Aabb>,
+}
+
+/// Descriptor for [`Mesh::new`].
+#[derive(Clone, Debug)]
+pub struct MeshDescriptor<'a> {
+    pub label: wgpu::Label<'a>,
+    /// Vertices of `vertex_stride` bytes each.
+    pub vertices: &'a [u8],
+    pub vertex_stride: wgpu::BufferAddress,
+    pub indices: &'a [u32],
+    pub bounds: Option<Aabb>,
+    /// Reorder triangles and vertices with [`optimize_mesh`] before uploading, given the
+    /// position of every vertex.
+    pub optimize: Option<&'a [[f32; 3]]>,
+    /// Additional usages of both buffers.
+    pub usage: wgpu::BufferUsages,
+}
+
+impl Mesh {
+    pub fn new(device: &wgpu::Device, descriptor: &MeshDescriptor<'_>) -> Self {
+        assert!(
+            descriptor.vertex_stride > 0
+                && (descriptor.vertices.len() as wgpu::BufferAddress)
+                    .is_multiple_of(descriptor.vertex_stride),
+            "vertices must be a multiple of the vertex stride"
+        );
+        let vertex_count = descriptor.vertices.len() / descriptor.vertex_stride as usize;
+
+        let optimized;
+        let remapped;
+        let (vertices, indices) = match descriptor.optimize {
+            Some(positions) => {
+                assert_eq!(
+                    positions.len(),
+                    vertex_count,
+                    "every vertex must have a position"
+                );
+                optimized = optimize_mesh(descriptor.indices, positions);
+                remapped = remap_vertices(
+                    descriptor.vertices,
+                    descriptor.vertex_stride as usize,
+                    &optimized.remap,
+                );
+                (remapped.as_slice(), optimized.indices.as_slice())
+            }
+            None => (descriptor.vertices, descriptor.indices),
+        };
+
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: descriptor.label,
+            contents: vertices,
+            size: None,
+            usage: descriptor.usage | wgpu::BufferUsages::VERTEX,
+        });
+        let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: descriptor.label,
+            contents: &indices
+                .iter()
+                .flat_map(|i| i.to_le_bytes())
+                .collect::<Vec<_>>(),
+            size: None,
+            usage: descriptor.usage | wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            vertices: vertex_buffer,
+            indices: index_buffer,
+            vertex_count: (vertices.len() / descriptor.vertex_stride as usize) as u32,
+            index_count: indices.len() as u32,
+            bounds: descriptor.bounds,
+        }
+    }
+
+    /// Binds the buffers at vertex buffer slot 0 and draws all triangles.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, instances: Range<u32>) {
+        pass.set_vertex_buffer(0, self.vertices.slice(..));
+        pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint32);
+        pass.draw_indexed(0..self.index_count, 0, instances);
+    }
+}
+
+/// Indices and vertex order produced by [`optimize_mesh`].
+#[derive(Clone, Debug)]
+pub struct OptimizedMesh {
+    /// Indices into the reordered vertices.
+    pub indices: Vec<u32>,
+    /// Index of the original vertex of every reordered vertex. Unreferenced vertices are
+    /// dropped.
+    pub remap: Vec<u32>,
+}
+
+/// [`optimize_vertex_cache`], [`optimize_overdraw`] and [`optimize_vertex_fetch`] for vertices
+/// at `positions`. Apply the remap to the vertices with [`remap_vertices`].
+pub fn optimize_mesh(indices: &[u32], positions: &[[f32; 3]]) -> OptimizedMesh {
+    let indices = optimize_vertex_cache(indices, positions.len());
+    let mut indices = optimize_overdraw(&indices, positions, CACHE_SIZE);
+    let remap = optimize_vertex_fetch(&mut indices, positions.len());
+    OptimizedMesh { indices, remap }
+}
+
+/// Simulated post-transform cache size of [`optimize_vertex_cache`].
+const CACHE_SIZE: usize = 32;
+
+/// Reorders triangles to reuse recently transformed vertices, using Tom Forsyth's linear-speed
+/// vertex cache optimization.
+pub fn optimize_vertex_cache(indices: &[u32], vertex_count: usize) -> Vec<u32> {
+    assert!(
+        indices.len().is_multiple_of(3),
+        "indices must be a triangle list"
+    );
+    let triangle_count = indices.len() / 3;
+
+    // Triangles adjacent to each vertex, shrinking as triangles are emitted.
+    let mut offsets = vec![0; vertex_count + 1];
+    for &index in indices {
+        offsets[index as usize + 1] += 1;
+    }
+    for i in 0..vertex_count {
+        offsets[i + 1] += offsets[i];
+    }
+    let mut remaining: Vec<u32> = (0..vertex_count)
+        .map(|v| (offsets[v + 1] - offsets[v]) as u32)
+        .collect();
+    let mut adjacency = vec![0u32; indices.len()];
+    let mut fill = offsets.clone();
+    for (i, &index) in indices.iter().enumerate() {
+        adjacency[fill[index as usize]] = (i / 3) as u32;
+        fill[index as usize] += 1;
+    }
+
+    let mut cache_position = vec![None; vertex_count];
+    let mut vertex_scores: Vec<f32> = (0..vertex_count)
+        .map(|v| vertex_score(None, remaining[v]))
+        .collect();
+    let mut emitted = vec![false; triangle_count];
+
+    let mut output = Vec::with_capacity(indices.len());
+    let mut cache: Vec<u32> = Vec::with_capacity(CACHE_SIZE + 3);
+    let mut next_candidate = 0;
+    let mut best = None;
+
+    while output.len() < indices.len() {
+        let triangle = match best.take() {
+            Some(triangle) => triangle,
+            None => {
+                // No cached vertex has triangles left, continue with the next in input order.
+                while emitted[next_candidate] {
+                    next_candidate += 1;
+                }
+                next_candidate
+            }
+        };
+        emitted[triangle] = true;
+        let vertices = &indices[triangle * 3..triangle * 3 + 3];
+        output.extend_from_slice(vertices);
+
+        for &v in vertices {
+            let v = v as usize;
+            let adjacent = &mut adjacency[offsets[v]..offsets[v] + remaining[v] as usize];
+            if let Some(i) = adjacent.iter().position(|&t| t as usize == triangle) {
+                let last = adjacent.len() - 1;
+                adjacent.swap(i, last);
+                remaining[v] -= 1;
+            }
+        }
+
+        // Move the triangle's vertices to the front of the cache.
+        let mut new_cache: Vec<u32> = vertices.to_vec();
+        new_cache.extend(cache.iter().filter(|v| !vertices.contains(v)));
+        for &v in new_cache.iter().skip(CACHE_SIZE) {
+            cache_position[v as usize] = None;
+            vertex_scores[v as usize] = vertex_score(None, remaining[v as usize]);
+        }
+        new_cache.truncate(CACHE_SIZE);
+        cache = new_cache;
+
+        for (position, &v) in cache.iter().enumerate() {
+            cache_position[v as usize] = Some(position);
+            vertex_scores[v as usize] = vertex_score(Some(position), remaining[v as usize]);
+        }
+
+        // Rescore the triangles of cached vertices and pick the best one.
+        let mut best_score = -1.0;
+        for &v in &cache {
+            let v = v as usize;
+            for &t in &adjacency[offsets[v]..offsets[v] + remaining[v] as usize] {
+                let t = t as usize;
+                let score = indices[t * 3..t * 3 + 3]
+                    .iter()
+                    .map(|&v| vertex_scores[v as usize])
+                    .sum();
+                if score > best_score {
+                    best_score = score;
+                    best = Some(t);
+                }
+            }
+        }
+    }
+
+    output
+}
+
+fn vertex_score(cache_position: Option<usize>, remaining: u32) -> f32 {
+    if remaining == 0 {
+        return -1.0;
+    }
+
+    let cache_score = match cache_position {
+        None => 0.0,
+        // The last triangle's vertices get a fixed score, so its neighbors aren't favored too
+        // much over other cached triangles.
+        Some(position) if position < 3 => 0.75,
+        Some(position) => {
+            let scale = 1.0 / (CACHE_SIZE - 3) as f32;
+            (1.0 - (position - 3) as f32 * scale).powf(1.5)
+        }
+    };
+    // Favor vertices with few triangles left, to finish them off.
+    let valence_score = 2.0 * (remaining as f32).powf(-0.5);
+    cache_score + valence_score
+}
+
+/// Reorders vertices in the order they're first referenced, improving locality of vertex
+/// fetches. Rewrites `indices` and returns the original index of every new vertex.
+pub fn optimize_vertex_fetch(indices: &mut [u32], vertex_count: usize) -> Vec<u32> {
+    let mut new_index = vec![u32::MAX; vertex_count];
+    let mut remap = Vec::new();
+    for index in indices {
+        let new = &mut new_index[*index as usize];
+        if *new == u32::MAX {
+            *new = remap.len() as u32;
+            remap.push(*index);
+        }
+        *index = *new;
+    }
+    remap
+}
+
+/// Reorders vertices of `stride` bytes according to a remap of [`optimize_vertex_fetch`].
+pub fn remap_vertices(vertices: &[u8], stride: usize, remap: &[u32]) -> Vec<u8> {
+    remap
+        .iter()
+        .flat_map(|&v| &vertices[v as usize * stride..(v as usize + 1) * stride])
+        .copied()
+        .collect()
+}
+
+/// Reorders clusters of triangles so outward facing ones come first, reducing overdraw.
+///
+/// Call it after [`optimize_vertex_cache`]. Clusters are split where the cache simulation of
+/// `cache_size` vertices misses all vertices of a triangle, so cache efficiency is mostly kept.
+pub fn optimize_overdraw(indices: &[u32], positions: &[[f32; 3]], cache_size: usize) -> Vec<u32> {
+    assert!(
+        indices.len().is_multiple_of(3),
+        "indices must be a triangle list"
+    );
+
+    // Split into clusters at triangles missing the cache completely.
+    let mut clusters = vec![0];
+    let mut cache: VecDeque<u32> = VecDeque::with_capacity(cache_size + 1);
+    for (t, triangle) in indices.chunks_exact(3).enumerate() {
+        let mut misses = 0;
+        for &v in triangle {
+            if !cache.contains(&v) {
+                misses += 1;
+                cache.push_back(v);
+                if cache.len() > cache_size {
+                    cache.pop_front();
+                }
+            }
+        }
+        if misses == 3 && t > 0 {
+            clusters.push(t);
+        }
+    }
+    clusters.push(indices.len() / 3);
+
+    let triangle = |t: usize| [0, 1, 2].map(|i| positions[indices[t * 3 + i] as usize]);
+    let mesh_centroid = {
+        let sum = indices
+            .iter()
+            .map(|&v| positions[v as usize])
+            .fold([0.0; 3], |sum, p| [0, 1, 2].map(|i| sum[i] + p[i]));
+        sum.map(|c| c / indices.len().max(1) as f32)
+    };
+
+    // Sort by how much the cluster faces away from the mesh's center.
+    let mut keyed: Vec<(f32, Range<usize>)> = clusters
+        .windows(2)
+        .map(|range| {
+            let (mut centroid, mut normal, mut area) = ([0.0; 3], [0.0; 3], 0.0);
+            for t in range[0]..range[1] {
+                let [a, b, c] = triangle(t);
+                let e1 = [0, 1, 2].map(|i| b[i] - a[i]);
+                let e2 = [0, 1, 2].map(|i| c[i] - a[i]);
+                let n = [
+                    e1[1] * e2[2] - e1[2] * e2[1],
+                    e1[2] * e2[0] - e1[0] * e2[2],
+                    e1[0] * e2[1] - e1[1] * e2[0],
+                ];
+                let triangle_area = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+                for i in 0..3 {
+                    centroid[i] += (a[i] + b[i] + c[i]) / 3.0 * triangle_area;
+                    normal[i] += n[i];
+                }
+                area += triangle_area;
+            }
+            let key = match area > 0.0 {
+                true => (0..3)
+                    .map(|i| (centroid[i] / area - mesh_centroid[i]) * normal[i])
+                    .sum(),
+                false => 0.0,
+            };
+            (key, range[0]..range[1])
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    keyed
+        .into_iter()
+        .flat_map(|(_, triangles)| &indices[triangles.start * 3..triangles.end * 3])
+        .copied()
+        .collect()
+}
+
+/// Average number of simulated FIFO cache misses per triangle, between 0.5 for ideal meshes and 3.
+pub fn average_cache_miss_ratio(indices: &[u32], vertex_count: usize, cache_size: usize) -> f32 {
+    // Time at which each vertex entered the cache.
+    let mut entered = vec![None; vertex_count];
+    let mut time = 0usize;
+    for &v in indices {
+        let cached = entered[v as usize].is_some_and(|t| time - t < cache_size);
+        if !cached {
+            entered[v as usize] = Some(time);
+            time += 1;
+        }
+    }
+    time as f32 / (indices.len() / 3).max(1) as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn normalize(v: [f32; 3]) -> [f32; 3] {
+        let length = (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        v.map(|c| c / length)
+    }
+
+    fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+        a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+    }
+
+    /// Flat grid of `n` x `n` quads, two triangles each.
+    fn grid(n: u32) -> (Vec<[f32; 3]>, Vec<u32>) {
+        let positions = (0..=n)
+            .flat_map(|y| (0..=n).map(move |x| [x as f32, y as f32, 0.0]))
+            .collect();
+        let indices = (0..n)
+            .flat_map(|y| (0..n).map(move |x| y * (n + 1) + x))
+            .flat_map(|i| [i, i + 1, i + n + 2, i, i + n + 2, i + n + 1])
+            .collect();
+        (positions, indices)
+    }
+
+    /// The triangles of `indices` in a deterministic pseudo-random order.
+    fn shuffled(indices: &[u32]) -> Vec<u32> {
+        let mut triangles: Vec<&[u32]> = indices.chunks_exact(3).collect();
+        let mut state = 0x2545_f491_u32;
+        for i in (1..triangles.len()).rev() {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            triangles.swap(i, state as usize % (i + 1));
+        }
+        triangles.concat()
+    }
+
+    /// Triangles of `indices`, sorted so permutations of the same triangles compare equal.
+    fn sorted_triangles(indices: &[u32]) -> Vec<[u32; 3]> {
+        let mut triangles: Vec<[u32; 3]> = indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]])
+            .collect();
+        triangles.sort_unstable();
+        triangles
+    }
+
+    #[test]
+    fn vertex_cache_optimization_reorders_triangles_and_lowers_acmr() {
+        let (positions, indices) = grid(32);
+        for input in [indices.clone(), shuffled(&indices)] {
+            let optimized = optimize_vertex_cache(&input, positions.len());
+            assert_eq!(sorted_triangles(&optimized), sorted_triangles(&input));
+
+            let before = average_cache_miss_ratio(&input, positions.len(), 16);
+            let after = average_cache_miss_ratio(&optimized, positions.len(), 16);
+            assert!(after <= before, "ACMR went from {} to {}", before, after);
+            assert!(after < 0.8, "ACMR of {} is far from ideal", after);
+        }
+    }
+
+    #[test]
+    fn overdraw_optimization_moves_whole_triangles() {
+        let (positions, indices) = grid(16);
+        // Bend the grid into a half pipe, so clusters face different directions.
+        let positions: Vec<[f32; 3]> = positions
+            .iter()
+            .map(|p| {
+                let angle = p[0] / 16.0 * std::f32::consts::PI;
+                [angle.cos() * 8.0, p[1], angle.sin() * 8.0]
+            })
+            .collect();
+        let input = shuffled(&indices);
+        let optimized = optimize_overdraw(&input, &positions, 16);
+        assert_ne!(optimized, input, "clusters should be reordered");
+        assert_eq!(sorted_triangles(&optimized), sorted_triangles(&input));
+    }
+
+    #[test]
+    fn vertex_fetch_remap_is_a_bijection() {
+        let (positions, indices) = grid(8);
+        let input = shuffled(&indices);
+        let mut optimized = input.clone();
+        let remap = optimize_vertex_fetch(&mut optimized, positions.len());
+
+        let mut sorted = remap.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..positions.len() as u32).collect::<Vec<_>>());
+        let restored: Vec<u32> = optimized.iter().map(|&i| remap[i as usize]).collect();
+        assert_eq!(restored, input);
+
+        // Vertices are numbered in the order they're first referenced.
+        let mut next = 0;
+        for &i in &optimized {
+            assert!(i <= next);
+            if i == next {
+                next += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn optimized_meshes_keep_their_triangles() {
+        let (positions, indices) = grid(32);
+        for input in [indices.clone(), shuffled(&indices)] {
+            let optimized = optimize_mesh(&input, &positions);
+
+            let mut sorted = optimized.remap.clone();
+            sorted.sort_unstable();
+            assert_eq!(sorted, (0..positions.len() as u32).collect::<Vec<_>>());
+
+            let restored: Vec<u32> = optimized
+                .indices
+                .iter()
+                .map(|&i| optimized.remap[i as usize])
+                .collect();
+            assert_eq!(sorted_triangles(&restored), sorted_triangles(&input));
+
+            let before = average_cache_miss_ratio(&input, positions.len(), 16);
+            let after = average_cache_miss_ratio(&optimized.indices, positions.len(), 16);
+            assert!(after <= before, "ACMR went from {} to {}", before, after);
+        }
+    }
+
+    #[test]
+    fn remapped_vertices_follow_the_remap() {
+        let vertices: Vec<u8> = (0..12).collect();
+        assert_eq!(
+            remap_vertices(&vertices, 4, &[2, 0, 1]),
+            [8, 9, 10, 11, 0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn positions_round_trip_within_half_a_step() {
+        let bounds = Aabb {
+            min: [-3.0, 10.0, 0.25],
+            max: [5.0, 10.5, 0.25],
+        };
+        let extent = bounds.extent();
+        for i in 0..=100 {
+            let t = i as f32 / 100.0;
+            let position = [0, 1, 2].map(|a| bounds.min[a] + t * extent[a]);
+            let decoded = dequantize_position(quantize_position(position, &bounds), &bounds);
+            for a in 0..3 {
+                // One snorm16 step covers 1 / 65534 of the extent.
+                let bound = 0.5 * extent[a] / 65534.0 + 1e-6 * position[a].abs();
+                assert!(
+                    (decoded[a] - position[a]).abs() <= bound,
+                    "axis {} of {:?} decoded to {:?}",
+                    a,
+                    position,
+                    decoded
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_corners_and_flat_axes_are_exact() {
+        let bounds = Aabb::from_points(&[[-1.0, 2.0, 7.0], [4.0, 2.0, 9.0]]);
+        assert_eq!(quantize_position(bounds.min, &bounds), [-32767, 0, -32767]);
+        assert_eq!(quantize_position(bounds.max, &bounds), [32767, 0, 32767]);
+        assert_eq!(
+            dequantize_position(quantize_position(bounds.min, &bounds), &bounds),
+            bounds.min
+        );
+        assert_eq!(
+            dequantize_position(quantize_position(bounds.max, &bounds), &bounds),
+            bounds.max
+        );
+
+        let point = Aabb::from_points(&[[1.5, -2.0, 0.0]]);
+        assert_eq!(quantize_position(point.min, &point), [0, 0, 0]);
+        assert_eq!(
+            dequantize_position(quantize_position(point.min, &point), &point),
+            point.min
+        );
+    }
+
+    #[test]
+    fn octahedral_normals_round_trip() {
+        let mut normals = vec![
+            // Poles.
+            [0.0, 0.0, 1.0],
+            [0.0, 0.0, -1.0],
+            // The z = 0 seam, where the hemispheres are folded together.
+            [1.0, 0.0, 0.0],
+            [-1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.6, -0.8, 0.0],
+            [-0.6, 0.8, 0.0],
+            normalize([0.6, -0.8, 1e-4]),
+            normalize([0.6, -0.8, -1e-4]),
+            normalize([-1.0, -1.0, -1e-4]),
+        ];
+        for i in 0..64 {
+            let (theta, phi) = (i as f32 * 0.37, i as f32 * 0.11 - 3.0);
+            normals.push([
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            ]);
+        }
+
+        for normal in normals {
+            let decoded = decode_octahedral(encode_octahedral(normal));
+            assert!(
+                dot(decoded, normal) > 1.0 - 1e-6,
+                "{:?} decoded to {:?}",
+                normal,
+                decoded
+            );
+        }
+    }
+
+    #[test]
+    fn poles_use_the_square_center_and_corners() {
+        assert_eq!(encode_octahedral([0.0, 0.0, 1.0]), [0, 0]);
+        assert_eq!(encode_octahedral([0.0, 0.0, -1.0]), [32767, 32767]);
+        for corner in [
+            [32767, 32767],
+            [-32767, 32767],
+            [32767, -32767],
+            [-32767, -32767],
+        ] {
+            assert_eq!(decode_octahedral(corner), [0.0, 0.0, -1.0]);
+        }
+    }
+
+    #[test]
+    fn vertices_match_their_attributes() {
+        let positions = [[0.0, 0.0, 0.0], [2.0, 1.0, -4.0], [1.0, 0.5, -2.0]];
+        let normals = [
+            [0.0, 0.0, 1.0],
+            [0.0, -1.0, 0.0],
+            normalize([1.0, 1.0, -1.0]),
+        ];
+        let quantized = quantize_vertices(&positions, &normals);
+        assert_eq!(
+            quantized.vertices.len(),
+            positions.len() * QUANTIZED_VERTEX_SIZE as usize
+        );
+
+        let [position, normal] = quantized_attributes(3, 7);
+        assert_eq!((position.shader_location, normal.shader_location), (3, 7));
+        assert_eq!(normal.offset, position.offset + position.format.size());
+        assert_eq!(normal.offset + normal.format.size(), QUANTIZED_VERTEX_SIZE);
+
+        let read = |vertex: &[u8], offset: u64, count: usize| -> Vec<i16> {
+            (0..count)
+                .map(|i| {
+                    let at = offset as usize + i * 2;
+                    i16::from_le_bytes([vertex[at], vertex[at + 1]])
+                })
+                .collect()
+        };
+        for (i, vertex) in quantized
+            .vertices
+            .chunks_exact(QUANTIZED_VERTEX_SIZE as usize)
+            .enumerate()
+        {
+            let p = read(vertex, position.offset, 4);
+            assert_eq!(p[3], 0, "the padding component is zero");
+            let decoded = dequantize_position([p[0], p[1], p[2]], &quantized.bounds);
+            for a in 0..3 {
+                assert!((decoded[a] - positions[i][a]).abs() < 1e-3);
+            }
+
+            let n = read(vertex, normal.offset, 2);
+            assert!(dot(decode_octahedral([n[0], n[1]]), normals[i]) > 1.0 - 1e-6);
+        }
+    }
+}
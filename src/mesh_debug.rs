@@ -0,0 +1,386 @@
+//! Visualizing the wireframe, normals and tangents of meshes for debugging.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{CameraBuffer, CAMERA_SNIPPET},
+    mesh::Mesh,
+    pulling::{PullingDescriptor, PullingError},
+    shader::ShaderComposer,
+    BufferInitDescriptor, DeviceExt,
+};
+
+const PARAMS_SIZE: wgpu::BufferAddress = 64;
+
+const MESH_DEBUG_WGSL: &str = r#"
+#include "wgpu_util::camera"
+
+struct MeshDebugParams {
+    wire_color: vec4<f32>,
+    normal_color: vec4<f32>,
+    tangent_color: vec4<f32>,
+    line_length: f32,
+    wire_width: f32,
+    _padding0: f32,
+    _padding1: f32,
+};
+
+@group(0) @binding(1)
+var<uniform> mesh_debug: MeshDebugParams;
+@group(1) @binding(1)
+var<storage, read> indices: array<u32>;
+
+struct WireframeOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) barycentric: vec3<f32>,
+};
+
+// Draws every index as its own vertex, so triangles know their barycentric coordinates.
+@vertex
+fn vs_wireframe(@builtin(vertex_index) index: u32) -> WireframeOutput {
+    let vertex = pull_vertex(indices[index]);
+    let corner = index % 3u;
+    var out: WireframeOutput;
+    out.position = camera_clip_position(vertex.{position}.xyz);
+    out.barycentric = vec3<f32>(
+        select(0.0, 1.0, corner == 0u),
+        select(0.0, 1.0, corner == 1u),
+        select(0.0, 1.0, corner == 2u),
+    );
+    return out;
+}
+
+@fragment
+fn fs_wireframe(in: WireframeOutput) -> @location(0) vec4<f32> {
+    let distance = in.barycentric / fwidth(in.barycentric);
+    let edge = min(min(distance.x, distance.y), distance.z);
+    let coverage = clamp(mesh_debug.wire_width * 0.5 + 0.5 - edge, 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(mesh_debug.wire_color.rgb, mesh_debug.wire_color.a * coverage);
+}
+
+struct LineOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+// Two vertices per mesh vertex, the second one offset along `direction`.
+fn line_vertex(index: u32, position: vec3<f32>, direction: vec3<f32>, color: vec4<f32>) -> LineOutput {
+    let offset = normalize(direction) * mesh_debug.line_length * f32(index % 2u);
+    var out: LineOutput;
+    out.position = camera_clip_position(position + offset);
+    out.color = color;
+    return out;
+}
+
+@vertex
+fn vs_normals(@builtin(vertex_index) index: u32) -> LineOutput {
+    let vertex = pull_vertex(index / 2u);
+    return line_vertex(index, vertex.{position}.xyz, vertex.{normal}.xyz, mesh_debug.normal_color);
+}
+
+@fragment
+fn fs_line(in: LineOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+const TANGENTS_WGSL: &str = r#"
+@vertex
+fn vs_tangents(@builtin(vertex_index) index: u32) -> LineOutput {
+    let vertex = pull_vertex(index / 2u);
+    return line_vertex(index, vertex.{position}.xyz, vertex.{tangent}.xyz, mesh_debug.tangent_color);
+}
+"#;
+
+/// What [`MeshDebugRenderer::draw`] visualizes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MeshDebugView {
+    /// Edges of the triangles, overlaid without depending on polygon fill modes.
+    Wireframe,
+    /// A line along the normal of every vertex.
+    Normals,
+    /// A line along the tangent of every vertex, needs [`MeshDebugDescriptor::tangent_location`].
+    Tangents,
+}
+
+/// Colors and sizes of [`MeshDebugRenderer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshDebugParams {
+    pub wire_color: [f32; 4],
+    pub normal_color: [f32; 4],
+    pub tangent_color: [f32; 4],
+    /// Length of normal and tangent lines in world units.
+    pub line_length: f32,
+    /// Width of wireframe edges in pixels.
+    pub wire_width: f32,
+}
+
+impl Default for MeshDebugParams {
+    fn default() -> Self {
+        Self {
+            wire_color: [1.0, 1.0, 1.0, 1.0],
+            normal_color: [0.2, 0.4, 1.0, 1.0],
+            tangent_color: [1.0, 0.2, 0.2, 1.0],
+            line_length: 0.1,
+            wire_width: 1.0,
+        }
+    }
+}
+
+impl MeshDebugParams {
+    fn to_bytes(self) -> Vec<u8> {
+        self.wire_color
+            .into_iter()
+            .chain(self.normal_color)
+            .chain(self.tangent_color)
+            .chain([self.line_length, self.wire_width, 0.0, 0.0])
+            .flat_map(f32::to_le_bytes)
+            .collect()
+    }
+}
+
+/// Descriptor for [`MeshDebugRenderer::new`].
+#[derive(Clone, Debug)]
+pub struct MeshDebugDescriptor<'a> {
+    /// Layout of the vertices of the meshes to visualize.
+    pub layout: wgpu::VertexBufferLayout<'a>,
+    /// Shader location of the position attribute, taken as world space.
+    pub position_location: u32,
+    pub normal_location: u32,
+    pub tangent_location: Option<u32>,
+    /// Format of the color target, lines are alpha blended onto it.
+    pub format: wgpu::TextureFormat,
+    /// Depth state of the pass. Wireframes drawn over their mesh need
+    /// [`wgpu::CompareFunction::LessEqual`] or a bias.
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub sample_count: u32,
+    pub params: MeshDebugParams,
+}
+
+/// Pipelines drawing debug views of any [`Mesh`] by pulling its vertices, so no vertex layout
+/// specific pipelines or line geometry are needed.
+///
+/// Meshes must be created with [`wgpu::BufferUsages::STORAGE`] and bound with a bind group from
+/// [`Self::create_bind_group`].
+#[derive(Debug)]
+pub struct MeshDebugRenderer {
+    wireframe: wgpu::RenderPipeline,
+    normals: wgpu::RenderPipeline,
+    tangents: Option<wgpu::RenderPipeline>,
+    mesh_bind_group_layout: wgpu::BindGroupLayout,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    params: MeshDebugParams,
+}
+
+impl MeshDebugRenderer {
+    /// Fails if the vertex layout can't be pulled, see [`crate::pulling::generate_snippet`].
+    pub fn new(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        descriptor: &MeshDebugDescriptor<'_>,
+    ) -> Result<Self, PullingError> {
+        let attribute = |location: u32| format!("attribute_{}", location);
+        let pulling = crate::pulling::generate_snippet(
+            &descriptor.layout,
+            &PullingDescriptor {
+                group: 1,
+                binding: 0,
+                buffer_name: "vertices",
+                struct_name: "MeshDebugVertex",
+                function_name: "pull_vertex",
+                attribute_names: &[],
+            },
+        )?;
+        let mut source = pulling + MESH_DEBUG_WGSL;
+        if descriptor.tangent_location.is_some() {
+            source.push_str(TANGENTS_WGSL);
+        }
+        let source = source
+            .replace("{position}", &attribute(descriptor.position_location))
+            .replace("{normal}", &attribute(descriptor.normal_location))
+            .replace(
+                "{tangent}",
+                &attribute(descriptor.tangent_location.unwrap_or_default()),
+            );
+
+        let mut composer = ShaderComposer::new();
+        composer.add_snippet(CAMERA_SNIPPET, crate::camera::camera_snippet(0, 0));
+        let shader = composer
+            .create_shader_module(device, Some("mesh debug shader"), &source)
+            .expect("builtin snippets must compose");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("mesh debug bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ..camera.bind_group_layout_entry()
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let mesh_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("mesh debug mesh bind group layout"),
+                entries: &[
+                    crate::pulling::storage_layout_entry(0, wgpu::ShaderStages::VERTEX),
+                    crate::pulling::storage_layout_entry(1, wgpu::ShaderStages::VERTEX),
+                ],
+            });
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("mesh debug params buffer"),
+            contents: &descriptor.params.to_bytes(),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mesh debug bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("mesh debug pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout, &mesh_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, vertex_entry_point, fragment_entry_point, topology| {
+            crate::resource_log::create_render_pipeline(
+                device,
+                &wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: vertex_entry_point,
+                        buffers: &[],
+                    },
+                    primitive: wgpu::PrimitiveState {
+                        topology,
+                        ..Default::default()
+                    },
+                    depth_stencil: descriptor.depth_stencil.clone(),
+                    multisample: wgpu::MultisampleState {
+                        count: descriptor.sample_count,
+                        ..Default::default()
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: fragment_entry_point,
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format: descriptor.format,
+                            blend: Some(crate::blend::STRAIGHT_ALPHA),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    multiview: None,
+                },
+            )
+        };
+
+        Ok(Self {
+            wireframe: pipeline(
+                "mesh wireframe pipeline",
+                "vs_wireframe",
+                "fs_wireframe",
+                wgpu::PrimitiveTopology::TriangleList,
+            ),
+            normals: pipeline(
+                "mesh normals pipeline",
+                "vs_normals",
+                "fs_line",
+                wgpu::PrimitiveTopology::LineList,
+            ),
+            tangents: descriptor.tangent_location.map(|_| {
+                pipeline(
+                    "mesh tangents pipeline",
+                    "vs_tangents",
+                    "fs_line",
+                    wgpu::PrimitiveTopology::LineList,
+                )
+            }),
+            mesh_bind_group_layout,
+            bind_group,
+            params_buffer,
+            params: descriptor.params,
+        })
+    }
+
+    /// Bind group pulling the vertices and indices of `mesh`, which must have
+    /// [`wgpu::BufferUsages::STORAGE`].
+    pub fn create_bind_group(&self, device: &wgpu::Device, mesh: &Mesh) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("mesh debug mesh bind group"),
+            layout: &self.mesh_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: mesh.vertices.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: mesh.indices.as_entire_binding(),
+                },
+            ],
+        })
+    }
+
+    /// Draws `view` of `mesh` into `pass`, with `bind_group` created for `mesh` by
+    /// [`Self::create_bind_group`].
+    ///
+    /// Panics for [`MeshDebugView::Tangents`] without a tangent location.
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        mesh: &Mesh,
+        bind_group: &'a wgpu::BindGroup,
+        view: MeshDebugView,
+    ) {
+        let (pipeline, vertices) = match view {
+            MeshDebugView::Wireframe => (&self.wireframe, mesh.index_count),
+            MeshDebugView::Normals => (&self.normals, mesh.vertex_count * 2),
+            MeshDebugView::Tangents => (
+                self.tangents
+                    .as_ref()
+                    .expect("tangents need a tangent location"),
+                mesh.vertex_count * 2,
+            ),
+        };
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, bind_group, &[]);
+        pass.draw(0..vertices, 0..1);
+    }
+
+    pub fn params(&self) -> &MeshDebugParams {
+        &self.params
+    }
+
+    /// Changes colors and sizes and uploads them using [`wgpu::Queue`].
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: MeshDebugParams) {
+        self.params = params;
+        queue.write_buffer(&self.params_buffer, 0, &params.to_bytes());
+    }
+}
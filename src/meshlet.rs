@@ -0,0 +1,343 @@
+//! Splitting meshes into meshlets, small clusters of triangles culled individually on the GPU.
+
+use crate::{BufferInitDescriptor, DeviceExt};
+
+/// Range of a meshlet in [`Meshlets::vertices`] and [`Meshlets::triangles`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Meshlet {
+    pub vertex_offset: u32,
+    pub vertex_count: u32,
+    pub triangle_offset: u32,
+    pub triangle_count: u32,
+}
+
+/// Bounding sphere and normal cone of a meshlet.
+///
+/// A meshlet is back-facing and can be culled if
+/// `dot(normalize(center - camera), cone_axis) >= cone_cutoff + radius / length(center - camera)`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshletBounds {
+    pub center: [f32; 3],
+    pub radius: f32,
+    pub cone_axis: [f32; 3],
+    /// Cutoff of the culling test, `1.0` if the meshlet can't be cone culled.
+    pub cone_cutoff: f32,
+}
+
+/// Meshlets built by [`build_meshlets`].
+#[derive(Clone, Debug, Default)]
+pub struct Meshlets {
+    pub meshlets: Vec<Meshlet>,
+    pub bounds: Vec<MeshletBounds>,
+    /// Indices into the original vertices, referenced by the meshlets' vertex ranges.
+    pub vertices: Vec<u32>,
+    /// Triangles as three 8 bit indices into the meshlet's vertices, packed into the low 24 bits.
+    pub triangles: Vec<u32>,
+}
+
+/// Storage buffers of [`Meshlets`], created by [`Meshlets::upload`].
+#[derive(Debug)]
+pub struct MeshletBuffers {
+    /// [`Meshlet`]s as 4 `u32`s each.
+    pub meshlets: wgpu::Buffer,
+    /// [`MeshletBounds`] as 8 `f32`s each.
+    pub bounds: wgpu::Buffer,
+    pub vertices: wgpu::Buffer,
+    pub triangles: wgpu::Buffer,
+    pub count: u32,
+}
+
+impl Meshlets {
+    /// Creates storage buffers of the meshlets. [`wgpu::BufferUsages::STORAGE`] is added to
+    /// `usage`.
+    pub fn upload(&self, device: &wgpu::Device, usage: wgpu::BufferUsages) -> MeshletBuffers {
+        let usage = usage | wgpu::BufferUsages::STORAGE;
+        let create = |label, contents: Vec<u8>| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some(label),
+                contents: &contents,
+                size: None,
+                usage,
+            })
+        };
+
+        MeshletBuffers {
+            meshlets: create(
+                "meshlets",
+                self.meshlets
+                    .iter()
+                    .flat_map(|m| {
+                        [
+                            m.vertex_offset,
+                            m.vertex_count,
+                            m.triangle_offset,
+                            m.triangle_count,
+                        ]
+                    })
+                    .flat_map(u32::to_le_bytes)
+                    .collect(),
+            ),
+            bounds: create(
+                "meshlet bounds",
+                self.bounds
+                    .iter()
+                    .flat_map(|b| {
+                        let [x, y, z] = b.center;
+                        let [ax, ay, az] = b.cone_axis;
+                        [x, y, z, b.radius, ax, ay, az, b.cone_cutoff]
+                    })
+                    .flat_map(f32::to_le_bytes)
+                    .collect(),
+            ),
+            vertices: create(
+                "meshlet vertices",
+                self.vertices.iter().flat_map(|v| v.to_le_bytes()).collect(),
+            ),
+            triangles: create(
+                "meshlet triangles",
+                self.triangles
+                    .iter()
+                    .flat_map(|t| t.to_le_bytes())
+                    .collect(),
+            ),
+            count: self.meshlets.len() as u32,
+        }
+    }
+}
+
+/// Greedily splits the triangle list `indices` into meshlets of at most `max_vertices` unique
+/// vertices and `max_triangles` triangles, in the order of the triangles.
+///
+/// `max_vertices` must be between 3 and 256, so local indices fit into 8 bits. Triangles should be
+/// ordered spatially, e.g. by an index optimizer, for tight bounds.
+pub fn build_meshlets(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+    max_vertices: usize,
+    max_triangles: usize,
+) -> Meshlets {
+    assert!(
+        (3..=256).contains(&max_vertices),
+        "max vertices must be between 3 and 256"
+    );
+    assert!(max_triangles > 0, "max triangles must not be zero");
+    assert!(
+        indices.len().is_multiple_of(3),
+        "indices must be a triangle list"
+    );
+
+    let mut meshlets = Meshlets::default();
+    // Local index of every vertex in the current meshlet.
+    let mut local = vec![u8::MAX as u32 + 1; positions.len()];
+    let mut current = Meshlet::default();
+
+    for triangle in indices.chunks_exact(3) {
+        let new_vertices = triangle
+            .iter()
+            .enumerate()
+            .filter(|&(i, v)| local[*v as usize] > u8::MAX as u32 && !triangle[..i].contains(v))
+            .count();
+        if current.vertex_count as usize + new_vertices > max_vertices
+            || current.triangle_count as usize == max_triangles
+        {
+            finish_meshlet(&mut meshlets, &mut current, &mut local, positions);
+        }
+
+        let mut packed = 0;
+        for (i, &vertex) in triangle.iter().enumerate() {
+            let slot = &mut local[vertex as usize];
+            if *slot > u8::MAX as u32 {
+                *slot = current.vertex_count;
+                meshlets.vertices.push(vertex);
+                current.vertex_count += 1;
+            }
+            packed |= *slot << (8 * i);
+        }
+        meshlets.triangles.push(packed);
+        current.triangle_count += 1;
+    }
+    finish_meshlet(&mut meshlets, &mut current, &mut local, positions);
+
+    meshlets
+}
+
+/// Pushes `current` with its bounds and starts a new meshlet.
+fn finish_meshlet(
+    meshlets: &mut Meshlets,
+    current: &mut Meshlet,
+    local: &mut [u32],
+    positions: &[[f32; 3]],
+) {
+    if current.triangle_count == 0 {
+        return;
+    }
+
+    let vertices = &meshlets.vertices[current.vertex_offset as usize..];
+    let triangles = &meshlets.triangles[current.triangle_offset as usize..];
+    meshlets
+        .bounds
+        .push(compute_bounds(positions, vertices, triangles));
+    meshlets.meshlets.push(*current);
+
+    for &vertex in vertices {
+        local[vertex as usize] = u8::MAX as u32 + 1;
+    }
+    *current = Meshlet {
+        vertex_offset: meshlets.vertices.len() as u32,
+        vertex_count: 0,
+        triangle_offset: meshlets.triangles.len() as u32,
+        triangle_count: 0,
+    };
+}
+
+fn compute_bounds(positions: &[[f32; 3]], vertices: &[u32], triangles: &[u32]) -> MeshletBounds {
+    let position = |local: u32| positions[vertices[local as usize] as usize];
+
+    let mut min = [f32::INFINITY; 3];
+    let mut max = [f32::NEG_INFINITY; 3];
+    for &vertex in vertices {
+        let p = positions[vertex as usize];
+        for i in 0..3 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+    let center = [0, 1, 2].map(|i| (min[i] + max[i]) * 0.5);
+    let radius = vertices
+        .iter()
+        .map(|&vertex| length(sub(positions[vertex as usize], center)))
+        .fold(0.0, f32::max);
+
+    let normals: Vec<_> = triangles
+        .iter()
+        .map(|&packed| {
+            let [a, b, c] = [0, 8, 16].map(|shift| position((packed >> shift) & 0xff));
+            normalize(cross(sub(b, a), sub(c, a)))
+        })
+        .filter(|n| n.iter().all(|c| c.is_finite()))
+        .collect();
+    let axis = normalize(normals.iter().fold([0.0; 3], |sum, n| add(sum, *n)));
+    let (cone_axis, cone_cutoff) = match axis.iter().all(|c| c.is_finite()) {
+        true => {
+            let min_dot = normals.iter().map(|n| dot(*n, axis)).fold(1.0, f32::min);
+            // Triangles facing away from the axis make the cone span more than a hemisphere.
+            match min_dot > 0.0 {
+                true => (axis, (1.0 - min_dot * min_dot).sqrt()),
+                false => ([0.0; 3], 1.0),
+            }
+        }
+        false => ([0.0; 3], 1.0),
+    };
+
+    MeshletBounds {
+        center,
+        radius,
+        cone_axis,
+        cone_cutoff,
+    }
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = length(a);
+    [a[0] / length, a[1] / length, a[2] / length]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flat grid of `n` x `n` quads, two triangles each.
+    fn grid(n: u32) -> (Vec<[f32; 3]>, Vec<u32>) {
+        let positions = (0..=n)
+            .flat_map(|y| (0..=n).map(move |x| [x as f32, y as f32, 0.0]))
+            .collect();
+        let indices = (0..n)
+            .flat_map(|y| (0..n).map(move |x| y * (n + 1) + x))
+            .flat_map(|i| [i, i + 1, i + n + 2, i, i + n + 2, i + n + 1])
+            .collect();
+        (positions, indices)
+    }
+
+    fn check(positions: &[[f32; 3]], indices: &[u32], max_vertices: usize, max_triangles: usize) {
+        let meshlets = build_meshlets(positions, indices, max_vertices, max_triangles);
+        assert_eq!(meshlets.meshlets.len(), meshlets.bounds.len());
+
+        let mut rebuilt = Vec::new();
+        for (meshlet, bounds) in meshlets.meshlets.iter().zip(&meshlets.bounds) {
+            assert!(meshlet.vertex_count as usize <= max_vertices);
+            assert!(meshlet.triangle_count as usize <= max_triangles);
+
+            let start = meshlet.vertex_offset as usize;
+            let vertices = &meshlets.vertices[start..start + meshlet.vertex_count as usize];
+            let start = meshlet.triangle_offset as usize;
+            let triangles = &meshlets.triangles[start..start + meshlet.triangle_count as usize];
+            for &packed in triangles {
+                assert_eq!(packed >> 24, 0, "only the low 24 bits are used");
+                for shift in [0, 8, 16] {
+                    let local = (packed >> shift) & 0xff;
+                    assert!(local < meshlet.vertex_count);
+                    rebuilt.push(vertices[local as usize]);
+                }
+            }
+            for &vertex in vertices {
+                let distance = length(sub(positions[vertex as usize], bounds.center));
+                assert!(distance <= bounds.radius * (1.0 + 1e-6));
+            }
+        }
+        assert_eq!(rebuilt, indices, "triangles must be kept in order");
+    }
+
+    #[test]
+    fn meshlets_respect_limits() {
+        let (positions, indices) = grid(20);
+        for (max_vertices, max_triangles) in [(3, 1), (4, 2), (64, 124), (64, 16), (128, 512)] {
+            check(&positions, &indices, max_vertices, max_triangles);
+        }
+    }
+
+    #[test]
+    fn local_indices_use_all_8_bits() {
+        let (positions, indices) = grid(40);
+        let meshlets = build_meshlets(&positions, &indices, 256, 1024);
+        assert!(meshlets.meshlets.iter().any(|m| m.vertex_count == 256));
+        assert!(meshlets
+            .triangles
+            .iter()
+            .any(|t| [0, 8, 16].iter().any(|shift| (t >> shift) & 0xff == 255)));
+        check(&positions, &indices, 256, 1024);
+    }
+
+    #[test]
+    fn flat_meshlets_can_be_cone_culled() {
+        let (positions, indices) = grid(4);
+        let meshlets = build_meshlets(&positions, &indices, 64, 124);
+        assert_eq!(meshlets.meshlets.len(), 1);
+        let bounds = meshlets.bounds[0];
+        assert_eq!(bounds.cone_axis, [0.0, 0.0, 1.0]);
+        assert!(bounds.cone_cutoff.abs() < 1e-3);
+    }
+}
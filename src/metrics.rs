@@ -0,0 +1,116 @@
+//! Publishing statistics through the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! Resource creation, [`GpuBudget`](crate::budget::GpuBudget) usage,
+//! [`AmortizedUploader`](crate::upload::AmortizedUploader) throughput and
+//! [`BindGroupPool`](crate::binding::BindGroupPool) hits are recorded as they happen, so any
+//! installed recorder, e.g. a Prometheus exporter, can monitor headless render farms. Call
+//! [`describe`] once after installing the recorder to add units and descriptions.
+
+use ::metrics::{describe_counter, describe_gauge, Unit};
+
+use crate::upload::UploadPriority;
+
+pub const BUFFERS_CREATED: &str = "wgpu_util_buffers_created_total";
+pub const BUFFER_BYTES_CREATED: &str = "wgpu_util_buffer_bytes_created_total";
+pub const TEXTURES_CREATED: &str = "wgpu_util_textures_created_total";
+pub const TEXTURE_BYTES_CREATED: &str = "wgpu_util_texture_bytes_created_total";
+pub const PIPELINES_CREATED: &str = "wgpu_util_pipelines_created_total";
+pub const BUDGET_USED_BYTES: &str = "wgpu_util_budget_used_bytes";
+pub const BUDGET_BYTES: &str = "wgpu_util_budget_bytes";
+pub const UPLOAD_BYTES: &str = "wgpu_util_upload_bytes_total";
+pub const UPLOAD_PENDING_BYTES: &str = "wgpu_util_upload_pending_bytes";
+pub const UPLOAD_UTILIZATION: &str = "wgpu_util_upload_utilization";
+pub const BIND_GROUP_POOL_HITS: &str = "wgpu_util_bind_group_pool_hits_total";
+pub const BIND_GROUP_POOL_MISSES: &str = "wgpu_util_bind_group_pool_misses_total";
+
+/// Registers units and descriptions of all metrics with the installed recorder.
+pub fn describe() {
+    describe_counter!(BUFFERS_CREATED, Unit::Count, "buffers created");
+    describe_counter!(
+        BUFFER_BYTES_CREATED,
+        Unit::Bytes,
+        "bytes of buffers created"
+    );
+    describe_counter!(TEXTURES_CREATED, Unit::Count, "textures created");
+    describe_counter!(
+        TEXTURE_BYTES_CREATED,
+        Unit::Bytes,
+        "estimated bytes of textures created"
+    );
+    describe_counter!(PIPELINES_CREATED, Unit::Count, "pipelines created");
+    describe_gauge!(
+        BUDGET_USED_BYTES,
+        Unit::Bytes,
+        "bytes accounted against gpu budgets"
+    );
+    describe_gauge!(BUDGET_BYTES, Unit::Bytes, "configured gpu budgets");
+    describe_counter!(
+        UPLOAD_BYTES,
+        Unit::Bytes,
+        "bytes copied by amortized uploads"
+    );
+    describe_gauge!(
+        UPLOAD_PENDING_BYTES,
+        Unit::Bytes,
+        "bytes of queued amortized uploads"
+    );
+    describe_gauge!(
+        UPLOAD_UTILIZATION,
+        Unit::Count,
+        "fraction of the per frame upload budget used in the last frame"
+    );
+    describe_counter!(
+        BIND_GROUP_POOL_HITS,
+        Unit::Count,
+        "bind groups reused from pools"
+    );
+    describe_counter!(
+        BIND_GROUP_POOL_MISSES,
+        Unit::Count,
+        "bind groups created by pools"
+    );
+}
+
+pub(crate) fn record_buffer(size: wgpu::BufferAddress) {
+    ::metrics::counter!(BUFFERS_CREATED).increment(1);
+    ::metrics::counter!(BUFFER_BYTES_CREATED).increment(size);
+}
+
+pub(crate) fn record_texture(descriptor: &wgpu::TextureDescriptor) {
+    ::metrics::counter!(TEXTURES_CREATED).increment(1);
+    ::metrics::counter!(TEXTURE_BYTES_CREATED).increment(crate::texture::estimate_size(descriptor));
+}
+
+pub(crate) fn record_pipeline(kind: &'static str) {
+    ::metrics::counter!(PIPELINES_CREATED, "kind" => kind).increment(1);
+}
+
+pub(crate) fn record_budget(used: u64, budget: u64) {
+    ::metrics::gauge!(BUDGET_USED_BYTES).set(used as f64);
+    ::metrics::gauge!(BUDGET_BYTES).set(budget as f64);
+}
+
+pub(crate) fn record_upload(
+    priority: UploadPriority,
+    bytes: wgpu::BufferAddress,
+    pending: wgpu::BufferAddress,
+) {
+    let priority = match priority {
+        UploadPriority::High => "high",
+        UploadPriority::Normal => "normal",
+        UploadPriority::Low => "low",
+    };
+    ::metrics::counter!(UPLOAD_BYTES, "priority" => priority).increment(bytes);
+    ::metrics::gauge!(UPLOAD_PENDING_BYTES, "priority" => priority).set(pending as f64);
+}
+
+pub(crate) fn record_upload_utilization(bytes: wgpu::BufferAddress, budget: wgpu::BufferAddress) {
+    ::metrics::gauge!(UPLOAD_UTILIZATION).set(bytes as f64 / budget as f64);
+}
+
+pub(crate) fn record_bind_group(hit: bool) {
+    match hit {
+        true => ::metrics::counter!(BIND_GROUP_POOL_HITS).increment(1),
+        false => ::metrics::counter!(BIND_GROUP_POOL_MISSES).increment(1),
+    }
+}
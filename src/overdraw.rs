@@ -0,0 +1,249 @@
+//! Visualizing overdraw, how often every pixel is shaded, as a heatmap.
+//!
+//! Render the scene with the vertex stages of its pipelines and the fragment stage of
+//! [`OverdrawView::fragment_state`] into an [`OVERDRAW_FORMAT`] target cleared to zero, then map
+//! the counts through a color ramp with [`OverdrawView::render`]. Disable depth testing in the
+//! counting pipelines to count every fragment, or keep it to count only shaded fragments.
+
+use std::num::NonZeroU64;
+
+use crate::lut::{ColorStop, GradientTexture, LutDescriptor};
+
+/// Format of the target fragments are counted in. Blendable and exact up to 2048 fragments.
+pub const OVERDRAW_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R16Float;
+
+const OVERDRAW_TARGETS: &[Option<wgpu::ColorTargetState>] = &[Some(wgpu::ColorTargetState {
+    format: OVERDRAW_FORMAT,
+    blend: Some(crate::blend::ADDITIVE),
+    write_mask: wgpu::ColorWrites::RED,
+})];
+
+/// Ramp from black over blue, green and yellow to red, then white at the highest counts.
+pub const HEAT_RAMP: &[ColorStop] = &[
+    ColorStop {
+        position: 0.0,
+        color: [0.0, 0.0, 0.0, 1.0],
+    },
+    ColorStop {
+        position: 0.2,
+        color: [0.0, 0.1, 0.8, 1.0],
+    },
+    ColorStop {
+        position: 0.4,
+        color: [0.0, 0.7, 0.2, 1.0],
+    },
+    ColorStop {
+        position: 0.6,
+        color: [0.9, 0.9, 0.0, 1.0],
+    },
+    ColorStop {
+        position: 0.8,
+        color: [0.9, 0.1, 0.0, 1.0],
+    },
+    ColorStop {
+        position: 1.0,
+        color: [1.0, 1.0, 1.0, 1.0],
+    },
+];
+
+/// Descriptor for [`OverdrawView::new`].
+#[derive(Clone, Debug)]
+pub struct OverdrawViewDescriptor<'a> {
+    /// Format of the output texture. Non-sRGB formats get encoded in the shader.
+    pub output_format: wgpu::TextureFormat,
+    /// Count mapped to the end of the ramp.
+    pub max_count: f32,
+    /// Linear colors the counts are mapped through, from zero to `max_count`.
+    pub ramp: &'a [ColorStop],
+}
+
+impl Default for OverdrawViewDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            output_format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            max_count: 8.0,
+            ramp: HEAT_RAMP,
+        }
+    }
+}
+
+/// Counts fragments and maps the counts to colors.
+#[derive(Debug)]
+pub struct OverdrawView {
+    shader: wgpu::ShaderModule,
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    ramp: GradientTexture,
+    params: wgpu::Buffer,
+
+    max_count: f32,
+    encode_srgb: bool,
+}
+
+impl OverdrawView {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        descriptor: &OverdrawViewDescriptor<'_>,
+    ) -> Self {
+        let shader = crate::post::fullscreen_shader_module(
+            device,
+            Some("overdraw shader"),
+            include_str!("shaders/overdraw.wgsl"),
+        );
+
+        let texture_entry = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("overdraw bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                texture_entry(1, false),
+                texture_entry(2, true),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("overdraw pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::post::fullscreen_pipeline(
+            device,
+            Some("overdraw pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(descriptor.output_format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("overdraw ramp sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let ramp = GradientTexture::bake(
+            device,
+            queue,
+            descriptor.ramp,
+            &LutDescriptor {
+                label: Some("overdraw ramp"),
+                as_2d: true,
+                ..Default::default()
+            },
+        );
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("overdraw params"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            shader,
+            pipeline,
+            bind_group_layout,
+            sampler,
+            ramp,
+            params,
+
+            max_count: descriptor.max_count,
+            encode_srgb: !descriptor.output_format.describe().srgb,
+        }
+    }
+
+    /// Fragment stage adding one per fragment to an [`OVERDRAW_FORMAT`] target. It has no inputs,
+    /// so it fits the vertex stage of any pipeline.
+    pub fn fragment_state(&self) -> wgpu::FragmentState<'_> {
+        wgpu::FragmentState {
+            module: &self.shader,
+            entry_point: "fs_overdraw",
+            targets: OVERDRAW_TARGETS,
+        }
+    }
+
+    /// Sets the count mapped to the end of the ramp.
+    pub fn set_max_count(&mut self, max_count: f32) {
+        self.max_count = max_count;
+    }
+
+    /// Maps the counts of `counts`, an [`OVERDRAW_FORMAT`] texture, to colors in `output`.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        counts: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let mut params = Vec::with_capacity(16);
+        params.extend_from_slice(&self.max_count.to_le_bytes());
+        params.extend_from_slice(&(self.encode_srgb as u32).to_le_bytes());
+        params.resize(16, 0);
+        queue.write_buffer(&self.params, 0, &params);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("overdraw bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(counts),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(self.ramp.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("overdraw pass")
+                .read("counts", counts)
+                .write("output", output)
+        });
+        crate::post::draw_fullscreen(
+            encoder,
+            Some("overdraw pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+}
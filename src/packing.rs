@@ -0,0 +1,353 @@
+//! Packing values into compact vertex and texture formats, e.g. `Float16x2`, `Snorm8x4`,
+//! `Rgb9e5Ufloat` or `Rg11b10Float`.
+
+/// Converts to a half-float, as stored in `Float16` vertex and texture formats.
+pub fn f16_from_f32(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        // Subnormal or zero.
+        if exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return sign | ((mantissa >> shift) + round) as u16;
+    }
+    // Rounding may carry into the exponent, which correctly rounds up to infinity.
+    let round = (mantissa >> 12) & 1;
+    sign | ((((exponent as u32) << 10) | (mantissa >> 13)) + round) as u16
+}
+
+/// Converts from a half-float.
+pub fn f32_from_f16(bits: u16) -> f32 {
+    let sign = ((bits & 0x8000) as u32) << 16;
+    let exponent = ((bits >> 10) & 0x1f) as u32;
+    let mantissa = (bits & 0x3ff) as u32;
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        // Subnormal, renormalize.
+        (0, _) => {
+            let shift = mantissa.leading_zeros() - 21;
+            let mantissa = (mantissa << shift) & 0x3ff;
+            sign | ((127 - 15 + 1 - shift) << 23) | (mantissa << 13)
+        }
+        (0x1f, _) => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// [`f16_from_f32`] for a slice. `dst` must have the same length as `src`.
+pub fn f16_from_f32_slice(src: &[f32], dst: &mut [u16]) {
+    assert_eq!(src.len(), dst.len(), "slices must have the same length");
+    for (dst, &src) in dst.iter_mut().zip(src) {
+        *dst = f16_from_f32(src);
+    }
+}
+
+/// [`f32_from_f16`] for a slice. `dst` must have the same length as `src`.
+pub fn f32_from_f16_slice(src: &[u16], dst: &mut [f32]) {
+    assert_eq!(src.len(), dst.len(), "slices must have the same length");
+    for (dst, &src) in dst.iter_mut().zip(src) {
+        *dst = f32_from_f16(src);
+    }
+}
+
+/// Converts to little endian half-float bytes, e.g. for uploading `Rgba16Float` texels.
+pub fn f16_bytes_from_f32(src: &[f32]) -> Vec<u8> {
+    src.iter()
+        .flat_map(|&value| f16_from_f32(value).to_le_bytes())
+        .collect()
+}
+
+pub fn pack_unorm8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+pub fn unpack_unorm8(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+pub fn pack_snorm8(value: f32) -> i8 {
+    (value.clamp(-1.0, 1.0) * 127.0).round() as i8
+}
+
+pub fn unpack_snorm8(value: i8) -> f32 {
+    (value as f32 / 127.0).max(-1.0)
+}
+
+pub fn pack_unorm16(value: f32) -> u16 {
+    (value.clamp(0.0, 1.0) * 65535.0).round() as u16
+}
+
+pub fn unpack_unorm16(value: u16) -> f32 {
+    value as f32 / 65535.0
+}
+
+pub fn pack_snorm16(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * 32767.0).round() as i16
+}
+
+pub fn unpack_snorm16(value: i16) -> f32 {
+    (value as f32 / 32767.0).max(-1.0)
+}
+
+/// Packs into `Unorm8x4` with the first component in the lowest byte, like WGSL's
+/// `pack4x8unorm`.
+pub fn pack_unorm8x4(values: [f32; 4]) -> u32 {
+    u32::from_le_bytes(values.map(pack_unorm8))
+}
+
+pub fn unpack_unorm8x4(packed: u32) -> [f32; 4] {
+    packed.to_le_bytes().map(unpack_unorm8)
+}
+
+/// Packs into `Snorm8x4` with the first component in the lowest byte, like WGSL's
+/// `pack4x8snorm`.
+pub fn pack_snorm8x4(values: [f32; 4]) -> u32 {
+    u32::from_le_bytes(values.map(|value| pack_snorm8(value) as u8))
+}
+
+pub fn unpack_snorm8x4(packed: u32) -> [f32; 4] {
+    packed.to_le_bytes().map(|byte| unpack_snorm8(byte as i8))
+}
+
+/// Packs into `Unorm16x2` with the first component in the low half, like WGSL's
+/// `pack2x16unorm`.
+pub fn pack_unorm16x2(values: [f32; 2]) -> u32 {
+    pack_unorm16(values[0]) as u32 | (pack_unorm16(values[1]) as u32) << 16
+}
+
+pub fn unpack_unorm16x2(packed: u32) -> [f32; 2] {
+    [packed as u16, (packed >> 16) as u16].map(unpack_unorm16)
+}
+
+/// Packs into `Snorm16x2` with the first component in the low half, like WGSL's
+/// `pack2x16snorm`.
+pub fn pack_snorm16x2(values: [f32; 2]) -> u32 {
+    pack_snorm16(values[0]) as u16 as u32 | (pack_snorm16(values[1]) as u16 as u32) << 16
+}
+
+pub fn unpack_snorm16x2(packed: u32) -> [f32; 2] {
+    [packed as i16, (packed >> 16) as i16].map(unpack_snorm16)
+}
+
+const RGB9E5_MANTISSA_BITS: i32 = 9;
+const RGB9E5_EXPONENT_BIAS: i32 = 15;
+const RGB9E5_MAX_EXPONENT: i32 = 31;
+
+/// Packs into `Rgb9e5Ufloat`, three 9 bit mantissas sharing a 5 bit exponent.
+///
+/// Negative values become zero and values above 65408 are clamped.
+pub fn pack_rgb9e5(rgb: [f32; 3]) -> u32 {
+    let max_value = ((1 << RGB9E5_MANTISSA_BITS) - 1) as f32 / (1 << RGB9E5_MANTISSA_BITS) as f32
+        * 2f32.powi(RGB9E5_MAX_EXPONENT - RGB9E5_EXPONENT_BIAS);
+    // `max` ignores NaN, turning it into zero.
+    let rgb = rgb.map(|c| c.max(0.0).min(max_value));
+    let max_component = rgb[0].max(rgb[1]).max(rgb[2]);
+
+    // floor(log2(max_component)), exact for normal floats.
+    let log2 = ((max_component.to_bits() >> 23) & 0xff) as i32 - 127;
+    let mut exponent = log2.max(-RGB9E5_EXPONENT_BIAS - 1) + 1 + RGB9E5_EXPONENT_BIAS;
+    let mut scale = 2f32.powi(exponent - RGB9E5_EXPONENT_BIAS - RGB9E5_MANTISSA_BITS);
+    if (max_component / scale + 0.5).floor() as i32 == 1 << RGB9E5_MANTISSA_BITS {
+        scale *= 2.0;
+        exponent += 1;
+    }
+
+    let [r, g, b] = rgb.map(|c| (c / scale + 0.5).floor() as u32);
+    r | g << 9 | b << 18 | (exponent as u32) << 27
+}
+
+pub fn unpack_rgb9e5(packed: u32) -> [f32; 3] {
+    let exponent = (packed >> 27) as i32;
+    let scale = 2f32.powi(exponent - RGB9E5_EXPONENT_BIAS - RGB9E5_MANTISSA_BITS);
+    [0, 9, 18].map(|shift| ((packed >> shift) & 0x1ff) as f32 * scale)
+}
+
+/// Packs into `Rg11b10Float`, two 11 bit and one 10 bit unsigned floats.
+///
+/// Negative values become zero and too large values are clamped to the largest finite value.
+pub fn pack_rg11b10(rgb: [f32; 3]) -> u32 {
+    pack_unsigned_float(rgb[0], 6)
+        | pack_unsigned_float(rgb[1], 6) << 11
+        | pack_unsigned_float(rgb[2], 5) << 22
+}
+
+pub fn unpack_rg11b10(packed: u32) -> [f32; 3] {
+    [
+        unpack_unsigned_float(packed & 0x7ff, 6),
+        unpack_unsigned_float((packed >> 11) & 0x7ff, 6),
+        unpack_unsigned_float(packed >> 22, 5),
+    ]
+}
+
+/// Unsigned float with a 5 bit exponent like a half-float, but `mantissa_bits` wide mantissa.
+fn pack_unsigned_float(value: f32, mantissa_bits: u32) -> u32 {
+    let infinity = 0x1f << mantissa_bits;
+    let max_finite = infinity - 1;
+    if value.is_nan() {
+        return infinity | 1;
+    }
+    if value <= 0.0 {
+        return 0;
+    }
+    if value.is_infinite() {
+        return infinity;
+    }
+
+    let bits = value.to_bits();
+    let exponent = ((bits >> 23) & 0xff) as i32 - 127 + 15;
+    let mantissa = bits & 0x7f_ffff;
+    let dropped = 23 - mantissa_bits;
+    if exponent >= 0x1f {
+        return max_finite;
+    }
+    if exponent <= 0 {
+        // Subnormal or zero.
+        if exponent < -(mantissa_bits as i32) {
+            return 0;
+        }
+        let mantissa = mantissa | 0x80_0000;
+        let shift = dropped + (1 - exponent) as u32;
+        let round = (mantissa >> (shift - 1)) & 1;
+        return (mantissa >> shift) + round;
+    }
+    let round = (mantissa >> (dropped - 1)) & 1;
+    // Rounding may carry into the exponent, which must not round up to infinity.
+    ((((exponent as u32) << mantissa_bits) | (mantissa >> dropped)) + round).min(max_finite)
+}
+
+fn unpack_unsigned_float(bits: u32, mantissa_bits: u32) -> f32 {
+    let exponent = bits >> mantissa_bits;
+    let mantissa = bits & ((1 << mantissa_bits) - 1);
+    match exponent {
+        0 => mantissa as f32 * 2f32.powi(-14 - mantissa_bits as i32),
+        0x1f if mantissa == 0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => f32::from_bits(((exponent + 127 - 15) << 23) | (mantissa << (23 - mantissa_bits))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn f16_round_trips_all_non_nan_values() {
+        for bits in 0..=u16::MAX {
+            let value = f32_from_f16(bits);
+            match value.is_nan() {
+                true => assert!(f32_from_f16(f16_from_f32(value)).is_nan()),
+                false => assert_eq!(f16_from_f32(value), bits, "{:#06x}", bits),
+            }
+        }
+    }
+
+    #[test]
+    fn f16_denormals_and_overflow() {
+        // Smallest and largest subnormal.
+        assert_eq!(f16_from_f32(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_from_f16(0x03ff), 1023.0 * 2f32.powi(-24));
+        // Values round to the nearest subnormal, tiny ones flush to zero.
+        assert_eq!(f16_from_f32(3.0 * 2f32.powi(-26)), 0x0001);
+        assert_eq!(f16_from_f32(2f32.powi(-26)), 0x0000);
+        assert_eq!(f16_from_f32(-2f32.powi(-26)), 0x8000);
+
+        assert_eq!(f16_from_f32(65504.0), 0x7bff);
+        // Halfway to the next power of two rounds up to infinity.
+        assert_eq!(f16_from_f32(65520.0), 0x7c00);
+        assert_eq!(f16_from_f32(1e10), 0x7c00);
+        assert_eq!(f16_from_f32(-1e10), 0xfc00);
+        assert_eq!(f16_from_f32(f32::INFINITY), 0x7c00);
+        assert!(f32_from_f16(f16_from_f32(f32::NAN)).is_nan());
+    }
+
+    #[test]
+    fn rgb9e5_round_trips_representable_values() {
+        for rgb in [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.5, 0.25],
+            [3.0, 511.0, 0.0],
+            [65408.0, 1024.0, 128.0],
+        ] {
+            assert_eq!(unpack_rgb9e5(pack_rgb9e5(rgb)), rgb);
+        }
+    }
+
+    #[test]
+    fn rgb9e5_denormals_and_overflow() {
+        // The smallest exponent shares a scale of 2^-24 between the components.
+        let smallest = 2f32.powi(-24);
+        let rgb = [smallest, 3.0 * smallest, 511.0 * smallest];
+        assert_eq!(unpack_rgb9e5(pack_rgb9e5(rgb)), rgb);
+        assert_eq!(unpack_rgb9e5(pack_rgb9e5([smallest / 4.0; 3])), [0.0; 3]);
+
+        assert_eq!(
+            unpack_rgb9e5(pack_rgb9e5([1e10, f32::INFINITY, -1.0])),
+            [65408.0, 65408.0, 0.0]
+        );
+        assert_eq!(unpack_rgb9e5(pack_rgb9e5([f32::NAN; 3])), [0.0; 3]);
+    }
+
+    #[test]
+    fn rgb9e5_error_is_bounded_by_the_shared_exponent() {
+        for i in 0..1000 {
+            let rgb = [
+                i as f32 * 0.731,
+                (i % 17) as f32 * 3.1,
+                1000.0 / (i + 1) as f32,
+            ];
+            let max = rgb[0].max(rgb[1]).max(rgb[2]);
+            for (packed, value) in unpack_rgb9e5(pack_rgb9e5(rgb)).iter().zip(rgb) {
+                assert!((packed - value).abs() <= max * 2f32.powi(-9), "{:?}", rgb);
+            }
+        }
+    }
+
+    #[test]
+    fn rg11b10_round_trips_all_finite_values() {
+        for bits in 0..0x7c0 {
+            let value = unpack_unsigned_float(bits, 6);
+            assert_eq!(pack_unsigned_float(value, 6), bits);
+        }
+        for bits in 0..0x3e0 {
+            let value = unpack_unsigned_float(bits, 5);
+            assert_eq!(pack_unsigned_float(value, 5), bits);
+        }
+        let rgb = [1.0, 0.5, 2f32.powi(-19)];
+        assert_eq!(unpack_rg11b10(pack_rg11b10(rgb)), rgb);
+    }
+
+    #[test]
+    fn rg11b10_denormals_and_overflow() {
+        // Smallest subnormals of the 6 and 5 bit mantissas.
+        let rgb = [2f32.powi(-20), 2f32.powi(-20), 2f32.powi(-19)];
+        assert_eq!(pack_rg11b10(rgb), 1 | 1 << 11 | 1 << 22);
+        assert_eq!(unpack_rg11b10(pack_rg11b10(rgb)), rgb);
+        assert_eq!(pack_rg11b10([2f32.powi(-22); 3]), 0);
+
+        // Too large values clamp to the largest finite value instead of rounding to infinity.
+        let [r, g, b] = unpack_rg11b10(pack_rg11b10([1e10, 65500.0, 1e10]));
+        assert_eq!([r, g, b], [65024.0, 65024.0, 64512.0]);
+        assert_eq!(
+            unpack_rg11b10(pack_rg11b10([f32::INFINITY, -1.0, 0.0])),
+            [f32::INFINITY, 0.0, 0.0]
+        );
+        assert!(unpack_rg11b10(pack_rg11b10([f32::NAN, 0.0, 0.0]))[0].is_nan());
+    }
+}
@@ -0,0 +1,190 @@
+//! Tessellating 2D paths into triangle [`Mesh`]es with [lyon](https://github.com/nical/lyon).
+//!
+//! Paths are built with [`lyon::path::Path::builder`], e.g. from lines, quadratic and cubic
+//! Bézier curves and arcs, and tessellated into filled and stroked shapes with one color each:
+//!
+//! ```ignore
+//! let mut builder = Path::builder();
+//! builder.begin(point(0.0, 0.0));
+//! builder.cubic_bezier_to(point(50.0, -40.0), point(100.0, 40.0), point(150.0, 0.0));
+//! builder.end(false);
+//! let path = builder.build();
+//!
+//! let mut tessellator = PathTessellator::new();
+//! tessellator.stroke(&path, &StrokeOptions::default().with_line_width(4.0), [1.0; 4])?;
+//! let mesh = tessellator.create_mesh(device, Some("curve"), wgpu::BufferUsages::empty());
+//! ```
+
+pub use lyon;
+
+use lyon::{
+    path::Path,
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, StrokeOptions, StrokeTessellator,
+        StrokeVertex, TessellationError, VertexBuffers,
+    },
+};
+
+use crate::mesh::{Aabb, Mesh, MeshDescriptor};
+
+/// Size of a [`PathVertex`] in the vertex buffer.
+pub const PATH_VERTEX_SIZE: wgpu::BufferAddress = 24;
+
+/// Vertex of a tessellated path.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PathVertex {
+    /// Position in the coordinate space of the path.
+    pub position: [f32; 2],
+    /// Color of the shape the vertex belongs to.
+    pub color: [f32; 4],
+}
+
+impl PathVertex {
+    /// Layout of [`PathVertex`] in the vertex buffer, with the position at location 0 and the
+    /// color at location 1.
+    pub fn vertex_buffer_layout() -> wgpu::VertexBufferLayout<'static> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 2] = [
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x2,
+                offset: 0,
+                shader_location: 0,
+            },
+            wgpu::VertexAttribute {
+                format: wgpu::VertexFormat::Float32x4,
+                offset: 8,
+                shader_location: 1,
+            },
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: PATH_VERTEX_SIZE,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+/// Accumulates the triangles of filled and stroked paths into one indexed triangle list.
+///
+/// Triangles aren't wound consistently, so pipelines drawing them shouldn't cull faces. Shapes
+/// are drawn in the order they were added, later shapes on top.
+pub struct PathTessellator {
+    fill: FillTessellator,
+    stroke: StrokeTessellator,
+    geometry: VertexBuffers<PathVertex, u32>,
+}
+
+impl PathTessellator {
+    pub fn new() -> Self {
+        Self {
+            fill: FillTessellator::new(),
+            stroke: StrokeTessellator::new(),
+            geometry: VertexBuffers::new(),
+        }
+    }
+
+    /// Adds the interior of `path`, which is closed implicitly.
+    ///
+    /// Nothing is added if tessellation fails.
+    pub fn fill(
+        &mut self,
+        path: &Path,
+        options: &FillOptions,
+        color: [f32; 4],
+    ) -> Result<(), TessellationError> {
+        let mut builder =
+            BuffersBuilder::new(&mut self.geometry, |vertex: FillVertex| PathVertex {
+                position: vertex.position().to_array(),
+                color,
+            });
+        self.fill.tessellate_path(path, options, &mut builder)
+    }
+
+    /// Adds the outline of `path`, with the width, caps and joins of `options`.
+    ///
+    /// Nothing is added if tessellation fails.
+    pub fn stroke(
+        &mut self,
+        path: &Path,
+        options: &StrokeOptions,
+        color: [f32; 4],
+    ) -> Result<(), TessellationError> {
+        let mut builder =
+            BuffersBuilder::new(&mut self.geometry, |vertex: StrokeVertex| PathVertex {
+                position: vertex.position().to_array(),
+                color,
+            });
+        self.stroke.tessellate_path(path, options, &mut builder)
+    }
+
+    pub fn vertices(&self) -> &[PathVertex] {
+        &self.geometry.vertices
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.geometry.indices
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.geometry.indices.is_empty()
+    }
+
+    /// Removes all shapes, keeping the allocations for the next ones.
+    pub fn clear(&mut self) {
+        self.geometry.vertices.clear();
+        self.geometry.indices.clear();
+    }
+
+    /// Bounds of all vertices, with z zero.
+    pub fn bounds(&self) -> Aabb {
+        self.geometry
+            .vertices
+            .iter()
+            .fold(Aabb::EMPTY, |aabb, vertex| {
+                aabb.union_point([vertex.position[0], vertex.position[1], 0.0])
+            })
+    }
+
+    /// [`PathVertex`]es as laid out by [`PathVertex::vertex_buffer_layout`].
+    pub fn vertex_bytes(&self) -> Vec<u8> {
+        let mut bytes =
+            Vec::with_capacity(self.geometry.vertices.len() * PATH_VERTEX_SIZE as usize);
+        for vertex in &self.geometry.vertices {
+            for component in vertex.position.iter().chain(&vertex.color) {
+                bytes.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    /// Uploads all shapes into a [`Mesh`], see [`PathVertex::vertex_buffer_layout`].
+    pub fn create_mesh(
+        &self,
+        device: &wgpu::Device,
+        label: wgpu::Label,
+        usage: wgpu::BufferUsages,
+    ) -> Mesh {
+        let bounds = self.bounds();
+        Mesh::new(
+            device,
+            &MeshDescriptor {
+                label,
+                vertices: &self.vertex_bytes(),
+                vertex_stride: PATH_VERTEX_SIZE,
+                indices: &self.geometry.indices,
+                bounds: match bounds.is_empty() {
+                    true => None,
+                    false => Some(bounds),
+                },
+                // Triangles of overlapping shapes must stay in order.
+                optimize: None,
+                usage,
+            },
+        )
+    }
+}
+
+impl Default for PathTessellator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,909 @@
+//! Builders for pipelines, defaulting everything but the shader, bind group layouts and targets,
+//! and a cache of pipelines created on first use.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    future::Future,
+    hash::Hash,
+    panic::AssertUnwindSafe,
+    pin::Pin,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+/// Builds a [`wgpu::RenderPipeline`] and its layout.
+///
+/// Defaults to entry points `vs_main` and `fs_main` of the same shader, no vertex buffers, a
+/// triangle list with counter-clockwise front faces without culling, no depth-stencil, a single
+/// sample and one target without blending. Override them e.g. with the presets of
+/// [`crate::blend`], [`crate::depth`] and [`crate::primitive`].
+#[derive(Clone, Debug)]
+pub struct RenderPipelineBuilder<'a> {
+    label: wgpu::Label<'a>,
+    shader: &'a wgpu::ShaderModule,
+    vertex_entry_point: &'a str,
+    fragment_shader: &'a wgpu::ShaderModule,
+    fragment_entry_point: Option<&'a str>,
+    bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
+    push_constant_ranges: &'a [wgpu::PushConstantRange],
+    vertex_buffers: &'a [wgpu::VertexBufferLayout<'a>],
+    targets: Vec<Option<wgpu::ColorTargetState>>,
+    primitive: wgpu::PrimitiveState,
+    depth_stencil: Option<wgpu::DepthStencilState>,
+    multisample: wgpu::MultisampleState,
+}
+
+impl<'a> RenderPipelineBuilder<'a> {
+    pub fn new(
+        shader: &'a wgpu::ShaderModule,
+        bind_group_layouts: &'a [&'a wgpu::BindGroupLayout],
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        Self {
+            label: None,
+            shader,
+            vertex_entry_point: "vs_main",
+            fragment_shader: shader,
+            fragment_entry_point: Some("fs_main"),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+            vertex_buffers: &[],
+            targets: vec![Some(format.into())],
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        }
+    }
+
+    /// Label of the pipeline. The layout is labeled after it.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn vertex_entry_point(mut self, entry_point: &'a str) -> Self {
+        self.vertex_entry_point = entry_point;
+        self
+    }
+
+    /// Takes the fragment stage from another shader module.
+    pub fn fragment_shader(mut self, shader: &'a wgpu::ShaderModule) -> Self {
+        self.fragment_shader = shader;
+        self
+    }
+
+    /// Fragment entry point, or `None` for a pipeline without fragment stage, e.g. for shadow
+    /// maps or depth prepasses. Targets are ignored without fragment stage.
+    pub fn fragment_entry_point(mut self, entry_point: Option<&'a str>) -> Self {
+        self.fragment_entry_point = entry_point;
+        self
+    }
+
+    pub fn push_constant_ranges(mut self, ranges: &'a [wgpu::PushConstantRange]) -> Self {
+        self.push_constant_ranges = ranges;
+        self
+    }
+
+    pub fn vertex_buffers(mut self, buffers: &'a [wgpu::VertexBufferLayout<'a>]) -> Self {
+        self.vertex_buffers = buffers;
+        self
+    }
+
+    /// Sets the blend state of all targets.
+    pub fn blend(mut self, blend: wgpu::BlendState) -> Self {
+        for target in self.targets.iter_mut().flatten() {
+            target.blend = Some(blend);
+        }
+        self
+    }
+
+    /// Replaces all targets, e.g. for multiple render targets.
+    pub fn targets(mut self, targets: &[Option<wgpu::ColorTargetState>]) -> Self {
+        self.targets = targets.to_vec();
+        self
+    }
+
+    pub fn primitive(mut self, primitive: wgpu::PrimitiveState) -> Self {
+        self.primitive = primitive;
+        self
+    }
+
+    pub fn depth_stencil(mut self, depth_stencil: Option<wgpu::DepthStencilState>) -> Self {
+        self.depth_stencil = depth_stencil;
+        self
+    }
+
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.multisample.count = count;
+        self
+    }
+
+    pub fn multisample(mut self, multisample: wgpu::MultisampleState) -> Self {
+        self.multisample = multisample;
+        self
+    }
+
+    pub fn build(&self, device: &wgpu::Device) -> wgpu::RenderPipeline {
+        let layout_label = self.label.map(|label| format!("{label} layout"));
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: layout_label.as_deref(),
+            bind_group_layouts: self.bind_group_layouts,
+            push_constant_ranges: self.push_constant_ranges,
+        });
+
+        crate::resource_log::create_render_pipeline(
+            device,
+            &wgpu::RenderPipelineDescriptor {
+                label: self.label,
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: self.shader,
+                    entry_point: self.vertex_entry_point,
+                    buffers: self.vertex_buffers,
+                },
+                primitive: self.primitive,
+                depth_stencil: self.depth_stencil.clone(),
+                multisample: self.multisample,
+                fragment: self
+                    .fragment_entry_point
+                    .map(|entry_point| wgpu::FragmentState {
+                        module: self.fragment_shader,
+                        entry_point,
+                        targets: &self.targets,
+                    }),
+                multiview: None,
+            },
+        )
+    }
+}
+
+/// Error returned by [`ComputePipelineBuilder::build`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComputePipelineError {
+    /// The source passed to [`ComputePipelineBuilder::reflect`] isn't valid WGSL.
+    Parse(String),
+    /// The source passed to [`ComputePipelineBuilder::reflect`] failed validation.
+    Validation(String),
+    /// The source has no compute entry point of this name.
+    MissingEntryPoint(String),
+    /// The shader uses a binding that has no layout equivalent, e.g. a storage texture format
+    /// wgpu doesn't know.
+    UnsupportedBinding {
+        group: u32,
+        binding: u32,
+        name: Option<String>,
+    },
+    /// The shader uses a binding missing from the provided layout entries of its group.
+    MissingBinding {
+        group: u32,
+        binding: u32,
+        name: Option<String>,
+    },
+    /// The provided layout entry doesn't match how the shader declares the binding.
+    BindingMismatch {
+        group: u32,
+        binding: u32,
+        name: Option<String>,
+        shader: wgpu::BindingType,
+        layout: wgpu::BindingType,
+    },
+}
+
+impl fmt::Display for ComputePipelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let binding_name = |group: &u32, binding: &u32, name: &Option<String>| match name {
+            Some(name) => format!("binding `{}` (group {}, binding {})", name, group, binding),
+            None => format!("binding (group {}, binding {})", group, binding),
+        };
+        match self {
+            Self::Parse(message) => write!(f, "failed to parse shader: {}", message),
+            Self::Validation(message) => write!(f, "shader failed validation: {}", message),
+            Self::MissingEntryPoint(name) => {
+                write!(f, "shader has no compute entry point `{}`", name)
+            }
+            Self::UnsupportedBinding {
+                group,
+                binding,
+                name,
+            } => write!(
+                f,
+                "{} has no bind group layout equivalent",
+                binding_name(group, binding, name)
+            ),
+            Self::MissingBinding {
+                group,
+                binding,
+                name,
+            } => write!(
+                f,
+                "{} is used by the shader but missing from the provided layout",
+                binding_name(group, binding, name)
+            ),
+            Self::BindingMismatch {
+                group,
+                binding,
+                name,
+                shader,
+                layout,
+            } => write!(
+                f,
+                "{} is {:?} in the shader but {:?} in the provided layout",
+                binding_name(group, binding, name),
+                shader,
+                layout
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ComputePipelineError {}
+
+/// Builds a [`wgpu::ComputePipeline`] and its bind group layouts.
+///
+/// Layout entries of bind groups are provided with [`Self::bind_group`]. With the `reflect`
+/// feature, [`Self::reflect`] derives the entries of all other groups from the WGSL source and
+/// checks the provided ones against it, so mismatches are reported with binding names instead
+/// of failing pipeline creation.
+#[derive(Clone, Debug)]
+pub struct ComputePipelineBuilder<'a> {
+    label: wgpu::Label<'a>,
+    shader: &'a wgpu::ShaderModule,
+    entry_point: &'a str,
+    groups: Vec<Option<&'a [wgpu::BindGroupLayoutEntry]>>,
+    push_constant_ranges: &'a [wgpu::PushConstantRange],
+    #[cfg(feature = "reflect")]
+    source: Option<&'a str>,
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    pub fn new(shader: &'a wgpu::ShaderModule, entry_point: &'a str) -> Self {
+        Self {
+            label: None,
+            shader,
+            entry_point,
+            groups: Vec::new(),
+            push_constant_ranges: &[],
+            #[cfg(feature = "reflect")]
+            source: None,
+        }
+    }
+
+    /// Label of the pipeline. Layouts are labeled after it.
+    pub fn label(mut self, label: &'a str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Provides the layout entries of bind group `group`. Groups without entries are empty,
+    /// unless reflected.
+    pub fn bind_group(mut self, group: u32, entries: &'a [wgpu::BindGroupLayoutEntry]) -> Self {
+        let group = group as usize;
+        if self.groups.len() <= group {
+            self.groups.resize(group + 1, None);
+        }
+        self.groups[group] = Some(entries);
+        self
+    }
+
+    pub fn push_constant_ranges(mut self, ranges: &'a [wgpu::PushConstantRange]) -> Self {
+        self.push_constant_ranges = ranges;
+        self
+    }
+
+    /// Reflects the bindings of the entry point from `source`, the WGSL the shader module was
+    /// created from, after composing.
+    #[cfg(feature = "reflect")]
+    pub fn reflect(mut self, source: &'a str) -> Self {
+        self.source = Some(source);
+        self
+    }
+
+    pub fn build(&self, device: &wgpu::Device) -> Result<ComputeKernel, ComputePipelineError> {
+        let groups: Vec<Vec<wgpu::BindGroupLayoutEntry>> = self
+            .groups
+            .iter()
+            .map(|entries| entries.map(<[_]>::to_vec).unwrap_or_default())
+            .collect();
+        #[cfg(feature = "reflect")]
+        let groups = self.reflect_groups(groups)?;
+
+        let layout_label = |suffix: &str| self.label.map(|label| format!("{label} {suffix}"));
+        let bind_group_layouts: Vec<_> = groups
+            .iter()
+            .enumerate()
+            .map(|(i, entries)| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: layout_label(&format!("bind group layout {i}")).as_deref(),
+                    entries,
+                })
+            })
+            .collect();
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: layout_label("layout").as_deref(),
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: self.push_constant_ranges,
+        });
+        let pipeline = crate::resource_log::create_compute_pipeline(
+            device,
+            &wgpu::ComputePipelineDescriptor {
+                label: self.label,
+                layout: Some(&layout),
+                module: self.shader,
+                entry_point: self.entry_point,
+            },
+        );
+
+        Ok(ComputeKernel {
+            pipeline,
+            bind_group_layouts,
+        })
+    }
+}
+
+#[cfg(feature = "reflect")]
+impl ComputePipelineBuilder<'_> {
+    /// Adds the reflected entries to groups without provided entries and checks the provided
+    /// ones.
+    fn reflect_groups(
+        &self,
+        mut groups: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
+    ) -> Result<Vec<Vec<wgpu::BindGroupLayoutEntry>>, ComputePipelineError> {
+        let source = match self.source {
+            Some(source) => source,
+            None => return Ok(groups),
+        };
+        for reflected in reflect_bindings(source, self.entry_point)? {
+            let group = reflected.group as usize;
+            if groups.len() <= group {
+                groups.resize(group + 1, Vec::new());
+            }
+            match self.groups.get(group).copied().flatten() {
+                Some(provided) => check_binding(&reflected, provided)?,
+                None => groups[group].push(reflected.entry),
+            }
+        }
+        Ok(groups)
+    }
+}
+
+/// A compute pipeline with the bind group layouts it was built with.
+#[derive(Debug)]
+pub struct ComputeKernel {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+}
+
+impl ComputeKernel {
+    pub fn pipeline(&self) -> &wgpu::ComputePipeline {
+        &self.pipeline
+    }
+
+    /// Layout of bind group `group`, for creating its bind groups.
+    pub fn bind_group_layout(&self, group: u32) -> Option<&wgpu::BindGroupLayout> {
+        self.bind_group_layouts.get(group as usize)
+    }
+}
+
+type CreateFn<K, P> = dyn Fn(&wgpu::Device, &K) -> P + Send + Sync;
+
+/// Pipelines created on first use by a user provided function, e.g. permutations of a material
+/// keyed by their defines.
+///
+/// Creating pipelines on first use hitches, so loading screens can compile the expected keys on
+/// a background thread with [`Self::warmup_with_progress`].
+pub struct PipelineCache<K, P> {
+    pipelines: HashMap<K, P>,
+    create: Arc<CreateFn<K, P>>,
+    /// Pipelines compiled by warmups.
+    sender: mpsc::Sender<(K, P)>,
+    receiver: mpsc::Receiver<(K, P)>,
+}
+
+impl<K: fmt::Debug, P: fmt::Debug> fmt::Debug for PipelineCache<K, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipelineCache")
+            .field("pipelines", &self.pipelines)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<K, P> PipelineCache<K, P>
+where
+    K: Clone + Eq + Hash + Send + 'static,
+    P: Send + 'static,
+{
+    pub fn new(create: impl Fn(&wgpu::Device, &K) -> P + Send + Sync + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            pipelines: HashMap::new(),
+            create: Arc::new(create),
+            sender,
+            receiver,
+        }
+    }
+
+    /// Gets the pipeline of `key`, creating it if it wasn't created or warmed up before.
+    pub fn get(&mut self, device: &wgpu::Device, key: &K) -> &P {
+        self.receive_warmed();
+        if !self.pipelines.contains_key(key) {
+            let pipeline = (self.create)(device, key);
+            self.pipelines.insert(key.clone(), pipeline);
+        }
+        &self.pipelines[key]
+    }
+
+    /// Gets the pipeline of `key` if it was already created.
+    pub fn peek(&mut self, key: &K) -> Option<&P> {
+        self.receive_warmed();
+        self.pipelines.get(key)
+    }
+
+    /// Whether the pipeline of `key` was already created.
+    pub fn contains(&mut self, key: &K) -> bool {
+        self.receive_warmed();
+        self.pipelines.contains_key(key)
+    }
+
+    /// Number of created pipelines.
+    pub fn len(&mut self) -> usize {
+        self.receive_warmed();
+        self.pipelines.len()
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+
+    /// Creates the pipelines of `keys` on a background thread, skipping already created ones.
+    ///
+    /// Pipelines become available to the cache as they are compiled. The returned handle reports
+    /// the progress, e.g. for a loading screen, and can be awaited or waited on.
+    pub fn warmup_with_progress(
+        &mut self,
+        device: Arc<wgpu::Device>,
+        keys: impl IntoIterator<Item = K>,
+    ) -> WarmupHandle {
+        self.receive_warmed();
+        let mut keys: Vec<K> = keys
+            .into_iter()
+            .filter(|key| !self.pipelines.contains_key(key))
+            .collect();
+        // Duplicate keys would be compiled twice.
+        let mut seen = HashSet::new();
+        keys.retain(|key| seen.insert(key.clone()));
+
+        let state = Arc::new(WarmupState {
+            total: keys.len(),
+            progress: Mutex::default(),
+            condvar: Condvar::new(),
+        });
+        let create = self.create.clone();
+        let sender = self.sender.clone();
+        let thread_state = state.clone();
+        let thread = std::thread::Builder::new()
+            .name("pipeline warmup".to_owned())
+            .spawn(move || {
+                for key in keys {
+                    let result =
+                        std::panic::catch_unwind(AssertUnwindSafe(|| create(&device, &key)));
+                    let pipeline = match result {
+                        Ok(pipeline) => pipeline,
+                        Err(panic) => {
+                            // Waiters must not wait for the remaining pipelines.
+                            thread_state.panicked();
+                            std::panic::resume_unwind(panic);
+                        }
+                    };
+                    // The cache may have been dropped, the pipeline is discarded then.
+                    let _ = sender.send((key, pipeline));
+                    thread_state.compiled();
+                }
+            })
+            .expect("failed to spawn pipeline warmup thread");
+
+        WarmupHandle {
+            state,
+            thread: Some(thread),
+        }
+    }
+
+    fn receive_warmed(&mut self) {
+        for (key, pipeline) in self.receiver.try_iter() {
+            self.pipelines.entry(key).or_insert(pipeline);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct WarmupProgress {
+    compiled: usize,
+    /// Set if the create function panicked, which ends the warmup.
+    panicked: bool,
+    waker: Option<Waker>,
+}
+
+impl WarmupProgress {
+    fn is_done(&self, total: usize) -> bool {
+        self.compiled == total || self.panicked
+    }
+}
+
+#[derive(Debug)]
+struct WarmupState {
+    total: usize,
+    progress: Mutex<WarmupProgress>,
+    condvar: Condvar,
+}
+
+impl WarmupState {
+    fn compiled(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.compiled += 1;
+        if progress.is_done(self.total) {
+            if let Some(waker) = progress.waker.take() {
+                waker.wake();
+            }
+        }
+        self.condvar.notify_all();
+    }
+
+    fn panicked(&self) {
+        let mut progress = self.progress.lock().unwrap();
+        progress.panicked = true;
+        if let Some(waker) = progress.waker.take() {
+            waker.wake();
+        }
+        self.condvar.notify_all();
+    }
+}
+
+/// Progress of a [`PipelineCache::warmup_with_progress`], resolving once all pipelines are
+/// compiled or the create function panicked, see [`Self::panicked`].
+#[derive(Debug)]
+pub struct WarmupHandle {
+    state: Arc<WarmupState>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl WarmupHandle {
+    /// Number of pipelines compiled by the warmup.
+    pub fn total(&self) -> usize {
+        self.state.total
+    }
+
+    /// Number of pipelines which remain to be compiled.
+    pub fn remaining(&self) -> usize {
+        self.state.total - self.state.progress.lock().unwrap().compiled
+    }
+
+    /// Compiled fraction between `0.0` and `1.0`.
+    pub fn fraction(&self) -> f32 {
+        match self.state.total {
+            0 => 1.0,
+            total => (total - self.remaining()) as f32 / total as f32,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    /// Whether the create function panicked, which leaves the remaining pipelines uncompiled.
+    pub fn panicked(&self) -> bool {
+        self.state.progress.lock().unwrap().panicked
+    }
+
+    /// Blocks until all pipelines are compiled.
+    ///
+    /// # Panics
+    ///
+    /// Resumes a panic of the create function.
+    pub fn wait(mut self) {
+        if let Some(thread) = self.thread.take() {
+            if let Err(panic) = thread.join() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+    }
+
+    /// Blocks until all pipelines are compiled or `timeout` elapsed, returning whether they are
+    /// compiled, e.g. to bound the time a loading screen waits before showing the next frame.
+    ///
+    /// Returns `false` early if the create function panicked.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let progress = self.state.progress.lock().unwrap();
+        let (progress, _) = self
+            .state
+            .condvar
+            .wait_timeout_while(progress, timeout, |progress| {
+                !progress.is_done(self.state.total)
+            })
+            .unwrap();
+        progress.compiled == self.state.total
+    }
+}
+
+impl Future for WarmupHandle {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut progress = self.state.progress.lock().unwrap();
+        match progress.is_done(self.state.total) {
+            true => Poll::Ready(()),
+            false => {
+                progress.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(feature = "reflect")]
+struct ReflectedBinding {
+    group: u32,
+    name: Option<String>,
+    entry: wgpu::BindGroupLayoutEntry,
+}
+
+/// Layout entries of the resources used by compute entry point `entry_point`.
+#[cfg(feature = "reflect")]
+fn reflect_bindings(
+    source: &str,
+    entry_point: &str,
+) -> Result<Vec<ReflectedBinding>, ComputePipelineError> {
+    use naga::{AddressSpace, ImageClass, TypeInner};
+
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|error| ComputePipelineError::Parse(error.emit_to_string(source)))?;
+    let info = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|error| ComputePipelineError::Validation(error.to_string()))?;
+    let index = module
+        .entry_points
+        .iter()
+        .position(|ep| ep.stage == naga::ShaderStage::Compute && ep.name == entry_point)
+        .ok_or_else(|| ComputePipelineError::MissingEntryPoint(entry_point.to_owned()))?;
+    let uses = info.get_entry_point(index);
+
+    let used: Vec<_> = module
+        .global_variables
+        .iter()
+        .filter(|(handle, _)| !uses[*handle].is_empty())
+        .filter_map(|(_, var)| var.binding.as_ref().map(|binding| (binding, var)))
+        .collect();
+    // Textures sampled with a filtering sampler must be filterable, others may be either.
+    let filterable = used.iter().any(|(_, var)| {
+        matches!(
+            module.types[var.ty].inner,
+            TypeInner::Sampler { comparison: false }
+        )
+    });
+
+    used.into_iter()
+        .map(|(binding, var)| {
+            let unsupported = || ComputePipelineError::UnsupportedBinding {
+                group: binding.group,
+                binding: binding.binding,
+                name: var.name.clone(),
+            };
+            let inner = &module.types[var.ty].inner;
+            let min_binding_size = wgpu::BufferSize::new(inner.size(&module.constants) as u64);
+            let ty = match (var.space, inner) {
+                (AddressSpace::Uniform, _) => wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size,
+                },
+                (AddressSpace::Storage { access }, _) => wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage {
+                        read_only: !access.contains(naga::StorageAccess::STORE),
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size,
+                },
+                (AddressSpace::Handle, TypeInner::Sampler { comparison }) => {
+                    wgpu::BindingType::Sampler(match comparison {
+                        true => wgpu::SamplerBindingType::Comparison,
+                        false => wgpu::SamplerBindingType::Filtering,
+                    })
+                }
+                (
+                    AddressSpace::Handle,
+                    TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class,
+                    },
+                ) => {
+                    let view_dimension = view_dimension(*dim, *arrayed).ok_or_else(unsupported)?;
+                    match *class {
+                        ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                            sample_type: match kind {
+                                naga::ScalarKind::Float => wgpu::TextureSampleType::Float {
+                                    filterable: filterable && !multi,
+                                },
+                                naga::ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                                naga::ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                                naga::ScalarKind::Bool => return Err(unsupported()),
+                            },
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Depth { multi } => wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Depth,
+                            view_dimension,
+                            multisampled: multi,
+                        },
+                        ImageClass::Storage { format, access } => {
+                            wgpu::BindingType::StorageTexture {
+                                access: match (
+                                    access.contains(naga::StorageAccess::LOAD),
+                                    access.contains(naga::StorageAccess::STORE),
+                                ) {
+                                    (true, true) => wgpu::StorageTextureAccess::ReadWrite,
+                                    (true, false) => wgpu::StorageTextureAccess::ReadOnly,
+                                    _ => wgpu::StorageTextureAccess::WriteOnly,
+                                },
+                                format: texture_format(format),
+                                view_dimension,
+                            }
+                        }
+                    }
+                }
+                _ => return Err(unsupported()),
+            };
+
+            Ok(ReflectedBinding {
+                group: binding.group,
+                name: var.name.clone(),
+                entry: wgpu::BindGroupLayoutEntry {
+                    binding: binding.binding,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty,
+                    count: None,
+                },
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "reflect")]
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> Option<wgpu::TextureViewDimension> {
+    use naga::ImageDimension as D;
+    use wgpu::TextureViewDimension as V;
+
+    match (dim, arrayed) {
+        (D::D1, false) => Some(V::D1),
+        (D::D2, false) => Some(V::D2),
+        (D::D2, true) => Some(V::D2Array),
+        (D::D3, false) => Some(V::D3),
+        (D::Cube, false) => Some(V::Cube),
+        (D::Cube, true) => Some(V::CubeArray),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "reflect")]
+fn texture_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    use naga::StorageFormat as S;
+    use wgpu::TextureFormat as F;
+
+    match format {
+        S::R8Unorm => F::R8Unorm,
+        S::R8Snorm => F::R8Snorm,
+        S::R8Uint => F::R8Uint,
+        S::R8Sint => F::R8Sint,
+        S::R16Uint => F::R16Uint,
+        S::R16Sint => F::R16Sint,
+        S::R16Float => F::R16Float,
+        S::Rg8Unorm => F::Rg8Unorm,
+        S::Rg8Snorm => F::Rg8Snorm,
+        S::Rg8Uint => F::Rg8Uint,
+        S::Rg8Sint => F::Rg8Sint,
+        S::R32Uint => F::R32Uint,
+        S::R32Sint => F::R32Sint,
+        S::R32Float => F::R32Float,
+        S::Rg16Uint => F::Rg16Uint,
+        S::Rg16Sint => F::Rg16Sint,
+        S::Rg16Float => F::Rg16Float,
+        S::Rgba8Unorm => F::Rgba8Unorm,
+        S::Rgba8Snorm => F::Rgba8Snorm,
+        S::Rgba8Uint => F::Rgba8Uint,
+        S::Rgba8Sint => F::Rgba8Sint,
+        S::Rgb10a2Unorm => F::Rgb10a2Unorm,
+        S::Rg11b10Float => F::Rg11b10Float,
+        S::Rg32Uint => F::Rg32Uint,
+        S::Rg32Sint => F::Rg32Sint,
+        S::Rg32Float => F::Rg32Float,
+        S::Rgba16Uint => F::Rgba16Uint,
+        S::Rgba16Sint => F::Rgba16Sint,
+        S::Rgba16Float => F::Rgba16Float,
+        S::Rgba32Uint => F::Rgba32Uint,
+        S::Rgba32Sint => F::Rgba32Sint,
+        S::Rgba32Float => F::Rgba32Float,
+    }
+}
+
+/// Checks that `provided` has an entry compatible with the reflected binding.
+#[cfg(feature = "reflect")]
+fn check_binding(
+    reflected: &ReflectedBinding,
+    provided: &[wgpu::BindGroupLayoutEntry],
+) -> Result<(), ComputePipelineError> {
+    use wgpu::BindingType as B;
+
+    let (group, binding, name) = (
+        reflected.group,
+        reflected.entry.binding,
+        reflected.name.clone(),
+    );
+    let layout = match provided.iter().find(|entry| entry.binding == binding) {
+        Some(entry) => entry.ty,
+        None => {
+            return Err(ComputePipelineError::MissingBinding {
+                group,
+                binding,
+                name,
+            })
+        }
+    };
+    let shader = reflected.entry.ty;
+    let compatible = match (shader, layout) {
+        (
+            B::Buffer {
+                ty: shader_ty,
+                min_binding_size: shader_size,
+                ..
+            },
+            B::Buffer {
+                ty: layout_ty,
+                min_binding_size: layout_size,
+                ..
+            },
+        ) => {
+            shader_ty == layout_ty
+                && match (shader_size, layout_size) {
+                    (Some(shader_size), Some(layout_size)) => layout_size >= shader_size,
+                    _ => true,
+                }
+        }
+        (B::Sampler(shader_ty), B::Sampler(layout_ty)) => {
+            (shader_ty == wgpu::SamplerBindingType::Comparison)
+                == (layout_ty == wgpu::SamplerBindingType::Comparison)
+        }
+        (
+            B::Texture {
+                sample_type: shader_sample_type,
+                view_dimension: shader_dimension,
+                multisampled: shader_multisampled,
+            },
+            B::Texture {
+                sample_type: layout_sample_type,
+                view_dimension: layout_dimension,
+                multisampled: layout_multisampled,
+            },
+        ) => {
+            std::mem::discriminant(&shader_sample_type)
+                == std::mem::discriminant(&layout_sample_type)
+                && shader_dimension == layout_dimension
+                && shader_multisampled == layout_multisampled
+        }
+        (B::StorageTexture { .. }, B::StorageTexture { .. }) => shader == layout,
+        _ => false,
+    };
+    match compatible {
+        true => Ok(()),
+        false => Err(ComputePipelineError::BindingMismatch {
+            group,
+            binding,
+            name,
+            shader,
+            layout,
+        }),
+    }
+}
@@ -0,0 +1,326 @@
+//! Drawing point clouds from storage buffers, e.g. for scientific visualization.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{CameraBuffer, CAMERA_SNIPPET},
+    lut::GradientTexture,
+    pipeline::RenderPipelineBuilder,
+    shader::ShaderComposer,
+    BufferInitDescriptor, DeviceExt,
+};
+
+const PARAMS_SIZE: wgpu::BufferAddress = 48;
+
+/// Size of a point in the storage buffer: the world position in `xyz` and the attribute in `w`
+/// of a `vec4<f32>`.
+pub const POINT_SIZE: wgpu::BufferAddress = 16;
+
+/// How [`PointCloudRenderer`] rasterizes points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PointPrimitive {
+    /// Camera facing quads of [`PointCloudParams::size`].
+    Billboard,
+    /// Native points, which are always a single pixel, but cheapest for dense clouds.
+    Hardware,
+}
+
+/// Appearance of the points of a [`PointCloudRenderer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PointCloudParams {
+    /// Diameter of billboards in pixels, or in world units if attenuated.
+    pub size: f32,
+    /// Whether billboards shrink with distance like world space geometry.
+    pub attenuate: bool,
+    /// Whether billboards are discs instead of squares.
+    pub round: bool,
+    /// Color of the points, multiplied with the gradient if there is one.
+    pub color: [f32; 4],
+    /// Attribute values mapped to the start and the end of the gradient. Values outside are
+    /// clamped.
+    pub attribute_range: [f32; 2],
+}
+
+impl Default for PointCloudParams {
+    fn default() -> Self {
+        Self {
+            size: 4.0,
+            attenuate: false,
+            round: true,
+            color: [1.0; 4],
+            attribute_range: [0.0, 1.0],
+        }
+    }
+}
+
+impl PointCloudParams {
+    fn to_bytes(self, viewport: [f32; 2]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self
+            .color
+            .into_iter()
+            .chain(self.attribute_range)
+            .chain(viewport)
+            .chain([self.size])
+            .flat_map(f32::to_le_bytes)
+            .collect();
+        for flag in [self.attenuate, self.round] {
+            bytes.extend_from_slice(&(flag as u32).to_le_bytes());
+        }
+        bytes.resize(PARAMS_SIZE as usize, 0);
+        bytes
+    }
+}
+
+/// Descriptor for [`PointCloudRenderer::new`].
+#[derive(Clone, Debug)]
+pub struct PointCloudDescriptor<'a> {
+    pub format: wgpu::TextureFormat,
+    pub depth_stencil: Option<wgpu::DepthStencilState>,
+    pub sample_count: u32,
+    pub primitive: PointPrimitive,
+    /// Gradient coloring points by their attribute, otherwise they have a single color.
+    pub gradient: Option<&'a GradientTexture>,
+    pub params: PointCloudParams,
+}
+
+impl Default for PointCloudDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            format: wgpu::TextureFormat::Bgra8UnormSrgb,
+            depth_stencil: Some(crate::depth::read_write_less(
+                wgpu::TextureFormat::Depth32Float,
+            )),
+            sample_count: 1,
+            primitive: PointPrimitive::Billboard,
+            gradient: None,
+            params: PointCloudParams::default(),
+        }
+    }
+}
+
+/// Draws points from storage buffers of [`POINT_SIZE`] bytes per point, seen by the camera of a
+/// [`CameraBuffer`].
+///
+/// Points are pulled in the vertex shader, so buffers written by compute shaders, e.g. of
+/// simulations, are drawn without copies. They need [`wgpu::BufferUsages::STORAGE`] and a bind
+/// group from [`Self::create_bind_group`].
+#[derive(Debug)]
+pub struct PointCloudRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    points_bind_group_layout: wgpu::BindGroupLayout,
+    params_buffer: wgpu::Buffer,
+    params: PointCloudParams,
+    viewport: [f32; 2],
+    primitive: PointPrimitive,
+}
+
+impl PointCloudRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        descriptor: &PointCloudDescriptor<'_>,
+    ) -> Self {
+        let dimension = descriptor
+            .gradient
+            .map_or(wgpu::TextureViewDimension::D2, |gradient| {
+                gradient.view_dimension()
+            });
+        let (gradient_texture, gradient_coordinate) = match dimension {
+            wgpu::TextureViewDimension::D1 => ("texture_1d<f32>", "in.t"),
+            _ => ("texture_2d<f32>", "vec2<f32>(in.t, 0.5)"),
+        };
+        let source = include_str!("shaders/point_cloud.wgsl")
+            .replace("{gradient_texture}", gradient_texture)
+            .replace("{gradient_coordinate}", gradient_coordinate);
+        let mut composer = ShaderComposer::new();
+        composer.add_snippet(CAMERA_SNIPPET, crate::camera::camera_snippet(0, 0));
+        let shader = composer
+            .create_shader_module(device, Some("point cloud shader"), &source)
+            .expect("builtin snippets must compose");
+
+        let mut entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                ..camera.bind_group_layout_entry()
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                },
+                count: None,
+            },
+        ];
+        if descriptor.gradient.is_some() {
+            entries.extend([
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: dimension,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]);
+        }
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("point cloud bind group layout"),
+            entries: &entries,
+        });
+        let points_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("point cloud points bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(POINT_SIZE),
+                    },
+                    count: None,
+                }],
+            });
+
+        let viewport = [1.0; 2];
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("point cloud params buffer"),
+            contents: &descriptor.params.to_bytes(viewport),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("point cloud gradient sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.buffer().as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: params_buffer.as_entire_binding(),
+            },
+        ];
+        if let Some(gradient) = descriptor.gradient {
+            entries.extend([
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(gradient.view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ]);
+        }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("point cloud bind group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+
+        let (vertex_entry_point, topology) = match descriptor.primitive {
+            PointPrimitive::Billboard => ("vs_billboard", wgpu::PrimitiveTopology::TriangleList),
+            PointPrimitive::Hardware => ("vs_point", wgpu::PrimitiveTopology::PointList),
+        };
+        let fragment_entry_point = match descriptor.gradient {
+            Some(_) => "fs_gradient",
+            None => "fs_color",
+        };
+        let pipeline = RenderPipelineBuilder::new(
+            &shader,
+            &[&bind_group_layout, &points_bind_group_layout],
+            descriptor.format,
+        )
+        .label("point cloud pipeline")
+        .vertex_entry_point(vertex_entry_point)
+        .fragment_entry_point(Some(fragment_entry_point))
+        .primitive(wgpu::PrimitiveState {
+            topology,
+            ..Default::default()
+        })
+        .depth_stencil(descriptor.depth_stencil.clone())
+        .sample_count(descriptor.sample_count)
+        .build(device);
+
+        Self {
+            pipeline,
+            bind_group,
+            points_bind_group_layout,
+            params_buffer,
+            params: descriptor.params,
+            viewport,
+            primitive: descriptor.primitive,
+        }
+    }
+
+    /// Bind group pulling the points of `points`, which must have
+    /// [`wgpu::BufferUsages::STORAGE`].
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        points: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("point cloud points bind group"),
+            layout: &self.points_bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: points.as_entire_binding(),
+            }],
+        })
+    }
+
+    /// Draws the first `count` points of the buffer of `bind_group`, created by
+    /// [`Self::create_bind_group`], into `pass`.
+    pub fn draw<'a>(
+        &'a self,
+        pass: &mut wgpu::RenderPass<'a>,
+        bind_group: &'a wgpu::BindGroup,
+        count: u32,
+    ) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, bind_group, &[]);
+        match self.primitive {
+            PointPrimitive::Billboard => pass.draw(0..6, 0..count),
+            PointPrimitive::Hardware => pass.draw(0..count, 0..1),
+        }
+    }
+
+    /// Sets the size of the target in pixels, which sizes billboards that aren't attenuated.
+    pub fn set_viewport(&mut self, queue: &wgpu::Queue, width: u32, height: u32) {
+        self.viewport = [width.max(1) as f32, height.max(1) as f32];
+        self.write_params(queue);
+    }
+
+    pub fn params(&self) -> &PointCloudParams {
+        &self.params
+    }
+
+    /// Changes the appearance and uploads it using [`wgpu::Queue`].
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: PointCloudParams) {
+        self.params = params;
+        self.write_params(queue);
+    }
+
+    fn write_params(&self, queue: &wgpu::Queue) {
+        queue.write_buffer(&self.params_buffer, 0, &self.params.to_bytes(self.viewport));
+    }
+}
@@ -0,0 +1,403 @@
+use std::num::{NonZeroU32, NonZeroU64};
+
+/// A 3D color lookup table.
+///
+/// Entries are ordered with red changing fastest, then green, then blue, as in `.cube` files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lut3d {
+    pub title: Option<String>,
+    /// Number of entries along each axis.
+    pub size: u32,
+    /// Input color mapped to the first entry.
+    pub domain_min: [f32; 3],
+    /// Input color mapped to the last entry.
+    pub domain_max: [f32; 3],
+    /// `size³` output colors.
+    pub data: Vec<[f32; 3]>,
+}
+
+impl Lut3d {
+    /// LUT which maps every color to itself.
+    pub fn identity(size: u32) -> Self {
+        assert!(size >= 2, "size must be at least 2");
+        let max = (size - 1) as f32;
+        let data = (0..size)
+            .flat_map(|b| (0..size).flat_map(move |g| (0..size).map(move |r| (r, g, b))))
+            .map(|(r, g, b)| [r as f32 / max, g as f32 / max, b as f32 / max])
+            .collect();
+
+        Self {
+            title: None,
+            size,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            data,
+        }
+    }
+
+    /// Parses an Adobe/Resolve `.cube` file containing a 3D LUT.
+    #[cfg(feature = "cube")]
+    pub fn parse_cube(source: &str) -> Result<Self, CubeError> {
+        let mut title = None;
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut data = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let syntax_error = |message: &str| CubeError::Syntax {
+                line: line_number,
+                message: message.to_owned(),
+            };
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let rest = rest.trim();
+            let parse_triple = |s: &str| -> Result<[f32; 3], CubeError> {
+                let values = s
+                    .split_whitespace()
+                    .map(str::parse)
+                    .collect::<Result<Vec<f32>, _>>()
+                    .map_err(|_| syntax_error("expected three numbers"))?;
+                values
+                    .try_into()
+                    .map_err(|_| syntax_error("expected three numbers"))
+            };
+
+            match keyword {
+                "TITLE" => title = Some(rest.trim_matches('"').to_owned()),
+                "LUT_3D_SIZE" => {
+                    let parsed: u32 = rest
+                        .parse()
+                        .map_err(|_| syntax_error("expected an integer size"))?;
+                    if parsed < 2 {
+                        return Err(syntax_error("size must be at least 2"));
+                    }
+                    size = Some(parsed);
+                }
+                "LUT_1D_SIZE" => return Err(CubeError::Unsupported1d),
+                "DOMAIN_MIN" => domain_min = parse_triple(rest)?,
+                "DOMAIN_MAX" => domain_max = parse_triple(rest)?,
+                // Unknown keywords, e.g. LUT_3D_INPUT_RANGE of some tools, are ignored.
+                keyword if keyword.starts_with(|c: char| c.is_ascii_alphabetic()) => {}
+                _ => data.push(parse_triple(line)?),
+            }
+        }
+
+        let size = size.ok_or(CubeError::MissingSize)?;
+        let expected = (size as usize).pow(3);
+        if data.len() != expected {
+            return Err(CubeError::EntryCount {
+                expected,
+                found: data.len(),
+            });
+        }
+
+        Ok(Self {
+            title,
+            size,
+            domain_min,
+            domain_max,
+            data,
+        })
+    }
+}
+
+/// Error returned by [`Lut3d::parse_cube`].
+#[cfg(feature = "cube")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CubeError {
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// The file contains a 1D LUT.
+    Unsupported1d,
+    /// `LUT_3D_SIZE` is missing.
+    MissingSize,
+    /// The number of entries doesn't match `LUT_3D_SIZE`.
+    EntryCount {
+        expected: usize,
+        found: usize,
+    },
+}
+
+#[cfg(feature = "cube")]
+impl std::fmt::Display for CubeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            Self::Unsupported1d => write!(f, "1D LUTs are not supported"),
+            Self::MissingSize => write!(f, "missing LUT_3D_SIZE"),
+            Self::EntryCount { expected, found } => {
+                write!(f, "expected {} entries, found {}", expected, found)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "cube")]
+impl std::error::Error for CubeError {}
+
+/// [`Lut3d`] uploaded into a 3D texture for [`ColorGradingPass`].
+#[derive(Debug)]
+pub struct ColorGradingLut {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    size: u32,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+}
+
+impl ColorGradingLut {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, lut: &Lut3d) -> Self {
+        assert_eq!(
+            lut.data.len(),
+            (lut.size as usize).pow(3),
+            "LUT must have size³ entries"
+        );
+
+        let extent = wgpu::Extent3d {
+            width: lut.size,
+            height: lut.size,
+            depth_or_array_layers: lut.size,
+        };
+        let texture = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("color grading lut"),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format: wgpu::TextureFormat::Rgba16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            },
+        );
+
+        let texels: Vec<u8> = lut
+            .data
+            .iter()
+            .flat_map(|&[r, g, b]| [r, g, b, 1.0])
+            .flat_map(|c| crate::packing::f16_from_f32(c).to_le_bytes())
+            .collect();
+        queue.write_texture(
+            texture.as_image_copy(),
+            &texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(lut.size * 8),
+                rows_per_image: NonZeroU32::new(lut.size),
+            },
+            extent,
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            texture,
+            view,
+            size: lut.size,
+            domain_min: lut.domain_min,
+            domain_max: lut.domain_max,
+        }
+    }
+
+    /// Get a reference to the texture.
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// Number of entries along each axis.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+}
+
+/// Descriptor for [`ColorGradingPass`].
+#[derive(Clone, Debug)]
+pub struct ColorGradingPassDescriptor {
+    /// Format of the output texture. Non-sRGB formats get encoded in the shader.
+    pub output_format: wgpu::TextureFormat,
+    /// Blend factor between the input (`0.0`) and the graded color (`1.0`).
+    pub strength: f32,
+}
+
+/// Applies a [`ColorGradingLut`] with trilinear sampling.
+///
+/// The LUT is looked up with sRGB encoded colors clamped to `[0, 1]`, so the pass belongs after
+/// tonemapping.
+#[derive(Debug)]
+pub struct ColorGradingPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+
+    strength: f32,
+    encode_srgb: bool,
+}
+
+impl ColorGradingPass {
+    pub fn new(device: &wgpu::Device, descriptor: &ColorGradingPassDescriptor) -> Self {
+        let shader = super::fullscreen_shader_module(
+            device,
+            Some("color grading shader"),
+            include_str!("../shaders/color_grading.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("color grading bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(48),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("color grading pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::fullscreen_pipeline(
+            device,
+            Some("color grading pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(descriptor.output_format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("color grading sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("color grading params"),
+                size: 48,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+
+            strength: descriptor.strength,
+            encode_srgb: !descriptor.output_format.describe().srgb,
+        }
+    }
+
+    /// Sets the blend factor between the input and the graded color.
+    pub fn set_strength(&mut self, strength: f32) {
+        self.strength = strength;
+    }
+
+    /// Grades `input` with `lut` into `output`.
+    ///
+    /// `input` must be a filterable float texture, `output` must have the output format.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        lut: &ColorGradingLut,
+        output: &wgpu::TextureView,
+    ) {
+        let mut params = Vec::with_capacity(48);
+        params.extend_from_slice(&self.strength.to_le_bytes());
+        params.extend_from_slice(&(self.encode_srgb as u32).to_le_bytes());
+        params.extend_from_slice(&(lut.size as f32).to_le_bytes());
+        params.extend_from_slice(&0u32.to_le_bytes());
+        for domain in [lut.domain_min, lut.domain_max] {
+            for c in domain.into_iter().chain([0.0]) {
+                params.extend_from_slice(&c.to_le_bytes());
+            }
+        }
+        queue.write_buffer(&self.params, 0, &params);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("color grading bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&lut.view),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("color grading pass")
+                .read("input", input)
+                .read("lut", &lut.view)
+                .write("output", output)
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("color grading pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+}
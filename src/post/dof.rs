@@ -0,0 +1,358 @@
+use std::num::NonZeroU64;
+
+use super::{half_size, DownsamplePass, DownsamplePassDescriptor};
+use crate::texture::TexturePool;
+
+/// Format of the intermediate targets, holding color and the circle of confusion.
+const DOF_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Descriptor for [`DofPass`].
+#[derive(Clone, Debug)]
+pub struct DofPassDescriptor {
+    /// Format of the output texture.
+    pub output_format: wgpu::TextureFormat,
+    /// Near plane of the perspective projection the depth was rendered with.
+    pub near_plane: f32,
+    /// Far plane of the perspective projection the depth was rendered with.
+    pub far_plane: f32,
+    /// Distance in focus, in view space units.
+    pub focal_distance: f32,
+    /// Blur radius in pixels of objects at infinity. Objects in front of the focal plane blur
+    /// faster.
+    pub aperture: f32,
+    /// Largest blur radius in pixels.
+    pub max_coc_radius: f32,
+    /// Number of samples gathered per pixel.
+    pub sample_count: u32,
+}
+
+impl Default for DofPassDescriptor {
+    fn default() -> Self {
+        Self {
+            output_format: wgpu::TextureFormat::Rgba16Float,
+            near_plane: 0.1,
+            far_plane: 1000.0,
+            focal_distance: 10.0,
+            aperture: 8.0,
+            max_coc_radius: 16.0,
+            sample_count: 32,
+        }
+    }
+}
+
+/// Depth of field from a circle of confusion, blurring the near and far field separately.
+///
+/// Computes the circle of confusion at full resolution, gathers the blur at half resolution
+/// using [`DownsamplePass`] and composites it with the sharp input. Intermediate targets are
+/// acquired from a [`TexturePool`] and released afterwards.
+#[derive(Debug)]
+pub struct DofPass {
+    coc_pipeline: wgpu::RenderPipeline,
+    coc_bind_group_layout: wgpu::BindGroupLayout,
+    gather_pipeline: wgpu::RenderPipeline,
+    gather_bind_group_layout: wgpu::BindGroupLayout,
+    composite_pipeline: wgpu::RenderPipeline,
+    composite_bind_group_layout: wgpu::BindGroupLayout,
+    downsample: DownsamplePass,
+    sampler: wgpu::Sampler,
+    coc_params: wgpu::Buffer,
+    gather_params: wgpu::Buffer,
+
+    near_plane: f32,
+    far_plane: f32,
+    focal_distance: f32,
+    aperture: f32,
+    max_coc_radius: f32,
+    sample_count: u32,
+}
+
+impl DofPass {
+    pub fn new(device: &wgpu::Device, descriptor: &DofPassDescriptor) -> Self {
+        let uniform_entry = |binding, size| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(size),
+            },
+            count: None,
+        };
+        let texture_entry = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let sampler_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+            count: None,
+        };
+        let create_pipeline = |name: &str, source, entries: &[_], format: wgpu::TextureFormat| {
+            let shader = super::fullscreen_shader_module(
+                device,
+                Some(&format!("dof {} shader", name)),
+                source,
+            );
+            let bind_group_layout =
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(&format!("dof {} bind group layout", name)),
+                    entries,
+                });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&format!("dof {} pipeline layout", name)),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            let pipeline = super::fullscreen_pipeline(
+                device,
+                Some(&format!("dof {} pipeline", name)),
+                &pipeline_layout,
+                &shader,
+                &[Some(format.into())],
+            );
+            (pipeline, bind_group_layout)
+        };
+
+        // Depth is bound as float, see the shader.
+        let (coc_pipeline, coc_bind_group_layout) = create_pipeline(
+            "coc",
+            include_str!("../shaders/dof_coc.wgsl"),
+            &[
+                uniform_entry(0, 32),
+                texture_entry(1, false),
+                texture_entry(2, false),
+            ],
+            DOF_FORMAT,
+        );
+        let (gather_pipeline, gather_bind_group_layout) = create_pipeline(
+            "gather",
+            include_str!("../shaders/dof_gather.wgsl"),
+            &[
+                uniform_entry(0, 16),
+                texture_entry(1, true),
+                sampler_entry(2),
+            ],
+            DOF_FORMAT,
+        );
+        let (composite_pipeline, composite_bind_group_layout) = create_pipeline(
+            "composite",
+            include_str!("../shaders/dof_composite.wgsl"),
+            &[
+                texture_entry(0, false),
+                texture_entry(1, true),
+                sampler_entry(2),
+            ],
+            descriptor.output_format,
+        );
+
+        let downsample =
+            DownsamplePass::new(device, &DownsamplePassDescriptor { format: DOF_FORMAT });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("dof sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let create_params = |label, size| {
+            crate::resource_log::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                },
+            )
+        };
+
+        Self {
+            coc_pipeline,
+            coc_bind_group_layout,
+            gather_pipeline,
+            gather_bind_group_layout,
+            composite_pipeline,
+            composite_bind_group_layout,
+            downsample,
+            sampler,
+            coc_params: create_params("dof coc params", 32),
+            gather_params: create_params("dof gather params", 16),
+
+            near_plane: descriptor.near_plane,
+            far_plane: descriptor.far_plane,
+            focal_distance: descriptor.focal_distance,
+            aperture: descriptor.aperture,
+            max_coc_radius: descriptor.max_coc_radius,
+            sample_count: descriptor.sample_count,
+        }
+    }
+
+    /// Sets the distance in focus.
+    pub fn set_focal_distance(&mut self, focal_distance: f32) {
+        self.focal_distance = focal_distance;
+    }
+
+    /// Sets the blur radius in pixels of objects at infinity.
+    pub fn set_aperture(&mut self, aperture: f32) {
+        self.aperture = aperture;
+    }
+
+    /// Sets the near and far plane the depth was rendered with.
+    pub fn set_planes(&mut self, near_plane: f32, far_plane: f32) {
+        self.near_plane = near_plane;
+        self.far_plane = far_plane;
+    }
+
+    /// Applies depth of field to `color` of `size` using `depth` of the same size and writes the
+    /// result into `output`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut TexturePool,
+        color: &wgpu::TextureView,
+        depth: &wgpu::TextureView,
+        size: [u32; 2],
+        output: &wgpu::TextureView,
+    ) {
+        let mut coc_params = Vec::with_capacity(32);
+        for value in [
+            self.near_plane,
+            self.far_plane,
+            self.focal_distance,
+            self.aperture,
+            self.max_coc_radius,
+            0.0,
+            0.0,
+            0.0,
+        ] {
+            coc_params.extend_from_slice(&value.to_le_bytes());
+        }
+        queue.write_buffer(&self.coc_params, 0, &coc_params);
+
+        let mut gather_params = [0; 16];
+        gather_params[0..4].copy_from_slice(&self.max_coc_radius.to_le_bytes());
+        gather_params[4..8].copy_from_slice(&self.sample_count.to_le_bytes());
+        queue.write_buffer(&self.gather_params, 0, &gather_params);
+
+        let full = pool.acquire(device, &self.downsample.target_descriptor(size));
+        let half = pool.acquire(device, &self.downsample.target_descriptor(half_size(size)));
+        let blurred = pool.acquire(device, &self.downsample.target_descriptor(half_size(size)));
+        let view = |i| pool.view(i).expect("texture was just acquired");
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("dof coc bind group"),
+            layout: &self.coc_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.coc_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(color),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+            ],
+        });
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("dof coc pass")
+                .read("color", color)
+                .read("depth", depth)
+                .write("coc", view(full))
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("dof coc pass"),
+            &self.coc_pipeline,
+            &bind_group,
+            view(full),
+        );
+
+        self.downsample
+            .render(device, encoder, view(full), view(half));
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("dof gather bind group"),
+            layout: &self.gather_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.gather_params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(view(half)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("dof gather pass")
+                .read("coc half", view(half))
+                .write("blurred", view(blurred))
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("dof gather pass"),
+            &self.gather_pipeline,
+            &bind_group,
+            view(blurred),
+        );
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("dof composite bind group"),
+            layout: &self.composite_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(view(full)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(view(blurred)),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("dof composite pass")
+                .read("coc", view(full))
+                .read("blurred", view(blurred))
+                .write("output", output)
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("dof composite pass"),
+            &self.composite_pipeline,
+            &bind_group,
+            output,
+        );
+
+        for i in [full, half, blurred] {
+            pool.release(i);
+        }
+    }
+}
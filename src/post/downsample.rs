@@ -0,0 +1,167 @@
+use crate::texture::TexturePool;
+
+/// Size of the next smaller level of a downsample chain, at least 1x1.
+pub fn half_size(size: [u32; 2]) -> [u32; 2] {
+    size.map(|s| (s / 2).max(1))
+}
+
+/// Descriptor for [`DownsamplePass`].
+#[derive(Clone, Debug)]
+pub struct DownsamplePassDescriptor {
+    /// Format of the output textures.
+    pub format: wgpu::TextureFormat,
+}
+
+/// Halves the size of a texture with a filter that avoids aliasing, as a building block for
+/// blurs, bloom and depth of field.
+#[derive(Debug)]
+pub struct DownsamplePass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    format: wgpu::TextureFormat,
+}
+
+impl DownsamplePass {
+    pub fn new(device: &wgpu::Device, descriptor: &DownsamplePassDescriptor) -> Self {
+        let shader = super::fullscreen_shader_module(
+            device,
+            Some("downsample shader"),
+            include_str!("../shaders/downsample.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("downsample bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("downsample pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::fullscreen_pipeline(
+            device,
+            Some("downsample pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(descriptor.format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("downsample sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            format: descriptor.format,
+        }
+    }
+
+    /// Downsamples `input` into `output`, which should be half its size.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("downsample bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("downsample pass")
+                .read("input", input)
+                .write("output", output)
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("downsample pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+
+    /// Downsamples `input` of `size` `levels` times into textures acquired from `pool`.
+    ///
+    /// Returns the pool indices of the levels, largest first. Release them when done.
+    pub fn render_chain(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        pool: &mut TexturePool,
+        input: &wgpu::TextureView,
+        size: [u32; 2],
+        levels: u32,
+    ) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(levels as usize);
+        let mut size = size;
+        for _ in 0..levels {
+            size = half_size(size);
+            let index = pool.acquire(device, &self.target_descriptor(size));
+            let output = pool.view(index).expect("texture was just acquired");
+            let input = match indices.last() {
+                Some(&previous) => pool.view(previous).expect("texture is occupied"),
+                None => input,
+            };
+            self.render(device, encoder, input, output);
+            indices.push(index);
+        }
+        indices
+    }
+
+    /// Descriptor of a downsampled render target of `size`, which can be sampled by the next
+    /// level.
+    pub fn target_descriptor(&self, size: [u32; 2]) -> wgpu::TextureDescriptor<'static> {
+        wgpu::TextureDescriptor {
+            label: Some("downsample target"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Post-processing passes operating on whole textures.
+//!
+//! Passes draw a single fullscreen triangle and create their bind groups per invocation, so
+//! inputs and outputs can change every frame.
+
+mod color_grading;
+mod dof;
+mod downsample;
+mod motion;
+mod motion_blur;
+mod taa;
+mod temporal;
+mod tonemap;
+
+pub use color_grading::*;
+pub use dof::*;
+pub use downsample::*;
+pub use motion::*;
+pub use motion_blur::*;
+pub use taa::*;
+pub use temporal::*;
+pub use tonemap::*;
+
+/// Creates a shader module with the fullscreen vertex shader `vs_fullscreen` prepended to
+/// `source`. Includes of builtin snippets in `source` are resolved.
+pub(crate) fn fullscreen_shader_module(
+    device: &wgpu::Device,
+    label: wgpu::Label,
+    source: &str,
+) -> wgpu::ShaderModule {
+    let source = format!("#include \"wgpu_util::fullscreen\"\n{}", source);
+    crate::shader::ShaderComposer::new()
+        .create_shader_module(device, label, &source)
+        .expect("builtin snippets must compose")
+}
+
+/// Creates a pipeline drawing a fullscreen triangle with fragment entry point `fs_main`.
+pub(crate) fn fullscreen_pipeline(
+    device: &wgpu::Device,
+    label: wgpu::Label,
+    layout: &wgpu::PipelineLayout,
+    shader: &wgpu::ShaderModule,
+    targets: &[Option<wgpu::ColorTargetState>],
+) -> wgpu::RenderPipeline {
+    crate::resource_log::create_render_pipeline(
+        device,
+        &wgpu::RenderPipelineDescriptor {
+            label,
+            layout: Some(layout),
+            vertex: wgpu::VertexState {
+                module: shader,
+                entry_point: "vs_fullscreen",
+                buffers: &[],
+            },
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            fragment: Some(wgpu::FragmentState {
+                module: shader,
+                entry_point: "fs_main",
+                targets,
+            }),
+            multiview: None,
+        },
+    )
+}
+
+/// Records a render pass drawing the fullscreen triangle of `pipeline` into `output`.
+pub(crate) fn draw_fullscreen(
+    encoder: &mut wgpu::CommandEncoder,
+    label: wgpu::Label,
+    pipeline: &wgpu::RenderPipeline,
+    bind_group: &wgpu::BindGroup,
+    output: &wgpu::TextureView,
+) {
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label,
+        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+            view: output,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                store: true,
+            },
+        })],
+        depth_stencil_attachment: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.draw(0..3, 0..1);
+}
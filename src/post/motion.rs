@@ -0,0 +1,112 @@
+use std::num::NonZeroU64;
+
+use crate::camera::{CameraBuffer, CAMERA_SIZE};
+
+/// Format of the motion vectors written by [`MotionVectorPass`].
+pub const MOTION_VECTOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Float;
+
+/// Writes motion vectors caused by camera movement, reconstructed from depth.
+///
+/// Covers static geometry. Moving or skinned meshes write their own motion vectors with the
+/// `wgpu_util::motion` snippet on top, e.g. by rendering them into the same target afterwards.
+#[derive(Debug)]
+pub struct MotionVectorPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl MotionVectorPass {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let shader = super::fullscreen_shader_module(
+            device,
+            Some("motion vector shader"),
+            include_str!("../shaders/motion_vectors.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("motion vector bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(CAMERA_SIZE),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("motion vector pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::fullscreen_pipeline(
+            device,
+            Some("motion vector pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(MOTION_VECTOR_FORMAT.into())],
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+
+    /// Writes motion vectors for `depth` seen by `camera` into `output` of
+    /// [`MOTION_VECTOR_FORMAT`].
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        depth: &wgpu::TextureView,
+        camera: &CameraBuffer,
+        output: &wgpu::TextureView,
+    ) {
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("motion vector bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: camera.buffer().as_entire_binding(),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("motion vector pass")
+                .read("depth", depth)
+                .read("camera", camera.buffer())
+                .write("output", output)
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("motion vector pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+}
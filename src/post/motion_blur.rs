@@ -0,0 +1,191 @@
+use std::num::NonZeroU64;
+
+/// Descriptor for [`MotionBlurPass`].
+#[derive(Clone, Debug)]
+pub struct MotionBlurPassDescriptor {
+    /// Format of the output texture.
+    pub output_format: wgpu::TextureFormat,
+    /// Number of samples along the motion vector. Values below 2 disable the blur.
+    pub sample_count: u32,
+    /// Fraction of the frame's motion the shutter is open for, e.g. `0.5` for a 180° shutter.
+    pub shutter_scale: f32,
+}
+
+impl Default for MotionBlurPassDescriptor {
+    fn default() -> Self {
+        Self {
+            output_format: wgpu::TextureFormat::Rgba16Float,
+            sample_count: 8,
+            shutter_scale: 0.5,
+        }
+    }
+}
+
+/// Blurs a texture along motion vectors, e.g. written by [`super::MotionVectorPass`].
+#[derive(Debug)]
+pub struct MotionBlurPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+
+    sample_count: u32,
+    shutter_scale: f32,
+}
+
+impl MotionBlurPass {
+    pub fn new(device: &wgpu::Device, descriptor: &MotionBlurPassDescriptor) -> Self {
+        let shader = super::fullscreen_shader_module(
+            device,
+            Some("motion blur shader"),
+            include_str!("../shaders/motion_blur.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("motion blur bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("motion blur pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::fullscreen_pipeline(
+            device,
+            Some("motion blur pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(descriptor.output_format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("motion blur sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("motion blur params"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+
+            sample_count: descriptor.sample_count,
+            shutter_scale: descriptor.shutter_scale,
+        }
+    }
+
+    /// Sets the number of samples along the motion vector.
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
+    }
+
+    /// Sets the fraction of the frame's motion the shutter is open for.
+    pub fn set_shutter_scale(&mut self, shutter_scale: f32) {
+        self.shutter_scale = shutter_scale;
+    }
+
+    /// Blurs `input` along `motion_vectors` of the same size into `output`.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        motion_vectors: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let mut params = [0; 16];
+        params[0..4].copy_from_slice(&self.sample_count.to_le_bytes());
+        params[4..8].copy_from_slice(&self.shutter_scale.to_le_bytes());
+        queue.write_buffer(&self.params, 0, &params);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("motion blur bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(motion_vectors),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("motion blur pass")
+                .read("input", input)
+                .read("motion vectors", motion_vectors)
+                .write("output", output)
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("motion blur pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use std::num::NonZeroU64;
+
+use super::TemporalHistory;
+
+/// Sub-pixel offsets for jittering the projection every frame, from the Halton (2, 3) sequence.
+#[derive(Clone, Debug)]
+pub struct JitterSequence {
+    length: u32,
+    index: u32,
+}
+
+impl JitterSequence {
+    /// Creates a sequence repeating after `length` frames. 8 or 16 are common choices.
+    pub fn new(length: u32) -> Self {
+        assert!(length > 0, "length must be greater than 0");
+        Self { length, index: 0 }
+    }
+
+    /// Moves on to the next offset and returns it.
+    pub fn next_offset(&mut self) -> [f32; 2] {
+        self.index = (self.index + 1) % self.length;
+        self.offset()
+    }
+
+    /// Current offset in pixels, in `[-0.5, 0.5)`.
+    pub fn offset(&self) -> [f32; 2] {
+        // Halton indices start at 1, as 0 maps to the corner.
+        let i = self.index + 1;
+        [halton(i, 2) - 0.5, halton(i, 3) - 0.5]
+    }
+
+    /// Current offset in clip space for a target of `size` pixels.
+    pub fn clip_offset(&self, size: [u32; 2]) -> [f32; 2] {
+        let [x, y] = self.offset();
+        [2.0 * x / size[0] as f32, 2.0 * y / size[1] as f32]
+    }
+
+    /// Applies the current offset to a column-major projection matrix for a target of `size`
+    /// pixels. Works for perspective and orthographic projections.
+    pub fn jitter_projection(&self, projection: [[f32; 4]; 4], size: [u32; 2]) -> [[f32; 4]; 4] {
+        let [x, y] = self.clip_offset(size);
+        // Translate in clip space, scaled by w so the offset survives the perspective divide.
+        projection.map(|mut column| {
+            column[0] += x * column[3];
+            column[1] += y * column[3];
+            column
+        })
+    }
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// How history is constrained to the current frame's neighborhood to reject stale samples.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HistoryClamp {
+    /// Keep history as is. Ghosts on disocclusion.
+    None,
+    /// Clamp to the min and max of the 3x3 neighborhood.
+    MinMax,
+    /// Clamp to the mean ± `gamma` standard deviations of the 3x3 neighborhood. Tighter than
+    /// [`Self::MinMax`], around `1.0` is a good start.
+    Variance { gamma: f32 },
+}
+
+impl Default for HistoryClamp {
+    fn default() -> Self {
+        Self::Variance { gamma: 1.0 }
+    }
+}
+
+/// Descriptor for [`TaaPass`].
+#[derive(Clone, Debug)]
+pub struct TaaPassDescriptor {
+    /// Color format of the [`TemporalHistory`] the pass resolves into.
+    pub format: wgpu::TextureFormat,
+    /// Weight of the current frame, e.g. `0.1`. Lower values smooth more but ghost longer.
+    pub blend_factor: f32,
+    pub history_clamp: HistoryClamp,
+}
+
+/// Temporal anti-aliasing, accumulating jittered frames into a [`TemporalHistory`].
+///
+/// Each frame, render the scene with a projection jittered by [`JitterSequence`], call
+/// [`TemporalHistory::advance`] and then [`Self::render`]. The resolved frame ends up in
+/// [`TemporalHistory::current_color_view`] and serves as history for the next frame.
+#[derive(Debug)]
+pub struct TaaPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+    dummy_motion_vectors: wgpu::TextureView,
+
+    blend_factor: f32,
+    history_clamp: HistoryClamp,
+}
+
+impl TaaPass {
+    pub fn new(device: &wgpu::Device, descriptor: &TaaPassDescriptor) -> Self {
+        let shader = super::fullscreen_shader_module(
+            device,
+            Some("taa shader"),
+            include_str!("../shaders/taa.wgsl"),
+        );
+
+        let texture_entry = |binding, filterable| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("taa bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                texture_entry(1, false),
+                texture_entry(2, true),
+                texture_entry(3, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("taa pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::fullscreen_pipeline(
+            device,
+            Some("taa pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(descriptor.format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("taa sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("taa params"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        // Bound when no motion vectors are provided, never read.
+        let dummy_motion_vectors = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("taa dummy motion vectors"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rg16Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+            dummy_motion_vectors,
+
+            blend_factor: descriptor.blend_factor,
+            history_clamp: descriptor.history_clamp,
+        }
+    }
+
+    /// Sets the weight of the current frame.
+    pub fn set_blend_factor(&mut self, blend_factor: f32) {
+        self.blend_factor = blend_factor;
+    }
+
+    /// Sets how history is constrained to the current frame.
+    pub fn set_history_clamp(&mut self, history_clamp: HistoryClamp) {
+        self.history_clamp = history_clamp;
+    }
+
+    /// Resolves the jittered `current` frame with the previous frame of `history` into the
+    /// current frame of `history`.
+    ///
+    /// Without `motion_vectors`, the camera and scene are assumed to be static. Until `history`
+    /// holds a previous frame, `current` is passed through.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        current: &wgpu::TextureView,
+        motion_vectors: Option<&wgpu::TextureView>,
+        history: &TemporalHistory,
+    ) {
+        let (clamp_mode, variance_gamma) = match self.history_clamp {
+            HistoryClamp::None => (0u32, 0.0f32),
+            HistoryClamp::MinMax => (1, 0.0),
+            HistoryClamp::Variance { gamma } => (2, gamma),
+        };
+        let flags = history.has_history() as u32 | (motion_vectors.is_some() as u32) << 1;
+        let mut params = Vec::with_capacity(16);
+        params.extend_from_slice(&self.blend_factor.to_le_bytes());
+        params.extend_from_slice(&variance_gamma.to_le_bytes());
+        params.extend_from_slice(&clamp_mode.to_le_bytes());
+        params.extend_from_slice(&flags.to_le_bytes());
+        queue.write_buffer(&self.params, 0, &params);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("taa bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(current),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(history.previous_color_view()),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(
+                        motion_vectors.unwrap_or(&self.dummy_motion_vectors),
+                    ),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("taa pass")
+                .read("current", current)
+                .read("history", history.previous_color_view())
+                .read(
+                    "motion vectors",
+                    motion_vectors.unwrap_or(&self.dummy_motion_vectors),
+                )
+                .write("history", history.current_color_view())
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("taa pass"),
+            &self.pipeline,
+            &bind_group,
+            history.current_color_view(),
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use std::num::NonZeroU64;
+
+use crate::frame::PerFrame;
+
+/// Descriptor for [`TemporalHistory`].
+#[derive(Clone, Debug)]
+pub struct TemporalHistoryDescriptor<'a> {
+    pub label: wgpu::Label<'a>,
+    pub width: u32,
+    pub height: u32,
+    /// Format of the color history.
+    pub color_format: wgpu::TextureFormat,
+    /// Format of the depth history, if depth is kept. Must be copyable, e.g.
+    /// [`wgpu::TextureFormat::Depth32Float`].
+    pub depth_format: Option<wgpu::TextureFormat>,
+}
+
+#[derive(Debug)]
+struct HistoryFrame {
+    color: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    depth: Option<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+/// Color and depth of the current and previous frame, for temporal techniques like TAA and
+/// temporal upscaling.
+///
+/// Call [`Self::advance`] at the start of every frame, then write the current frame's targets,
+/// e.g. with [`Self::store_color`], while reading the previous ones.
+#[derive(Debug)]
+pub struct TemporalHistory {
+    frames: PerFrame<HistoryFrame>,
+    label: crate::OwnedLabel,
+    width: u32,
+    height: u32,
+    color_format: wgpu::TextureFormat,
+    depth_format: Option<wgpu::TextureFormat>,
+
+    /// Number of frames written since creation or the last resize, saturating at 2.
+    written_frames: u32,
+}
+
+impl TemporalHistory {
+    pub fn new(device: &wgpu::Device, descriptor: &TemporalHistoryDescriptor<'_>) -> Self {
+        Self {
+            frames: PerFrame::new(2, |_| create_history_frame(device, descriptor)),
+            label: descriptor.label.map(|l| l.to_owned()),
+            width: descriptor.width,
+            height: descriptor.height,
+            color_format: descriptor.color_format,
+            depth_format: descriptor.depth_format,
+
+            written_frames: 0,
+        }
+    }
+
+    /// Recreates the history with a new size. The history is invalid until two frames were
+    /// written again.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if (width, height) == (self.width, self.height) {
+            return;
+        }
+        self.width = width;
+        self.height = height;
+        let descriptor = TemporalHistoryDescriptor {
+            label: self.label.as_deref(),
+            width,
+            height,
+            color_format: self.color_format,
+            depth_format: self.depth_format,
+        };
+        for frame in self.frames.iter_mut() {
+            *frame = create_history_frame(device, &descriptor);
+        }
+        self.written_frames = 0;
+    }
+
+    /// Starts a new frame. The previous current frame becomes the history.
+    pub fn advance(&mut self) {
+        self.frames.advance();
+        self.written_frames = (self.written_frames + 1).min(2);
+    }
+
+    /// Whether the previous frame holds valid data, i.e. [`Self::advance`] was called at least
+    /// twice since creation or the last resize.
+    pub fn has_history(&self) -> bool {
+        self.written_frames >= 2
+    }
+
+    /// Records a copy of `color` into the current color history. `color` must have the same
+    /// size and format and [`wgpu::TextureUsages::COPY_SRC`].
+    pub fn store_color(&self, encoder: &mut wgpu::CommandEncoder, color: &wgpu::Texture) {
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::copy("store history color")
+                .read("color", color)
+                .write("history", &self.frames.current().color_view)
+        });
+        encoder.copy_texture_to_texture(
+            color.as_image_copy(),
+            self.frames.current().color.as_image_copy(),
+            self.extent(),
+        );
+    }
+
+    /// Records a copy of `depth` into the current depth history. `depth` must have the same
+    /// size and format and [`wgpu::TextureUsages::COPY_SRC`].
+    pub fn store_depth(&self, encoder: &mut wgpu::CommandEncoder, depth: &wgpu::Texture) {
+        let (history, _) = self
+            .frames
+            .current()
+            .depth
+            .as_ref()
+            .expect("history has no depth");
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::copy("store history depth")
+                .read("depth", depth)
+                .write(
+                    "history depth",
+                    &self.frames.current().depth.as_ref().unwrap().1,
+                )
+        });
+        encoder.copy_texture_to_texture(
+            depth.as_image_copy(),
+            history.as_image_copy(),
+            self.extent(),
+        );
+    }
+
+    /// Color texture of the current frame. Usable as render attachment, binding and copy
+    /// destination.
+    pub fn current_color(&self) -> &wgpu::Texture {
+        &self.frames.current().color
+    }
+
+    /// View of [`Self::current_color`].
+    pub fn current_color_view(&self) -> &wgpu::TextureView {
+        &self.frames.current().color_view
+    }
+
+    /// View of the color of the previous frame.
+    pub fn previous_color_view(&self) -> &wgpu::TextureView {
+        &self.frames.previous().color_view
+    }
+
+    /// View of the depth of the previous frame, if depth is kept.
+    pub fn previous_depth_view(&self) -> Option<&wgpu::TextureView> {
+        self.frames.previous().depth.as_ref().map(|(_, view)| view)
+    }
+
+    /// Size of the history textures.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn extent(&self) -> wgpu::Extent3d {
+        wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        }
+    }
+}
+
+fn create_history_frame(
+    device: &wgpu::Device,
+    descriptor: &TemporalHistoryDescriptor<'_>,
+) -> HistoryFrame {
+    let size = wgpu::Extent3d {
+        width: descriptor.width,
+        height: descriptor.height,
+        depth_or_array_layers: 1,
+    };
+    let create_texture = |format| {
+        crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: descriptor.label,
+                size,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::COPY_DST,
+            },
+        )
+    };
+
+    let color = create_texture(descriptor.color_format);
+    let color_view = color.create_view(&wgpu::TextureViewDescriptor::default());
+    let depth = descriptor.depth_format.map(|format| {
+        let texture = create_texture(format);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        (texture, view)
+    });
+
+    HistoryFrame {
+        color,
+        color_view,
+        depth,
+    }
+}
+
+/// Descriptor for [`ReprojectPass`].
+#[derive(Clone, Debug)]
+pub struct ReprojectPassDescriptor {
+    /// Format of the output texture.
+    pub output_format: wgpu::TextureFormat,
+    /// Relative depth difference above which history is rejected, if depth is provided.
+    pub depth_threshold: f32,
+}
+
+/// Depth of the current and previous frame for rejecting disoccluded history in
+/// [`ReprojectPass::render`].
+#[derive(Clone, Copy, Debug)]
+pub struct ReprojectDepth<'a> {
+    pub current: &'a wgpu::TextureView,
+    pub previous: &'a wgpu::TextureView,
+}
+
+/// Reprojects the previous frame's color into the current frame using motion vectors.
+///
+/// Motion vectors are read from an `Rg*Float` texture of the output size and point from the
+/// previous to the current position in UV space. The output alpha is `1.0` where history is
+/// valid and `0.0` where it left the screen or, if depth is provided, got disoccluded.
+#[derive(Debug)]
+pub struct ReprojectPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+    dummy_depth: wgpu::TextureView,
+
+    depth_threshold: f32,
+}
+
+impl ReprojectPass {
+    pub fn new(device: &wgpu::Device, descriptor: &ReprojectPassDescriptor) -> Self {
+        let shader = super::fullscreen_shader_module(
+            device,
+            Some("reproject shader"),
+            include_str!("../shaders/reproject.wgsl"),
+        );
+
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("reproject bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                texture_entry(1, wgpu::TextureSampleType::Float { filterable: true }),
+                texture_entry(2, wgpu::TextureSampleType::Float { filterable: false }),
+                // Depth is bound as float, see the shader.
+                texture_entry(3, wgpu::TextureSampleType::Float { filterable: false }),
+                texture_entry(4, wgpu::TextureSampleType::Float { filterable: false }),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("reproject pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::fullscreen_pipeline(
+            device,
+            Some("reproject pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(descriptor.output_format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("reproject sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("reproject params"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        // Bound when no depth is provided, never read.
+        let dummy_depth = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some("reproject dummy depth"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Depth32Float,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        )
+        .create_view(&wgpu::TextureViewDescriptor::default());
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+            dummy_depth,
+
+            depth_threshold: descriptor.depth_threshold,
+        }
+    }
+
+    /// Sets the relative depth difference above which history is rejected.
+    pub fn set_depth_threshold(&mut self, depth_threshold: f32) {
+        self.depth_threshold = depth_threshold;
+    }
+
+    /// Reprojects `previous_color` into `output`, e.g. a texture acquired from a
+    /// [`crate::texture::TexturePool`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        previous_color: &wgpu::TextureView,
+        motion_vectors: &wgpu::TextureView,
+        depth: Option<ReprojectDepth<'_>>,
+        output: &wgpu::TextureView,
+    ) {
+        let mut params = Vec::with_capacity(16);
+        params.extend_from_slice(&self.depth_threshold.to_le_bytes());
+        params.extend_from_slice(&(depth.is_some() as u32).to_le_bytes());
+        params.extend_from_slice(&[0; 8]);
+        queue.write_buffer(&self.params, 0, &params);
+
+        let (current_depth, previous_depth) = match depth {
+            Some(depth) => (depth.current, depth.previous),
+            None => (&self.dummy_depth, &self.dummy_depth),
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("reproject bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(previous_color),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(motion_vectors),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(current_depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(previous_depth),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("reproject pass")
+                .read("previous color", previous_color)
+                .read("motion vectors", motion_vectors)
+                .read("depth", current_depth)
+                .read("previous depth", previous_depth)
+                .write("output", output)
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("reproject pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+}
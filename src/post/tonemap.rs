@@ -0,0 +1,196 @@
+use std::num::NonZeroU64;
+
+/// Curve mapping HDR colors to the displayable range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TonemapOperator {
+    /// Pass colors through unchanged. Useful for HDR outputs.
+    None,
+    /// `x / (x + 1)`.
+    Reinhard,
+    /// Filmic ACES approximation.
+    #[default]
+    Aces,
+    /// Clamp to `[0, 1]`.
+    Clamp,
+}
+
+impl TonemapOperator {
+    fn index(self) -> u32 {
+        match self {
+            Self::None => 0,
+            Self::Reinhard => 1,
+            Self::Aces => 2,
+            Self::Clamp => 3,
+        }
+    }
+}
+
+/// Descriptor for [`TonemapPass`].
+#[derive(Clone, Debug)]
+pub struct TonemapPassDescriptor {
+    /// Format of the output texture. Non-sRGB formats get encoded in the shader.
+    pub output_format: wgpu::TextureFormat,
+    /// Tonemapping curve.
+    pub operator: TonemapOperator,
+    /// Linear scale applied before the curve.
+    pub exposure: f32,
+}
+
+/// Maps a linear HDR texture to the output format.
+#[derive(Debug)]
+pub struct TonemapPass {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    params: wgpu::Buffer,
+
+    operator: TonemapOperator,
+    exposure: f32,
+    encode_srgb: bool,
+}
+
+impl TonemapPass {
+    pub fn new(device: &wgpu::Device, descriptor: &TonemapPassDescriptor) -> Self {
+        let shader = super::fullscreen_shader_module(
+            device,
+            Some("tonemap shader"),
+            include_str!("../shaders/tonemap.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tonemap bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("tonemap pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = super::fullscreen_pipeline(
+            device,
+            Some("tonemap pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(descriptor.output_format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("tonemap sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let params = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("tonemap params"),
+                size: 16,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+            params,
+
+            operator: descriptor.operator,
+            exposure: descriptor.exposure,
+            encode_srgb: !descriptor.output_format.describe().srgb,
+        }
+    }
+
+    /// Sets the tonemapping curve.
+    pub fn set_operator(&mut self, operator: TonemapOperator) {
+        self.operator = operator;
+    }
+
+    /// Sets the linear scale applied before the curve.
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure;
+    }
+
+    /// Tonemaps `input` into `output`.
+    ///
+    /// `input` must be a filterable float texture, `output` must have the output format.
+    pub fn render(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::TextureView,
+        output: &wgpu::TextureView,
+    ) {
+        let mut params = Vec::with_capacity(16);
+        params.extend_from_slice(&self.exposure.to_le_bytes());
+        params.extend_from_slice(&self.operator.index().to_le_bytes());
+        params.extend_from_slice(&(self.encode_srgb as u32).to_le_bytes());
+        params.extend_from_slice(&0u32.to_le_bytes());
+        queue.write_buffer(&self.params, 0, &params);
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tonemap bind group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(input),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+
+        #[cfg(feature = "debug")]
+        crate::timeline::record(|| {
+            crate::timeline::Operation::render_pass("tonemap pass")
+                .read("input", input)
+                .write("output", output)
+        });
+        super::draw_fullscreen(
+            encoder,
+            Some("tonemap pass"),
+            &self.pipeline,
+            &bind_group,
+            output,
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! [`wgpu::PrimitiveState`] presets named by intent. Strip index formats are unset, so strips
+//! can't be restarted.
+
+fn triangles(front_face: wgpu::FrontFace, cull_mode: Option<wgpu::Face>) -> wgpu::PrimitiveState {
+    wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        front_face,
+        cull_mode,
+        ..Default::default()
+    }
+}
+
+/// Triangle list with counter-clockwise front faces, culling back faces.
+pub fn triangles_ccw_cull_back() -> wgpu::PrimitiveState {
+    triangles(wgpu::FrontFace::Ccw, Some(wgpu::Face::Back))
+}
+
+/// Triangle list with clockwise front faces, culling back faces.
+pub fn triangles_cw_cull_back() -> wgpu::PrimitiveState {
+    triangles(wgpu::FrontFace::Cw, Some(wgpu::Face::Back))
+}
+
+/// Triangle list drawing both faces, e.g. for foliage or geometry without consistent winding.
+pub fn triangles_no_cull() -> wgpu::PrimitiveState {
+    triangles(wgpu::FrontFace::Ccw, None)
+}
+
+/// Line list.
+pub fn lines() -> wgpu::PrimitiveState {
+    wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::LineList,
+        ..Default::default()
+    }
+}
+
+/// Point list.
+pub fn points() -> wgpu::PrimitiveState {
+    wgpu::PrimitiveState {
+        topology: wgpu::PrimitiveTopology::PointList,
+        ..Default::default()
+    }
+}
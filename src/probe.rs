@@ -0,0 +1,464 @@
+//! Capturing environment probes into cube maps.
+
+use crate::{
+    camera::{Mat4Uniform, Matrix4},
+    BufferInitDescriptor, DeviceExt,
+};
+
+/// Descriptor for [`Probe`].
+#[derive(Clone, Debug)]
+pub struct ProbeDescriptor<'a> {
+    pub label: &'a str,
+    /// Width and height of the faces.
+    pub size: u32,
+    pub format: wgpu::TextureFormat,
+    /// Format of the depth attachment shared by the faces, if any.
+    pub depth_format: Option<wgpu::TextureFormat>,
+    pub mip_level_count: u32,
+    /// Whether [`Probe::capture`] prefilters the mip levels for increasing roughness.
+    pub prefilter: bool,
+    /// Near and far plane of the face cameras.
+    pub near: f32,
+    pub far: f32,
+    /// Color the faces are cleared to before rendering the scene.
+    pub clear_color: wgpu::Color,
+    /// Additional usages of the cube map.
+    pub usage: wgpu::TextureUsages,
+}
+
+impl Default for ProbeDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: "probe",
+            size: 128,
+            format: wgpu::TextureFormat::Rgba16Float,
+            depth_format: Some(wgpu::TextureFormat::Depth32Float),
+            mip_level_count: 1,
+            prefilter: false,
+            near: 0.1,
+            far: 1000.0,
+            clear_color: wgpu::Color::BLACK,
+            usage: wgpu::TextureUsages::empty(),
+        }
+    }
+}
+
+/// A face of a [`Probe`] being captured, handed to the scene callback of [`Probe::capture`].
+#[derive(Debug)]
+pub struct ProbeFace<'a> {
+    /// Layer of the face in the cube map: +X, -X, +Y, -Y, +Z, -Z.
+    pub index: u32,
+    /// World position of the probe.
+    pub position: [f32; 3],
+    pub view: Mat4Uniform,
+    pub projection: Mat4Uniform,
+    pub view_projection: Mat4Uniform,
+    color: &'a wgpu::TextureView,
+    depth: Option<&'a wgpu::TextureView>,
+    clear_color: wgpu::Color,
+}
+
+impl ProbeFace<'_> {
+    /// Begins a pass rendering into the face, clearing color to the clear color of the
+    /// descriptor and depth to 1.
+    ///
+    /// The projection mirrors the image horizontally to match the cube map layout, so the
+    /// winding order of triangles is flipped: pipelines culling back faces need
+    /// [`wgpu::FrontFace::Cw`] for counter-clockwise meshes.
+    pub fn begin_render_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'a> {
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("probe face pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.color,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(self.clear_color),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: self.depth.map(|view| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+        })
+    }
+
+    /// View of the face at the top mip level, for passes not using [`Self::begin_render_pass`].
+    pub fn color_view(&self) -> &wgpu::TextureView {
+        self.color
+    }
+
+    pub fn depth_view(&self) -> Option<&wgpu::TextureView> {
+        self.depth
+    }
+}
+
+/// Forward and up directions of the faces, following the cube map conventions of WebGPU.
+const FACES: [([f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, -1.0]),
+    ([0.0, -1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+];
+
+/// An environment probe: a cube map of the scene as seen from a position, e.g. for reflections
+/// and image based lighting.
+///
+/// With prefiltering, mip level `i` of `n` is convolved with a GGX lobe of roughness
+/// `i / (n - 1)` for specular image based lighting, sampled with `textureSampleLevel` at
+/// `roughness * (n - 1)`. Each level filters the previous one with the lobe widening it to its
+/// roughness, which approximates filtering the top level at a fraction of the cost.
+#[derive(Debug)]
+pub struct Probe {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    face_views: Vec<wgpu::TextureView>,
+    depth: Option<wgpu::TextureView>,
+    prefilter: Option<Prefilter>,
+    size: u32,
+    mip_level_count: u32,
+    format: wgpu::TextureFormat,
+    near: f32,
+    far: f32,
+    clear_color: wgpu::Color,
+}
+
+#[derive(Debug)]
+struct Prefilter {
+    pipeline: wgpu::RenderPipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+}
+
+impl Probe {
+    pub fn new(device: &wgpu::Device, descriptor: &ProbeDescriptor<'_>) -> Self {
+        let size = descriptor.size.max(1);
+        let mip_level_count = descriptor
+            .mip_level_count
+            .clamp(1, u32::BITS - size.leading_zeros());
+        let texture = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(descriptor.label),
+                size: wgpu::Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 6,
+                },
+                mip_level_count,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: descriptor.format,
+                usage: descriptor.usage
+                    | wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let face_views = (0..mip_level_count)
+            .flat_map(|mip| (0..6).map(move |face| (mip, face)))
+            .map(|(mip, face)| face_view(&texture, mip, face))
+            .collect();
+
+        let depth = descriptor.depth_format.map(|format| {
+            crate::resource_log::create_texture(
+                device,
+                &wgpu::TextureDescriptor {
+                    label: Some(&format!("{} depth", descriptor.label)),
+                    size: wgpu::Extent3d {
+                        width: size,
+                        height: size,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                },
+            )
+            .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+
+        let prefilter = match descriptor.prefilter && mip_level_count > 1 {
+            true => Some(Prefilter::new(device, descriptor.format)),
+            false => None,
+        };
+
+        Self {
+            texture,
+            view,
+            face_views,
+            depth,
+            prefilter,
+            size,
+            mip_level_count,
+            format: descriptor.format,
+            near: descriptor.near,
+            far: descriptor.far,
+            clear_color: descriptor.clear_color,
+        }
+    }
+
+    /// Records the scene into the six faces as seen from `position`, then prefilters the mip
+    /// levels if enabled.
+    ///
+    /// `render_scene` is called once per face with `encoder` and the [`ProbeFace`] holding its
+    /// camera, and records the scene into it, e.g. with [`ProbeFace::begin_render_pass`]. Face
+    /// cameras have a right-handed view space and a depth range of 0 to 1.
+    pub fn capture(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        mut render_scene: impl FnMut(&mut wgpu::CommandEncoder, &ProbeFace<'_>),
+        position: [f32; 3],
+    ) {
+        let projection = self.projection();
+        for (index, (forward, up)) in FACES.into_iter().enumerate() {
+            let view = look_to(position, forward, up);
+            let face = ProbeFace {
+                index: index as u32,
+                position,
+                view: Mat4Uniform::from_column_major(view),
+                projection: Mat4Uniform::from_column_major(projection),
+                view_projection: Mat4Uniform::from_column_major(multiply(&projection, &view)),
+                color: &self.face_views[index],
+                depth: self.depth.as_ref(),
+                clear_color: self.clear_color,
+            };
+            render_scene(encoder, &face);
+        }
+
+        if let Some(prefilter) = &self.prefilter {
+            prefilter.render(device, encoder, self);
+        }
+    }
+
+    /// Right-handed perspective projection with a 90 degree field of view, mirrored along x.
+    fn projection(&self) -> Matrix4 {
+        let (near, far) = (self.near, self.far);
+        let range = far / (near - far);
+        [
+            [-1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, range, -1.0],
+            [0.0, 0.0, range * near, 0.0],
+        ]
+    }
+
+    /// Cube view of all mip levels.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn mip_level_count(&self) -> u32 {
+        self.mip_level_count
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.format
+    }
+
+    /// View of one face at one mip level.
+    pub fn face_view(&self, mip_level: u32, face: u32) -> &wgpu::TextureView {
+        &self.face_views[(mip_level * 6 + face) as usize]
+    }
+}
+
+impl Prefilter {
+    fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = crate::post::fullscreen_shader_module(
+            device,
+            Some("probe prefilter shader"),
+            include_str!("shaders/probe_prefilter.wgsl"),
+        );
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("probe prefilter bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(8),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("probe prefilter pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = crate::post::fullscreen_pipeline(
+            device,
+            Some("probe prefilter pipeline"),
+            &pipeline_layout,
+            &shader,
+            &[Some(format.into())],
+        );
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("probe prefilter sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            sampler,
+        }
+    }
+
+    /// Filters every mip level of `probe` below the top one from the level above it.
+    fn render(&self, device: &wgpu::Device, encoder: &mut wgpu::CommandEncoder, probe: &Probe) {
+        let levels = probe.mip_level_count();
+        let alpha = |mip: u32| (mip as f32 / (levels - 1) as f32).powi(2);
+        for mip in 1..levels {
+            let source = probe.texture.create_view(&wgpu::TextureViewDescriptor {
+                dimension: Some(wgpu::TextureViewDimension::Cube),
+                base_mip_level: mip - 1,
+                mip_level_count: std::num::NonZeroU32::new(1),
+                ..Default::default()
+            });
+            // GGX lobes roughly add up in the square of alpha.
+            let alpha = (alpha(mip).powi(2) - alpha(mip - 1).powi(2)).sqrt();
+            for face in 0..6u32 {
+                let mut params = Vec::with_capacity(8);
+                params.extend_from_slice(&face.to_le_bytes());
+                params.extend_from_slice(&alpha.to_le_bytes());
+                let params = device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("probe prefilter params buffer"),
+                    contents: &params,
+                    size: None,
+                    usage: wgpu::BufferUsages::UNIFORM,
+                });
+                let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: Some("probe prefilter bind group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        wgpu::BindGroupEntry {
+                            binding: 0,
+                            resource: wgpu::BindingResource::TextureView(&source),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 1,
+                            resource: wgpu::BindingResource::Sampler(&self.sampler),
+                        },
+                        wgpu::BindGroupEntry {
+                            binding: 2,
+                            resource: params.as_entire_binding(),
+                        },
+                    ],
+                });
+
+                let output = probe.face_view(mip, face);
+                #[cfg(feature = "debug")]
+                crate::timeline::record(|| {
+                    crate::timeline::Operation::render_pass("probe prefilter pass")
+                        .read("source", &source)
+                        .write("output", output)
+                });
+                crate::post::draw_fullscreen(
+                    encoder,
+                    Some("probe prefilter pass"),
+                    &self.pipeline,
+                    &bind_group,
+                    output,
+                );
+            }
+        }
+    }
+}
+
+fn face_view(texture: &wgpu::Texture, mip_level: u32, face: u32) -> wgpu::TextureView {
+    texture.create_view(&wgpu::TextureViewDescriptor {
+        dimension: Some(wgpu::TextureViewDimension::D2),
+        base_mip_level: mip_level,
+        mip_level_count: std::num::NonZeroU32::new(1),
+        base_array_layer: face,
+        array_layer_count: std::num::NonZeroU32::new(1),
+        ..Default::default()
+    })
+}
+
+/// Right-handed view matrix of a camera at `position` looking along `forward`.
+fn look_to(position: [f32; 3], forward: [f32; 3], up: [f32; 3]) -> Matrix4 {
+    let right = cross(forward, up);
+    let dot = |a: [f32; 3], b: [f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+    let back = forward.map(|f| -f);
+    [
+        [right[0], up[0], back[0], 0.0],
+        [right[1], up[1], back[1], 0.0],
+        [right[2], up[2], back[2], 0.0],
+        [
+            -dot(right, position),
+            -dot(up, position),
+            -dot(back, position),
+            1.0,
+        ],
+    ]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let mut result = [[0.0; 4]; 4];
+    for (column, b_column) in result.iter_mut().zip(b) {
+        for (r, value) in column.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[k][r] * b_column[k]).sum();
+        }
+    }
+    result
+}
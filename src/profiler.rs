@@ -0,0 +1,517 @@
+//! Measuring GPU time with timestamp queries.
+
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::Path,
+    sync::mpsc::{self, Receiver, TryRecvError},
+    time::{Duration, Instant},
+};
+
+/// Number of frames which may be measured at the same time.
+const FRAMES_IN_FLIGHT: usize = 3;
+
+/// GPU duration of a named scope.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileScope {
+    pub label: String,
+    /// Nesting level, `0` for top-level scopes.
+    pub depth: u32,
+    /// Time from the first timestamp of the frame to the start of the scope.
+    pub start: Duration,
+    pub duration: Duration,
+}
+
+/// Where [`GpuProfiler`] can write timestamps, depending on the enabled device features.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TimestampPrecision {
+    /// [`wgpu::Features::TIMESTAMP_QUERY`] is missing, so only CPU time is measured.
+    None,
+    /// Timestamps are written on command encoders, between passes. Scopes begun or ended inside
+    /// a pass aren't measured on the GPU.
+    Encoder,
+    /// [`wgpu::Features::WRITE_TIMESTAMP_INSIDE_PASSES`] is enabled, so scopes can also measure
+    /// parts of render and compute passes.
+    Passes,
+}
+
+impl TimestampPrecision {
+    /// Highest precision supported with `features`.
+    pub fn from_features(features: wgpu::Features) -> Self {
+        match (
+            features.contains(wgpu::Features::TIMESTAMP_QUERY),
+            features.contains(wgpu::Features::WRITE_TIMESTAMP_INSIDE_PASSES),
+        ) {
+            (false, _) => Self::None,
+            (true, false) => Self::Encoder,
+            (true, true) => Self::Passes,
+        }
+    }
+}
+
+/// Anything [`GpuProfiler`] scopes can be begun and ended on.
+pub trait TimestampWriter {
+    /// Precision required to write timestamps on `Self`.
+    const PRECISION: TimestampPrecision;
+
+    fn write_timestamp(&mut self, query_set: &wgpu::QuerySet, query_index: u32);
+}
+
+impl TimestampWriter for wgpu::CommandEncoder {
+    const PRECISION: TimestampPrecision = TimestampPrecision::Encoder;
+
+    fn write_timestamp(&mut self, query_set: &wgpu::QuerySet, query_index: u32) {
+        wgpu::CommandEncoder::write_timestamp(self, query_set, query_index);
+    }
+}
+
+impl TimestampWriter for wgpu::RenderPass<'_> {
+    const PRECISION: TimestampPrecision = TimestampPrecision::Passes;
+
+    fn write_timestamp(&mut self, query_set: &wgpu::QuerySet, query_index: u32) {
+        wgpu::RenderPass::write_timestamp(self, query_set, query_index);
+    }
+}
+
+impl TimestampWriter for wgpu::ComputePass<'_> {
+    const PRECISION: TimestampPrecision = TimestampPrecision::Passes;
+
+    fn write_timestamp(&mut self, query_set: &wgpu::QuerySet, query_index: u32) {
+        wgpu::ComputePass::write_timestamp(self, query_set, query_index);
+    }
+}
+
+/// Results of a frame measured by [`GpuProfiler`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfilerFrame {
+    /// Index of the frame among all frames passed to [`GpuProfiler::begin_frame`].
+    pub index: u64,
+    /// Time between the first and the last timestamp of the frame.
+    pub gpu_time: Duration,
+    /// Scopes in the order they were started.
+    pub scopes: Vec<ProfileScope>,
+}
+
+#[derive(Debug)]
+enum SlotState {
+    Idle,
+    Recording,
+    /// Resolved into the staging buffer, waiting for submission.
+    Resolved,
+    Mapping(Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Track {
+    Cpu,
+    Gpu,
+}
+
+/// A captured span, relative to the start of the capture.
+#[derive(Clone, Debug)]
+struct Span {
+    label: String,
+    track: Track,
+    start: Duration,
+    duration: Duration,
+}
+
+/// Spans collected between [`GpuProfiler::start_capture`] and [`GpuProfiler::stop_capture`].
+#[derive(Debug)]
+struct Capture {
+    start: Instant,
+    spans: Vec<Span>,
+    active: bool,
+}
+
+struct Slot {
+    query_set: wgpu::QuerySet,
+    staging: wgpu::Buffer,
+    state: SlotState,
+
+    frame: u64,
+    /// CPU time at [`GpuProfiler::begin_frame`], which the GPU timestamps are aligned to.
+    cpu_start: Instant,
+    /// Label, depth, start query and end query per scope.
+    scopes: Vec<(String, u32, u32, Option<u32>)>,
+    queries: u32,
+}
+
+impl std::fmt::Debug for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Slot")
+            .field("staging", &self.staging)
+            .field("state", &self.state)
+            .field("frame", &self.frame)
+            .field("scopes", &self.scopes)
+            .field("queries", &self.queries)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Measures the GPU time of frames and scopes inside them using
+/// [`wgpu::Features::TIMESTAMP_QUERY`].
+///
+/// Scopes can be measured on command encoders and, with
+/// [`wgpu::Features::WRITE_TIMESTAMP_INSIDE_PASSES`], inside passes. Without the features the
+/// profiler degrades instead of failing, see [`Self::precision`].
+///
+/// Results arrive a few frames late without stalling. If all frames in flight are still being
+/// read back, the current frame isn't measured.
+///
+/// Frames and scopes measured during a capture, together with the CPU time spent recording
+/// them, can be written with [`Self::export_chrome_trace`].
+#[derive(Debug)]
+pub struct GpuProfiler {
+    slots: Vec<Slot>,
+    current: Option<usize>,
+    stack: Vec<usize>,
+    max_queries: u32,
+    period: f32,
+    precision: TimestampPrecision,
+
+    frame: u64,
+    last_frame: Option<ProfilerFrame>,
+
+    /// Label and start of the open frame and scopes on the CPU.
+    cpu_stack: Vec<(String, Instant)>,
+    capture: Option<Capture>,
+}
+
+impl GpuProfiler {
+    /// Creates a profiler measuring up to `max_scopes` scopes per frame.
+    ///
+    /// The precision is chosen from the features enabled on `device`. Without
+    /// [`wgpu::Features::TIMESTAMP_QUERY`] no GPU results arrive, but CPU scopes are still
+    /// captured.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, max_scopes: u32) -> Self {
+        let precision = TimestampPrecision::from_features(device.features());
+
+        // One pair per scope and one for the whole frame.
+        let max_queries = (max_scopes + 1) * 2;
+        let size = max_queries as wgpu::BufferAddress * 8;
+        let frames = match precision {
+            TimestampPrecision::None => 0,
+            _ => FRAMES_IN_FLIGHT,
+        };
+        let slots = (0..frames)
+            .map(|_| Slot {
+                query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                    label: Some("profiler query set"),
+                    ty: wgpu::QueryType::Timestamp,
+                    count: max_queries,
+                }),
+                staging: crate::resource_log::create_buffer(
+                    device,
+                    &wgpu::BufferDescriptor {
+                        label: Some("profiler staging buffer"),
+                        size,
+                        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                        mapped_at_creation: false,
+                    },
+                ),
+                state: SlotState::Idle,
+
+                frame: 0,
+                cpu_start: Instant::now(),
+                scopes: Vec::new(),
+                queries: 0,
+            })
+            .collect();
+
+        Self {
+            slots,
+            current: None,
+            stack: Vec::new(),
+            max_queries,
+            period: queue.get_timestamp_period(),
+            precision,
+
+            frame: 0,
+            last_frame: None,
+
+            cpu_stack: Vec::new(),
+            capture: None,
+        }
+    }
+
+    /// Starts measuring a frame. `encoder` must be the first encoder submitted in the frame.
+    pub fn begin_frame(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        assert!(self.current.is_none(), "frame already begun");
+        let frame = self.frame;
+        self.frame += 1;
+        let now = Instant::now();
+        self.cpu_stack.push((format!("frame {}", frame), now));
+
+        self.current = self
+            .slots
+            .iter()
+            .position(|slot| matches!(slot.state, SlotState::Idle));
+        if let Some(slot) = self.current_slot() {
+            slot.state = SlotState::Recording;
+            slot.frame = frame;
+            slot.cpu_start = now;
+            slot.scopes.clear();
+            slot.queries = 1;
+            encoder.write_timestamp(&slot.query_set, 0);
+        }
+    }
+
+    /// Timestamps written by this profiler.
+    pub fn precision(&self) -> TimestampPrecision {
+        self.precision
+    }
+
+    /// Starts a named scope on a command encoder or, depending on [`Self::precision`], inside a
+    /// pass. Scopes may be nested and must be ended in reverse order.
+    ///
+    /// Scopes which can't be measured at the current precision only show up as CPU spans.
+    pub fn begin_scope<W: TimestampWriter>(&mut self, encoder: &mut W, label: &str) {
+        self.cpu_stack.push((label.to_owned(), Instant::now()));
+        let depth = self.stack.len() as u32;
+        let max_queries = self.max_queries;
+        let supported = W::PRECISION <= self.precision;
+        let index = self.current_slot().filter(|_| supported).and_then(|slot| {
+            // Keep queries for the ends of open scopes and the end of the frame.
+            if slot.queries + depth + 2 >= max_queries {
+                return None;
+            }
+            encoder.write_timestamp(&slot.query_set, slot.queries);
+            slot.scopes
+                .push((label.to_owned(), depth, slot.queries, None));
+            slot.queries += 1;
+            Some(slot.scopes.len() - 1)
+        });
+        self.stack.push(index.unwrap_or(usize::MAX));
+    }
+
+    /// Ends the innermost scope. If the end can't be measured at the current precision, the
+    /// scope is left out of the GPU results.
+    pub fn end_scope<W: TimestampWriter>(&mut self, encoder: &mut W) {
+        let index = self.stack.pop().expect("no scope to end");
+        self.end_cpu_span();
+        let supported = W::PRECISION <= self.precision;
+        if let Some(slot) = self.current_slot().filter(|_| supported) {
+            if let Some(scope) = slot.scopes.get_mut(index) {
+                encoder.write_timestamp(&slot.query_set, slot.queries);
+                scope.3 = Some(slot.queries);
+                slot.queries += 1;
+            }
+        }
+    }
+
+    /// Ends measuring the frame. `encoder` must be the last encoder submitted in the frame.
+    pub fn end_frame(&mut self, encoder: &mut wgpu::CommandEncoder) {
+        assert!(self.stack.is_empty(), "all scopes must be ended");
+        if let Some(slot) = self.current_slot() {
+            encoder.write_timestamp(&slot.query_set, slot.queries);
+            slot.queries += 1;
+            encoder.resolve_query_set(&slot.query_set, 0..slot.queries, &slot.staging, 0);
+            slot.state = SlotState::Resolved;
+        }
+        self.current = None;
+        self.end_cpu_span();
+    }
+
+    /// Starts a scope measuring only CPU time, e.g. culling or building command buffers, which
+    /// shows up in exported traces. Must be ended in reverse order with scopes.
+    pub fn begin_cpu_scope(&mut self, label: &str) {
+        self.cpu_stack.push((label.to_owned(), Instant::now()));
+    }
+
+    /// Ends the innermost CPU scope.
+    pub fn end_cpu_scope(&mut self) {
+        assert!(!self.cpu_stack.is_empty(), "no cpu scope to end");
+        self.end_cpu_span();
+    }
+
+    /// Starts collecting spans of all following frames for [`Self::export_chrome_trace`],
+    /// discarding previously collected ones.
+    pub fn start_capture(&mut self) {
+        self.capture = Some(Capture {
+            start: Instant::now(),
+            spans: Vec::new(),
+            active: true,
+        });
+    }
+
+    /// Stops collecting spans. GPU results of frames measured during the capture are still
+    /// added as they arrive.
+    pub fn stop_capture(&mut self) {
+        if let Some(capture) = &mut self.capture {
+            capture.active = false;
+        }
+    }
+
+    /// Writes the captured spans as Chrome tracing JSON, viewable in Perfetto, `chrome://tracing`
+    /// and speedscope.
+    ///
+    /// CPU and GPU spans are shown as separate threads. GPU timestamps are aligned to the CPU
+    /// time at [`Self::begin_frame`], so GPU spans are shifted by the submission latency.
+    pub fn export_chrome_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.chrome_trace())
+    }
+
+    /// Captured spans as Chrome tracing JSON, see [`Self::export_chrome_trace`].
+    pub fn chrome_trace(&self) -> String {
+        let mut events: Vec<String> = [(Track::Cpu, "CPU"), (Track::Gpu, "GPU")]
+            .iter()
+            .map(|(track, name)| {
+                format!(
+                    concat!(
+                        r#"{{"name":"thread_name","ph":"M","pid":0,"tid":{},"#,
+                        r#""args":{{"name":"{}"}}}}"#
+                    ),
+                    *track as u32, name
+                )
+            })
+            .collect();
+
+        let spans = self.capture.as_ref().map_or(&[][..], |c| &c.spans);
+        events.extend(spans.iter().map(|span| {
+            format!(
+                concat!(
+                    r#"{{"name":"{}","cat":"{}","ph":"X","ts":{:.3},"dur":{:.3},"#,
+                    r#""pid":0,"tid":{}}}"#
+                ),
+                escape_json(&span.label),
+                match span.track {
+                    Track::Cpu => "cpu",
+                    Track::Gpu => "gpu",
+                },
+                span.start.as_secs_f64() * 1e6,
+                span.duration.as_secs_f64() * 1e6,
+                span.track as u32,
+            )
+        }));
+
+        format!(
+            "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+            events.join(",\n")
+        )
+    }
+
+    /// Must be called after the encoder passed to [`Self::end_frame`] got submitted.
+    ///
+    /// Starts reading back the frame and collects finished frames. Doesn't block.
+    pub fn after_submit(&mut self, device: &wgpu::Device) {
+        for slot in &mut self.slots {
+            if matches!(slot.state, SlotState::Resolved) {
+                let (sender, receiver) = mpsc::channel();
+                slot.staging
+                    .slice(..)
+                    .map_async(wgpu::MapMode::Read, move |result| {
+                        let _ = sender.send(result);
+                    });
+                slot.state = SlotState::Mapping(receiver);
+            }
+        }
+        device.poll(wgpu::Maintain::Poll);
+
+        for slot in &mut self.slots {
+            let result = match &slot.state {
+                SlotState::Mapping(receiver) => receiver.try_recv(),
+                _ => continue,
+            };
+            let mapped = match result {
+                Ok(mapped) => mapped.is_ok(),
+                Err(TryRecvError::Empty) => continue,
+                Err(TryRecvError::Disconnected) => false,
+            };
+            slot.state = SlotState::Idle;
+            if !mapped {
+                continue;
+            }
+
+            let timestamps: Vec<u64> = slot.staging.slice(..).get_mapped_range()
+                [..slot.queries as usize * 8]
+                .chunks_exact(8)
+                .map(|t| u64::from_le_bytes(t.try_into().unwrap()))
+                .collect();
+            slot.staging.unmap();
+
+            let period = self.period;
+            let duration = |start: u32, end: u32| {
+                let ticks = timestamps[end as usize].saturating_sub(timestamps[start as usize]);
+                Duration::from_nanos((ticks as f64 * period as f64) as u64)
+            };
+            let frame = ProfilerFrame {
+                index: slot.frame,
+                gpu_time: duration(0, slot.queries - 1),
+                scopes: slot
+                    .scopes
+                    .iter()
+                    .filter_map(|(label, depth, start, end)| {
+                        end.map(|end| ProfileScope {
+                            label: label.clone(),
+                            depth: *depth,
+                            start: duration(0, *start),
+                            duration: duration(*start, end),
+                        })
+                    })
+                    .collect(),
+            };
+
+            if let Some(capture) = &mut self.capture {
+                // Frames begun before the capture started are skipped.
+                if let Some(frame_start) = slot.cpu_start.checked_duration_since(capture.start) {
+                    capture.spans.push(Span {
+                        label: format!("frame {}", frame.index),
+                        track: Track::Gpu,
+                        start: frame_start,
+                        duration: frame.gpu_time,
+                    });
+                    capture.spans.extend(frame.scopes.iter().map(|scope| Span {
+                        label: scope.label.clone(),
+                        track: Track::Gpu,
+                        start: frame_start + scope.start,
+                        duration: scope.duration,
+                    }));
+                }
+            }
+            if self
+                .last_frame
+                .as_ref()
+                .is_none_or(|last| last.index < frame.index)
+            {
+                self.last_frame = Some(frame);
+            }
+        }
+    }
+
+    /// Most recent frame whose results arrived.
+    pub fn last_frame(&self) -> Option<&ProfilerFrame> {
+        self.last_frame.as_ref()
+    }
+
+    fn current_slot(&mut self) -> Option<&mut Slot> {
+        self.current.map(|i| &mut self.slots[i])
+    }
+
+    fn end_cpu_span(&mut self) {
+        let (label, start) = self.cpu_stack.pop().expect("cpu span must be open");
+        if let Some(capture) = self.capture.as_mut().filter(|c| c.active) {
+            if let Some(offset) = start.checked_duration_since(capture.start) {
+                capture.spans.push(Span {
+                    label,
+                    track: Track::Cpu,
+                    start: offset,
+                    duration: start.elapsed(),
+                });
+            }
+        }
+    }
+}
+
+fn escape_json(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,286 @@
+//! Vertex pulling, fetching vertices from storage buffers by index instead of vertex buffers.
+//!
+//! [`generate_snippet`] converts a [`wgpu::VertexBufferLayout`] into WGSL declaring the vertex
+//! struct, the storage buffer binding and a function decoding a vertex from it. The snippet can be
+//! registered with [`ShaderComposer::add_snippet`](crate::shader::ShaderComposer::add_snippet) and
+//! used from vertex shaders drawn without vertex buffers.
+
+use std::fmt::{self, Write};
+
+/// Error returned by [`generate_snippet`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PullingError {
+    /// Storage buffers are read in words, so strides must be a multiple of 4.
+    UnalignedStride { stride: wgpu::BufferAddress },
+    /// Storage buffers are read in words, so offsets must be a multiple of 4.
+    UnalignedOffset {
+        location: u32,
+        offset: wgpu::BufferAddress,
+    },
+    /// The format can't be decoded from whole words.
+    UnsupportedFormat {
+        location: u32,
+        format: wgpu::VertexFormat,
+    },
+    /// Instance step mode can't be pulled by vertex index.
+    InstanceStepMode,
+}
+
+impl fmt::Display for PullingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnalignedStride { stride } => {
+                write!(f, "vertex stride {} isn't a multiple of 4", stride)
+            }
+            Self::UnalignedOffset { location, offset } => write!(
+                f,
+                "offset {} of attribute at location {} isn't a multiple of 4",
+                offset, location
+            ),
+            Self::UnsupportedFormat { location, format } => write!(
+                f,
+                "format {:?} of attribute at location {} can't be pulled",
+                format, location
+            ),
+            Self::InstanceStepMode => write!(f, "instance step mode can't be pulled"),
+        }
+    }
+}
+
+impl std::error::Error for PullingError {}
+
+/// Names and binding of the WGSL generated by [`generate_snippet`].
+#[derive(Clone, Debug)]
+pub struct PullingDescriptor<'a> {
+    pub group: u32,
+    pub binding: u32,
+    /// Name of the `array<u32>` storage buffer variable.
+    pub buffer_name: &'a str,
+    pub struct_name: &'a str,
+    /// Name of the function taking a vertex index and returning the vertex struct.
+    pub function_name: &'a str,
+    /// Field names of the attributes in order. Attributes without name are called
+    /// `attribute_{location}`.
+    pub attribute_names: &'a [&'a str],
+}
+
+impl Default for PullingDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            group: 0,
+            binding: 0,
+            buffer_name: "vertices",
+            struct_name: "Vertex",
+            function_name: "pull_vertex",
+            attribute_names: &[],
+        }
+    }
+}
+
+/// Layout entry of a read-only storage buffer holding pulled vertices or indices.
+pub fn storage_layout_entry(
+    binding: u32,
+    visibility: wgpu::ShaderStages,
+) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only: true },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        count: None,
+    }
+}
+
+/// Generates WGSL pulling vertices of `layout` from a storage buffer.
+///
+/// Buffers bound for pulling need [`wgpu::BufferUsages::STORAGE`]. Only formats consisting of
+/// whole 4 byte words are supported.
+pub fn generate_snippet(
+    layout: &wgpu::VertexBufferLayout<'_>,
+    descriptor: &PullingDescriptor<'_>,
+) -> Result<String, PullingError> {
+    if layout.step_mode == wgpu::VertexStepMode::Instance {
+        return Err(PullingError::InstanceStepMode);
+    }
+    if !layout.array_stride.is_multiple_of(4) {
+        return Err(PullingError::UnalignedStride {
+            stride: layout.array_stride,
+        });
+    }
+
+    let mut fields = String::new();
+    let mut decoders = String::new();
+    for (i, attribute) in layout.attributes.iter().enumerate() {
+        let location = attribute.shader_location;
+        if !attribute.offset.is_multiple_of(4) {
+            return Err(PullingError::UnalignedOffset {
+                location,
+                offset: attribute.offset,
+            });
+        }
+        let (ty, decode) = decode_format(
+            attribute.format,
+            descriptor.buffer_name,
+            attribute.offset / 4,
+        )
+        .ok_or(PullingError::UnsupportedFormat {
+            location,
+            format: attribute.format,
+        })?;
+
+        let name = match descriptor.attribute_names.get(i) {
+            Some(name) => name.to_string(),
+            None => format!("attribute_{}", location),
+        };
+        writeln!(fields, "    {}: {},", name, ty).unwrap();
+        writeln!(decoders, "    vertex.{} = {};", name, decode).unwrap();
+    }
+
+    let mut snippet = String::new();
+    writeln!(snippet, "struct {} {{", descriptor.struct_name).unwrap();
+    snippet.push_str(&fields);
+    snippet.push_str("};\n\n");
+    writeln!(
+        snippet,
+        "@group({}) @binding({})\nvar<storage, read> {}: array<u32>;\n",
+        descriptor.group, descriptor.binding, descriptor.buffer_name
+    )
+    .unwrap();
+    writeln!(
+        snippet,
+        "fn {}(index: u32) -> {} {{",
+        descriptor.function_name, descriptor.struct_name
+    )
+    .unwrap();
+    writeln!(
+        snippet,
+        "    let base = index * {}u;\n    var vertex: {};",
+        layout.array_stride / 4,
+        descriptor.struct_name
+    )
+    .unwrap();
+    snippet.push_str(&decoders);
+    snippet.push_str("    return vertex;\n}\n");
+    Ok(snippet)
+}
+
+/// WGSL type and expression decoding `format` from `buffer` at word `offset` from the vertex
+/// base.
+fn decode_format(
+    format: wgpu::VertexFormat,
+    buffer: &str,
+    offset: u64,
+) -> Option<(&'static str, String)> {
+    use wgpu::VertexFormat as F;
+
+    let word = |i: u64| format!("{}[base + {}u]", buffer, offset + i);
+    let words = |count: u64, map: &dyn Fn(String) -> String| {
+        (0..count)
+            .map(|i| map(word(i)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let float = |w: String| format!("bitcast<f32>({})", w);
+    let sint = |w: String| format!("bitcast<i32>({})", w);
+    let uint = |w: String| w;
+    // Sign extension of the `bits` wide value at `shift` via arithmetic shift.
+    let sext = |w: &str, shift: u32, bits: u32| {
+        format!(
+            "(bitcast<i32>({} << {}u) >> {}u)",
+            w,
+            32 - shift - bits,
+            32 - bits
+        )
+    };
+    let zext = |w: &str, shift: u32, bits: u32| {
+        format!("(({} >> {}u) & {}u)", w, shift, (1u64 << bits) - 1)
+    };
+
+    Some(match format {
+        F::Float32 => ("f32", float(word(0))),
+        F::Float32x2 => ("vec2<f32>", format!("vec2<f32>({})", words(2, &float))),
+        F::Float32x3 => ("vec3<f32>", format!("vec3<f32>({})", words(3, &float))),
+        F::Float32x4 => ("vec4<f32>", format!("vec4<f32>({})", words(4, &float))),
+        F::Uint32 => ("u32", word(0)),
+        F::Uint32x2 => ("vec2<u32>", format!("vec2<u32>({})", words(2, &uint))),
+        F::Uint32x3 => ("vec3<u32>", format!("vec3<u32>({})", words(3, &uint))),
+        F::Uint32x4 => ("vec4<u32>", format!("vec4<u32>({})", words(4, &uint))),
+        F::Sint32 => ("i32", sint(word(0))),
+        F::Sint32x2 => ("vec2<i32>", format!("vec2<i32>({})", words(2, &sint))),
+        F::Sint32x3 => ("vec3<i32>", format!("vec3<i32>({})", words(3, &sint))),
+        F::Sint32x4 => ("vec4<i32>", format!("vec4<i32>({})", words(4, &sint))),
+        F::Unorm8x4 => ("vec4<f32>", format!("unpack4x8unorm({})", word(0))),
+        F::Snorm8x4 => ("vec4<f32>", format!("unpack4x8snorm({})", word(0))),
+        F::Unorm16x2 => ("vec2<f32>", format!("unpack2x16unorm({})", word(0))),
+        F::Snorm16x2 => ("vec2<f32>", format!("unpack2x16snorm({})", word(0))),
+        F::Float16x2 => ("vec2<f32>", format!("unpack2x16float({})", word(0))),
+        F::Unorm16x4 => (
+            "vec4<f32>",
+            format!(
+                "vec4<f32>(unpack2x16unorm({}), unpack2x16unorm({}))",
+                word(0),
+                word(1)
+            ),
+        ),
+        F::Snorm16x4 => (
+            "vec4<f32>",
+            format!(
+                "vec4<f32>(unpack2x16snorm({}), unpack2x16snorm({}))",
+                word(0),
+                word(1)
+            ),
+        ),
+        F::Float16x4 => (
+            "vec4<f32>",
+            format!(
+                "vec4<f32>(unpack2x16float({}), unpack2x16float({}))",
+                word(0),
+                word(1)
+            ),
+        ),
+        F::Uint8x4 => {
+            let w = word(0);
+            let parts = [0, 8, 16, 24].map(|shift| zext(&w, shift, 8));
+            ("vec4<u32>", format!("vec4<u32>({})", parts.join(", ")))
+        }
+        F::Sint8x4 => {
+            let w = word(0);
+            let parts = [0, 8, 16, 24].map(|shift| sext(&w, shift, 8));
+            ("vec4<i32>", format!("vec4<i32>({})", parts.join(", ")))
+        }
+        F::Uint16x2 => {
+            let w = word(0);
+            let parts = [0, 16].map(|shift| zext(&w, shift, 16));
+            ("vec2<u32>", format!("vec2<u32>({})", parts.join(", ")))
+        }
+        F::Sint16x2 => {
+            let w = word(0);
+            let parts = [0, 16].map(|shift| sext(&w, shift, 16));
+            ("vec2<i32>", format!("vec2<i32>({})", parts.join(", ")))
+        }
+        F::Uint16x4 => {
+            let (w0, w1) = (word(0), word(1));
+            let parts = [
+                zext(&w0, 0, 16),
+                zext(&w0, 16, 16),
+                zext(&w1, 0, 16),
+                zext(&w1, 16, 16),
+            ];
+            ("vec4<u32>", format!("vec4<u32>({})", parts.join(", ")))
+        }
+        F::Sint16x4 => {
+            let (w0, w1) = (word(0), word(1));
+            let parts = [
+                sext(&w0, 0, 16),
+                sext(&w0, 16, 16),
+                sext(&w1, 0, 16),
+                sext(&w1, 16, 16),
+            ];
+            ("vec4<i32>", format!("vec4<i32>({})", parts.join(", ")))
+        }
+        _ => return None,
+    })
+}
@@ -0,0 +1,139 @@
+//! Seeding random number generators on the GPU.
+
+use std::num::NonZeroU64;
+
+use crate::shader::ShaderComposer;
+
+const SEED_SIZE: wgpu::BufferAddress = 16;
+
+/// Name of the snippet registered by [`SeedBuffer::register`].
+pub const SEED_SNIPPET: &str = "wgpu_util::seed";
+
+const SEED_WGSL: &str = r#"
+#include "wgpu_util::random"
+
+struct WgpuUtilSeed {
+    frame_seed: u32,
+    frame_index: u32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+@group({group}) @binding({binding})
+var<uniform> wgpu_util_seed: WgpuUtilSeed;
+
+// Per-invocation seed for the current frame.
+fn frame_random_seed(id: vec3<u32>) -> u32 {
+    return random_seed(id, wgpu_util_seed.frame_seed);
+}
+"#;
+
+/// Uniform buffer holding a seed which changes every frame.
+///
+/// Register with a [`ShaderComposer`] and `#include "wgpu_util::seed"` to get
+/// `frame_random_seed(id)`, which seeds the generators of `wgpu_util::random` per invocation.
+/// Seeds are derived deterministically from the initial seed, so runs can be reproduced.
+#[derive(Debug)]
+pub struct SeedBuffer {
+    buffer: wgpu::Buffer,
+    group: u32,
+    binding: u32,
+
+    state: u64,
+    frame_index: u32,
+    frame_seed: u32,
+}
+
+impl SeedBuffer {
+    /// Creates a buffer bound at `group` and `binding` in shaders. Call [`Self::next_frame`]
+    /// before using it.
+    pub fn new(device: &wgpu::Device, seed: u64, group: u32, binding: u32) -> Self {
+        let buffer = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("seed buffer"),
+                size: SEED_SIZE,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+
+        Self {
+            buffer,
+            group,
+            binding,
+
+            state: seed,
+            frame_index: 0,
+            frame_seed: 0,
+        }
+    }
+
+    /// Generates the seed of the next frame and uploads it using [`wgpu::Queue`].
+    pub fn next_frame(&mut self, queue: &wgpu::Queue) {
+        self.frame_seed = splitmix64(&mut self.state) as u32;
+        self.frame_index = self.frame_index.wrapping_add(1);
+
+        let mut contents = [0; SEED_SIZE as usize];
+        contents[0..4].copy_from_slice(&self.frame_seed.to_le_bytes());
+        contents[4..8].copy_from_slice(&self.frame_index.to_le_bytes());
+        queue.write_buffer(&self.buffer, 0, &contents);
+    }
+
+    /// WGSL source of the snippet for this buffer's group and binding.
+    pub fn snippet(&self) -> String {
+        SEED_WGSL
+            .replace("{group}", &self.group.to_string())
+            .replace("{binding}", &self.binding.to_string())
+    }
+
+    /// Registers the snippet as [`SEED_SNIPPET`].
+    pub fn register(&self, composer: &mut ShaderComposer) {
+        composer.add_snippet(SEED_SNIPPET, self.snippet());
+    }
+
+    /// Layout entry for binding the buffer in all shader stages.
+    pub fn bind_group_layout_entry(&self) -> wgpu::BindGroupLayoutEntry {
+        wgpu::BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility: wgpu::ShaderStages::all(),
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(SEED_SIZE),
+            },
+            count: None,
+        }
+    }
+
+    /// Bind group entry for the buffer.
+    pub fn bind_group_entry(&self) -> wgpu::BindGroupEntry<'_> {
+        wgpu::BindGroupEntry {
+            binding: self.binding,
+            resource: self.buffer.as_entire_binding(),
+        }
+    }
+
+    /// Seed of the current frame.
+    pub fn frame_seed(&self) -> u32 {
+        self.frame_seed
+    }
+
+    /// Number of calls to [`Self::next_frame`].
+    pub fn frame_index(&self) -> u32 {
+        self.frame_index
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,308 @@
+//! Reading data back from the GPU.
+
+use std::{
+    future::Future,
+    num::NonZeroU32,
+    ops::Range,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+/// Size and format of a 2D texture, which can't be queried from a [`wgpu::Texture`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+}
+
+impl TextureInfo {
+    /// Size of a single texel in bytes.
+    pub fn texel_size(&self) -> u32 {
+        self.format.describe().block_size as u32
+    }
+
+    /// Size of a tightly packed row in bytes.
+    pub fn unpadded_bytes_per_row(&self) -> u32 {
+        self.width * self.texel_size()
+    }
+
+    /// Size of a row padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`], as required for copies
+    /// into buffers.
+    pub fn padded_bytes_per_row(&self) -> u32 {
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        self.unpadded_bytes_per_row().div_ceil(align) * align
+    }
+}
+
+/// Copies `range` of `buffer` into a staging buffer and blocks until its contents are
+/// available on the CPU.
+///
+/// `buffer` must have [`wgpu::BufferUsages::COPY_SRC`]. The copied range is widened to
+/// [`wgpu::COPY_BUFFER_ALIGNMENT`], so the aligned range has to lie within `buffer`. Only the
+/// requested bytes are returned.
+pub fn read_buffer(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    range: Range<wgpu::BufferAddress>,
+) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    assert!(
+        range.start <= range.end,
+        "range start must not exceed range end"
+    );
+    if range.start == range.end {
+        return Ok(Vec::new());
+    }
+
+    let (staging, skip) = copy_buffer_to_staging(device, queue, buffer, &range);
+    map_read(device, &staging)?;
+
+    let len = (range.end - range.start) as usize;
+    let bytes = staging.slice(..).get_mapped_range()[skip..skip + len].to_vec();
+    staging.unmap();
+
+    Ok(bytes)
+}
+
+/// [`read_buffer`] but without blocking.
+///
+/// The returned future only makes progress while the device is polled, e.g. with
+/// [`wgpu::Maintain::Poll`] once per frame or [`wgpu::Maintain::Wait`].
+pub fn read_buffer_async(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    range: Range<wgpu::BufferAddress>,
+) -> ReadFuture {
+    assert!(
+        range.start <= range.end,
+        "range start must not exceed range end"
+    );
+    if range.start == range.end {
+        return ReadFuture::ready();
+    }
+
+    let (staging, skip) = copy_buffer_to_staging(device, queue, buffer, &range);
+    let len = (range.end - range.start) as usize;
+    ReadFuture::new(staging, skip..skip + len)
+}
+
+/// Future of the bytes read back by [`read_buffer_async`].
+#[derive(Debug)]
+pub struct ReadFuture {
+    staging: Option<wgpu::Buffer>,
+    range: Range<usize>,
+    state: Arc<Mutex<MapState>>,
+}
+
+#[derive(Debug, Default)]
+struct MapState {
+    result: Option<Result<(), wgpu::BufferAsyncError>>,
+    waker: Option<Waker>,
+}
+
+impl ReadFuture {
+    /// Starts mapping the whole `staging` buffer, which must have
+    /// [`wgpu::BufferUsages::MAP_READ`]. Resolves to `range` of its contents.
+    pub(crate) fn new(staging: wgpu::Buffer, range: Range<usize>) -> Self {
+        let state = Arc::new(Mutex::new(MapState::default()));
+        let callback_state = Arc::clone(&state);
+        staging
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let mut state = callback_state.lock().unwrap();
+                state.result = Some(result);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            });
+
+        Self {
+            staging: Some(staging),
+            range,
+            state,
+        }
+    }
+
+    fn ready() -> Self {
+        Self {
+            staging: None,
+            range: 0..0,
+            state: Arc::new(Mutex::new(MapState::default())),
+        }
+    }
+}
+
+impl Future for ReadFuture {
+    type Output = Result<Vec<u8>, wgpu::BufferAsyncError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let staging = match &self.staging {
+            Some(staging) => staging,
+            None => return Poll::Ready(Ok(Vec::new())),
+        };
+
+        let result = {
+            let mut state = self.state.lock().unwrap();
+            match state.result.take() {
+                Some(result) => result,
+                None => {
+                    state.waker = Some(cx.waker().clone());
+                    return Poll::Pending;
+                }
+            }
+        };
+
+        let bytes = result.map(|()| {
+            let bytes = staging.slice(..).get_mapped_range()[self.range.clone()].to_vec();
+            staging.unmap();
+            bytes
+        });
+        self.staging = None;
+        Poll::Ready(bytes)
+    }
+}
+
+/// Maps the whole `buffer` for reading and blocks until the mapping is done.
+///
+/// `buffer` must have [`wgpu::BufferUsages::MAP_READ`]. The caller is responsible for unmapping.
+pub fn map_read(
+    device: &wgpu::Device,
+    buffer: &wgpu::Buffer,
+) -> Result<(), wgpu::BufferAsyncError> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            // The receiver only goes away if this function already returned.
+            let _ = sender.send(result);
+        });
+    device.poll(wgpu::Maintain::Wait);
+
+    receiver
+        .recv()
+        .expect("map callback must be called after waiting on the device")
+}
+
+/// Copies the first mip level of a 2D `texture` into a staging buffer and blocks until its
+/// contents are available on the CPU.
+///
+/// `texture` must have [`wgpu::TextureUsages::COPY_SRC`] and an uncompressed format. The returned
+/// texels are tightly packed, row by row from top to bottom.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    info: &TextureInfo,
+) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    assert!(
+        !info.format.describe().is_compressed(),
+        "texture format must not be compressed"
+    );
+    if info.width == 0 || info.height == 0 {
+        return Ok(Vec::new());
+    }
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("wgpu-util texture readback encoder"),
+    });
+    let staging = copy_texture_to_staging(device, &mut encoder, texture, info);
+    queue.submit(Some(encoder.finish()));
+
+    map_read(device, &staging)?;
+
+    let texels = unpad_rows(&staging.slice(..).get_mapped_range(), info);
+    staging.unmap();
+
+    Ok(texels)
+}
+
+/// Records a copy of the first mip level of a 2D `texture` into a new mappable staging buffer
+/// with rows padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`].
+pub(crate) fn copy_texture_to_staging(
+    device: &wgpu::Device,
+    encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    info: &TextureInfo,
+) -> wgpu::Buffer {
+    let padded_bytes_per_row = info.padded_bytes_per_row();
+
+    let staging = crate::resource_log::create_buffer(
+        device,
+        &wgpu::BufferDescriptor {
+            label: Some("wgpu-util texture readback staging buffer"),
+            size: padded_bytes_per_row as wgpu::BufferAddress * info.height as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        },
+    );
+
+    #[cfg(feature = "debug")]
+    crate::timeline::record(|| {
+        crate::timeline::Operation::copy("texture readback")
+            .read("texture", texture)
+            .write("staging", &staging)
+    });
+    encoder.copy_texture_to_buffer(
+        texture.as_image_copy(),
+        wgpu::ImageCopyBuffer {
+            buffer: &staging,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(padded_bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        wgpu::Extent3d {
+            width: info.width,
+            height: info.height,
+            depth_or_array_layers: 1,
+        },
+    );
+
+    staging
+}
+
+/// Submits a copy of `range` of `buffer`, widened to [`wgpu::COPY_BUFFER_ALIGNMENT`], into a new
+/// mappable staging buffer. Returns the staging buffer and the offset of `range.start` in it.
+fn copy_buffer_to_staging(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &wgpu::Buffer,
+    range: &Range<wgpu::BufferAddress>,
+) -> (wgpu::Buffer, usize) {
+    let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+    let aligned_start = range.start & !align_mask;
+    let aligned_end = (range.end + align_mask) & !align_mask;
+    let aligned_size = aligned_end - aligned_start;
+
+    let staging = crate::resource_log::create_buffer(
+        device,
+        &wgpu::BufferDescriptor {
+            label: Some("wgpu-util readback staging buffer"),
+            size: aligned_size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        },
+    );
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("wgpu-util readback encoder"),
+    });
+    encoder.copy_buffer_to_buffer(buffer, aligned_start, &staging, 0, aligned_size);
+    queue.submit(Some(encoder.finish()));
+
+    (staging, (range.start - aligned_start) as usize)
+}
+
+/// Strips the row padding of a buffer filled by [`copy_texture_to_staging`].
+pub(crate) fn unpad_rows(padded: &[u8], info: &TextureInfo) -> Vec<u8> {
+    let unpadded_bytes_per_row = info.unpadded_bytes_per_row() as usize;
+    padded
+        .chunks_exact(info.padded_bytes_per_row() as usize)
+        .flat_map(|row| &row[..unpadded_bytes_per_row])
+        .copied()
+        .collect()
+}
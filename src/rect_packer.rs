@@ -0,0 +1,442 @@
+//! Packing rectangles into a fixed area, e.g. the layers of a
+//! [`TextureAtlas`](crate::atlas::TextureAtlas).
+
+/// A rectangle placed by a [`RectPacker`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PackedRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Strategy of a [`RectPacker`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PackingAlgorithm {
+    /// Rectangles are placed left to right on horizontal shelves whose height is determined by
+    /// the first rectangle placed on them. Fast and tight for rectangles of similar height, e.g.
+    /// glyphs.
+    #[default]
+    Shelf,
+    /// Rectangles are placed as low as possible on the skyline formed by the top edges of all
+    /// placed rectangles. Handles mixed heights better than shelves.
+    Skyline,
+    /// Free space is kept as rectangles, split in two whenever a rectangle is placed into one.
+    /// Handles widely varying sizes best but fragments over time.
+    Guillotine,
+}
+
+#[derive(Clone, Debug)]
+struct Shelf {
+    y: u32,
+    height: u32,
+    cursor: u32,
+}
+
+/// Segment of the skyline, spanning `width` from `x` at height `y`.
+#[derive(Clone, Debug)]
+struct SkylineNode {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+#[derive(Clone, Debug)]
+enum PackerState {
+    Shelf(Vec<Shelf>),
+    Skyline(Vec<SkylineNode>),
+    Guillotine(Vec<PackedRect>),
+}
+
+/// Places rectangles into a `width` x `height` area without overlap.
+///
+/// Deallocated rectangles are kept in a free list, merged with adjacent free space and reused
+/// before placing rectangles with the algorithm.
+#[derive(Clone, Debug)]
+pub struct RectPacker {
+    width: u32,
+    height: u32,
+    algorithm: PackingAlgorithm,
+    state: PackerState,
+    /// Deallocated rectangles of the shelf and skyline algorithms. The guillotine algorithm
+    /// returns them to its own free list.
+    free: Vec<PackedRect>,
+}
+
+impl RectPacker {
+    /// Creates a new empty packer.
+    pub fn new(width: u32, height: u32, algorithm: PackingAlgorithm) -> Self {
+        let mut packer = Self {
+            width,
+            height,
+            algorithm,
+            state: PackerState::Shelf(Vec::new()),
+            free: Vec::new(),
+        };
+        packer.clear();
+        packer
+    }
+
+    /// Places a `width` x `height` rectangle.
+    ///
+    /// Returns `None` if there is no space left.
+    pub fn allocate(&mut self, width: u32, height: u32) -> Option<PackedRect> {
+        if width > self.width || height > self.height {
+            return None;
+        }
+        if width == 0 || height == 0 {
+            return Some(PackedRect {
+                x: 0,
+                y: 0,
+                width,
+                height,
+            });
+        }
+        if let Some(rect) = allocate_guillotine(&mut self.free, width, height) {
+            return Some(rect);
+        }
+        match &mut self.state {
+            PackerState::Shelf(shelves) => {
+                allocate_shelf(shelves, self.width, self.height, width, height)
+            }
+            PackerState::Skyline(nodes) => {
+                allocate_skyline(nodes, self.width, self.height, width, height)
+            }
+            PackerState::Guillotine(free) => allocate_guillotine(free, width, height),
+        }
+    }
+
+    /// Returns a rectangle placed by [`Self::allocate`] for reuse.
+    pub fn deallocate(&mut self, rect: PackedRect) {
+        if rect.width == 0 || rect.height == 0 {
+            return;
+        }
+        match &mut self.state {
+            PackerState::Guillotine(free) => insert_free(free, rect),
+            _ => insert_free(&mut self.free, rect),
+        }
+    }
+
+    /// Marks the whole area as vacant.
+    pub fn clear(&mut self) {
+        self.free.clear();
+        self.state = match self.algorithm {
+            PackingAlgorithm::Shelf => PackerState::Shelf(Vec::new()),
+            PackingAlgorithm::Skyline => PackerState::Skyline(vec![SkylineNode {
+                x: 0,
+                y: 0,
+                width: self.width,
+            }]),
+            PackingAlgorithm::Guillotine => PackerState::Guillotine(vec![PackedRect {
+                x: 0,
+                y: 0,
+                width: self.width,
+                height: self.height,
+            }]),
+        };
+    }
+
+    pub fn algorithm(&self) -> PackingAlgorithm {
+        self.algorithm
+    }
+
+    /// Width and height of the area.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+}
+
+fn allocate_shelf(
+    shelves: &mut Vec<Shelf>,
+    area_width: u32,
+    area_height: u32,
+    width: u32,
+    height: u32,
+) -> Option<PackedRect> {
+    // Best fit: the lowest shelf which is high enough and has space left.
+    let shelf = shelves
+        .iter_mut()
+        .filter(|s| s.height >= height && area_width - s.cursor >= width)
+        .min_by_key(|s| s.height);
+
+    let shelf = match shelf {
+        Some(shelf) => shelf,
+        None => {
+            let y = shelves.last().map_or(0, |s| s.y + s.height);
+            if area_height - y < height {
+                return None;
+            }
+            shelves.push(Shelf {
+                y,
+                height,
+                cursor: 0,
+            });
+            shelves.last_mut().unwrap()
+        }
+    };
+
+    let rect = PackedRect {
+        x: shelf.cursor,
+        y: shelf.y,
+        width,
+        height,
+    };
+    shelf.cursor += width;
+    Some(rect)
+}
+
+fn allocate_skyline(
+    nodes: &mut Vec<SkylineNode>,
+    area_width: u32,
+    area_height: u32,
+    width: u32,
+    height: u32,
+) -> Option<PackedRect> {
+    // Bottom left: the position with the lowest top edge, ties broken by the narrowest node.
+    let mut best: Option<(usize, u32, u32)> = None;
+    for (i, node) in nodes.iter().enumerate() {
+        if node.x + width > area_width {
+            break;
+        }
+        let y = nodes[i..]
+            .iter()
+            .take_while(|other| other.x < node.x + width)
+            .map(|other| other.y)
+            .max()
+            .unwrap();
+        if y + height > area_height {
+            continue;
+        }
+        let better = match best {
+            Some((best_i, best_y, _)) => {
+                y < best_y || (y == best_y && node.width < nodes[best_i].width)
+            }
+            None => true,
+        };
+        if better {
+            best = Some((i, y, node.x));
+        }
+    }
+    let (i, y, x) = best?;
+
+    // Raise the skyline under the rectangle and cut off the nodes it covers.
+    nodes.insert(
+        i,
+        SkylineNode {
+            x,
+            y: y + height,
+            width,
+        },
+    );
+    let right = x + width;
+    while let Some(node) = nodes.get_mut(i + 1) {
+        if node.x >= right {
+            break;
+        }
+        let end = node.x + node.width;
+        if end <= right {
+            nodes.remove(i + 1);
+        } else {
+            node.width = end - right;
+            node.x = right;
+            break;
+        }
+    }
+    // Merge neighbors at the same height.
+    nodes.dedup_by(|next, node| {
+        let merge = node.y == next.y;
+        if merge {
+            node.width += next.width;
+        }
+        merge
+    });
+
+    Some(PackedRect {
+        x,
+        y,
+        width,
+        height,
+    })
+}
+
+fn allocate_guillotine(free: &mut Vec<PackedRect>, width: u32, height: u32) -> Option<PackedRect> {
+    // Best short side fit: the free rectangle leaving the least space along one axis.
+    let (i, _) = free
+        .iter()
+        .enumerate()
+        .filter(|(_, rect)| rect.width >= width && rect.height >= height)
+        .min_by_key(|(_, rect)| (rect.width - width).min(rect.height - height))?;
+    let rect = free.swap_remove(i);
+
+    // Split along the shorter leftover axis, keeping the larger remainder in one piece.
+    let (right, bottom) = match rect.width - width < rect.height - height {
+        true => (
+            PackedRect {
+                x: rect.x + width,
+                y: rect.y,
+                width: rect.width - width,
+                height,
+            },
+            PackedRect {
+                x: rect.x,
+                y: rect.y + height,
+                width: rect.width,
+                height: rect.height - height,
+            },
+        ),
+        false => (
+            PackedRect {
+                x: rect.x + width,
+                y: rect.y,
+                width: rect.width - width,
+                height: rect.height,
+            },
+            PackedRect {
+                x: rect.x,
+                y: rect.y + height,
+                width,
+                height: rect.height - height,
+            },
+        ),
+    };
+    free.extend(
+        [right, bottom]
+            .into_iter()
+            .filter(|rect| rect.width > 0 && rect.height > 0),
+    );
+
+    Some(PackedRect {
+        x: rect.x,
+        y: rect.y,
+        width,
+        height,
+    })
+}
+
+/// Adds `rect` to `free`, merging it with free rectangles sharing a whole edge.
+fn insert_free(free: &mut Vec<PackedRect>, mut rect: PackedRect) {
+    while let Some(i) = free.iter().position(|other| {
+        let horizontal = other.y == rect.y
+            && other.height == rect.height
+            && (other.x + other.width == rect.x || rect.x + rect.width == other.x);
+        let vertical = other.x == rect.x
+            && other.width == rect.width
+            && (other.y + other.height == rect.y || rect.y + rect.height == other.y);
+        horizontal || vertical
+    }) {
+        let other = free.swap_remove(i);
+        let (x, y) = (rect.x.min(other.x), rect.y.min(other.y));
+        rect = PackedRect {
+            x,
+            y,
+            width: (rect.x + rect.width).max(other.x + other.width) - x,
+            height: (rect.y + rect.height).max(other.y + other.height) - y,
+        };
+    }
+    free.push(rect);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHMS: [PackingAlgorithm; 3] = [
+        PackingAlgorithm::Shelf,
+        PackingAlgorithm::Skyline,
+        PackingAlgorithm::Guillotine,
+    ];
+
+    fn overlap(a: &PackedRect, b: &PackedRect) -> bool {
+        a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+    }
+
+    fn assert_valid(packer: &RectPacker, rects: &[PackedRect]) {
+        let (width, height) = packer.size();
+        for (i, a) in rects.iter().enumerate() {
+            assert!(
+                a.x + a.width <= width && a.y + a.height <= height,
+                "{:?}",
+                a
+            );
+            for b in &rects[i + 1..] {
+                assert!(!overlap(a, b), "{:?} overlaps {:?}", a, b);
+            }
+        }
+    }
+
+    /// Deterministic sizes between 1 and 32.
+    fn sizes(count: u32) -> impl Iterator<Item = (u32, u32)> {
+        (0..count).map(|i| (i * 7 % 32 + 1, i * 13 % 29 + 1))
+    }
+
+    #[test]
+    fn allocations_do_not_overlap() {
+        for algorithm in ALGORITHMS {
+            let mut packer = RectPacker::new(256, 256, algorithm);
+            let rects: Vec<_> = sizes(200)
+                .filter_map(|(width, height)| packer.allocate(width, height))
+                .collect();
+            assert!(
+                rects.len() > 50,
+                "{:?} placed only {}",
+                algorithm,
+                rects.len()
+            );
+            assert_valid(&packer, &rects);
+        }
+    }
+
+    #[test]
+    fn full_packer_rejects_allocations() {
+        for algorithm in ALGORITHMS {
+            let mut packer = RectPacker::new(64, 64, algorithm);
+            let rects: Vec<_> = (0..16).map(|_| packer.allocate(16, 16).unwrap()).collect();
+            assert_valid(&packer, &rects);
+            assert_eq!(packer.allocate(16, 16), None, "{:?}", algorithm);
+            assert_eq!(packer.allocate(65, 1), None);
+        }
+    }
+
+    #[test]
+    fn deallocated_space_is_reused() {
+        for algorithm in ALGORITHMS {
+            let mut packer = RectPacker::new(64, 64, algorithm);
+            let mut rects: Vec<_> = (0..16).map(|_| packer.allocate(16, 16).unwrap()).collect();
+
+            // Two horizontally adjacent cells merge into room for a wider rectangle.
+            let left = rects.remove(0);
+            let right_index = rects
+                .iter()
+                .position(|r| r.x == left.x + 16 && r.y == left.y)
+                .unwrap();
+            let right = rects.remove(right_index);
+            packer.deallocate(left);
+            packer.deallocate(right);
+            let wide = packer
+                .allocate(32, 16)
+                .expect("freed cells should be merged");
+            rects.push(wide);
+            assert_valid(&packer, &rects);
+            assert_eq!(packer.allocate(1, 1), None, "{:?}", algorithm);
+
+            // Every freed rectangle can be allocated again.
+            for rect in rects.drain(..) {
+                packer.deallocate(rect);
+            }
+            let rects: Vec<_> = (0..16).map(|_| packer.allocate(16, 16)).collect();
+            assert!(rects.iter().all(Option::is_some), "{:?}", algorithm);
+            assert_valid(&packer, &rects.into_iter().flatten().collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn clear_vacates_everything() {
+        for algorithm in ALGORITHMS {
+            let mut packer = RectPacker::new(32, 32, algorithm);
+            assert!(packer.allocate(32, 32).is_some());
+            assert_eq!(packer.allocate(1, 1), None);
+            packer.clear();
+            assert!(packer.allocate(32, 32).is_some(), "{:?}", algorithm);
+        }
+    }
+}
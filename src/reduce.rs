@@ -0,0 +1,333 @@
+//! Reducing buffers of floats on the GPU.
+//!
+//! Adapters without enough compute support, e.g. WebGL2, fall back to a CPU reference
+//! implementation, see [`ReduceBackend`].
+
+use std::num::NonZeroU64;
+
+use crate::{BufferInitDescriptor, DeviceExt};
+
+const WORKGROUP_SIZE: u32 = 256;
+
+/// Operation combining the values in [`Reducer::reduce`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReduceOp {
+    Sum,
+    Min,
+    Max,
+}
+
+impl ReduceOp {
+    fn index(self) -> u32 {
+        match self {
+            Self::Sum => 0,
+            Self::Min => 1,
+            Self::Max => 2,
+        }
+    }
+
+    /// Value leaving every other value unchanged when combined with it.
+    pub fn identity(self) -> f32 {
+        match self {
+            Self::Sum => 0.0,
+            Self::Min => f32::MAX,
+            Self::Max => f32::MIN,
+        }
+    }
+
+    fn combine(self, a: f32, b: f32) -> f32 {
+        match self {
+            Self::Sum => a + b,
+            Self::Min => a.min(b),
+            Self::Max => a.max(b),
+        }
+    }
+}
+
+/// Reduces `values` with `op` on the CPU.
+///
+/// Combines values in blocks of 256 like the GPU passes, so results of both backends agree up to
+/// rounding, making this usable as a reference in differential tests.
+pub fn reduce_cpu(values: &[f32], op: ReduceOp) -> f32 {
+    let mut values = values.to_vec();
+    while values.len() > 1 {
+        values = values
+            .chunks(WORKGROUP_SIZE as usize)
+            .map(|chunk| chunk.iter().fold(op.identity(), |a, &b| op.combine(a, b)))
+            .collect();
+    }
+    values.first().copied().unwrap_or(op.identity())
+}
+
+/// Where a [`Reducer`] runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReduceBackend {
+    /// Compute passes recorded by [`Reducer::reduce`].
+    Gpu,
+    /// [`reduce_cpu`] on values read back from the input buffer. Only
+    /// [`Reducer::reduce_to_f32`] is available.
+    Cpu,
+}
+
+impl ReduceBackend {
+    /// [`Self::Gpu`] if `limits` allow the reduce pipeline, [`Self::Cpu`] otherwise.
+    pub fn select(limits: &wgpu::Limits) -> Self {
+        let supported = limits.max_storage_buffers_per_shader_stage >= 2
+            && limits.max_storage_buffer_binding_size >= WORKGROUP_SIZE * 4
+            && limits.max_compute_invocations_per_workgroup >= WORKGROUP_SIZE
+            && limits.max_compute_workgroup_size_x >= WORKGROUP_SIZE
+            && limits.max_compute_workgroups_per_dimension > 0;
+        match supported {
+            true => Self::Gpu,
+            false => Self::Cpu,
+        }
+    }
+}
+
+/// Descriptor for [`Reducer`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ReducerDescriptor {
+    /// Use [`ReduceBackend::Cpu`] even if the device supports the GPU passes, e.g. to compare
+    /// both backends.
+    pub force_cpu: bool,
+}
+
+/// Compute pipeline reducing `f32` buffers to a single value.
+///
+/// Each pass reduces 256 values per workgroup, so a million values take three passes.
+#[derive(Debug)]
+pub struct Reducer {
+    /// `None` for [`ReduceBackend::Cpu`].
+    gpu: Option<GpuReducer>,
+}
+
+#[derive(Debug)]
+struct GpuReducer {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Reducer {
+    /// Creates a reducer with the backend chosen by [`ReduceBackend::select`].
+    pub fn new(device: &wgpu::Device) -> Self {
+        Self::with_descriptor(device, &ReducerDescriptor::default())
+    }
+
+    pub fn with_descriptor(device: &wgpu::Device, descriptor: &ReducerDescriptor) -> Self {
+        let backend = match descriptor.force_cpu {
+            true => ReduceBackend::Cpu,
+            false => ReduceBackend::select(&device.limits()),
+        };
+        Self {
+            gpu: match backend {
+                ReduceBackend::Gpu => Some(GpuReducer::new(device)),
+                ReduceBackend::Cpu => None,
+            },
+        }
+    }
+
+    pub fn backend(&self) -> ReduceBackend {
+        match self.gpu {
+            Some(_) => ReduceBackend::Gpu,
+            None => ReduceBackend::Cpu,
+        }
+    }
+
+    /// Records passes reducing the first `len` `f32`s of `input` with `op`.
+    ///
+    /// `input` must have [`wgpu::BufferUsages::STORAGE`]. Returns a new buffer with
+    /// [`wgpu::BufferUsages::COPY_SRC`] holding the result as its first `f32`. Reducing no values
+    /// results in the identity of `op`.
+    ///
+    /// # Panics
+    ///
+    /// If the backend is [`ReduceBackend::Cpu`], since the input is only known once `encoder` is
+    /// submitted. Use [`Self::reduce_to_f32`] to support both backends.
+    pub fn reduce(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        input: &wgpu::Buffer,
+        len: u32,
+        op: ReduceOp,
+    ) -> wgpu::Buffer {
+        let gpu = self
+            .gpu
+            .as_ref()
+            .expect("Reducer::reduce requires the GPU backend");
+        if len == 0 {
+            return device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("reduce result buffer"),
+                contents: &f32::to_le_bytes(op.identity()),
+                size: None,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            });
+        }
+
+        let max_groups = device.limits().max_compute_workgroups_per_dimension;
+        let mut len = len;
+        let mut previous: Option<wgpu::Buffer> = None;
+        loop {
+            let groups = len.div_ceil(WORKGROUP_SIZE);
+            let row_length = groups.min(max_groups);
+            let output = crate::resource_log::create_buffer(
+                device,
+                &wgpu::BufferDescriptor {
+                    label: Some("reduce result buffer"),
+                    size: groups as wgpu::BufferAddress * 4,
+                    usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                },
+            );
+            let params = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("reduce params buffer"),
+                contents: &[len, op.index(), row_length, 0]
+                    .iter()
+                    .flat_map(|w| w.to_le_bytes())
+                    .collect::<Vec<_>>(),
+                size: None,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("reduce bind group"),
+                layout: &gpu.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: previous.as_ref().unwrap_or(input),
+                            offset: 0,
+                            size: NonZeroU64::new(len as wgpu::BufferAddress * 4),
+                        }),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: output.as_entire_binding(),
+                    },
+                ],
+            });
+
+            #[cfg(feature = "debug")]
+            crate::timeline::record(|| {
+                crate::timeline::Operation::dispatch("reduce pass")
+                    .read("input", previous.as_ref().unwrap_or(input))
+                    .write("output", &output)
+            });
+            {
+                let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("reduce pass"),
+                });
+                pass.set_pipeline(&gpu.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                crate::dispatch(
+                    &mut pass,
+                    "reduce pass",
+                    [row_length, groups.div_ceil(row_length), 1],
+                );
+            }
+
+            if groups == 1 {
+                return output;
+            }
+            len = groups;
+            previous = Some(output);
+        }
+    }
+
+    /// Reduces the first `len` `f32`s of `input` with `op` and blocks until the result is read
+    /// back.
+    ///
+    /// With [`ReduceBackend::Cpu`] the values are read back instead and `input` needs
+    /// [`wgpu::BufferUsages::COPY_SRC`] rather than [`wgpu::BufferUsages::STORAGE`].
+    pub fn reduce_to_f32(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        input: &wgpu::Buffer,
+        len: u32,
+        op: ReduceOp,
+    ) -> Result<f32, wgpu::BufferAsyncError> {
+        if self.gpu.is_none() {
+            let bytes = crate::readback::read_buffer(
+                device,
+                queue,
+                input,
+                0..len as wgpu::BufferAddress * 4,
+            )?;
+            let values: Vec<f32> = bytes
+                .chunks_exact(4)
+                .map(|v| f32::from_le_bytes(v.try_into().unwrap()))
+                .collect();
+            return Ok(reduce_cpu(&values, op));
+        }
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("reduce encoder"),
+        });
+        let result = self.reduce(device, &mut encoder, input, len, op);
+        queue.submit(Some(encoder.finish()));
+
+        let bytes = crate::readback::read_buffer(device, queue, &result, 0..4)?;
+        Ok(f32::from_le_bytes(bytes[..4].try_into().unwrap()))
+    }
+}
+
+impl GpuReducer {
+    fn new(device: &wgpu::Device) -> Self {
+        let source = include_str!("shaders/reduce.wgsl");
+
+        let shader = crate::create_shader_module(device, Some("reduce shader"), source);
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(4),
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("reduce bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(16),
+                    },
+                    count: None,
+                },
+                storage_entry(1, true),
+                storage_entry(2, false),
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("reduce pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = crate::resource_log::create_compute_pipeline(
+            device,
+            &wgpu::ComputePipelineDescriptor {
+                label: Some("reduce pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: "reduce",
+            },
+        );
+
+        Self {
+            pipeline,
+            bind_group_layout,
+        }
+    }
+}
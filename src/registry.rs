@@ -0,0 +1,240 @@
+//! Resources registered under string names, for passes defined in data files.
+
+use std::{collections::HashMap, fmt, hash, marker::PhantomData};
+
+use crate::binding::ResourceId;
+
+/// Typed handle of a resource in a [`ResourceRegistry`], cheaper to look up than its name.
+pub struct Handle<T> {
+    index: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    fn new(index: usize) -> Self {
+        Self {
+            index,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> hash::Hash for Handle<T> {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> fmt::Debug for Handle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Handle").field(&self.index).finish()
+    }
+}
+
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Storage<T> {
+    /// Removed resources leave `None`, so handles are never reused.
+    slots: Vec<Option<(String, T, ResourceId)>>,
+    names: HashMap<String, usize>,
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self {
+            slots: Vec::new(),
+            names: HashMap::new(),
+        }
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+}
+
+/// Resource types a [`ResourceRegistry`] can hold.
+pub trait RegistryResource: private::Sealed + Sized + 'static {
+    #[doc(hidden)]
+    fn storage(registry: &ResourceRegistry) -> &Storage<Self>;
+    #[doc(hidden)]
+    fn storage_mut(registry: &mut ResourceRegistry) -> &mut Storage<Self>;
+}
+
+macro_rules! registry_resource {
+    ($ty:ty, $field:ident) => {
+        impl private::Sealed for $ty {}
+
+        impl RegistryResource for $ty {
+            fn storage(registry: &ResourceRegistry) -> &Storage<Self> {
+                &registry.$field
+            }
+
+            fn storage_mut(registry: &mut ResourceRegistry) -> &mut Storage<Self> {
+                &mut registry.$field
+            }
+        }
+    };
+}
+
+registry_resource!(wgpu::Buffer, buffers);
+registry_resource!(wgpu::Texture, textures);
+registry_resource!(wgpu::TextureView, views);
+registry_resource!(wgpu::Sampler, samplers);
+registry_resource!(wgpu::BindGroup, bind_groups);
+
+/// Buffers, textures, texture views, samplers and bind groups registered under string names,
+/// e.g. `"gbuffer.albedo"`, so passes defined in data files can reference them.
+///
+/// Every resource type has its own namespace, so a texture and its view can share a name.
+/// Look up names once with [`Self::handle`] and keep the [`Handle`] for per frame access.
+#[derive(Debug, Default)]
+pub struct ResourceRegistry {
+    buffers: Storage<wgpu::Buffer>,
+    textures: Storage<wgpu::Texture>,
+    views: Storage<wgpu::TextureView>,
+    samplers: Storage<wgpu::Sampler>,
+    bind_groups: Storage<wgpu::BindGroup>,
+}
+
+impl ResourceRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `resource` under `name`, returning its handle.
+    ///
+    /// A resource previously registered under `name` is replaced and returned, keeping its
+    /// handle, so handles stay valid when e.g. render targets are recreated on resize.
+    pub fn register<T: RegistryResource>(
+        &mut self,
+        name: impl Into<String>,
+        resource: T,
+    ) -> (Handle<T>, Option<T>) {
+        let name = name.into();
+        let storage = T::storage_mut(self);
+        match storage.names.get(&name) {
+            Some(&index) => {
+                let (_, previous, _) = storage.slots[index]
+                    .replace((name, resource, ResourceId::unique()))
+                    .unwrap();
+                (Handle::new(index), Some(previous))
+            }
+            None => {
+                let index = storage.slots.len();
+                storage.names.insert(name.clone(), index);
+                storage
+                    .slots
+                    .push(Some((name, resource, ResourceId::unique())));
+                (Handle::new(index), None)
+            }
+        }
+    }
+
+    /// Removes the resource registered under `name`. Its handle stays invalid, even if another
+    /// resource is registered under the same name later.
+    pub fn remove<T: RegistryResource>(&mut self, name: &str) -> Option<T> {
+        let storage = T::storage_mut(self);
+        let index = storage.names.remove(name)?;
+        storage.slots[index].take().map(|(_, resource, _)| resource)
+    }
+
+    /// Gets the handle of the resource registered under `name`.
+    pub fn handle<T: RegistryResource>(&self, name: &str) -> Option<Handle<T>> {
+        T::storage(self).names.get(name).copied().map(Handle::new)
+    }
+
+    pub fn get<T: RegistryResource>(&self, handle: Handle<T>) -> Option<&T> {
+        T::storage(self)
+            .slots
+            .get(handle.index)?
+            .as_ref()
+            .map(|(_, resource, _)| resource)
+    }
+
+    /// Identity of the resource of `handle`, which changes when it's replaced by
+    /// [`Self::register`], see [`ResourceId`].
+    pub fn id<T: RegistryResource>(&self, handle: Handle<T>) -> Option<ResourceId> {
+        T::storage(self)
+            .slots
+            .get(handle.index)?
+            .as_ref()
+            .map(|(_, _, id)| *id)
+    }
+
+    pub fn get_by_name<T: RegistryResource>(&self, name: &str) -> Option<&T> {
+        self.get(self.handle(name)?)
+    }
+
+    /// Name the resource of `handle` is registered under.
+    pub fn name<T: RegistryResource>(&self, handle: Handle<T>) -> Option<&str> {
+        T::storage(self)
+            .slots
+            .get(handle.index)?
+            .as_ref()
+            .map(|(name, _, _)| name.as_str())
+    }
+
+    /// Names of all registered resources of type `T`, in registration order.
+    pub fn names<T: RegistryResource>(&self) -> impl Iterator<Item = &str> {
+        T::storage(self)
+            .slots
+            .iter()
+            .flatten()
+            .map(|(name, _, _)| name.as_str())
+    }
+
+    /// Creates a buffer labeled and registered with `name`.
+    pub fn create_buffer(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        descriptor: &wgpu::BufferDescriptor,
+    ) -> Handle<wgpu::Buffer> {
+        let buffer = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some(name),
+                ..*descriptor
+            },
+        );
+        self.register(name, buffer).0
+    }
+
+    /// Creates a texture labeled and registered with `name`, and registers its default view
+    /// under the same name.
+    pub fn create_texture(
+        &mut self,
+        device: &wgpu::Device,
+        name: &str,
+        descriptor: &wgpu::TextureDescriptor,
+    ) -> (Handle<wgpu::Texture>, Handle<wgpu::TextureView>) {
+        let texture = crate::resource_log::create_texture(
+            device,
+            &wgpu::TextureDescriptor {
+                label: Some(name),
+                ..*descriptor
+            },
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some(name),
+            ..Default::default()
+        });
+        (self.register(name, texture).0, self.register(name, view).0)
+    }
+}
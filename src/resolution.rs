@@ -0,0 +1,160 @@
+//! Scaling render resolution to hold a frame rate.
+
+use std::time::Duration;
+
+use crate::{profiler::GpuProfiler, texture::TexturePool};
+
+/// Descriptor for [`DynamicResolution`].
+#[derive(Clone, Debug)]
+pub struct DynamicResolutionDescriptor {
+    /// GPU time per frame to hold, e.g. 16.6 ms for 60 fps.
+    pub target_frame_time: Duration,
+    /// Smallest scale factor of each axis.
+    pub min_scale: f32,
+    /// Largest scale factor of each axis.
+    pub max_scale: f32,
+    /// Scales are rounded to multiples of this, so targets aren't reallocated every frame.
+    pub scale_step: f32,
+    /// Fraction of the target frame time to aim for, leaving room for spikes.
+    pub headroom: f32,
+}
+
+impl Default for DynamicResolutionDescriptor {
+    fn default() -> Self {
+        Self {
+            target_frame_time: Duration::from_micros(16_667),
+            min_scale: 0.5,
+            max_scale: 1.0,
+            scale_step: 0.05,
+            headroom: 0.9,
+        }
+    }
+}
+
+/// Adjusts a render target scale factor based on measured GPU frame times.
+///
+/// Render into targets of [`Self::scaled_size`], e.g. acquired with [`Self::acquire_target`], and
+/// upscale them to the output.
+#[derive(Debug)]
+pub struct DynamicResolution {
+    target_frame_time: f32,
+    min_scale: f32,
+    max_scale: f32,
+    scale_step: f32,
+    headroom: f32,
+
+    scale: f32,
+    smoothed_frame_time: Option<f32>,
+    last_profiler_frame: Option<u64>,
+}
+
+impl DynamicResolution {
+    /// Smoothing factor of the exponential moving average of frame times.
+    const SMOOTHING: f32 = 0.1;
+
+    pub fn new(descriptor: &DynamicResolutionDescriptor) -> Self {
+        assert!(
+            0.0 < descriptor.min_scale && descriptor.min_scale <= descriptor.max_scale,
+            "scale range must be positive and not empty"
+        );
+
+        Self {
+            target_frame_time: descriptor.target_frame_time.as_secs_f32(),
+            min_scale: descriptor.min_scale,
+            max_scale: descriptor.max_scale,
+            scale_step: descriptor.scale_step,
+            headroom: descriptor.headroom,
+
+            scale: descriptor.max_scale,
+            smoothed_frame_time: None,
+            last_profiler_frame: None,
+        }
+    }
+
+    /// Feeds the GPU time of a frame rendered at the current scale. Returns whether the scale
+    /// changed.
+    pub fn update(&mut self, gpu_frame_time: Duration) -> bool {
+        let frame_time = gpu_frame_time.as_secs_f32();
+        let smoothed = match self.smoothed_frame_time {
+            Some(smoothed) => smoothed + (frame_time - smoothed) * Self::SMOOTHING,
+            None => frame_time,
+        };
+        self.smoothed_frame_time = Some(smoothed);
+        if smoothed <= 0.0 {
+            return false;
+        }
+
+        // GPU time is roughly proportional to the pixel count, which grows with the square of
+        // the scale.
+        let budget = self.target_frame_time * self.headroom;
+        let ideal = self.scale * (budget / smoothed).sqrt();
+        let mut scale = ideal.clamp(self.min_scale, self.max_scale);
+        if self.scale_step > 0.0 {
+            scale = ((scale / self.scale_step).floor() * self.scale_step)
+                .clamp(self.min_scale, self.max_scale);
+        }
+
+        // Only grow again once the ideal scale clearly exceeds the next step, to avoid
+        // oscillating between two steps.
+        if scale > self.scale && ideal < self.scale + self.scale_step * 1.5 {
+            return false;
+        }
+        if scale == self.scale {
+            return false;
+        }
+
+        // Frame times measured at the old scale don't predict the new one.
+        self.smoothed_frame_time = Some(smoothed * (scale / self.scale).powi(2));
+        self.scale = scale;
+        true
+    }
+
+    /// Feeds the most recent frame of `profiler`, if it wasn't fed already. Returns whether the
+    /// scale changed.
+    pub fn update_from_profiler(&mut self, profiler: &GpuProfiler) -> bool {
+        let frame = match profiler.last_frame() {
+            Some(frame) if Some(frame.index) != self.last_profiler_frame => frame,
+            _ => return false,
+        };
+        self.last_profiler_frame = Some(frame.index);
+        self.update(frame.gpu_time)
+    }
+
+    /// Current scale factor of each axis.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// Size of render targets for an output of `full_size`.
+    pub fn scaled_size(&self, full_size: [u32; 2]) -> [u32; 2] {
+        full_size.map(|s| ((s as f32 * self.scale).round() as u32).clamp(1, s.max(1)))
+    }
+
+    /// Viewport `[x, y, width, height]` covering the scaled region of a target of `full_size`,
+    /// for rendering into full size targets instead of reallocating them.
+    pub fn viewport(&self, full_size: [u32; 2]) -> [f32; 4] {
+        let [width, height] = self.scaled_size(full_size);
+        [0.0, 0.0, width as f32, height as f32]
+    }
+
+    /// Acquires a texture from `pool` with `descriptor` scaled to [`Self::scaled_size`].
+    pub fn acquire_target(
+        &self,
+        pool: &mut TexturePool,
+        device: &wgpu::Device,
+        descriptor: &wgpu::TextureDescriptor<'_>,
+    ) -> usize {
+        let [width, height] = self.scaled_size([descriptor.size.width, descriptor.size.height]);
+        pool.acquire(
+            device,
+            &wgpu::TextureDescriptor {
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    ..descriptor.size
+                },
+                ..descriptor.clone()
+            },
+        )
+    }
+}
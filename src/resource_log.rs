@@ -0,0 +1,192 @@
+//! Logging resources created by this crate.
+//!
+//! While enabled, every buffer, texture and pipeline created by wgpu-util is logged with its
+//! label, size and usage at `debug` level with target [`LOG_TARGET`]. Counts are aggregated until
+//! [`ResourceLogger::end_frame`], which makes allocations repeated every frame easy to spot.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+/// Target of all log messages of this module.
+pub const LOG_TARGET: &str = "wgpu_util::resources";
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static FRAME: Mutex<ResourceSummary> = Mutex::new(ResourceSummary::EMPTY);
+
+/// Resources created since the last [`ResourceLogger::end_frame`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ResourceSummary {
+    pub buffers: u32,
+    pub buffer_bytes: u64,
+    pub textures: u32,
+    /// Estimated from the format, size, mip levels and sample count.
+    pub texture_bytes: u64,
+    pub pipelines: u32,
+}
+
+impl ResourceSummary {
+    pub const EMPTY: Self = Self {
+        buffers: 0,
+        buffer_bytes: 0,
+        textures: 0,
+        texture_bytes: 0,
+        pipelines: 0,
+    };
+
+    /// Whether no resources were created.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::EMPTY
+    }
+}
+
+impl fmt::Display for ResourceSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} buffers ({} bytes), {} textures (~{} bytes), {} pipelines",
+            self.buffers, self.buffer_bytes, self.textures, self.texture_bytes, self.pipelines
+        )
+    }
+}
+
+/// Opt-in logging of resource creation, see the [module documentation](self).
+#[derive(Debug)]
+pub struct ResourceLogger;
+
+impl ResourceLogger {
+    /// Starts logging, resetting the counts of the current frame.
+    pub fn enable() {
+        *FRAME.lock().unwrap() = ResourceSummary::EMPTY;
+        ENABLED.store(true, Ordering::Relaxed);
+    }
+
+    pub fn disable() {
+        ENABLED.store(false, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    /// Resources created in the current frame so far.
+    pub fn summary() -> ResourceSummary {
+        *FRAME.lock().unwrap()
+    }
+
+    /// Ends the frame, logging and returning the resources created in it. Nothing is logged for
+    /// frames without allocations.
+    pub fn end_frame() -> ResourceSummary {
+        let summary = std::mem::take(&mut *FRAME.lock().unwrap());
+        if Self::is_enabled() && !summary.is_empty() {
+            log::debug!(target: LOG_TARGET, "created in frame: {}", summary);
+        }
+        summary
+    }
+}
+
+fn count(update: impl FnOnce(&mut ResourceSummary)) {
+    update(&mut FRAME.lock().unwrap());
+}
+
+pub(crate) fn create_buffer(
+    device: &wgpu::Device,
+    descriptor: &wgpu::BufferDescriptor,
+) -> wgpu::Buffer {
+    #[cfg(feature = "trace")]
+    crate::trace::record_create_buffer(&crate::BufferInitDescriptor {
+        label: descriptor.label,
+        contents: &[],
+        size: Some(descriptor.size),
+        usage: descriptor.usage,
+    });
+
+    create_buffer_untraced(device, descriptor)
+}
+
+/// [`create_buffer`] for callers recording the creation in the trace themselves.
+pub(crate) fn create_buffer_untraced(
+    device: &wgpu::Device,
+    descriptor: &wgpu::BufferDescriptor,
+) -> wgpu::Buffer {
+    if ResourceLogger::is_enabled() {
+        log::debug!(
+            target: LOG_TARGET,
+            "created buffer {:?}: {} bytes, {:?}",
+            descriptor.label,
+            descriptor.size,
+            descriptor.usage
+        );
+        count(|s| {
+            s.buffers += 1;
+            s.buffer_bytes += descriptor.size;
+        });
+    }
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_buffer(descriptor.size);
+
+    device.create_buffer(descriptor)
+}
+
+pub(crate) fn create_texture(
+    device: &wgpu::Device,
+    descriptor: &wgpu::TextureDescriptor,
+) -> wgpu::Texture {
+    if ResourceLogger::is_enabled() {
+        let size = descriptor.size;
+        log::debug!(
+            target: LOG_TARGET,
+            "created texture {:?}: {}x{}x{} {:?}, {} mips, {:?}",
+            descriptor.label,
+            size.width,
+            size.height,
+            size.depth_or_array_layers,
+            descriptor.format,
+            descriptor.mip_level_count,
+            descriptor.usage
+        );
+        count(|s| {
+            s.textures += 1;
+            s.texture_bytes += crate::texture::estimate_size(descriptor);
+        });
+    }
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_texture(descriptor);
+
+    device.create_texture(descriptor)
+}
+
+pub(crate) fn create_render_pipeline(
+    device: &wgpu::Device,
+    descriptor: &wgpu::RenderPipelineDescriptor,
+) -> wgpu::RenderPipeline {
+    log_pipeline("render", descriptor.label);
+    #[cfg(feature = "trace")]
+    crate::trace::record_pipeline(descriptor.label);
+
+    device.create_render_pipeline(descriptor)
+}
+
+pub(crate) fn create_compute_pipeline(
+    device: &wgpu::Device,
+    descriptor: &wgpu::ComputePipelineDescriptor,
+) -> wgpu::ComputePipeline {
+    log_pipeline("compute", descriptor.label);
+    #[cfg(feature = "trace")]
+    crate::trace::record_pipeline(descriptor.label);
+
+    device.create_compute_pipeline(descriptor)
+}
+
+fn log_pipeline(kind: &'static str, label: wgpu::Label) {
+    if ResourceLogger::is_enabled() {
+        log::debug!(target: LOG_TARGET, "created {} pipeline {:?}", kind, label);
+        count(|s| s.pipelines += 1);
+    }
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_pipeline(kind);
+}
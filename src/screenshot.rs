@@ -0,0 +1,207 @@
+//! Saving textures to image files.
+
+use std::{fmt, io::Write, path::Path};
+
+use crate::readback::{read_texture, TextureInfo};
+
+/// Options for [`save_texture`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct SaveOptions {
+    /// Encode linear unorm texels as sRGB. Texels of sRGB formats are always stored as they are.
+    pub encode_srgb: bool,
+    /// Treat the texels as premultiplied and store them with straight alpha, as PNG expects.
+    /// EXR stores premultiplied alpha, so float formats are not affected.
+    pub unpremultiply_alpha: bool,
+}
+
+/// Error returned by [`save_texture`].
+#[derive(Debug)]
+pub enum SaveError {
+    /// Reading back the texture failed.
+    Readback(wgpu::BufferAsyncError),
+    /// The format can't be stored. Float formats require the `exr` feature.
+    UnsupportedFormat(wgpu::TextureFormat),
+    Io(std::io::Error),
+    Png(png::EncodingError),
+    #[cfg(feature = "exr")]
+    Exr(exr::error::Error),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Readback(e) => write!(f, "failed to read back texture: {}", e),
+            Self::UnsupportedFormat(format) => write!(f, "can't save {:?} textures", format),
+            Self::Io(e) => write!(f, "failed to write file: {}", e),
+            Self::Png(e) => write!(f, "failed to encode PNG: {}", e),
+            #[cfg(feature = "exr")]
+            Self::Exr(e) => write!(f, "failed to encode EXR: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+/// Reads back `texture` and saves it to `path`.
+///
+/// Unorm formats (`R8Unorm`, `Rgba8Unorm`, `Bgra8Unorm` and their sRGB variants) are saved as
+/// PNG, float formats (`R16Float`, `R32Float`, `Rgba16Float`, `Rgba32Float`) as EXR, which
+/// requires the `exr` feature. Blocks until the texture is read back.
+pub fn save_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    info: &TextureInfo,
+    path: impl AsRef<Path>,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    use wgpu::TextureFormat as F;
+    if !matches!(
+        info.format,
+        F::R8Unorm
+            | F::Rgba8Unorm
+            | F::Rgba8UnormSrgb
+            | F::Bgra8Unorm
+            | F::Bgra8UnormSrgb
+            | F::R16Float
+            | F::R32Float
+            | F::Rgba16Float
+            | F::Rgba32Float
+    ) {
+        return Err(SaveError::UnsupportedFormat(info.format));
+    }
+
+    let texels = read_texture(device, queue, texture, info).map_err(SaveError::Readback)?;
+    save_texels(&texels, info, path.as_ref(), options)
+}
+
+/// Saves already read back, tightly packed `texels`. See [`save_texture`].
+pub fn save_texels(
+    texels: &[u8],
+    info: &TextureInfo,
+    path: &Path,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    #[cfg(feature = "exr")]
+    {
+        use wgpu::TextureFormat as F;
+        if matches!(
+            info.format,
+            F::R16Float | F::R32Float | F::Rgba16Float | F::Rgba32Float
+        ) {
+            return write_exr(path, info, texels);
+        }
+    }
+
+    // Encode first, so unsupported formats don't leave an empty file behind.
+    let png = encode_png(texels, info, options)?;
+    std::fs::write(path, png).map_err(SaveError::Io)
+}
+
+/// Encodes tightly packed `texels` of a unorm format as PNG into memory. See [`save_texture`].
+pub fn encode_png(
+    texels: &[u8],
+    info: &TextureInfo,
+    options: &SaveOptions,
+) -> Result<Vec<u8>, SaveError> {
+    let mut png = Vec::new();
+    write_png(&mut png, texels, info, options)?;
+    Ok(png)
+}
+
+fn write_png(
+    writer: impl Write,
+    texels: &[u8],
+    info: &TextureInfo,
+    options: &SaveOptions,
+) -> Result<(), SaveError> {
+    use wgpu::TextureFormat as F;
+    let (color_type, mut data) = match info.format {
+        F::R8Unorm => (png::ColorType::Grayscale, texels.to_vec()),
+        F::Rgba8Unorm | F::Rgba8UnormSrgb => (png::ColorType::Rgba, texels.to_vec()),
+        F::Bgra8Unorm | F::Bgra8UnormSrgb => (
+            png::ColorType::Rgba,
+            texels
+                .chunks_exact(4)
+                .flat_map(|bgra| [bgra[2], bgra[1], bgra[0], bgra[3]])
+                .collect(),
+        ),
+        format => return Err(SaveError::UnsupportedFormat(format)),
+    };
+
+    if color_type == png::ColorType::Rgba {
+        if options.unpremultiply_alpha {
+            for texel in data.chunks_exact_mut(4) {
+                let alpha = texel[3] as u32;
+                for channel in &mut texel[..3] {
+                    // Fully transparent texels have no color to restore.
+                    if let Some(straight) = (*channel as u32 * 255 + alpha / 2).checked_div(alpha) {
+                        *channel = straight.min(255) as u8;
+                    }
+                }
+            }
+        }
+        if options.encode_srgb && !info.format.describe().srgb {
+            let lut = srgb_lut();
+            for texel in data.chunks_exact_mut(4) {
+                for channel in &mut texel[..3] {
+                    *channel = lut[*channel as usize];
+                }
+            }
+        }
+    } else if options.encode_srgb && !info.format.describe().srgb {
+        let lut = srgb_lut();
+        for channel in &mut data {
+            *channel = lut[*channel as usize];
+        }
+    }
+
+    let mut encoder = png::Encoder::new(writer, info.width, info.height);
+    encoder.set_color(color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let mut writer = encoder.write_header().map_err(SaveError::Png)?;
+    writer.write_image_data(&data).map_err(SaveError::Png)
+}
+
+/// Maps linear 8 bit values to sRGB encoded ones.
+fn srgb_lut() -> [u8; 256] {
+    let mut lut = [0; 256];
+    for (i, value) in lut.iter_mut().enumerate() {
+        let linear = i as f32 / 255.0;
+        let srgb = if linear <= 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+        *value = (srgb * 255.0).round() as u8;
+    }
+    lut
+}
+
+#[cfg(feature = "exr")]
+fn write_exr(path: &Path, info: &TextureInfo, texels: &[u8]) -> Result<(), SaveError> {
+    use wgpu::TextureFormat as F;
+
+    let channels = info.format.describe().components as usize;
+    let floats: Vec<f32> = match info.format {
+        F::R16Float | F::Rgba16Float => texels
+            .chunks_exact(2)
+            .map(|b| crate::packing::f32_from_f16(u16::from_le_bytes([b[0], b[1]])))
+            .collect(),
+        _ => texels
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    };
+
+    let width = info.width as usize;
+    exr::prelude::write_rgba_file(path, width, info.height as usize, |x, y| {
+        let texel = &floats[(y * width + x) * channels..][..channels];
+        match channels {
+            1 => (texel[0], texel[0], texel[0], 1.0),
+            _ => (texel[0], texel[1], texel[2], texel[3]),
+        }
+    })
+    .map_err(SaveError::Exr)
+}
@@ -0,0 +1,184 @@
+//! Serving captured frames over HTTP, for inspecting headless or remote instances.
+//!
+//! A [`FrameServer`] is fed through the [`FrameSink`] of a [`FrameDumper`](crate::dump::FrameDumper),
+//! so frames are read back and encoded off the render thread. It answers:
+//!
+//! - `GET /`: a page showing the latest frame, reloading it every second.
+//! - `GET /frame.png`: the latest frame as PNG.
+
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{dump::FrameSink, screenshot::SaveOptions};
+
+/// How long a client may stall reading or writing before its connection is dropped.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const PAGE: &str = "<!DOCTYPE html>
+<html>
+<head><title>wgpu-util frame server</title></head>
+<body style=\"margin: 0; background: #202020\">
+<img id=\"frame\" src=\"/frame.png\" style=\"max-width: 100%\">
+<script>
+setInterval(() => {
+    document.getElementById(\"frame\").src = \"/frame.png?\" + Date.now();
+}, 1000);
+</script>
+</body>
+</html>
+";
+
+#[derive(Debug, Default)]
+struct LatestFrame {
+    index: u64,
+    png: Option<Arc<Vec<u8>>>,
+}
+
+/// A tiny HTTP server serving the latest captured frame as PNG.
+///
+/// Connections are handled one at a time on a worker thread. It's meant for local debugging and
+/// shouldn't be exposed to untrusted networks.
+#[derive(Debug)]
+pub struct FrameServer {
+    address: SocketAddr,
+    latest: Arc<Mutex<LatestFrame>>,
+    shutdown: Arc<AtomicBool>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl FrameServer {
+    /// Starts serving on `address`, e.g. `"127.0.0.1:8080"`. Port 0 picks a free port, see
+    /// [`Self::address`].
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        let address = listener.local_addr()?;
+        let latest = Arc::new(Mutex::new(LatestFrame::default()));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let worker = {
+            let latest = Arc::clone(&latest);
+            let shutdown = Arc::clone(&shutdown);
+            std::thread::Builder::new()
+                .name("wgpu-util frame server".into())
+                .spawn(move || {
+                    for stream in listener.incoming() {
+                        if shutdown.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        // A failed connection only concerns its client.
+                        if let Ok(stream) = stream {
+                            let _ = handle_connection(stream, &latest);
+                        }
+                    }
+                })?
+        };
+
+        Ok(Self {
+            address,
+            latest,
+            shutdown,
+            worker: Some(worker),
+        })
+    }
+
+    /// The address the server listens on.
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// A sink encoding captured frames and making them the latest served frame.
+    ///
+    /// Frames whose format can't be encoded as PNG are skipped.
+    pub fn sink(&self, options: SaveOptions) -> FrameSink {
+        let latest = Arc::clone(&self.latest);
+        FrameSink::Callback(Box::new(move |frame| {
+            if let Ok(png) = crate::screenshot::encode_png(&frame.texels, &frame.info, &options) {
+                *latest.lock().unwrap() = LatestFrame {
+                    index: frame.index,
+                    png: Some(Arc::new(png)),
+                };
+            }
+        }))
+    }
+
+    /// Index of the latest served frame, if any frame was captured yet.
+    pub fn latest_index(&self) -> Option<u64> {
+        let latest = self.latest.lock().unwrap();
+        latest.png.as_ref().map(|_| latest.index)
+    }
+}
+
+impl Drop for FrameServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        // Wake up the blocking accept. A wildcard address can't be connected to, so its
+        // loopback is used instead.
+        let mut address = self.address;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address {
+                SocketAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                SocketAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+        let _ = TcpStream::connect(address);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, latest: &Mutex<LatestFrame>) -> io::Result<()> {
+    // Don't let a stalled client block the server.
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut request_line = String::new();
+    BufReader::new(&stream).read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next(), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or("");
+
+    if method != Some("GET") {
+        return respond(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+    match path {
+        "/" => respond(&mut stream, "200 OK", "text/html", PAGE.as_bytes()),
+        "/frame.png" => {
+            let png = latest.lock().unwrap().png.clone();
+            match png {
+                Some(png) => respond(&mut stream, "200 OK", "image/png", &png),
+                None => respond(
+                    &mut stream,
+                    "503 Service Unavailable",
+                    "text/plain",
+                    b"no frame captured yet",
+                ),
+            }
+        }
+        _ => respond(&mut stream, "404 Not Found", "text/plain", b"not found"),
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()
+}
@@ -0,0 +1,220 @@
+//! Composing WGSL sources from reusable snippets.
+
+use std::{collections::HashMap, fmt};
+
+/// Snippets registered by [`ShaderComposer::new`] as `(name, source)`.
+///
+/// - `wgpu_util::fullscreen`: `vs_fullscreen` vertex shader drawing a fullscreen triangle with 3
+///   vertices, outputting `FullscreenOutput`.
+/// - `wgpu_util::tonemap`: `tonemap_reinhard`, `tonemap_aces` and `tonemap_clamp`.
+/// - `wgpu_util::random`: `pcg_hash`, `xorshift32` and `random_u32`/`random_f32`/`random_vec2f`
+///   advancing a per-invocation state seeded with `random_seed`/`random_seed_pixel`.
+/// - `wgpu_util::color`: sRGB, HSV and luminance conversions.
+/// - `wgpu_util::prefix_sum`: `prefix_sum_workgroup` for workgroups of 256 invocations.
+/// - `wgpu_util::motion`: `motion_vector` from current and previous clip positions.
+/// - `wgpu_util::quantization`: `dequantize_position` and `decode_octahedral` for vertices
+///   quantized by [`quantize_vertices`](crate::mesh::quantize_vertices).
+/// - `wgpu_util::mip`: `mip_level` and `mip_level_anisotropic` estimating sampled mip levels from
+///   uv derivatives, and `mip_level_color` matching
+///   [`create_mip_debug_texture`](crate::texture::create_mip_debug_texture).
+/// - `wgpu_util::msdf`: `msdf_median`, `msdf_screen_px_range` and `msdf_coverage` for glyphs of
+///   [`MsdfFont`](crate::text::MsdfFont) atlases.
+pub const BUILTIN_SNIPPETS: &[(&str, &str)] = &[
+    (
+        "wgpu_util::fullscreen",
+        include_str!("shaders/lib/fullscreen.wgsl"),
+    ),
+    (
+        "wgpu_util::tonemap",
+        include_str!("shaders/lib/tonemap.wgsl"),
+    ),
+    ("wgpu_util::random", include_str!("shaders/lib/random.wgsl")),
+    ("wgpu_util::color", include_str!("shaders/lib/color.wgsl")),
+    (
+        "wgpu_util::prefix_sum",
+        include_str!("shaders/lib/prefix_sum.wgsl"),
+    ),
+    ("wgpu_util::motion", include_str!("shaders/lib/motion.wgsl")),
+    (
+        "wgpu_util::quantization",
+        include_str!("shaders/lib/quantization.wgsl"),
+    ),
+    ("wgpu_util::mip", include_str!("shaders/lib/mip.wgsl")),
+    ("wgpu_util::msdf", include_str!("shaders/lib/msdf.wgsl")),
+];
+
+/// Error returned by [`ShaderComposer::compose`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ComposeError {
+    /// An included snippet isn't registered.
+    UnknownSnippet(String),
+    /// A snippet includes itself, directly or indirectly.
+    Cycle(String),
+    /// An `#include` directive couldn't be parsed.
+    MalformedInclude { line: String },
+}
+
+impl fmt::Display for ComposeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownSnippet(name) => write!(f, "unknown snippet \"{}\"", name),
+            Self::Cycle(name) => write!(f, "snippet \"{}\" includes itself", name),
+            Self::MalformedInclude { line } => write!(f, "malformed include: {}", line),
+        }
+    }
+}
+
+impl std::error::Error for ComposeError {}
+
+/// Resolves `#include "name"` directives in WGSL sources against registered snippets.
+///
+/// Directives must stand on their own line. Every snippet is included at most once per
+/// composition, so snippets can include their dependencies without duplicating definitions.
+#[derive(Clone, Debug)]
+pub struct ShaderComposer {
+    snippets: HashMap<String, String>,
+}
+
+impl Default for ShaderComposer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderComposer {
+    /// Creates a composer with the [`BUILTIN_SNIPPETS`].
+    pub fn new() -> Self {
+        let mut composer = Self::empty();
+        for (name, source) in BUILTIN_SNIPPETS {
+            composer.add_snippet(*name, *source);
+        }
+        composer
+    }
+
+    /// Creates a composer without any snippets.
+    pub fn empty() -> Self {
+        Self {
+            snippets: HashMap::new(),
+        }
+    }
+
+    /// Registers a snippet, replacing any previous snippet with the same name.
+    pub fn add_snippet(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.snippets.insert(name.into(), source.into());
+    }
+
+    /// Removes a snippet, returning its source.
+    pub fn remove_snippet(&mut self, name: &str) -> Option<String> {
+        self.snippets.remove(name)
+    }
+
+    /// Get the source of a snippet.
+    pub fn snippet(&self, name: &str) -> Option<&str> {
+        self.snippets.get(name).map(String::as_str)
+    }
+
+    /// Resolves all includes of `source`.
+    pub fn compose(&self, source: &str) -> Result<String, ComposeError> {
+        self.compose_with_includes(source)
+            .map(|(composed, _)| composed)
+    }
+
+    /// Names of the snippets included by `source`, directly or indirectly, in the order they are
+    /// composed.
+    ///
+    /// A hot reloader can use these to find the shaders, and the pipelines created from them,
+    /// which need to be recomposed when a snippet changed, see [`Self::dependents`].
+    pub fn dependencies(&self, source: &str) -> Result<Vec<String>, ComposeError> {
+        self.compose_with_includes(source)
+            .map(|(_, included)| included.into_iter().map(str::to_owned).collect())
+    }
+
+    /// Whether `source` includes the snippet `name`, directly or indirectly.
+    ///
+    /// Sources which fail to compose are considered dependent, so recomposing them reports the
+    /// error.
+    pub fn depends_on(&self, source: &str, name: &str) -> bool {
+        self.compose_with_includes(source)
+            .map_or(true, |(_, included)| included.contains(&name))
+    }
+
+    /// Names of the registered snippets including `name`, directly or indirectly, e.g. to
+    /// invalidate everything depending on a snippet edited on disk.
+    pub fn dependents(&self, name: &str) -> Vec<&str> {
+        self.snippets
+            .iter()
+            .filter(|(snippet, source)| *snippet != name && self.depends_on(source, name))
+            .map(|(snippet, _)| snippet.as_str())
+            .collect()
+    }
+
+    /// Composes `source` and creates a shader module from it.
+    pub fn create_shader_module(
+        &self,
+        device: &wgpu::Device,
+        label: wgpu::Label,
+        source: &str,
+    ) -> Result<wgpu::ShaderModule, ComposeError> {
+        let source = self.compose(source)?;
+        Ok(crate::create_shader_module(device, label, &source))
+    }
+
+    fn compose_with_includes(&self, source: &str) -> Result<(String, Vec<&str>), ComposeError> {
+        let mut composed = String::with_capacity(source.len());
+        let mut included = Vec::new();
+        let mut stack = Vec::new();
+        self.compose_into(source, &mut composed, &mut included, &mut stack)?;
+        Ok((composed, included))
+    }
+
+    fn compose_into<'a>(
+        &'a self,
+        source: &str,
+        composed: &mut String,
+        included: &mut Vec<&'a str>,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<(), ComposeError> {
+        for line in source.lines() {
+            let name = match parse_include(line)? {
+                Some(name) => name,
+                None => {
+                    composed.push_str(line);
+                    composed.push('\n');
+                    continue;
+                }
+            };
+
+            let (name, snippet) = self
+                .snippets
+                .get_key_value(name)
+                .ok_or_else(|| ComposeError::UnknownSnippet(name.to_owned()))?;
+            if stack.contains(&name.as_str()) {
+                return Err(ComposeError::Cycle(name.clone()));
+            }
+            if included.contains(&name.as_str()) {
+                continue;
+            }
+
+            included.push(name);
+            stack.push(name);
+            self.compose_into(snippet, composed, included, stack)?;
+            stack.pop();
+        }
+        Ok(())
+    }
+}
+
+/// Returns the snippet name if `line` is an include directive.
+fn parse_include(line: &str) -> Result<Option<&str>, ComposeError> {
+    let rest = match line.trim().strip_prefix("#include") {
+        Some(rest) => rest.trim(),
+        None => return Ok(None),
+    };
+    rest.strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .filter(|name| !name.is_empty() && !name.contains('"'))
+        .map(Some)
+        .ok_or_else(|| ComposeError::MalformedInclude {
+            line: line.to_owned(),
+        })
+}
@@ -0,0 +1,23 @@
+struct Params {
+    unpremultiply: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var input: texture_2d<f32>;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureLoad(input, vec2<i32>(in.position.xy), 0);
+    if (params.unpremultiply == 0u) {
+        return vec4<f32>(color.rgb * color.a, color.a);
+    }
+    if (color.a <= 0.0) {
+        return vec4<f32>(0.0);
+    }
+    return vec4<f32>(color.rgb / color.a, color.a);
+}
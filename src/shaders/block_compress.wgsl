@@ -0,0 +1,248 @@
+// Block compression of 4x4 texel blocks into BC1, BC4 and BC7 mode 6. Blocks are written into a
+// storage buffer row by row, with rows padded to `row_words` u32s.
+
+struct Params {
+    width: u32,
+    height: u32,
+    row_words: u32,
+    // Non-zero to encode linear texels as sRGB.
+    srgb: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var input: texture_2d<f32>;
+@group(0) @binding(2)
+var<storage, read_write> output: array<u32>;
+
+var<private> texels: array<vec4<f32>, 16>;
+var<private> block: array<u32, 4>;
+
+fn linear_to_srgb(c: vec3<f32>) -> vec3<f32> {
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+// Loads the texels of the block at `block_id` scaled to 0-255, clamping to the edge of the input.
+fn load_block(block_id: vec2<u32>) {
+    let max_coord = vec2<i32>(i32(params.width) - 1, i32(params.height) - 1);
+    for (var i = 0u; i < 16u; i = i + 1u) {
+        let coord = vec2<i32>(block_id * 4u + vec2<u32>(i % 4u, i / 4u));
+        var texel = clamp(textureLoad(input, min(coord, max_coord), 0), vec4<f32>(0.0), vec4<f32>(1.0));
+        if (params.srgb != 0u) {
+            texel = vec4<f32>(linear_to_srgb(texel.rgb), texel.a);
+        }
+        texels[i] = texel * 255.0;
+    }
+    block[0] = 0u;
+    block[1] = 0u;
+    block[2] = 0u;
+    block[3] = 0u;
+}
+
+// Writes the lowest `count` bits of `value` at bit `offset` of the block, count <= 8.
+fn put_bits(offset: u32, count: u32, value: u32) {
+    let word = offset / 32u;
+    let shift = offset % 32u;
+    let masked = value & ((1u << count) - 1u);
+    block[word] = block[word] | (masked << shift);
+    if (shift + count > 32u) {
+        block[word + 1u] = block[word + 1u] | (masked >> (32u - shift));
+    }
+}
+
+fn store_block(block_id: vec2<u32>, words: u32) {
+    let base = block_id.y * params.row_words + block_id.x * words;
+    for (var i = 0u; i < words; i = i + 1u) {
+        output[base + i] = block[i];
+    }
+}
+
+fn blocks() -> vec2<u32> {
+    return (vec2<u32>(params.width, params.height) + 3u) / 4u;
+}
+
+fn to_565(c: vec3<f32>) -> u32 {
+    let q = vec3<u32>(round(clamp(c, vec3<f32>(0.0), vec3<f32>(255.0)) * vec3<f32>(31.0, 63.0, 31.0) / 255.0));
+    return (q.r << 11u) | (q.g << 5u) | q.b;
+}
+
+fn from_565(c: u32) -> vec3<f32> {
+    let q = vec3<f32>(f32((c >> 11u) & 31u), f32((c >> 5u) & 63u), f32(c & 31u));
+    return q * 255.0 / vec3<f32>(31.0, 63.0, 31.0);
+}
+
+// Channels to flip between the corners of the bounding box from `low` to `high`, so its diagonal
+// follows the texels of channels decreasing while the widest channel increases. Only the first
+// `channels` channels are considered.
+fn flipped_channels(low: vec4<f32>, high: vec4<f32>, channels: u32) -> vec4<bool> {
+    let extent = high - low;
+    var widest = 0u;
+    for (var c = 1u; c < channels; c = c + 1u) {
+        if (extent[c] > extent[widest]) {
+            widest = c;
+        }
+    }
+    let center = (low + high) * 0.5;
+    var covariance = vec4<f32>(0.0);
+    for (var i = 0u; i < 16u; i = i + 1u) {
+        let d = texels[i] - center;
+        covariance = covariance + d * d[widest];
+    }
+    return covariance < vec4<f32>(0.0);
+}
+
+@compute @workgroup_size(8, 8)
+fn bc1(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id.xy >= blocks())) {
+        return;
+    }
+    load_block(id.xy);
+
+    var low = texels[0].rgb;
+    var high = texels[0].rgb;
+    for (var i = 1u; i < 16u; i = i + 1u) {
+        low = min(low, texels[i].rgb);
+        high = max(high, texels[i].rgb);
+    }
+    let flip = flipped_channels(vec4<f32>(low, 0.0), vec4<f32>(high, 0.0), 3u).xyz;
+    let flipped_low = select(low, high, flip);
+    high = select(high, low, flip);
+    low = flipped_low;
+    // Inset the bounding box by 1/16 of its size, reducing the error of the interpolated colors.
+    let inset = (high - low) / 16.0;
+    var color0 = to_565(high - inset);
+    var color1 = to_565(low + inset);
+    if (color0 < color1) {
+        let swap = color0;
+        color0 = color1;
+        color1 = swap;
+    }
+
+    block[0] = color0 | (color1 << 16u);
+    if (color0 != color1) {
+        let end0 = from_565(color0);
+        let axis = from_565(color1) - end0;
+        let length_squared = dot(axis, axis);
+        // Palette order is end0, end1, 2/3 end0 + 1/3 end1, 1/3 end0 + 2/3 end1.
+        var indices = array<u32, 4>(0u, 2u, 3u, 1u);
+        for (var i = 0u; i < 16u; i = i + 1u) {
+            let t = clamp(dot(texels[i].rgb - end0, axis) / length_squared, 0.0, 1.0);
+            put_bits(32u + 2u * i, 2u, indices[u32(round(t * 3.0))]);
+        }
+    }
+    store_block(id.xy, 2u);
+}
+
+@compute @workgroup_size(8, 8)
+fn bc4(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id.xy >= blocks())) {
+        return;
+    }
+    load_block(id.xy);
+
+    var low = texels[0].r;
+    var high = texels[0].r;
+    for (var i = 1u; i < 16u; i = i + 1u) {
+        low = min(low, texels[i].r);
+        high = max(high, texels[i].r);
+    }
+    let red0 = u32(round(high));
+    let red1 = u32(round(low));
+
+    put_bits(0u, 8u, red0);
+    put_bits(8u, 8u, red1);
+    if (red0 != red1) {
+        // With red0 > red1, indices 0 and 1 are the endpoints and 2 to 7 interpolate from red0
+        // to red1.
+        for (var i = 0u; i < 16u; i = i + 1u) {
+            let t = clamp((f32(red0) - texels[i].r) / f32(red0 - red1), 0.0, 1.0);
+            let level = u32(round(t * 7.0));
+            var index = level + 1u;
+            if (level == 0u) {
+                index = 0u;
+            } else if (level == 7u) {
+                index = 1u;
+            }
+            put_bits(16u + 3u * i, 3u, index);
+        }
+    }
+    store_block(id.xy, 2u);
+}
+
+// Quantizes an endpoint to 7 bits per channel, restored as `2 * q + p`.
+fn quantize_bc7(endpoint: vec4<f32>, p: u32) -> vec4<u32> {
+    return vec4<u32>(clamp(round((endpoint - f32(p)) / 2.0), vec4<f32>(0.0), vec4<f32>(127.0)));
+}
+
+fn bc7_error(endpoint: vec4<f32>, p: u32) -> f32 {
+    let restored = vec4<f32>(quantize_bc7(endpoint, p) * 2u + p);
+    let d = restored - endpoint;
+    return dot(d, d);
+}
+
+// P-bit of an endpoint with the smaller quantization error.
+fn bc7_p_bit(endpoint: vec4<f32>) -> u32 {
+    return select(0u, 1u, bc7_error(endpoint, 1u) < bc7_error(endpoint, 0u));
+}
+
+@compute @workgroup_size(8, 8)
+fn bc7(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id.xy >= blocks())) {
+        return;
+    }
+    load_block(id.xy);
+
+    var low = texels[0];
+    var high = texels[0];
+    for (var i = 1u; i < 16u; i = i + 1u) {
+        low = min(low, texels[i]);
+        high = max(high, texels[i]);
+    }
+    let flip = flipped_channels(low, high, 4u);
+    let flipped_low = select(low, high, flip);
+    high = select(high, low, flip);
+    low = flipped_low;
+
+    var p0 = bc7_p_bit(low);
+    var p1 = bc7_p_bit(high);
+    var end0 = quantize_bc7(low, p0);
+    var end1 = quantize_bc7(high, p1);
+    let restored0 = vec4<f32>(end0 * 2u + p0);
+    let axis = vec4<f32>(end1 * 2u + p1) - restored0;
+    let length_squared = max(dot(axis, axis), 1e-6);
+
+    var indices: array<u32, 16>;
+    for (var i = 0u; i < 16u; i = i + 1u) {
+        let t = clamp(dot(texels[i] - restored0, axis) / length_squared, 0.0, 1.0);
+        indices[i] = u32(round(t * 15.0));
+    }
+    // The highest bit of the first index is implicitly zero, swap the endpoints if it's set.
+    if (indices[0] >= 8u) {
+        let swap_end = end0;
+        end0 = end1;
+        end1 = swap_end;
+        let swap_p = p0;
+        p0 = p1;
+        p1 = swap_p;
+        for (var i = 0u; i < 16u; i = i + 1u) {
+            indices[i] = 15u - indices[i];
+        }
+    }
+
+    // Mode 6: 7 mode bits, 7 bit RGBA endpoints, two p-bits, 4 bit indices.
+    put_bits(0u, 7u, 64u);
+    for (var c = 0u; c < 4u; c = c + 1u) {
+        put_bits(7u + 14u * c, 7u, end0[c]);
+        put_bits(14u + 14u * c, 7u, end1[c]);
+    }
+    put_bits(63u, 1u, p0);
+    put_bits(64u, 1u, p1);
+    put_bits(65u, 3u, indices[0]);
+    for (var i = 1u; i < 16u; i = i + 1u) {
+        put_bits(64u + 4u * i, 4u, indices[i]);
+    }
+    store_block(id.xy, 4u);
+}
@@ -0,0 +1,63 @@
+struct Params {
+    view: mat4x4<f32>,
+    // Tiles along x and y, depth slices and the number of lights.
+    dimensions: vec4<u32>,
+    viewport: vec2<f32>,
+    near: f32,
+    far: f32,
+    max_lights_per_cluster: u32,
+};
+
+// View space bounding box of a cluster, w is unused.
+struct Bounds {
+    min_point: vec4<f32>,
+    max_point: vec4<f32>,
+};
+
+struct Light {
+    position: vec3<f32>,
+    radius: f32,
+};
+
+struct Cluster {
+    offset: u32,
+    count: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read> bounds: array<Bounds>;
+@group(0) @binding(2)
+var<storage, read> lights: array<Light>;
+@group(0) @binding(3)
+var<storage, read_write> clusters: array<Cluster>;
+@group(0) @binding(4)
+var<storage, read_write> light_indices: array<u32>;
+
+// Collects the lights whose spheres intersect the bounding box of a cluster into its slots.
+@compute @workgroup_size(64)
+fn assign(@builtin(global_invocation_id) id: vec3<u32>) {
+    let cluster = id.x;
+    if (cluster >= params.dimensions.x * params.dimensions.y * params.dimensions.z) {
+        return;
+    }
+    let min_point = bounds[cluster].min_point.xyz;
+    let max_point = bounds[cluster].max_point.xyz;
+    let offset = cluster * params.max_lights_per_cluster;
+
+    var count = 0u;
+    for (var i = 0u; i < params.dimensions.w; i = i + 1u) {
+        let light = lights[i];
+        let center = (params.view * vec4<f32>(light.position, 1.0)).xyz;
+        let delta = clamp(center, min_point, max_point) - center;
+        if (dot(delta, delta) <= light.radius * light.radius) {
+            if (count >= params.max_lights_per_cluster) {
+                break;
+            }
+            light_indices[offset + count] = i;
+            count = count + 1u;
+        }
+    }
+    clusters[cluster] = Cluster(offset, count);
+}
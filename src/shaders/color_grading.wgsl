@@ -0,0 +1,39 @@
+#include "wgpu_util::color"
+
+struct Params {
+    strength: f32,
+    encode_srgb: u32,
+    lut_size: f32,
+    _padding: u32,
+    domain_min: vec4<f32>,
+    domain_max: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var input_sampler: sampler;
+@group(0) @binding(3)
+var lut: texture_3d<f32>;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(input_texture, input_sampler, in.uv);
+    // LUTs are authored for display encoded colors.
+    let encoded = srgb_from_linear(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)));
+
+    let domain = (encoded - params.domain_min.rgb) / (params.domain_max.rgb - params.domain_min.rgb);
+    // Map [0, 1] to the centers of the outermost texels.
+    let scale = (params.lut_size - 1.0) / params.lut_size;
+    let offset = 0.5 / params.lut_size;
+    let coords = clamp(domain, vec3<f32>(0.0), vec3<f32>(1.0)) * scale + offset;
+    let graded = textureSample(lut, input_sampler, coords).rgb;
+
+    var result = mix(encoded, graded, params.strength);
+    if (params.encode_srgb == 0u) {
+        result = linear_from_srgb(result);
+    }
+    return vec4<f32>(result, color.a);
+}
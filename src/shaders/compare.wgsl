@@ -0,0 +1,70 @@
+#include "wgpu_util::color"
+
+struct Params {
+    width: u32,
+    height: u32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var texture_a: texture_2d<f32>;
+@group(0) @binding(2)
+var texture_b: texture_2d<f32>;
+@group(0) @binding(3)
+var<storage, read_write> result: array<f32>;
+
+// Sum of squared RGB differences per texel.
+@compute @workgroup_size(8, 8)
+fn squared_error(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+    let pixel = vec2<i32>(id.xy);
+    let difference = textureLoad(texture_a, pixel, 0).rgb - textureLoad(texture_b, pixel, 0).rgb;
+    result[id.y * params.width + id.x] = dot(difference, difference);
+}
+
+// Structural similarity of the luminance per texel, over an 11x11 Gaussian window with a standard
+// deviation of 1.5 as proposed by Wang et al.
+@compute @workgroup_size(8, 8)
+fn ssim(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.width || id.y >= params.height) {
+        return;
+    }
+    let max_pixel = vec2<i32>(i32(params.width) - 1, i32(params.height) - 1);
+
+    var weight_sum = 0.0;
+    var mean_a = 0.0;
+    var mean_b = 0.0;
+    var moment_aa = 0.0;
+    var moment_bb = 0.0;
+    var moment_ab = 0.0;
+    for (var y = -5; y <= 5; y = y + 1) {
+        for (var x = -5; x <= 5; x = x + 1) {
+            let pixel = clamp(vec2<i32>(id.xy) + vec2<i32>(x, y), vec2<i32>(0), max_pixel);
+            let a = luminance(textureLoad(texture_a, pixel, 0).rgb);
+            let b = luminance(textureLoad(texture_b, pixel, 0).rgb);
+            let weight = exp(-f32(x * x + y * y) / 4.5);
+            weight_sum = weight_sum + weight;
+            mean_a = mean_a + weight * a;
+            mean_b = mean_b + weight * b;
+            moment_aa = moment_aa + weight * a * a;
+            moment_bb = moment_bb + weight * b * b;
+            moment_ab = moment_ab + weight * a * b;
+        }
+    }
+    mean_a = mean_a / weight_sum;
+    mean_b = mean_b / weight_sum;
+    let variance_a = moment_aa / weight_sum - mean_a * mean_a;
+    let variance_b = moment_bb / weight_sum - mean_b * mean_b;
+    let covariance = moment_ab / weight_sum - mean_a * mean_b;
+
+    // Stabilizing constants for a dynamic range of 1.
+    let c1 = 0.0001;
+    let c2 = 0.0009;
+    result[id.y * params.width + id.x] = (2.0 * mean_a * mean_b + c1) * (2.0 * covariance + c2)
+        / ((mean_a * mean_a + mean_b * mean_b + c1) * (variance_a + variance_b + c2));
+}
@@ -0,0 +1,33 @@
+struct Params {
+    near_plane: f32,
+    far_plane: f32,
+    focal_distance: f32,
+    aperture: f32,
+    max_coc_radius: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var color_texture: texture_2d<f32>;
+// Depth textures are bound as float, as loading from depth textures isn't supported everywhere.
+@group(0) @binding(2)
+var depth_texture: texture_2d<f32>;
+
+// Color with the signed circle of confusion radius in pixels in alpha, negative in front of the
+// focal plane.
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let color = textureLoad(color_texture, pixel, 0);
+    let depth = textureLoad(depth_texture, pixel, 0).x;
+
+    let near = params.near_plane;
+    let far = params.far_plane;
+    let distance = near * far / (far - depth * (far - near));
+    let coc = params.aperture * (1.0 - params.focal_distance / distance);
+    return vec4<f32>(color.rgb, clamp(coc, -params.max_coc_radius, params.max_coc_radius));
+}
@@ -0,0 +1,18 @@
+// Full resolution color with circle of confusion radius in alpha.
+@group(0) @binding(0)
+var coc_texture: texture_2d<f32>;
+// Half resolution blurred color with near field coverage in alpha.
+@group(0) @binding(1)
+var blurred_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var blurred_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let sharp = textureLoad(coc_texture, vec2<i32>(in.position.xy), 0);
+    let blurred = textureSampleLevel(blurred_texture, blurred_sampler, in.uv, 0.0);
+    // Fade in the blur over the first pixels of the circle of confusion, near field blur covers
+    // sharp pixels behind it.
+    let blend = max(clamp((abs(sharp.a) - 0.5) * 0.5, 0.0, 1.0), blurred.a);
+    return vec4<f32>(mix(sharp.rgb, blurred.rgb, blend), 1.0);
+}
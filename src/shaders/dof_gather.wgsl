@@ -0,0 +1,50 @@
+struct Params {
+    max_coc_radius: f32,
+    sample_count: u32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+// Half resolution color with circle of confusion radius in full resolution pixels in alpha.
+@group(0) @binding(1)
+var coc_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var coc_sampler: sampler;
+
+let GOLDEN_ANGLE: f32 = 2.39996323;
+
+// Blurred color with the coverage of the near field in alpha.
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let full_size = 2.0 * vec2<f32>(textureDimensions(coc_texture));
+    let center = textureSampleLevel(coc_texture, coc_sampler, in.uv, 0.0);
+    let center_far = max(center.a, 0.0);
+
+    var far_color = center.rgb;
+    var far_weight = 1.0;
+    var near_color = vec3<f32>(0.0);
+    var near_weight = 0.0;
+    for (var i = 0u; i < params.sample_count; i = i + 1u) {
+        // Points of a golden angle spiral cover the disk evenly.
+        let radius = sqrt((f32(i) + 0.5) / f32(params.sample_count)) * params.max_coc_radius;
+        let angle = f32(i) * GOLDEN_ANGLE;
+        let offset = vec2<f32>(cos(angle), sin(angle)) * radius;
+        let tap = textureSampleLevel(coc_texture, coc_sampler, in.uv + offset / full_size, 0.0);
+
+        // A sample contributes if its circle of confusion reaches the center. Far samples are
+        // limited by the center's own blur, so sharp foreground doesn't bleed into the background.
+        let far = clamp(min(max(tap.a, 0.0), center_far) - radius + 1.0, 0.0, 1.0);
+        let near = clamp(-tap.a - radius + 1.0, 0.0, 1.0);
+        far_color = far_color + tap.rgb * far;
+        far_weight = far_weight + far;
+        near_color = near_color + tap.rgb * near;
+        near_weight = near_weight + near;
+    }
+
+    let far_result = far_color / far_weight;
+    let coverage = clamp(2.0 * near_weight / f32(max(params.sample_count, 1u)), 0.0, 1.0);
+    let near_result = near_color / max(near_weight, 1e-4);
+    return vec4<f32>(mix(far_result, near_result, coverage), coverage);
+}
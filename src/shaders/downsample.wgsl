@@ -0,0 +1,16 @@
+@group(0) @binding(0)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var input_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    // Four bilinear taps one input texel off the center average a 4x4 tent, which avoids
+    // aliasing of a plain 2x2 box.
+    let texel = 1.0 / vec2<f32>(textureDimensions(input_texture));
+    var color = textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(-texel.x, -texel.y), 0.0);
+    color = color + textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(texel.x, -texel.y), 0.0);
+    color = color + textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(-texel.x, texel.y), 0.0);
+    color = color + textureSampleLevel(input_texture, input_sampler, in.uv + vec2<f32>(texel.x, texel.y), 0.0);
+    return color * 0.25;
+}
@@ -0,0 +1,43 @@
+#include "wgpu_util::color"
+
+struct Screen {
+    size_in_points: vec2<f32>,
+    _padding: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> screen: Screen;
+@group(0) @binding(1)
+var atlas: texture_2d<f32>;
+@group(0) @binding(2)
+var atlas_sampler: sampler;
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        2.0 * position.x / screen.size_in_points.x - 1.0,
+        1.0 - 2.0 * position.y / screen.size_in_points.y,
+        0.0,
+        1.0,
+    );
+    out.uv = uv;
+    // egui colors are premultiplied sRGB, blending happens in linear space.
+    out.color = vec4<f32>(linear_from_srgb(color.rgb), color.a);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color * textureSample(atlas, atlas_sampler, in.uv);
+}
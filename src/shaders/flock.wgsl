@@ -0,0 +1,82 @@
+struct Params {
+    count: u32,
+    dt: f32,
+    // Half extent of the cube boids wrap around in.
+    bounds: f32,
+    min_speed: f32,
+    max_speed: f32,
+    separation_radius: f32,
+    alignment_radius: f32,
+    cohesion_radius: f32,
+    separation_weight: f32,
+    alignment_weight: f32,
+    cohesion_weight: f32,
+    _padding: u32,
+};
+
+struct Boid {
+    position: vec4<f32>,
+    velocity: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read> boids: array<Boid>;
+@group(0) @binding(2)
+var<storage, read_write> next: array<Boid>;
+
+// Steers every boid away from close, along with nearby and towards the center of nearby boids.
+@compute @workgroup_size(64)
+fn update(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= params.count) {
+        return;
+    }
+    let position = boids[i].position.xyz;
+    let velocity = boids[i].velocity.xyz;
+
+    var separation = vec3<f32>(0.0);
+    var alignment = vec3<f32>(0.0);
+    var center = vec3<f32>(0.0);
+    var aligned = 0u;
+    var cohesive = 0u;
+    for (var j = 0u; j < params.count; j = j + 1u) {
+        if (j == i) {
+            continue;
+        }
+        let offset = boids[j].position.xyz - position;
+        let distance = length(offset);
+        if (distance < params.separation_radius && distance > 0.0) {
+            separation = separation - offset / (distance * distance);
+        }
+        if (distance < params.alignment_radius) {
+            alignment = alignment + boids[j].velocity.xyz;
+            aligned = aligned + 1u;
+        }
+        if (distance < params.cohesion_radius) {
+            center = center + boids[j].position.xyz;
+            cohesive = cohesive + 1u;
+        }
+    }
+
+    var new_velocity = velocity + separation * params.separation_weight * params.dt;
+    if (aligned > 0u) {
+        new_velocity = new_velocity
+            + (alignment / f32(aligned) - velocity) * params.alignment_weight * params.dt;
+    }
+    if (cohesive > 0u) {
+        new_velocity = new_velocity
+            + (center / f32(cohesive) - position) * params.cohesion_weight * params.dt;
+    }
+    let speed = length(new_velocity);
+    if (speed > 0.0) {
+        new_velocity = new_velocity * (clamp(speed, params.min_speed, params.max_speed) / speed);
+    }
+
+    var new_position = position + new_velocity * params.dt;
+    let size = 2.0 * params.bounds;
+    new_position = new_position - size * floor((new_position + params.bounds) / size);
+
+    next[i] = Boid(vec4<f32>(new_position, 1.0), vec4<f32>(new_velocity, 0.0));
+}
@@ -0,0 +1,73 @@
+#include "wgpu_util::fullscreen"
+#include "wgpu_util::camera"
+
+struct GridParams {
+    spacing: f32,
+    major_every: f32,
+    // Width of the lines in pixels.
+    line_width: f32,
+    fade_distance: f32,
+    minor_color: vec4<f32>,
+    major_color: vec4<f32>,
+    x_axis_color: vec4<f32>,
+    z_axis_color: vec4<f32>,
+};
+
+@group(0) @binding(1)
+var<uniform> grid: GridParams;
+
+struct GridOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let world = wgpu_util_camera.inverse_view_projection * vec4<f32>(ndc, 1.0);
+    return world.xyz / world.w;
+}
+
+// Anti-aliased coverage of the lines at multiples of `spacing` along both axes, faded out where
+// they get too dense to resolve.
+fn line_coverage(coordinate: vec2<f32>, spacing: f32) -> f32 {
+    let scaled = coordinate / spacing;
+    let derivative = fwidth(scaled);
+    let distance = abs(fract(scaled - 0.5) - 0.5) / derivative;
+    let coverage = clamp(grid.line_width * 0.5 + 0.5 - min(distance.x, distance.y), 0.0, 1.0);
+    return coverage * (1.0 - clamp(max(derivative.x, derivative.y) * 4.0 - 1.0, 0.0, 1.0));
+}
+
+fn axis_coverage(coordinate: f32) -> f32 {
+    return clamp(grid.line_width + 0.5 - abs(coordinate) / fwidth(coordinate), 0.0, 1.0);
+}
+
+// Intersects the view ray with the y = 0 plane.
+@fragment
+fn fs_main(in: FullscreenOutput) -> GridOutput {
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    // Points on the ray at depths which are finite for both regular and reversed depth.
+    let a = unproject(vec3<f32>(ndc, 0.25));
+    let b = unproject(vec3<f32>(ndc, 0.75));
+    let hit = a + (b - a) * (-a.y / (b.y - a.y));
+    let clip = wgpu_util_camera.view_projection * vec4<f32>(hit, 1.0);
+
+    let minor = line_coverage(hit.xz, grid.spacing);
+    let major = line_coverage(hit.xz, grid.spacing * grid.major_every);
+    let x_axis = axis_coverage(hit.z);
+    let z_axis = axis_coverage(hit.x);
+
+    var color = vec4<f32>(grid.minor_color.rgb, grid.minor_color.a * minor);
+    color = mix(color, grid.major_color, major);
+    color = mix(color, grid.x_axis_color, x_axis);
+    color = mix(color, grid.z_axis_color, z_axis);
+    color.a = color.a * (1.0 - smoothstep(0.0, grid.fade_distance, clip.w));
+
+    let depth = clip.z / clip.w;
+    // Behind the camera or beyond the clip planes.
+    if (clip.w <= 0.0 || depth < 0.0 || depth > 1.0 || color.a <= 0.0) {
+        discard;
+    }
+    var out: GridOutput;
+    out.color = color;
+    out.depth = depth;
+    return out;
+}
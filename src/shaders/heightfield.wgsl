@@ -0,0 +1,52 @@
+struct Params {
+    size: vec2<u32>,
+    dt: f32,
+    wave_speed: f32,
+    // Fraction of the velocity lost per second.
+    damping: f32,
+    splash_count: u32,
+    _padding0: u32,
+    _padding1: u32,
+    // Center in uv, radius in uv and height added at the center.
+    splashes: array<vec4<f32>, 16>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+// Height and vertical velocity.
+@group(0) @binding(1)
+var state: texture_2d<f32>;
+@group(0) @binding(2)
+var next: texture_storage_2d<rgba32float, write>;
+
+fn height(p: vec2<i32>) -> f32 {
+    let clamped = clamp(p, vec2<i32>(0), vec2<i32>(params.size) - 1);
+    return textureLoad(state, clamped, 0).x;
+}
+
+// Integrates the wave equation for one time step.
+@compute @workgroup_size(8, 8)
+fn step(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.size.x || id.y >= params.size.y) {
+        return;
+    }
+    let p = vec2<i32>(id.xy);
+    let current = textureLoad(state, p, 0).xy;
+
+    let neighbors = height(p + vec2<i32>(1, 0)) + height(p - vec2<i32>(1, 0))
+        + height(p + vec2<i32>(0, 1)) + height(p - vec2<i32>(0, 1));
+    let acceleration = params.wave_speed * params.wave_speed * (neighbors - 4.0 * current.x);
+    let velocity = (current.y + acceleration * params.dt) * max(1.0 - params.damping * params.dt, 0.0);
+    var height = current.x + velocity * params.dt;
+
+    let uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(params.size);
+    for (var i = 0u; i < params.splash_count; i = i + 1u) {
+        let splash = params.splashes[i];
+        let distance = distance(uv, splash.xy);
+        if (distance < splash.z) {
+            height = height + splash.w * 0.5 * (1.0 + cos(3.14159265 * distance / splash.z));
+        }
+    }
+
+    textureStore(next, p, vec4<f32>(height, velocity, 0.0, 0.0));
+}
@@ -0,0 +1,113 @@
+#include "wgpu_util::camera"
+#include "wgpu_util::prefix_sum"
+
+struct Params {
+    // Bounding sphere of the mesh in model space.
+    center: vec3<f32>,
+    radius: f32,
+    instance_count: u32,
+    // Workgroups along x of the dispatch, which is split into rows.
+    row_length: u32,
+    lod_count: u32,
+    // Instances fitting into the region of each level of detail in `visible_instances`.
+    capacity: u32,
+    // Smallest projected size of each level of detail, four per vector.
+    min_screen_sizes: array<vec4<f32>, 2>,
+};
+
+struct DrawIndexedArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+};
+
+@group(1) @binding(0)
+var<uniform> params: Params;
+@group(1) @binding(1)
+var<storage, read> instances: array<mat4x4<f32>>;
+@group(1) @binding(2)
+var<storage, read_write> visible_instances: array<mat4x4<f32>>;
+@group(1) @binding(3)
+var<storage, read_write> args: array<DrawIndexedArgs>;
+
+var<workgroup> workgroup_base: u32;
+
+fn view_projection_row(i: u32) -> vec4<f32> {
+    let m = wgpu_util_camera.view_projection;
+    return vec4<f32>(m[0][i], m[1][i], m[2][i], m[3][i]);
+}
+
+// Whether a world space sphere is at least partially inside the clip volume -w <= x, y <= w and
+// 0 <= z <= w. Degenerate planes, e.g. the far plane of infinite projections, are skipped.
+fn in_frustum(center: vec3<f32>, radius: f32) -> bool {
+    let x = view_projection_row(0u);
+    let y = view_projection_row(1u);
+    let z = view_projection_row(2u);
+    let w = view_projection_row(3u);
+    var planes = array<vec4<f32>, 6>(w + x, w - x, w + y, w - y, z, w - z);
+    for (var i = 0u; i < 6u; i = i + 1u) {
+        let plane = planes[i];
+        let normal_length = length(plane.xyz);
+        if (normal_length > 0.0 && dot(plane.xyz, center) + plane.w < -radius * normal_length) {
+            return false;
+        }
+    }
+    return true;
+}
+
+// Finest level of detail whose minimum projected size the sphere reaches, measured as its
+// diameter relative to the viewport height. Spheres around the camera use the finest level.
+fn select_lod(center: vec3<f32>, radius: f32) -> u32 {
+    let w = dot(view_projection_row(3u), vec4<f32>(center, 1.0));
+    if (w <= 0.0) {
+        return 0u;
+    }
+    let screen_size = radius * length(view_projection_row(1u).xyz) / w;
+    var lod = 0u;
+    loop {
+        let min_screen_size = params.min_screen_sizes[lod >> 2u][lod & 3u];
+        if (lod + 1u >= params.lod_count || screen_size >= min_screen_size) {
+            break;
+        }
+        lod = lod + 1u;
+    }
+    return lod;
+}
+
+// Copies the transforms of visible instances to the front of the region of their level of detail
+// in `visible_instances`, reserving the range of a workgroup with a single atomic per level on
+// the instance count of its draw.
+@compute @workgroup_size(256)
+fn cull(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let index = (workgroup_id.y * params.row_length + workgroup_id.x) * 256u + local_index;
+
+    var model: mat4x4<f32>;
+    var visible = false;
+    var instance_lod = 0u;
+    if (index < params.instance_count) {
+        model = instances[index];
+        let center = (model * vec4<f32>(params.center, 1.0)).xyz;
+        let scale = max(length(model[0].xyz), max(length(model[1].xyz), length(model[2].xyz)));
+        visible = in_frustum(center, params.radius * scale);
+        instance_lod = select_lod(center, params.radius * scale);
+    }
+
+    for (var lod = 0u; lod < params.lod_count; lod = lod + 1u) {
+        let selected = select(0u, 1u, visible && instance_lod == lod);
+        let offset = prefix_sum_workgroup(local_index, selected);
+        if (local_index == 255u) {
+            workgroup_base = atomicAdd(&args[lod].instance_count, offset);
+        }
+        workgroupBarrier();
+        if (selected == 1u) {
+            visible_instances[lod * params.capacity + workgroup_base + offset - 1u] = model;
+        }
+        // The next level overwrites `workgroup_base`.
+        workgroupBarrier();
+    }
+}
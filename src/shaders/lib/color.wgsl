@@ -0,0 +1,51 @@
+// Color space conversions. All colors are RGB unless stated otherwise.
+
+fn srgb_from_linear(rgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = rgb < vec3<f32>(0.0031308);
+    let lower = rgb * 12.92;
+    let higher = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(higher, lower, cutoff);
+}
+
+fn linear_from_srgb(srgb: vec3<f32>) -> vec3<f32> {
+    let cutoff = srgb < vec3<f32>(0.04045);
+    let lower = srgb / vec3<f32>(12.92);
+    let higher = pow((srgb + vec3<f32>(0.055)) / vec3<f32>(1.055), vec3<f32>(2.4));
+    return select(higher, lower, cutoff);
+}
+
+// Relative luminance of linear Rec. 709 primaries.
+fn luminance(rgb: vec3<f32>) -> f32 {
+    return dot(rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+// Hue in [0, 1), saturation and value.
+fn hsv_from_rgb(rgb: vec3<f32>) -> vec3<f32> {
+    let max_component = max(rgb.r, max(rgb.g, rgb.b));
+    let min_component = min(rgb.r, min(rgb.g, rgb.b));
+    let delta = max_component - min_component;
+
+    var hue = 0.0;
+    if (delta > 0.0) {
+        if (max_component == rgb.r) {
+            hue = (rgb.g - rgb.b) / delta;
+        } else if (max_component == rgb.g) {
+            hue = (rgb.b - rgb.r) / delta + 2.0;
+        } else {
+            hue = (rgb.r - rgb.g) / delta + 4.0;
+        }
+        hue = fract(hue / 6.0);
+    }
+    var saturation = 0.0;
+    if (max_component > 0.0) {
+        saturation = delta / max_component;
+    }
+    return vec3<f32>(hue, saturation, max_component);
+}
+
+fn rgb_from_hsv(hsv: vec3<f32>) -> vec3<f32> {
+    let k = vec3<f32>(5.0, 3.0, 1.0);
+    let p = (k + vec3<f32>(hsv.x * 6.0)) % vec3<f32>(6.0);
+    let weight = clamp(min(p, vec3<f32>(4.0) - p), vec3<f32>(0.0), vec3<f32>(1.0));
+    return hsv.z - hsv.z * hsv.y * weight;
+}
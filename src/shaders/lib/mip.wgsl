@@ -0,0 +1,39 @@
+// Estimating sampled mip levels from uv derivatives, for visualizing texture resolution.
+//
+// Derivatives are only defined in fragment shaders and uniform control flow.
+
+// Level selected without anisotropic filtering for `uv` on a texture of `texture_size` texels.
+fn mip_level(uv: vec2<f32>, texture_size: vec2<f32>) -> f32 {
+    let dx = dpdx(uv) * texture_size;
+    let dy = dpdy(uv) * texture_size;
+    return max(0.5 * log2(max(dot(dx, dx), dot(dy, dy))), 0.0);
+}
+
+// Level selected with up to `max_anisotropy` samples along the major axis of the footprint.
+fn mip_level_anisotropic(uv: vec2<f32>, texture_size: vec2<f32>, max_anisotropy: f32) -> f32 {
+    let dx = length(dpdx(uv) * texture_size);
+    let dy = length(dpdy(uv) * texture_size);
+    let major = max(dx, dy);
+    let minor = max(min(dx, dy), 1e-8);
+    let samples = min(ceil(major / minor), max_anisotropy);
+    return max(log2(major / samples), 0.0);
+}
+
+// Color of a mip level, interpolated between the colors of whole levels. Matches the levels of
+// `wgpu_util::texture::create_mip_debug_texture`.
+fn mip_level_color(level: f32) -> vec3<f32> {
+    var colors = array<vec3<f32>, 8>(
+        vec3<f32>(1.0, 0.0, 0.0),
+        vec3<f32>(1.0, 0.5, 0.0),
+        vec3<f32>(1.0, 1.0, 0.0),
+        vec3<f32>(0.0, 1.0, 0.0),
+        vec3<f32>(0.0, 1.0, 1.0),
+        vec3<f32>(0.0, 0.3, 1.0),
+        vec3<f32>(0.6, 0.0, 1.0),
+        vec3<f32>(0.5, 0.5, 0.5),
+    );
+    let clamped = clamp(level, 0.0, 7.0);
+    let lower = u32(floor(clamped));
+    let upper = min(lower + 1u, 7u);
+    return mix(colors[lower], colors[upper], fract(clamped));
+}
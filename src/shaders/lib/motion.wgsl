@@ -0,0 +1,13 @@
+// Screen-space motion vectors, as consumed by the temporal and motion blur passes.
+
+// UV of a clip space position, with y pointing down.
+fn uv_from_clip(clip: vec4<f32>) -> vec2<f32> {
+    let ndc = clip.xy / clip.w;
+    return vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+}
+
+// Motion from the previous to the current clip position in UV space. Interpolate both clip
+// positions from the vertex shader and call this in the fragment shader.
+fn motion_vector(current_clip: vec4<f32>, previous_clip: vec4<f32>) -> vec2<f32> {
+    return uv_from_clip(current_clip) - uv_from_clip(previous_clip);
+}
@@ -0,0 +1,21 @@
+// Coverage of multi-channel signed distance field glyphs.
+
+fn msdf_median(texel: vec3<f32>) -> f32 {
+    return max(min(texel.r, texel.g), min(max(texel.r, texel.g), texel.b));
+}
+
+// Distance range of the atlas in screen pixels, from the range in atlas texels, the atlas size
+// and `fwidth` of the atlas uv. The derivative is passed in, so the snippet can be included in
+// modules with vertex shaders on backends compiling every function for every stage.
+fn msdf_screen_px_range(px_range: f32, atlas_size: vec2<f32>, uv_width: vec2<f32>) -> f32 {
+    let unit_range = vec2<f32>(px_range) / atlas_size;
+    let screen_texel_size = vec2<f32>(1.0) / uv_width;
+    return max(0.5 * dot(unit_range, screen_texel_size), 1.0);
+}
+
+// Anti-aliased coverage of a sampled atlas texel, with `offset` in screen pixels growing the
+// glyph, e.g. for bold text or outlines.
+fn msdf_coverage(texel: vec3<f32>, screen_px_range: f32, offset: f32) -> f32 {
+    let signed_distance = screen_px_range * (msdf_median(texel) - 0.5) + offset;
+    return clamp(signed_distance + 0.5, 0.0, 1.0);
+}
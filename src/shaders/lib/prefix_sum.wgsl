@@ -0,0 +1,21 @@
+// Inclusive prefix sum over a workgroup of 256 invocations.
+//
+// Must be called by all invocations of a `@workgroup_size(256)` workgroup in uniform control
+// flow.
+
+var<workgroup> prefix_sum_scratch: array<u32, 256>;
+
+fn prefix_sum_workgroup(local_index: u32, value: u32) -> u32 {
+    prefix_sum_scratch[local_index] = value;
+    workgroupBarrier();
+    for (var offset = 1u; offset < 256u; offset = offset << 1u) {
+        var sum = prefix_sum_scratch[local_index];
+        if (local_index >= offset) {
+            sum = sum + prefix_sum_scratch[local_index - offset];
+        }
+        workgroupBarrier();
+        prefix_sum_scratch[local_index] = sum;
+        workgroupBarrier();
+    }
+    return prefix_sum_scratch[local_index];
+}
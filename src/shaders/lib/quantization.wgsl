@@ -0,0 +1,19 @@
+// Decoding vertices quantized by `wgpu_util::mesh::quantize_vertices`.
+
+// Position from its snorm16 attribute and the bounds it was quantized within.
+fn dequantize_position(
+    quantized: vec3<f32>,
+    bounds_min: vec3<f32>,
+    bounds_max: vec3<f32>,
+) -> vec3<f32> {
+    return mix(bounds_min, bounds_max, quantized * 0.5 + 0.5);
+}
+
+// Unit vector from its octahedral snorm16x2 attribute.
+fn decode_octahedral(encoded: vec2<f32>) -> vec3<f32> {
+    var n = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+    let t = max(-n.z, 0.0);
+    n.x = n.x + select(t, -t, n.x >= 0.0);
+    n.y = n.y + select(t, -t, n.y >= 0.0);
+    return normalize(n);
+}
@@ -0,0 +1,47 @@
+// PCG based hashing and random numbers.
+//
+// Keep a `var state: u32` per invocation, seeded with `pcg_hash`, and pass `&state` to the
+// `random_*` functions.
+
+fn pcg_hash(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn random_u32(state: ptr<function, u32>) -> u32 {
+    let value = pcg_hash(*state);
+    *state = value;
+    return value;
+}
+
+// Uniformly distributed in [0, 1).
+fn random_f32(state: ptr<function, u32>) -> f32 {
+    return f32(random_u32(state) >> 8u) / 16777216.0;
+}
+
+fn random_vec2f(state: ptr<function, u32>) -> vec2<f32> {
+    let x = random_f32(state);
+    return vec2<f32>(x, random_f32(state));
+}
+
+// Marsaglia's xorshift32. Cheaper than PCG but lower quality; `state` must not be 0.
+fn xorshift32(state: ptr<function, u32>) -> u32 {
+    var x = *state;
+    x = x ^ (x << 13u);
+    x = x ^ (x >> 17u);
+    x = x ^ (x << 5u);
+    *state = x;
+    return x;
+}
+
+// Seed for the invocation `id` (e.g. `global_invocation_id`) which differs every frame when
+// `frame_seed` does.
+fn random_seed(id: vec3<u32>, frame_seed: u32) -> u32 {
+    return pcg_hash(id.x ^ pcg_hash(id.y ^ pcg_hash(id.z ^ pcg_hash(frame_seed))));
+}
+
+// Seed for a pixel, see `random_seed`.
+fn random_seed_pixel(pixel: vec2<u32>, frame_seed: u32) -> u32 {
+    return random_seed(vec3<u32>(pixel, 0u), frame_seed);
+}
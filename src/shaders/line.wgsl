@@ -0,0 +1,150 @@
+#include "wgpu_util::camera"
+
+struct LineParams {
+    viewport: vec2<f32>,
+    // Longest miter relative to half the line width.
+    miter_limit: f32,
+};
+
+@group(0) @binding(1)
+var<uniform> line_params: LineParams;
+
+struct LineInstance {
+    @location(0) start: vec3<f32>,
+    @location(1) end: vec3<f32>,
+    @location(2) previous: vec3<f32>,
+    @location(3) next: vec3<f32>,
+    @location(4) color: vec4<f32>,
+    @location(5) width: f32,
+    // Modes of the start and end: 0 butt, 1 square, 2 round, 3 mitered join.
+    @location(6) modes: u32,
+};
+
+struct LineOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Position in pixels along and across the segment, relative to its start.
+    @location(1) local: vec2<f32>,
+    @location(2) @interpolate(flat) segment_length: f32,
+    @location(3) @interpolate(flat) half_width: f32,
+    @location(4) @interpolate(flat) modes: u32,
+};
+
+let MITER: u32 = 3u;
+
+// Moves `clip` along the segment towards `other` until it's in front of the camera.
+fn clip_near(clip: vec4<f32>, other: vec4<f32>) -> vec4<f32> {
+    let epsilon = 1e-5;
+    if (clip.w >= epsilon || other.w < epsilon) {
+        return clip;
+    }
+    return mix(clip, other, (epsilon - clip.w) / (other.w - clip.w));
+}
+
+fn to_screen(clip: vec4<f32>) -> vec2<f32> {
+    return clip.xy / clip.w * 0.5 * line_params.viewport;
+}
+
+fn direction(from_point: vec2<f32>, to_point: vec2<f32>) -> vec2<f32> {
+    let delta = to_point - from_point;
+    if (dot(delta, delta) < 1e-12) {
+        return vec2<f32>(1.0, 0.0);
+    }
+    return normalize(delta);
+}
+
+// Expands the segment of an instance to a quad in screen space, widened by a pixel for the
+// anti-aliased edge.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, instance: LineInstance) -> LineOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+    );
+    let corner = corners[index];
+    let at_end = corner.x > 0.5;
+    let side = corner.y;
+
+    let start_clip = camera_clip_position(instance.start);
+    let end_clip = camera_clip_position(instance.end);
+    let a = clip_near(start_clip, end_clip);
+    let b = clip_near(end_clip, start_clip);
+    let screen_a = to_screen(a);
+    let screen_b = to_screen(b);
+    let along = direction(screen_a, screen_b);
+    let normal = vec2<f32>(-along.y, along.x);
+
+    let half_width = instance.width * 0.5;
+    let extent = half_width + 1.0;
+    var mode = instance.modes & 3u;
+    var anchor = screen_a;
+    var cap_sign = -1.0;
+    if (at_end) {
+        mode = instance.modes >> 2u;
+        anchor = screen_b;
+        cap_sign = 1.0;
+    }
+
+    var offset = normal * extent * side;
+    if (mode == MITER) {
+        // Direction of the neighboring segment, pointing the same way as this one.
+        var neighbor = direction(to_screen(camera_clip_position(instance.previous)), screen_a);
+        if (at_end) {
+            neighbor = direction(screen_b, to_screen(camera_clip_position(instance.next)));
+        }
+        let miter = normalize(normal + vec2<f32>(-neighbor.y, neighbor.x));
+        let scale = 1.0 / max(dot(miter, normal), 1.0 / line_params.miter_limit);
+        offset = miter * extent * scale * side;
+    } else {
+        // Butt caps only need room for the anti-aliased edge.
+        var cap = 1.0;
+        if (mode != 0u) {
+            cap = extent;
+        }
+        offset = offset + along * cap * cap_sign;
+    }
+    let position = anchor + offset;
+
+    var depth = a.z / a.w;
+    if (at_end) {
+        depth = b.z / b.w;
+    }
+    var out: LineOutput;
+    out.position = vec4<f32>(position / (0.5 * line_params.viewport), depth, 1.0);
+    out.color = instance.color;
+    out.local = vec2<f32>(dot(position - screen_a, along), dot(position - screen_a, normal));
+    out.segment_length = distance(screen_a, screen_b);
+    out.half_width = half_width;
+    out.modes = instance.modes;
+    return out;
+}
+
+// Coverage from the signed distance to the outline in pixels.
+@fragment
+fn fs_main(in: LineOutput) -> @location(0) vec4<f32> {
+    let across = abs(in.local.y) - in.half_width;
+    // Signed distance beyond the nearer end of the segment.
+    let beyond = max(-in.local.x, in.local.x - in.segment_length);
+    var mode = in.modes >> 2u;
+    if (in.local.x < in.segment_length * 0.5) {
+        mode = in.modes & 3u;
+    }
+
+    var signed_distance = across;
+    if (mode == 0u) {
+        signed_distance = max(across, beyond);
+    } else if (mode == 1u) {
+        signed_distance = max(across, beyond - in.half_width);
+    } else if (mode == 2u && beyond > 0.0) {
+        signed_distance = length(vec2<f32>(beyond, in.local.y)) - in.half_width;
+    }
+    let coverage = clamp(0.5 - signed_distance, 0.0, 1.0);
+    if (coverage <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
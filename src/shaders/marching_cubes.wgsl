@@ -0,0 +1,153 @@
+#include "wgpu_util::prefix_sum"
+
+struct Params {
+    // Number of density samples along each axis.
+    dimensions: vec3<u32>,
+    iso_value: f32,
+    bounds_min: vec3<f32>,
+    max_vertices: u32,
+    bounds_max: vec3<f32>,
+    // Workgroups along x of the dispatch, which is split into rows.
+    row_length: u32,
+};
+
+struct Vertex {
+    position: vec4<f32>,
+    normal: vec4<f32>,
+};
+
+// Indirect draw arguments followed by the number of vertices required to fit all triangles.
+struct DrawArgs {
+    vertex_count: u32,
+    instance_count: u32,
+    first_vertex: u32,
+    first_instance: u32,
+    required_vertices: atomic<u32>,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+// Edges crossed by the triangles of each cube configuration as 4 bit indices, two words per
+// configuration, terminated by 15.
+@group(0) @binding(1)
+var<storage, read> triangle_table: array<u32>;
+@group(0) @binding(2)
+var<storage, read_write> vertices: array<Vertex>;
+@group(0) @binding(3)
+var<storage, read_write> args: DrawArgs;
+@group(0) @binding(4)
+{density_binding}
+
+var<workgroup> workgroup_base: u32;
+
+fn density(p: vec3<i32>) -> f32 {
+    let q = vec3<u32>(clamp(p, vec3<i32>(0), vec3<i32>(params.dimensions) - 1));
+    return {density_load};
+}
+
+fn gradient(p: vec3<i32>) -> vec3<f32> {
+    return vec3<f32>(
+        density(p + vec3<i32>(1, 0, 0)) - density(p - vec3<i32>(1, 0, 0)),
+        density(p + vec3<i32>(0, 1, 0)) - density(p - vec3<i32>(0, 1, 0)),
+        density(p + vec3<i32>(0, 0, 1)) - density(p - vec3<i32>(0, 0, 1)),
+    );
+}
+
+fn corner_offset(corner: u32) -> vec3<i32> {
+    return vec3<i32>(i32(corner & 1u), i32((corner >> 1u) & 1u), i32(corner >> 2u));
+}
+
+// Corners of an edge, edges 0 to 3 run along x, 4 to 7 along y and 8 to 11 along z.
+fn edge_corners(edge: u32) -> vec2<u32> {
+    let axis = edge >> 2u;
+    let index = edge & 3u;
+    var start = (index & 1u) << 1u | (index >> 1u) << 2u;
+    if (axis == 1u) {
+        start = (index & 1u) | (index >> 1u) << 2u;
+    } else if (axis == 2u) {
+        start = index;
+    }
+    return vec2<u32>(start, start | 1u << axis);
+}
+
+fn table_entry(configuration: u32, i: u32) -> u32 {
+    let word = triangle_table[configuration * 2u + (i >> 3u)];
+    return (word >> ((i & 7u) * 4u)) & 15u;
+}
+
+fn vertex_count(configuration: u32) -> u32 {
+    var count = 0u;
+    loop {
+        if (count == 15u || table_entry(configuration, count) == 15u) {
+            break;
+        }
+        count = count + 1u;
+    }
+    return count;
+}
+
+// Emits the triangles of the cells of a workgroup, compacted into the vertex buffer at a range
+// reserved with a single atomic per workgroup.
+@compute @workgroup_size(256)
+fn generate(
+    @builtin(workgroup_id) workgroup_id: vec3<u32>,
+    @builtin(local_invocation_index) local_index: u32,
+) {
+    let cells = params.dimensions - 1u;
+    let cell_index = (workgroup_id.y * params.row_length + workgroup_id.x) * 256u + local_index;
+    let in_grid = cell_index < cells.x * cells.y * cells.z;
+    let cell = vec3<i32>(vec3<u32>(
+        cell_index % cells.x,
+        cell_index / cells.x % cells.y,
+        cell_index / (cells.x * cells.y),
+    ));
+
+    var configuration = 0u;
+    var count = 0u;
+    if (in_grid) {
+        for (var corner = 0u; corner < 8u; corner = corner + 1u) {
+            if (density(cell + corner_offset(corner)) > params.iso_value) {
+                configuration = configuration | 1u << corner;
+            }
+        }
+        count = vertex_count(configuration);
+    }
+
+    let offset = prefix_sum_workgroup(local_index, count);
+    if (local_index == 255u) {
+        workgroup_base = atomicAdd(&args.required_vertices, offset);
+    }
+    workgroupBarrier();
+    let base = workgroup_base + offset - count;
+
+    let scale = (params.bounds_max - params.bounds_min) / vec3<f32>(params.dimensions - 1u);
+    for (var i = 0u; i < count; i = i + 1u) {
+        let index = base + i;
+        if (index >= params.max_vertices) {
+            break;
+        }
+        let corners = edge_corners(table_entry(configuration, i));
+        let p0 = cell + corner_offset(corners.x);
+        let p1 = cell + corner_offset(corners.y);
+        let d0 = density(p0);
+        let d1 = density(p1);
+        let t = clamp((params.iso_value - d0) / (d1 - d0), 0.0, 1.0);
+        let grid_position = mix(vec3<f32>(p0), vec3<f32>(p1), t);
+        // Density decreases outwards.
+        var normal = -mix(gradient(p0), gradient(p1), t) / scale;
+        if (dot(normal, normal) > 0.0) {
+            normal = normalize(normal);
+        }
+
+        var output: Vertex;
+        output.position = vec4<f32>(params.bounds_min + grid_position * scale, 1.0);
+        output.normal = vec4<f32>(normal, 0.0);
+        vertices[index] = output;
+    }
+}
+
+// Clamps the drawn vertices to the capacity of the vertex buffer.
+@compute @workgroup_size(1)
+fn finish() {
+    args.vertex_count = min(atomicLoad(&args.required_vertices), params.max_vertices);
+}
@@ -0,0 +1,34 @@
+struct Params {
+    sample_count: u32,
+    shutter_scale: f32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(2)
+var motion_vectors: texture_2d<f32>;
+@group(0) @binding(3)
+var input_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let center = textureSampleLevel(input_texture, input_sampler, in.uv, 0.0);
+    // Motion vectors point from the previous to the current position, the shutter stays open
+    // for `shutter_scale` of that motion, centered on the current frame.
+    let blur = textureLoad(motion_vectors, vec2<i32>(in.position.xy), 0).xy * params.shutter_scale;
+    if (params.sample_count < 2u) {
+        return center;
+    }
+
+    var color = vec4<f32>(0.0);
+    for (var i = 0u; i < params.sample_count; i = i + 1u) {
+        let t = f32(i) / f32(params.sample_count - 1u) - 0.5;
+        let uv = clamp(in.uv - blur * t, vec2<f32>(0.0), vec2<f32>(1.0));
+        color = color + textureSampleLevel(input_texture, input_sampler, uv, 0.0);
+    }
+    return color / f32(params.sample_count);
+}
@@ -0,0 +1,28 @@
+#include "wgpu_util::motion"
+
+// Layout of `CameraBuffer`.
+struct Camera {
+    view_projection: mat4x4<f32>,
+    previous_view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    jitter: vec2<f32>,
+    previous_jitter: vec2<f32>,
+};
+
+// Depth textures are bound as float, as loading from depth textures isn't supported everywhere.
+@group(0) @binding(0)
+var depth_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var<uniform> camera: Camera;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let depth = textureLoad(depth_texture, vec2<i32>(in.position.xy), 0).x;
+    let ndc = vec2<f32>(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let current_clip = vec4<f32>(ndc, depth, 1.0);
+
+    // Reprojection is linear in homogeneous coordinates, so the divide can be deferred.
+    let world = camera.inverse_view_projection * current_clip;
+    let previous_clip = camera.previous_view_projection * world;
+    return vec4<f32>(motion_vector(current_clip, previous_clip), 0.0, 0.0);
+}
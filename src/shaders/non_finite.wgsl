@@ -0,0 +1,59 @@
+struct Report {
+    nan_count: atomic<u32>,
+    inf_count: atomic<u32>,
+    first_nan: atomic<u32>,
+    first_inf: atomic<u32>,
+};
+
+struct Params {
+    len: u32,
+    row_length: u32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+@group(0) @binding(0)
+var<storage, read_write> report: Report;
+@group(0) @binding(1)
+var<uniform> params: Params;
+@group(0) @binding(2)
+var input_texture: texture_2d<f32>;
+@group(0) @binding(3)
+var<storage, read> input_values: array<u32>;
+
+// Counts `bits` if it encodes a NaN or infinity and keeps the smallest index of each.
+fn classify(bits: u32, index: u32) {
+    if ((bits & 0x7f800000u) != 0x7f800000u) {
+        return;
+    }
+    if ((bits & 0x007fffffu) != 0u) {
+        atomicAdd(&report.nan_count, 1u);
+        atomicMin(&report.first_nan, index);
+    } else {
+        atomicAdd(&report.inf_count, 1u);
+        atomicMin(&report.first_inf, index);
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn scan_texture(@builtin(global_invocation_id) id: vec3<u32>) {
+    let size = vec2<u32>(textureDimensions(input_texture));
+    if (id.x >= size.x || id.y >= size.y) {
+        return;
+    }
+    let texel = bitcast<vec4<u32>>(textureLoad(input_texture, vec2<i32>(id.xy), 0));
+    let index = id.y * size.x + id.x;
+    classify(texel.x, index);
+    classify(texel.y, index);
+    classify(texel.z, index);
+    classify(texel.w, index);
+}
+
+@compute @workgroup_size(64)
+fn scan_buffer(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.y * params.row_length + id.x;
+    if (index >= params.len) {
+        return;
+    }
+    classify(input_values[index], index);
+}
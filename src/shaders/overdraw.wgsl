@@ -0,0 +1,37 @@
+#include "wgpu_util::color"
+
+struct Params {
+    // Count mapped to the end of the ramp.
+    max_count: f32,
+    encode_srgb: u32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var counts: texture_2d<f32>;
+@group(0) @binding(2)
+var ramp: texture_2d<f32>;
+@group(0) @binding(3)
+var ramp_sampler: sampler;
+
+// Every fragment adds one with additive blending, whatever the vertex stage outputs.
+@fragment
+fn fs_overdraw() -> @location(0) vec4<f32> {
+    return vec4<f32>(1.0, 0.0, 0.0, 0.0);
+}
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let size = textureDimensions(counts);
+    let texel = min(vec2<i32>(in.uv * vec2<f32>(size)), size - 1);
+    let count = textureLoad(counts, texel, 0).r;
+    let t = clamp(count / params.max_count, 0.0, 1.0);
+    var color = textureSampleLevel(ramp, ramp_sampler, vec2<f32>(t, 0.5), 0.0).rgb;
+    if (params.encode_srgb != 0u) {
+        color = srgb_from_linear(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
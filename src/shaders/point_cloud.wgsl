@@ -0,0 +1,98 @@
+#include "wgpu_util::camera"
+
+struct PointCloudParams {
+    color: vec4<f32>,
+    attribute_range: vec2<f32>,
+    viewport: vec2<f32>,
+    size: f32,
+    attenuate: u32,
+    round: u32,
+};
+
+@group(0) @binding(1)
+var<uniform> params: PointCloudParams;
+@group(0) @binding(2)
+var gradient_texture: {gradient_texture};
+@group(0) @binding(3)
+var gradient_sampler: sampler;
+
+// Position in xyz, attribute in w.
+@group(1) @binding(0)
+var<storage, read> points: array<vec4<f32>>;
+
+struct PointOutput {
+    @builtin(position) position: vec4<f32>,
+    // Position within the billboard, from -1 to 1.
+    @location(0) corner: vec2<f32>,
+    @location(1) t: f32,
+};
+
+fn gradient_position(value: f32) -> f32 {
+    let range = params.attribute_range;
+    return clamp((value - range.x) / (range.y - range.x), 0.0, 1.0);
+}
+
+@vertex
+fn vs_billboard(
+    @builtin(vertex_index) index: u32,
+    @builtin(instance_index) instance: u32,
+) -> PointOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[index];
+    let data = points[instance];
+    let clip = camera_clip_position(data.xyz);
+
+    let radius = params.size * 0.5;
+    var offset = corner * radius / params.viewport * 2.0 * clip.w;
+    if (params.attenuate != 0u) {
+        // The rows of the view projection are scaled by the projection, so the offset shrinks
+        // with distance like a world space quad facing the camera.
+        let m = wgpu_util_camera.view_projection;
+        let scale = vec2<f32>(
+            length(vec3<f32>(m[0].x, m[1].x, m[2].x)),
+            length(vec3<f32>(m[0].y, m[1].y, m[2].y)),
+        );
+        offset = corner * radius * scale;
+    }
+
+    var out: PointOutput;
+    out.position = vec4<f32>(clip.xy + offset, clip.zw);
+    out.corner = corner;
+    out.t = gradient_position(data.w);
+    return out;
+}
+
+@vertex
+fn vs_point(@builtin(vertex_index) index: u32) -> PointOutput {
+    let data = points[index];
+    var out: PointOutput;
+    out.position = camera_clip_position(data.xyz);
+    out.corner = vec2<f32>(0.0);
+    out.t = gradient_position(data.w);
+    return out;
+}
+
+@fragment
+fn fs_color(in: PointOutput) -> @location(0) vec4<f32> {
+    if (params.round != 0u && dot(in.corner, in.corner) > 1.0) {
+        discard;
+    }
+    return params.color;
+}
+
+// Maps the attribute through the gradient, tinted by the color.
+@fragment
+fn fs_gradient(in: PointOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(gradient_texture, gradient_sampler, {gradient_coordinate});
+    if (params.round != 0u && dot(in.corner, in.corner) > 1.0) {
+        discard;
+    }
+    return color * params.color;
+}
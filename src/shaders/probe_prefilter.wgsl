@@ -0,0 +1,71 @@
+struct Params {
+    face: u32,
+    // GGX alpha widening the lobe the source level was filtered with to the one of the output.
+    alpha: f32,
+};
+
+@group(0) @binding(0)
+var source_texture: texture_cube<f32>;
+@group(0) @binding(1)
+var source_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> params: Params;
+
+let SAMPLE_COUNT: u32 = 64u;
+let PI: f32 = 3.14159265;
+
+// Direction through `uv` of a cube face, following the cube map conventions of WebGPU.
+fn face_direction(face: u32, uv: vec2<f32>) -> vec3<f32> {
+    let s = uv.x * 2.0 - 1.0;
+    let t = uv.y * 2.0 - 1.0;
+    var direction = vec3<f32>(s, -t, -1.0);
+    if (face == 0u) {
+        direction = vec3<f32>(1.0, -t, -s);
+    } else if (face == 1u) {
+        direction = vec3<f32>(-1.0, -t, s);
+    } else if (face == 2u) {
+        direction = vec3<f32>(s, 1.0, t);
+    } else if (face == 3u) {
+        direction = vec3<f32>(s, -1.0, -t);
+    } else if (face == 4u) {
+        direction = vec3<f32>(s, -t, 1.0);
+    } else {
+        direction = vec3<f32>(-s, -t, -1.0);
+    }
+    return normalize(direction);
+}
+
+fn hammersley(i: u32) -> vec2<f32> {
+    return vec2<f32>(f32(i) / f32(SAMPLE_COUNT), f32(reverseBits(i)) * 2.3283064e-10);
+}
+
+// Convolves the source with a GGX lobe around the direction of the texel, assuming the view and
+// normal directions match it.
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let n = face_direction(params.face, in.uv);
+    var up = vec3<f32>(1.0, 0.0, 0.0);
+    if (abs(n.z) < 0.999) {
+        up = vec3<f32>(0.0, 0.0, 1.0);
+    }
+    let tangent = normalize(cross(up, n));
+    let bitangent = cross(n, tangent);
+
+    let a2 = params.alpha * params.alpha;
+    var color = vec3<f32>(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < SAMPLE_COUNT; i = i + 1u) {
+        let xi = hammersley(i);
+        let phi = 2.0 * PI * xi.x;
+        let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a2 - 1.0) * xi.y));
+        let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+        let h = tangent * cos(phi) * sin_theta + bitangent * sin(phi) * sin_theta + n * cos_theta;
+        let l = 2.0 * dot(n, h) * h - n;
+        let n_dot_l = dot(n, l);
+        if (n_dot_l > 0.0) {
+            color = color + textureSampleLevel(source_texture, source_sampler, l, 0.0).rgb * n_dot_l;
+            weight = weight + n_dot_l;
+        }
+    }
+    return vec4<f32>(color / max(weight, 1e-4), 1.0);
+}
@@ -0,0 +1,62 @@
+struct Params {
+    len: u32,
+    // 0: sum, 1: min, 2: max
+    op: u32,
+    // Number of workgroups per row of the dispatch.
+    row_length: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read> values: array<f32>;
+@group(0) @binding(2)
+var<storage, read_write> reduced: array<f32>;
+
+var<workgroup> partial: array<f32, 256>;
+
+fn identity() -> f32 {
+    if (params.op == 1u) {
+        return 3.40282347e38;
+    } else if (params.op == 2u) {
+        return -3.40282347e38;
+    }
+    return 0.0;
+}
+
+fn combine(a: f32, b: f32) -> f32 {
+    if (params.op == 1u) {
+        return min(a, b);
+    } else if (params.op == 2u) {
+        return max(a, b);
+    }
+    return a + b;
+}
+
+// Reduces 256 values per workgroup into one.
+@compute @workgroup_size(256)
+fn reduce(
+    @builtin(local_invocation_index) local_index: u32,
+    @builtin(workgroup_id) group_id: vec3<u32>,
+) {
+    let group_index = group_id.y * params.row_length + group_id.x;
+    let index = group_index * 256u + local_index;
+    var value = identity();
+    if (index < params.len) {
+        value = values[index];
+    }
+    partial[local_index] = value;
+    workgroupBarrier();
+
+    for (var stride = 128u; stride > 0u; stride = stride >> 1u) {
+        if (local_index < stride) {
+            partial[local_index] = combine(partial[local_index], partial[local_index + stride]);
+        }
+        workgroupBarrier();
+    }
+
+    if (local_index == 0u && group_index * 256u < params.len) {
+        reduced[group_index] = partial[0];
+    }
+}
@@ -0,0 +1,45 @@
+struct Params {
+    depth_threshold: f32,
+    use_depth: u32,
+    _padding0: u32,
+    _padding1: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var previous_color: texture_2d<f32>;
+@group(0) @binding(2)
+var motion_vectors: texture_2d<f32>;
+// Depth textures are bound as float, as loading from depth textures isn't supported everywhere.
+@group(0) @binding(3)
+var current_depth: texture_2d<f32>;
+@group(0) @binding(4)
+var previous_depth: texture_2d<f32>;
+@group(0) @binding(5)
+var history_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    // Motion vectors point from the previous to the current position in UV space.
+    let motion = textureLoad(motion_vectors, pixel, 0).xy;
+    let previous_uv = in.uv - motion;
+
+    var valid = all(previous_uv >= vec2<f32>(0.0)) && all(previous_uv <= vec2<f32>(1.0));
+    let color = textureSampleLevel(previous_color, history_sampler, previous_uv, 0.0);
+
+    if (params.use_depth != 0u && valid) {
+        let depth = textureLoad(current_depth, pixel, 0).x;
+        let previous_size = vec2<i32>(textureDimensions(previous_depth));
+        let previous_pixel = clamp(
+            vec2<i32>(previous_uv * vec2<f32>(previous_size)),
+            vec2<i32>(0),
+            previous_size - vec2<i32>(1),
+        );
+        let history_depth = textureLoad(previous_depth, previous_pixel, 0).x;
+        valid = abs(history_depth - depth) <= params.depth_threshold * max(depth, 1e-6);
+    }
+
+    return vec4<f32>(color.rgb, select(0.0, 1.0, valid));
+}
@@ -0,0 +1,76 @@
+#include "wgpu_util::camera"
+
+struct SkyboxParams {
+    zenith_color: vec4<f32>,
+    horizon_color: vec4<f32>,
+    ground_color: vec4<f32>,
+    // Direction towards the sun, w is the cosine of its angular radius.
+    sun_direction: vec4<f32>,
+    sun_color: vec4<f32>,
+    intensity: f32,
+    lod: f32,
+    // Depth of the far plane, 1 or 0 with reversed depth.
+    depth: f32,
+};
+
+@group(0) @binding(1)
+var<uniform> skybox: SkyboxParams;
+@group(0) @binding(2)
+var skybox_texture: texture_cube<f32>;
+@group(0) @binding(3)
+var skybox_sampler: sampler;
+
+struct SkyboxOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// Fullscreen triangle on the far plane.
+@vertex
+fn vs_skybox(@builtin(vertex_index) index: u32) -> SkyboxOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    var out: SkyboxOutput;
+    out.position = vec4<f32>(ndc, skybox.depth, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let world = wgpu_util_camera.inverse_view_projection * vec4<f32>(ndc, 1.0);
+    return world.xyz / world.w;
+}
+
+// World direction of the view ray through `ndc`.
+fn view_direction(ndc: vec2<f32>) -> vec3<f32> {
+    // Points on the ray at depths which are finite for both regular and reversed depth.
+    let a = unproject(vec3<f32>(ndc, 0.25));
+    let b = unproject(vec3<f32>(ndc, 0.75));
+    return normalize(b - a);
+}
+
+@fragment
+fn fs_cubemap(in: SkyboxOutput) -> @location(0) vec4<f32> {
+    let direction = view_direction(in.ndc);
+    let color = textureSampleLevel(skybox_texture, skybox_sampler, direction, skybox.lod).rgb;
+    return vec4<f32>(color * skybox.intensity, 1.0);
+}
+
+// Blends from the horizon to the zenith above and to the ground below, with a sun disk.
+@fragment
+fn fs_procedural(in: SkyboxOutput) -> @location(0) vec4<f32> {
+    let direction = view_direction(in.ndc);
+    let height = direction.y;
+    var color = mix(skybox.horizon_color.rgb, skybox.zenith_color.rgb, sqrt(max(height, 0.0)));
+    if (height < 0.0) {
+        color = mix(skybox.horizon_color.rgb, skybox.ground_color.rgb, sqrt(-height));
+    }
+
+    let cos_angle = dot(direction, normalize(skybox.sun_direction.xyz));
+    let cos_radius = skybox.sun_direction.w;
+    // Soften the edge of the disk to avoid aliasing.
+    let edge = (1.0 - cos_radius) * 0.2;
+    let sun = smoothstep(cos_radius - edge, cos_radius + edge, cos_angle);
+    color = mix(color, skybox.sun_color.rgb, sun);
+    return vec4<f32>(color * skybox.intensity, 1.0);
+}
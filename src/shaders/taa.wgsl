@@ -0,0 +1,73 @@
+#include "wgpu_util::color"
+
+struct Params {
+    blend_factor: f32,
+    variance_gamma: f32,
+    // 0: none, 1: min/max, 2: variance
+    clamp_mode: u32,
+    // Bit 0: history is valid, bit 1: motion vectors are bound.
+    flags: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var current_color: texture_2d<f32>;
+@group(0) @binding(2)
+var history_color: texture_2d<f32>;
+@group(0) @binding(3)
+var motion_vectors: texture_2d<f32>;
+@group(0) @binding(4)
+var history_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(in.position.xy);
+    let size = vec2<i32>(textureDimensions(current_color));
+    let current = textureLoad(current_color, pixel, 0);
+    if ((params.flags & 1u) == 0u) {
+        return current;
+    }
+
+    var previous_uv = in.uv;
+    if ((params.flags & 2u) != 0u) {
+        previous_uv = in.uv - textureLoad(motion_vectors, pixel, 0).xy;
+    }
+    if (any(previous_uv < vec2<f32>(0.0)) || any(previous_uv > vec2<f32>(1.0))) {
+        return current;
+    }
+    var history = textureSampleLevel(history_color, history_sampler, previous_uv, 0.0).rgb;
+
+    // Statistics of the 3x3 neighborhood of the current frame.
+    var neighborhood_min = current.rgb;
+    var neighborhood_max = current.rgb;
+    var moment1 = vec3<f32>(0.0);
+    var moment2 = vec3<f32>(0.0);
+    for (var y = -1; y <= 1; y = y + 1) {
+        for (var x = -1; x <= 1; x = x + 1) {
+            let neighbor_pixel = clamp(pixel + vec2<i32>(x, y), vec2<i32>(0), size - vec2<i32>(1));
+            let neighbor = textureLoad(current_color, neighbor_pixel, 0).rgb;
+            neighborhood_min = min(neighborhood_min, neighbor);
+            neighborhood_max = max(neighborhood_max, neighbor);
+            moment1 = moment1 + neighbor;
+            moment2 = moment2 + neighbor * neighbor;
+        }
+    }
+
+    if (params.clamp_mode == 1u) {
+        history = clamp(history, neighborhood_min, neighborhood_max);
+    } else if (params.clamp_mode == 2u) {
+        let mean = moment1 / 9.0;
+        let deviation = sqrt(max(moment2 / 9.0 - mean * mean, vec3<f32>(0.0)));
+        let box_min = max(mean - params.variance_gamma * deviation, neighborhood_min);
+        let box_max = min(mean + params.variance_gamma * deviation, neighborhood_max);
+        history = clamp(history, box_min, box_max);
+    }
+
+    // Weight by inverse luminance to reduce flickering of bright samples.
+    let current_weight = params.blend_factor / (1.0 + luminance(current.rgb));
+    let history_weight = (1.0 - params.blend_factor) / (1.0 + luminance(history));
+    let color = (current.rgb * current_weight + history * history_weight)
+        / (current_weight + history_weight);
+    return vec4<f32>(color, current.a);
+}
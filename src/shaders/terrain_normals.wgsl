@@ -0,0 +1,49 @@
+struct Params {
+    // Size of the heightmap in samples.
+    width: u32,
+    depth: u32,
+    // First sample of the patch in the heightmap.
+    patch_origin: vec2<u32>,
+    // Size of the patch in vertices.
+    patch_size: vec2<u32>,
+    spacing: vec2<f32>,
+    height_scale: f32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var<storage, read> heights: array<f32>;
+// Vertices of 3 position and 3 normal floats.
+@group(0) @binding(2)
+var<storage, read_write> vertices: array<f32>;
+
+fn height(x: u32, z: u32) -> f32 {
+    return heights[z * params.width + x] * params.height_scale;
+}
+
+// Writes the normal of every vertex of the patch from central differences of the heightmap.
+@compute @workgroup_size(8, 8)
+fn compute_normals(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.patch_size.x || id.y >= params.patch_size.y) {
+        return;
+    }
+    let x = params.patch_origin.x + id.x;
+    let z = params.patch_origin.y + id.y;
+    let x0 = max(x, 1u) - 1u;
+    let x1 = min(x + 1u, params.width - 1u);
+    let z0 = max(z, 1u) - 1u;
+    let z1 = min(z + 1u, params.depth - 1u);
+
+    let dx = (height(x1, z) - height(x0, z)) / (f32(x1 - x0) * params.spacing.x);
+    let dz = (height(x, z1) - height(x, z0)) / (f32(z1 - z0) * params.spacing.y);
+    let normal = normalize(vec3<f32>(-dx, 1.0, -dz));
+
+    let base = (id.y * params.patch_size.x + id.x) * 6u + 3u;
+    vertices[base] = normal.x;
+    vertices[base + 1u] = normal.y;
+    vertices[base + 2u] = normal.z;
+}
@@ -0,0 +1,38 @@
+#include "wgpu_util::color"
+#include "wgpu_util::tonemap"
+
+struct Params {
+    exposure: f32,
+    tonemap_operator: u32,
+    encode_srgb: u32,
+    _padding: u32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: Params;
+@group(0) @binding(1)
+var input: texture_2d<f32>;
+@group(0) @binding(2)
+var input_sampler: sampler;
+
+@fragment
+fn fs_main(in: FullscreenOutput) -> @location(0) vec4<f32> {
+    let hdr = textureSample(input, input_sampler, in.uv);
+    var color = hdr.rgb * params.exposure;
+    switch params.tonemap_operator {
+        case 1u: {
+            color = tonemap_reinhard(color);
+        }
+        case 2u: {
+            color = tonemap_aces(color);
+        }
+        case 3u: {
+            color = tonemap_clamp(color);
+        }
+        default: {}
+    }
+    if (params.encode_srgb != 0u) {
+        color = srgb_from_linear(color);
+    }
+    return vec4<f32>(color, hdr.a);
+}
@@ -0,0 +1,111 @@
+#include "wgpu_util::camera"
+
+struct VolumeParams {
+    // Corners of the cropped box in world space.
+    box_min: vec3<f32>,
+    step_count: u32,
+    box_max: vec3<f32>,
+    density: f32,
+    // Corners of the whole volume in world space.
+    bounds_min: vec3<f32>,
+    value_min: f32,
+    bounds_max: vec3<f32>,
+    value_max: f32,
+    // Depth of the near plane, 0 or 1 with reversed depth.
+    near_depth: f32,
+};
+
+@group(0) @binding(1)
+var<uniform> volume: VolumeParams;
+@group(0) @binding(2)
+var volume_texture: texture_3d<f32>;
+@group(0) @binding(3)
+var volume_sampler: sampler;
+@group(0) @binding(4)
+var transfer_texture: {transfer_texture};
+
+struct VolumeOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+struct VolumeFragment {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+@vertex
+fn vs_volume(@builtin(vertex_index) index: u32) -> VolumeOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    var out: VolumeOutput;
+    out.position = vec4<f32>(ndc, 0.5, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+fn unproject(ndc: vec3<f32>) -> vec3<f32> {
+    let world = wgpu_util_camera.inverse_view_projection * vec4<f32>(ndc, 1.0);
+    return world.xyz / world.w;
+}
+
+fn transfer_texel(i: i32) -> vec4<f32> {
+    return {transfer_load};
+}
+
+// Color and opacity of a volume value. The transfer function is interpolated manually, implicit
+// derivatives aren't available in the non-uniform ray loop.
+fn transfer(value: f32) -> vec4<f32> {
+    let t = clamp((value - volume.value_min) / (volume.value_max - volume.value_min), 0.0, 1.0);
+    let size = {transfer_size};
+    let x = t * f32(size) - 0.5;
+    let i = i32(floor(x));
+    let a = transfer_texel(clamp(i, 0, size - 1));
+    let b = transfer_texel(clamp(i + 1, 0, size - 1));
+    return mix(a, b, x - floor(x));
+}
+
+// Marches the view ray through the box from front to back, compositing the transfer function of
+// the first channel of the volume.
+@fragment
+fn fs_volume(in: VolumeOutput) -> VolumeFragment {
+    let origin = unproject(vec3<f32>(in.ndc, volume.near_depth));
+    let direction = normalize(unproject(vec3<f32>(in.ndc, 0.5)) - origin);
+
+    // Slab test, infinite reciprocals of axis aligned directions work out.
+    let inverse_direction = 1.0 / direction;
+    let a = (volume.box_min - origin) * inverse_direction;
+    let b = (volume.box_max - origin) * inverse_direction;
+    let near = min(a, b);
+    let far = max(a, b);
+    let t_enter = max(max(max(near.x, near.y), near.z), 0.0);
+    let t_exit = min(min(far.x, far.y), far.z);
+    if (t_exit <= t_enter) {
+        discard;
+    }
+
+    // Steps are spaced for the diagonal of the whole volume, so opacity only depends on the
+    // length of the ray through it, and density is the optical depth along the diagonal.
+    let extent = volume.bounds_max - volume.bounds_min;
+    let step_length = length(extent) / f32(volume.step_count);
+    let steps = min(u32(ceil((t_exit - t_enter) / step_length)), volume.step_count);
+    var color = vec4<f32>(0.0);
+    for (var i = 0u; i < steps; i = i + 1u) {
+        let t = t_enter + (f32(i) + 0.5) * step_length;
+        let position = origin + direction * min(t, t_exit);
+        let uvw = (position - volume.bounds_min) / extent;
+        let value = textureSampleLevel(volume_texture, volume_sampler, uvw, 0.0).r;
+        let mapped = transfer(value);
+        let alpha = 1.0 - exp(-mapped.a * volume.density / f32(volume.step_count));
+        color = color + (1.0 - color.a) * vec4<f32>(mapped.rgb * alpha, alpha);
+        if (color.a > 0.99) {
+            break;
+        }
+    }
+
+    let entry = camera_clip_position(origin + direction * t_enter);
+    var out: VolumeFragment;
+    out.color = color;
+    out.depth = clamp(entry.z / entry.w, 0.0, 1.0);
+    return out;
+}
@@ -0,0 +1,409 @@
+//! Simplifying meshes into levels of detail by collapsing edges with quadric error metrics.
+//!
+//! Simplification only rewrites indices, vertices are collapsed onto their neighbors and the
+//! vertex buffer is shared by all levels. Vertices on open borders are never moved, so meshes
+//! split into patches stay watertight.
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use crate::mesh::Aabb;
+
+/// A level of detail produced by [`generate_lods`].
+#[derive(Clone, Debug)]
+pub struct Lod {
+    pub indices: Vec<u32>,
+    /// Bounds of the vertices referenced by `indices`.
+    pub bounds: Aabb,
+    /// Approximate largest distance of the simplified surface from the original one.
+    pub error: f32,
+}
+
+/// Generates a level of detail for every ratio of the original triangle count, e.g.
+/// `[1.0, 0.5, 0.25]`.
+///
+/// Every level is simplified from the previous one, so ratios should be decreasing. Levels may
+/// keep more triangles than targeted if further collapses would distort the mesh.
+pub fn generate_lods(positions: &[[f32; 3]], indices: &[u32], ratios: &[f32]) -> Vec<Lod> {
+    let triangle_count = indices.len() / 3;
+    let mut current = indices.to_vec();
+    let mut error = 0.0f32;
+
+    ratios
+        .iter()
+        .map(|ratio| {
+            let target = (triangle_count as f32 * ratio.clamp(0.0, 1.0)).round() as usize;
+            if target < current.len() / 3 {
+                let (simplified, lod_error) = simplify(positions, &current, target);
+                current = simplified;
+                error = error.max(lod_error);
+            }
+            let bounds = current.iter().fold(Aabb::EMPTY, |aabb, &v| {
+                aabb.union_point(positions[v as usize])
+            });
+            Lod {
+                indices: current.clone(),
+                bounds,
+                error,
+            }
+        })
+        .collect()
+}
+
+/// Collapses edges of the triangle list `indices` until at most `target_triangles` remain or no
+/// collapse is possible without flipping triangles. Returns the new indices and the
+/// approximate error.
+pub fn simplify(
+    positions: &[[f32; 3]],
+    indices: &[u32],
+    target_triangles: usize,
+) -> (Vec<u32>, f32) {
+    assert!(
+        indices.len().is_multiple_of(3),
+        "indices must be a triangle list"
+    );
+
+    let mut triangles: Vec<[u32; 3]> = indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|[a, b, c]| a != b && b != c && c != a)
+        .collect();
+    let mut alive = vec![true; triangles.len()];
+    let mut alive_count = triangles.len();
+
+    let mut adjacency = vec![Vec::new(); positions.len()];
+    for (t, triangle) in triangles.iter().enumerate() {
+        for &v in triangle {
+            adjacency[v as usize].push(t as u32);
+        }
+    }
+
+    let mut quadrics = vec![Quadric::default(); positions.len()];
+    for triangle in &triangles {
+        let quadric = Quadric::from_triangle(triangle.map(|v| positions[v as usize]));
+        for &v in triangle {
+            quadrics[v as usize].add(&quadric);
+        }
+    }
+
+    let locked = border_vertices(&triangles, positions.len());
+    let mut version = vec![0u32; positions.len()];
+    let mut heap = BinaryHeap::new();
+    for triangle in &triangles {
+        for i in 0..3 {
+            let (a, b) = (triangle[i], triangle[(i + 1) % 3]);
+            if a < b {
+                push_edge(&mut heap, a, b, positions, &quadrics, &locked, &version);
+            }
+        }
+    }
+
+    let mut error = 0.0f64;
+    while alive_count > target_triangles {
+        let Some(collapse) = heap.pop() else {
+            break;
+        };
+        let (from, to) = (collapse.from as usize, collapse.to as usize);
+        if collapse.versions != (version[from], version[to]) {
+            continue;
+        }
+        if flips(&triangles, &alive, &adjacency[from], from, to, positions) {
+            continue;
+        }
+
+        for &t in &std::mem::take(&mut adjacency[from]) {
+            let t = t as usize;
+            if !alive[t] {
+                continue;
+            }
+            if triangles[t].contains(&(to as u32)) {
+                alive[t] = false;
+                alive_count -= 1;
+            } else {
+                for v in &mut triangles[t] {
+                    if *v as usize == from {
+                        *v = to as u32;
+                    }
+                }
+                adjacency[to].push(t as u32);
+            }
+        }
+        adjacency[to].retain(|&t| alive[t as usize]);
+
+        let quadric = quadrics[from];
+        quadrics[to].add(&quadric);
+        error = error.max(collapse.cost);
+        version[from] += 1;
+        version[to] += 1;
+
+        let mut neighbors: Vec<u32> = adjacency[to]
+            .iter()
+            .flat_map(|&t| triangles[t as usize])
+            .filter(|&v| v as usize != to)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        for neighbor in neighbors {
+            version[neighbor as usize] += 1;
+            push_edge(
+                &mut heap, to as u32, neighbor, positions, &quadrics, &locked, &version,
+            );
+            // Refresh the neighbor's other edges, which were invalidated by its new version.
+            let others: Vec<u32> = adjacency[neighbor as usize]
+                .iter()
+                .flat_map(|&t| triangles[t as usize])
+                .filter(|&v| v != neighbor && v as usize != to)
+                .collect();
+            for other in others {
+                push_edge(
+                    &mut heap, neighbor, other, positions, &quadrics, &locked, &version,
+                );
+            }
+        }
+    }
+
+    let indices = triangles
+        .iter()
+        .zip(&alive)
+        .filter(|(_, alive)| **alive)
+        .flat_map(|(triangle, _)| *triangle)
+        .collect();
+    (indices, error.max(0.0).sqrt() as f32)
+}
+
+/// Vertices on edges with a single triangle.
+fn border_vertices(triangles: &[[u32; 3]], vertex_count: usize) -> Vec<bool> {
+    let mut edges: Vec<(u32, u32)> = triangles
+        .iter()
+        .flat_map(|t| [0, 1, 2].map(|i| (t[i].min(t[(i + 1) % 3]), t[i].max(t[(i + 1) % 3]))))
+        .collect();
+    edges.sort_unstable();
+
+    let mut locked = vec![false; vertex_count];
+    let mut i = 0;
+    while i < edges.len() {
+        let count = edges[i..].iter().take_while(|e| **e == edges[i]).count();
+        if count == 1 {
+            locked[edges[i].0 as usize] = true;
+            locked[edges[i].1 as usize] = true;
+        }
+        i += count;
+    }
+    locked
+}
+
+/// Whether collapsing `from` onto `to` flips or degenerates a remaining triangle of `from`.
+fn flips(
+    triangles: &[[u32; 3]],
+    alive: &[bool],
+    adjacent: &[u32],
+    from: usize,
+    to: usize,
+    positions: &[[f32; 3]],
+) -> bool {
+    adjacent.iter().any(|&t| {
+        let t = t as usize;
+        if !alive[t] || triangles[t].contains(&(to as u32)) {
+            return false;
+        }
+        let before = triangles[t].map(|v| positions[v as usize]);
+        let after = triangles[t].map(|v| match v as usize == from {
+            true => positions[to],
+            false => positions[v as usize],
+        });
+        let (n0, n1) = (normal(before), normal(after));
+        let dot: f64 = (0..3).map(|i| n0[i] * n1[i]).sum();
+        let length = |n: [f64; 3]| (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        dot <= 0.2 * length(n0) * length(n1)
+    })
+}
+
+fn normal(triangle: [[f32; 3]; 3]) -> [f64; 3] {
+    let [a, b, c] = triangle.map(|p| p.map(f64::from));
+    let e1 = [b[0] - a[0], b[1] - a[1], b[2] - a[2]];
+    let e2 = [c[0] - a[0], c[1] - a[1], c[2] - a[2]];
+    [
+        e1[1] * e2[2] - e1[2] * e2[1],
+        e1[2] * e2[0] - e1[0] * e2[2],
+        e1[0] * e2[1] - e1[1] * e2[0],
+    ]
+}
+
+/// Queues the cheaper direction of collapsing the edge between `a` and `b`.
+fn push_edge(
+    heap: &mut BinaryHeap<Collapse>,
+    a: u32,
+    b: u32,
+    positions: &[[f32; 3]],
+    quadrics: &[Quadric],
+    locked: &[bool],
+    version: &[u32],
+) {
+    let mut quadric = quadrics[a as usize];
+    quadric.add(&quadrics[b as usize]);
+
+    let candidates = [(a, b), (b, a)]
+        .into_iter()
+        .filter(|(from, _)| !locked[*from as usize])
+        .map(|(from, to)| (quadric.error(positions[to as usize]), from, to));
+    if let Some((cost, from, to)) = candidates.min_by(|x, y| x.0.total_cmp(&y.0)) {
+        heap.push(Collapse {
+            cost,
+            from,
+            to,
+            versions: (version[from as usize], version[to as usize]),
+        });
+    }
+}
+
+/// An edge collapse, ordered by ascending cost in a [`BinaryHeap`].
+#[derive(Debug)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    /// Versions of the vertices when queued, collapses of changed vertices are stale.
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Symmetric 4x4 matrix summing squared distances to planes, stored as its upper triangle.
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Quadric of the triangle's plane, weighted by its area.
+    fn from_triangle(triangle: [[f32; 3]; 3]) -> Self {
+        let n = normal(triangle);
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if length == 0.0 {
+            return Self::default();
+        }
+        let [a, b, c] = n.map(|c| c / length);
+        let p = triangle[0].map(f64::from);
+        let d = -(a * p[0] + b * p[1] + c * p[2]);
+        let area = length * 0.5;
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|q| q * area),
+        )
+    }
+
+    fn add(&mut self, other: &Self) {
+        for (q, o) in self.0.iter_mut().zip(other.0) {
+            *q += o;
+        }
+    }
+
+    fn error(&self, position: [f32; 3]) -> f64 {
+        let [x, y, z] = position.map(f64::from);
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Slightly bumpy grid of `n` x `n` quads, an open mesh with a square border.
+    fn grid(n: u32) -> (Vec<[f32; 3]>, Vec<u32>) {
+        let positions = (0..=n)
+            .flat_map(|y| {
+                (0..=n).map(move |x| {
+                    let (x, y) = (x as f32, y as f32);
+                    [x, y, 0.1 * (x * 0.7).sin() * (y * 0.5).cos()]
+                })
+            })
+            .collect();
+        let indices = (0..n)
+            .flat_map(|y| (0..n).map(move |x| y * (n + 1) + x))
+            .flat_map(|i| [i, i + 1, i + n + 2, i, i + n + 2, i + n + 1])
+            .collect();
+        (positions, indices)
+    }
+
+    /// Undirected edges used by exactly one triangle.
+    fn border_edges(indices: &[u32]) -> Vec<(u32, u32)> {
+        let mut counts = HashMap::new();
+        for t in indices.chunks_exact(3) {
+            for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+                *counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        let mut edges: Vec<_> = counts
+            .into_iter()
+            .filter(|&(_, count)| count == 1)
+            .map(|(edge, _)| edge)
+            .collect();
+        edges.sort_unstable();
+        edges
+    }
+
+    #[test]
+    fn border_vertices_never_move() {
+        let (positions, indices) = grid(16);
+        let (simplified, _) = simplify(&positions, &indices, 32);
+        assert!(simplified.len() < indices.len() / 4);
+        // Vertices are only ever collapsed onto others, so a moved border vertex would break up
+        // the border edges.
+        assert_eq!(border_edges(&simplified), border_edges(&indices));
+    }
+
+    #[test]
+    fn lods_shrink_with_growing_error() {
+        let (positions, indices) = grid(16);
+        let lods = generate_lods(&positions, &indices, &[1.0, 0.5, 0.25, 0.1]);
+        assert_eq!(lods[0].indices, indices);
+        assert_eq!(lods[0].error, 0.0);
+        for pair in lods.windows(2) {
+            assert!(pair[1].indices.len() < pair[0].indices.len());
+            assert!(pair[1].error >= pair[0].error);
+        }
+        for lod in &lods {
+            assert_eq!(border_edges(&lod.indices), border_edges(&indices));
+            assert_eq!(lod.bounds.min[0..2], [0.0, 0.0]);
+            assert_eq!(lod.bounds.max[0..2], [16.0, 16.0]);
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Drawing a cube map or a procedural sky as background.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{CameraBuffer, CAMERA_SNIPPET},
+    pipeline::RenderPipelineBuilder,
+    shader::ShaderComposer,
+    BufferInitDescriptor, DeviceExt,
+};
+
+const PARAMS_SIZE: wgpu::BufferAddress = 96;
+
+/// What [`Skybox`] draws.
+#[derive(Clone, Copy, Debug)]
+pub enum SkyboxSource<'a> {
+    /// A cube view of a cube map, e.g. [`Probe::view`](crate::probe::Probe::view).
+    Cubemap(&'a wgpu::TextureView),
+    /// A gradient from the ground over the horizon to the zenith with a sun disk, see
+    /// [`SkyboxParams`].
+    Procedural,
+}
+
+/// Appearance of a [`Skybox`]. Colors are linear.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SkyboxParams {
+    /// Multiplier of the color, e.g. to match the exposure of the scene.
+    pub intensity: f32,
+    /// Mip level the cube map is sampled at, higher levels blur the background.
+    pub lod: f32,
+    pub zenith_color: [f32; 3],
+    pub horizon_color: [f32; 3],
+    pub ground_color: [f32; 3],
+    /// Direction towards the sun, doesn't need to be normalized.
+    pub sun_direction: [f32; 3],
+    pub sun_color: [f32; 3],
+    /// Angular radius of the sun disk in radians.
+    pub sun_radius: f32,
+}
+
+impl Default for SkyboxParams {
+    fn default() -> Self {
+        Self {
+            intensity: 1.0,
+            lod: 0.0,
+            zenith_color: [0.15, 0.35, 0.75],
+            horizon_color: [0.7, 0.8, 0.9],
+            ground_color: [0.25, 0.22, 0.2],
+            sun_direction: [0.3, 0.6, 0.4],
+            sun_color: [20.0, 18.0, 15.0],
+            sun_radius: 0.01,
+        }
+    }
+}
+
+impl SkyboxParams {
+    fn to_bytes(self, depth: f32) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(PARAMS_SIZE as usize);
+        for (color, w) in [
+            (self.zenith_color, 0.0),
+            (self.horizon_color, 0.0),
+            (self.ground_color, 0.0),
+            (self.sun_direction, self.sun_radius.cos()),
+            (self.sun_color, 0.0),
+        ] {
+            for value in color.into_iter().chain([w]) {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        for value in [self.intensity, self.lod, depth] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.resize(PARAMS_SIZE as usize, 0);
+        bytes
+    }
+}
+
+/// Descriptor for [`Skybox::new`].
+#[derive(Clone, Debug)]
+pub struct SkyboxDescriptor<'a> {
+    pub source: SkyboxSource<'a>,
+    pub format: wgpu::TextureFormat,
+    /// Format of the depth attachment of the pass, if any.
+    pub depth_format: Option<wgpu::TextureFormat>,
+    /// Whether the camera uses reversed depth, with depth cleared to 0 and the far plane at 0.
+    pub reversed_depth: bool,
+    pub sample_count: u32,
+    pub params: SkyboxParams,
+}
+
+impl Default for SkyboxDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            source: SkyboxSource::Procedural,
+            format: wgpu::TextureFormat::Rgba16Float,
+            depth_format: Some(wgpu::TextureFormat::Depth32Float),
+            reversed_depth: false,
+            sample_count: 1,
+            params: SkyboxParams::default(),
+        }
+    }
+}
+
+/// Draws the background seen by the camera of a [`CameraBuffer`].
+///
+/// The skybox is a fullscreen triangle on the far plane, looking up the view ray of every pixel,
+/// so it works with any projection. It tests depth without writing it, so drawing it after
+/// opaque geometry only shades uncovered pixels.
+#[derive(Debug)]
+pub struct Skybox {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    params: SkyboxParams,
+    depth: f32,
+}
+
+impl Skybox {
+    pub fn new(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        descriptor: &SkyboxDescriptor<'_>,
+    ) -> Self {
+        let mut composer = ShaderComposer::new();
+        composer.add_snippet(CAMERA_SNIPPET, crate::camera::camera_snippet(0, 0));
+        let shader = composer
+            .create_shader_module(
+                device,
+                Some("skybox shader"),
+                include_str!("shaders/skybox.wgsl"),
+            )
+            .expect("builtin snippets must compose");
+
+        let mut entries = vec![
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                ..camera.bind_group_layout_entry()
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                },
+                count: None,
+            },
+        ];
+        if let SkyboxSource::Cubemap(_) = descriptor.source {
+            entries.extend([
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ]);
+        }
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("skybox bind group layout"),
+            entries: &entries,
+        });
+
+        let depth = match descriptor.reversed_depth {
+            true => 0.0,
+            false => 1.0,
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("skybox params buffer"),
+            contents: &descriptor.params.to_bytes(depth),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("skybox sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let mut entries = vec![
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera.buffer().as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: params_buffer.as_entire_binding(),
+            },
+        ];
+        if let SkyboxSource::Cubemap(view) = descriptor.source {
+            entries.extend([
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+            ]);
+        }
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("skybox bind group"),
+            layout: &bind_group_layout,
+            entries: &entries,
+        });
+
+        // The triangle lies exactly on the far plane, which only passes against cleared depth
+        // with an inclusive comparison.
+        let depth_stencil = descriptor
+            .depth_format
+            .map(|format| wgpu::DepthStencilState {
+                depth_compare: match descriptor.reversed_depth {
+                    true => wgpu::CompareFunction::GreaterEqual,
+                    false => wgpu::CompareFunction::LessEqual,
+                },
+                ..crate::depth::read_only_less_equal(format)
+            });
+        let fragment_entry_point = match descriptor.source {
+            SkyboxSource::Cubemap(_) => "fs_cubemap",
+            SkyboxSource::Procedural => "fs_procedural",
+        };
+        let pipeline =
+            RenderPipelineBuilder::new(&shader, &[&bind_group_layout], descriptor.format)
+                .label("skybox pipeline")
+                .vertex_entry_point("vs_skybox")
+                .fragment_entry_point(Some(fragment_entry_point))
+                .depth_stencil(depth_stencil)
+                .sample_count(descriptor.sample_count)
+                .build(device);
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            params: descriptor.params,
+            depth,
+        }
+    }
+
+    /// Draws the skybox into `pass`, which must match the targets of the descriptor.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    pub fn params(&self) -> &SkyboxParams {
+        &self.params
+    }
+
+    /// Changes the appearance and uploads it using [`wgpu::Queue`].
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: SkyboxParams) {
+        self.params = params;
+        queue.write_buffer(&self.params_buffer, 0, &params.to_bytes(self.depth));
+    }
+}
@@ -0,0 +1,180 @@
+//! Storage buffers tracking which bytes were initialized.
+
+use std::ops::Range;
+
+use crate::binding::{BindableBuffer, BindingError, BufferBinding};
+
+/// Granularity of the tracking. Writes to buffers are aligned to 4 bytes anyway.
+const WORD_SIZE: wgpu::BufferAddress = 4;
+
+/// A storage buffer which records which bytes were ever written.
+///
+/// Buffers are zeroed on creation, so reading unwritten bytes doesn't fail, it silently yields
+/// zeros or data depending on the order of submissions. Bindings created with [`Self::binding`]
+/// check their range and, with the `debug` feature, log a warning if it exposes unwritten bytes.
+///
+/// Writes through [`Self::write`] are tracked automatically. Writes on the GPU, e.g. by compute
+/// shaders or copies, must be recorded with [`Self::mark_initialized`].
+#[derive(Debug)]
+pub struct TrackedStorageBuffer {
+    raw: wgpu::Buffer,
+
+    label: crate::OwnedLabel,
+    size: wgpu::BufferAddress,
+    usage: wgpu::BufferUsages,
+
+    /// One bit per word.
+    initialized: Vec<u64>,
+}
+
+impl TrackedStorageBuffer {
+    /// Creates a new buffer with no initialized bytes. `descriptor.usage` must contain
+    /// [`wgpu::BufferUsages::STORAGE`].
+    pub fn new(device: &wgpu::Device, descriptor: &wgpu::BufferDescriptor) -> Self {
+        assert!(
+            descriptor.usage.contains(wgpu::BufferUsages::STORAGE),
+            "usage must contain STORAGE"
+        );
+        let words = descriptor.size.div_ceil(WORD_SIZE) as usize;
+
+        Self {
+            raw: crate::resource_log::create_buffer(device, descriptor),
+
+            label: descriptor.label.map(|l| l.to_owned()),
+            size: descriptor.size,
+            usage: descriptor.usage,
+
+            initialized: vec![0; words.div_ceil(64)],
+        }
+    }
+
+    /// Creates a new buffer with `descriptor.contents` initialized.
+    pub fn new_init(device: &wgpu::Device, descriptor: &crate::BufferInitDescriptor) -> Self {
+        use crate::DeviceExt;
+
+        assert!(
+            descriptor.usage.contains(wgpu::BufferUsages::STORAGE),
+            "usage must contain STORAGE"
+        );
+        let size = descriptor
+            .size
+            .unwrap_or(descriptor.contents.len() as wgpu::BufferAddress);
+        let words = size.div_ceil(WORD_SIZE) as usize;
+
+        let mut buffer = Self {
+            raw: device.create_buffer_init(descriptor),
+
+            label: descriptor.label.map(|l| l.to_owned()),
+            size,
+            usage: descriptor.usage,
+
+            initialized: vec![0; words.div_ceil(64)],
+        };
+        buffer.mark_initialized(0..descriptor.contents.len() as wgpu::BufferAddress);
+        buffer
+    }
+
+    /// Uploads `data` at `offset` using [`wgpu::Queue`] and marks it initialized.
+    pub fn write(&mut self, queue: &wgpu::Queue, offset: wgpu::BufferAddress, data: &[u8]) {
+        queue.write_buffer(&self.raw, offset, data);
+        self.mark_initialized(offset..offset + data.len() as wgpu::BufferAddress);
+    }
+
+    /// Records that `range` was written on the GPU.
+    pub fn mark_initialized(&mut self, range: Range<wgpu::BufferAddress>) {
+        for word in self.words(range) {
+            self.initialized[word / 64] |= 1 << (word % 64);
+        }
+    }
+
+    /// Forgets all writes, e.g. when the contents become stale.
+    pub fn reset_initialized(&mut self) {
+        self.initialized.fill(0);
+    }
+
+    /// Whether all bytes of `range` were written.
+    pub fn is_initialized(&self, range: Range<wgpu::BufferAddress>) -> bool {
+        self.uninitialized_ranges(range).is_empty()
+    }
+
+    /// Unwritten ranges within `range`, rounded to 4 bytes.
+    pub fn uninitialized_ranges(
+        &self,
+        range: Range<wgpu::BufferAddress>,
+    ) -> Vec<Range<wgpu::BufferAddress>> {
+        let mut ranges: Vec<Range<wgpu::BufferAddress>> = Vec::new();
+        for word in self.words(range) {
+            if self.initialized[word / 64] & (1 << (word % 64)) != 0 {
+                continue;
+            }
+            let start = word as wgpu::BufferAddress * WORD_SIZE;
+            let end = (start + WORD_SIZE).min(self.size);
+            match ranges.last_mut() {
+                Some(last) if last.end == start => last.end = end,
+                _ => ranges.push(start..end),
+            }
+        }
+        ranges
+    }
+
+    /// Binds `size` bytes at `offset`, or the rest of the buffer if `size` is `None`.
+    ///
+    /// With the `debug` feature, logs a warning if the range contains unwritten bytes.
+    pub fn binding(
+        &self,
+        offset: wgpu::BufferAddress,
+        size: Option<wgpu::BufferAddress>,
+    ) -> Result<BufferBinding<'_>, BindingError> {
+        let binding = BufferBinding::new(self, offset, size)?;
+
+        #[cfg(feature = "debug")]
+        {
+            let uninitialized = self.uninitialized_ranges(offset..offset + binding.size().get());
+            if !uninitialized.is_empty() {
+                log::warn!(
+                    "binding of buffer {:?} exposes uninitialized ranges {:?}",
+                    self.label.as_deref().unwrap_or("<unlabeled>"),
+                    uninitialized
+                );
+            }
+        }
+
+        Ok(binding)
+    }
+
+    /// Get a reference to the raw buffer.
+    pub fn raw(&self) -> &wgpu::Buffer {
+        &self.raw
+    }
+
+    /// Size of the buffer in bytes.
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.size
+    }
+
+    /// Get the label.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Indices of the words overlapping `range`, clamped to the buffer.
+    fn words(&self, range: Range<wgpu::BufferAddress>) -> Range<usize> {
+        let end = range.end.min(self.size);
+        let start = range.start.min(end);
+        (start / WORD_SIZE) as usize..end.div_ceil(WORD_SIZE) as usize
+    }
+}
+
+impl BindableBuffer for TrackedStorageBuffer {
+    fn raw_buffer(&self) -> &wgpu::Buffer {
+        &self.raw
+    }
+
+    fn buffer_size(&self) -> wgpu::BufferAddress {
+        self.size
+    }
+
+    fn buffer_usage(&self) -> Option<wgpu::BufferUsages> {
+        Some(self.usage)
+    }
+}
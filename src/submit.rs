@@ -0,0 +1,136 @@
+//! Organizing independent compute and graphics work into separate submissions.
+
+/// Copy of a buffer written by compute work, read by graphics work.
+///
+/// Compute submissions copy their results into the snapshot at their end, see
+/// [`ParallelSubmitter::compute_with_snapshots`], so graphics reads consistent results while
+/// the next compute submission already writes the source buffer again.
+#[derive(Debug)]
+pub struct SnapshotBuffer {
+    buffer: wgpu::Buffer,
+    size: wgpu::BufferAddress,
+}
+
+impl SnapshotBuffer {
+    /// Creates a snapshot of `size` bytes. [`wgpu::BufferUsages::COPY_DST`] is added to `usage`.
+    pub fn new(
+        device: &wgpu::Device,
+        label: wgpu::Label,
+        size: wgpu::BufferAddress,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        let buffer = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label,
+                size,
+                usage: usage | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            },
+        );
+        Self { buffer, size }
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn size(&self) -> wgpu::BufferAddress {
+        self.size
+    }
+}
+
+/// Records compute and graphics work into separate command buffers and submits them
+/// interleaved, approximating async compute.
+///
+/// wgpu exposes a single queue, so submissions still execute in order. Splitting independent
+/// work keeps command buffers small, lets the driver start on compute work before graphics
+/// recording finished and avoids barriers between unrelated passes within a command buffer.
+/// Graphics should only read compute results through [`SnapshotBuffer`]s, so both can run a
+/// frame apart, e.g. drawing the boids of [`GpuFlock`](crate::flock::GpuFlock) of the previous
+/// update while the next one is computed.
+#[derive(Debug, Default)]
+pub struct ParallelSubmitter {
+    compute: Vec<wgpu::CommandBuffer>,
+    graphics: Vec<wgpu::CommandBuffer>,
+}
+
+impl ParallelSubmitter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records compute work into its own command buffer.
+    pub fn compute(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        record: impl FnOnce(&mut wgpu::CommandEncoder),
+    ) {
+        self.compute_with_snapshots(device, label, &[], record);
+    }
+
+    /// Records compute work into its own command buffer, followed by copies of the source
+    /// buffers into their snapshots.
+    ///
+    /// Sources need [`wgpu::BufferUsages::COPY_SRC`] and must be at least as large as their
+    /// snapshots.
+    pub fn compute_with_snapshots(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        snapshots: &[(&wgpu::Buffer, &SnapshotBuffer)],
+        record: impl FnOnce(&mut wgpu::CommandEncoder),
+    ) {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(label) });
+        record(&mut encoder);
+        for (source, snapshot) in snapshots {
+            #[cfg(feature = "debug")]
+            crate::timeline::record(|| {
+                crate::timeline::Operation::copy("snapshot copy")
+                    .read("source", *source)
+                    .write("snapshot", &snapshot.buffer)
+            });
+            encoder.copy_buffer_to_buffer(source, 0, &snapshot.buffer, 0, snapshot.size);
+        }
+        self.compute.push(encoder.finish());
+    }
+
+    /// Records graphics work into its own command buffer.
+    pub fn graphics(
+        &mut self,
+        device: &wgpu::Device,
+        label: &str,
+        record: impl FnOnce(&mut wgpu::CommandEncoder),
+    ) {
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some(label) });
+        record(&mut encoder);
+        self.graphics.push(encoder.finish());
+    }
+
+    /// Number of recorded compute and graphics command buffers.
+    pub fn pending(&self) -> (usize, usize) {
+        (self.compute.len(), self.graphics.len())
+    }
+
+    /// Submits the recorded command buffers, alternating between compute and graphics starting
+    /// with compute, each in its own submission. Returns the index of the last submission, or
+    /// `None` if nothing was recorded.
+    pub fn submit(&mut self, queue: &wgpu::Queue) -> Option<wgpu::SubmissionIndex> {
+        let mut compute = self.compute.drain(..);
+        let mut graphics = self.graphics.drain(..);
+        let mut last = None;
+        loop {
+            let (c, g) = (compute.next(), graphics.next());
+            if c.is_none() && g.is_none() {
+                break;
+            }
+            for buffer in [c, g].into_iter().flatten() {
+                last = Some(queue.submit(Some(buffer)));
+            }
+        }
+        last
+    }
+}
@@ -0,0 +1,250 @@
+//! Emulating subpasses with input attachments, e.g. a gbuffer pass followed by lighting.
+
+use crate::texture::TexturePool;
+
+/// Descriptor for [`Subpasses`].
+#[derive(Clone, Debug)]
+pub struct SubpassesDescriptor<'a> {
+    pub label: &'a str,
+    /// Formats of the color attachments written by the first pass and read by the second.
+    pub attachments: &'a [wgpu::TextureFormat],
+    /// Format of the depth attachment of the first pass, which is read by the second pass too.
+    pub depth_format: Option<wgpu::TextureFormat>,
+    /// Stages of the second pass reading the attachments.
+    pub visibility: wgpu::ShaderStages,
+}
+
+impl Default for SubpassesDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: "subpasses",
+            attachments: &[],
+            depth_format: None,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+        }
+    }
+}
+
+/// Two passes where the second reads the attachments of the first at the same texel, like
+/// subpasses with input attachments in Vulkan.
+///
+/// wgpu has no subpasses, so the attachments are transient textures of a [`TexturePool`] and the
+/// second pass reads them through a bind group created by [`Self::acquire`]. Color attachments
+/// are bound in order as non-filterable `texture_2d<f32>`, followed by the depth attachment as
+/// `texture_depth_2d`, and read with `textureLoad`.
+#[derive(Debug)]
+pub struct Subpasses {
+    label: String,
+    attachments: Vec<wgpu::TextureFormat>,
+    depth_format: Option<wgpu::TextureFormat>,
+    bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl Subpasses {
+    pub fn new(device: &wgpu::Device, descriptor: &SubpassesDescriptor<'_>) -> Self {
+        let texture_entry = |binding, sample_type| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: descriptor.visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let mut entries: Vec<_> = (0..descriptor.attachments.len() as u32)
+            .map(|binding| {
+                texture_entry(
+                    binding,
+                    wgpu::TextureSampleType::Float { filterable: false },
+                )
+            })
+            .collect();
+        if descriptor.depth_format.is_some() {
+            entries.push(texture_entry(
+                entries.len() as u32,
+                wgpu::TextureSampleType::Depth,
+            ));
+        }
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} input bind group layout", descriptor.label)),
+            entries: &entries,
+        });
+
+        Self {
+            label: descriptor.label.to_owned(),
+            attachments: descriptor.attachments.to_vec(),
+            depth_format: descriptor.depth_format,
+            bind_group_layout,
+        }
+    }
+
+    /// Layout of the bind group reading the attachments, for the pipelines of the second pass.
+    pub fn bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Color targets of the first pass without blending, for its pipelines.
+    pub fn color_targets(&self) -> Vec<Option<wgpu::ColorTargetState>> {
+        self.attachments
+            .iter()
+            .map(|&format| Some(format.into()))
+            .collect()
+    }
+
+    /// Depth-stencil state of the first pass with a less comparison, for its pipelines.
+    pub fn depth_stencil(&self) -> Option<wgpu::DepthStencilState> {
+        self.depth_format.map(|format| wgpu::DepthStencilState {
+            format,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::Less,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        })
+    }
+
+    /// Acquires the attachments of a `width` x `height` frame from `pool` and creates the bind
+    /// group of the second pass. Release them with [`SubpassTargets::release`] after recording
+    /// both passes.
+    pub fn acquire(
+        &self,
+        device: &wgpu::Device,
+        pool: &mut TexturePool,
+        width: u32,
+        height: u32,
+    ) -> SubpassTargets {
+        let mut acquire = |label: &str, format| {
+            let index = pool.acquire(
+                device,
+                &wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width,
+                        height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                        | wgpu::TextureUsages::TEXTURE_BINDING,
+                },
+            );
+            let view = pool
+                .get(index)
+                .expect("texture is acquired")
+                .create_view(&wgpu::TextureViewDescriptor::default());
+            (index, view)
+        };
+        let color: Vec<_> = self
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(i, &format)| acquire(&format!("{} attachment {}", self.label, i), format))
+            .collect();
+        let depth = self
+            .depth_format
+            .map(|format| acquire(&format!("{} depth attachment", self.label), format));
+        // Depth-stencil textures can only be bound through views of their depth aspect.
+        let depth_input = depth.as_ref().map(|&(index, _)| {
+            pool.get(index).expect("texture is acquired").create_view(
+                &wgpu::TextureViewDescriptor {
+                    aspect: wgpu::TextureAspect::DepthOnly,
+                    ..Default::default()
+                },
+            )
+        });
+
+        let entries: Vec<_> = color
+            .iter()
+            .map(|(_, view)| view)
+            .chain(&depth_input)
+            .enumerate()
+            .map(|(binding, view)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: wgpu::BindingResource::TextureView(view),
+            })
+            .collect();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} input bind group", self.label)),
+            layout: &self.bind_group_layout,
+            entries: &entries,
+        });
+
+        SubpassTargets {
+            label: self.label.clone(),
+            color,
+            depth,
+            bind_group,
+        }
+    }
+}
+
+/// Attachments of a frame of [`Subpasses`].
+#[derive(Debug)]
+pub struct SubpassTargets {
+    label: String,
+    /// Pool indices and views.
+    color: Vec<(usize, wgpu::TextureView)>,
+    depth: Option<(usize, wgpu::TextureView)>,
+    bind_group: wgpu::BindGroup,
+}
+
+impl SubpassTargets {
+    /// Begins the first pass, clearing the color attachments to transparent and depth to 1.
+    pub fn begin_first_pass<'a>(
+        &'a self,
+        encoder: &'a mut wgpu::CommandEncoder,
+    ) -> wgpu::RenderPass<'a> {
+        let color_attachments: Vec<_> = self
+            .color
+            .iter()
+            .map(|(_, view)| {
+                Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })
+            })
+            .collect();
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&self.label),
+            color_attachments: &color_attachments,
+            depth_stencil_attachment: self.depth.as_ref().map(|(_, view)| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+        })
+    }
+
+    /// Bind group reading the attachments in the second pass.
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    /// Views of the color attachments.
+    pub fn views(&self) -> impl Iterator<Item = &wgpu::TextureView> {
+        self.color.iter().map(|(_, view)| view)
+    }
+
+    pub fn depth_view(&self) -> Option<&wgpu::TextureView> {
+        self.depth.as_ref().map(|(_, view)| view)
+    }
+
+    /// Returns the attachments to `pool`, so later passes of the frame can reuse them.
+    pub fn release(self, pool: &mut TexturePool) {
+        for (index, _) in self.color.iter().chain(&self.depth) {
+            pool.release(*index);
+        }
+    }
+}
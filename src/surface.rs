@@ -0,0 +1,592 @@
+//! Surface configuration and frame acquisition.
+
+#[cfg(feature = "raw-window-handle")]
+use std::fmt;
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    context::ContextError,
+    post::{TonemapOperator, TonemapPass, TonemapPassDescriptor},
+};
+
+/// Format of HDR surfaces and of the intermediate target used when HDR is requested.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// How frames are paced, mapped to the best [`wgpu::PresentMode`] the surface supports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum PresentPolicy {
+    /// No tearing, frame rate capped to the display refresh rate.
+    #[default]
+    VsyncPreferred,
+    /// No tearing if possible, but newer frames replace queued ones.
+    LowLatency,
+    /// Present as fast as possible, tearing is allowed.
+    Uncapped,
+}
+
+impl PresentPolicy {
+    /// Picks the best of the `supported` present modes for this policy.
+    ///
+    /// Falls back to [`wgpu::PresentMode::Fifo`], which is supported everywhere.
+    pub fn select(self, supported: &[wgpu::PresentMode]) -> wgpu::PresentMode {
+        use wgpu::PresentMode::*;
+        let preferences: &[wgpu::PresentMode] = match self {
+            Self::VsyncPreferred => &[Fifo],
+            Self::LowLatency => &[Mailbox, FifoRelaxed, Fifo],
+            Self::Uncapped => &[Immediate, Mailbox, Fifo],
+        };
+        preferences
+            .iter()
+            .copied()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(Fifo)
+    }
+}
+
+/// Number of present intervals the refresh interval is estimated from.
+const PACING_WINDOW: usize = 64;
+
+/// Frame pacing measured by [`SurfaceManager::acquire`] and [`SurfaceManager::present`].
+///
+/// Stutter with long [`Self::last_gpu_wait`]s is GPU-bound, the GPU didn't finish earlier
+/// frames in time. Stutter with long [`Self::last_acquire`]s is presentation-bound, the
+/// swapchain had no image available. Missed vsyncs with neither point at the CPU.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PacingStats {
+    /// Number of presented frames.
+    pub frames: u64,
+    /// Time the last acquire waited for frames in flight.
+    pub last_gpu_wait: Duration,
+    /// Time the last acquire waited for a surface texture.
+    pub last_acquire: Duration,
+    /// Time between the last two presents.
+    pub last_interval: Duration,
+    /// Estimated refresh interval, the lower quartile of recent present intervals. `None`
+    /// until enough frames were presented.
+    ///
+    /// Only meaningful with vsync, otherwise it estimates the fastest frame times.
+    pub refresh_interval: Option<Duration>,
+    /// Number of present intervals longer than 1.5 refresh intervals.
+    pub missed_vsyncs: u64,
+    /// Whether the last present interval missed a vsync.
+    pub last_missed_vsync: bool,
+    /// Number of acquires waiting longer than a refresh interval for frames in flight.
+    pub long_gpu_waits: u64,
+    /// Number of acquires waiting longer than a refresh interval for a surface texture.
+    pub long_acquires: u64,
+}
+
+#[derive(Debug, Default)]
+struct PacingTracker {
+    stats: PacingStats,
+    last_present: Option<Instant>,
+    intervals: VecDeque<Duration>,
+}
+
+impl PacingTracker {
+    fn is_long(&self, duration: Duration) -> bool {
+        self.stats
+            .refresh_interval
+            .is_some_and(|refresh| duration > refresh)
+    }
+
+    fn acquired(&mut self, gpu_wait: Duration, acquire: Duration) {
+        self.stats.last_gpu_wait = gpu_wait;
+        self.stats.last_acquire = acquire;
+        if self.is_long(gpu_wait) {
+            self.stats.long_gpu_waits += 1;
+        }
+        if self.is_long(acquire) {
+            self.stats.long_acquires += 1;
+        }
+    }
+
+    fn presented(&mut self, now: Instant) {
+        self.stats.frames += 1;
+        let Some(last_present) = self.last_present.replace(now) else {
+            return;
+        };
+        let interval = now - last_present;
+        self.stats.last_interval = interval;
+        self.stats.last_missed_vsync = self
+            .stats
+            .refresh_interval
+            .is_some_and(|refresh| interval > refresh * 3 / 2);
+        if self.stats.last_missed_vsync {
+            self.stats.missed_vsyncs += 1;
+        }
+
+        if self.intervals.len() == PACING_WINDOW {
+            self.intervals.pop_front();
+        }
+        self.intervals.push_back(interval);
+        if self.intervals.len() >= PACING_WINDOW / 4 {
+            let mut sorted: Vec<_> = self.intervals.iter().copied().collect();
+            sorted.sort_unstable();
+            self.stats.refresh_interval = Some(sorted[sorted.len() / 4]);
+        }
+    }
+}
+
+/// Descriptor for [`SurfaceManager`].
+#[derive(Clone, Debug)]
+pub struct SurfaceManagerDescriptor {
+    /// Width of the surface in physical pixels.
+    pub width: u32,
+    /// Height of the surface in physical pixels.
+    pub height: u32,
+    /// Format of the surface. If unspecified, the format is negotiated with the adapter, see
+    /// [`Self::hdr`].
+    pub format: Option<wgpu::TextureFormat>,
+    /// Opt into HDR output.
+    ///
+    /// The surface prefers [`HDR_FORMAT`] if the platform exposes it and the manager provides an
+    /// HDR render target, which [`SurfaceManager::resolve_hdr`] either passes through or
+    /// tonemaps into the SDR surface.
+    pub hdr: bool,
+    /// Frame pacing policy of the surface.
+    pub present_policy: PresentPolicy,
+    /// Maximum number of frames presented through [`SurfaceManager::present`] which may be in
+    /// flight before [`SurfaceManager::acquire`] waits for the GPU.
+    pub desired_maximum_frame_latency: u32,
+    /// Usages of the surface textures.
+    pub usage: wgpu::TextureUsages,
+}
+
+impl Default for SurfaceManagerDescriptor {
+    fn default() -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            format: None,
+            hdr: false,
+            present_policy: PresentPolicy::default(),
+            desired_maximum_frame_latency: 2,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        }
+    }
+}
+
+/// A [`wgpu::Surface`] together with its configuration.
+///
+/// Keeps the surface configured across resizes and recovers from lost or outdated surfaces.
+///
+/// wgpu doesn't expose the swapchain length, so the frame latency is limited on the CPU instead:
+/// frames presented through [`Self::present`] are counted until the GPU finished them.
+#[derive(Debug)]
+pub struct SurfaceManager {
+    surface: wgpu::Surface,
+    config: wgpu::SurfaceConfiguration,
+
+    supported_present_modes: Vec<wgpu::PresentMode>,
+    present_policy: PresentPolicy,
+
+    desired_maximum_frame_latency: u32,
+    /// Submissions of the presented frames which may still be in flight, oldest first.
+    submissions: VecDeque<wgpu::SubmissionIndex>,
+    frames_in_flight: Arc<AtomicU32>,
+
+    pacing: PacingTracker,
+
+    hdr: Option<HdrResolve>,
+}
+
+#[derive(Debug)]
+struct HdrResolve {
+    target: wgpu::Texture,
+    view: wgpu::TextureView,
+    tonemap: TonemapPass,
+}
+
+impl SurfaceManager {
+    /// Configures `surface` for `device`.
+    ///
+    /// Fails if no format was requested and the adapter supports none for the surface.
+    pub fn new(
+        device: &wgpu::Device,
+        adapter: &wgpu::Adapter,
+        surface: wgpu::Surface,
+        descriptor: &SurfaceManagerDescriptor,
+    ) -> Result<Self, ContextError> {
+        let format = match descriptor.format {
+            Some(format) => format,
+            None => select_format(&surface.get_supported_formats(adapter), descriptor.hdr)
+                .ok_or(ContextError::NoSurfaceFormat)?,
+        };
+        let supported_present_modes = surface.get_supported_modes(adapter);
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: descriptor.usage,
+            format,
+            width: descriptor.width,
+            height: descriptor.height,
+            present_mode: descriptor.present_policy.select(&supported_present_modes),
+        };
+
+        let hdr = descriptor.hdr.then(|| {
+            let (target, view) = create_hdr_target(device, config.width, config.height);
+            let tonemap = TonemapPass::new(
+                device,
+                &TonemapPassDescriptor {
+                    output_format: config.format,
+                    operator: match config.format == HDR_FORMAT {
+                        true => TonemapOperator::None,
+                        false => TonemapOperator::default(),
+                    },
+                    exposure: 1.0,
+                },
+            );
+            HdrResolve {
+                target,
+                view,
+                tonemap,
+            }
+        });
+
+        let manager = Self {
+            surface,
+            config,
+
+            supported_present_modes,
+            present_policy: descriptor.present_policy,
+
+            desired_maximum_frame_latency: descriptor.desired_maximum_frame_latency.max(1),
+            submissions: VecDeque::new(),
+            frames_in_flight: Arc::new(AtomicU32::new(0)),
+
+            pacing: PacingTracker::default(),
+
+            hdr,
+        };
+        manager.configure(device);
+        Ok(manager)
+    }
+
+    /// Creates a surface for a raw window handle and configures it for `device`.
+    ///
+    /// Works with any windowing library implementing [`raw_window_handle::HasRawWindowHandle`].
+    /// The handle is validated before the surface is created and the adapter is checked to be
+    /// able to present to it.
+    ///
+    /// # Safety
+    /// `window` must be a valid window handle which outlives the returned manager.
+    #[cfg(feature = "raw-window-handle")]
+    pub unsafe fn from_raw_handle<W: raw_window_handle::HasRawWindowHandle>(
+        instance: &wgpu::Instance,
+        device: &wgpu::Device,
+        adapter: &wgpu::Adapter,
+        window: &W,
+        descriptor: &SurfaceManagerDescriptor,
+    ) -> Result<Self, SurfaceCreationError> {
+        validate_raw_handle(window.raw_window_handle(), descriptor)?;
+
+        let surface = instance.create_surface(window);
+        if !adapter.is_surface_supported(&surface) {
+            return Err(SurfaceCreationError::UnsupportedAdapter);
+        }
+
+        Self::new(device, adapter, surface, descriptor).map_err(SurfaceCreationError::Context)
+    }
+
+    /// Resizes the surface. Zero sized surfaces are not configured until they get a size again.
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        if (width, height) == (self.config.width, self.config.height) {
+            return;
+        }
+        self.config.width = width;
+        self.config.height = height;
+        self.configure(device);
+
+        if let Some(hdr) = &mut self.hdr {
+            (hdr.target, hdr.view) = create_hdr_target(device, width, height);
+        }
+    }
+
+    /// Switches the present policy and reconfigures the surface if the present mode changes.
+    pub fn set_present_policy(&mut self, device: &wgpu::Device, policy: PresentPolicy) {
+        self.present_policy = policy;
+        let present_mode = policy.select(&self.supported_present_modes);
+        if present_mode != self.config.present_mode {
+            self.config.present_mode = present_mode;
+            self.configure(device);
+        }
+    }
+
+    /// Sets the maximum number of frames in flight. Clamped to at least 1.
+    pub fn set_desired_maximum_frame_latency(&mut self, latency: u32) {
+        self.desired_maximum_frame_latency = latency.max(1);
+    }
+
+    /// Applies the current configuration to the surface.
+    pub fn configure(&self, device: &wgpu::Device) {
+        if !self.is_zero_sized() {
+            self.surface.configure(device, &self.config);
+        }
+    }
+
+    /// Acquires the next surface texture.
+    ///
+    /// Waits for the oldest frame in flight to finish on the GPU if too many are in flight.
+    /// Lost and outdated surfaces are reconfigured and acquiring is retried once.
+    /// Zero sized surfaces always return [`wgpu::SurfaceError::Outdated`].
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+    ) -> Result<wgpu::SurfaceTexture, wgpu::SurfaceError> {
+        if self.is_zero_sized() {
+            return Err(wgpu::SurfaceError::Outdated);
+        }
+
+        let start = Instant::now();
+        device.poll(wgpu::Maintain::Poll);
+        // Frames finish in order, so only the newest submissions can still be in flight.
+        let in_flight = self.frames_in_flight() as usize;
+        while self.submissions.len() > in_flight {
+            self.submissions.pop_front();
+        }
+        while self.submissions.len() >= self.desired_maximum_frame_latency as usize {
+            let submission = self.submissions.pop_front().unwrap();
+            device.poll(wgpu::Maintain::WaitForSubmissionIndex(submission));
+        }
+        let waited = Instant::now();
+
+        let result = match self.surface.get_current_texture() {
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.configure(device);
+                self.surface.get_current_texture()
+            }
+            result => result,
+        };
+        self.pacing.acquired(waited - start, waited.elapsed());
+        result
+    }
+
+    /// Presents `frame`, whose last work was submitted as `submission`, and counts it as in
+    /// flight until all work submitted so far is done.
+    pub fn present(
+        &mut self,
+        queue: &wgpu::Queue,
+        frame: wgpu::SurfaceTexture,
+        submission: wgpu::SubmissionIndex,
+    ) {
+        self.submissions.push_back(submission);
+        self.frames_in_flight.fetch_add(1, Ordering::AcqRel);
+        let frames_in_flight = self.frames_in_flight.clone();
+        queue.on_submitted_work_done(move || {
+            frames_in_flight.fetch_sub(1, Ordering::AcqRel);
+        });
+        frame.present();
+        self.pacing.presented(Instant::now());
+    }
+
+    /// Frame pacing of the frames acquired and presented so far.
+    pub fn pacing_stats(&self) -> PacingStats {
+        self.pacing.stats
+    }
+
+    /// Resets the pacing statistics, e.g. after changing the present policy.
+    pub fn reset_pacing_stats(&mut self) {
+        self.pacing = PacingTracker::default();
+    }
+
+    /// Number of presented frames the GPU hasn't finished yet.
+    pub fn frames_in_flight(&self) -> u32 {
+        self.frames_in_flight.load(Ordering::Acquire)
+    }
+
+    /// Current present policy.
+    pub fn present_policy(&self) -> PresentPolicy {
+        self.present_policy
+    }
+
+    /// Present modes supported by the surface on this adapter.
+    pub fn supported_present_modes(&self) -> &[wgpu::PresentMode] {
+        &self.supported_present_modes
+    }
+
+    /// Whether the surface itself has an HDR format.
+    pub fn is_hdr(&self) -> bool {
+        self.config.format == HDR_FORMAT
+    }
+
+    /// The HDR render target with [`HDR_FORMAT`], if HDR was requested.
+    pub fn hdr_view(&self) -> Option<&wgpu::TextureView> {
+        self.hdr.as_ref().map(|hdr| &hdr.view)
+    }
+
+    /// The pass resolving the HDR render target, e.g. to change the exposure.
+    pub fn hdr_tonemap_mut(&mut self) -> Option<&mut TonemapPass> {
+        self.hdr.as_mut().map(|hdr| &mut hdr.tonemap)
+    }
+
+    /// Resolves the HDR render target into `frame`, a view of the acquired surface texture.
+    ///
+    /// HDR surfaces get the target passed through, SDR surfaces get it tonemapped.
+    ///
+    /// # Panics
+    /// If HDR wasn't requested.
+    pub fn resolve_hdr(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        frame: &wgpu::TextureView,
+    ) {
+        let hdr = self.hdr.as_ref().expect("HDR wasn't requested");
+        hdr.tonemap.render(device, queue, encoder, &hdr.view, frame);
+    }
+
+    /// Get a reference to the surface.
+    pub fn surface(&self) -> &wgpu::Surface {
+        &self.surface
+    }
+
+    /// Get a reference to the current configuration.
+    pub fn config(&self) -> &wgpu::SurfaceConfiguration {
+        &self.config
+    }
+
+    /// Format of the surface textures.
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.config.format
+    }
+
+    /// Size of the surface in physical pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
+
+    /// Whether the surface has no area, e.g. because the window is minimized.
+    pub fn is_zero_sized(&self) -> bool {
+        self.config.width == 0 || self.config.height == 0
+    }
+}
+
+/// Picks [`HDR_FORMAT`] if requested and supported, otherwise the first sRGB format. `None` if
+/// no format is supported.
+fn select_format(supported: &[wgpu::TextureFormat], hdr: bool) -> Option<wgpu::TextureFormat> {
+    // wgpu doesn't expose surface color spaces, so 10 bit formats can't be used for HDR10.
+    if hdr && supported.contains(&HDR_FORMAT) {
+        return Some(HDR_FORMAT);
+    }
+    supported
+        .iter()
+        .copied()
+        .find(|format| format.describe().srgb)
+        .or_else(|| supported.first().copied())
+}
+
+fn create_hdr_target(
+    device: &wgpu::Device,
+    width: u32,
+    height: u32,
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let target = crate::resource_log::create_texture(
+        device,
+        &wgpu::TextureDescriptor {
+            label: Some("hdr render target"),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        },
+    );
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+    (target, view)
+}
+
+/// Error returned by [`SurfaceManager::from_raw_handle`].
+#[cfg(feature = "raw-window-handle")]
+#[derive(Debug)]
+pub enum SurfaceCreationError {
+    /// The handle contains a null window, view or display.
+    NullHandle(&'static str),
+    /// The platform doesn't report a surface size on its own (e.g. Wayland), so a non-zero size
+    /// in physical pixels must be passed in the descriptor.
+    MissingSize(&'static str),
+    /// The platform is not supported by wgpu.
+    UnsupportedPlatform(&'static str),
+    /// The adapter can't present to the surface.
+    UnsupportedAdapter,
+    /// The surface couldn't be configured.
+    Context(ContextError),
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl fmt::Display for SurfaceCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NullHandle(platform) => write!(f, "{} window handle is null", platform),
+            Self::MissingSize(platform) => write!(
+                f,
+                "{} surfaces need an explicit size, pass the window size in physical pixels \
+                 (logical size times scale factor)",
+                platform
+            ),
+            Self::UnsupportedPlatform(platform) => {
+                write!(f, "{} windows are not supported by wgpu", platform)
+            }
+            Self::UnsupportedAdapter => write!(f, "adapter can't present to the surface"),
+            Self::Context(e) => write!(f, "failed to configure the surface: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+impl std::error::Error for SurfaceCreationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::NullHandle(_)
+            | Self::MissingSize(_)
+            | Self::UnsupportedPlatform(_)
+            | Self::UnsupportedAdapter => None,
+            Self::Context(e) => Some(e),
+        }
+    }
+}
+
+#[cfg(feature = "raw-window-handle")]
+fn validate_raw_handle(
+    handle: raw_window_handle::RawWindowHandle,
+    descriptor: &SurfaceManagerDescriptor,
+) -> Result<(), SurfaceCreationError> {
+    use raw_window_handle::RawWindowHandle;
+    use SurfaceCreationError::*;
+
+    let zero_sized = descriptor.width == 0 || descriptor.height == 0;
+    match handle {
+        RawWindowHandle::Xlib(h) if h.window == 0 || h.display.is_null() => Err(NullHandle("Xlib")),
+        RawWindowHandle::Xcb(h) if h.window == 0 || h.connection.is_null() => {
+            Err(NullHandle("Xcb"))
+        }
+        RawWindowHandle::Wayland(h) if h.surface.is_null() || h.display.is_null() => {
+            Err(NullHandle("Wayland"))
+        }
+        // Wayland surfaces take whatever size the first buffer has.
+        RawWindowHandle::Wayland(_) if zero_sized => Err(MissingSize("Wayland")),
+        RawWindowHandle::Win32(h) if h.hwnd.is_null() => Err(NullHandle("Win32")),
+        RawWindowHandle::AppKit(h) if h.ns_view.is_null() => Err(NullHandle("AppKit")),
+        RawWindowHandle::UiKit(h) if h.ui_view.is_null() => Err(NullHandle("UiKit")),
+        RawWindowHandle::AndroidNdk(h) if h.a_native_window.is_null() => {
+            Err(NullHandle("Android NDK"))
+        }
+        RawWindowHandle::Web(h) if h.id == 0 => Err(NullHandle("Web")),
+        RawWindowHandle::WinRt(_) => Err(UnsupportedPlatform("WinRT")),
+        RawWindowHandle::Orbital(_) => Err(UnsupportedPlatform("Orbital")),
+        RawWindowHandle::Haiku(_) => Err(UnsupportedPlatform("Haiku")),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,357 @@
+//! Building terrain meshes from heightmaps, split into patches for culling.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    mesh::{Aabb, Mesh, MeshDescriptor},
+    BufferInitDescriptor, DeviceExt,
+};
+
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Size of a terrain vertex in bytes: `f32` position and normal.
+pub const TERRAIN_VERTEX_SIZE: wgpu::BufferAddress = 24;
+
+/// Vertex attributes of terrain vertices: the position and the normal as `Float32x3`.
+pub fn terrain_attributes(
+    position_location: u32,
+    normal_location: u32,
+) -> [wgpu::VertexAttribute; 2] {
+    [
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x3,
+            offset: 0,
+            shader_location: position_location,
+        },
+        wgpu::VertexAttribute {
+            format: wgpu::VertexFormat::Float32x3,
+            offset: 12,
+            shader_location: normal_location,
+        },
+    ]
+}
+
+/// Descriptor for [`TerrainPatch::from_heightmap`].
+#[derive(Clone, Debug)]
+pub struct TerrainDescriptor<'a> {
+    pub label: wgpu::Label<'a>,
+    /// Number of height samples along x.
+    pub width: u32,
+    /// Number of height samples along z.
+    pub depth: u32,
+    /// Distance between samples along x and z.
+    pub spacing: [f32; 2],
+    /// Factor heights are multiplied with.
+    pub height_scale: f32,
+    /// Position of the first sample at height 0.
+    pub origin: [f32; 3],
+    /// Size of a patch in quads along both axes. Patches at the far edges may be smaller.
+    pub patch_size: u32,
+    /// Compute the normals in a compute pass instead of on the CPU, adding
+    /// [`wgpu::BufferUsages::STORAGE`] to the vertex buffers.
+    pub gpu_normals: bool,
+    /// Additional usages of the vertex and index buffers.
+    pub usage: wgpu::BufferUsages,
+}
+
+impl Default for TerrainDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: None,
+            width: 0,
+            depth: 0,
+            spacing: [1.0; 2],
+            height_scale: 1.0,
+            origin: [0.0; 3],
+            patch_size: 64,
+            gpu_normals: false,
+            usage: wgpu::BufferUsages::empty(),
+        }
+    }
+}
+
+/// A rectangular piece of terrain with its own mesh and bounds.
+///
+/// Neighboring patches share the samples on their edges, so they meet without cracks.
+#[derive(Debug)]
+pub struct TerrainPatch {
+    /// Mesh of [`TERRAIN_VERTEX_SIZE`] vertices, see [`terrain_attributes`].
+    pub mesh: Mesh,
+    pub bounds: Aabb,
+    /// First sample of the patch in the heightmap.
+    pub origin: [u32; 2],
+    /// Size of the patch in quads.
+    pub size: [u32; 2],
+}
+
+impl TerrainPatch {
+    /// Builds the patches of the row-major `heights` of `width * depth` samples. y is up and
+    /// triangles are counter-clockwise seen from above.
+    ///
+    /// With [`TerrainDescriptor::gpu_normals`], the normals are computed in a pass submitted to
+    /// `queue`.
+    pub fn from_heightmap(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        heights: &[f32],
+        descriptor: &TerrainDescriptor<'_>,
+    ) -> Vec<TerrainPatch> {
+        let (width, depth) = (descriptor.width, descriptor.depth);
+        assert!(
+            width >= 2 && depth >= 2,
+            "terrain must have at least 2x2 samples"
+        );
+        assert_eq!(
+            heights.len(),
+            (width * depth) as usize,
+            "heights must have width * depth samples"
+        );
+        assert!(descriptor.patch_size > 0, "patch size must not be zero");
+
+        let usage = match descriptor.gpu_normals {
+            true => descriptor.usage | wgpu::BufferUsages::STORAGE,
+            false => descriptor.usage,
+        };
+        let mut patches = Vec::new();
+        for z in (0..depth - 1).step_by(descriptor.patch_size as usize) {
+            for x in (0..width - 1).step_by(descriptor.patch_size as usize) {
+                let size = [
+                    descriptor.patch_size.min(width - 1 - x),
+                    descriptor.patch_size.min(depth - 1 - z),
+                ];
+                patches.push(build_patch(
+                    device,
+                    heights,
+                    descriptor,
+                    [x, z],
+                    size,
+                    usage,
+                ));
+            }
+        }
+
+        if descriptor.gpu_normals {
+            compute_normals(device, queue, heights, descriptor, &patches);
+        }
+        patches
+    }
+
+    /// Number of vertices along x and z.
+    fn vertex_size(&self) -> [u32; 2] {
+        [self.size[0] + 1, self.size[1] + 1]
+    }
+}
+
+fn build_patch(
+    device: &wgpu::Device,
+    heights: &[f32],
+    descriptor: &TerrainDescriptor<'_>,
+    origin: [u32; 2],
+    size: [u32; 2],
+    usage: wgpu::BufferUsages,
+) -> TerrainPatch {
+    let (width, depth) = (descriptor.width, descriptor.depth);
+    let [sx, sz] = descriptor.spacing;
+    let height = |x: u32, z: u32| heights[(z * width + x) as usize] * descriptor.height_scale;
+    let position = |x: u32, z: u32| {
+        [
+            descriptor.origin[0] + x as f32 * sx,
+            descriptor.origin[1] + height(x, z),
+            descriptor.origin[2] + z as f32 * sz,
+        ]
+    };
+
+    let mut vertices =
+        Vec::with_capacity(((size[0] + 1) * (size[1] + 1)) as usize * TERRAIN_VERTEX_SIZE as usize);
+    let mut bounds = Aabb::EMPTY;
+    for z in origin[1]..=origin[1] + size[1] {
+        for x in origin[0]..=origin[0] + size[0] {
+            let p = position(x, z);
+            bounds = bounds.union_point(p);
+
+            let normal = match descriptor.gpu_normals {
+                true => [0.0; 3],
+                false => {
+                    let (x0, x1) = (x.saturating_sub(1), (x + 1).min(width - 1));
+                    let (z0, z1) = (z.saturating_sub(1), (z + 1).min(depth - 1));
+                    let dx = (height(x1, z) - height(x0, z)) / ((x1 - x0) as f32 * sx);
+                    let dz = (height(x, z1) - height(x, z0)) / ((z1 - z0) as f32 * sz);
+                    let length = (dx * dx + 1.0 + dz * dz).sqrt();
+                    [-dx / length, 1.0 / length, -dz / length]
+                }
+            };
+            for component in p.into_iter().chain(normal) {
+                vertices.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+    }
+
+    let row = size[0] + 1;
+    let mut indices = Vec::with_capacity((size[0] * size[1] * 6) as usize);
+    for z in 0..size[1] {
+        for x in 0..size[0] {
+            let a = z * row + x;
+            let (b, c, d) = (a + 1, a + row, a + row + 1);
+            indices.extend_from_slice(&[a, c, b, b, c, d]);
+        }
+    }
+
+    let mesh = Mesh::new(
+        device,
+        &MeshDescriptor {
+            label: descriptor.label,
+            vertices: &vertices,
+            vertex_stride: TERRAIN_VERTEX_SIZE,
+            indices: &indices,
+            bounds: Some(bounds),
+            // The compute pass relies on the grid order of the vertices.
+            optimize: None,
+            usage,
+        },
+    );
+    TerrainPatch {
+        mesh,
+        bounds,
+        origin,
+        size,
+    }
+}
+
+/// Fills the normals of the patches' vertex buffers from the heightmap.
+fn compute_normals(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    heights: &[f32],
+    descriptor: &TerrainDescriptor<'_>,
+    patches: &[TerrainPatch],
+) {
+    let source = include_str!("shaders/terrain_normals.wgsl");
+
+    let shader = crate::create_shader_module(device, Some("terrain normals shader"), source);
+
+    let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::COMPUTE,
+        ty: wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(4),
+        },
+        count: None,
+    };
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+        label: Some("terrain normals bind group layout"),
+        entries: &[
+            wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(48),
+                },
+                count: None,
+            },
+            storage_entry(1, true),
+            storage_entry(2, false),
+        ],
+    });
+    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+        label: Some("terrain normals pipeline layout"),
+        bind_group_layouts: &[&bind_group_layout],
+        push_constant_ranges: &[],
+    });
+    let pipeline = crate::resource_log::create_compute_pipeline(
+        device,
+        &wgpu::ComputePipelineDescriptor {
+            label: Some("terrain normals pipeline"),
+            layout: Some(&pipeline_layout),
+            module: &shader,
+            entry_point: "compute_normals",
+        },
+    );
+
+    let heights_buffer = device.create_buffer_init(&BufferInitDescriptor {
+        label: Some("terrain heights buffer"),
+        contents: &heights
+            .iter()
+            .flat_map(|h| h.to_le_bytes())
+            .collect::<Vec<_>>(),
+        size: None,
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let bind_groups: Vec<_> = patches
+        .iter()
+        .map(|patch| {
+            let [vertices_x, vertices_z] = patch.vertex_size();
+            let mut params = Vec::with_capacity(48);
+            for word in [
+                descriptor.width,
+                descriptor.depth,
+                patch.origin[0],
+                patch.origin[1],
+                vertices_x,
+                vertices_z,
+            ] {
+                params.extend_from_slice(&word.to_le_bytes());
+            }
+            for value in [
+                descriptor.spacing[0],
+                descriptor.spacing[1],
+                descriptor.height_scale,
+            ] {
+                params.extend_from_slice(&value.to_le_bytes());
+            }
+            params.resize(48, 0);
+            let params = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("terrain normals params buffer"),
+                contents: &params,
+                size: None,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("terrain normals bind group"),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: heights_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: patch.mesh.vertices.as_entire_binding(),
+                    },
+                ],
+            })
+        })
+        .collect();
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("terrain normals encoder"),
+    });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("terrain normals pass"),
+        });
+        pass.set_pipeline(&pipeline);
+        for (patch, bind_group) in patches.iter().zip(&bind_groups) {
+            let [vertices_x, vertices_z] = patch.vertex_size();
+            pass.set_bind_group(0, bind_group, &[]);
+            crate::dispatch(
+                &mut pass,
+                "terrain normals pass",
+                [
+                    vertices_x.div_ceil(WORKGROUP_SIZE),
+                    vertices_z.div_ceil(WORKGROUP_SIZE),
+                    1,
+                ],
+            );
+        }
+    }
+    queue.submit(Some(encoder.finish()));
+}
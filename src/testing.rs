@@ -0,0 +1,620 @@
+//! Helpers for unit testing GPU code.
+
+use std::{
+    fmt,
+    future::Future,
+    ops::Range,
+    pin::pin,
+    sync::Arc,
+    task::{Context, Poll, Wake},
+    thread::{self, Thread},
+};
+
+use crate::{
+    context::{ContextError, GpuContext, GpuContextDescriptor},
+    inspect::ScalarType,
+    BufferInitDescriptor, DeviceExt,
+};
+
+/// Maximum number of ranges and values per range shown when formatting a [`DiffReport`].
+const DISPLAY_LIMIT: usize = 8;
+
+/// A run of consecutive differing elements.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffRange {
+    /// Element indices of the run.
+    pub elements: Range<usize>,
+    /// Formatted values of the first side. Missing values are absent.
+    pub a: Vec<String>,
+    /// Formatted values of the second side. Missing values are absent.
+    pub b: Vec<String>,
+}
+
+impl DiffRange {
+    /// Byte range of the run relative to the compared range.
+    pub fn bytes(&self, ty: ScalarType) -> Range<wgpu::BufferAddress> {
+        let size = ty.size();
+        self.elements.start as wgpu::BufferAddress * size
+            ..self.elements.end as wgpu::BufferAddress * size
+    }
+}
+
+/// Result of comparing two buffers element by element.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiffReport {
+    /// Type the bytes were interpreted as.
+    pub ty: ScalarType,
+    /// Number of elements of both sides.
+    pub len: (usize, usize),
+    /// Number of elements which differ by more than the tolerance or are missing on one side.
+    pub mismatched: usize,
+    /// Largest absolute difference between elements present on both sides.
+    pub max_difference: f64,
+    pub ranges: Vec<DiffRange>,
+}
+
+impl DiffReport {
+    /// Whether both sides have the same length and all elements are within tolerance.
+    pub fn is_equal(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_equal() {
+            return write!(f, "{} {:?} elements are equal", self.len.0, self.ty);
+        }
+
+        write!(
+            f,
+            "{} of {} {:?} elements differ (max difference {:?})",
+            self.mismatched,
+            self.len.0.max(self.len.1),
+            self.ty,
+            self.max_difference,
+        )?;
+        if self.len.0 != self.len.1 {
+            write!(f, ", lengths are {} and {}", self.len.0, self.len.1)?;
+        }
+        for range in self.ranges.iter().take(DISPLAY_LIMIT) {
+            write!(
+                f,
+                "\n  [{}..{}]: {} != {}",
+                range.elements.start,
+                range.elements.end,
+                format_values(&range.a),
+                format_values(&range.b),
+            )?;
+        }
+        if self.ranges.len() > DISPLAY_LIMIT {
+            write!(
+                f,
+                "\n  ... {} more ranges",
+                self.ranges.len() - DISPLAY_LIMIT
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn format_values(values: &[String]) -> String {
+    let mut formatted = values
+        .iter()
+        .take(DISPLAY_LIMIT)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if values.len() > DISPLAY_LIMIT {
+        formatted.push_str(", ...");
+    }
+    format!("[{}]", formatted)
+}
+
+/// Compares `a` and `b` as tightly packed `ty` elements.
+///
+/// Elements are equal if they are bitwise equal or differ by at most `tolerance`. Trailing bytes
+/// which don't form a whole element are ignored.
+pub fn diff_bytes(a: &[u8], b: &[u8], ty: ScalarType, tolerance: f64) -> DiffReport {
+    let size = ty.size() as usize;
+    let len = (a.len() / size, b.len() / size);
+    let element = |bytes: &[u8], i: usize| bytes.get(i * size..(i + 1) * size).map(<[u8]>::to_vec);
+
+    let mut report = DiffReport {
+        ty,
+        len,
+        mismatched: 0,
+        max_difference: 0.0,
+        ranges: Vec::new(),
+    };
+    for i in 0..len.0.max(len.1) {
+        let a = element(a, i).filter(|_| i < len.0);
+        let b = element(b, i).filter(|_| i < len.1);
+        let differs = match (&a, &b) {
+            (Some(a), Some(b)) => {
+                let difference = (ty.to_f64(a) - ty.to_f64(b)).abs();
+                if !difference.is_nan() {
+                    report.max_difference = report.max_difference.max(difference);
+                }
+                a != b && (difference > tolerance || difference.is_nan())
+            }
+            _ => true,
+        };
+        if !differs {
+            continue;
+        }
+
+        report.mismatched += 1;
+        let range = match report.ranges.last_mut() {
+            Some(range) if range.elements.end == i => range,
+            _ => {
+                report.ranges.push(DiffRange {
+                    elements: i..i,
+                    a: Vec::new(),
+                    b: Vec::new(),
+                });
+                report.ranges.last_mut().unwrap()
+            }
+        };
+        range.elements.end = i + 1;
+        range.a.extend(a.map(|a| ty.format(&a)));
+        range.b.extend(b.map(|b| ty.format(&b)));
+    }
+    report
+}
+
+/// Reads back `range` of `a` and `b` and compares them with [`diff_bytes`].
+///
+/// Both buffers must have [`wgpu::BufferUsages::COPY_SRC`]. Blocks until the data is available.
+pub fn diff_buffers(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    a: &wgpu::Buffer,
+    b: &wgpu::Buffer,
+    range: Range<wgpu::BufferAddress>,
+    ty: ScalarType,
+    tolerance: f64,
+) -> Result<DiffReport, wgpu::BufferAsyncError> {
+    let a = crate::readback::read_buffer(device, queue, a, range.clone())?;
+    let b = crate::readback::read_buffer(device, queue, b, range)?;
+    Ok(diff_bytes(&a, &b, ty, tolerance))
+}
+
+/// Asserts that two byte slices are equal when interpreted as
+/// [`ScalarType`](crate::inspect::ScalarType) elements, optionally within a tolerance.
+/// Takes `(a, b, ty)` or `(a, b, ty, tolerance)`, see [`diff_bytes`](crate::testing::diff_bytes).
+#[macro_export]
+macro_rules! assert_bytes_eq {
+    ($a:expr, $b:expr, $ty:expr $(,)?) => {
+        $crate::assert_bytes_eq!($a, $b, $ty, 0.0)
+    };
+    ($a:expr, $b:expr, $ty:expr, $tolerance:expr $(,)?) => {{
+        let report = $crate::testing::diff_bytes($a, $b, $ty, $tolerance);
+        assert!(report.is_equal(), "bytes differ: {}", report);
+    }};
+}
+
+/// Asserts that `range` of two buffers is equal when interpreted as
+/// [`ScalarType`](crate::inspect::ScalarType) elements, optionally within a tolerance.
+/// Takes `(device, queue, a, b, range, ty)` optionally followed by `tolerance`, see
+/// [`diff_buffers`](crate::testing::diff_buffers).
+#[macro_export]
+macro_rules! assert_buffers_eq {
+    ($device:expr, $queue:expr, $a:expr, $b:expr, $range:expr, $ty:expr $(,)?) => {
+        $crate::assert_buffers_eq!($device, $queue, $a, $b, $range, $ty, 0.0)
+    };
+    ($device:expr, $queue:expr, $a:expr, $b:expr, $range:expr, $ty:expr, $tolerance:expr $(,)?) => {{
+        let report =
+            $crate::testing::diff_buffers($device, $queue, $a, $b, $range, $ty, $tolerance)
+                .expect("failed to read back buffers");
+        assert!(report.is_equal(), "buffers differ: {}", report);
+    }};
+}
+
+/// Error returned by [`run_compute`].
+#[derive(Debug)]
+pub enum ComputeTestError {
+    /// No headless device could be created.
+    Context(ContextError),
+    /// The shader or pipeline failed validation.
+    Validation(wgpu::Error),
+    /// An output couldn't be read back.
+    Readback(wgpu::BufferAsyncError),
+}
+
+impl fmt::Display for ComputeTestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Context(e) => write!(f, "failed to create headless device: {}", e),
+            Self::Validation(e) => write!(f, "validation failed: {}", e),
+            Self::Readback(e) => write!(f, "failed to read back output: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ComputeTestError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Context(e) => Some(e),
+            Self::Validation(e) => Some(e),
+            Self::Readback(e) => Some(e),
+        }
+    }
+}
+
+/// A single dispatch of a compute shader, see [`run_compute`].
+#[derive(Clone, Debug)]
+pub struct ComputeTest<'a> {
+    /// WGSL source of the kernel.
+    pub source: &'a str,
+    pub entry_point: &'a str,
+    /// Contents of the input buffers, bound as `var<storage, read>` at `@group(0)
+    /// @binding(0..inputs.len())`. Must not be empty.
+    pub inputs: &'a [&'a [u8]],
+    /// Sizes of the zero initialized output buffers, bound as `var<storage, read_write>` at the
+    /// bindings following the inputs.
+    pub output_sizes: &'a [wgpu::BufferAddress],
+    /// Number of workgroups to dispatch.
+    pub workgroups: [u32; 3],
+}
+
+impl ComputeTest<'_> {
+    /// Dispatches once on the device of `context` and returns the contents of the outputs.
+    pub fn run(&self, context: &GpuContext) -> Result<Vec<Vec<u8>>, ComputeTestError> {
+        let device = &context.device;
+        let queue = &context.queue;
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let input_count = self.inputs.len() as u32;
+        let layout_entries: Vec<_> = (0..input_count)
+            .map(|binding| storage_entry(binding, true))
+            .chain(
+                (0..self.output_sizes.len() as u32).map(|i| storage_entry(input_count + i, false)),
+            )
+            .collect();
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("compute test bind group layout"),
+            entries: &layout_entries,
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("compute test pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let shader = crate::create_shader_module(device, Some("compute test shader"), self.source);
+        let pipeline = crate::resource_log::create_compute_pipeline(
+            device,
+            &wgpu::ComputePipelineDescriptor {
+                label: Some("compute test pipeline"),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point: self.entry_point,
+            },
+        );
+
+        let inputs: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|contents| {
+                device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("compute test input"),
+                    contents,
+                    size: None,
+                    usage: wgpu::BufferUsages::STORAGE,
+                })
+            })
+            .collect();
+        let outputs: Vec<_> = self
+            .output_sizes
+            .iter()
+            .map(|&size| {
+                let align_mask = wgpu::COPY_BUFFER_ALIGNMENT - 1;
+                crate::resource_log::create_buffer(
+                    device,
+                    &wgpu::BufferDescriptor {
+                        label: Some("compute test output"),
+                        size: ((size + align_mask) & !align_mask).max(wgpu::COPY_BUFFER_ALIGNMENT),
+                        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                        mapped_at_creation: false,
+                    },
+                )
+            })
+            .collect();
+        let bind_group_entries: Vec<_> = inputs
+            .iter()
+            .chain(&outputs)
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("compute test bind group"),
+            layout: &bind_group_layout,
+            entries: &bind_group_entries,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("compute test encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("compute test pass"),
+            });
+            pass.set_pipeline(&pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            let [x, y, z] = self.workgroups;
+            crate::dispatch(&mut pass, "compute test pass", [x, y, z]);
+        }
+        queue.submit(Some(encoder.finish()));
+
+        if let Some(error) = block_on(device.pop_error_scope()) {
+            return Err(ComputeTestError::Validation(error));
+        }
+
+        outputs
+            .iter()
+            .zip(self.output_sizes)
+            .map(|(buffer, &size)| {
+                crate::readback::read_buffer(device, queue, buffer, 0..size)
+                    .map_err(ComputeTestError::Readback)
+            })
+            .collect()
+    }
+}
+
+/// Creates a [`GpuContext`] with default settings, blocking until it's ready.
+pub fn headless_context() -> Result<GpuContext, ContextError> {
+    block_on(GpuContext::new(&GpuContextDescriptor {
+        label: Some("wgpu-util headless device"),
+        ..Default::default()
+    }))
+}
+
+/// Runs `entry_point` of `source` once with a single workgroup on a new headless device and
+/// returns the contents of the outputs.
+///
+/// See [`ComputeTest`] for how inputs and outputs are bound. Use [`ComputeTest::run`] to
+/// dispatch more workgroups or reuse a device.
+pub fn run_compute(
+    source: &str,
+    entry_point: &str,
+    inputs: &[&[u8]],
+    output_sizes: &[wgpu::BufferAddress],
+) -> Result<Vec<Vec<u8>>, ComputeTestError> {
+    let context = headless_context().map_err(ComputeTestError::Context)?;
+    ComputeTest {
+        source,
+        entry_point,
+        inputs,
+        output_sizes,
+        workgroups: [1, 1, 1],
+    }
+    .run(&context)
+}
+
+/// Environment variable which makes [`check_golden_image`] overwrite golden images instead of
+/// comparing against them.
+#[cfg(feature = "png")]
+pub const UPDATE_GOLDEN_ENV: &str = "WGPU_UTIL_UPDATE_GOLDEN";
+
+/// Thresholds for [`check_golden_image`].
+#[cfg(feature = "png")]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GoldenTolerance {
+    /// Smallest accepted PSNR in decibels.
+    pub min_psnr: f64,
+    /// Smallest accepted SSIM, `None` to skip computing it.
+    pub min_ssim: Option<f64>,
+}
+
+#[cfg(feature = "png")]
+impl Default for GoldenTolerance {
+    fn default() -> Self {
+        Self {
+            min_psnr: 40.0,
+            min_ssim: Some(0.99),
+        }
+    }
+}
+
+/// Error returned by [`check_golden_image`].
+#[cfg(feature = "png")]
+#[derive(Debug)]
+pub enum GoldenImageError {
+    /// Only `Rgba8Unorm` and `Rgba8UnormSrgb` textures can be compared with PNG files.
+    UnsupportedFormat(wgpu::TextureFormat),
+    Readback(wgpu::BufferAsyncError),
+    Save(crate::screenshot::SaveError),
+    Io(std::io::Error),
+    Decode(png::DecodingError),
+    /// The golden image has a different size than the texture.
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    /// The texture differs more than the tolerance allows. It was saved next to the golden image
+    /// with the extension `actual.png`.
+    Mismatch(crate::compare::Metrics),
+}
+
+#[cfg(feature = "png")]
+impl fmt::Display for GoldenImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedFormat(format) => {
+                write!(f, "can't compare {:?} textures to golden images", format)
+            }
+            Self::Readback(e) => write!(f, "failed to read back texture: {}", e),
+            Self::Save(e) => write!(f, "failed to save image: {}", e),
+            Self::Io(e) => write!(f, "failed to read golden image: {}", e),
+            Self::Decode(e) => write!(f, "failed to decode golden image: {}", e),
+            Self::SizeMismatch { expected, actual } => write!(
+                f,
+                "golden image is {}x{}, texture is {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            Self::Mismatch(metrics) => write!(f, "texture differs from golden image: {}", metrics),
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+impl std::error::Error for GoldenImageError {}
+
+/// Compares `texture` with the PNG at `path` within `tolerance`.
+///
+/// If the golden image doesn't exist or [`UPDATE_GOLDEN_ENV`] is set, the texture is saved as
+/// the new golden image instead. `texture` must have [`wgpu::TextureUsages::TEXTURE_BINDING`] and
+/// [`wgpu::TextureUsages::COPY_SRC`]. Blocks until the comparison is done.
+#[cfg(feature = "png")]
+pub fn check_golden_image(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+    info: &crate::readback::TextureInfo,
+    path: impl AsRef<std::path::Path>,
+    tolerance: &GoldenTolerance,
+) -> Result<crate::compare::Metrics, GoldenImageError> {
+    use crate::{
+        compare::{compare_textures, CompareOptions, Metrics},
+        screenshot::{save_texture, SaveOptions},
+    };
+
+    if !matches!(
+        info.format,
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb
+    ) {
+        return Err(GoldenImageError::UnsupportedFormat(info.format));
+    }
+
+    let path = path.as_ref();
+    if !path.exists() || std::env::var_os(UPDATE_GOLDEN_ENV).is_some() {
+        save_texture(device, queue, texture, info, path, &SaveOptions::default())
+            .map_err(GoldenImageError::Save)?;
+        return Ok(Metrics {
+            mse: 0.0,
+            psnr: f64::INFINITY,
+            ssim: tolerance.min_ssim.map(|_| 1.0),
+        });
+    }
+
+    let golden = read_golden_png(path)?;
+    if golden.0 != (info.width, info.height) {
+        return Err(GoldenImageError::SizeMismatch {
+            expected: golden.0,
+            actual: (info.width, info.height),
+        });
+    }
+    let size = wgpu::Extent3d {
+        width: info.width,
+        height: info.height,
+        depth_or_array_layers: 1,
+    };
+    let golden_texture = crate::resource_log::create_texture(
+        device,
+        &wgpu::TextureDescriptor {
+            label: Some("golden image texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: info.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        },
+    );
+    queue.write_texture(
+        golden_texture.as_image_copy(),
+        &golden.1,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(info.width * 4),
+            rows_per_image: None,
+        },
+        size,
+    );
+
+    let options = CompareOptions {
+        ssim: tolerance.min_ssim.is_some(),
+    };
+    let metrics = compare_textures(device, queue, texture, &golden_texture, info, &options)
+        .map_err(GoldenImageError::Readback)?;
+    let ssim_ok = match (metrics.ssim, tolerance.min_ssim) {
+        (Some(ssim), Some(min_ssim)) => ssim >= min_ssim,
+        _ => true,
+    };
+    if metrics.psnr >= tolerance.min_psnr && ssim_ok {
+        return Ok(metrics);
+    }
+
+    save_texture(
+        device,
+        queue,
+        texture,
+        info,
+        path.with_extension("actual.png"),
+        &SaveOptions::default(),
+    )
+    .map_err(GoldenImageError::Save)?;
+    Err(GoldenImageError::Mismatch(metrics))
+}
+
+/// Decodes a PNG into its size and RGBA8 texels.
+#[cfg(feature = "png")]
+fn read_golden_png(path: &std::path::Path) -> Result<((u32, u32), Vec<u8>), GoldenImageError> {
+    let mut decoder = png::Decoder::new(std::fs::File::open(path).map_err(GoldenImageError::Io)?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(GoldenImageError::Decode)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(GoldenImageError::Decode)?;
+    let texels = &buffer[..frame.buffer_size()];
+
+    let rgba = match frame.color_type {
+        png::ColorType::Grayscale => texels.iter().flat_map(|&l| [l, l, l, 255]).collect(),
+        png::ColorType::GrayscaleAlpha => texels
+            .chunks_exact(2)
+            .flat_map(|la| [la[0], la[0], la[0], la[1]])
+            .collect(),
+        png::ColorType::Rgb => texels
+            .chunks_exact(3)
+            .flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255])
+            .collect(),
+        _ => texels.to_vec(),
+    };
+    Ok(((frame.width, frame.height), rgba))
+}
+
+/// Minimal executor for the futures of wgpu, which resolve once the device is polled.
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Arc::new(ThreadWaker(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//! Text from multi-channel signed distance field (MSDF) font atlases, which stay crisp at any
+//! scale.
+//!
+//! Atlases are baked offline, e.g. with `msdf-atlas-gen -type msdf -csv`, and drawn with the
+//! `wgpu_util::msdf` snippet:
+//!
+//! ```wgsl
+//! #include "wgpu_util::msdf"
+//!
+//! let texel = textureSample(atlas, atlas_sampler, in.uv).rgb;
+//! let size = vec2<f32>(textureDimensions(atlas));
+//! let range = msdf_screen_px_range(DISTANCE_RANGE, size, fwidth(in.uv));
+//! let alpha = msdf_coverage(texel, range, 0.0);
+//! ```
+
+use std::{collections::HashMap, fmt};
+
+use crate::texture::{SourceLayout, TextureInitDescriptor};
+
+/// Where rows of the atlas are counted from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum YOrigin {
+    Bottom,
+    Top,
+}
+
+/// Properties of an MSDF atlas that aren't part of its glyph table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MsdfAtlasInfo {
+    /// Width and height of the atlas in texels.
+    pub width: u32,
+    pub height: u32,
+    /// Range of the encoded distances in atlas texels, `-pxrange` of `msdf-atlas-gen`.
+    pub distance_range: f32,
+    pub y_origin: YOrigin,
+    /// Distance between baselines in ems.
+    pub line_height: f32,
+}
+
+impl Default for MsdfAtlasInfo {
+    fn default() -> Self {
+        Self {
+            width: 512,
+            height: 512,
+            distance_range: 2.0,
+            y_origin: YOrigin::Bottom,
+            line_height: 1.2,
+        }
+    }
+}
+
+/// Edges of a rectangle.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GlyphBounds {
+    pub left: f32,
+    pub bottom: f32,
+    pub right: f32,
+    pub top: f32,
+}
+
+/// Metrics of a glyph in an MSDF atlas.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MsdfGlyph {
+    /// Horizontal advance in ems.
+    pub advance: f32,
+    /// Quad of the glyph in ems relative to the pen position on the baseline, y pointing up.
+    /// `None` for glyphs without outline, e.g. spaces.
+    pub plane_bounds: Option<GlyphBounds>,
+    /// Rectangle of the glyph in the atlas in texels, rows counted from
+    /// [`MsdfAtlasInfo::y_origin`].
+    pub atlas_bounds: Option<GlyphBounds>,
+}
+
+/// A glyph positioned by [`MsdfFont::layout`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphQuad {
+    /// Top left and bottom right corner in pixels, y pointing down.
+    pub position_min: [f32; 2],
+    pub position_max: [f32; 2],
+    /// Atlas texture coordinates of the top left and bottom right corner.
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+}
+
+/// Glyph metrics of a pre-baked MSDF atlas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MsdfFont {
+    pub info: MsdfAtlasInfo,
+    pub glyphs: HashMap<char, MsdfGlyph>,
+}
+
+impl MsdfFont {
+    /// Parses the glyph table written by `msdf-atlas-gen -csv`, one glyph per line as
+    /// `unicode,advance,plane left,bottom,right,top,atlas left,bottom,right,top`.
+    ///
+    /// Glyphs without outline have zero bounds, which are stored as `None`.
+    pub fn parse_csv(source: &str, info: MsdfAtlasInfo) -> Result<Self, MsdfError> {
+        let mut glyphs = HashMap::new();
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let syntax_error = |message: &str| MsdfError::Syntax {
+                line: line_number,
+                message: message.to_owned(),
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.split(',').map(str::trim);
+            let unicode: u32 = fields
+                .next()
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| syntax_error("expected a code point"))?;
+            let character =
+                char::from_u32(unicode).ok_or_else(|| syntax_error("invalid code point"))?;
+            let values = fields
+                .map(str::parse)
+                .collect::<Result<Vec<f32>, _>>()
+                .map_err(|_| syntax_error("expected numbers"))?;
+            let values: [f32; 9] = values
+                .try_into()
+                .map_err(|_| syntax_error("expected 10 fields"))?;
+
+            let bounds = |v: &[f32]| {
+                let bounds = GlyphBounds {
+                    left: v[0],
+                    bottom: v[1],
+                    right: v[2],
+                    top: v[3],
+                };
+                match bounds == GlyphBounds::default() {
+                    true => None,
+                    false => Some(bounds),
+                }
+            };
+            glyphs.insert(
+                character,
+                MsdfGlyph {
+                    advance: values[0],
+                    plane_bounds: bounds(&values[1..5]),
+                    atlas_bounds: bounds(&values[5..9]),
+                },
+            );
+        }
+        Ok(Self { info, glyphs })
+    }
+
+    pub fn glyph(&self, character: char) -> Option<&MsdfGlyph> {
+        self.glyphs.get(&character)
+    }
+
+    /// Positions the glyphs of `text` at `size` pixels per em, starting at the baseline at
+    /// `origin` in pixels with y pointing down.
+    ///
+    /// Lines are broken at `\n`. Characters missing from the atlas are skipped without advancing.
+    pub fn layout(&self, text: &str, origin: [f32; 2], size: f32) -> Vec<GlyphQuad> {
+        let [width, height] = [self.info.width as f32, self.info.height as f32];
+        let v = |y: f32| match self.info.y_origin {
+            YOrigin::Bottom => 1.0 - y / height,
+            YOrigin::Top => y / height,
+        };
+
+        let mut quads = Vec::with_capacity(text.len());
+        let mut pen = origin;
+        for character in text.chars() {
+            if character == '\n' {
+                pen = [origin[0], pen[1] + self.info.line_height * size];
+                continue;
+            }
+            let glyph = match self.glyphs.get(&character) {
+                Some(glyph) => glyph,
+                None => continue,
+            };
+            if let (Some(plane), Some(atlas)) = (glyph.plane_bounds, glyph.atlas_bounds) {
+                quads.push(GlyphQuad {
+                    position_min: [pen[0] + plane.left * size, pen[1] - plane.top * size],
+                    position_max: [pen[0] + plane.right * size, pen[1] - plane.bottom * size],
+                    uv_min: [atlas.left / width, v(atlas.top)],
+                    uv_max: [atlas.right / width, v(atlas.bottom)],
+                });
+            }
+            pen[0] += glyph.advance * size;
+        }
+        quads
+    }
+
+    /// Width of the longest line of `text` at `size` pixels per em, and the height of its lines.
+    pub fn measure(&self, text: &str, size: f32) -> [f32; 2] {
+        let advance = |line: &str| -> f32 {
+            line.chars()
+                .filter_map(|character| self.glyphs.get(&character))
+                .map(|glyph| glyph.advance)
+                .sum()
+        };
+        let width = text.split('\n').map(advance).fold(0.0, f32::max);
+        let lines = text.split('\n').count() as f32;
+        [width * size, lines * self.info.line_height * size]
+    }
+}
+
+/// Error returned by [`MsdfFont::parse_csv`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MsdfError {
+    Syntax { line: usize, message: String },
+}
+
+impl fmt::Display for MsdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for MsdfError {}
+
+/// The texture of an MSDF atlas.
+///
+/// Distances are linear, so the texture is [`wgpu::TextureFormat::Rgba8Unorm`] and must be
+/// sampled with linear filtering.
+#[derive(Debug)]
+pub struct MsdfAtlas {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    info: MsdfAtlasInfo,
+}
+
+impl MsdfAtlas {
+    /// Uploads the texels of the atlas described by `info`, rows counted from the top as in
+    /// decoded images, converted from `source` or as RGBA8 if `None`, e.g. RGB8 from
+    /// `msdf-atlas-gen` PNGs.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        info: &MsdfAtlasInfo,
+        texels: &[u8],
+        source: Option<SourceLayout>,
+    ) -> Self {
+        let texture = crate::texture::create_texture_init(
+            device,
+            queue,
+            &TextureInitDescriptor {
+                texture: wgpu::TextureDescriptor {
+                    label: Some("msdf atlas texture"),
+                    size: wgpu::Extent3d {
+                        width: info.width,
+                        height: info.height,
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format: wgpu::TextureFormat::Rgba8Unorm,
+                    usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                },
+                contents: texels,
+                source,
+            },
+        );
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("msdf atlas sampler"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        Self {
+            texture,
+            view,
+            sampler,
+            info: *info,
+        }
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Linear clamping sampler for the atlas.
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    pub fn info(&self) -> &MsdfAtlasInfo {
+        &self.info
+    }
+}
@@ -0,0 +1,947 @@
+//! Texture helpers.
+
+use std::{collections::HashMap, fmt, hash::Hash, num::NonZeroU32};
+
+use crate::{
+    binding::ResourceId,
+    budget::{BudgetAllocation, GpuBudget},
+    compress::CompressedBlocks,
+    readback::TextureInfo,
+    upload::{Asset, AssetFuture, AsyncUploadSender},
+};
+
+/// Everything of a [`wgpu::TextureDescriptor`] except the label.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct TextureKey {
+    size: wgpu::Extent3d,
+    mip_level_count: u32,
+    sample_count: u32,
+    dimension: wgpu::TextureDimension,
+    format: wgpu::TextureFormat,
+    usage: wgpu::TextureUsages,
+}
+
+impl TextureKey {
+    fn new(descriptor: &wgpu::TextureDescriptor<'_>) -> Self {
+        Self {
+            size: descriptor.size,
+            mip_level_count: descriptor.mip_level_count,
+            sample_count: descriptor.sample_count,
+            dimension: descriptor.dimension,
+            format: descriptor.format,
+            usage: descriptor.usage,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PooledTexture {
+    key: TextureKey,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    occupied: bool,
+    last_used: u64,
+    _allocation: Option<BudgetAllocation>,
+}
+
+/// Descriptor for [`TexturePool`].
+#[derive(Clone, Debug)]
+pub struct TexturePoolDescriptor {
+    /// Number of [`TexturePool::clear`] calls a texture may stay unused before it's dropped.
+    pub max_unused_frames: u64,
+    /// Budget the pooled textures are accounted against.
+    pub budget: Option<GpuBudget>,
+}
+
+impl Default for TexturePoolDescriptor {
+    fn default() -> Self {
+        Self {
+            max_unused_frames: 2,
+            budget: None,
+        }
+    }
+}
+
+/// A pool of transient textures, e.g. render targets of intermediate passes.
+///
+/// Textures are matched by their descriptor, so resized targets are allocated as needed while
+/// stale sizes are dropped after [`TexturePoolDescriptor::max_unused_frames`].
+#[derive(Debug)]
+pub struct TexturePool {
+    textures: Vec<PooledTexture>,
+    frame: u64,
+    max_unused_frames: u64,
+    budget: Option<GpuBudget>,
+}
+
+impl TexturePool {
+    /// Creates a new empty pool.
+    pub fn new(descriptor: &TexturePoolDescriptor) -> Self {
+        Self {
+            textures: Vec::new(),
+            frame: 0,
+            max_unused_frames: descriptor.max_unused_frames,
+            budget: descriptor.budget.clone(),
+        }
+    }
+
+    /// Occupies a vacant texture matching `descriptor`.
+    ///
+    /// Returns texture index. If no matching vacant texture is available, a new one is
+    /// allocated. The label is only used for new textures.
+    pub fn acquire(
+        &mut self,
+        device: &wgpu::Device,
+        descriptor: &wgpu::TextureDescriptor<'_>,
+    ) -> usize {
+        let key = TextureKey::new(descriptor);
+        let index = match self
+            .textures
+            .iter()
+            .position(|texture| !texture.occupied && texture.key == key)
+        {
+            Some(index) => index,
+            None => {
+                let allocation = self
+                    .budget
+                    .as_ref()
+                    .map(|budget| budget.track(estimate_size(descriptor)));
+                let texture = crate::resource_log::create_texture(device, descriptor);
+                let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+                self.textures.push(PooledTexture {
+                    key,
+                    texture,
+                    view,
+                    occupied: false,
+                    last_used: self.frame,
+                    _allocation: allocation,
+                });
+                self.textures.len() - 1
+            }
+        };
+
+        let texture = &mut self.textures[index];
+        texture.occupied = true;
+        texture.last_used = self.frame;
+        index
+    }
+
+    /// Marks an occupied texture as vacant, so it can be reused within the same frame.
+    pub fn release(&mut self, i: usize) {
+        if let Some(texture) = self.textures.get_mut(i) {
+            texture.occupied = false;
+        }
+    }
+
+    /// Marks all textures as vacant and drops textures unused for too long. Invalidates all
+    /// indices.
+    pub fn clear(&mut self) {
+        self.frame += 1;
+        let (frame, max_unused_frames) = (self.frame, self.max_unused_frames);
+        self.textures
+            .retain(|texture| frame - texture.last_used <= max_unused_frames);
+        for texture in &mut self.textures {
+            texture.occupied = false;
+        }
+    }
+
+    /// Get occupied texture by index.
+    pub fn get(&self, i: usize) -> Option<&wgpu::Texture> {
+        self.occupied(i).map(|texture| &texture.texture)
+    }
+
+    /// Get the default view of an occupied texture by index.
+    pub fn view(&self, i: usize) -> Option<&wgpu::TextureView> {
+        self.occupied(i).map(|texture| &texture.view)
+    }
+
+    /// Pool size (occupied + vacant)
+    pub fn size(&self) -> usize {
+        self.textures.len()
+    }
+
+    fn occupied(&self, i: usize) -> Option<&PooledTexture> {
+        self.textures.get(i).filter(|texture| texture.occupied)
+    }
+}
+
+/// Estimated memory of a texture in bytes, from its format, size, mip levels and sample count.
+pub fn estimate_size(descriptor: &wgpu::TextureDescriptor) -> u64 {
+    let info = descriptor.format.describe();
+    let (block_width, block_height) = info.block_dimensions;
+    (0..descriptor.mip_level_count)
+        .map(|level| {
+            let size = descriptor.mip_level_size(level).unwrap_or_default();
+            size.width.div_ceil(block_width as u32) as u64
+                * size.height.div_ceil(block_height as u32) as u64
+                * size.depth_or_array_layers as u64
+                * info.block_size as u64
+        })
+        .sum::<u64>()
+        * descriptor.sample_count as u64
+}
+
+/// Colors of the mip levels of [`create_mip_debug_texture`] and `mip_level_color` of the
+/// `wgpu_util::mip` snippet. Levels past the last share its color.
+pub const MIP_LEVEL_COLORS: [[f32; 3]; 8] = [
+    [1.0, 0.0, 0.0],
+    [1.0, 0.5, 0.0],
+    [1.0, 1.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 1.0, 1.0],
+    [0.0, 0.3, 1.0],
+    [0.6, 0.0, 1.0],
+    [0.5, 0.5, 0.5],
+];
+
+/// Creates a [`wgpu::TextureFormat::Rgba8Unorm`] texture with a full mip chain, every level
+/// filled with its color of [`MIP_LEVEL_COLORS`] in a checkerboard of single texels.
+///
+/// Bound in place of a texture of the same size, it shows which level the sampler picks,
+/// including the effects of anisotropic filtering and LOD bias, and how large texels appear.
+pub fn create_mip_debug_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    width: u32,
+    height: u32,
+) -> wgpu::Texture {
+    let descriptor = wgpu::TextureDescriptor {
+        label: Some("mip debug texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: u32::BITS - width.max(height).leading_zeros(),
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8Unorm,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+    };
+    let texture = crate::resource_log::create_texture(device, &descriptor);
+
+    for level in 0..descriptor.mip_level_count {
+        let size = descriptor.mip_level_size(level).unwrap();
+        let color = MIP_LEVEL_COLORS[(level as usize).min(MIP_LEVEL_COLORS.len() - 1)];
+        let texels: Vec<u8> = (0..size.height)
+            .flat_map(|y| (0..size.width).map(move |x| (x + y) % 2))
+            .flat_map(|odd| {
+                let shade = match odd {
+                    0 => 1.0,
+                    _ => 0.75,
+                };
+                let [r, g, b] = color.map(|c| (c * shade * 255.0).round() as u8);
+                [r, g, b, 255]
+            })
+            .collect();
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &texture,
+                mip_level: level,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: NonZeroU32::new(size.width * 4),
+                rows_per_image: None,
+            },
+            size,
+        );
+    }
+    texture
+}
+
+/// Order of the channels of source texels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SourceChannels {
+    Rgb,
+    Rgba,
+    Bgr,
+    Bgra,
+}
+
+impl SourceChannels {
+    fn count(self) -> usize {
+        match self {
+            Self::Rgb | Self::Bgr => 3,
+            Self::Rgba | Self::Bgra => 4,
+        }
+    }
+}
+
+/// Size and byte order of the channels of source texels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SourceDepth {
+    U8,
+    /// Little-endian 16-bit channels.
+    U16Le,
+    /// Big-endian 16-bit channels, as decoded from 16-bit PNGs.
+    U16Be,
+}
+
+impl SourceDepth {
+    fn size(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16Le | Self::U16Be => 2,
+        }
+    }
+}
+
+/// Layout of unsigned normalized source texels, converted to the format of the texture on
+/// upload.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SourceLayout {
+    pub channels: SourceChannels,
+    pub depth: SourceDepth,
+    /// Alpha of sources without an alpha channel, between `0.0` and `1.0`.
+    pub alpha: f32,
+    /// Premultiply the color channels by alpha with [`crate::blend::premultiply`].
+    pub premultiply: bool,
+}
+
+impl Default for SourceLayout {
+    fn default() -> Self {
+        Self {
+            channels: SourceChannels::Rgba,
+            depth: SourceDepth::U8,
+            alpha: 1.0,
+            premultiply: false,
+        }
+    }
+}
+
+/// Whether textures of a float format can be sampled with filtering samplers, see
+/// [`FloatFiltering::detect`].
+///
+/// 32 bit float formats are only filterable on some adapters and only with
+/// [`wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES`] enabled. Using them with a
+/// filtering sampler otherwise fails validation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FloatFiltering {
+    Filterable,
+    NonFiltering,
+}
+
+impl FloatFiltering {
+    /// Filtering support of `format` on `device`, created from `adapter`.
+    pub fn detect(
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Self {
+        let features = match device
+            .features()
+            .contains(wgpu::Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES)
+        {
+            true => adapter.get_texture_format_features(format),
+            false => format.describe().guaranteed_format_features,
+        };
+        match features
+            .flags
+            .contains(wgpu::TextureFormatFeatureFlags::FILTERABLE)
+        {
+            true => Self::Filterable,
+            false => Self::NonFiltering,
+        }
+    }
+
+    pub fn is_filterable(self) -> bool {
+        self == Self::Filterable
+    }
+
+    /// Sample type of bind group layout entries.
+    pub fn sample_type(self) -> wgpu::TextureSampleType {
+        wgpu::TextureSampleType::Float {
+            filterable: self.is_filterable(),
+        }
+    }
+
+    /// Binding type of samplers used with the texture.
+    pub fn sampler_binding_type(self) -> wgpu::SamplerBindingType {
+        match self {
+            Self::Filterable => wgpu::SamplerBindingType::Filtering,
+            Self::NonFiltering => wgpu::SamplerBindingType::NonFiltering,
+        }
+    }
+
+    /// Filter mode of samplers used with the texture.
+    pub fn filter_mode(self) -> wgpu::FilterMode {
+        match self {
+            Self::Filterable => wgpu::FilterMode::Linear,
+            Self::NonFiltering => wgpu::FilterMode::Nearest,
+        }
+    }
+}
+
+/// Returns `format` if it is filterable on `device`, otherwise its 16 bit float counterpart,
+/// e.g. [`wgpu::TextureFormat::Rgba16Float`] for [`wgpu::TextureFormat::Rgba32Float`].
+///
+/// Meant for choosing the format of loaded HDR images and intermediates sampled by filtering
+/// passes. Falling back is logged. Formats without a 16 bit counterpart are returned as they are.
+pub fn filterable_float_format(
+    adapter: &wgpu::Adapter,
+    device: &wgpu::Device,
+    format: wgpu::TextureFormat,
+) -> wgpu::TextureFormat {
+    use wgpu::TextureFormat as F;
+
+    if FloatFiltering::detect(adapter, device, format).is_filterable() {
+        return format;
+    }
+    let fallback = match format {
+        F::R32Float => F::R16Float,
+        F::Rg32Float => F::Rg16Float,
+        F::Rgba32Float => F::Rgba16Float,
+        _ => return format,
+    };
+    log::info!(
+        "{:?} isn't filterable, using {:?} instead",
+        format,
+        fallback
+    );
+    fallback
+}
+
+/// Trade-off between size and quality in [`TextureCompressionSupport::choose_format`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CompressionQuality {
+    /// Smallest formats, e.g. BC1 or ASTC 8x8.
+    Low,
+    /// ASTC 6x6, otherwise like [`Self::Low`].
+    Medium,
+    /// Best quality formats, e.g. BC7 or ASTC 4x4.
+    High,
+}
+
+/// Block compressed texture formats supported by an adapter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TextureCompressionSupport {
+    /// BC1 to BC7, usually on desktop.
+    pub bc: bool,
+    /// ETC2 and EAC, usually on mobile and WebGL.
+    pub etc2: bool,
+    /// ASTC with LDR channels.
+    pub astc_ldr: bool,
+    /// ASTC with HDR channels.
+    pub astc_hdr: bool,
+}
+
+impl TextureCompressionSupport {
+    /// Formats supported by `adapter`. The corresponding [`Self::features`] must be requested
+    /// when creating the device.
+    pub fn detect(adapter: &wgpu::Adapter) -> Self {
+        Self::from_features(adapter.features())
+    }
+
+    pub fn from_features(features: wgpu::Features) -> Self {
+        Self {
+            bc: features.contains(wgpu::Features::TEXTURE_COMPRESSION_BC),
+            etc2: features.contains(wgpu::Features::TEXTURE_COMPRESSION_ETC2),
+            astc_ldr: features.contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR),
+            astc_hdr: features.contains(wgpu::Features::TEXTURE_COMPRESSION_ASTC_HDR),
+        }
+    }
+
+    /// Features enabling the supported formats.
+    pub fn features(&self) -> wgpu::Features {
+        let mut features = wgpu::Features::empty();
+        features.set(wgpu::Features::TEXTURE_COMPRESSION_BC, self.bc);
+        features.set(wgpu::Features::TEXTURE_COMPRESSION_ETC2, self.etc2);
+        features.set(wgpu::Features::TEXTURE_COMPRESSION_ASTC_LDR, self.astc_ldr);
+        features.set(wgpu::Features::TEXTURE_COMPRESSION_ASTC_HDR, self.astc_hdr);
+        features
+    }
+
+    /// Chooses a compressed format for color textures, preferring BC, then ASTC, then ETC2.
+    ///
+    /// LDR formats are sRGB. Returns `None` if no supported format fits, e.g. for HDR textures
+    /// with alpha without ASTC HDR, in which case textures should stay uncompressed.
+    pub fn choose_format(
+        &self,
+        quality: CompressionQuality,
+        alpha: bool,
+        hdr: bool,
+    ) -> Option<wgpu::TextureFormat> {
+        use wgpu::TextureFormat as F;
+
+        let astc_block = match quality {
+            CompressionQuality::Low => wgpu::AstcBlock::B8x8,
+            CompressionQuality::Medium => wgpu::AstcBlock::B6x6,
+            CompressionQuality::High => wgpu::AstcBlock::B4x4,
+        };
+        let astc = |channel| F::Astc {
+            block: astc_block,
+            channel,
+        };
+
+        if hdr {
+            return match (self.bc && !alpha, self.astc_hdr) {
+                (true, _) => Some(F::Bc6hRgbUfloat),
+                (false, true) => Some(astc(wgpu::AstcChannel::Hdr)),
+                (false, false) => None,
+            };
+        }
+        if self.bc {
+            return Some(match (quality, alpha) {
+                (CompressionQuality::High, _) => F::Bc7RgbaUnormSrgb,
+                (_, true) => F::Bc3RgbaUnormSrgb,
+                (_, false) => F::Bc1RgbaUnormSrgb,
+            });
+        }
+        if self.astc_ldr {
+            return Some(astc(wgpu::AstcChannel::UnormSrgb));
+        }
+        if self.etc2 {
+            return Some(match alpha {
+                true => F::Etc2Rgba8UnormSrgb,
+                false => F::Etc2Rgb8UnormSrgb,
+            });
+        }
+        None
+    }
+}
+
+/// Descriptor for [`create_texture_init`].
+#[derive(Clone, Debug)]
+pub struct TextureInitDescriptor<'a> {
+    /// [`wgpu::TextureUsages::COPY_DST`] is added to its usages.
+    pub texture: wgpu::TextureDescriptor<'a>,
+    /// Texels of all mip levels of all array layers, layer by layer, tightly packed.
+    pub contents: &'a [u8],
+    /// Layout of `contents`, or `None` if they are already in the texture format.
+    pub source: Option<SourceLayout>,
+}
+
+/// Creates a texture and uploads its contents, converting them from the source layout.
+///
+/// Conversion happens on the CPU and supports [`wgpu::TextureFormat::Rgba8Unorm`],
+/// [`wgpu::TextureFormat::Bgra8Unorm`] and their sRGB variants,
+/// [`wgpu::TextureFormat::Rgba16Unorm`], [`wgpu::TextureFormat::Rgba16Float`] and
+/// [`wgpu::TextureFormat::Rgba32Float`]. Channel values are stored as they are, so sRGB encoded
+/// sources must go into sRGB formats to be decoded.
+pub fn create_texture_init(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    descriptor: &TextureInitDescriptor<'_>,
+) -> wgpu::Texture {
+    let converted;
+    let contents = match &descriptor.source {
+        Some(source) => {
+            converted = convert_texels(descriptor.contents, source, descriptor.texture.format);
+            &converted[..]
+        }
+        None => descriptor.contents,
+    };
+
+    let mut texture_descriptor = descriptor.texture.clone();
+    texture_descriptor.usage |= wgpu::TextureUsages::COPY_DST;
+    let texture = crate::resource_log::create_texture(device, &texture_descriptor);
+
+    let info = texture_descriptor.format.describe();
+    let (block_width, block_height) = info.block_dimensions;
+    let mut offset = 0;
+    for layer in 0..texture_descriptor.array_layer_count() {
+        for level in 0..texture_descriptor.mip_level_count {
+            let mut size = texture_descriptor.mip_level_size(level).unwrap();
+            if texture_descriptor.dimension != wgpu::TextureDimension::D3 {
+                size.depth_or_array_layers = 1;
+            }
+            let physical_size = size.physical_size(texture_descriptor.format);
+            let bytes_per_row = physical_size.width / block_width as u32 * info.block_size as u32;
+            let rows = physical_size.height / block_height as u32;
+            let end = offset + (bytes_per_row * rows * size.depth_or_array_layers) as usize;
+            assert!(
+                end <= contents.len(),
+                "contents must cover all mip levels and array layers"
+            );
+
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: level,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &contents[offset..end],
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: NonZeroU32::new(bytes_per_row),
+                    rows_per_image: NonZeroU32::new(rows),
+                },
+                physical_size,
+            );
+            offset = end;
+        }
+    }
+    texture
+}
+
+/// Converts texels of `source` layout to `format`. See [`create_texture_init`] for the supported
+/// formats.
+///
+/// # Panics
+///
+/// If `format` isn't supported or `contents` isn't a whole number of texels.
+pub fn convert_texels(
+    contents: &[u8],
+    source: &SourceLayout,
+    format: wgpu::TextureFormat,
+) -> Vec<u8> {
+    use wgpu::TextureFormat as F;
+
+    let channels = source.channels.count();
+    let texel_size = channels * source.depth.size();
+    assert!(
+        contents.len().is_multiple_of(texel_size),
+        "contents must be a whole number of texels"
+    );
+    let target_size = match format {
+        F::Rgba8Unorm | F::Rgba8UnormSrgb | F::Bgra8Unorm | F::Bgra8UnormSrgb => 4,
+        F::Rgba16Unorm | F::Rgba16Float => 8,
+        F::Rgba32Float => 16,
+        _ => panic!("texel conversion to {format:?} is unsupported"),
+    };
+
+    let mut texels = Vec::with_capacity(contents.len() / texel_size * target_size);
+    for texel in contents.chunks_exact(texel_size) {
+        let channel = |i: usize| match source.depth {
+            SourceDepth::U8 => texel[i] as f32 / 255.0,
+            SourceDepth::U16Le => {
+                u16::from_le_bytes([texel[2 * i], texel[2 * i + 1]]) as f32 / 65535.0
+            }
+            SourceDepth::U16Be => {
+                u16::from_be_bytes([texel[2 * i], texel[2 * i + 1]]) as f32 / 65535.0
+            }
+        };
+        let alpha = match channels {
+            4 => channel(3),
+            _ => source.alpha.clamp(0.0, 1.0),
+        };
+        let rgba = match source.channels {
+            SourceChannels::Rgb | SourceChannels::Rgba => {
+                [channel(0), channel(1), channel(2), alpha]
+            }
+            SourceChannels::Bgr | SourceChannels::Bgra => {
+                [channel(2), channel(1), channel(0), alpha]
+            }
+        };
+        let rgba = match source.premultiply {
+            true => crate::blend::premultiply(rgba),
+            false => rgba,
+        };
+
+        match format {
+            F::Rgba8Unorm | F::Rgba8UnormSrgb => {
+                texels.extend(rgba.map(|c| (c * 255.0).round() as u8));
+            }
+            F::Bgra8Unorm | F::Bgra8UnormSrgb => {
+                let [r, g, b, a] = rgba;
+                texels.extend([b, g, r, a].map(|c| (c * 255.0).round() as u8));
+            }
+            F::Rgba16Unorm => {
+                texels.extend(
+                    rgba.iter()
+                        .flat_map(|c| ((c * 65535.0).round() as u16).to_le_bytes()),
+                );
+            }
+            F::Rgba16Float => {
+                texels.extend(
+                    rgba.iter()
+                        .flat_map(|&c| crate::packing::f16_from_f32(c).to_le_bytes()),
+                );
+            }
+            _ => texels.extend(rgba.iter().flat_map(|c| c.to_le_bytes())),
+        }
+    }
+    texels
+}
+
+type ReloadFn<K> =
+    Box<dyn FnMut(&K, &AsyncUploadSender) -> Option<(TextureInfo, AssetFuture)> + Send>;
+
+/// Descriptor for [`TextureCache`].
+#[derive(Clone, Debug)]
+pub struct TextureCacheDescriptor {
+    /// Memory in bytes resident textures may occupy before the least recently used ones are
+    /// evicted.
+    pub budget: u64,
+    /// Budget shared with other allocating types the resident textures are accounted against.
+    pub gpu_budget: Option<GpuBudget>,
+}
+
+/// A texture resident in a [`TextureCache`].
+#[derive(Debug)]
+pub struct CachedTexture {
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    size: u64,
+    id: ResourceId,
+    last_used: u64,
+    _allocation: Option<BudgetAllocation>,
+}
+
+impl CachedTexture {
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
+    /// The default view of the texture.
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.view
+    }
+
+    /// Memory of the texture in bytes, as accounted against the budget.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Identity of the texture and its view, new for every insertion or reload of a key.
+    pub fn id(&self) -> ResourceId {
+        self.id
+    }
+}
+
+/// Textures keyed by user asset ids, evicting the least recently used ones when exceeding a
+/// memory budget.
+///
+/// Evicted textures may still be referenced by recorded commands, so they are only destroyed
+/// once the GPU finished the work submitted before the next [`Self::after_submit`]. With a
+/// reload hook, accessing an evicted key pushes its contents again through an
+/// [`AsyncUploadQueue`](crate::upload::AsyncUploadQueue); the texture is resident again once
+/// the upload completed.
+pub struct TextureCache<K> {
+    entries: HashMap<K, CachedTexture>,
+    /// Reloads which didn't complete yet, with the size of their texture.
+    pending: HashMap<K, (u64, AssetFuture)>,
+    /// Evicted textures, destroyed after the next submit.
+    evicted: Vec<wgpu::Texture>,
+
+    budget: u64,
+    used: u64,
+    gpu_budget: Option<GpuBudget>,
+    /// Incremented on every access, for ordering entries by last use.
+    tick: u64,
+
+    sender: Option<AsyncUploadSender>,
+    reload: Option<ReloadFn<K>>,
+}
+
+impl<K: fmt::Debug> fmt::Debug for TextureCache<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TextureCache")
+            .field("entries", &self.entries)
+            .field("pending", &self.pending)
+            .field("evicted", &self.evicted)
+            .field("budget", &self.budget)
+            .field("used", &self.used)
+            .field("gpu_budget", &self.gpu_budget)
+            .field("reload", &self.reload.is_some())
+            .finish()
+    }
+}
+
+impl<K: Clone + Eq + Hash> TextureCache<K> {
+    /// Creates a new empty cache.
+    pub fn new(descriptor: &TextureCacheDescriptor) -> Self {
+        Self {
+            entries: HashMap::new(),
+            pending: HashMap::new(),
+            evicted: Vec::new(),
+
+            budget: descriptor.budget,
+            used: 0,
+            gpu_budget: descriptor.gpu_budget.clone(),
+            tick: 0,
+
+            sender: None,
+            reload: None,
+        }
+    }
+
+    /// Sets the hook reloading evicted textures.
+    ///
+    /// It is called with the key of a missing texture and should push its contents through the
+    /// sender, returning the info of the pushed texture and its future. Returning `None` leaves
+    /// the key missing.
+    pub fn set_reload(
+        &mut self,
+        sender: AsyncUploadSender,
+        reload: impl FnMut(&K, &AsyncUploadSender) -> Option<(TextureInfo, AssetFuture)>
+            + Send
+            + 'static,
+    ) {
+        self.sender = Some(sender);
+        self.reload = Some(Box::new(reload));
+    }
+
+    /// Inserts a texture occupying `size` bytes, e.g. from [`estimate_size`], evicting the least
+    /// recently used textures if the budget is exceeded.
+    ///
+    /// A texture previously stored under `key` is evicted.
+    pub fn insert(&mut self, key: K, texture: wgpu::Texture, size: u64) {
+        self.remove(&key);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        self.tick += 1;
+        self.entries.insert(
+            key.clone(),
+            CachedTexture {
+                texture,
+                view,
+                size,
+                id: ResourceId::unique(),
+                last_used: self.tick,
+                _allocation: self.gpu_budget.as_ref().map(|budget| budget.track(size)),
+            },
+        );
+        self.used += size;
+        self.evict_over_budget(Some(&key));
+    }
+
+    /// Inserts a texture created from compressed blocks, e.g. render-to-texture results
+    /// compressed by a [`BlockCompressor`](crate::compress::BlockCompressor), recording the copy
+    /// into `encoder`.
+    ///
+    /// The device must have [`wgpu::Features::TEXTURE_COMPRESSION_BC`] enabled.
+    pub fn insert_compressed(
+        &mut self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        key: K,
+        blocks: &CompressedBlocks,
+    ) {
+        let texture = blocks.create_texture(
+            device,
+            encoder,
+            Some("compressed cached texture"),
+            wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let size = blocks.unpadded_bytes_per_row() as u64 * blocks.block_rows() as u64;
+        self.insert(key, texture, size);
+    }
+
+    /// Gets a resident texture, marking it as recently used.
+    ///
+    /// If `key` isn't resident, a completed reload is inserted first, or a new one started
+    /// through the reload hook. Returns `None` while the texture is being reloaded.
+    pub fn get(&mut self, key: &K) -> Option<&CachedTexture> {
+        if !self.entries.contains_key(key) {
+            self.poll_reload(key);
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|entry| {
+            entry.last_used = tick;
+            &*entry
+        })
+    }
+
+    /// Gets a resident texture without marking it as used or reloading it.
+    pub fn peek(&self, key: &K) -> Option<&CachedTexture> {
+        self.entries.get(key)
+    }
+
+    /// Whether the texture of `key` is resident.
+    pub fn contains(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// Evicts the texture of `key` and cancels its reload. Returns whether it was resident.
+    pub fn remove(&mut self, key: &K) -> bool {
+        self.pending.remove(key);
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.used -= entry.size;
+                self.evicted.push(entry.texture);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Changes the budget, evicting textures if it's exceeded.
+    pub fn set_budget(&mut self, budget: u64) {
+        self.budget = budget;
+        self.evict_over_budget(None);
+    }
+
+    pub fn budget(&self) -> u64 {
+        self.budget
+    }
+
+    /// Memory occupied by resident textures in bytes.
+    pub fn used(&self) -> u64 {
+        self.used
+    }
+
+    /// Number of resident textures.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Must be called after submitting the commands of the frame. Destroys the evicted textures
+    /// once the GPU is done with them.
+    pub fn after_submit(&mut self, queue: &wgpu::Queue) {
+        if self.evicted.is_empty() {
+            return;
+        }
+        let evicted = std::mem::take(&mut self.evicted);
+        queue.on_submitted_work_done(move || {
+            for texture in evicted {
+                texture.destroy();
+            }
+        });
+    }
+
+    fn poll_reload(&mut self, key: &K) {
+        if let Some((size, future)) = self.pending.get_mut(key) {
+            let size = *size;
+            match future.try_take() {
+                Some(Asset::Texture(texture)) => self.insert(key.clone(), texture, size),
+                Some(Asset::Buffer(_)) => {
+                    log::warn!("texture cache reload resolved to a buffer");
+                    self.pending.remove(key);
+                }
+                None => {}
+            }
+            return;
+        }
+
+        if let (Some(reload), Some(sender)) = (&mut self.reload, &self.sender) {
+            if let Some((info, future)) = reload(key, sender) {
+                let size = info.unpadded_bytes_per_row() as u64 * info.height as u64;
+                self.pending.insert(key.clone(), (size, future));
+            }
+        }
+    }
+
+    /// Evicts least recently used textures, except `keep`, until the budget is met.
+    fn evict_over_budget(&mut self, keep: Option<&K>) {
+        while self.used > self.budget {
+            let lru = self
+                .entries
+                .iter()
+                .filter(|(key, _)| Some(*key) != keep)
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            match lru {
+                Some(key) => {
+                    self.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+//! Rendering images larger than the maximum texture size in tiles.
+
+use crate::{
+    camera::Matrix4,
+    readback::{read_texture, TextureInfo},
+};
+
+/// Region of the image rendered by one call of the [`render_tiled`] callback.
+///
+/// Tiles all have the same size and cover the image from the top left, the parts of tiles on the
+/// right and bottom edges exceeding the image are discarded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Tile {
+    /// Position of the top left texel of the tile in the image.
+    pub x: u32,
+    pub y: u32,
+    /// Size of the render target.
+    pub size: u32,
+    pub image_width: u32,
+    pub image_height: u32,
+}
+
+impl Tile {
+    /// Column-major matrix mapping clip space of the whole image to clip space of the tile.
+    ///
+    /// Multiply it onto the projection, i.e. `tile.projection() * projection`, so the tile shows
+    /// its part of the image. Since tiles are aligned to texels, the stitched image matches
+    /// rendering the whole image at once, except for screen space effects crossing tile edges.
+    pub fn projection(&self) -> Matrix4 {
+        let (width, height) = (self.image_width as f32, self.image_height as f32);
+        let size = self.size as f32;
+
+        let scale_x = width / size;
+        let scale_y = height / size;
+        // Center of the tile in normalized device coordinates of the image, y pointing up.
+        let center_x = (2.0 * self.x as f32 + size) / width - 1.0;
+        let center_y = 1.0 - (2.0 * self.y as f32 + size) / height;
+        [
+            [scale_x, 0.0, 0.0, 0.0],
+            [0.0, scale_y, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [-center_x * scale_x, -center_y * scale_y, 0.0, 1.0],
+        ]
+    }
+}
+
+/// Renders an image of `info.width` x `info.height` texels in tiles and stitches them into
+/// tightly packed texels, row by row from top to bottom.
+///
+/// `tile_size` is clamped to [`wgpu::Limits::max_texture_dimension_2d`] and the image size. For
+/// every tile, `callback` records the scene into the encoder, drawing into the view of the
+/// square tile target with the projection adjusted by [`Tile::projection`]. Blocks until every
+/// tile is read back.
+pub fn render_tiled(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    info: &TextureInfo,
+    tile_size: u32,
+    mut callback: impl FnMut(&mut wgpu::CommandEncoder, &wgpu::TextureView, &Tile),
+) -> Result<Vec<u8>, wgpu::BufferAsyncError> {
+    let size = tile_size
+        .min(device.limits().max_texture_dimension_2d)
+        .min(info.width.max(info.height))
+        .max(1);
+    let tile_info = TextureInfo {
+        width: size,
+        height: size,
+        format: info.format,
+    };
+    let target = crate::resource_log::create_texture(
+        device,
+        &wgpu::TextureDescriptor {
+            label: Some("tile render target"),
+            size: wgpu::Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: info.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        },
+    );
+    let view = target.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let texel_size = info.texel_size() as usize;
+    let row_size = info.width as usize * texel_size;
+    let mut image = vec![0; row_size * info.height as usize];
+    for y in (0..info.height).step_by(size as usize) {
+        for x in (0..info.width).step_by(size as usize) {
+            let tile = Tile {
+                x,
+                y,
+                size,
+                image_width: info.width,
+                image_height: info.height,
+            };
+            let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("tile render encoder"),
+            });
+            callback(&mut encoder, &view, &tile);
+            queue.submit(Some(encoder.finish()));
+
+            let texels = read_texture(device, queue, &target, &tile_info)?;
+            let tile_row_size = size as usize * texel_size;
+            let visible_width = (info.width - x).min(size) as usize * texel_size;
+            let visible_height = (info.height - y).min(size) as usize;
+            for row in 0..visible_height {
+                let src = row * tile_row_size;
+                let dst = (y as usize + row) * row_size + x as usize * texel_size;
+                image[dst..dst + visible_width].copy_from_slice(&texels[src..src + visible_width]);
+            }
+        }
+    }
+    target.destroy();
+
+    Ok(image)
+}
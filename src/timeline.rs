@@ -0,0 +1,345 @@
+//! Recording which resources the operations of a frame access.
+//!
+//! While recording, render passes, dispatches and copies issued by wgpu-util append the resources
+//! they read and write to a global [`Timeline`]. Its [`Hazard`]s are the points where wgpu has to
+//! synchronize, which can be printed as text or rendered with Graphviz.
+//!
+//! Resources are identified by the address of their wgpu handle, so a texture and its views are
+//! distinct resources unless they're given the same name with [`name`]. Unnamed resources are
+//! shown with their address. Operations built directly with wgpu can be added with [`record`].
+
+use std::{fmt::Write, sync::Mutex};
+
+static RECORDING: Mutex<Option<Vec<Operation>>> = Mutex::new(None);
+static NAMES: Mutex<Vec<(ResourceKey, String)>> = Mutex::new(Vec::new());
+
+/// Identity of a resource, the address of its handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceKey {
+    pub kind: ResourceKind,
+    pub address: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+    TextureView,
+}
+
+/// A resource whose accesses can be recorded.
+pub trait TrackedResource {
+    fn resource_key(&self) -> ResourceKey;
+}
+
+macro_rules! impl_tracked_resource {
+    ($($ty:ty => $kind:ident),*) => {$(
+        impl TrackedResource for $ty {
+            fn resource_key(&self) -> ResourceKey {
+                ResourceKey {
+                    kind: ResourceKind::$kind,
+                    address: self as *const Self as usize,
+                }
+            }
+        }
+    )*};
+}
+
+impl_tracked_resource!(
+    wgpu::Buffer => Buffer,
+    wgpu::Texture => Texture,
+    wgpu::TextureView => TextureView
+);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OperationKind {
+    RenderPass,
+    Dispatch,
+    Copy,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// An access of a resource by an [`Operation`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResourceAccess {
+    pub resource: ResourceKey,
+    /// Role of the resource in the operation, e.g. `input`.
+    pub role: String,
+    /// Name given with [`name`].
+    pub name: Option<String>,
+    pub access: Access,
+}
+
+impl ResourceAccess {
+    /// The name, or the kind and address for unnamed resources.
+    pub fn identity(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("{:?} @{:x}", self.resource.kind, self.resource.address),
+        }
+    }
+}
+
+/// A recorded operation and the resources it accessed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Operation {
+    pub kind: OperationKind,
+    pub label: String,
+    pub accesses: Vec<ResourceAccess>,
+}
+
+impl Operation {
+    pub fn new(kind: OperationKind, label: &str) -> Self {
+        Self {
+            kind,
+            label: label.to_owned(),
+            accesses: Vec::new(),
+        }
+    }
+
+    pub fn render_pass(label: &str) -> Self {
+        Self::new(OperationKind::RenderPass, label)
+    }
+
+    pub fn dispatch(label: &str) -> Self {
+        Self::new(OperationKind::Dispatch, label)
+    }
+
+    pub fn copy(label: &str) -> Self {
+        Self::new(OperationKind::Copy, label)
+    }
+
+    /// Adds a read of `resource`, which has `role` in the operation.
+    pub fn read(self, role: &str, resource: &impl TrackedResource) -> Self {
+        self.access(role, resource.resource_key(), Access::Read)
+    }
+
+    /// Adds a write of `resource`, which has `role` in the operation.
+    pub fn write(self, role: &str, resource: &impl TrackedResource) -> Self {
+        self.access(role, resource.resource_key(), Access::Write)
+    }
+
+    fn access(mut self, role: &str, resource: ResourceKey, access: Access) -> Self {
+        let name = NAMES
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(key, _)| *key == resource)
+            .map(|(_, name)| name.clone());
+        self.accesses.push(ResourceAccess {
+            resource,
+            role: role.to_owned(),
+            name,
+            access,
+        });
+        self
+    }
+}
+
+/// Starts recording, discarding anything recorded before.
+pub fn start() {
+    *RECORDING.lock().unwrap() = Some(Vec::new());
+}
+
+/// Stops recording and returns the timeline.
+pub fn stop() -> Timeline {
+    Timeline {
+        operations: RECORDING.lock().unwrap().take().unwrap_or_default(),
+    }
+}
+
+/// Whether operations are currently recorded.
+pub fn is_recording() -> bool {
+    RECORDING.lock().unwrap().is_some()
+}
+
+/// Records an operation if recording. The operation is only built while recording.
+pub fn record(operation: impl FnOnce() -> Operation) {
+    if let Some(operations) = RECORDING.lock().unwrap().as_mut() {
+        operations.push(operation());
+    }
+}
+
+/// Names `resource` in all operations recorded afterwards.
+///
+/// Names outlive the resource, rename or [`clear_names`] when a new resource may reuse the
+/// address of a dropped one.
+pub fn name(resource: &impl TrackedResource, name: &str) {
+    let key = resource.resource_key();
+    let mut names = NAMES.lock().unwrap();
+    match names.iter_mut().find(|(k, _)| *k == key) {
+        Some((_, n)) => *n = name.to_owned(),
+        None => names.push((key, name.to_owned())),
+    }
+}
+
+/// Forgets all names given with [`name`].
+pub fn clear_names() {
+    NAMES.lock().unwrap().clear();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum HazardKind {
+    ReadAfterWrite,
+    WriteAfterRead,
+    WriteAfterWrite,
+}
+
+/// A dependency between two operations accessing the same resource, requiring synchronization.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hazard {
+    /// Index of the earlier operation.
+    pub from: usize,
+    /// Index of the later operation.
+    pub to: usize,
+    pub resource: String,
+    pub kind: HazardKind,
+}
+
+/// A recorded sequence of operations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Timeline {
+    pub operations: Vec<Operation>,
+}
+
+impl Timeline {
+    /// Dependencies between the operations, in order of the later operation.
+    ///
+    /// Resources are matched by name, so named textures and their views are the same resource.
+    pub fn hazards(&self) -> Vec<Hazard> {
+        #[derive(Default)]
+        struct State {
+            last_write: Option<usize>,
+            reads: Vec<usize>,
+        }
+        let mut states: Vec<(String, State)> = Vec::new();
+        let mut hazards = Vec::new();
+
+        for (i, operation) in self.operations.iter().enumerate() {
+            // Reads before writes, an operation reading and writing a resource reads old contents.
+            let mut accesses: Vec<&ResourceAccess> = operation.accesses.iter().collect();
+            accesses.sort_by_key(|a| a.access == Access::Write);
+
+            for access in accesses {
+                let name = access.identity();
+                let state = match states.iter().position(|(n, _)| *n == name) {
+                    Some(index) => &mut states[index].1,
+                    None => {
+                        states.push((name.clone(), State::default()));
+                        &mut states.last_mut().unwrap().1
+                    }
+                };
+                let mut push = |from, kind| {
+                    if from != i {
+                        hazards.push(Hazard {
+                            from,
+                            to: i,
+                            resource: name.clone(),
+                            kind,
+                        });
+                    }
+                };
+
+                match access.access {
+                    Access::Read => {
+                        if let Some(from) = state.last_write {
+                            push(from, HazardKind::ReadAfterWrite);
+                        }
+                        state.reads.push(i);
+                    }
+                    Access::Write => {
+                        if state.reads.is_empty() {
+                            if let Some(from) = state.last_write {
+                                push(from, HazardKind::WriteAfterWrite);
+                            }
+                        }
+                        for from in state.reads.drain(..) {
+                            push(from, HazardKind::WriteAfterRead);
+                        }
+                        state.last_write = Some(i);
+                    }
+                }
+            }
+        }
+
+        hazards.dedup();
+        hazards
+    }
+
+    /// Lists the operations with their accesses, followed by the hazards.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for (i, operation) in self.operations.iter().enumerate() {
+            writeln!(text, "{:>4} {:?} {:?}", i, operation.kind, operation.label).unwrap();
+            for access in &operation.accesses {
+                let access_name = match access.access {
+                    Access::Read => "read ",
+                    Access::Write => "write",
+                };
+                writeln!(
+                    text,
+                    "       {} {}: {}",
+                    access_name,
+                    access.role,
+                    access.identity()
+                )
+                .unwrap();
+            }
+        }
+
+        let hazards = self.hazards();
+        if !hazards.is_empty() {
+            writeln!(text, "hazards:").unwrap();
+        }
+        for hazard in hazards {
+            writeln!(
+                text,
+                "{:>4} -> {:<4} {:?} on {}",
+                hazard.from, hazard.to, hazard.kind, hazard.resource
+            )
+            .unwrap();
+        }
+        text
+    }
+
+    /// Graphviz graph with the operations as nodes and the hazards as edges.
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+
+        let mut dot = String::from("digraph timeline {\n    node [shape=box];\n");
+        for (i, operation) in self.operations.iter().enumerate() {
+            writeln!(
+                dot,
+                "    op{} [label=\"{}: {:?}\\n{}\"];",
+                i,
+                i,
+                operation.kind,
+                escape(&operation.label)
+            )
+            .unwrap();
+        }
+        for hazard in self.hazards() {
+            let style = match hazard.kind {
+                HazardKind::ReadAfterWrite => "solid",
+                HazardKind::WriteAfterRead => "dashed",
+                HazardKind::WriteAfterWrite => "bold",
+            };
+            writeln!(
+                dot,
+                "    op{} -> op{} [label=\"{}\", style={}];",
+                hazard.from,
+                hazard.to,
+                escape(&hazard.resource),
+                style
+            )
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
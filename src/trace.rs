@@ -0,0 +1,400 @@
+//! Recording and replaying wgpu-util operations.
+//!
+//! While recording, buffer creations and uploads (including their contents), shader modules,
+//! pipeline creations and dispatches performed through this crate are appended to a global
+//! [`Trace`]. A saved trace can be replayed against a fresh device to reproduce bugs.
+//!
+//! Replays reproduce data, not work: buffers and shader modules are recreated and uploads
+//! repeated, so the inputs of a failing pass can be inspected or fed to it again. Pipeline
+//! creations and dispatches only log what ran in which order, since the layouts and bind
+//! groups they use aren't recorded.
+//!
+//! Only operations going through wgpu-util are recorded, direct wgpu calls are invisible.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+/// Identifies a resource inside a [`Trace`].
+pub type ResourceId = u64;
+
+static RECORDING: Mutex<Option<Vec<TraceEvent>>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A recorded operation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TraceEvent {
+    CreateBuffer {
+        id: ResourceId,
+        label: Option<String>,
+        size: wgpu::BufferAddress,
+        usage: wgpu::BufferUsages,
+        contents: Vec<u8>,
+    },
+    /// `buffer` is `None` if the written buffer isn't tracked by the trace.
+    WriteBuffer {
+        buffer: Option<ResourceId>,
+        offset: wgpu::BufferAddress,
+        data: Vec<u8>,
+    },
+    CreateShaderModule {
+        id: ResourceId,
+        label: Option<String>,
+        source: String,
+    },
+    /// Logged in order but not recreated on replay, see the [module documentation](self).
+    CreatePipeline { label: Option<String> },
+    /// Logged in order but not re-executed on replay, see the [module documentation](self).
+    Dispatch {
+        label: Option<String>,
+        workgroups: [u32; 3],
+    },
+}
+
+/// Starts recording, discarding anything recorded before.
+pub fn start() {
+    *RECORDING.lock().unwrap() = Some(Vec::new());
+}
+
+/// Stops recording and returns the trace.
+pub fn stop() -> Trace {
+    Trace {
+        events: RECORDING.lock().unwrap().take().unwrap_or_default(),
+    }
+}
+
+/// Whether operations are currently recorded.
+pub fn is_recording() -> bool {
+    RECORDING.lock().unwrap().is_some()
+}
+
+/// Records an event if recording. The event is only built while recording.
+pub fn record(event: impl FnOnce() -> TraceEvent) {
+    if let Some(events) = RECORDING.lock().unwrap().as_mut() {
+        events.push(event());
+    }
+}
+
+/// Allocates a new resource id.
+pub fn next_id() -> ResourceId {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Records a dispatch of `workgroups`.
+pub fn record_dispatch(label: wgpu::Label, workgroups: [u32; 3]) {
+    record(|| TraceEvent::Dispatch {
+        label: label.map(str::to_owned),
+        workgroups,
+    });
+}
+
+pub(crate) fn record_create_buffer(descriptor: &crate::BufferInitDescriptor) -> ResourceId {
+    let id = next_id();
+    record(|| TraceEvent::CreateBuffer {
+        id,
+        label: descriptor.label.map(str::to_owned),
+        size: descriptor
+            .size
+            .unwrap_or(descriptor.contents.len() as wgpu::BufferAddress),
+        usage: descriptor.usage,
+        contents: descriptor.contents.to_vec(),
+    });
+    id
+}
+
+pub(crate) fn record_write_buffer(
+    buffer: Option<ResourceId>,
+    offset: wgpu::BufferAddress,
+    data: &[u8],
+) {
+    record(|| TraceEvent::WriteBuffer {
+        buffer,
+        offset,
+        data: data.to_vec(),
+    });
+}
+
+pub(crate) fn record_shader_module(label: wgpu::Label, source: &str) -> ResourceId {
+    let id = next_id();
+    record(|| TraceEvent::CreateShaderModule {
+        id,
+        label: label.map(str::to_owned),
+        source: source.to_owned(),
+    });
+    id
+}
+
+pub(crate) fn record_pipeline(label: wgpu::Label) {
+    record(|| TraceEvent::CreatePipeline {
+        label: label.map(str::to_owned),
+    });
+}
+
+/// 64 bit FNV-1a hash, stable across platforms and versions.
+pub fn hash(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Error returned when loading or replaying a [`Trace`].
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    /// A line of the log couldn't be parsed.
+    Parse {
+        line: usize,
+    },
+    /// The data of a line doesn't match its recorded hash.
+    HashMismatch {
+        line: usize,
+    },
+    /// A write refers to a buffer not created in the trace.
+    UnknownBuffer(ResourceId),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Parse { line } => write!(f, "malformed trace in line {}", line),
+            Self::HashMismatch { line } => write!(f, "corrupted data in line {}", line),
+            Self::UnknownBuffer(id) => write!(f, "unknown buffer {}", id),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+/// A recorded sequence of operations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    /// Writes the trace as a line based text log.
+    ///
+    /// Strings and data are hex encoded, data is preceded by its [`hash`].
+    pub fn save(&self, mut writer: impl Write) -> io::Result<()> {
+        for event in &self.events {
+            match event {
+                TraceEvent::CreateBuffer {
+                    id,
+                    label,
+                    size,
+                    usage,
+                    contents,
+                } => writeln!(
+                    writer,
+                    "create_buffer {} {} {} {} {:016x} {}",
+                    id,
+                    encode_label(label),
+                    size,
+                    usage.bits(),
+                    hash(contents),
+                    encode_hex(contents),
+                ),
+                TraceEvent::WriteBuffer {
+                    buffer,
+                    offset,
+                    data,
+                } => writeln!(
+                    writer,
+                    "write_buffer {} {} {:016x} {}",
+                    buffer.map_or("-".to_owned(), |id| id.to_string()),
+                    offset,
+                    hash(data),
+                    encode_hex(data),
+                ),
+                TraceEvent::CreateShaderModule { id, label, source } => writeln!(
+                    writer,
+                    "create_shader_module {} {} {}",
+                    id,
+                    encode_label(label),
+                    encode_hex(source.as_bytes()),
+                ),
+                TraceEvent::CreatePipeline { label } => {
+                    writeln!(writer, "create_pipeline {}", encode_label(label))
+                }
+                TraceEvent::Dispatch { label, workgroups } => writeln!(
+                    writer,
+                    "dispatch {} {} {} {}",
+                    encode_label(label),
+                    workgroups[0],
+                    workgroups[1],
+                    workgroups[2],
+                ),
+            }?;
+        }
+        Ok(())
+    }
+
+    /// Reads a trace written by [`Self::save`], verifying all data hashes.
+    pub fn load(reader: impl BufRead) -> Result<Self, TraceError> {
+        let mut events = Vec::new();
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.map_err(TraceError::Io)?;
+            if line.is_empty() {
+                continue;
+            }
+            events.push(parse_event(&line, i + 1)?);
+        }
+        Ok(Self { events })
+    }
+
+    /// Recreates the recorded buffers and shader modules on `device` and repeats all uploads.
+    ///
+    /// Writes to buffers the trace doesn't track, pipelines and dispatches are skipped.
+    pub fn replay(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> Result<Replay, TraceError> {
+        use crate::DeviceExt;
+
+        let mut replay = Replay::default();
+        for event in &self.events {
+            match event {
+                TraceEvent::CreateBuffer {
+                    id,
+                    label,
+                    size,
+                    usage,
+                    contents,
+                } => {
+                    let buffer = device.create_buffer_init(&crate::BufferInitDescriptor {
+                        label: label.as_deref(),
+                        contents,
+                        size: Some(*size),
+                        usage: *usage,
+                    });
+                    replay.buffers.insert(*id, buffer);
+                }
+                TraceEvent::WriteBuffer {
+                    buffer: Some(id),
+                    offset,
+                    data,
+                } => {
+                    let buffer = replay
+                        .buffers
+                        .get(id)
+                        .ok_or(TraceError::UnknownBuffer(*id))?;
+                    queue.write_buffer(buffer, *offset, data);
+                }
+                TraceEvent::CreateShaderModule { id, label, source } => {
+                    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                        label: label.as_deref(),
+                        source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+                    });
+                    replay.shader_modules.insert(*id, module);
+                }
+                TraceEvent::WriteBuffer { buffer: None, .. }
+                | TraceEvent::CreatePipeline { .. }
+                | TraceEvent::Dispatch { .. } => {}
+            }
+        }
+        Ok(replay)
+    }
+}
+
+/// Resources recreated by [`Trace::replay`].
+#[derive(Debug, Default)]
+pub struct Replay {
+    pub buffers: HashMap<ResourceId, wgpu::Buffer>,
+    pub shader_modules: HashMap<ResourceId, wgpu::ShaderModule>,
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    if data.is_empty() {
+        return "-".to_owned();
+    }
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex == "-" {
+        return Some(Vec::new());
+    }
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn encode_label(label: &Option<String>) -> String {
+    match label {
+        // Distinguish an empty label from no label.
+        Some(label) => format!("l{}", encode_hex(label.as_bytes())),
+        None => "-".to_owned(),
+    }
+}
+
+fn decode_label(token: &str, line: usize) -> Result<Option<String>, TraceError> {
+    if token == "-" {
+        return Ok(None);
+    }
+    token
+        .strip_prefix('l')
+        .and_then(decode_hex)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .map(Some)
+        .ok_or(TraceError::Parse { line })
+}
+
+fn decode_data(hash_token: &str, data_token: &str, line: usize) -> Result<Vec<u8>, TraceError> {
+    let expected: u64 =
+        u64::from_str_radix(hash_token, 16).map_err(|_| TraceError::Parse { line })?;
+    let data = decode_hex(data_token).ok_or(TraceError::Parse { line })?;
+    match hash(&data) == expected {
+        true => Ok(data),
+        false => Err(TraceError::HashMismatch { line }),
+    }
+}
+
+fn parse<T: std::str::FromStr>(token: &str, line: usize) -> Result<T, TraceError> {
+    token.parse().map_err(|_| TraceError::Parse { line })
+}
+
+fn parse_event(text: &str, line: usize) -> Result<TraceEvent, TraceError> {
+    let tokens: Vec<&str> = text.split(' ').collect();
+    let event = match tokens.as_slice() {
+        ["create_buffer", id, label, size, usage, hash, contents] => TraceEvent::CreateBuffer {
+            id: parse(id, line)?,
+            label: decode_label(label, line)?,
+            size: parse(size, line)?,
+            usage: wgpu::BufferUsages::from_bits(parse(usage, line)?)
+                .ok_or(TraceError::Parse { line })?,
+            contents: decode_data(hash, contents, line)?,
+        },
+        ["write_buffer", buffer, offset, hash, data] => TraceEvent::WriteBuffer {
+            buffer: match *buffer {
+                "-" => None,
+                id => Some(parse(id, line)?),
+            },
+            offset: parse(offset, line)?,
+            data: decode_data(hash, data, line)?,
+        },
+        ["create_shader_module", id, label, source] => TraceEvent::CreateShaderModule {
+            id: parse(id, line)?,
+            label: decode_label(label, line)?,
+            source: decode_hex(source)
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .ok_or(TraceError::Parse { line })?,
+        },
+        ["create_pipeline", label] => TraceEvent::CreatePipeline {
+            label: decode_label(label, line)?,
+        },
+        ["dispatch", label, x, y, z] => TraceEvent::Dispatch {
+            label: decode_label(label, line)?,
+            workgroups: [parse(x, line)?, parse(y, line)?, parse(z, line)?],
+        },
+        _ => return Err(TraceError::Parse { line }),
+    };
+    Ok(event)
+}
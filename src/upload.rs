@@ -0,0 +1,755 @@
+//! Spreading large uploads over multiple frames and feeding uploads from loader threads.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::{mpsc, Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{readback::TextureInfo, BufferPool, BufferPoolDescriptor};
+
+/// Progress of an upload, passed to the callback of [`AmortizedUploader::upload_with_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct UploadProgress {
+    /// Bytes recorded for copying so far.
+    pub uploaded: wgpu::BufferAddress,
+    pub total: wgpu::BufferAddress,
+}
+
+impl UploadProgress {
+    /// Uploaded fraction between `0.0` and `1.0`.
+    pub fn fraction(&self) -> f32 {
+        match self.total {
+            0 => 1.0,
+            total => (self.uploaded as f64 / total as f64) as f32,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct CompletionState {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+/// Future resolving once the GPU finished all copies of an upload.
+///
+/// Like other wgpu callbacks, it only makes progress while the device is polled.
+#[derive(Debug)]
+pub struct UploadFuture {
+    state: Arc<Mutex<CompletionState>>,
+}
+
+impl UploadFuture {
+    /// Whether the upload is complete, without polling.
+    pub fn is_complete(&self) -> bool {
+        self.state.lock().unwrap().done
+    }
+}
+
+impl Future for UploadFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.done {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+fn complete(state: &Mutex<CompletionState>) {
+    let mut state = state.lock().unwrap();
+    state.done = true;
+    if let Some(waker) = state.waker.take() {
+        waker.wake();
+    }
+}
+
+type ProgressFn = Box<dyn FnMut(UploadProgress) + Send>;
+
+/// Priority of an upload. Higher priorities are processed first, lower ones are deferred to
+/// later frames when the budget runs out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UploadPriority {
+    /// E.g. UI resources which must be visible next frame.
+    High,
+    /// E.g. textures of visible objects.
+    #[default]
+    Normal,
+    /// E.g. prefetching resources which might be needed soon.
+    Low,
+}
+
+impl UploadPriority {
+    /// All priorities, from highest to lowest.
+    pub const ALL: [Self; 3] = [Self::High, Self::Normal, Self::Low];
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Options for [`AmortizedUploader::upload_with`].
+#[derive(Default)]
+pub struct UploadOptions {
+    pub priority: UploadPriority,
+    /// Called after each frame the upload advanced.
+    pub progress: Option<Box<dyn FnMut(UploadProgress) + Send>>,
+}
+
+impl fmt::Debug for UploadOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UploadOptions")
+            .field("priority", &self.priority)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// Queued uploads of a priority, see [`UploadMetrics`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PriorityMetrics {
+    /// Number of uploads which weren't completely recorded yet.
+    pub pending_uploads: usize,
+    /// Bytes which weren't recorded yet.
+    pub pending_bytes: wgpu::BufferAddress,
+    /// Bytes recorded by the last [`AmortizedUploader::process`].
+    pub last_frame_bytes: wgpu::BufferAddress,
+}
+
+/// Queue depth of an [`AmortizedUploader`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct UploadMetrics {
+    /// Metrics per priority, indexed in the order of [`UploadPriority::ALL`].
+    pub priorities: [PriorityMetrics; 3],
+}
+
+impl UploadMetrics {
+    pub fn priority(&self, priority: UploadPriority) -> &PriorityMetrics {
+        &self.priorities[priority.index()]
+    }
+
+    pub fn pending_uploads(&self) -> usize {
+        self.priorities.iter().map(|p| p.pending_uploads).sum()
+    }
+
+    pub fn pending_bytes(&self) -> wgpu::BufferAddress {
+        self.priorities.iter().map(|p| p.pending_bytes).sum()
+    }
+
+    pub fn last_frame_bytes(&self) -> wgpu::BufferAddress {
+        self.priorities.iter().map(|p| p.last_frame_bytes).sum()
+    }
+}
+
+struct Job {
+    destination: Arc<wgpu::Buffer>,
+    offset: wgpu::BufferAddress,
+    data: Vec<u8>,
+    uploaded: usize,
+    progress: Option<ProgressFn>,
+    completion: Arc<Mutex<CompletionState>>,
+}
+
+impl fmt::Debug for Job {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Job")
+            .field("offset", &self.offset)
+            .field("len", &self.data.len())
+            .field("uploaded", &self.uploaded)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Descriptor for [`AmortizedUploader`].
+#[derive(Clone, Debug)]
+pub struct AmortizedUploaderDescriptor<'a> {
+    /// Label of the staging buffers.
+    pub label: wgpu::Label<'a>,
+    /// Size of a single copy. Must be a multiple of [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub chunk_size: wgpu::BufferAddress,
+    /// Bytes copied per call to [`AmortizedUploader::process`]. Must be a multiple of
+    /// [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub bytes_per_frame: wgpu::BufferAddress,
+}
+
+impl Default for AmortizedUploaderDescriptor<'_> {
+    fn default() -> Self {
+        Self {
+            label: Some("amortized upload staging buffer"),
+            chunk_size: 4 << 20,
+            bytes_per_frame: 16 << 20,
+        }
+    }
+}
+
+/// Streams large uploads into buffers in bounded chunks per frame to avoid hitches.
+///
+/// Each frame, [`Self::process`] stages up to [`AmortizedUploaderDescriptor::bytes_per_frame`]
+/// of the queued uploads in a [`BufferPool`] and records copies into their destinations. After
+/// submitting the encoder, [`Self::after_submit`] resolves the futures of finished uploads once
+/// the GPU executed their copies.
+///
+/// Uploads are processed by [`UploadPriority`], each priority may additionally be limited to a
+/// budget of its own with [`Self::set_priority_budget`].
+#[derive(Debug)]
+pub struct AmortizedUploader {
+    staging: BufferPool,
+    chunk_size: wgpu::BufferAddress,
+    bytes_per_frame: wgpu::BufferAddress,
+    priority_budgets: [Option<wgpu::BufferAddress>; 3],
+
+    /// Queued uploads per priority.
+    jobs: [VecDeque<Job>; 3],
+    last_frame_bytes: [wgpu::BufferAddress; 3],
+    /// Uploads whose last copy was recorded, but not yet submitted.
+    recorded: Vec<Arc<Mutex<CompletionState>>>,
+}
+
+impl AmortizedUploader {
+    pub fn new(descriptor: &AmortizedUploaderDescriptor) -> Self {
+        assert!(
+            descriptor.chunk_size > 0
+                && descriptor
+                    .chunk_size
+                    .is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            "chunk size must be a non-zero multiple of COPY_BUFFER_ALIGNMENT"
+        );
+        assert!(
+            descriptor.bytes_per_frame > 0
+                && descriptor
+                    .bytes_per_frame
+                    .is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            "bytes per frame must be a non-zero multiple of COPY_BUFFER_ALIGNMENT"
+        );
+
+        Self {
+            staging: BufferPool::new(&BufferPoolDescriptor {
+                label: descriptor.label,
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            }),
+            chunk_size: descriptor.chunk_size,
+            bytes_per_frame: descriptor.bytes_per_frame,
+            priority_budgets: [None; 3],
+
+            jobs: Default::default(),
+            last_frame_bytes: [0; 3],
+            recorded: Vec::new(),
+        }
+    }
+
+    /// Sets the bytes copied per call to [`Self::process`], a multiple of
+    /// [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub fn set_bytes_per_frame(&mut self, bytes_per_frame: wgpu::BufferAddress) {
+        assert!(
+            bytes_per_frame > 0 && bytes_per_frame.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            "bytes per frame must be a non-zero multiple of COPY_BUFFER_ALIGNMENT"
+        );
+        self.bytes_per_frame = bytes_per_frame;
+    }
+
+    /// Limits the bytes of `priority` copied per frame within the overall budget, e.g. to keep
+    /// prefetching from delaying uploads queued in the next frames. `None` removes the limit.
+    pub fn set_priority_budget(
+        &mut self,
+        priority: UploadPriority,
+        budget: Option<wgpu::BufferAddress>,
+    ) {
+        assert!(
+            budget.is_none_or(|b| b.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)),
+            "budget must be a multiple of COPY_BUFFER_ALIGNMENT"
+        );
+        self.priority_budgets[priority.index()] = budget;
+    }
+
+    /// Queues an upload of `data` at `offset` of `destination`, which must have
+    /// [`wgpu::BufferUsages::COPY_DST`]. `offset` and the length of `data` must be multiples of
+    /// [`wgpu::COPY_BUFFER_ALIGNMENT`].
+    pub fn upload(
+        &mut self,
+        destination: Arc<wgpu::Buffer>,
+        offset: wgpu::BufferAddress,
+        data: Vec<u8>,
+    ) -> UploadFuture {
+        self.upload_with(destination, offset, data, UploadOptions::default())
+    }
+
+    /// [`Self::upload`] calling `progress` after each frame the upload advanced.
+    pub fn upload_with_progress(
+        &mut self,
+        destination: Arc<wgpu::Buffer>,
+        offset: wgpu::BufferAddress,
+        data: Vec<u8>,
+        progress: impl FnMut(UploadProgress) + Send + 'static,
+    ) -> UploadFuture {
+        let options = UploadOptions {
+            progress: Some(Box::new(progress)),
+            ..Default::default()
+        };
+        self.upload_with(destination, offset, data, options)
+    }
+
+    /// [`Self::upload`] with a priority and progress callback.
+    pub fn upload_with(
+        &mut self,
+        destination: Arc<wgpu::Buffer>,
+        offset: wgpu::BufferAddress,
+        data: Vec<u8>,
+        options: UploadOptions,
+    ) -> UploadFuture {
+        assert!(
+            offset.is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT)
+                && (data.len() as wgpu::BufferAddress).is_multiple_of(wgpu::COPY_BUFFER_ALIGNMENT),
+            "offset and length must be multiples of COPY_BUFFER_ALIGNMENT"
+        );
+
+        let completion = Arc::new(Mutex::new(CompletionState::default()));
+        self.jobs[options.priority.index()].push_back(Job {
+            destination,
+            offset,
+            data,
+            uploaded: 0,
+            progress: options.progress,
+            completion: Arc::clone(&completion),
+        });
+        UploadFuture { state: completion }
+    }
+
+    /// Stages the next chunks of the queued uploads and records their copies into `encoder`.
+    /// Uploads are processed by priority, then in the order they were queued.
+    pub fn process(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) {
+        // Staging buffers of the previous frame are reusable, their writes are ordered before
+        // the writes of this frame.
+        self.staging.clear();
+
+        let mut frame_budget = self.bytes_per_frame as usize;
+        for priority in UploadPriority::ALL {
+            let limit = self.priority_budgets[priority.index()].map_or(usize::MAX, |b| b as usize);
+            let mut budget = frame_budget.min(limit);
+            let before = budget;
+            self.process_priority(device, queue, encoder, priority, &mut budget);
+            frame_budget -= before - budget;
+            self.last_frame_bytes[priority.index()] = (before - budget) as wgpu::BufferAddress;
+        }
+
+        #[cfg(feature = "metrics")]
+        {
+            let metrics = self.metrics();
+            for priority in UploadPriority::ALL {
+                let priority_metrics = metrics.priority(priority);
+                crate::metrics::record_upload(
+                    priority,
+                    priority_metrics.last_frame_bytes,
+                    priority_metrics.pending_bytes,
+                );
+            }
+            crate::metrics::record_upload_utilization(
+                metrics.last_frame_bytes(),
+                self.bytes_per_frame,
+            );
+        }
+    }
+
+    fn process_priority(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        priority: UploadPriority,
+        budget: &mut usize,
+    ) {
+        let jobs = &mut self.jobs[priority.index()];
+        while *budget > 0 {
+            let Some(job) = jobs.front_mut() else {
+                break;
+            };
+
+            let start = job.uploaded;
+            while *budget > 0 && job.uploaded < job.data.len() {
+                let len = (self.chunk_size as usize)
+                    .min(*budget)
+                    .min(job.data.len() - job.uploaded);
+                let chunk = &job.data[job.uploaded..job.uploaded + len];
+                let staging = self.staging.upload(device, queue, chunk);
+                encoder.copy_buffer_to_buffer(
+                    self.staging.get(staging).expect("buffer was just uploaded"),
+                    0,
+                    &job.destination,
+                    job.offset + job.uploaded as wgpu::BufferAddress,
+                    len as wgpu::BufferAddress,
+                );
+                job.uploaded += len;
+                *budget -= len;
+            }
+
+            if job.uploaded > start || job.data.is_empty() {
+                if let Some(progress) = &mut job.progress {
+                    progress(UploadProgress {
+                        uploaded: job.uploaded as wgpu::BufferAddress,
+                        total: job.data.len() as wgpu::BufferAddress,
+                    });
+                }
+            }
+            if job.uploaded == job.data.len() {
+                let job = jobs.pop_front().expect("job is queued");
+                self.recorded.push(job.completion);
+            }
+        }
+    }
+
+    /// Must be called after the encoder passed to [`Self::process`] got submitted. Resolves the
+    /// futures of uploads finished in it once the GPU is done.
+    pub fn after_submit(&mut self, queue: &wgpu::Queue) {
+        if self.recorded.is_empty() {
+            return;
+        }
+        let recorded = std::mem::take(&mut self.recorded);
+        queue.on_submitted_work_done(move || {
+            for completion in &recorded {
+                complete(completion);
+            }
+        });
+    }
+
+    /// Bytes of queued uploads which weren't recorded yet.
+    pub fn pending_bytes(&self) -> wgpu::BufferAddress {
+        self.metrics().pending_bytes()
+    }
+
+    /// Queue depth per priority.
+    pub fn metrics(&self) -> UploadMetrics {
+        let mut metrics = UploadMetrics::default();
+        for (i, jobs) in self.jobs.iter().enumerate() {
+            metrics.priorities[i] = PriorityMetrics {
+                pending_uploads: jobs.len(),
+                pending_bytes: jobs
+                    .iter()
+                    .map(|job| (job.data.len() - job.uploaded) as wgpu::BufferAddress)
+                    .sum(),
+                last_frame_bytes: self.last_frame_bytes[i],
+            };
+        }
+        metrics
+    }
+
+    /// Whether all uploads were recorded.
+    pub fn is_idle(&self) -> bool {
+        self.jobs.iter().all(VecDeque::is_empty)
+    }
+}
+
+/// A resource created by [`AsyncUploadQueue::drain_into`].
+#[derive(Debug)]
+pub enum Asset {
+    Buffer(wgpu::Buffer),
+    Texture(wgpu::Texture),
+}
+
+impl Asset {
+    pub fn into_buffer(self) -> Option<wgpu::Buffer> {
+        match self {
+            Self::Buffer(buffer) => Some(buffer),
+            Self::Texture(_) => None,
+        }
+    }
+
+    pub fn into_texture(self) -> Option<wgpu::Texture> {
+        match self {
+            Self::Texture(texture) => Some(texture),
+            Self::Buffer(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct AssetState {
+    asset: Option<Asset>,
+    waker: Option<Waker>,
+}
+
+/// Future resolving to an [`Asset`] once the GPU finished copying its contents.
+///
+/// Like other wgpu callbacks, it only makes progress while the device is polled.
+#[derive(Debug)]
+pub struct AssetFuture {
+    state: Arc<Mutex<AssetState>>,
+}
+
+impl AssetFuture {
+    /// Takes the asset if it's ready, without polling.
+    pub fn try_take(&mut self) -> Option<Asset> {
+        self.state.lock().unwrap().asset.take()
+    }
+}
+
+impl Future for AssetFuture {
+    type Output = Asset;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Asset> {
+        let mut state = self.state.lock().unwrap();
+        match state.asset.take() {
+            Some(asset) => Poll::Ready(asset),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+enum AssetData {
+    Buffer {
+        label: Option<String>,
+        usage: wgpu::BufferUsages,
+        size: wgpu::BufferAddress,
+        /// Padded to [`wgpu::COPY_BUFFER_ALIGNMENT`].
+        contents: Vec<u8>,
+    },
+    Texture {
+        label: Option<String>,
+        info: TextureInfo,
+        usage: wgpu::TextureUsages,
+        /// Rows padded to [`wgpu::COPY_BYTES_PER_ROW_ALIGNMENT`].
+        texels: Vec<u8>,
+    },
+}
+
+type AssetMessage = (AssetData, Arc<Mutex<AssetState>>);
+
+/// Sending half of an [`AsyncUploadQueue`], for loader threads.
+///
+/// Contents are padded for copying on the sending thread.
+#[derive(Clone, Debug)]
+pub struct AsyncUploadSender {
+    sender: mpsc::Sender<AssetMessage>,
+}
+
+impl AsyncUploadSender {
+    /// Queues a buffer with `contents`. [`wgpu::BufferUsages::COPY_DST`] is added to `usage`.
+    pub fn push_buffer(
+        &self,
+        label: Option<&str>,
+        usage: wgpu::BufferUsages,
+        mut contents: Vec<u8>,
+    ) -> AssetFuture {
+        let size = contents.len() as wgpu::BufferAddress;
+        let padded = size.div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT;
+        contents.resize(padded as usize, 0);
+
+        self.send(AssetData::Buffer {
+            label: label.map(str::to_owned),
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            size,
+            contents,
+        })
+    }
+
+    /// Queues a 2D texture with a single mip level, `texels` being tightly packed rows from top
+    /// to bottom. [`wgpu::TextureUsages::COPY_DST`] is added to `usage`.
+    pub fn push_texture(
+        &self,
+        label: Option<&str>,
+        info: TextureInfo,
+        usage: wgpu::TextureUsages,
+        texels: &[u8],
+    ) -> AssetFuture {
+        let unpadded = info.unpadded_bytes_per_row() as usize;
+        let padded = info.padded_bytes_per_row() as usize;
+        assert_eq!(
+            texels.len(),
+            unpadded * info.height as usize,
+            "texels must be tightly packed rows of the texture"
+        );
+
+        let mut padded_texels = vec![0; padded * info.height as usize];
+        if unpadded > 0 {
+            for (row, padded_row) in texels
+                .chunks_exact(unpadded)
+                .zip(padded_texels.chunks_exact_mut(padded))
+            {
+                padded_row[..unpadded].copy_from_slice(row);
+            }
+        }
+
+        self.send(AssetData::Texture {
+            label: label.map(str::to_owned),
+            info,
+            usage: usage | wgpu::TextureUsages::COPY_DST,
+            texels: padded_texels,
+        })
+    }
+
+    fn send(&self, data: AssetData) -> AssetFuture {
+        let state = Arc::new(Mutex::new(AssetState::default()));
+        // If the queue is gone, the future never resolves, like any upload of a dropped queue.
+        let _ = self.sender.send((data, Arc::clone(&state)));
+        AssetFuture { state }
+    }
+}
+
+/// Creates resources from contents produced on other threads and uploads them.
+///
+/// Loader threads push decoded contents through an [`AsyncUploadSender`]. Each frame, the main
+/// thread calls [`Self::drain_into`] to create the resources and record the staged copies and
+/// [`Self::after_submit`] after submitting, which resolves the [`AssetFuture`]s once the GPU is
+/// done.
+#[derive(Debug)]
+pub struct AsyncUploadQueue {
+    sender: mpsc::Sender<AssetMessage>,
+    receiver: mpsc::Receiver<AssetMessage>,
+    staging: BufferPool,
+
+    /// Assets whose copies were recorded, but not yet submitted.
+    recorded: Vec<(Asset, Arc<Mutex<AssetState>>)>,
+}
+
+impl Default for AsyncUploadQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AsyncUploadQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender,
+            receiver,
+            staging: BufferPool::new(&BufferPoolDescriptor {
+                label: Some("async upload staging buffer"),
+                usage: wgpu::BufferUsages::COPY_SRC | wgpu::BufferUsages::COPY_DST,
+            }),
+
+            recorded: Vec::new(),
+        }
+    }
+
+    /// A sender for pushing contents from other threads.
+    pub fn sender(&self) -> AsyncUploadSender {
+        AsyncUploadSender {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Creates the resources of all contents pushed so far and records their copies into
+    /// `encoder`. Returns the number of drained assets.
+    pub fn drain_into(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+    ) -> usize {
+        self.staging.clear();
+
+        let mut count = 0;
+        while let Ok((data, state)) = self.receiver.try_recv() {
+            let asset = match data {
+                AssetData::Buffer {
+                    label,
+                    usage,
+                    size,
+                    contents,
+                } => {
+                    let buffer = crate::resource_log::create_buffer(
+                        device,
+                        &wgpu::BufferDescriptor {
+                            label: label.as_deref(),
+                            size: contents.len() as wgpu::BufferAddress,
+                            usage,
+                            mapped_at_creation: false,
+                        },
+                    );
+                    if size > 0 {
+                        let staging = self.staging.upload(device, queue, &contents);
+                        encoder.copy_buffer_to_buffer(
+                            self.staging.get(staging).expect("buffer was just uploaded"),
+                            0,
+                            &buffer,
+                            0,
+                            contents.len() as wgpu::BufferAddress,
+                        );
+                    }
+                    Asset::Buffer(buffer)
+                }
+                AssetData::Texture {
+                    label,
+                    info,
+                    usage,
+                    texels,
+                } => {
+                    let size = wgpu::Extent3d {
+                        width: info.width,
+                        height: info.height,
+                        depth_or_array_layers: 1,
+                    };
+                    let texture = crate::resource_log::create_texture(
+                        device,
+                        &wgpu::TextureDescriptor {
+                            label: label.as_deref(),
+                            size,
+                            mip_level_count: 1,
+                            sample_count: 1,
+                            dimension: wgpu::TextureDimension::D2,
+                            format: info.format,
+                            usage,
+                        },
+                    );
+                    if !texels.is_empty() {
+                        let staging = self.staging.upload(device, queue, &texels);
+                        encoder.copy_buffer_to_texture(
+                            wgpu::ImageCopyBuffer {
+                                buffer: self
+                                    .staging
+                                    .get(staging)
+                                    .expect("buffer was just uploaded"),
+                                layout: wgpu::ImageDataLayout {
+                                    offset: 0,
+                                    bytes_per_row: NonZeroU32::new(info.padded_bytes_per_row()),
+                                    rows_per_image: None,
+                                },
+                            },
+                            texture.as_image_copy(),
+                            size,
+                        );
+                    }
+                    Asset::Texture(texture)
+                }
+            };
+            self.recorded.push((asset, state));
+            count += 1;
+        }
+        count
+    }
+
+    /// Must be called after the encoder passed to [`Self::drain_into`] got submitted. Resolves
+    /// the futures of the drained assets once the GPU is done.
+    pub fn after_submit(&mut self, queue: &wgpu::Queue) {
+        if self.recorded.is_empty() {
+            return;
+        }
+        let recorded = std::mem::take(&mut self.recorded);
+        queue.on_submitted_work_done(move || {
+            for (asset, state) in recorded {
+                let mut state = state.lock().unwrap();
+                state.asset = Some(asset);
+                if let Some(waker) = state.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+    }
+}
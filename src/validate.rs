@@ -0,0 +1,365 @@
+//! Validating data on the GPU.
+
+use std::{
+    future::Future,
+    num::NonZeroU64,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use crate::{
+    readback::{ReadFuture, TextureInfo},
+    BufferInitDescriptor, DeviceExt,
+};
+
+const REPORT_SIZE: wgpu::BufferAddress = 16;
+const BUFFER_WORKGROUP_SIZE: u32 = 64;
+const TEXTURE_WORKGROUP_SIZE: u32 = 8;
+
+/// Data scanned by [`NonFiniteDetector::detect_non_finite`].
+#[derive(Clone, Copy, Debug)]
+pub enum NonFiniteInput<'a> {
+    /// The first mip level of a 2D texture with a float format and
+    /// [`wgpu::TextureUsages::TEXTURE_BINDING`]. All four components of every texel are checked.
+    Texture {
+        texture: &'a wgpu::Texture,
+        info: &'a TextureInfo,
+    },
+    /// `len` `f32`s starting at `offset` of a buffer with [`wgpu::BufferUsages::STORAGE`].
+    /// `offset` must be a multiple of [`wgpu::Limits::min_storage_buffer_offset_alignment`].
+    Buffer {
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        len: u32,
+    },
+}
+
+/// Position of a value found by [`NonFiniteDetector`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum NonFiniteLocation {
+    Texel {
+        x: u32,
+        y: u32,
+    },
+    /// Index of the `f32` relative to the scanned range.
+    Element(u32),
+}
+
+/// Result of [`NonFiniteDetector::detect_non_finite`].
+///
+/// "First" refers to the smallest element index or texel in row-major order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct NonFiniteReport {
+    pub nan_count: u32,
+    pub inf_count: u32,
+    pub first_nan: Option<NonFiniteLocation>,
+    pub first_inf: Option<NonFiniteLocation>,
+}
+
+impl NonFiniteReport {
+    /// Whether no NaN or infinity was found.
+    pub fn is_finite(&self) -> bool {
+        self.nan_count == 0 && self.inf_count == 0
+    }
+}
+
+/// Compute passes scanning float textures and buffers for NaN and infinity.
+#[derive(Debug)]
+pub struct NonFiniteDetector {
+    texture_pipeline: wgpu::ComputePipeline,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    buffer_pipeline: wgpu::ComputePipeline,
+    buffer_bind_group_layout: wgpu::BindGroupLayout,
+}
+
+impl NonFiniteDetector {
+    pub fn new(device: &wgpu::Device) -> Self {
+        let source = include_str!("shaders/non_finite.wgsl");
+
+        let shader =
+            crate::create_shader_module(device, Some("non-finite detector shader"), source);
+
+        let report_entry = wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(REPORT_SIZE),
+            },
+            count: None,
+        };
+        let params_entry = wgpu::BindGroupLayoutEntry {
+            binding: 1,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(16),
+            },
+            count: None,
+        };
+
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("non-finite detector texture bind group layout"),
+                entries: &[
+                    report_entry,
+                    params_entry,
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let buffer_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("non-finite detector buffer bind group layout"),
+                entries: &[
+                    report_entry,
+                    params_entry,
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Storage { read_only: true },
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(4),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+
+        let create_pipeline = |label, layout, entry_point| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label,
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            crate::resource_log::create_compute_pipeline(
+                device,
+                &wgpu::ComputePipelineDescriptor {
+                    label,
+                    layout: Some(&pipeline_layout),
+                    module: &shader,
+                    entry_point,
+                },
+            )
+        };
+        let texture_pipeline = create_pipeline(
+            Some("non-finite detector texture pipeline"),
+            &texture_bind_group_layout,
+            "scan_texture",
+        );
+        let buffer_pipeline = create_pipeline(
+            Some("non-finite detector buffer pipeline"),
+            &buffer_bind_group_layout,
+            "scan_buffer",
+        );
+
+        Self {
+            texture_pipeline,
+            texture_bind_group_layout,
+            buffer_pipeline,
+            buffer_bind_group_layout,
+        }
+    }
+
+    /// Submits a scan of `input` for NaN and infinity.
+    ///
+    /// The returned future only makes progress while the device is polled, see
+    /// [`crate::readback::read_buffer_async`].
+    pub fn detect_non_finite(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        input: NonFiniteInput<'_>,
+    ) -> NonFiniteFuture {
+        let (len, row_width) = match input {
+            NonFiniteInput::Texture { info, .. } => {
+                assert!(
+                    matches!(
+                        info.format.describe().sample_type,
+                        wgpu::TextureSampleType::Float { .. }
+                    ),
+                    "texture format must be a float format"
+                );
+                (info.width * info.height, Some(info.width))
+            }
+            NonFiniteInput::Buffer { len, .. } => (len, None),
+        };
+        if len == 0 {
+            return NonFiniteFuture {
+                read: None,
+                row_width,
+            };
+        }
+
+        let (dispatch, row_length) = match input {
+            NonFiniteInput::Texture { info, .. } => (
+                [
+                    info.width.div_ceil(TEXTURE_WORKGROUP_SIZE),
+                    info.height.div_ceil(TEXTURE_WORKGROUP_SIZE),
+                ],
+                0,
+            ),
+            NonFiniteInput::Buffer { len, .. } => {
+                let max = device.limits().max_compute_workgroups_per_dimension;
+                let groups = len.div_ceil(BUFFER_WORKGROUP_SIZE);
+                let x = groups.min(max);
+                ([x, groups.div_ceil(x)], x * BUFFER_WORKGROUP_SIZE)
+            }
+        };
+
+        let report = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("non-finite report buffer"),
+            contents: &words_to_bytes(&[0, 0, u32::MAX, u32::MAX]),
+            size: None,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        });
+        let params = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("non-finite params buffer"),
+            contents: &words_to_bytes(&[len, row_length, 0, 0]),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let staging = crate::resource_log::create_buffer(
+            device,
+            &wgpu::BufferDescriptor {
+                label: Some("non-finite report staging buffer"),
+                size: REPORT_SIZE,
+                usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            },
+        );
+
+        let texture_view;
+        let (pipeline, layout, input_entry) = match input {
+            NonFiniteInput::Texture { texture, .. } => {
+                texture_view = texture.create_view(&wgpu::TextureViewDescriptor {
+                    label: Some("non-finite input view"),
+                    base_mip_level: 0,
+                    mip_level_count: std::num::NonZeroU32::new(1),
+                    ..Default::default()
+                });
+                (
+                    &self.texture_pipeline,
+                    &self.texture_bind_group_layout,
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&texture_view),
+                    },
+                )
+            }
+            NonFiniteInput::Buffer {
+                buffer,
+                offset,
+                len,
+            } => (
+                &self.buffer_pipeline,
+                &self.buffer_bind_group_layout,
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset,
+                        size: NonZeroU64::new(len as wgpu::BufferAddress * 4),
+                    }),
+                },
+            ),
+        };
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("non-finite detector bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: report.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params.as_entire_binding(),
+                },
+                input_entry,
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("non-finite detector encoder"),
+        });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("non-finite detector pass"),
+            });
+            pass.set_pipeline(pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            crate::dispatch(
+                &mut pass,
+                "non-finite detector pass",
+                [dispatch[0], dispatch[1], 1],
+            );
+        }
+        encoder.copy_buffer_to_buffer(&report, 0, &staging, 0, REPORT_SIZE);
+        queue.submit(Some(encoder.finish()));
+
+        NonFiniteFuture {
+            read: Some(ReadFuture::new(staging, 0..REPORT_SIZE as usize)),
+            row_width,
+        }
+    }
+}
+
+/// Future of the report of [`NonFiniteDetector::detect_non_finite`].
+#[derive(Debug)]
+pub struct NonFiniteFuture {
+    read: Option<ReadFuture>,
+    /// Width of the scanned texture, `None` for buffers.
+    row_width: Option<u32>,
+}
+
+impl Future for NonFiniteFuture {
+    type Output = Result<NonFiniteReport, wgpu::BufferAsyncError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let read = match &mut self.read {
+            Some(read) => read,
+            None => return Poll::Ready(Ok(NonFiniteReport::default())),
+        };
+        let bytes = match Pin::new(read).poll(cx) {
+            Poll::Ready(bytes) => bytes?,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        let words: Vec<u32> = bytes
+            .chunks_exact(4)
+            .map(|w| u32::from_le_bytes(w.try_into().unwrap()))
+            .collect();
+        let row_width = self.row_width;
+        let location = |index: u32| {
+            (index != u32::MAX).then(|| match row_width {
+                Some(width) => NonFiniteLocation::Texel {
+                    x: index % width,
+                    y: index / width,
+                },
+                None => NonFiniteLocation::Element(index),
+            })
+        };
+        Poll::Ready(Ok(NonFiniteReport {
+            nan_count: words[0],
+            inf_count: words[1],
+            first_nan: location(words[2]),
+            first_inf: location(words[3]),
+        }))
+    }
+}
+
+fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|w| w.to_le_bytes()).collect()
+}
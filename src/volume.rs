@@ -0,0 +1,310 @@
+//! Raymarching 3D textures, e.g. for scientific visualization of scalar fields.
+
+use std::num::NonZeroU64;
+
+use crate::{
+    camera::{CameraBuffer, CAMERA_SNIPPET},
+    lut::GradientTexture,
+    pipeline::RenderPipelineBuilder,
+    shader::ShaderComposer,
+    texture::{FloatFiltering, TextureInitDescriptor},
+    BufferInitDescriptor, DeviceExt,
+};
+
+const PARAMS_SIZE: wgpu::BufferAddress = 80;
+
+/// Creates a 3D texture of `size` texels with `contents` in `format`, slice by slice, for
+/// [`VolumeRenderer`].
+///
+/// Only the first channel is raymarched, so single channel formats like
+/// [`wgpu::TextureFormat::R8Unorm`] or [`wgpu::TextureFormat::R16Float`] are the natural choice.
+pub fn create_volume_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    label: wgpu::Label<'_>,
+    size: [u32; 3],
+    format: wgpu::TextureFormat,
+    contents: &[u8],
+) -> wgpu::Texture {
+    crate::texture::create_texture_init(
+        device,
+        queue,
+        &TextureInitDescriptor {
+            texture: wgpu::TextureDescriptor {
+                label,
+                size: wgpu::Extent3d {
+                    width: size[0],
+                    height: size[1],
+                    depth_or_array_layers: size[2],
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D3,
+                format,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+            },
+            contents,
+            source: None,
+        },
+    )
+}
+
+/// Placement and appearance of the volume of a [`VolumeRenderer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VolumeParams {
+    /// Corner of the volume at texture coordinate `[0, 0, 0]` in world space.
+    pub bounds_min: [f32; 3],
+    /// Corner of the volume at texture coordinate `[1, 1, 1]` in world space.
+    pub bounds_max: [f32; 3],
+    /// Part of the volume that is drawn, in texture coordinates, e.g. to cut it open.
+    pub crop_min: [f32; 3],
+    pub crop_max: [f32; 3],
+    /// Samples along the diagonal of the volume. Shorter rays take proportionally fewer.
+    pub step_count: u32,
+    /// Optical depth along the diagonal of the volume where the transfer function is opaque.
+    pub density: f32,
+    /// Volume values mapped to the start and the end of the transfer function. Values outside
+    /// are clamped.
+    pub value_range: [f32; 2],
+}
+
+impl Default for VolumeParams {
+    fn default() -> Self {
+        Self {
+            bounds_min: [-0.5; 3],
+            bounds_max: [0.5; 3],
+            crop_min: [0.0; 3],
+            crop_max: [1.0; 3],
+            step_count: 128,
+            density: 4.0,
+            value_range: [0.0, 1.0],
+        }
+    }
+}
+
+impl VolumeParams {
+    fn to_bytes(self, near_depth: f32) -> Vec<u8> {
+        let corner = |crop: [f32; 3]| {
+            let mut corner = [0.0; 3];
+            for (i, corner) in corner.iter_mut().enumerate() {
+                *corner = self.bounds_min[i] + (self.bounds_max[i] - self.bounds_min[i]) * crop[i];
+            }
+            corner
+        };
+        let mut bytes = Vec::with_capacity(PARAMS_SIZE as usize);
+        for (vector, w) in [
+            (corner(self.crop_min), self.step_count.max(1).to_le_bytes()),
+            (corner(self.crop_max), self.density.to_le_bytes()),
+            (self.bounds_min, self.value_range[0].to_le_bytes()),
+            (self.bounds_max, self.value_range[1].to_le_bytes()),
+        ] {
+            for value in vector {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&w);
+        }
+        bytes.extend_from_slice(&near_depth.to_le_bytes());
+        bytes.resize(PARAMS_SIZE as usize, 0);
+        bytes
+    }
+}
+
+/// Descriptor for [`VolumeRenderer::new`].
+#[derive(Clone, Debug)]
+pub struct VolumeDescriptor<'a> {
+    /// 3D view of the volume, e.g. of [`create_volume_texture`].
+    pub volume: &'a wgpu::TextureView,
+    /// Filtering support of the volume format, non-filterable volumes are sampled nearest.
+    pub filtering: FloatFiltering,
+    /// Transfer function mapping volume values to color and opacity.
+    pub transfer: &'a GradientTexture,
+    /// Format of the color target, the volume is blended onto it with premultiplied alpha.
+    pub format: wgpu::TextureFormat,
+    /// Format of the depth attachment of the pass, if any.
+    pub depth_format: Option<wgpu::TextureFormat>,
+    /// Whether the camera uses reversed depth, with the near plane at 1.
+    pub reversed_depth: bool,
+    pub sample_count: u32,
+    pub params: VolumeParams,
+}
+
+/// Draws a 3D texture seen by the camera of a [`CameraBuffer`] in a single pass.
+///
+/// Every pixel marches its view ray through the box of the volume, front to back, maps the first
+/// channel through the transfer function and composites until it is opaque. The depth of the
+/// entry point is tested against the depth attachment without writing it, so opaque geometry in
+/// front hides the volume, but geometry inside doesn't cut it off.
+#[derive(Debug)]
+pub struct VolumeRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    params_buffer: wgpu::Buffer,
+    params: VolumeParams,
+    near_depth: f32,
+}
+
+impl VolumeRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        camera: &CameraBuffer,
+        descriptor: &VolumeDescriptor<'_>,
+    ) -> Self {
+        let dimension = descriptor.transfer.view_dimension();
+        let (transfer_texture, transfer_load, transfer_size) = match dimension {
+            wgpu::TextureViewDimension::D1 => (
+                "texture_1d<f32>",
+                "textureLoad(transfer_texture, i, 0)",
+                "textureDimensions(transfer_texture)",
+            ),
+            _ => (
+                "texture_2d<f32>",
+                "textureLoad(transfer_texture, vec2<i32>(i, 0), 0)",
+                "textureDimensions(transfer_texture).x",
+            ),
+        };
+        let source = include_str!("shaders/volume.wgsl")
+            .replace("{transfer_texture}", transfer_texture)
+            .replace("{transfer_load}", transfer_load)
+            .replace("{transfer_size}", transfer_size);
+        let mut composer = ShaderComposer::new();
+        composer.add_snippet(CAMERA_SNIPPET, crate::camera::camera_snippet(0, 0));
+        let shader = composer
+            .create_shader_module(device, Some("volume shader"), &source)
+            .expect("builtin snippets must compose");
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("volume bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    ..camera.bind_group_layout_entry()
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(PARAMS_SIZE),
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: descriptor.filtering.sample_type(),
+                        view_dimension: wgpu::TextureViewDimension::D3,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(descriptor.filtering.sampler_binding_type()),
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: dimension,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let near_depth = match descriptor.reversed_depth {
+            true => 1.0,
+            false => 0.0,
+        };
+        let params_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("volume params buffer"),
+            contents: &descriptor.params.to_bytes(near_depth),
+            size: None,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let filter = descriptor.filtering.filter_mode();
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("volume sampler"),
+            mag_filter: filter,
+            min_filter: filter,
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("volume bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: camera.buffer().as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: params_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: wgpu::BindingResource::TextureView(descriptor.volume),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: wgpu::BindingResource::TextureView(descriptor.transfer.view()),
+                },
+            ],
+        });
+
+        let depth_stencil = descriptor
+            .depth_format
+            .map(|format| wgpu::DepthStencilState {
+                depth_compare: match descriptor.reversed_depth {
+                    true => wgpu::CompareFunction::GreaterEqual,
+                    false => wgpu::CompareFunction::LessEqual,
+                },
+                ..crate::depth::read_only_less_equal(format)
+            });
+        let pipeline =
+            RenderPipelineBuilder::new(&shader, &[&bind_group_layout], descriptor.format)
+                .label("volume pipeline")
+                .vertex_entry_point("vs_volume")
+                .fragment_entry_point(Some("fs_volume"))
+                .blend(crate::blend::PREMULTIPLIED_ALPHA)
+                .depth_stencil(depth_stencil)
+                .sample_count(descriptor.sample_count)
+                .build(device);
+
+        Self {
+            pipeline,
+            bind_group,
+            params_buffer,
+            params: descriptor.params,
+            near_depth,
+        }
+    }
+
+    /// Draws the volume into `pass`, which must match the targets of the descriptor. Draw it
+    /// after opaque geometry.
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    pub fn params(&self) -> &VolumeParams {
+        &self.params
+    }
+
+    /// Changes the placement and appearance and uploads them using [`wgpu::Queue`].
+    pub fn set_params(&mut self, queue: &wgpu::Queue, params: VolumeParams) {
+        self.params = params;
+        queue.write_buffer(&self.params_buffer, 0, &params.to_bytes(self.near_depth));
+    }
+}